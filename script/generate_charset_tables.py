@@ -0,0 +1,67 @@
+#!/usr/bin/env python3
+"""Generate the legacy charset decode tables used by src-tauri/src/parser/charset.rs.
+
+The tables come from Python's codecs, so regenerating does not need network access:
+
+    python3 script/generate_charset_tables.py > src-tauri/src/parser/charset/tables.rs
+"""
+
+SINGLE_BYTE = [
+    ('WINDOWS_1250', 'cp1250'),
+    ('WINDOWS_1251', 'cp1251'),
+    ('KOI8_R', 'koi8_r'),
+    ('ISO_8859_2', 'iso8859_2'),
+    ('ISO_8859_15', 'iso8859_15'),
+]
+
+# Shift_JIS lead bytes 0x81..=0x9F and 0xE0..=0xFC with 188 trail bytes each
+JIS0208_LEN = 60 * 188
+
+
+def single_byte_table(codec):
+    values = []
+    for byte in range(0x80, 0x100):
+        try:
+            values.append(ord(bytes([byte]).decode(codec)))
+        except UnicodeDecodeError:
+            values.append(0xFFFD)
+    return values
+
+
+def jis0208_table():
+    """JIS X 0208 by pointer, as Windows (code page 932) maps it; 0 marks unmapped pointers"""
+    values = []
+    for pointer in range(JIS0208_LEN):
+        lead, trail = divmod(pointer, 188)
+        lead += 0x81 if lead < 0x1F else 0xC1
+        trail += 0x40 if trail < 0x3F else 0x41
+        try:
+            text = bytes([lead, trail]).decode('cp932')
+            values.append(ord(text) if len(text) == 1 else 0)
+        except UnicodeDecodeError:
+            values.append(0)
+    return values
+
+
+def emit(name, values, doc):
+    print('/// {}'.format(doc))
+    print('pub static {}: [u16; {}] = ['.format(name, len(values)))
+    for start in range(0, len(values), 12):
+        row = ', '.join('0x{:04X}'.format(v) for v in values[start:start + 12])
+        print('    {},'.format(row))
+    print('];')
+
+
+def main():
+    print('// Generated by script/generate_charset_tables.py; do not edit by hand.')
+    print()
+    for name, codec in SINGLE_BYTE:
+        emit(name, single_byte_table(codec),
+             'Code points for bytes 0x80..=0xFF; 0xFFFD marks unassigned bytes')
+        print()
+    emit('JIS0208', jis0208_table(),
+         'JIS X 0208 code points by pointer (row * 94 + cell); 0 marks unassigned pointers')
+
+
+if __name__ == '__main__':
+    main()
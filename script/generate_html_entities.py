@@ -0,0 +1,29 @@
+#!/usr/bin/env python3
+"""Generate the HTML5 named character reference table used by src-tauri/src/parser/html.rs.
+
+    python3 script/generate_html_entities.py > src-tauri/src/parser/html/entities.rs
+"""
+from html.entities import html5
+
+
+def rust_string(value):
+    return ''.join(
+        c if 0x20 <= ord(c) < 0x7F and c not in '"\\' else '\\u{{{:X}}}'.format(ord(c))
+        for c in value
+    )
+
+
+def main():
+    names = sorted(html5)
+    print('// Generated by script/generate_html_entities.py; do not edit by hand.')
+    print()
+    print('/// HTML5 named character references, sorted by name. Names include the trailing `;`;')
+    print('/// the legacy forms browsers also accept without it appear a second time without one.')
+    print('pub static ENTITIES: [(&str, &str); {}] = ['.format(len(names)))
+    for name in names:
+        print('    ("{}", "{}"),'.format(rust_string(name), rust_string(html5[name])))
+    print('];')
+
+
+if __name__ == '__main__':
+    main()
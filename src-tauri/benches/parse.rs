@@ -0,0 +1,83 @@
+//! `cargo bench` baselines for parsing and IPC paths. Fixtures are generated by
+//! `msg_reader_lib::bench`; pass a name filter to run some of them, as in
+//! `cargo bench -- eml`.
+
+use msg_reader_lib::bench;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Time spent sampling each benchmark after warming up
+const MEASUREMENT: Duration = Duration::from_secs(3);
+
+/// Run `f` repeatedly and print the median, fastest and slowest iteration, plus the
+/// throughput for `bytes` of input
+fn run(name: &str, bytes: usize, mut f: impl FnMut() -> usize) {
+    let filter: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    if !filter.is_empty() && !filter.iter().any(|filter| name.contains(filter.as_str())) {
+        return;
+    }
+
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < MEASUREMENT / 3 {
+        black_box(f());
+    }
+    let mut samples = Vec::new();
+    let started = Instant::now();
+    while started.elapsed() < MEASUREMENT || samples.len() < 10 {
+        let iteration = Instant::now();
+        black_box(f());
+        samples.push(iteration.elapsed());
+    }
+    samples.sort_unstable();
+    let median = samples[samples.len() / 2];
+    let throughput = bytes as f64 / median.as_secs_f64() / (1024.0 * 1024.0);
+    println!(
+        "{:<32} median {:>10.3?}  min {:>10.3?}  max {:>10.3?}  {:>9.1} MiB/s  ({} runs)",
+        name,
+        median,
+        samples[0],
+        samples[samples.len() - 1],
+        throughput,
+        samples.len()
+    );
+}
+
+fn main() {
+    for (name, body, attachments) in [
+        ("parse_msg/small", 4 * 1024, vec![]),
+        ("parse_msg/medium", 64 * 1024, vec![200 * 1024; 5]),
+        ("parse_msg/large", 256 * 1024, vec![5 * 1024 * 1024; 4]),
+    ] {
+        let data = bench::msg(body, &attachments);
+        run(name, data.len(), || bench::parse_msg(&data));
+    }
+
+    let eml = bench::eml(10, 100 * 1024);
+    run("parse_eml/10_attachments", eml.len(), || {
+        bench::parse_eml(&eml)
+    });
+
+    let dir = std::env::temp_dir().join(format!("msg-reader-bench-{}", std::process::id()));
+    let size = bench::folder(&dir, 1000);
+    run("index_folder/1000", size, || bench::index_folder(&dir));
+    run("parse_folder/1000", size, || {
+        bench::parse_folder(&dir, false)
+    });
+    run("parse_folder/1000_previews", size, || {
+        bench::parse_folder(&dir, true)
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let html = bench::html_body(1024 * 1024);
+    run("html/1mb", html.len(), || bench::process_html(&html));
+
+    let file = bench::msg(64 * 1024, &[2 * 1024 * 1024]);
+    run("ipc/json_array", file.len(), || {
+        bench::ipc_json_array(&file)
+    });
+    run("ipc/base64", file.len(), || bench::ipc_base64(&file));
+    run("ipc/raw_response", file.len(), || bench::ipc_raw(&file));
+}
@@ -1,3 +1,46 @@
+use std::fmt::Write;
+
+/// Expands the command registry into the permission groups
+macro_rules! registry {
+    ($($group:literal: $description:literal => [$($module:ident::$command:ident),* $(,)?]),* $(,)?) => {
+        const GROUPS: &[(&str, &str, &[&str])] =
+            &[$(($group, $description, &[$(stringify!($command)),*])),*];
+        const COMMANDS: &[&str] = &[$($(stringify!($command)),*),*];
+    };
+}
+
+include!("src/commands/registry.rs");
+
+/// One permission set per group, granting its commands (the `allow-*` permissions
+/// tauri-build generates for each command)
+fn permission_sets() -> String {
+    let mut toml = String::from("# Generated by build.rs from src/commands/registry.rs\n");
+    for (group, description, commands) in GROUPS {
+        let _ = write!(
+            toml,
+            "\n[[set]]\nidentifier = \"{}\"\ndescription = \"{}\"\npermissions = [\n",
+            group, description
+        );
+        for command in *commands {
+            let _ = writeln!(toml, "  \"allow-{}\",", command.replace('_', "-"));
+        }
+        toml.push_str("]\n");
+    }
+    toml
+}
+
 fn main() {
-    tauri_build::build();
+    println!("cargo:rerun-if-changed=src/commands/registry.rs");
+    let path = std::path::Path::new("permissions/groups.toml");
+    let toml = permission_sets();
+    if std::fs::read_to_string(path).ok().as_deref() != Some(toml.as_str()) {
+        std::fs::create_dir_all("permissions").expect("failed to create permissions/");
+        std::fs::write(path, toml).expect("failed to write permissions/groups.toml");
+    }
+
+    tauri_build::try_build(
+        tauri_build::Attributes::new()
+            .app_manifest(tauri_build::AppManifest::new().commands(COMMANDS)),
+    )
+    .expect("failed to run tauri-build");
 }
@@ -0,0 +1,212 @@
+use crate::i18n;
+use crate::settings::SettingsStore;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// Prefix of the error returned for paths outside the access policy; the rest is the path,
+/// which the frontend can pass to `request_access`
+pub const PERMISSION_DENIED: &str = "PermissionDenied";
+
+/// Paths the webview may hand to commands: files the user opened through the OS (file
+/// associations, drag and drop, session restore) and folders they approved. Everything is
+/// stored canonicalized, so symlinks can't lead out of an allowed folder.
+#[derive(Default)]
+pub struct AccessPolicy(Mutex<Allowed>);
+
+#[derive(Default)]
+struct Allowed {
+    files: HashSet<PathBuf>,
+    /// Folders of opened files; their files (not subfolders) are allowed too
+    parents: HashSet<PathBuf>,
+    /// Folders allowed with everything below them for this run (dropped folders)
+    trees: HashSet<PathBuf>,
+}
+
+impl AccessPolicy {
+    /// Allow a file the user opened, and the other files next to it
+    pub fn allow_file(&self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        let mut allowed = self.0.lock().unwrap();
+        if path.is_dir() {
+            allowed.trees.insert(path);
+            return;
+        }
+        if let Some(parent) = path.parent() {
+            allowed.parents.insert(parent.to_path_buf());
+        }
+        allowed.files.insert(path);
+    }
+
+    /// Allow a file and nothing next to it, for files the user confirmed but didn't pick
+    /// (forwarded by a second instance)
+    pub fn allow_single_file(&self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        if path.is_file() {
+            self.0.lock().unwrap().files.insert(path);
+        }
+    }
+
+    /// Whether a canonical path may be accessed, given the user-approved folders
+    fn permits(&self, path: &Path, approved: &[PathBuf]) -> bool {
+        let allowed = self.0.lock().unwrap();
+        allowed.files.contains(path)
+            || path
+                .parent()
+                .is_some_and(|parent| allowed.parents.contains(parent))
+            || allowed.trees.iter().any(|dir| path.starts_with(dir))
+            || approved.iter().any(|dir| path.starts_with(dir))
+    }
+}
+
+/// Resolve `path` as the webview passed it, following symlinks. Paths that don't exist yet
+/// (export targets) are resolved through their folder.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(resolved) = path.canonicalize() {
+        return Some(resolved);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => return None,
+    };
+    let name = path.file_name()?;
+    parent.canonicalize().ok().map(|parent| parent.join(name))
+}
+
+/// Folders approved in settings, canonicalized
+fn approved_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    app.state::<SettingsStore>()
+        .get()
+        .approved_dirs
+        .iter()
+        .filter_map(|dir| Path::new(dir).canonicalize().ok())
+        .collect()
+}
+
+/// Check a path from the webview against the policy and return it resolved; used for
+/// reads and writes alike
+///
+/// A path that can't be resolved because its volume is gone fails with
+/// `volumes::VOLUME_UNAVAILABLE` instead of `PermissionDenied`.
+pub fn check(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    check_with(&app.state::<AccessPolicy>(), &approved_dirs(app), path)
+        .map_err(|denied| crate::volumes::unavailable(app, Path::new(path)).unwrap_or(denied))
+}
+
+/// `check` against a given policy and canonical approved folders
+pub fn check_with(
+    policy: &AccessPolicy,
+    approved: &[PathBuf],
+    path: &str,
+) -> Result<PathBuf, String> {
+    let denied = || format!("{}: {}", PERMISSION_DENIED, path);
+    let resolved = resolve(Path::new(path)).ok_or_else(denied)?;
+    if policy.permits(&resolved, approved) {
+        return Ok(resolved);
+    }
+    log_line!("Denied access to {}", resolved.display());
+    Err(denied())
+}
+
+/// Ask the user in a native dialog whether the folder of `path` (or `path` itself, if it is
+/// one) may be accessed; approved folders are remembered in settings
+pub fn request(app: &AppHandle, path: &str) -> Result<bool, String> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let resolved = resolve(Path::new(path)).ok_or_else(|| format!("Path not found: {}", path))?;
+    let dir = if resolved.is_dir() {
+        resolved
+    } else {
+        resolved
+            .parent()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| format!("Path not found: {}", path))?
+    };
+
+    let approved = app
+        .dialog()
+        .message(i18n::t(
+            "access.message",
+            &[("dir", dir.display().to_string())],
+        ))
+        .title(i18n::t("access.title", &[]))
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            i18n::t("access.allow", &[]),
+            i18n::t("access.deny", &[]),
+        ))
+        .blocking_show();
+    if !approved {
+        return Ok(false);
+    }
+
+    let dir = dir.to_string_lossy().to_string();
+    app.state::<SettingsStore>().update(|settings| {
+        if !settings.approved_dirs.contains(&dir) {
+            settings.approved_dirs.push(dir);
+        }
+    })?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opened_files_allow_their_folder_but_not_below() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let inbox = root.join("inbox");
+        let secrets = root.join("inbox").join("secrets");
+        std::fs::create_dir_all(&secrets).unwrap();
+        for file in [
+            inbox.join("a.msg"),
+            inbox.join("b.eml"),
+            secrets.join("key"),
+            root.join("other.txt"),
+        ] {
+            std::fs::write(file, b"x").unwrap();
+        }
+
+        let policy = AccessPolicy::default();
+        policy.allow_file(&inbox.join("a.msg"));
+        let permits = |path: &Path, approved: &[PathBuf]| {
+            resolve(path).is_some_and(|path| policy.permits(&path, approved))
+        };
+
+        assert!(permits(&inbox.join("a.msg"), &[]));
+        assert!(permits(&inbox.join("b.eml"), &[]));
+        assert!(permits(&inbox.join("export.mbox"), &[]));
+        assert!(!permits(&secrets.join("key"), &[]));
+        assert!(!permits(&root.join("other.txt"), &[]));
+        assert!(!permits(&inbox.join("..").join("other.txt"), &[]));
+
+        let approved = vec![root.canonicalize().unwrap()];
+        assert!(permits(&secrets.join("key"), &approved));
+
+        // Denials name the path as passed, for `request_access`
+        let key = secrets.join("key").to_string_lossy().to_string();
+        assert_eq!(
+            check_with(&policy, &[], &key),
+            Err(format!("{}: {}", PERMISSION_DENIED, key))
+        );
+        assert!(check_with(&policy, &[], "relative.msg").is_err());
+        assert_eq!(
+            check_with(&policy, &approved, &key),
+            Ok(secrets.join("key").canonicalize().unwrap())
+        );
+
+        #[cfg(unix)]
+        {
+            // A link in an allowed folder pointing outside of it is judged by its target
+            std::os::unix::fs::symlink(root.join("other.txt"), inbox.join("link.msg")).unwrap();
+            assert!(!permits(&inbox.join("link.msg"), &[]));
+        }
+    }
+}
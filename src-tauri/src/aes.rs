@@ -0,0 +1,168 @@
+use std::sync::OnceLock;
+
+/// AES encryption (FIPS-197) of single blocks, which is all CTR mode needs. Byte oriented
+/// and not constant time: it decrypts archives the user opened, it doesn't guard secrets.
+pub struct Aes {
+    round_keys: Vec<[u8; 16]>,
+}
+
+/// S-box, derived from the multiplicative inverse in GF(2^8) rather than typed in
+fn sbox() -> &'static [u8; 256] {
+    static SBOX: OnceLock<[u8; 256]> = OnceLock::new();
+    SBOX.get_or_init(|| {
+        let mut sbox = [0u8; 256];
+        for (x, entry) in sbox.iter_mut().enumerate() {
+            // x^254 is the inverse of x (and maps 0 to 0)
+            let mut inverse = 1u8;
+            let mut power = x as u8;
+            let mut exponent = 254u32;
+            while exponent > 0 {
+                if exponent & 1 == 1 {
+                    inverse = multiply(inverse, power);
+                }
+                power = multiply(power, power);
+                exponent >>= 1;
+            }
+            *entry = inverse
+                ^ inverse.rotate_left(1)
+                ^ inverse.rotate_left(2)
+                ^ inverse.rotate_left(3)
+                ^ inverse.rotate_left(4)
+                ^ 0x63;
+        }
+        sbox
+    })
+}
+
+fn multiply(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplication by x (2) in GF(2^8)
+fn xtime(a: u8) -> u8 {
+    (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+impl Aes {
+    /// Expand a 16, 24 or 32 byte key
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        let words = key.len() / 4;
+        if !matches!(key.len(), 16 | 24 | 32) {
+            return Err(format!("Invalid AES key length: {}", key.len()));
+        }
+        let rounds = words + 6;
+        let sbox = sbox();
+        let mut w: Vec<[u8; 4]> = key.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]).collect();
+        let mut rcon = 1u8;
+        for i in words..4 * (rounds + 1) {
+            let mut temp = w[i - 1];
+            if i % words == 0 {
+                temp = [
+                    sbox[temp[1] as usize] ^ rcon,
+                    sbox[temp[2] as usize],
+                    sbox[temp[3] as usize],
+                    sbox[temp[0] as usize],
+                ];
+                rcon = xtime(rcon);
+            } else if words > 6 && i % words == 4 {
+                temp = temp.map(|b| sbox[b as usize]);
+            }
+            let previous = w[i - words];
+            w.push([
+                previous[0] ^ temp[0],
+                previous[1] ^ temp[1],
+                previous[2] ^ temp[2],
+                previous[3] ^ temp[3],
+            ]);
+        }
+        let round_keys = w
+            .chunks(4)
+            .map(|words| {
+                let mut key = [0u8; 16];
+                for (i, word) in words.iter().enumerate() {
+                    key[4 * i..4 * i + 4].copy_from_slice(word);
+                }
+                key
+            })
+            .collect();
+        Ok(Self { round_keys })
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let sbox = sbox();
+        let last = self.round_keys.len() - 1;
+        add(block, &self.round_keys[0]);
+        for round in 1..=last {
+            for b in block.iter_mut() {
+                *b = sbox[*b as usize];
+            }
+            // Row r of the column-major state moves r columns left
+            let s = *block;
+            for column in 0..4 {
+                for row in 0..4 {
+                    block[4 * column + row] = s[4 * ((column + row) % 4) + row];
+                }
+            }
+            if round != last {
+                for column in block.chunks_mut(4) {
+                    let [a, b, c, d] = [column[0], column[1], column[2], column[3]];
+                    column[0] = xtime(a) ^ xtime(b) ^ b ^ c ^ d;
+                    column[1] = a ^ xtime(b) ^ xtime(c) ^ c ^ d;
+                    column[2] = a ^ b ^ xtime(c) ^ xtime(d) ^ d;
+                    column[3] = xtime(a) ^ a ^ b ^ c ^ xtime(d);
+                }
+            }
+            add(block, &self.round_keys[round]);
+        }
+    }
+}
+
+fn add(block: &mut [u8; 16], key: &[u8; 16]) {
+    for (b, k) in block.iter_mut().zip(key) {
+        *b ^= k;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fips_197_examples() {
+        let plain: [u8; 16] = hex("00112233445566778899aabbccddeeff").try_into().unwrap();
+        for (key, expected) in [
+            (
+                "000102030405060708090a0b0c0d0e0f",
+                "69c4e0d86a7b0430d8cdb78070b4c55a",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "dda97ca4864cdfe06eaf70a0ec0d7191",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "8ea2b7ca516745bfeafc49904b496089",
+            ),
+        ] {
+            let mut block = plain;
+            Aes::new(&hex(key)).unwrap().encrypt_block(&mut block);
+            assert_eq!(block.to_vec(), hex(expected), "key {}", key);
+        }
+        assert!(Aes::new(&[0; 20]).is_err());
+    }
+}
@@ -0,0 +1,600 @@
+use crate::aes::Aes;
+use crate::parser::MessageFormat;
+use flate2::read::DeflateDecoder;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime};
+
+/// Error prefix when an archive needs a password (`ArchivePasswordRequired: <path>`)
+pub const PASSWORD_REQUIRED: &str = "ArchivePasswordRequired";
+/// Error prefix of a wrong password (`WrongArchivePassword: <attempts left>`)
+pub const WRONG_PASSWORD: &str = "WrongArchivePassword";
+/// Error once the attempts for an archive are used up
+pub const ATTEMPTS_EXHAUSTED: &str = "ArchiveAttemptsExhausted";
+
+/// Wrong passwords accepted per archive until it is opened anew
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Archives larger than this aren't opened
+const MAX_ARCHIVE_SIZE: u64 = 512 * 1024 * 1024;
+
+const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+const CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: &[u8] = b"PK\x05\x06";
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// WinZip AES; the real method is in the 0x9901 extra field
+const METHOD_AES: u16 = 99;
+const FLAG_ENCRYPTED: u16 = 1;
+/// Sizes and CRC follow the data; the ZipCrypto check byte then comes from the time
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const AES_EXTRA_FIELD: u16 = 0x9901;
+const AES_AUTH_CODE_LEN: usize = 10;
+const AES_KEY_ITERATIONS: u32 = 1000;
+
+/// How the entry of an archive is encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encryption {
+    ZipCrypto,
+    /// WinZip AES with a 128, 192 or 256 bit key
+    Aes(u16),
+}
+
+/// The message entry of a zip holding just one, as archiving products export them
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub format: MessageFormat,
+    pub encryption: Option<Encryption>,
+    flags: u16,
+    method: u16,
+    crc: u32,
+    mod_time: u16,
+    compressed_size: usize,
+    size: usize,
+    local_header: usize,
+}
+
+/// The message taken out of an archive; it is only ever kept in memory
+pub struct Unpacked {
+    pub format: MessageFormat,
+    pub data: Vec<u8>,
+}
+
+/// Why an entry couldn't be unpacked
+#[derive(Debug, PartialEq)]
+pub enum UnpackError {
+    WrongPassword,
+    Corrupt(String),
+}
+
+/// The archive at a path, as far as it was opened
+pub enum Opened {
+    Ready(Arc<Unpacked>),
+    /// Encrypted and not unlocked yet
+    Locked(ArchiveEntry),
+}
+
+/// Payload of `needs-archive-password` events
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordRequest {
+    pub path: String,
+    /// Document id, if the frontend registered the archive already
+    pub doc_id: Option<String>,
+    pub entry: String,
+    pub encryption: Encryption,
+    pub attempts_left: u32,
+}
+
+struct ArchiveState {
+    modified: Option<SystemTime>,
+    unpacked: Option<Arc<Unpacked>>,
+    failed_attempts: u32,
+    /// Unlocked with a password, so the contents can't be unpacked again without asking
+    encrypted: bool,
+    used: Instant,
+}
+
+/// Messages unpacked from archives, by archive path, and the password attempts made
+#[derive(Default)]
+pub struct Archives(Mutex<HashMap<PathBuf, ArchiveState>>);
+
+impl Archives {
+    /// The message in the archive at `path` (already checked against the access policy):
+    /// unpacked right away if it isn't encrypted, taken from memory once unlocked
+    pub fn open(&self, path: &Path) -> Result<Opened, String> {
+        let modified = modified(path);
+        if let Some(unpacked) = self
+            .0
+            .lock()
+            .unwrap()
+            .get_mut(path)
+            .filter(|state| state.modified == modified)
+            .and_then(|state| {
+                state.used = Instant::now();
+                state.unpacked.clone()
+            })
+        {
+            return Ok(Opened::Ready(unpacked));
+        }
+
+        let data = read(path)?;
+        let entry = inspect(&data)
+            .ok_or_else(|| format!("{} doesn't hold a single message file", path.display()))?;
+        if entry.encryption.is_some() {
+            return Ok(Opened::Locked(entry));
+        }
+        let unpacked = unpack(&data, &entry, None).map_err(|e| match e {
+            UnpackError::Corrupt(e) => e,
+            UnpackError::WrongPassword => format!("Damaged archive entry: {}", entry.name),
+        })?;
+        Ok(Opened::Ready(self.store(path, modified, unpacked, false)))
+    }
+
+    /// Decrypt the archive at `path` with `password`. Every wrong password counts against
+    /// [`MAX_ATTEMPTS`]; the error tells how many are left.
+    pub fn unlock(&self, path: &Path, password: &str) -> Result<Arc<Unpacked>, String> {
+        let modified = modified(path);
+        let attempts = self
+            .0
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|state| state.modified == modified)
+            .map_or(0, |state| state.failed_attempts);
+        if attempts >= MAX_ATTEMPTS {
+            return Err(ATTEMPTS_EXHAUSTED.to_string());
+        }
+
+        let data = read(path)?;
+        let entry = inspect(&data)
+            .ok_or_else(|| format!("{} doesn't hold a single message file", path.display()))?;
+        match unpack(&data, &entry, Some(password)) {
+            Ok(unpacked) => Ok(self.store(path, modified, unpacked, true)),
+            Err(UnpackError::WrongPassword) => {
+                let mut archives = self.0.lock().unwrap();
+                let state = archives.entry(path.to_path_buf()).or_insert(ArchiveState {
+                    modified,
+                    unpacked: None,
+                    failed_attempts: 0,
+                    encrypted: true,
+                    used: Instant::now(),
+                });
+                if state.modified != modified {
+                    state.modified = modified;
+                    state.failed_attempts = 0;
+                }
+                state.failed_attempts += 1;
+                Err(format!(
+                    "{}: {}",
+                    WRONG_PASSWORD,
+                    MAX_ATTEMPTS - state.failed_attempts
+                ))
+            }
+            Err(UnpackError::Corrupt(e)) => Err(e),
+        }
+    }
+
+    /// Attempts left for the archive at `path`
+    pub fn attempts_left(&self, path: &Path) -> u32 {
+        let failed = self
+            .0
+            .lock()
+            .unwrap()
+            .get(path)
+            .map_or(0, |state| state.failed_attempts);
+        MAX_ATTEMPTS.saturating_sub(failed)
+    }
+
+    /// Drop the unpacked message and the attempt count, once no document shows the archive
+    pub fn forget(&self, path: &Path) {
+        self.0.lock().unwrap().remove(path);
+    }
+
+    /// Bytes of unpacked messages that can be unpacked again without a password, and when
+    /// they were last used, by archive path (see `memory`)
+    pub fn held(&self) -> Vec<(PathBuf, usize, Instant)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, state)| !state.encrypted)
+            .filter_map(|(path, state)| {
+                let unpacked = state.unpacked.as_ref()?;
+                Some((path.clone(), unpacked.data.len(), state.used))
+            })
+            .collect()
+    }
+
+    /// Drop the unpacked message of an archive that isn't encrypted; it's unpacked again
+    /// when next opened. Decrypted ones stay until `forget`.
+    pub fn release(&self, path: &Path) {
+        let mut archives = self.0.lock().unwrap();
+        if archives.get(path).is_some_and(|state| !state.encrypted) {
+            archives.remove(path);
+        }
+    }
+
+    fn store(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        unpacked: Unpacked,
+        encrypted: bool,
+    ) -> Arc<Unpacked> {
+        let unpacked = Arc::new(unpacked);
+        self.0.lock().unwrap().insert(
+            path.to_path_buf(),
+            ArchiveState {
+                modified,
+                unpacked: Some(unpacked.clone()),
+                failed_attempts: 0,
+                encrypted,
+                used: Instant::now(),
+            },
+        );
+        unpacked
+    }
+}
+
+/// Whether `path` names a zip archive
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read file {}: {}", path.display(), e);
+    let size = std::fs::metadata(path).map_err(read_error)?.len();
+    if size > MAX_ARCHIVE_SIZE {
+        return Err(format!("Archive too large: {}", path.display()));
+    }
+    std::fs::read(path).map_err(read_error)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// The entry of a zip holding exactly one .msg or .eml file (directories aside)
+pub fn inspect(data: &[u8]) -> Option<ArchiveEntry> {
+    if !data.starts_with(LOCAL_HEADER_SIGNATURE) {
+        return None;
+    }
+    // The end record sits within the last 64 KiB (its comment is at most that long)
+    let search_from = data.len().saturating_sub(65_557);
+    let eocd = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| data[pos..].starts_with(END_OF_CENTRAL_DIRECTORY_SIGNATURE))?;
+    let count = u16_at(data, eocd + 10)?;
+    let mut pos = u32_at(data, eocd + 16)? as usize;
+
+    let mut found = None;
+    for _ in 0..count {
+        if data.get(pos..pos + 4)? != CENTRAL_HEADER_SIGNATURE {
+            return None;
+        }
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let name = String::from_utf8_lossy(data.get(pos + 46..pos + 46 + name_len)?).into_owned();
+        let extra = data.get(pos + 46 + name_len..pos + 46 + name_len + extra_len)?;
+        let next = pos + 46 + name_len + extra_len + comment_len;
+        if name.ends_with('/') {
+            pos = next;
+            continue;
+        }
+        if found.is_some() {
+            return None;
+        }
+
+        let flags = u16_at(data, pos + 8)?;
+        let mut method = u16_at(data, pos + 10)?;
+        let mut encryption = (flags & FLAG_ENCRYPTED != 0).then_some(Encryption::ZipCrypto);
+        if method == METHOD_AES {
+            let (bits, actual) = aes_extra_field(extra)?;
+            encryption = Some(Encryption::Aes(bits));
+            method = actual;
+        }
+        found = Some(ArchiveEntry {
+            format: MessageFormat::from_path(Path::new(&name))?,
+            name,
+            encryption,
+            flags,
+            method,
+            mod_time: u16_at(data, pos + 12)?,
+            crc: u32_at(data, pos + 16)?,
+            compressed_size: u32_at(data, pos + 20)? as usize,
+            size: u32_at(data, pos + 24)? as usize,
+            local_header: u32_at(data, pos + 42)? as usize,
+        });
+        pos = next;
+    }
+    found
+}
+
+/// Key size in bits and the real compression method from the WinZip AES extra field
+fn aes_extra_field(mut extra: &[u8]) -> Option<(u16, u16)> {
+    while extra.len() >= 4 {
+        let id = u16_at(extra, 0)?;
+        let len = u16_at(extra, 2)? as usize;
+        let field = extra.get(4..4 + len)?;
+        if id == AES_EXTRA_FIELD && len >= 7 && &field[2..4] == b"AE" {
+            let bits = match field[4] {
+                1 => 128,
+                2 => 192,
+                3 => 256,
+                _ => return None,
+            };
+            return Some((bits, u16_at(field, 5)?));
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Decrypt (with `password`, for encrypted entries) and decompress the entry
+pub fn unpack(
+    data: &[u8],
+    entry: &ArchiveEntry,
+    password: Option<&str>,
+) -> Result<Unpacked, UnpackError> {
+    let corrupt = || UnpackError::Corrupt(format!("Damaged archive entry: {}", entry.name));
+    let pos = entry.local_header;
+    if data.get(pos..pos + 4) != Some(LOCAL_HEADER_SIGNATURE) {
+        return Err(corrupt());
+    }
+    let name_len = u16_at(data, pos + 26).ok_or_else(corrupt)? as usize;
+    let extra_len = u16_at(data, pos + 28).ok_or_else(corrupt)? as usize;
+    let start = pos + 30 + name_len + extra_len;
+    let stored = data
+        .get(start..start + entry.compressed_size)
+        .ok_or_else(corrupt)?;
+
+    let password = password.unwrap_or_default().as_bytes();
+    let (compressed, check_crc) = match entry.encryption {
+        None => (stored.to_vec(), true),
+        Some(Encryption::ZipCrypto) => (zip_crypto_decrypt(stored, entry, password)?, true),
+        // AE-2 leaves the CRC at 0 and relies on the authentication code
+        Some(Encryption::Aes(bits)) => (aes_decrypt(stored, bits, password)?, entry.crc != 0),
+    };
+
+    let content = match entry.method {
+        METHOD_STORED => compressed,
+        METHOD_DEFLATE => {
+            let mut content = Vec::with_capacity(entry.size.min(64 * 1024 * 1024));
+            let result = DeflateDecoder::new(&compressed[..])
+                .take(entry.size as u64 + 1)
+                .read_to_end(&mut content);
+            if result.is_err() {
+                // A ZipCrypto check byte matches one wrong password in 256
+                return Err(match entry.encryption {
+                    Some(Encryption::ZipCrypto) => UnpackError::WrongPassword,
+                    _ => corrupt(),
+                });
+            }
+            content
+        }
+        method => {
+            return Err(UnpackError::Corrupt(format!(
+                "Unsupported compression method {} in {}",
+                method, entry.name
+            )))
+        }
+    };
+    if content.len() != entry.size || (check_crc && crc32fast::hash(&content) != entry.crc) {
+        return Err(match entry.encryption {
+            Some(Encryption::ZipCrypto) => UnpackError::WrongPassword,
+            _ => corrupt(),
+        });
+    }
+    Ok(Unpacked {
+        format: entry.format,
+        data: content,
+    })
+}
+
+/// Traditional PKWARE encryption, with its 12 byte header
+fn zip_crypto_decrypt(
+    stored: &[u8],
+    entry: &ArchiveEntry,
+    password: &[u8],
+) -> Result<Vec<u8>, UnpackError> {
+    if stored.len() < 12 {
+        return Err(UnpackError::Corrupt(format!(
+            "Damaged archive entry: {}",
+            entry.name
+        )));
+    }
+    let mut keys = ZipCryptoKeys::new(password);
+    let mut header = [0u8; 12];
+    for (out, &byte) in header.iter_mut().zip(stored) {
+        *out = keys.decrypt(byte);
+    }
+    let check = if entry.flags & FLAG_DATA_DESCRIPTOR != 0 {
+        (entry.mod_time >> 8) as u8
+    } else {
+        (entry.crc >> 24) as u8
+    };
+    if header[11] != check {
+        return Err(UnpackError::WrongPassword);
+    }
+    Ok(stored[12..]
+        .iter()
+        .map(|&byte| keys.decrypt(byte))
+        .collect())
+}
+
+struct ZipCryptoKeys([u32; 3]);
+
+impl ZipCryptoKeys {
+    fn new(password: &[u8]) -> Self {
+        let mut keys = Self([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+        for &byte in password {
+            keys.update(byte);
+        }
+        keys
+    }
+
+    fn update(&mut self, byte: u8) {
+        let [k0, k1, k2] = &mut self.0;
+        *k0 = crc32_update(*k0, byte);
+        *k1 = k1
+            .wrapping_add(*k0 & 0xff)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        *k2 = crc32_update(*k2, (*k1 >> 24) as u8);
+    }
+
+    fn decrypt(&mut self, byte: u8) -> u8 {
+        let temp = (self.0[2] | 2) as u16;
+        let plain = byte ^ (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+        self.update(plain);
+        plain
+    }
+}
+
+/// One byte of the raw (unconditioned) CRC-32 the ZipCrypto keys are built on
+fn crc32_update(crc: u32, byte: u8) -> u32 {
+    static TABLE: OnceLock<[u32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        table
+    });
+    (crc >> 8) ^ table[((crc ^ byte as u32) & 0xff) as usize]
+}
+
+/// WinZip AES: salt, password verifier, AES-CTR data and an HMAC-SHA1 authentication code
+fn aes_decrypt(stored: &[u8], bits: u16, password: &[u8]) -> Result<Vec<u8>, UnpackError> {
+    let key_len = bits as usize / 8;
+    let salt_len = key_len / 2;
+    if stored.len() < salt_len + 2 + AES_AUTH_CODE_LEN {
+        return Err(UnpackError::Corrupt(
+            "Damaged encrypted archive".to_string(),
+        ));
+    }
+    let (salt, rest) = stored.split_at(salt_len);
+    let (verifier, rest) = rest.split_at(2);
+    let (encrypted, auth_code) = rest.split_at(rest.len() - AES_AUTH_CODE_LEN);
+
+    let mut keys = vec![0u8; 2 * key_len + 2];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA1,
+        NonZeroU32::new(AES_KEY_ITERATIONS).expect("nonzero"),
+        salt,
+        password,
+        &mut keys,
+    );
+    let (aes_key, rest) = keys.split_at(key_len);
+    let (mac_key, derived_verifier) = rest.split_at(key_len);
+    if derived_verifier != verifier {
+        return Err(UnpackError::WrongPassword);
+    }
+    let mac = ring::hmac::Key::new(ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, mac_key);
+    if &ring::hmac::sign(&mac, encrypted).as_ref()[..AES_AUTH_CODE_LEN] != auth_code {
+        // The verifier matches one wrong password in 65536
+        return Err(UnpackError::WrongPassword);
+    }
+
+    let aes = Aes::new(aes_key).map_err(UnpackError::Corrupt)?;
+    let mut plain = encrypted.to_vec();
+    for (counter, chunk) in plain.chunks_mut(16).enumerate() {
+        // Little-endian counter, starting at 1
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&(counter as u64 + 1).to_le_bytes());
+        aes.encrypt_block(&mut block);
+        for (b, k) in chunk.iter_mut().zip(block) {
+            *b ^= k;
+        }
+    }
+    Ok(plain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"From: Archive <archive@example.com>\r\nSubject: Archived message\r\nContent-Type: text/plain\r\n\r\nKept for seven years.\r\n";
+
+    #[test]
+    fn zip_crypto_archive() {
+        // Made with `zip -P s3cret`, which also sets the data descriptor flag
+        let data = include_bytes!("../tests/fixtures/archive/zipcrypto.zip");
+        let entry = inspect(data).unwrap();
+        assert_eq!(entry.name, "message.eml");
+        assert_eq!(entry.encryption, Some(Encryption::ZipCrypto));
+        assert!(matches!(
+            unpack(data, &entry, Some("secret")),
+            Err(UnpackError::WrongPassword)
+        ));
+        let unpacked = unpack(data, &entry, Some("s3cret")).unwrap();
+        assert_eq!(unpacked.format, MessageFormat::Eml);
+        assert_eq!(unpacked.data, MESSAGE);
+    }
+
+    #[test]
+    fn aes_archive() {
+        // AE-2, AES-256, deflated; encrypted with an independent AES implementation
+        let data = include_bytes!("../tests/fixtures/archive/aes256.zip");
+        let entry = inspect(data).unwrap();
+        assert_eq!(entry.encryption, Some(Encryption::Aes(256)));
+        assert!(matches!(
+            unpack(data, &entry, Some("S3cret")),
+            Err(UnpackError::WrongPassword)
+        ));
+        assert_eq!(unpack(data, &entry, Some("s3cret")).unwrap().data, MESSAGE);
+    }
+
+    #[test]
+    fn attempts_are_bounded() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("archive.zip");
+        std::fs::write(
+            &path,
+            include_bytes!("../tests/fixtures/archive/zipcrypto.zip"),
+        )
+        .unwrap();
+        let archives = Archives::default();
+        assert!(matches!(archives.open(&path), Ok(Opened::Locked(_))));
+        for left in (0..MAX_ATTEMPTS).rev() {
+            let error = archives.unlock(&path, "guess").err().unwrap();
+            assert_eq!(error, format!("{}: {}", WRONG_PASSWORD, left));
+        }
+        let exhausted = archives.unlock(&path, "s3cret").err();
+        archives.forget(&path);
+        let unlocked = archives.unlock(&path, "s3cret").is_ok();
+        let reopened = matches!(archives.open(&path), Ok(Opened::Ready(_)));
+        assert_eq!(exhausted.as_deref(), Some(ATTEMPTS_EXHAUSTED));
+        assert!(unlocked);
+        assert!(reopened);
+    }
+}
@@ -0,0 +1,698 @@
+use crate::export::{self, FailedExport};
+use crate::filenames::{self, TargetOs};
+use crate::folder_index::{parse_bound, parse_timestamp};
+use crate::notes::{self, Notes, TagMode};
+use crate::parser::{Attachment, Message, MessageFormat};
+use crate::save;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Messages whose attachment digests are kept; the cache is emptied when it grows past this
+const MAX_CACHED_MESSAGES: usize = 50_000;
+
+/// What deduplication needs to know about an attachment; the content itself isn't kept
+#[derive(Debug, Clone)]
+struct Digest {
+    index: usize,
+    file_name: String,
+    size: usize,
+    sha256: String,
+    /// Inline image or signature logo rather than a real attachment
+    inline: bool,
+}
+
+impl Digest {
+    fn of(attachment: &Attachment) -> Self {
+        Self {
+            index: attachment.index,
+            file_name: attachment.suggested_filename.clone(),
+            size: attachment.size,
+            sha256: attachment.sha256.clone(),
+            inline: is_inline(attachment),
+        }
+    }
+}
+
+/// Attachment digests by message content hash, so folders are only parsed once
+#[derive(Default)]
+pub struct DigestCache(Mutex<HashMap<String, Arc<Vec<Digest>>>>);
+
+impl DigestCache {
+    fn get(
+        &self,
+        path: &Path,
+        load: impl Fn(&Path) -> Result<Message, String>,
+    ) -> Result<Arc<Vec<Digest>>, String> {
+        let key = notes::content_hash(path)?;
+        if let Some(digests) = self.0.lock().unwrap().get(&key) {
+            return Ok(digests.clone());
+        }
+        let message = load(path)?;
+        let digests = Arc::new(message.attachments.iter().map(Digest::of).collect());
+        let mut cache = self.0.lock().unwrap();
+        if cache.len() >= MAX_CACHED_MESSAGES {
+            cache.clear();
+        }
+        cache.insert(key, Arc::clone(&digests));
+        Ok(digests)
+    }
+}
+
+/// An attachment of a message file
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AttachmentSource {
+    pub path: PathBuf,
+    pub index: usize,
+    pub file_name: String,
+}
+
+/// Attachments with identical content
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    pub size: usize,
+    /// In path order
+    pub sources: Vec<AttachmentSource>,
+}
+
+/// Outcome of `dedupe_report`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DedupeReport {
+    /// Contents attached more than once, largest waste first
+    pub groups: Vec<DuplicateGroup>,
+    /// Zero-byte attachments, which are all "identical" but rarely copies of each other
+    pub empty: Vec<AttachmentSource>,
+    /// Distinct non-empty contents
+    pub unique: usize,
+    /// Bytes saved by keeping one copy of each group
+    pub redundant_bytes: u64,
+    /// Message files that couldn't be read
+    pub failed: Vec<String>,
+}
+
+/// Outcome of `export_attachments`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AttachmentExport {
+    /// Files written, in order
+    pub written: Vec<save::SavedFile>,
+    /// Attachments not written again because an identical one was
+    pub skipped_duplicates: usize,
+    /// Message files that couldn't be read
+    pub failed: Vec<String>,
+    /// The manifest written with `unique: true`
+    pub manifest: Option<String>,
+}
+
+/// Manifest of a deduplicated export: for every message, which file holds each attachment
+#[derive(Debug, Serialize)]
+struct Manifest {
+    messages: Vec<ManifestMessage>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestMessage {
+    path: PathBuf,
+    attachments: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    index: usize,
+    file_name: String,
+    sha256: String,
+    /// Name of the exported file in the target folder
+    file: String,
+}
+
+/// Which attachments `export_matching` saves; all fields are optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AttachmentFilter {
+    /// Case-insensitive glob on the file name: `*` for any run of characters, `?` for one
+    pub name: Option<String>,
+    /// MIME type (detected, else declared), or a family like `image/*`
+    pub mime_type: Option<String>,
+    /// Size range in bytes, both ends included
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    /// Case-insensitive part of the message sender's name or address
+    pub sender: Option<String>,
+    /// Message date range, as in `ListingFilter`
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    /// Tag the message must carry
+    pub tag: Option<String>,
+    pub include_inline: bool,
+}
+
+impl AttachmentFilter {
+    fn message(&self, path: &Path, message: &Message, notes: &Notes) -> bool {
+        if let Some(sender) = non_empty(&self.sender) {
+            let from = message.from.as_ref().map(export::format_address);
+            if !from.is_some_and(|from| from.to_lowercase().contains(&sender.to_lowercase())) {
+                return false;
+            }
+        }
+        let from = self
+            .date_from
+            .as_deref()
+            .and_then(|d| parse_bound(d, false));
+        let to = self.date_to.as_deref().and_then(|d| parse_bound(d, true));
+        if from.is_some() || to.is_some() {
+            let Some(date) = message.date.as_deref().and_then(parse_timestamp) else {
+                return false;
+            };
+            if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+                return false;
+            }
+        }
+        match non_empty(&self.tag) {
+            Some(tag) => notes::content_hash(path)
+                .is_ok_and(|key| notes.matches(&key, &[tag.to_string()], TagMode::Any)),
+            None => true,
+        }
+    }
+
+    pub(crate) fn attachment(&self, attachment: &Attachment) -> bool {
+        if !self.include_inline && is_inline(attachment) {
+            return false;
+        }
+        if let Some(pattern) = non_empty(&self.name) {
+            if !glob_matches(pattern, &attachment.suggested_filename) {
+                return false;
+            }
+        }
+        if let Some(wanted) = non_empty(&self.mime_type) {
+            let mime = attachment
+                .detected_mime
+                .as_deref()
+                .or(attachment.declared_mime.as_deref())
+                .unwrap_or_default()
+                .to_lowercase();
+            let wanted = wanted.to_lowercase();
+            let matches = match wanted.strip_suffix("/*") {
+                Some(family) => mime.split('/').next() == Some(family),
+                None => mime == wanted,
+            };
+            if !matches {
+                return false;
+            }
+        }
+        self.min_size.is_none_or(|min| attachment.size >= min)
+            && self.max_size.is_none_or(|max| attachment.size <= max)
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Case-insensitive match of a whole name against `*`/`?` wildcards
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, tried)) => {
+                    p = after;
+                    n = tried + 1;
+                    star = Some((after, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Outcome of `export_matching`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MatchingExport {
+    /// Attachments that passed the filter
+    pub matched: usize,
+    /// Files written, in order
+    pub written: Vec<save::SavedFile>,
+    /// Attachments of readable messages that didn't pass the filter
+    pub skipped: usize,
+    /// Messages that couldn't be read and matched attachments that couldn't be written
+    pub failed: Vec<FailedExport>,
+    /// `manifest.csv`, mapping every written file to its message; `None` when nothing matched
+    pub manifest: Option<String>,
+}
+
+/// A row of the manifest of `export_matching`
+struct ManifestRow {
+    file: String,
+    message: String,
+    sender: String,
+    date: String,
+    subject: String,
+    index: usize,
+    file_name: String,
+    sha256: String,
+}
+
+/// Save the attachments of `paths` (message files, or folders whose message files are
+/// searched) that pass `filter` into `target`, or into a folder per message with
+/// `per_message_folders`. Names are sanitized and numbered when taken; `manifest.csv`
+/// maps the written files back to their messages.
+///
+/// Problems with single messages or attachments are listed in `failed` and the export goes
+/// on. `before_each(current, total)` runs before every message; an error from it (a
+/// cancelled job) stops the export, keeping what was written, without a manifest.
+pub fn export_matching(
+    paths: &[PathBuf],
+    filter: &AttachmentFilter,
+    target: &Path,
+    per_message_folders: bool,
+    notes: &Notes,
+    load: impl Fn(&Path) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<MatchingExport, String> {
+    let mut export = MatchingExport::default();
+    let mut files = Vec::new();
+    for path in paths {
+        match path.is_dir() {
+            true => match message_files(path) {
+                Ok(found) => files.extend(found),
+                Err(error) => export.failed.push(failure(path, error)),
+            },
+            false => files.push(path.clone()),
+        }
+    }
+    std::fs::create_dir_all(target)
+        .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+
+    let mut rows = Vec::new();
+    for (current, path) in files.iter().enumerate() {
+        before_each(current, files.len())?;
+        let message = match load(path) {
+            Ok(message) => message,
+            Err(error) => {
+                log_line!(
+                    "Skipping {} in attachment export: {}",
+                    path.display(),
+                    error
+                );
+                export.failed.push(failure(path, error));
+                continue;
+            }
+        };
+        if !filter.message(path, &message, notes) {
+            export.skipped += message.attachments.len();
+            continue;
+        }
+
+        let mut folder: Option<PathBuf> = None;
+        for attachment in &message.attachments {
+            if !filter.attachment(attachment) {
+                export.skipped += 1;
+                continue;
+            }
+            export.matched += 1;
+            let dir = match (per_message_folders, &folder) {
+                (false, _) => target.to_path_buf(),
+                (true, Some(folder)) => folder.clone(),
+                (true, None) => {
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    let name = filenames::sanitize_filename(&stem, TargetOs::CURRENT);
+                    let dir = filenames::dedupe_in_dir(target, &name);
+                    if let Err(e) = std::fs::create_dir_all(&dir) {
+                        let error = format!("Failed to create {}: {}", dir.display(), e);
+                        export.failed.push(failure(path, error));
+                        continue;
+                    }
+                    folder = Some(dir.clone());
+                    dir
+                }
+            };
+            let name =
+                filenames::sanitize_filename(&attachment.suggested_filename, TargetOs::CURRENT);
+            let output = filenames::dedupe_in_dir(&dir, &name);
+            match save::write_verified(
+                &output,
+                &attachment.data,
+                &attachment.sha256,
+                true,
+                |_, _| Ok(()),
+            ) {
+                Ok(saved) => {
+                    let file = output.strip_prefix(target).unwrap_or(&output);
+                    rows.push(ManifestRow {
+                        file: file.to_string_lossy().to_string(),
+                        message: path.to_string_lossy().to_string(),
+                        sender: message
+                            .from
+                            .as_ref()
+                            .map(export::format_address)
+                            .unwrap_or_default(),
+                        date: message.date.clone().unwrap_or_default(),
+                        subject: message.subject.clone().unwrap_or_default(),
+                        index: attachment.index,
+                        file_name: attachment.file_name.clone(),
+                        sha256: attachment.sha256.clone(),
+                    });
+                    export.written.push(saved);
+                }
+                Err(error) => export.failed.push(failure(path, error)),
+            }
+        }
+    }
+
+    if !rows.is_empty() {
+        let output = filenames::dedupe_in_dir(target, "manifest.csv");
+        save::write_atomic(&output, manifest_csv(&rows).as_bytes())?;
+        export.manifest = Some(output.to_string_lossy().to_string());
+    }
+    Ok(export)
+}
+
+fn failure(path: &Path, error: String) -> FailedExport {
+    FailedExport {
+        path: path.to_string_lossy().to_string(),
+        error,
+    }
+}
+
+/// The manifest as CSV, with a byte order mark so spreadsheet applications read it as
+/// UTF-8. Fields a spreadsheet would run as formulas get a leading `'`.
+fn manifest_csv(rows: &[ManifestRow]) -> String {
+    let field = export::csv_field;
+    let mut csv = String::from(
+        "\u{feff}file,message,sender,date,subject,attachment_index,attachment_name,sha256\r\n",
+    );
+    for row in rows {
+        let fields = [
+            field(&row.file),
+            field(&row.message),
+            field(&row.sender),
+            field(&row.date),
+            field(&row.subject),
+            row.index.to_string(),
+            field(&row.file_name),
+            row.sha256.clone(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Inline images are the ones the body shows (or Outlook hides), not attachments proper
+fn is_inline(attachment: &Attachment) -> bool {
+    attachment.hidden || attachment.referenced_in_body
+}
+
+/// Message files directly in `dir`, by path
+pub(crate) fn message_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && MessageFormat::from_path(path).is_some())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Group the attachments of the message files in `dir` by content. Inline images are left
+/// out unless `include_inline`; attachments of attached messages aren't looked into.
+///
+/// `before_each(current, total)` runs before every file; an error from it (a cancelled job)
+/// stops the report.
+pub fn dedupe_report(
+    dir: &Path,
+    include_inline: bool,
+    cache: &DigestCache,
+    load: impl Fn(&Path) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<DedupeReport, String> {
+    let paths = message_files(dir)?;
+    let mut messages = Vec::new();
+    let mut failed = Vec::new();
+    for (current, path) in paths.iter().enumerate() {
+        before_each(current, paths.len())?;
+        match cache.get(path, &load) {
+            Ok(digests) => messages.push((path.clone(), digests)),
+            Err(error) => {
+                log_line!(
+                    "Skipping {} in attachment report: {}",
+                    path.display(),
+                    error
+                );
+                failed.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    let mut report = group(&messages, include_inline);
+    report.failed = failed;
+    Ok(report)
+}
+
+fn group(messages: &[(PathBuf, Arc<Vec<Digest>>)], include_inline: bool) -> DedupeReport {
+    let mut by_hash: BTreeMap<&str, DuplicateGroup> = BTreeMap::new();
+    let mut empty = Vec::new();
+    for (path, digests) in messages {
+        for digest in digests.iter().filter(|d| include_inline || !d.inline) {
+            let source = AttachmentSource {
+                path: path.clone(),
+                index: digest.index,
+                file_name: digest.file_name.clone(),
+            };
+            if digest.size == 0 {
+                empty.push(source);
+                continue;
+            }
+            by_hash
+                .entry(&digest.sha256)
+                .or_insert_with(|| DuplicateGroup {
+                    sha256: digest.sha256.clone(),
+                    size: digest.size,
+                    sources: Vec::new(),
+                })
+                .sources
+                .push(source);
+        }
+    }
+
+    let unique = by_hash.len();
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_values()
+        .filter(|group| group.sources.len() > 1)
+        .collect();
+    let waste = |group: &DuplicateGroup| group.size as u64 * (group.sources.len() as u64 - 1);
+    groups.sort_by(|a, b| waste(b).cmp(&waste(a)).then(a.sha256.cmp(&b.sha256)));
+    DedupeReport {
+        redundant_bytes: groups.iter().map(waste).sum(),
+        groups,
+        empty,
+        unique,
+        failed: Vec::new(),
+    }
+}
+
+/// Save the attachments of `paths` into the folder `target`, under their suggested names
+/// (numbered when taken). Inline images are left out unless `include_inline`.
+///
+/// With `unique`, each content is written once, under the name it was first met with, and
+/// `manifest.json` maps every message's attachments to the files holding them. Messages that
+/// fail to load are skipped and listed. `before_each(current, total)` runs before every
+/// message; an error from it (a cancelled job) stops the export, keeping what was written.
+pub fn export_attachments(
+    paths: &[String],
+    target: &Path,
+    unique: bool,
+    include_inline: bool,
+    load: impl Fn(&str) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<AttachmentExport, String> {
+    std::fs::create_dir_all(target)
+        .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+
+    let mut export = AttachmentExport::default();
+    let mut files_by_hash: HashMap<String, String> = HashMap::new();
+    let mut manifest = Manifest {
+        messages: Vec::new(),
+    };
+    for (current, path) in paths.iter().enumerate() {
+        before_each(current, paths.len())?;
+        let message = match load(path) {
+            Ok(message) => message,
+            Err(error) => {
+                log_line!("Skipping {} in attachment export: {}", path, error);
+                export.failed.push(path.clone());
+                continue;
+            }
+        };
+
+        let mut entries = Vec::new();
+        for attachment in message
+            .attachments
+            .iter()
+            .filter(|a| include_inline || !is_inline(a))
+        {
+            let file = match files_by_hash.get(&attachment.sha256) {
+                Some(file) if unique => {
+                    export.skipped_duplicates += 1;
+                    file.clone()
+                }
+                _ => {
+                    let name = filenames::sanitize_filename(
+                        &attachment.suggested_filename,
+                        TargetOs::CURRENT,
+                    );
+                    let output = filenames::dedupe_in_dir(target, &name);
+                    let saved = save::write_verified(
+                        &output,
+                        &attachment.data,
+                        &attachment.sha256,
+                        true,
+                        |_, _| Ok(()),
+                    )?;
+                    export.written.push(saved);
+                    let file = output
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string();
+                    files_by_hash.insert(attachment.sha256.clone(), file.clone());
+                    file
+                }
+            };
+            entries.push(ManifestEntry {
+                index: attachment.index,
+                file_name: attachment.file_name.clone(),
+                sha256: attachment.sha256.clone(),
+                file,
+            });
+        }
+        manifest.messages.push(ManifestMessage {
+            path: PathBuf::from(path),
+            attachments: entries,
+        });
+    }
+
+    if unique {
+        let output = filenames::dedupe_in_dir(target, "manifest.json");
+        let json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| format!("Failed to encode manifest: {}", e))?;
+        save::write_atomic(&output, &json)?;
+        export.manifest = Some(output.to_string_lossy().to_string());
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(index: usize, sha256: &str, size: usize, inline: bool) -> Digest {
+        Digest {
+            index,
+            file_name: format!("file-{}", index),
+            size,
+            sha256: sha256.to_string(),
+            inline,
+        }
+    }
+
+    #[test]
+    fn identical_attachments_are_grouped() {
+        let messages = vec![
+            (
+                PathBuf::from("a.eml"),
+                Arc::new(vec![
+                    digest(0, "deck", 8_000, false),
+                    digest(1, "logo", 2_000, true),
+                    digest(2, "empty", 0, false),
+                ]),
+            ),
+            (
+                PathBuf::from("b.eml"),
+                Arc::new(vec![
+                    digest(0, "deck", 8_000, false),
+                    digest(1, "logo", 2_000, true),
+                    digest(2, "notes", 100, false),
+                ]),
+            ),
+            (
+                PathBuf::from("c.msg"),
+                Arc::new(vec![
+                    digest(0, "notes", 100, false),
+                    digest(1, "deck", 8_000, false),
+                ]),
+            ),
+        ];
+
+        let report = group(&messages, false);
+        assert_eq!(report.unique, 2);
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].sha256, "deck");
+        assert_eq!(report.groups[0].sources.len(), 3);
+        assert_eq!(report.groups[0].sources[2].path, PathBuf::from("c.msg"));
+        assert_eq!(report.redundant_bytes, 2 * 8_000 + 100);
+        assert_eq!(report.empty.len(), 1);
+
+        let report = group(&messages, true);
+        assert_eq!(report.unique, 3);
+        assert_eq!(report.redundant_bytes, 2 * 8_000 + 2_000 + 100);
+    }
+
+    #[test]
+    fn attachment_filters_match_names_types_and_sizes() {
+        assert!(glob_matches("*invoice*.pdf", "2026-10 Invoice 417.PDF"));
+        assert!(glob_matches("scan-??.jpg", "scan-07.jpg"));
+        assert!(!glob_matches("scan-??.jpg", "scan-7.jpg"));
+        assert!(!glob_matches("*.pdf", "invoice.pdf.exe"));
+
+        let mut attachment = Attachment::new(
+            0,
+            Some("invoice.pdf".to_string()),
+            Some("application/pdf".to_string()),
+            None,
+            b"%PDF-1.7".to_vec(),
+        );
+        let filter = AttachmentFilter {
+            name: Some("INVOICE*".to_string()),
+            mime_type: Some("application/*".to_string()),
+            max_size: Some(100),
+            ..AttachmentFilter::default()
+        };
+        assert!(filter.attachment(&attachment));
+        let too_small = AttachmentFilter {
+            min_size: Some(100),
+            ..filter.clone()
+        };
+        assert!(!too_small.attachment(&attachment));
+        attachment.referenced_in_body = true;
+        assert!(!filter.attachment(&attachment));
+
+        let csv = manifest_csv(&[ManifestRow {
+            file: "a/invoice.pdf".to_string(),
+            message: "a.eml".to_string(),
+            sender: "=cmd|' /C calc'!A0".to_string(),
+            date: String::new(),
+            subject: "Invoice, \"final\"".to_string(),
+            index: 0,
+            file_name: "invoice.pdf".to_string(),
+            sha256: "ab".to_string(),
+        }]);
+        assert!(csv.ends_with(
+            "a/invoice.pdf,a.eml,'=cmd|' /C calc'!A0,,\"Invoice, \"\"final\"\"\",0,invoice.pdf,ab\r\n"
+        ));
+    }
+}
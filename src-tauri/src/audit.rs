@@ -0,0 +1,293 @@
+//! Opt-in record (the `audit_log` setting) of attachments saved, exported or opened in
+//! other applications: one JSON object per line in `audit.jsonl`, appended and never
+//! rewritten. A file that grows past `MAX_SEGMENT_BYTES` is renamed with its start time
+//! and kept; the retention policy doesn't touch the log.
+
+use crate::save;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The current file is rotated once it would grow beyond this
+const MAX_SEGMENT_BYTES: u64 = 5 * 1024 * 1024;
+
+const CURRENT_FILE: &str = "audit.jsonl";
+const SEGMENT_PREFIX: &str = "audit-";
+
+/// Entries returned by `get_audit_log` unless the filter asks for fewer
+const DEFAULT_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Written to a temp file and handed to another application
+    OpenedExternally,
+    /// Saved through a "Save As" dialog
+    Saved,
+    /// Written into a folder by a bulk export
+    Exported,
+    /// Written into a folder by an auto-extract rule when the message was opened
+    AutoExtracted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub action: AuditAction,
+    /// Content hash (see `get_content_hash`) of the message the attachment came from;
+    /// `None` for content the webview passed in
+    pub message_hash: Option<String>,
+    pub message_path: Option<String>,
+    pub attachment_name: String,
+    /// SHA-256 of the attachment content (hex)
+    pub attachment_hash: String,
+    /// Where the content was written
+    pub destination: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        action: AuditAction,
+        message_path: Option<&Path>,
+        attachment_name: &str,
+        attachment_hash: &str,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            action,
+            message_hash: message_path.and_then(|path| crate::notes::content_hash(path).ok()),
+            message_path: message_path.map(|path| path.display().to_string()),
+            attachment_name: attachment_name.to_string(),
+            attachment_hash: attachment_hash.to_string(),
+            destination: None,
+        }
+    }
+
+    pub fn to(mut self, destination: &Path) -> Self {
+        self.destination = Some(destination.display().to_string());
+        self
+    }
+}
+
+/// Which entries `get_audit_log` returns; all fields are optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub message_hash: Option<String>,
+    pub attachment_hash: Option<String>,
+    /// Case-insensitive part of the attachment name
+    pub attachment_name: Option<String>,
+    /// RFC 3339 timestamps, both ends included
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// The newest this many entries (1000 by default)
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&entry.timestamp).ok();
+        let bound = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|value| chrono::DateTime::parse_from_rfc3339(value.trim()).ok())
+        };
+        let name = self
+            .attachment_name
+            .as_deref()
+            .map(|name| name.trim().to_lowercase());
+        self.action.is_none_or(|action| action == entry.action)
+            && self
+                .message_hash
+                .as_ref()
+                .is_none_or(|hash| entry.message_hash.as_ref() == Some(hash))
+            && self
+                .attachment_hash
+                .as_ref()
+                .is_none_or(|hash| *hash == entry.attachment_hash)
+            && name.is_none_or(|name| entry.attachment_name.to_lowercase().contains(&name))
+            && bound(&self.since).is_none_or(|since| timestamp.is_some_and(|t| t >= since))
+            && bound(&self.until).is_none_or(|until| timestamp.is_some_and(|t| t <= until))
+    }
+}
+
+/// The audit log directory; entries are only written while the setting is on
+pub struct AuditLog {
+    dir: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            lock: Mutex::default(),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            dir: None,
+            lock: Mutex::default(),
+        }
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Append an entry, rotating the current file first when it is full
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let Some(dir) = &self.dir else {
+            return Err("The audit log has no folder".to_string());
+        };
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().unwrap();
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create directory {}: {}", dir.display(), e))?;
+        let current = dir.join(CURRENT_FILE);
+        let size = std::fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > MAX_SEGMENT_BYTES {
+            rotate(dir, &current)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)
+            .map_err(|e| format!("Failed to open {}: {}", current.display(), e))?;
+        file.write_all(&line)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("Failed to write {}: {}", current.display(), e))
+    }
+
+    /// Entries matching `filter`, oldest first; lines that don't parse are skipped
+    pub fn entries(&self, filter: &AuditFilter) -> Vec<AuditEntry> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries: Vec<AuditEntry> = self
+            .segments()
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .flat_map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect::<Vec<AuditEntry>>()
+            })
+            .filter(|entry| filter.matches(entry))
+            .collect();
+        let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+        if entries.len() > limit {
+            entries.drain(..entries.len() - limit);
+        }
+        entries
+    }
+
+    /// Copy every segment, oldest first, into one JSONL file at `target`
+    pub fn export(&self, target: &Path) -> Result<usize, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut data = Vec::new();
+        for path in self.segments() {
+            let segment = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            data.extend_from_slice(&segment);
+            if !data.is_empty() && !data.ends_with(b"\n") {
+                data.push(b'\n');
+            }
+        }
+        let lines = data.iter().filter(|&&b| b == b'\n').count();
+        save::write_atomic(target, &data)?;
+        Ok(lines)
+    }
+
+    /// Rotated segments by age (their names sort by start time), then the current file
+    fn segments(&self) -> Vec<PathBuf> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        let mut segments: Vec<PathBuf> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(SEGMENT_PREFIX) && name.ends_with(".jsonl")
+                    })
+            })
+            .collect();
+        segments.sort();
+        let current = dir.join(CURRENT_FILE);
+        if current.is_file() {
+            segments.push(current);
+        }
+        segments
+    }
+}
+
+/// Rename the full current file after the time of its first entry
+fn rotate(dir: &Path, current: &Path) -> Result<(), String> {
+    let started = std::fs::read_to_string(current)
+        .ok()
+        .and_then(|text| {
+            let first = text.lines().next()?.to_string();
+            serde_json::from_str::<AuditEntry>(&first).ok()
+        })
+        .and_then(|entry| chrono::DateTime::parse_from_rfc3339(&entry.timestamp).ok())
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .unwrap_or_else(chrono::Utc::now);
+    let stamp = started.format("%Y%m%dT%H%M%S%.3fZ");
+    let mut target = dir.join(format!("{}{}.jsonl", SEGMENT_PREFIX, stamp));
+    let mut n = 2;
+    while target.exists() {
+        target = dir.join(format!("{}{}-{}.jsonl", SEGMENT_PREFIX, stamp, n));
+        n += 1;
+    }
+    std::fs::rename(current, &target)
+        .map_err(|e| format!("Failed to rotate {}: {}", current.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_appended_rotated_and_filtered() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let log = AuditLog::new(dir.to_path_buf());
+        let long_name = "x".repeat(MAX_SEGMENT_BYTES as usize);
+        let saved = AuditEntry::new(AuditAction::Saved, None, &long_name, "ab12");
+        let opened = AuditEntry::new(AuditAction::OpenedExternally, None, "report.pdf", "cd34")
+            .to(Path::new("/tmp/report.pdf"));
+        log.append(&saved).unwrap();
+
+        // The full segment is renamed and kept
+        log.append(&opened).unwrap();
+        assert_eq!(log.segments().len(), 2);
+        let current = dir.join(CURRENT_FILE);
+        assert_eq!(
+            std::fs::read_to_string(&current).unwrap().lines().count(),
+            1
+        );
+
+        assert_eq!(
+            log.entries(&AuditFilter::default()),
+            [saved, opened.clone()]
+        );
+        let filter = AuditFilter {
+            action: Some(AuditAction::OpenedExternally),
+            attachment_name: Some("REPORT".to_string()),
+            ..AuditFilter::default()
+        };
+        assert_eq!(log.entries(&filter), [opened]);
+
+        let exported = dir.join("export.jsonl");
+        assert_eq!(log.export(&exported).unwrap(), 2);
+    }
+}
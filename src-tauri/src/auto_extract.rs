@@ -0,0 +1,354 @@
+//! Rules from settings that save attachments into a folder as soon as a message is opened
+//! (`parse_message`). There are none by default and each rule starts switched off; files
+//! are never overwritten, a taken name gets a number instead.
+
+use crate::attachments::AttachmentFilter;
+use crate::filenames::{self, TargetOs};
+use crate::parser::{Attachment, Message};
+use crate::{save, sender_rules};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Placeholders a rule's target may use
+const PLACEHOLDERS: &[&str] = &["sender", "domain", "year", "month", "day", "subject"];
+
+/// Stands in for placeholders without a value (a message without date or subject)
+const UNKNOWN: &str = "unknown";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExtractRule {
+    pub name: String,
+    pub enabled: bool,
+    /// Sender address (`anna@example.com`) or domain (`example.com`, `*@example.com`); any
+    /// sender when unset. Only senders that authentication doesn't contradict match.
+    pub sender: Option<String>,
+    /// Case-insensitive glob on the attachment's file name (`*.pdf`)
+    pub attachment_name: Option<String>,
+    /// MIME type (detected, else declared), or a family like `image/*`
+    pub mime_type: Option<String>,
+    pub include_inline: bool,
+    /// Absolute folder to extract to, with `{sender}`, `{domain}`, `{year}`, `{month}`,
+    /// `{day}` (of the message date) and `{subject}` filled in as single folder names
+    pub target: String,
+}
+
+impl AutoExtractRule {
+    fn filter(&self) -> AttachmentFilter {
+        AttachmentFilter {
+            name: self.attachment_name.clone(),
+            mime_type: self.mime_type.clone(),
+            include_inline: self.include_inline,
+            ..AttachmentFilter::default()
+        }
+    }
+
+    /// The part of `target` before the first placeholder: the folder that has to be
+    /// accessible, everything below it is created as needed
+    pub fn base_dir(&self) -> Result<PathBuf, String> {
+        let target = self.target.trim();
+        let fixed = match target.find('{') {
+            Some(pos) => Path::new(&target[..pos])
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+            None => PathBuf::from(target),
+        };
+        match fixed.is_absolute() {
+            true => Ok(fixed),
+            false => Err(format!(
+                "Rule {:?}: the target must be an absolute folder: {:?}",
+                self.name, self.target
+            )),
+        }
+    }
+
+    fn matches_sender(&self, message: &Message) -> Result<bool, String> {
+        let Some(sender) = self.sender.as_deref().filter(|s| !s.trim().is_empty()) else {
+            return Ok(true);
+        };
+        let pattern = sender_rules::normalize_sender(sender)?;
+        Ok(
+            sender_rules::sender_address(message).is_some_and(|(address, verified)| {
+                verified && sender_rules::matches_address(&pattern, &address)
+            }),
+        )
+    }
+
+    /// `target` with the placeholders filled in for `message`
+    fn expand(&self, message: &Message) -> Result<PathBuf, String> {
+        let date = message
+            .date
+            .as_deref()
+            .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok());
+        let address = sender_rules::sender_address(message).map(|(address, _)| address);
+        let value = |name: &str| -> Option<String> {
+            match name {
+                "sender" => address.clone(),
+                "domain" => address
+                    .as_deref()
+                    .and_then(|a| a.rsplit_once('@'))
+                    .map(|(_, domain)| domain.to_string()),
+                "year" => date.map(|d| d.format("%Y").to_string()),
+                "month" => date.map(|d| d.format("%m").to_string()),
+                "day" => date.map(|d| d.format("%d").to_string()),
+                "subject" => message.subject.clone(),
+                _ => None,
+            }
+        };
+
+        let mut out = String::new();
+        let mut rest = self.target.trim();
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Rule {:?}: unclosed placeholder", self.name))?;
+            let name = &rest[start + 1..end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "Rule {:?}: unknown placeholder {{{}}}",
+                    self.name, name
+                ));
+            }
+            // One folder name, whatever the sender put in the subject
+            let filled = value(name)
+                .map(|v| filenames::sanitize_filename(v.trim(), TargetOs::CURRENT))
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| UNKNOWN.to_string());
+            out.push_str(&filled);
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(PathBuf::from(out))
+    }
+}
+
+/// An attachment a rule saves (or would save, for a dry run)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Extraction {
+    pub rule: String,
+    pub index: usize,
+    pub file_name: String,
+    pub path: String,
+    /// The name was taken, so a number was added
+    pub renamed: bool,
+    /// SHA-256 of the attachment (hex)
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExtractionFailure {
+    pub rule: String,
+    pub file_name: Option<String>,
+    pub error: String,
+}
+
+/// Payload of the `auto-extracted` event, and the result of a dry run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AutoExtraction {
+    pub message_path: String,
+    pub extracted: Vec<Extraction>,
+    pub failed: Vec<ExtractionFailure>,
+    /// Files already holding a matching attachment, which isn't written again
+    pub present: Vec<String>,
+    pub dry_run: bool,
+}
+
+impl AutoExtraction {
+    pub fn is_empty(&self) -> bool {
+        self.extracted.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// Apply `rules` to `message`: every rule whose sender matches saves its matching
+/// attachments, in rule order. `dry_run` only reports the paths that would be written.
+/// `permitted(base_dir)` is the access check for the folder a rule writes below, returning
+/// it resolved. A rule that fails is listed and the others still run.
+pub fn run(
+    rules: &[&AutoExtractRule],
+    message_path: &Path,
+    message: &Message,
+    dry_run: bool,
+    permitted: impl Fn(&Path) -> Result<PathBuf, String>,
+) -> AutoExtraction {
+    let mut result = AutoExtraction {
+        message_path: message_path.to_string_lossy().to_string(),
+        dry_run,
+        ..AutoExtraction::default()
+    };
+    let mut planned: HashSet<PathBuf> = HashSet::new();
+    for rule in rules {
+        let fail = |file_name: Option<&Attachment>, error: String| ExtractionFailure {
+            rule: rule.name.clone(),
+            file_name: file_name.map(|a| a.file_name.clone()),
+            error,
+        };
+        let prepared = rule
+            .matches_sender(message)
+            .and_then(|matches| match matches {
+                false => Ok(None),
+                true => {
+                    let base = rule.base_dir()?;
+                    let expanded = rule.expand(message)?;
+                    // Placeholders fill in single folder names, so the rest stays below `base`
+                    let below = expanded.strip_prefix(&base).unwrap_or(Path::new(""));
+                    Ok(Some(permitted(&base)?.join(below)))
+                }
+            });
+        let dir = match prepared {
+            Ok(Some(dir)) => dir,
+            Ok(None) => continue,
+            Err(error) => {
+                result.failed.push(fail(None, error));
+                continue;
+            }
+        };
+
+        let filter = rule.filter();
+        for attachment in message.attachments.iter().filter(|a| filter.attachment(a)) {
+            // Opening the message again doesn't extract it again
+            if let Some(existing) = identical_file(&dir, attachment) {
+                result.present.push(existing.to_string_lossy().to_string());
+                continue;
+            }
+            let name =
+                filenames::sanitize_filename(&attachment.suggested_filename, TargetOs::CURRENT);
+            let written = match dry_run {
+                true => Ok(filenames::dedupe_with(&dir, &name, |path| {
+                    planned.contains(path)
+                })),
+                false => std::fs::create_dir_all(&dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))
+                    .and_then(|()| {
+                        let output = filenames::dedupe_in_dir(&dir, &name);
+                        save::write_verified(
+                            &output,
+                            &attachment.data,
+                            &attachment.sha256,
+                            true,
+                            |_, _| Ok(()),
+                        )
+                        .map(|_| output)
+                    }),
+            };
+            match written {
+                Ok(output) => {
+                    planned.insert(output.clone());
+                    result.extracted.push(Extraction {
+                        rule: rule.name.clone(),
+                        index: attachment.index,
+                        file_name: attachment.file_name.clone(),
+                        renamed: output.file_name() != Some(name.as_ref()),
+                        path: output.to_string_lossy().to_string(),
+                        sha256: attachment.sha256.clone(),
+                    })
+                }
+                Err(error) => result.failed.push(fail(Some(attachment), error)),
+            }
+        }
+    }
+    result
+}
+
+/// A file in `dir` with the content of `attachment`
+fn identical_file(dir: &Path, attachment: &Attachment) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.len() == attachment.size as u64)
+        })
+        .map(|entry| entry.path())
+        .find(|path| {
+            save::file_sha256(path).is_ok_and(|sha| sha.eq_ignore_ascii_case(&attachment.sha256))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Address, MessageFormat};
+
+    #[test]
+    fn rules_extract_into_templated_folders_without_overwriting() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut message = Message::new(MessageFormat::Eml);
+        message.from = Some(Address::new(
+            None,
+            Some("billing@Vendor.example".to_string()),
+        ));
+        message.date = Some("2024-03-05T09:07:00+01:00".to_string());
+        message.subject = Some("Invoice ../../March".to_string());
+        for (index, name) in ["invoice.pdf", "logo.png", "invoice.pdf"]
+            .iter()
+            .enumerate()
+        {
+            message.attachments.push(Attachment::new(
+                index,
+                Some(name.to_string()),
+                None,
+                None,
+                format!("content {}", index).into_bytes(),
+            ));
+        }
+        let rule = AutoExtractRule {
+            name: "Invoices".to_string(),
+            enabled: true,
+            sender: Some("vendor.example".to_string()),
+            attachment_name: Some("*.PDF".to_string()),
+            target: format!(
+                "{}/{{domain}}/{{year}}-{{month}}/{{subject}}",
+                dir.display()
+            ),
+            ..AutoExtractRule::default()
+        };
+        let permitted = |base: &Path| Ok(base.to_path_buf());
+        let expected =
+            dir.join("vendor.example")
+                .join("2024-03")
+                .join(filenames::sanitize_filename(
+                    "Invoice ../../March",
+                    TargetOs::CURRENT,
+                ));
+
+        let preview = run(&[&rule], Path::new("m.eml"), &message, true, permitted);
+        assert!(preview.failed.is_empty(), "{:?}", preview.failed);
+        assert_eq!(preview.extracted.len(), 2);
+        assert_eq!(
+            Path::new(&preview.extracted[0].path),
+            expected.join("invoice.pdf")
+        );
+        assert!(preview.extracted[1].renamed);
+        assert!(!expected.exists());
+
+        std::fs::create_dir_all(&expected).unwrap();
+        std::fs::write(expected.join("invoice.pdf"), b"kept").unwrap();
+        let done = run(&[&rule], Path::new("m.eml"), &message, false, permitted);
+        assert_eq!(done.extracted.len(), 2);
+        assert!(done.extracted.iter().all(|e| e.renamed));
+        assert_eq!(
+            std::fs::read(expected.join("invoice.pdf")).unwrap(),
+            b"kept"
+        );
+        assert_eq!(
+            std::fs::read(&done.extracted[1].path).unwrap(),
+            b"content 2"
+        );
+        let again = run(&[&rule], Path::new("m.eml"), &message, false, permitted);
+        assert!(again.extracted.is_empty());
+        assert_eq!(again.present.len(), 2);
+
+        // Other senders and spoofed ones don't match
+        message.headers.push((
+            "Authentication-Results".to_string(),
+            "mx.example.net; dmarc=pass header.from=attacker.example".to_string(),
+        ));
+        assert!(run(&[&rule], Path::new("m.eml"), &message, true, permitted).is_empty());
+    }
+}
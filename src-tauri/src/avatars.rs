@@ -0,0 +1,353 @@
+use crate::bimi;
+use crate::parser::{sha256_hex, sniff, Attachment, Message};
+use crate::sender_rules;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Largest contact photo shown
+const MAX_PHOTO_BYTES: usize = 1024 * 1024;
+
+/// Senders whose avatars are kept; the cache is emptied when it grows past this
+const MAX_CACHED_SENDERS: usize = 10_000;
+
+/// Photo types the webview shows as avatars
+const PHOTO_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// What to show for a sender
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Avatar {
+    /// The photo of an attached vCard for the sender
+    Photo { data_url: String },
+    /// The sender's BIMI logo, checked against the SVG Tiny PS profile
+    Bimi { data_url: String },
+    /// Placeholder: up to two letters on a color derived from the address
+    Initials { initials: String, color: String },
+}
+
+struct CacheEntry {
+    avatar: Avatar,
+    /// Whether remote content was allowed when it was looked up; initials found without it
+    /// are looked up again once it is
+    remote_allowed: bool,
+}
+
+/// Avatars by lowercase sender address
+#[derive(Default)]
+pub struct AvatarCache(Mutex<HashMap<String, CacheEntry>>);
+
+impl AvatarCache {
+    pub fn get(&self, address: &str, remote_allowed: bool) -> Option<Avatar> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&address.to_lowercase())
+            .filter(|entry| {
+                entry.remote_allowed
+                    || !remote_allowed
+                    || !matches!(entry.avatar, Avatar::Initials { .. })
+            })
+            .map(|entry| entry.avatar.clone())
+    }
+
+    pub fn insert(&self, address: &str, avatar: Avatar, remote_allowed: bool) {
+        let mut cache = self.0.lock().unwrap();
+        if cache.len() >= MAX_CACHED_SENDERS {
+            cache.clear();
+        }
+        cache.insert(
+            address.to_lowercase(),
+            CacheEntry {
+                avatar,
+                remote_allowed,
+            },
+        );
+    }
+}
+
+/// Result of `lookup`: an avatar, or the URL of a BIMI logo that has to be fetched first
+pub enum Lookup {
+    Found(Avatar),
+    FetchLogo(String),
+}
+
+/// The avatar `message` itself provides: a photo of an attached vCard for the sender, then
+/// an embedded BIMI logo. Logos only count for messages whose sender domain passed DMARC
+/// (or DKIM/SPF, see `sender_rules::authenticated_domain`), so a spoofed `From` can't borrow
+/// a brand; a remote logo is left for the caller to fetch.
+pub fn lookup(message: &Message) -> Lookup {
+    let email = sender_email(message);
+    if let Some(photo) = email
+        .as_deref()
+        .and_then(|email| contact_photo(message, email))
+    {
+        return Lookup::Found(photo);
+    }
+    let logo = authenticated(message)
+        .then(|| bimi::indicator(&message.headers))
+        .flatten();
+    match logo {
+        Some(bimi::Indicator::Embedded(svg)) => match bimi::logo_data_url(&svg) {
+            Ok(data_url) => return Lookup::Found(Avatar::Bimi { data_url }),
+            Err(e) => log_line!("Ignoring BIMI logo: {}", e),
+        },
+        Some(bimi::Indicator::Location(url)) => return Lookup::FetchLogo(url),
+        None => {}
+    }
+    Lookup::Found(initials(message))
+}
+
+pub fn sender_email(message: &Message) -> Option<String> {
+    message
+        .from
+        .as_ref()
+        .and_then(|from| from.email.as_deref())
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty())
+}
+
+/// The authenticated domain is the sender's own
+fn authenticated(message: &Message) -> bool {
+    let Some(domain) = sender_email(message).and_then(|email| {
+        let (_, domain) = email.rsplit_once('@')?;
+        crate::parser::idn::host_info(domain).map(|host| host.ascii_host.to_lowercase())
+    }) else {
+        return false;
+    };
+    message
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("Authentication-Results"))
+        .filter_map(|(_, value)| sender_rules::authenticated_domain(value))
+        .any(|authenticated| authenticated == domain)
+}
+
+/// Initials from the display name (first and last word), else the address, with a color
+/// from its hash so a sender always gets the same one
+pub fn initials(message: &Message) -> Avatar {
+    let name = message.from.as_ref().and_then(|from| from.name.as_deref());
+    let email = sender_email(message).unwrap_or_default();
+    let letters = |words: Vec<&str>| -> String {
+        let first = |word: &&str| word.chars().find(|c| c.is_alphanumeric());
+        match (words.first().and_then(first), words.last().and_then(first)) {
+            (Some(a), Some(b)) if words.len() > 1 => format!("{}{}", a, b),
+            (Some(a), _) => a.to_string(),
+            _ => String::new(),
+        }
+    };
+    let from_name = name
+        .map(|name| letters(name.split_whitespace().collect()))
+        .unwrap_or_default();
+    let local = email.split('@').next().unwrap_or_default();
+    let initials = match from_name.is_empty() {
+        false => from_name,
+        true => letters(
+            local
+                .split(['.', '_', '-', '+'])
+                .filter(|w| !w.is_empty())
+                .collect(),
+        ),
+    };
+    let initials = match initials.is_empty() {
+        true => "?".to_string(),
+        false => initials.to_uppercase(),
+    };
+    let hash = sha256_hex(email.as_bytes());
+    let hue = u32::from_str_radix(&hash[..4], 16).unwrap_or(0) % 360;
+    Avatar::Initials {
+        initials,
+        color: format!("hsl({}, 45%, 42%)", hue),
+    }
+}
+
+/// Photo of an attached vCard listing `email`
+fn contact_photo(message: &Message, email: &str) -> Option<Avatar> {
+    message
+        .attachments
+        .iter()
+        .filter(|attachment| is_vcard(attachment))
+        .filter_map(|attachment| std::str::from_utf8(&attachment.data).ok())
+        .flat_map(vcards)
+        .find(|card| card.emails.iter().any(|e| e.eq_ignore_ascii_case(email)))
+        .and_then(|card| card.photo)
+        .map(|data_url| Avatar::Photo { data_url })
+}
+
+fn is_vcard(attachment: &Attachment) -> bool {
+    let mime = attachment
+        .detected_mime
+        .as_deref()
+        .or(attachment.declared_mime.as_deref())
+        .unwrap_or_default();
+    matches!(mime, "text/vcard" | "text/x-vcard" | "text/directory")
+        || attachment.file_name.to_lowercase().ends_with(".vcf")
+}
+
+/// What an avatar needs from a vCard
+struct Card {
+    emails: Vec<String>,
+    photo: Option<String>,
+}
+
+/// The cards of a vCard file (3.0 and 4.0), with folded lines joined
+fn vcards(text: &str) -> Vec<Card> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut cards = Vec::new();
+    let mut current: Option<Card> = None;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = name.split(';');
+        // Properties may be grouped (`item1.EMAIL`)
+        let property = params
+            .next()
+            .unwrap_or_default()
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        let params: Vec<String> = params.map(str::to_ascii_uppercase).collect();
+        match property.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(Card {
+                    emails: Vec::new(),
+                    photo: None,
+                })
+            }
+            "END" if value.eq_ignore_ascii_case("VCARD") => cards.extend(current.take()),
+            "EMAIL" => {
+                if let Some(card) = &mut current {
+                    card.emails.push(value.trim().to_string());
+                }
+            }
+            "PHOTO" => {
+                if let Some(card) = current.as_mut().filter(|card| card.photo.is_none()) {
+                    card.photo = photo(value, &params);
+                }
+            }
+            _ => {}
+        }
+    }
+    cards
+}
+
+/// A `PHOTO` value as a `data:` URL: inline base64 (`ENCODING=b` in vCard 3.0, a `data:`
+/// URI in 4.0); photos at remote URLs aren't fetched
+fn photo(value: &str, params: &[String]) -> Option<String> {
+    let encoded = match value.trim().strip_prefix("data:") {
+        Some(uri) => uri.split_once(";base64,")?.1,
+        None if params
+            .iter()
+            .any(|p| p == "ENCODING=B" || p == "ENCODING=BASE64") =>
+        {
+            value.trim()
+        }
+        None => return None,
+    };
+    let data = STANDARD
+        .decode(encoded.split_whitespace().collect::<String>())
+        .ok()?;
+    if data.len() > MAX_PHOTO_BYTES {
+        return None;
+    }
+    // The declared type isn't trusted; only images the webview shows are
+    let mime = sniff::sniff("photo", None, &data).detected_mime?;
+    PHOTO_TYPES
+        .contains(&mime)
+        .then(|| format!("data:{};base64,{}", mime, STANDARD.encode(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Address, MessageFormat};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+
+    fn message(name: Option<&str>, email: &str) -> Message {
+        let mut message = Message::new(MessageFormat::Eml);
+        message.from = Some(Address::new(
+            name.map(str::to_string),
+            Some(email.to_string()),
+        ));
+        message
+    }
+
+    #[test]
+    fn attached_contact_photos_win_over_initials() {
+        let mut with_card = message(Some("Anna Berg"), "Anna.Berg@example.com");
+        let card = format!(
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Anna Berg\r\nitem1.EMAIL;TYPE=work:anna.berg@example.com\r\n\
+             PHOTO;ENCODING=b;TYPE=PNG:{}\r\n {}\r\nEND:VCARD\r\n",
+            &STANDARD.encode(PNG)[..8],
+            &STANDARD.encode(PNG)[8..]
+        );
+        with_card.attachments.push(Attachment::new(
+            0,
+            Some("Anna Berg.vcf".to_string()),
+            Some("text/vcard".to_string()),
+            None,
+            card.into_bytes(),
+        ));
+        match lookup(&with_card) {
+            Lookup::Found(Avatar::Photo { data_url }) => {
+                assert!(data_url.starts_with("data:image/png;base64,"))
+            }
+            _ => panic!("expected the contact photo"),
+        }
+
+        let plain = message(Some("Anna Berg"), "anna.berg@example.com");
+        let Lookup::Found(Avatar::Initials { initials, color }) = lookup(&plain) else {
+            panic!("expected initials");
+        };
+        assert_eq!(initials, "AB");
+        assert_eq!(
+            Some(color),
+            match initials_of(None, "ANNA.BERG@example.com") {
+                Avatar::Initials { color, .. } => Some(color),
+                _ => None,
+            }
+        );
+        assert!(matches!(
+            initials_of(None, "j.doe@example.com"),
+            Avatar::Initials { initials, .. } if initials == "JD"
+        ));
+    }
+
+    fn initials_of(name: Option<&str>, email: &str) -> Avatar {
+        initials(&message(name, email))
+    }
+
+    #[test]
+    fn logos_need_an_authenticated_sender() {
+        let mut spoofed = message(Some("Bank"), "service@bank.example");
+        spoofed.headers = vec![
+            (
+                "Authentication-Results".to_string(),
+                "mx.example.net; dmarc=pass header.from=attacker.example".to_string(),
+            ),
+            (
+                "BIMI-Location".to_string(),
+                "v=BIMI1; l=https://bank.example/logo.svg".to_string(),
+            ),
+        ];
+        assert!(matches!(
+            lookup(&spoofed),
+            Lookup::Found(Avatar::Initials { .. })
+        ));
+
+        spoofed.headers[0].1 = "mx.example.net; dmarc=pass header.from=bank.example".to_string();
+        assert!(
+            matches!(lookup(&spoofed), Lookup::FetchLogo(url) if url == "https://bank.example/logo.svg")
+        );
+    }
+}
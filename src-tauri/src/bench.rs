@@ -0,0 +1,203 @@
+//! Deterministic fixtures and entry points for `benches/`; nothing here is used by the app
+
+use crate::parser::{self, MessageFormat, ParseOptions};
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+/// Pseudo-random but reproducible bytes, so attachments don't compress or repeat
+fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// Plain text of about `len` bytes in paragraphs
+fn prose(len: usize) -> String {
+    const SENTENCE: &str = "The quarterly figures are attached, see https://example.com/q3 \
+                            for the details. ";
+    let mut text = String::with_capacity(len + SENTENCE.len());
+    for sentence in 1.. {
+        if text.len() >= len {
+            break;
+        }
+        text.push_str(SENTENCE);
+        if sentence % 5 == 0 {
+            text.push_str("\r\n\r\n");
+        }
+    }
+    text
+}
+
+/// HTML body of about `len` bytes with links, images, tables and inline styles
+pub fn html_body(len: usize) -> String {
+    let mut html = String::from("<html><body>");
+    let mut i = 0;
+    while html.len() < len {
+        html.push_str(&format!(
+            "<p style=\"color:#333;font-family:Arial\">Paragraph {i} with \
+             <a href=\"https://example.com/{i}\">a link</a> &amp; an image \
+             <img src=\"cid:image{i}@x\" width=\"1\" height=\"1\"></p>\
+             <table><tr><td>{i}</td><td>&euro;{i}.00</td></tr></table>"
+        ));
+        i += 1;
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+/// A .msg file with a text and HTML body and attachments of the given sizes
+pub fn msg(body_len: usize, attachment_sizes: &[usize]) -> Vec<u8> {
+    let mut file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+    for (path, data) in [
+        ("/__properties_version1.0", vec![0; 32]),
+        ("/__substg1.0_0037001F", utf16("Quarterly figures")),
+        ("/__substg1.0_0C1A001F", utf16("Ann Example")),
+        ("/__substg1.0_5D01001F", utf16("ann@example.com")),
+        ("/__substg1.0_1000001F", utf16(&prose(body_len))),
+        ("/__substg1.0_10130102", html_body(body_len).into_bytes()),
+    ] {
+        file.create_stream(path).unwrap().write_all(&data).unwrap();
+    }
+
+    for (i, &size) in attachment_sizes.iter().enumerate() {
+        let storage = format!("/__attach_version1.0_#{:08X}", i);
+        file.create_storage(&storage).unwrap();
+        let mut props = vec![0; 8];
+        props.extend([0x03, 0x00, 0x05, 0x37, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        for (name, data) in [
+            ("__properties_version1.0", props),
+            ("__substg1.0_3707001F", utf16(&format!("report-{}.pdf", i))),
+            ("__substg1.0_37010102", noise(size, i as u64 + 1)),
+        ] {
+            let path = format!("{}/{}", storage, name);
+            file.create_stream(&path).unwrap().write_all(&data).unwrap();
+        }
+    }
+    file.flush().unwrap();
+    file.into_inner().into_inner()
+}
+
+/// A multipart .eml with a text and HTML alternative and base64 attachments
+pub fn eml(attachments: usize, attachment_size: usize) -> Vec<u8> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let mut eml = String::from(
+        "From: Ann Example <ann@example.com>\r\nTo: bob@example.com\r\n\
+         Subject: Quarterly figures\r\nDate: Tue, 5 Mar 2024 09:07:00 +0100\r\n\
+         MIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n\
+         --outer\r\nContent-Type: multipart/alternative; boundary=\"inner\"\r\n\r\n\
+         --inner\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+    );
+    eml.push_str(&prose(16 * 1024));
+    eml.push_str("\r\n--inner\r\nContent-Type: text/html; charset=utf-8\r\n\r\n");
+    eml.push_str(&html_body(32 * 1024));
+    eml.push_str("\r\n--inner--\r\n");
+    for i in 0..attachments {
+        let encoded = STANDARD.encode(noise(attachment_size, i as u64 + 1));
+        eml.push_str(&format!(
+            "--outer\r\nContent-Type: application/pdf; name=\"report-{i}.pdf\"\r\n\
+             Content-Disposition: attachment; filename=\"report-{i}.pdf\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n"
+        ));
+        for line in encoded.as_bytes().chunks(76) {
+            eml.push_str(std::str::from_utf8(line).unwrap());
+            eml.push_str("\r\n");
+        }
+    }
+    eml.push_str("--outer--\r\n");
+    eml.into_bytes()
+}
+
+/// Fill `dir` with `count` small .eml files, as a watched or indexed folder; returns their
+/// total size
+pub fn folder(dir: &Path, count: usize) -> usize {
+    std::fs::create_dir_all(dir).unwrap();
+    let mut total = 0;
+    for i in 0..count {
+        let eml = format!(
+            "From: Sender {i} <sender{i}@example.com>\r\nTo: bob@example.com\r\n\
+             Subject: Message number {i}\r\n\r\n{}",
+            prose(2 * 1024)
+        );
+        total += eml.len();
+        std::fs::write(dir.join(format!("message-{:04}.eml", i)), eml).unwrap();
+    }
+    total
+}
+
+pub fn parse_msg(data: &[u8]) -> usize {
+    parse(MessageFormat::Msg, data)
+}
+
+pub fn parse_eml(data: &[u8]) -> usize {
+    parse(MessageFormat::Eml, data)
+}
+
+fn parse(format: MessageFormat, data: &[u8]) -> usize {
+    let message = parser::parse_bytes(format, data, &ParseOptions::default()).unwrap();
+    message.attachments.len()
+}
+
+/// Subject and sender of every message in `dir`, the way notifications and listings read them
+pub fn index_folder(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| parser::peek_summary(&entry.ok()?.path()).ok())
+        .filter(|summary| summary.subject.is_some())
+        .count()
+}
+
+/// Fully parse every message in `dir`, as listings do for previews; with `previews`, the
+/// reading-pane snippets are made too, so the two runs give the cost of snippets
+pub fn parse_folder(dir: &Path, previews: bool) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            parser::parse_file(&path, &ParseOptions::default()).ok()
+        })
+        .map(|message| {
+            if previews {
+                parser::preview::snippet(&message, crate::i18n::Lang::En).len()
+            } else {
+                message.subject.map_or(0, |subject| subject.len())
+            }
+        })
+        .sum()
+}
+
+/// Backend work on an HTML body: the document tree, links, tracker images and plain text
+pub fn process_html(html: &str) -> usize {
+    parser::html::parse(html).len()
+        + parser::links::extract(None, Some(html)).len()
+        + parser::trackers::find(html, &[], &[]).len()
+        + parser::text::html_to_text(html, None).len()
+}
+
+/// File bytes as `read_file_as_bytes` returns them: a JSON array of numbers
+pub fn ipc_json_array(data: &[u8]) -> usize {
+    serde_json::to_vec(data).unwrap().len()
+}
+
+/// File bytes as a base64 string inside JSON, as the attachment commands take them
+pub fn ipc_base64(data: &[u8]) -> usize {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    serde_json::to_vec(&STANDARD.encode(data)).unwrap().len()
+}
+
+/// File bytes as a raw `tauri::ipc::Response` body, which only moves the buffer
+pub fn ipc_raw(data: &[u8]) -> usize {
+    let response = tauri::ipc::Response::new(data.to_vec());
+    std::hint::black_box(response);
+    data.len()
+}
@@ -0,0 +1,478 @@
+//! Brand logos announced with BIMI. The receiving server either embeds the logo in a
+//! `BIMI-Indicator` header or, as the sender publishes it, names its URL in
+//! `BIMI-Location`. Logos must follow the SVG Tiny Portable/Secure profile; anything outside
+//! it is refused as a whole rather than cleaned up, and what passes is written out again
+//! from the parsed elements so nothing unvetted reaches the webview.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+/// Largest logo the profile allows
+pub const MAX_SVG_BYTES: usize = 32 * 1024;
+
+/// Deepest element nesting accepted
+const MAX_DEPTH: usize = 32;
+
+/// Elements of SVG Tiny PS, less the ones that can reference anything (`use`, `image`,
+/// `a`) or animate
+const ELEMENTS: &[&str] = &[
+    "svg",
+    "g",
+    "defs",
+    "title",
+    "desc",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "solidColor",
+    "text",
+    "tspan",
+    "textArea",
+];
+
+/// Elements whose text content is kept
+const TEXT_ELEMENTS: &[&str] = &["title", "desc", "text", "tspan", "textArea"];
+
+/// Geometry and presentation attributes; no event handlers, links or `style`
+const ATTRIBUTES: &[&str] = &[
+    "xmlns",
+    "version",
+    "baseProfile",
+    "viewBox",
+    "preserveAspectRatio",
+    "id",
+    "width",
+    "height",
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "fx",
+    "fy",
+    "d",
+    "points",
+    "transform",
+    "offset",
+    "gradientUnits",
+    "gradientTransform",
+    "spreadMethod",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "stroke",
+    "stroke-width",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-opacity",
+    "opacity",
+    "color",
+    "display",
+    "visibility",
+    "stop-color",
+    "stop-opacity",
+    "solid-color",
+    "solid-opacity",
+    "font-family",
+    "font-size",
+    "font-style",
+    "font-weight",
+    "text-anchor",
+    "xml:space",
+];
+
+/// Where the logo of a message is: embedded by the receiving server or at a URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Indicator {
+    Embedded(Vec<u8>),
+    Location(String),
+}
+
+/// The logo announced in `headers`; `BIMI-Indicator` wins over `BIMI-Location`. Only
+/// `https` locations of SVG files count.
+pub fn indicator(headers: &[(String, String)]) -> Option<Indicator> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    if let Some(embedded) = header("BIMI-Indicator") {
+        let compact: String = embedded.split_whitespace().collect();
+        if let Ok(data) = STANDARD.decode(compact) {
+            return Some(Indicator::Embedded(data));
+        }
+    }
+    let location = header("BIMI-Location")?;
+    let url = location
+        .split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("l"))
+        .map(|(_, value)| value.trim())?;
+    let lower = url.to_ascii_lowercase();
+    (lower.starts_with("https://") && lower.split(['?', '#']).next()?.ends_with(".svg"))
+        .then(|| Indicator::Location(url.to_string()))
+}
+
+/// The logo as a `data:` URL when it is a valid SVG Tiny PS document
+pub fn logo_data_url(svg: &[u8]) -> Result<String, String> {
+    let clean = sanitize(svg)?;
+    Ok(format!(
+        "data:image/svg+xml;base64,{}",
+        STANDARD.encode(clean)
+    ))
+}
+
+/// Check `svg` against the profile and write it out again from what was parsed
+pub fn sanitize(svg: &[u8]) -> Result<String, String> {
+    if svg.len() > MAX_SVG_BYTES {
+        return Err(format!("BIMI logo larger than {} bytes", MAX_SVG_BYTES));
+    }
+    let text = std::str::from_utf8(svg).map_err(|_| "BIMI logo is not UTF-8".to_string())?;
+    let mut reader = Reader { text, pos: 0 };
+    reader.skip_prolog()?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut open: Vec<&str> = Vec::new();
+    let mut has_title = false;
+    let mut root_seen = false;
+    while let Some(token) = reader.next()? {
+        match token {
+            Token::Start {
+                name,
+                attributes,
+                empty,
+            } => {
+                if !ELEMENTS.contains(&name) {
+                    return Err(format!("<{}> is not allowed in a BIMI logo", name));
+                }
+                match open.is_empty() {
+                    true if root_seen => return Err("Content after the <svg> element".into()),
+                    true => {
+                        if name != "svg" {
+                            return Err("A BIMI logo must be an <svg> document".to_string());
+                        }
+                        check_root(&attributes)?;
+                        root_seen = true;
+                    }
+                    false if name == "svg" => return Err("Nested <svg> element".to_string()),
+                    false => {}
+                }
+                if name == "title" && open.len() == 1 {
+                    has_title = true;
+                }
+                out.push('<');
+                out.push_str(name);
+                for (attribute, value) in &attributes {
+                    check_attribute(attribute, value)?;
+                    out.push_str(&format!(" {}=\"{}\"", attribute, escape(value)));
+                }
+                match empty {
+                    true => out.push_str("/>"),
+                    false => {
+                        out.push('>');
+                        open.push(name);
+                        if open.len() > MAX_DEPTH {
+                            return Err("BIMI logo nested too deeply".to_string());
+                        }
+                    }
+                }
+            }
+            Token::End(name) => {
+                if open.pop() != Some(name) {
+                    return Err(format!("Unbalanced </{}> in BIMI logo", name));
+                }
+                out.push_str(&format!("</{}>", name));
+            }
+            Token::Text(text) => {
+                let in_text = open.last().is_some_and(|name| TEXT_ELEMENTS.contains(name));
+                match (in_text, text.trim().is_empty()) {
+                    (true, _) => out.push_str(&escape(&text)),
+                    (false, true) => {}
+                    (false, false) => return Err("Stray text in BIMI logo".to_string()),
+                }
+            }
+        }
+    }
+    if !root_seen || !open.is_empty() {
+        return Err("Incomplete BIMI logo".to_string());
+    }
+    if !has_title {
+        return Err("A BIMI logo needs a <title>".to_string());
+    }
+    Ok(out)
+}
+
+/// The root must declare the profile and may not be positioned
+fn check_root(attributes: &[(&str, String)]) -> Result<(), String> {
+    let value = |name: &str| {
+        attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    if value("baseProfile") != Some("tiny-ps") || value("version") != Some("1.2") {
+        return Err("A BIMI logo must declare baseProfile=\"tiny-ps\" version=\"1.2\"".into());
+    }
+    if value("x").is_some() || value("y").is_some() {
+        return Err("The <svg> of a BIMI logo may not have x or y".to_string());
+    }
+    Ok(())
+}
+
+fn check_attribute(name: &str, value: &str) -> Result<(), String> {
+    if !ATTRIBUTES.contains(&name) {
+        return Err(format!("Attribute {} is not allowed in a BIMI logo", name));
+    }
+    if name == "xmlns" && value != "http://www.w3.org/2000/svg" {
+        return Err(format!("Unexpected namespace {}", value));
+    }
+    // Paint may only refer to gradients of the document itself
+    let lower = value.to_ascii_lowercase();
+    let mut rest = lower.as_str();
+    while let Some(found) = rest.find("url(") {
+        rest = &rest[found + 4..];
+        if !rest.trim_start().starts_with('#') {
+            return Err(format!("External reference in {}", name));
+        }
+    }
+    if lower.contains("javascript:") || lower.contains("expression(") {
+        return Err(format!("Script in {}", name));
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+enum Token<'a> {
+    Start {
+        name: &'a str,
+        attributes: Vec<(&'a str, String)>,
+        empty: bool,
+    },
+    End(&'a str),
+    Text(String),
+}
+
+/// A strict reader of the small XML subset logos use: no DTDs, entities beyond the five
+/// predefined ones and character references, CDATA or processing instructions
+struct Reader<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// A byte order mark and the XML declaration
+    fn skip_prolog(&mut self) -> Result<(), String> {
+        if self.rest().starts_with('\u{feff}') {
+            self.pos += '\u{feff}'.len_utf8();
+        }
+        let trimmed = self.rest().trim_start();
+        if trimmed.starts_with("<?xml") {
+            let end = trimmed
+                .find("?>")
+                .ok_or_else(|| "Unterminated XML declaration".to_string())?;
+            self.pos = self.text.len() - trimmed.len() + end + 2;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>, String> {
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                let end = comment
+                    .find("-->")
+                    .ok_or_else(|| "Unterminated comment".to_string())?;
+                self.pos += 4 + end + 3;
+                continue;
+            }
+            if rest.starts_with("<!") || rest.starts_with("<?") {
+                return Err("Declarations are not allowed in a BIMI logo".to_string());
+            }
+            if let Some(closing) = rest.strip_prefix("</") {
+                let end = closing
+                    .find('>')
+                    .ok_or_else(|| "Unterminated end tag".to_string())?;
+                self.pos += 2 + end + 1;
+                return Ok(Some(Token::End(closing[..end].trim())));
+            }
+            if let Some(tag) = rest.strip_prefix('<') {
+                return self.start_tag(tag).map(Some);
+            }
+            let end = rest.find('<').unwrap_or(rest.len());
+            self.pos += end;
+            return Ok(Some(Token::Text(decode(&rest[..end])?)));
+        }
+    }
+
+    fn start_tag(&mut self, tag: &'a str) -> Result<Token<'a>, String> {
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .ok_or_else(|| "Unterminated start tag".to_string())?;
+        let name = &tag[..name_end];
+        let mut pos = name_end;
+        let mut attributes = Vec::new();
+        loop {
+            let rest = &tag[pos..];
+            let trimmed = rest.trim_start();
+            if let Some(after) = trimmed.strip_prefix("/>") {
+                self.pos += 1 + tag.len() - after.len();
+                return Ok(Token::Start {
+                    name,
+                    attributes,
+                    empty: true,
+                });
+            }
+            if let Some(after) = trimmed.strip_prefix('>') {
+                self.pos += 1 + tag.len() - after.len();
+                return Ok(Token::Start {
+                    name,
+                    attributes,
+                    empty: false,
+                });
+            }
+            let equals = trimmed
+                .find('=')
+                .ok_or_else(|| format!("Malformed attribute in <{}>", name))?;
+            let attribute = trimmed[..equals].trim();
+            let value_part = trimmed[equals + 1..].trim_start();
+            let quote = value_part
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| format!("Unquoted attribute in <{}>", name))?;
+            let value_end = value_part[1..]
+                .find(quote)
+                .ok_or_else(|| format!("Unterminated attribute in <{}>", name))?;
+            if attribute.is_empty() || attributes.iter().any(|(key, _)| *key == attribute) {
+                return Err(format!("Malformed attribute in <{}>", name));
+            }
+            attributes.push((attribute, decode(&value_part[1..1 + value_end])?));
+            pos = tag.len() - value_part.len() + 1 + value_end + 1;
+        }
+    }
+}
+
+/// Resolve the predefined entities and character references
+fn decode(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let end = after
+            .find(';')
+            .ok_or_else(|| "Unterminated entity".to_string())?;
+        let entity = &after[..end];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+                    .ok_or_else(|| format!("Unknown entity &{};", entity))?
+            }
+        };
+        out.push(c);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGO: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" version="1.2" baseProfile="tiny-ps" viewBox="0 0 100 100">
+  <title>Example &amp; Co</title>
+  <!-- brand colors -->
+  <defs><linearGradient id="g"><stop offset="0" stop-color="#c00"/></linearGradient></defs>
+  <circle cx="50" cy="50" r="40" fill="url(#g)"/>
+</svg>"##;
+
+    #[test]
+    fn only_tiny_ps_logos_pass() {
+        let clean = sanitize(LOGO.as_bytes()).unwrap();
+        assert!(clean.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.2""#));
+        assert!(clean.contains("<title>Example &amp; Co</title>"));
+        assert!(!clean.contains("brand colors"));
+
+        let reject = |from: &str, to: &str| {
+            let svg = LOGO.replace(from, to);
+            assert!(sanitize(svg.as_bytes()).is_err(), "accepted {}", to);
+        };
+        reject("<title>Example &amp; Co</title>", "");
+        reject(r#"baseProfile="tiny-ps""#, r#"baseProfile="tiny""#);
+        reject(r#"viewBox"#, r#"x="0" viewBox"#);
+        reject("<circle", r#"<script>alert(1)</script><circle"#);
+        reject(r#"r="40""#, r#"r="40" onload="alert(1)""#);
+        reject("url(#g)", "url(https://example.com/track.svg)");
+        reject(
+            "<circle",
+            r#"<image href="https://example.com/x.png"/><circle"#,
+        );
+        reject("<?xml", "<!DOCTYPE svg [<!ENTITY a \"b\">]><?xml");
+        assert!(sanitize(&vec![b' '; MAX_SVG_BYTES + 1]).is_err());
+    }
+
+    #[test]
+    fn indicators_come_from_headers() {
+        let headers = |name: &str, value: &str| vec![(name.to_string(), value.to_string())];
+        assert_eq!(
+            indicator(&headers(
+                "BIMI-Location",
+                "v=BIMI1; l=https://example.com/logo.svg; a=https://example.com/vmc.pem"
+            )),
+            Some(Indicator::Location(
+                "https://example.com/logo.svg".to_string()
+            ))
+        );
+        assert_eq!(
+            indicator(&headers(
+                "BIMI-Location",
+                "v=BIMI1; l=http://example.com/logo.svg"
+            )),
+            None
+        );
+        assert_eq!(
+            indicator(&headers("BIMI-Indicator", "PHN2Zy8+")),
+            Some(Indicator::Embedded(b"<svg/>".to_vec()))
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use crate::export;
+use crate::i18n;
+use crate::parser::{Message, MessageFormat};
+use crate::tempdir;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Header fields that make a block of `Name: value` lines a message header
+const MESSAGE_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "cc",
+    "subject",
+    "date",
+    "message-id",
+    "received",
+    "return-path",
+    "reply-to",
+    "sender",
+    "delivered-to",
+    "mime-version",
+    "content-type",
+];
+
+/// Known fields a header block needs before clipboard text counts as message source
+const MIN_MESSAGE_FIELDS: usize = 2;
+
+/// Files written from clipboard content, whose parse results are flagged `from_clipboard`
+#[derive(Default)]
+pub struct ClipboardFiles(Mutex<HashSet<PathBuf>>);
+
+impl ClipboardFiles {
+    pub fn contains(&self, path: &Path) -> bool {
+        self.0.lock().unwrap().contains(path)
+    }
+}
+
+/// What the clipboard offers, in each flavor the platform tools could read
+#[derive(Debug, Default)]
+pub struct Contents {
+    /// Copied files (file managers put references, not the content, on the clipboard)
+    pub files: Vec<PathBuf>,
+    pub text: Option<String>,
+    pub html: Option<String>,
+}
+
+/// Something on the clipboard that can be opened
+#[derive(Debug, PartialEq)]
+pub enum Openable {
+    /// Existing message files
+    Files(Vec<PathBuf>),
+    /// Message source to write to a temp .eml
+    Source(Vec<u8>),
+}
+
+/// Read the clipboard; flavors the platform doesn't have or can't read are left empty
+pub fn read() -> Contents {
+    let mut contents = platform::read();
+    contents.text = contents.text.filter(|text| !text.trim().is_empty());
+    contents.html = contents.html.filter(|html| !html.trim().is_empty());
+    contents
+}
+
+/// Pick the best openable interpretation of the clipboard: copied message files, then text
+/// naming such files, then pasted message source, then HTML wrapped into a minimal message
+pub fn classify(contents: &Contents) -> Option<Openable> {
+    let files: Vec<PathBuf> = contents
+        .files
+        .iter()
+        .filter(|path| is_message_file(path))
+        .cloned()
+        .collect();
+    if !files.is_empty() {
+        return Some(Openable::Files(files));
+    }
+
+    if let Some(text) = &contents.text {
+        let files: Vec<PathBuf> = text
+            .lines()
+            .filter_map(text_path)
+            .filter(|path| is_message_file(path))
+            .collect();
+        if !files.is_empty() {
+            return Some(Openable::Files(files));
+        }
+        if looks_like_message(text) {
+            return Some(Openable::Source(text.as_bytes().to_vec()));
+        }
+    }
+
+    let html = contents.html.as_ref()?;
+    let mut message = Message::new(MessageFormat::Eml);
+    message.subject = Some(i18n::t("clipboard.subject", &[]));
+    message.date = Some(chrono::Local::now().to_rfc3339());
+    message.body_text = contents.text.clone();
+    message.body_html = Some(html.clone());
+    Some(Openable::Source(export::message_to_eml(&message)))
+}
+
+/// Write message source from the clipboard to the session temp directory and remember it,
+/// so `load_message` can flag the result
+pub fn write_source(files: &ClipboardFiles, data: &[u8]) -> Result<PathBuf, String> {
+    let name = format!(
+        "clipboard-{}.eml",
+        chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")
+    );
+    let path = tempdir::write_file(&name, data, false)?;
+    let path = path.canonicalize().unwrap_or(path);
+    files.0.lock().unwrap().insert(path.clone());
+    Ok(path)
+}
+
+fn is_message_file(path: &Path) -> bool {
+    let supported = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("msg") || ext.eq_ignore_ascii_case("eml"));
+    supported && path.is_file()
+}
+
+/// A path on a line of clipboard text: plain, quoted (as "Copy as path" on Windows does)
+/// or as a `file://` URI
+fn text_path(line: &str) -> Option<PathBuf> {
+    let line = line.trim().trim_matches('"');
+    if line.is_empty() {
+        return None;
+    }
+    let Some(uri) = line.strip_prefix("file://") else {
+        return Some(PathBuf::from(line));
+    };
+    // file:///dir, or file:///C:/dir on Windows; files on other hosts aren't local
+    let path = uri.strip_prefix("localhost").unwrap_or(uri);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = crate::media::percent_decode(path);
+    #[cfg(windows)]
+    let path = path[1..].to_string();
+    Some(PathBuf::from(path))
+}
+
+/// Whether text starts with an RFC 5322 header block: `Name: value` fields, possibly folded,
+/// with at least a couple of well-known ones, ending at a blank line or the end of the text
+fn looks_like_message(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let mut lines = text.lines().peekable();
+    // The separator line of mbox files and of some "show original" views
+    if lines.peek().is_some_and(|line| line.starts_with("From ")) {
+        lines.next();
+    }
+
+    let mut fields = 0;
+    let mut known = 0;
+    for line in lines {
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if fields == 0 {
+                return false;
+            }
+            continue;
+        }
+        let Some((name, _)) = line.split_once(':') else {
+            return false;
+        };
+        if name.is_empty() || !name.bytes().all(|byte| byte.is_ascii_graphic()) {
+            return false;
+        }
+        fields += 1;
+        if MESSAGE_FIELDS.contains(&name.to_ascii_lowercase().as_str()) {
+            known += 1;
+        }
+    }
+    known >= MIN_MESSAGE_FIELDS
+}
+
+/// HTML from the Windows clipboard comes in the CF_HTML format, a header of offsets before
+/// the markup
+#[cfg(any(windows, test))]
+fn strip_cf_html_header(html: &str) -> &str {
+    if !html.starts_with("Version:") {
+        return html;
+    }
+    html.find('<').map_or(html, |start| &html[start..])
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Contents;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn osascript(script: &str) -> Option<String> {
+        let output = Command::new("osascript")
+            .args(["-e", script])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(
+            String::from_utf8_lossy(&output.stdout)
+                .trim_end()
+                .to_string(),
+        )
+    }
+
+    /// `«data HTML3C68746D6C3E…»` as AppleScript prints raw clipboard data
+    fn decode_data(data: &str) -> Option<String> {
+        let hex = data.strip_prefix("«data HTML")?.strip_suffix('»')?;
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    pub(super) fn read() -> Contents {
+        let files = osascript("POSIX path of (the clipboard as «class furl»)")
+            .map(|path| vec![PathBuf::from(path)])
+            .unwrap_or_default();
+        let text = Command::new("pbpaste")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string());
+        let html = osascript("the clipboard as «class HTML»").and_then(|data| decode_data(&data));
+        Contents { files, text, html }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Contents;
+    use std::os::windows::process::CommandExt;
+    use std::path::PathBuf;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    /// All flavors in one PowerShell run, which takes a while to start
+    const SCRIPT: &str = "[Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+        @{ files = @(Get-Clipboard -Format FileDropList -ErrorAction SilentlyContinue | \
+        ForEach-Object { $_.FullName }); \
+        text = (Get-Clipboard -Format Text -Raw -ErrorAction SilentlyContinue); \
+        html = (Get-Clipboard -Format Text -TextFormatType Html -Raw -ErrorAction SilentlyContinue) \
+        } | ConvertTo-Json -Compress";
+
+    #[derive(serde::Deserialize)]
+    struct Output {
+        #[serde(default)]
+        files: serde_json::Value,
+        text: Option<String>,
+        html: Option<String>,
+    }
+
+    pub(super) fn read() -> Contents {
+        let output = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output();
+        let Some(output) = output
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| serde_json::from_slice::<Output>(&output.stdout).ok())
+        else {
+            return Contents::default();
+        };
+        // A single file may come out as a plain string
+        let files = match output.files {
+            serde_json::Value::String(path) => vec![PathBuf::from(path)],
+            serde_json::Value::Array(paths) => paths
+                .iter()
+                .filter_map(|path| path.as_str().map(PathBuf::from))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Contents {
+            files,
+            text: output.text,
+            html: output
+                .html
+                .map(|html| super::strip_cf_html_header(&html).to_string()),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use super::Contents;
+    use std::process::Command;
+
+    /// Clipboard content of one MIME type, via wl-paste on Wayland and xclip on X11
+    fn paste(mime: &str) -> Option<String> {
+        let mut command = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut command = Command::new("wl-paste");
+            command.args(["--no-newline", "--type", mime]);
+            command
+        } else {
+            let mut command = Command::new("xclip");
+            command.args(["-selection", "clipboard", "-out", "-target", mime]);
+            command
+        };
+        let output = command
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(decode(&output.stdout))
+    }
+
+    /// Browsers may put HTML on the clipboard as UTF-16
+    fn decode(bytes: &[u8]) -> String {
+        match bytes {
+            [0xFF, 0xFE, rest @ ..] => {
+                let units: Vec<u16> = rest
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => String::from_utf8_lossy(bytes).to_string(),
+        }
+    }
+
+    pub(super) fn read() -> Contents {
+        // text/uri-list has comments, GNOME's own flavor starts with the "copy"/"cut" action
+        let files = paste("text/uri-list")
+            .or_else(|| paste("x-special/gnome-copied-files"))
+            .map(|list| {
+                list.lines()
+                    .filter(|line| line.starts_with("file://"))
+                    .filter_map(super::text_path)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Contents {
+            files,
+            text: paste("text/plain;charset=utf-8").or_else(|| paste("UTF8_STRING")),
+            html: paste("text/html"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParseOptions};
+
+    #[test]
+    fn detects_header_blocks() {
+        assert!(looks_like_message(
+            "Received: from mx.example.com\r\n\tby mail.example.org; Tue, 5 Mar 2024\r\n\
+             From: Ann <ann@example.com>\r\nSubject: Hi\r\n\r\nBody: not a header"
+        ));
+        assert!(looks_like_message(
+            "From ann@example.com Tue Mar  5 09:07:00 2024\nFrom: ann@example.com\nTo: bob@example.com"
+        ));
+        // Prose, a single field, unknown fields and a body before the headers
+        assert!(!looks_like_message(
+            "Dear Bob,\nSubject: the budget\nDate: Friday"
+        ));
+        assert!(!looks_like_message("Subject: Hi\n\nFrom: ann@example.com"));
+        assert!(!looks_like_message("Note: buy milk\nTodo: call Ann\n"));
+        assert!(!looks_like_message("Subject: Hi\nFrom a friend\nTo: bob"));
+    }
+
+    #[test]
+    fn classifies_clipboard_contents() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = dir.join("copied file.eml");
+        std::fs::write(&file, "From: a\r\n\r\n").unwrap();
+        let other = dir.join("notes.txt");
+        std::fs::write(&other, "").unwrap();
+
+        // File references, then paths in text; other and missing files don't count
+        let contents = Contents {
+            files: vec![other.clone(), file.clone(), dir.join("gone.msg")],
+            ..Contents::default()
+        };
+        assert_eq!(
+            classify(&contents),
+            Some(Openable::Files(vec![file.clone()]))
+        );
+        let contents = Contents {
+            files: vec![other],
+            text: Some(format!("\"{}\"\r\n", file.display())),
+            ..Contents::default()
+        };
+        assert_eq!(
+            classify(&contents),
+            Some(Openable::Files(vec![file.clone()]))
+        );
+        #[cfg(unix)]
+        {
+            let uri = format!("file://{}", file.display()).replace(' ', "%20");
+            let contents = Contents {
+                text: Some(uri),
+                ..Contents::default()
+            };
+            assert_eq!(classify(&contents), Some(Openable::Files(vec![file])));
+        }
+
+        let source = "From: ann@example.com\r\nSubject: Hi\r\n\r\nHello";
+        let contents = Contents {
+            text: Some(source.into()),
+            html: Some("<p>Hello</p>".into()),
+            ..Contents::default()
+        };
+        assert_eq!(classify(&contents), Some(Openable::Source(source.into())));
+
+        let contents = Contents {
+            text: Some("just some words".into()),
+            ..Contents::default()
+        };
+        assert_eq!(classify(&contents), None);
+        assert_eq!(classify(&Contents::default()), None);
+    }
+
+    #[test]
+    fn wraps_html_into_a_message() {
+        let contents = Contents {
+            text: Some("Quarterly figures".into()),
+            html: Some(
+                strip_cf_html_header(
+                    "Version:0.9\r\nStartHTML:0000000105\r\nEndHTML:0000000170\r\n\
+                 <html><body><p>Quarterly <b>figures</b></p></body></html>",
+                )
+                .to_string(),
+            ),
+            ..Contents::default()
+        };
+        let Some(Openable::Source(data)) = classify(&contents) else {
+            panic!("HTML is openable");
+        };
+        let message =
+            parser::parse_bytes(MessageFormat::Eml, &data, &ParseOptions::default()).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Pasted content"));
+        assert!(message.date.is_some());
+        assert!(message
+            .body_html
+            .unwrap()
+            .starts_with("<html><body><p>Quarterly"));
+        assert_eq!(
+            message.body_text.as_deref().map(str::trim),
+            Some("Quarterly figures")
+        );
+    }
+}
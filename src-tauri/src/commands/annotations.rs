@@ -0,0 +1,114 @@
+//! Commands for notes and tags on messages
+
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{access, notes, parser};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Store a note on a message by content hash, leaving the file untouched; an empty note
+/// removes it
+#[tauri::command]
+pub fn set_message_note(
+    state: tauri::State<'_, notes::Notes>,
+    path_hash: String,
+    note: String,
+) -> Result<(), Localized> {
+    state.set(&path_hash, &note).map_err(Localized::from)
+}
+
+/// Get the note on a message by content hash
+#[tauri::command]
+pub fn get_message_note(
+    state: tauri::State<'_, notes::Notes>,
+    path_hash: String,
+) -> Option<String> {
+    state.get(&path_hash)
+}
+
+/// All notes and tags with the content hash of their message, most recently updated first
+#[tauri::command]
+pub fn list_annotated_messages(
+    state: tauri::State<'_, notes::Notes>,
+) -> Vec<notes::AnnotatedMessage> {
+    state.list()
+}
+
+/// Tag a message by content hash; returns false if it already had the tag
+///
+/// Tags are trimmed and matched case-insensitively; a new tag differing from one in use only
+/// in case takes the existing spelling.
+#[tauri::command]
+pub fn add_tag(
+    state: tauri::State<'_, notes::Notes>,
+    path_hash: String,
+    tag: String,
+) -> Result<bool, Localized> {
+    Ok(state.tag_many(&[path_hash], &tag)? > 0)
+}
+
+/// Tag several messages at once, such as a search result; returns how many were newly tagged
+#[tauri::command]
+pub fn tag_many(
+    state: tauri::State<'_, notes::Notes>,
+    path_hashes: Vec<String>,
+    tag: String,
+) -> Result<usize, Localized> {
+    state.tag_many(&path_hashes, &tag).map_err(Localized::from)
+}
+
+/// Remove a tag from a message; returns false if it didn't have it
+#[tauri::command]
+pub fn remove_tag(
+    state: tauri::State<'_, notes::Notes>,
+    path_hash: String,
+    tag: String,
+) -> Result<bool, Localized> {
+    state.remove_tag(&path_hash, &tag).map_err(Localized::from)
+}
+
+/// Tags in use with how many messages carry them and their color from `tag_colors`
+#[tauri::command]
+pub fn list_tags(app: AppHandle) -> Vec<notes::TagInfo> {
+    let colors = app.state::<SettingsStore>().get().tag_colors;
+    app.state::<notes::Notes>().tags(&colors)
+}
+
+/// Message files in `dir` carrying any or all of `tags`, by path
+///
+/// Files are matched by content hash, so moved and renamed files keep their tags.
+#[tauri::command]
+pub async fn query_by_tags(
+    app: AppHandle,
+    dir: String,
+    tags: Vec<String>,
+    mode: notes::TagMode,
+) -> Result<Vec<String>, Localized> {
+    let dir = access::check(&app, &dir)?;
+    if !app.state::<notes::Notes>().any_tagged(&tags) {
+        return Ok(Vec::new());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && parser::MessageFormat::from_path(path).is_some())
+            .collect();
+        paths.sort();
+
+        let notes = app.state::<notes::Notes>();
+        Ok(paths
+            .into_iter()
+            .filter(|path| {
+                notes::content_hash(path).is_ok_and(|key| {
+                    notes.record_path(&key, path);
+                    notes.matches(&key, &tags, mode)
+                })
+            })
+            .map(|path| path.to_string_lossy().to_string())
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Tag query failed: {}", e))?
+}
@@ -0,0 +1,314 @@
+//! Commands for previewing, saving and exporting attachments
+
+use super::{load_message, record_audit};
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{
+    access, attachments, audit, auto_extract, export, filenames, images, jobs, notes, parser, save,
+};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+/// Return an image attachment of a message file as a `data:` URI for the in-app viewer
+///
+/// Attachments of attached messages are addressed through `part_path` (see `save_attachment`).
+/// Large PNGs are scaled down; other images, animated ones included, are passed through.
+/// Images above `max_bytes` (20 MB by default) and types the webview can't display fail with
+/// an error asking to open them externally.
+#[tauri::command]
+pub async fn get_attachment_data_uri(
+    app: AppHandle,
+    path: String,
+    index: usize,
+    part_path: Option<Vec<usize>>,
+    max_bytes: Option<usize>,
+) -> Result<images::ImagePreview, Localized> {
+    let message = load_message(&app, &path)?.into_nested(&part_path.unwrap_or_default())?;
+    let attachment = message
+        .attachments
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| format!("Attachment {} not found in {}", index, path))?;
+    let mime = attachment
+        .detected_mime
+        .or(attachment.declared_mime)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    images::preview(
+        &mime,
+        &attachment.data,
+        max_bytes.unwrap_or(images::DEFAULT_MAX_BYTES),
+    )
+    .map_err(Localized::from)
+}
+
+/// Show what auto-extract rules would save from a message file without writing anything:
+/// `rule` alone (switched on or not), else every enabled rule in settings
+#[tauri::command]
+pub async fn preview_auto_extract(
+    app: AppHandle,
+    path: String,
+    rule: Option<auto_extract::AutoExtractRule>,
+) -> Result<auto_extract::AutoExtraction, Localized> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let resolved = access::check(&app, &path)?;
+        let message = load_message(&app, &path)?;
+        let settings = app.state::<SettingsStore>().get();
+        let rules: Vec<&auto_extract::AutoExtractRule> = match &rule {
+            Some(rule) => vec![rule],
+            None => settings
+                .auto_extract_rules
+                .iter()
+                .filter(|rule| rule.enabled)
+                .collect(),
+        };
+        Ok(auto_extract::run(
+            &rules,
+            &resolved,
+            &message,
+            true,
+            |dir| access::check(&app, &dir.to_string_lossy()),
+        ))
+    })
+    .await
+    .map_err(|e| format!("Failed to preview auto-extract rules: {}", e))?
+}
+
+/// Parse a message file and return the message attached to it at `part_path`
+///
+/// `part_path` lists attachment indices from the outer message inwards: `[2, 0]` is the first
+/// attachment of the message attached third. The result has its own attachments and content
+/// ids, like a message opened from a file.
+#[tauri::command]
+pub fn open_nested_message(
+    app: AppHandle,
+    path: String,
+    part_path: Vec<usize>,
+) -> Result<parser::Message, Localized> {
+    let mut nested = load_message(&app, &path)?.into_nested(&part_path)?;
+    nested.sender_trust = app
+        .state::<SettingsStore>()
+        .get()
+        .sender_rules
+        .evaluate(&nested);
+    Ok(nested)
+}
+
+/// Find attachments with identical content among the message files in `dir`
+///
+/// Groups are by SHA-256, largest waste first; zero-byte attachments are listed apart and
+/// inline images left out unless `include_inline: true`. Digests are cached by message
+/// content hash, so repeating the report after changes only parses new files.
+///
+/// Runs as a job like `export_mbox`, with a `hash` phase over the files of the folder.
+#[tauri::command]
+pub async fn dedupe_attachments_report(
+    app: AppHandle,
+    dir: String,
+    include_inline: Option<bool>,
+    job_id: Option<String>,
+) -> Result<attachments::DedupeReport, Localized> {
+    let dir = access::check(&app, &dir)?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        attachments::dedupe_report(
+            &dir,
+            include_inline.unwrap_or(false),
+            &app.state::<attachments::DigestCache>(),
+            |path| load_message(&app, &path.to_string_lossy()),
+            |current, total| {
+                job.progress("hash", current, total);
+                job.check_cancelled()
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Attachment report failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Save the attachments of message files into the folder `target`
+///
+/// Inline images are left out unless `include_inline: true`. With `unique: true` each
+/// content is written once and a `manifest.json` maps every message's attachments to the
+/// files holding them. Messages that fail to load are skipped and listed.
+///
+/// Runs as a job like `export_mbox`; after `cancel_job` the files written so far are kept.
+#[tauri::command]
+pub async fn export_attachments(
+    app: AppHandle,
+    paths: Vec<String>,
+    target: String,
+    unique: Option<bool>,
+    include_inline: Option<bool>,
+    job_id: Option<String>,
+) -> Result<attachments::AttachmentExport, Localized> {
+    let target = access::check(&app, &target)?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let total = paths.len();
+        let export = attachments::export_attachments(
+            &paths,
+            &target,
+            unique.unwrap_or(false),
+            include_inline.unwrap_or(false),
+            |path| load_message(&app, path),
+            |current, total| {
+                job.progress("export", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        job.progress("export", total, total);
+        for file in &export.written {
+            let path = std::path::Path::new(&file.path);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            record_audit(&app, || {
+                audit::AuditEntry::new(audit::AuditAction::Exported, None, &name, &file.sha256)
+                    .to(path)
+            })?;
+        }
+        Ok(export)
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))?
+}
+
+/// Save the attachments passing `filter` from message files and folders (their message
+/// files) into the folder `target`, optionally in a folder per message, with a
+/// `manifest.csv` mapping the written files back to their messages
+///
+/// Inline images are left out unless the filter includes them. Runs as a job like
+/// `export_attachments`; messages or attachments that fail are listed and skipped.
+#[tauri::command]
+pub async fn export_attachments_matching(
+    app: AppHandle,
+    paths: Vec<String>,
+    filter: Option<attachments::AttachmentFilter>,
+    target: String,
+    per_message_folders: Option<bool>,
+    job_id: Option<String>,
+) -> Result<attachments::MatchingExport, Localized> {
+    let target = access::check(&app, &target)?;
+    let paths = paths
+        .iter()
+        .map(|path| access::check(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut messages = 0;
+        let export = attachments::export_matching(
+            &paths,
+            &filter.unwrap_or_default(),
+            &target,
+            per_message_folders.unwrap_or(false),
+            &app.state::<notes::Notes>(),
+            |path| load_message(&app, &path.to_string_lossy()),
+            |current, total| {
+                messages = total;
+                job.progress("export", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        job.progress("export", messages, messages);
+        for file in &export.written {
+            let path = std::path::Path::new(&file.path);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            record_audit(&app, || {
+                audit::AuditEntry::new(audit::AuditAction::Exported, None, &name, &file.sha256)
+                    .to(path)
+            })?;
+        }
+        Ok(export)
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))?
+}
+
+/// Save an attachment of a message file with a "Save As" dialog
+///
+/// The dialog proposes the attachment's suggested file name unless `use_original_name` is set.
+/// Attachments of attached messages are addressed through `part_path` (see
+/// `open_nested_message`); with `as_eml: true` an attached message is saved as a standalone
+/// .eml file, converting .msg ones. The file is written in chunks with `job-progress` events for `job_id`, then read back and
+/// checked against the attachment's SHA-256; `atomic: true` writes to a temporary name first.
+/// Returns the saved file with its hash, or `None` if the dialog was cancelled.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Command arguments are named fields for the frontend
+pub async fn save_attachment(
+    app: AppHandle,
+    path: String,
+    index: usize,
+    part_path: Option<Vec<usize>>,
+    use_original_name: Option<bool>,
+    as_eml: Option<bool>,
+    atomic: Option<bool>,
+    job_id: Option<String>,
+) -> Result<Option<save::SavedFile>, Localized> {
+    use tauri_plugin_dialog::FilePath;
+
+    let message = load_message(&app, &path)?.into_nested(&part_path.unwrap_or_default())?;
+    let mut attachment = message
+        .attachments
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| format!("Attachment {} not found in {}", index, path))?;
+
+    let mut file_name = if use_original_name.unwrap_or(false) {
+        attachment.file_name.clone()
+    } else {
+        attachment.suggested_filename.clone()
+    };
+    if as_eml.unwrap_or(false) {
+        let nested = attachment
+            .nested
+            .as_deref()
+            .ok_or_else(|| format!("Attachment {} is not a message", index))?;
+        if nested.format == parser::MessageFormat::Msg {
+            attachment.data = export::message_to_eml(nested);
+            attachment.sha256 = parser::sha256_hex(&attachment.data);
+        }
+        file_name = std::path::Path::new(&file_name)
+            .with_extension("eml")
+            .to_string_lossy()
+            .to_string();
+    }
+    let file_name = filenames::sanitize_filename(&file_name, filenames::TargetOs::CURRENT);
+    let extension = std::path::Path::new(&file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let file_path = app
+        .dialog()
+        .file()
+        .set_file_name(&file_name)
+        .add_filter("File", &[&extension])
+        .blocking_save_file();
+
+    let Some(FilePath::Path(target)) = file_path else {
+        return Ok(None); // User cancelled
+    };
+    record_audit(&app, || {
+        audit::AuditEntry::new(
+            audit::AuditAction::Saved,
+            Some(std::path::Path::new(&path)),
+            &attachment.file_name,
+            &attachment.sha256,
+        )
+        .to(&target)
+    })?;
+    let job = jobs::Job::start(&app, job_id)?;
+    save::write_verified(
+        &target,
+        &attachment.data,
+        &attachment.sha256,
+        atomic.unwrap_or(false),
+        |written, total| {
+            job.progress("save", written, total);
+            job.check_cancelled()
+        },
+    )
+    .map(Some)
+    .map_err(Localized::from)
+}
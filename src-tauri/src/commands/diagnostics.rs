@@ -0,0 +1,58 @@
+//! Commands for the audit log, diagnostics bundles and the self-test
+
+use crate::i18n::Localized;
+use crate::{access, audit, diagnostics, quarantine, self_test};
+use tauri::{AppHandle, Manager};
+
+/// Entries of the audit log (see the `audit_log` setting) matching `filter`, oldest first:
+/// by action, message or attachment hash, part of the attachment name and time range, the
+/// newest `limit` (1000 by default)
+#[tauri::command]
+pub fn get_audit_log(app: AppHandle, filter: Option<audit::AuditFilter>) -> Vec<audit::AuditEntry> {
+    app.state::<audit::AuditLog>()
+        .entries(&filter.unwrap_or_default())
+}
+
+/// Write all entries of the audit log, rotated segments included, into one JSONL file at
+/// `target`; returns the number of entries
+#[tauri::command]
+pub async fn export_audit_log(app: AppHandle, target: String) -> Result<usize, Localized> {
+    let target = access::check(&app, &target)?;
+    tauri::async_runtime::spawn_blocking(move || app.state::<audit::AuditLog>().export(&target))
+        .await
+        .map_err(|e| format!("Export failed: {}", e))?
+        .map_err(Localized::from)
+}
+
+/// Write a diagnostics zip (versions, OS, settings, recent log, crash report) and return its path
+///
+/// With `include_file`, parse warnings for that message are added; its content only with
+/// `include_file_content: true`, and never in quarantine mode. Message notes are only added
+/// with `include_notes: true`.
+#[tauri::command]
+pub fn create_diagnostics_bundle(
+    app: AppHandle,
+    include_file: Option<String>,
+    include_file_content: Option<bool>,
+    include_notes: Option<bool>,
+) -> Result<String, Localized> {
+    let include_file = include_file
+        .map(|path| access::check(&app, &path))
+        .transpose()?;
+    diagnostics::create_bundle(
+        &app,
+        include_file.as_deref(),
+        include_file_content.unwrap_or(false) && !quarantine::active(&app),
+        include_notes.unwrap_or(false),
+    )
+    .map_err(Localized::from)
+}
+
+/// Check the critical paths with built-in data: parsing an MSG and an EML file, a temp file
+/// round trip, saving settings, finding an application for .txt files (without launching
+/// it) and, unless `offline` or in quarantine mode, reaching the updater endpoint. The
+/// report's `text` is meant for pasting into a support request.
+#[tauri::command]
+pub async fn run_self_test(app: AppHandle, offline: Option<bool>) -> self_test::SelfTestReport {
+    self_test::run(&app, offline.unwrap_or(false)).await
+}
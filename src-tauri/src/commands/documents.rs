@@ -0,0 +1,154 @@
+//! Commands for the documents open across windows
+
+use crate::i18n::Localized;
+use crate::state::WindowFiles;
+use crate::{
+    access, archive, documents, freshness, memory, quarantine, session, volumes, window_placement,
+};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Open a message file in a new window and return the window's label
+///
+/// The window is cascaded from the calling one and kept within the work area of that
+/// window's monitor, sized for the monitor's scale factor. Its webview gets the file
+/// from `get_pending_files`.
+#[tauri::command]
+pub fn open_in_new_window(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+) -> Result<String, Localized> {
+    static NEXT_WINDOW: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+    access::check(&app, &path)?;
+    let mut config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == "main")
+        .cloned()
+        .ok_or_else(|| "No window configuration".to_string())?;
+    config.label = format!(
+        "viewer-{}",
+        NEXT_WINDOW.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    );
+    config.center = false;
+    // Shown once placed, so it doesn't flash up at the default position
+    config.visible = false;
+
+    let placement = window_placement::outer_rect(&window).map(|parent| {
+        window_placement::cascade(
+            parent,
+            (config.width, config.height),
+            &window_placement::monitors(&window),
+        )
+    });
+    app.state::<WindowFiles>()
+        .assign(&config.label, vec![PathBuf::from(path)]);
+    let new_window = tauri::WebviewWindowBuilder::from_config(&app, &config)
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to open a window: {}", e))?;
+    if let Some(rect) = placement {
+        window_placement::apply(&new_window, rect);
+    }
+    quarantine::apply_title(&new_window);
+    let _ = new_window.show();
+    let _ = new_window.set_focus();
+    Ok(config.label)
+}
+
+/// Register a file the frontend has opened and return its document id
+#[tauri::command]
+pub fn register_document(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, documents::OpenDocuments>,
+    path: String,
+) -> documents::OpenDocument {
+    let document = state
+        .0
+        .lock()
+        .unwrap()
+        .open(PathBuf::from(path), window.label());
+    session::schedule_save(&app);
+    document
+}
+
+/// Forget a document the frontend has closed
+#[tauri::command]
+pub fn close_document(
+    app: AppHandle,
+    state: tauri::State<'_, documents::OpenDocuments>,
+    doc_id: String,
+) -> Result<(), Localized> {
+    let mut registry = state.0.lock().unwrap();
+    let path = registry.get(&doc_id).map(|doc| doc.path.clone());
+    if !registry.close(&doc_id) {
+        return Err(format!("Unknown document: {}", doc_id).into());
+    }
+    // Decrypted archive contents stay in memory only while a document shows them
+    let unused = path.filter(|path| registry.list().iter().all(|doc| &doc.path != path));
+    // Checking access looks up the registry for a path on a volume that went away
+    drop(registry);
+    if let Some(path) = unused {
+        if let Ok(resolved) = access::check(&app, &path.to_string_lossy()) {
+            app.state::<archive::Archives>().forget(&resolved);
+        }
+    }
+    session::schedule_save(&app);
+    Ok(())
+}
+
+/// Check whether a document on a volume that went away (`document-unavailable`) can be
+/// read again, and bring it back: its file is verified against what was parsed, and
+/// `stale` means it changed meanwhile and should be reloaded. Fails with
+/// `error.volume_unavailable` while the volume is still away; documents also come back by
+/// themselves, with a `document-available` event, once the volume returns.
+#[tauri::command]
+pub async fn retry_document(
+    app: AppHandle,
+    doc_id: String,
+) -> Result<freshness::Freshness, Localized> {
+    tauri::async_runtime::spawn_blocking(move || volumes::retry(&app, &doc_id))
+        .await
+        .map_err(|e| format!("Retrying failed: {}", e))?
+        .map_err(Localized::from)
+}
+
+/// Mark the document currently shown in the reading pane
+#[tauri::command]
+pub fn set_active_document(
+    app: AppHandle,
+    state: tauri::State<'_, documents::OpenDocuments>,
+    doc_id: String,
+) -> Result<(), Localized> {
+    if !state.0.lock().unwrap().set_active(&doc_id) {
+        return Err(format!("Unknown document: {}", doc_id).into());
+    }
+    session::schedule_save(&app);
+    Ok(())
+}
+
+/// What each open document keeps in memory, and the total counted against the
+/// `memory_threshold` setting
+#[tauri::command]
+pub fn get_memory_report(app: AppHandle) -> memory::MemoryReport {
+    memory::report(&app)
+}
+
+/// Drop the extracted attachments, source and archive contents cached for a document and
+/// return the bytes released; the document stays open and they're rebuilt when needed
+#[tauri::command]
+pub fn unload_document_caches(app: AppHandle, doc_id: String) -> Result<usize, Localized> {
+    Ok(memory::unload(&app, &doc_id)?)
+}
+
+/// List the open documents
+#[tauri::command]
+pub fn list_documents(
+    state: tauri::State<'_, documents::OpenDocuments>,
+) -> Vec<documents::OpenDocument> {
+    state.0.lock().unwrap().list().to_vec()
+}
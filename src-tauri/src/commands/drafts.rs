@@ -0,0 +1,206 @@
+//! Commands for forwards and meeting replies put together in the app
+
+use super::{date_formatter, load_message};
+use crate::i18n::Localized;
+use crate::parser::calendar;
+use crate::settings::SettingsStore;
+use crate::{access, drafts, filenames, i18n, launcher, onboarding, save, tempdir};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+/// Where `generate_calendar_response` wrote the reply
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarReply {
+    /// The .ics file
+    pub ics: String,
+    /// The unsent .eml to the organizer with the .ics attached, opened in the mail client;
+    /// `None` when the reply was saved to a target
+    pub draft: Option<String>,
+}
+
+/// Start forwarding a message file: the draft has the subject prefixed with `Fwd:`, no
+/// recipients yet and every attachment included
+#[tauri::command]
+pub async fn create_draft_from(app: AppHandle, path: String) -> Result<drafts::Draft, Localized> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let message = load_message(&app, &path)?;
+        let source = access::check(&app, &path)?;
+        app.state::<drafts::Drafts>().create(&source, &message)
+    })
+    .await
+    .map_err(|e| format!("Creating the draft failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Change a draft's recipients, subject or note, or which attachments it includes (by
+/// index); fields left out of `patch` stay as they are
+///
+/// Every recipient must be one address (`Name <address>` or the address alone).
+#[tauri::command]
+pub fn update_draft(
+    app: AppHandle,
+    id: String,
+    patch: drafts::DraftPatch,
+) -> Result<drafts::Draft, Localized> {
+    app.state::<drafts::Drafts>()
+        .update(&id, patch)
+        .map_err(Localized::from)
+}
+
+/// Drafts not written yet, including those of an earlier run
+#[tauri::command]
+pub fn list_drafts(app: AppHandle) -> Vec<drafts::Draft> {
+    app.state::<drafts::Drafts>().list()
+}
+
+/// Throw a draft away; false if there was none of that id
+#[tauri::command]
+pub fn discard_draft(app: AppHandle, id: String) -> Result<bool, Localized> {
+    app.state::<drafts::Drafts>()
+        .remove(&id)
+        .map_err(Localized::from)
+}
+
+/// Write a draft as an .eml marked unsent, to `target`, or without one to the temp
+/// directory and opened in the mail client; returns where it was written
+///
+/// The original is read again, so its attachments are forwarded as they are now. The draft
+/// is gone once written.
+#[tauri::command]
+pub async fn finalize_draft(
+    app: AppHandle,
+    id: String,
+    target: Option<String>,
+) -> Result<String, Localized> {
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let store = app.state::<drafts::Drafts>();
+        let draft = store.get(&id).ok_or_else(|| format!("No draft {}", id))?;
+        let original = load_message(&app, &draft.source)?;
+        let lang = i18n::current();
+        let eml = drafts::compose(&draft, original, lang, &date_formatter(&app, lang));
+        let path = match target {
+            Some(target) => {
+                let target = access::check(&app, &target)?;
+                save::write_atomic(&target, &eml)?;
+                target
+            }
+            None => {
+                let name = match draft.subject.trim() {
+                    "" => "forward.eml".to_string(),
+                    subject => format!("{}.eml", subject),
+                };
+                let name = filenames::sanitize_filename(&name, filenames::TargetOs::CURRENT);
+                let path = tempdir::write_file_or_ask(&app, &name, &eml, false)?;
+                launcher::open_with(&app, &path, mail_client(&app).as_deref())?;
+                path
+            }
+        };
+        store.remove(&id)?;
+        Ok(path.display().to_string())
+    })
+    .await
+    .map_err(|e| format!("Writing the draft failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Answer the meeting request in a message file as the `calendar_address` of the settings
+/// (an iTIP `REPLY`): saved as an .ics to `target`, or without one written to the temp
+/// directory and attached to an unsent .eml to the organizer, opened in the mail client
+///
+/// The answer is for the whole series of a recurring meeting unless `instance` names one
+/// occurrence by its `RECURRENCE-ID` (`20261021T090000`, in the form of the start).
+#[tauri::command]
+pub async fn generate_calendar_response(
+    app: AppHandle,
+    path: String,
+    response: calendar::Response,
+    comment: Option<String>,
+    target: Option<String>,
+    instance: Option<String>,
+) -> Result<CalendarReply, Localized> {
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let attendee = app
+            .state::<SettingsStore>()
+            .get()
+            .calendar_address
+            .filter(|address| !address.trim().is_empty())
+            .ok_or("Set your own address (calendar_address) to answer meeting requests")?;
+        let message = load_message(&app, &path)?;
+        let part = calendar::find(&message).ok_or("The message has no meeting request")?;
+        let request = calendar::parse(&String::from_utf8_lossy(&part.data))
+            .ok_or("The meeting request can't be read")?;
+        let reply = calendar::reply(
+            &request,
+            &attendee,
+            response,
+            comment.as_deref(),
+            instance.as_deref(),
+            chrono::Utc::now(),
+        )?;
+        let ics = reply.to_ics().into_bytes();
+
+        if let Some(target) = target {
+            let target = access::check(&app, &target)?;
+            save::write_atomic(&target, &ics)?;
+            return Ok(CalendarReply {
+                ics: target.display().to_string(),
+                draft: None,
+            });
+        }
+        let event = calendar::request_event(&request);
+        let organizer = event
+            .and_then(|event| event.get("ORGANIZER"))
+            .and_then(calendar::Property::address)
+            .ok_or("The meeting request names no organizer")?;
+        let title = event
+            .and_then(|event| event.get("SUMMARY"))
+            .map(|summary| calendar::unescape_text(&summary.value))
+            .or(message.subject.clone())
+            .unwrap_or_default();
+        let key = match response {
+            calendar::Response::Accept => "calendar.accept",
+            calendar::Response::Decline => "calendar.decline",
+            calendar::Response::Tentative => "calendar.tentative",
+        };
+        let subject = i18n::t(key, &[("title", title)]);
+        let ics_path = tempdir::write_file_or_ask(&app, "reply.ics", &ics, false)?;
+        let eml = drafts::compose_calendar_reply(&organizer, &subject, comment.as_deref(), ics);
+        let name =
+            filenames::sanitize_filename(&format!("{}.eml", subject), filenames::TargetOs::CURRENT);
+        let draft = tempdir::write_file_or_ask(&app, &name, &eml, false)?;
+        launcher::open_with(&app, &draft, mail_client(&app).as_deref())?;
+        Ok(CalendarReply {
+            ics: ics_path.display().to_string(),
+            draft: Some(draft.display().to_string()),
+        })
+    })
+    .await
+    .map_err(|e| format!("Answering the meeting request failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// An application registered for .eml files other than this one, preferably the one
+/// handling `mailto:` links; `None` leaves it to the system's default
+fn mail_client(app: &AppHandle) -> Option<String> {
+    let product = app.package_info().name.to_ascii_lowercase();
+    let others: Vec<launcher::Application> = launcher::applications_for("eml")
+        .into_iter()
+        .filter(|candidate| {
+            !candidate.id.to_ascii_lowercase().contains(&product)
+                && !candidate.name.to_ascii_lowercase().contains(&product)
+        })
+        .collect();
+    // `thunderbird` on Linux, `Outlook.URL.mailto.15` on Windows, `Mail` on macOS
+    let default = onboarding::default_mail_client()
+        .and_then(|client| client.split('.').next().map(str::to_ascii_lowercase))
+        .filter(|key| !key.is_empty());
+    let preferred = default.and_then(|key| {
+        others.iter().find(|candidate| {
+            candidate.id.to_ascii_lowercase().contains(&key)
+                || candidate.name.to_ascii_lowercase().contains(&key)
+        })
+    });
+    preferred
+        .or(others.first())
+        .map(|candidate| candidate.id.clone())
+}
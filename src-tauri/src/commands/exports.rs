@@ -0,0 +1,207 @@
+//! Commands that export messages to other formats
+
+use super::{date_formatter, load_message};
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{
+    access, export, formatting, html_archive, i18n, jobs, notes, parser, reader, report, save,
+};
+use tauri::{AppHandle, Manager};
+
+/// Export a message file as plain text to `target`
+///
+/// HTML-only messages are converted to text. Body lines are wrapped at `wrap_at` characters
+/// when given, and the signature block is left out with `strip_signature: true`.
+#[tauri::command]
+pub fn export_as_txt(
+    app: AppHandle,
+    path: String,
+    target: String,
+    wrap_at: Option<usize>,
+    strip_signature: Option<bool>,
+) -> Result<(), Localized> {
+    let message = load_message(&app, &path)?;
+    let target = access::check(&app, &target)?;
+    let options = export::TextExportOptions {
+        wrap_at,
+        strip_signature: strip_signature.unwrap_or(false),
+        lang: i18n::current(),
+        dates: date_formatter(&app, i18n::current()),
+    };
+    save::write_atomic(
+        &target,
+        export::message_to_text(&message, options).as_bytes(),
+    )
+    .map_err(Localized::from)
+}
+
+/// Export a message file as a single self-contained .html file to `target`, for people
+/// without a mail client
+///
+/// The header block, the sanitized body with its inline images, the plain-text
+/// alternative and the attachments up to `html_archive_embed_limit` are embedded; the file
+/// loads nothing from the network.
+#[tauri::command]
+pub fn export_html_archive(app: AppHandle, path: String, target: String) -> Result<(), Localized> {
+    let message = load_message(&app, &path)?;
+    let target = access::check(&app, &target)?;
+    let source_name = std::path::Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let options = html_archive::ArchiveOptions {
+        embed_limit: app.state::<SettingsStore>().get().html_archive_embed_limit,
+        lang: i18n::current(),
+        dates: date_formatter(&app, i18n::current()),
+        version: app.package_info().version.to_string(),
+    };
+    let html = html_archive::render(&message, &source_name, &options);
+    save::write_atomic(&target, html.as_bytes()).map_err(Localized::from)
+}
+
+/// Export every To, Cc and Bcc recipient of a message file to `target` as CSV
+/// (`type,name,email`), however many the viewer shows
+#[tauri::command]
+pub fn export_recipients_csv(
+    app: AppHandle,
+    path: String,
+    target: String,
+) -> Result<(), Localized> {
+    let message = load_message(&app, &path)?;
+    let target = access::check(&app, &target)?;
+    save::write_atomic(&target, export::recipients_csv(&message).as_bytes())
+        .map_err(Localized::from)
+}
+
+/// Show how exports would write a date, for the settings UI
+///
+/// `sample` is an RFC 3339 or RFC 5322 date (now when left out); `format` and `time_zone`
+/// default to the current settings, so unsaved choices can be previewed. Invalid custom
+/// patterns are reported as errors here, while exports fall back to ISO 8601 for them.
+#[tauri::command]
+pub fn get_date_format_preview(
+    app: AppHandle,
+    sample: Option<String>,
+    format: Option<formatting::DateFormat>,
+    time_zone: Option<formatting::TimeZoneChoice>,
+) -> Result<String, Localized> {
+    let settings = app.state::<SettingsStore>().get();
+    let format = format.unwrap_or(settings.date_format);
+    if let formatting::DateFormat::Custom(pattern) = &format {
+        formatting::validate_pattern(pattern)?;
+    }
+    let formatter = formatting::DateFormatter::new(
+        format,
+        time_zone.unwrap_or(settings.date_time_zone),
+        i18n::current(),
+    );
+    let Some(sample) = sample else {
+        return Ok(formatter.now());
+    };
+    let date = chrono::DateTime::parse_from_rfc3339(sample.trim())
+        .ok()
+        .map(|date| date.to_rfc3339())
+        .or_else(|| parser::headers::parse_date(&sample));
+    Ok(formatter.format(date.as_deref()))
+}
+
+/// Write message files into the mbox archive `target`, replacing it or appending to it
+///
+/// Messages are written in order; when one fails, the report names it and lists the ones
+/// that made it into the archive.
+///
+/// Runs as a job: progress is reported as `job-progress` events for `job_id`, and after
+/// `cancel_job` the archive is left as it was and the command fails with `Cancelled`.
+#[tauri::command]
+pub async fn export_mbox(
+    app: AppHandle,
+    paths: Vec<String>,
+    target: String,
+    append: bool,
+    job_id: Option<String>,
+) -> Result<export::MboxExport, Localized> {
+    let target = access::check(&app, &target)?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let total = paths.len();
+        let report = export::export_mbox(
+            &paths,
+            &target,
+            append,
+            |path| load_message(&app, path),
+            |current, total| {
+                job.progress("export", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        job.progress("export", total, total);
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))?
+}
+
+/// Write an HTML or PDF report of the annotated messages in `dir` to `target`
+///
+/// Lists every message with a note or tags, grouped by tag, with its metadata, note and a
+/// body excerpt, plus counts per tag, the folder and the time of generation. Annotated files
+/// that were last seen in `dir` but are gone or changed are listed and marked.
+///
+/// Runs as a job like `export_mbox`, with a `report` phase over the files of the folder.
+#[tauri::command]
+pub async fn export_review_report(
+    app: AppHandle,
+    dir: String,
+    target: String,
+    format: report::ReportFormat,
+    job_id: Option<String>,
+) -> Result<report::ReviewReport, Localized> {
+    let dir = access::check(&app, &dir)?;
+    let target = access::check(&app, &target)?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = report::export_review_report(
+            &dir,
+            &target,
+            format,
+            &date_formatter(&app, i18n::current()),
+            &app.state::<notes::Notes>(),
+            |path| load_message(&app, &path.to_string_lossy()),
+            |current, total| {
+                job.progress("report", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))?
+}
+
+/// Write a copy of the message file at `path` to `target` with its content redacted, for
+/// attaching to a bug report
+///
+/// Text keeps its length and encoding, addresses get consistent stand-ins and attachments
+/// random bytes, while the structure stays as it was (see `parser::redact`). Both files are
+/// parsed afterwards; `reproduces: false` means the copy no longer shows the original's
+/// warnings or error, so it may not reproduce the problem.
+#[tauri::command]
+pub async fn export_redacted(
+    app: AppHandle,
+    path: String,
+    target: String,
+) -> Result<parser::redact::Verification, Localized> {
+    let resolved = access::check(&app, &path)?;
+    let target = access::check(&app, &target)?;
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let format = parser::MessageFormat::from_path(&resolved)
+            .ok_or_else(|| format!("Unsupported file type: {}", resolved.display()))?;
+        let data = reader::read_all(&resolved)?;
+        let redacted = parser::redact::redact(format, &data)?;
+        save::write_atomic(&target, &redacted)?;
+        Ok(parser::redact::verify(format, &data, &redacted))
+    })
+    .await
+    .map_err(|e| format!("Redaction failed: {}", e))?
+    .map_err(Localized::from)
+}
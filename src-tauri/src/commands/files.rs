@@ -0,0 +1,591 @@
+//! Commands for reading, opening and listing message files
+
+use super::{folder_preview, load_message, record_audit};
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::state::{PendingFiles, WindowFiles};
+use crate::{
+    access, archive, audit, clipboard, documents, file_open, filenames, folder_index, i18n,
+    integrity, jobs, launcher, notes, open_queue, parser, previews, reader, save, session, tempdir,
+    watcher,
+};
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+/// Read a file from the filesystem and return its bytes
+///
+/// Message files read this way are registered as open documents of the calling window.
+/// A message with the same content as an open document at another path isn't opened
+/// twice: its window is focused, a `duplicate-open` event is emitted and the read fails
+/// with `error.already_open`.
+/// Like all commands taking paths, only files allowed by the access policy can be read.
+///
+/// Reads taking longer than a moment, such as from network shares or of cloud placeholder
+/// files being downloaded, emit `read-progress` events. With `job_id` the read can be
+/// stopped with `cancel_job`, failing with `Cancelled`.
+#[tauri::command]
+pub async fn read_file_as_bytes(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    job_id: Option<String>,
+) -> Result<Vec<u8>, Localized> {
+    let resolved = access::check(&app, &path)?;
+    let job = job_id
+        .map(|id| jobs::Job::start(&app, Some(id)))
+        .transpose()?;
+    let progress_app = app.clone();
+    let progress_path = path.clone();
+    let bytes = tauri::async_runtime::spawn_blocking(move || {
+        reader::read(
+            &resolved,
+            |progress| {
+                // Report the path as the frontend knows it
+                let progress = reader::ReadProgress {
+                    path: &progress_path,
+                    ..progress
+                };
+                if let Err(e) = progress_app.emit("read-progress", &progress) {
+                    log_line!("Failed to emit read-progress: {}", e);
+                }
+            },
+            || job.as_ref().map_or(Ok(()), jobs::Job::check_cancelled),
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to read file {}: {}", path, e))??;
+
+    let path = PathBuf::from(path);
+    if parser::MessageFormat::from_path(&path).is_some() {
+        let documents = app.state::<documents::OpenDocuments>();
+        let mut registry = documents.0.lock().unwrap();
+        let mut fingerprint = documents::Fingerprint::of(&bytes);
+        if let Some(existing) = registry.same_content(&path, &mut fingerprint, &bytes) {
+            drop(registry);
+            return Err(duplicate_open(&app, existing, path).into());
+        }
+        let document = registry.open(path, window.label());
+        registry.set_fingerprint(&document.id, fingerprint);
+        drop(registry);
+        session::schedule_save(&app);
+    }
+
+    Ok(bytes)
+}
+
+/// Payload of the `duplicate-open` event
+#[derive(Clone, serde::Serialize)]
+struct DuplicateOpen {
+    existing_path: String,
+    new_path: String,
+}
+
+/// Focus the window showing `existing` instead of opening a copy of it again, and tell the
+/// frontend with a `duplicate-open` event; returns the `AlreadyOpen` error for the read
+fn duplicate_open(app: &AppHandle, existing: documents::OpenDocument, new_path: PathBuf) -> String {
+    if let Some(window) = app.get_webview_window(&existing.window) {
+        let _ = window.set_focus();
+    }
+    let payload = DuplicateOpen {
+        existing_path: existing.path.to_string_lossy().to_string(),
+        new_path: new_path.to_string_lossy().to_string(),
+    };
+    if let Err(e) = app.emit("duplicate-open", &payload) {
+        log_line!("Failed to emit duplicate-open: {}", e);
+    }
+    format!("{}: {}", documents::ALREADY_OPEN, payload.existing_path)
+}
+
+/// Save a base64-encoded file to temp directory and open with system viewer
+///
+/// The file name's extension is corrected to match the content unless `use_original_name` is set.
+/// An application chosen for the extension in settings is used instead of the default.
+#[tauri::command]
+pub fn open_file_with_system(
+    app: AppHandle,
+    base64_content: String,
+    file_name: String,
+    use_original_name: Option<bool>,
+) -> Result<(), Localized> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    // Decode base64 content
+    let bytes = STANDARD
+        .decode(&base64_content)
+        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+
+    let file_name = if use_original_name.unwrap_or(false) {
+        file_name
+    } else {
+        parser::sniff::sniff(&file_name, None, &bytes).suggested_filename
+    };
+    let file_name = filenames::sanitize_filename(&file_name, filenames::TargetOs::CURRENT);
+
+    // Write to the private session temp directory; executables are marked as downloaded
+    let executable = parser::sniff::is_executable(&file_name, &bytes);
+    let temp_path = tempdir::write_file_or_ask(&app, &file_name, &bytes, executable)?;
+    record_audit(&app, || {
+        audit::AuditEntry::new(
+            audit::AuditAction::OpenedExternally,
+            None,
+            &file_name,
+            &parser::sha256_hex(&bytes),
+        )
+        .to(&temp_path)
+    })?;
+    launcher::open(&app, &temp_path).map_err(Localized::from)
+}
+
+/// List the applications registered for files with `extension` (without dot)
+#[tauri::command]
+pub async fn list_applications_for(extension: String) -> Vec<launcher::Application> {
+    launcher::applications_for(&extension)
+}
+
+/// Open a file, or with `index` an attachment of the message file at `path` (see
+/// `save_attachment` for `part_path`), with the application `app_id`
+///
+/// Without `app_id` the application chosen in settings or the system default is used. An
+/// application that is no longer installed falls back to the default and emits
+/// `open-with-fallback`.
+#[tauri::command]
+pub async fn open_with(
+    app: AppHandle,
+    path: String,
+    index: Option<usize>,
+    part_path: Option<Vec<usize>>,
+    app_id: Option<String>,
+) -> Result<(), Localized> {
+    let file = match index {
+        None => access::check(&app, &path)?,
+        Some(index) => {
+            let message = load_message(&app, &path)?.into_nested(&part_path.unwrap_or_default())?;
+            let attachment = message
+                .attachments
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| format!("Attachment {} not found in {}", index, path))?;
+            let executable =
+                parser::sniff::is_executable(&attachment.suggested_filename, &attachment.data);
+            let file = tempdir::write_file_or_ask(
+                &app,
+                &attachment.suggested_filename,
+                &attachment.data,
+                executable,
+            )?;
+            record_audit(&app, || {
+                audit::AuditEntry::new(
+                    audit::AuditAction::OpenedExternally,
+                    Some(std::path::Path::new(&path)),
+                    &attachment.file_name,
+                    &attachment.sha256,
+                )
+                .to(&file)
+            })?;
+            file
+        }
+    };
+    match app_id {
+        Some(app_id) => launcher::open_with(&app, &file, Some(&app_id)),
+        None => launcher::open(&app, &file),
+    }
+    .map_err(Localized::from)
+}
+
+/// Save a file with a "Save As" dialog
+#[tauri::command]
+pub async fn save_file_with_dialog(
+    app: AppHandle,
+    base64_content: String,
+    file_name: String,
+) -> Result<bool, Localized> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use tauri_plugin_dialog::FilePath;
+
+    let file_name = filenames::sanitize_filename(&file_name, filenames::TargetOs::CURRENT);
+
+    // Extract file extension for filter
+    let extension = std::path::Path::new(&file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    // Build the save dialog
+    let file_path = app
+        .dialog()
+        .file()
+        .set_file_name(&file_name)
+        .add_filter("File", &[&extension])
+        .blocking_save_file();
+
+    match file_path {
+        Some(FilePath::Path(path)) => {
+            // Decode base64 content
+            let bytes = STANDARD
+                .decode(&base64_content)
+                .map_err(|e| format!("Failed to decode base64: {}", e))?;
+
+            // Write to the selected file
+            save::write_atomic(&path, &bytes)?;
+
+            Ok(true)
+        }
+        _ => Ok(false), // User cancelled
+    }
+}
+
+/// Unlock the encrypted zip archive of a document and return the message in it
+///
+/// Zips holding a single .msg or .eml (ZipCrypto or WinZip AES) are decrypted in memory,
+/// and the message is never written to disk unless saved. Once unlocked, the other commands
+/// open the document like any message file. A wrong password fails with
+/// `error.wrong_archive_password` and the attempts left; after the last one, the archive
+/// stays locked until its document is closed.
+#[tauri::command]
+pub async fn provide_archive_password(
+    app: AppHandle,
+    doc_id: String,
+    password: String,
+) -> Result<parser::Message, Localized> {
+    let path = app
+        .state::<documents::OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .get(&doc_id)
+        .map(|doc| doc.path.clone())
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    let resolved = access::check(&app, &path.to_string_lossy())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<archive::Archives>()
+            .unlock(&resolved, &password)?;
+        load_message(&app, &path.to_string_lossy())
+    })
+    .await
+    .map_err(|e| format!("Failed to open archive: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Listing metadata of message files: subject, sender, date, attachment count and a
+/// reading-pane preview (the start of the sender's own text; "[image]" for image-only
+/// bodies, "Invitation: {title}, {date}" for meeting requests)
+///
+/// Results are cached by path and modification time. Files that can't be read are returned
+/// with `error` set.
+#[tauri::command]
+pub async fn get_message_previews(
+    app: AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<previews::MessagePreview>, Localized> {
+    let lang = i18n::current();
+    tauri::async_runtime::spawn_blocking(move || {
+        let cache = app.state::<previews::PreviewCache>();
+        paths
+            .iter()
+            .map(|path| match access::check(&app, path) {
+                Ok(resolved) => cache.get(&resolved, lang, || load_message(&app, path)),
+                Err(error) => previews::MessagePreview {
+                    path: path.clone(),
+                    subject: None,
+                    sender: None,
+                    date: None,
+                    attachments: 0,
+                    attachment_summary: parser::grouping::AttachmentSummary::default(),
+                    delivered_to: Vec::new(),
+                    source_labels: Vec::new(),
+                    source_flags: None,
+                    preview: String::new(),
+                    language: None,
+                    error: Some(error),
+                },
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Listing failed: {}", e).into())
+}
+
+/// List the message files of a folder a page at a time
+///
+/// Indexes the folder (listing metadata as `get_message_previews` returns it, cached the
+/// same way) and returns the first `limit` entries, at most 1000, with the total. Later
+/// pages come from `get_index_page`, which sorts and filters the same snapshot, so pages
+/// stay consistent while files are added. A `folder-changed` event for the folder
+/// drops the snapshot; pages requested after it come from a new one with a new
+/// `generation`.
+///
+/// Runs as a job like `export_mbox`, with an `index` phase over the files of the folder.
+#[tauri::command]
+pub async fn list_messages_in_dir(
+    app: AppHandle,
+    dir: String,
+    limit: usize,
+    sort_by: Option<folder_index::SortBy>,
+    filter: Option<folder_index::ListingFilter>,
+    job_id: Option<String>,
+) -> Result<folder_index::IndexPage, Localized> {
+    let resolved = access::check(&app, &dir)?;
+    let job = jobs::Job::start(&app, job_id)?;
+    let lang = i18n::current();
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let indexes = app.state::<folder_index::FolderIndexes>();
+        indexes.invalidate(&resolved);
+        let index = indexes.get(
+            &resolved,
+            lang,
+            |path| folder_preview(&app, path, lang),
+            |current, total| {
+                job.progress("index", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        Ok(index.page(
+            0,
+            limit,
+            sort_by.unwrap_or_default(),
+            &filter.unwrap_or_default(),
+            &app.state::<notes::Notes>(),
+        ))
+    })
+    .await
+    .map_err(|e| format!("Listing failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Serve `limit` entries (at most 1000) from `offset` of a folder listing started with
+/// `list_messages_in_dir`, sorted by `sort_by` and filtered by sender, date range,
+/// attachments, tag or parse errors
+#[tauri::command]
+pub async fn get_index_page(
+    app: AppHandle,
+    dir: String,
+    offset: usize,
+    limit: usize,
+    sort_by: Option<folder_index::SortBy>,
+    filter: Option<folder_index::ListingFilter>,
+) -> Result<folder_index::IndexPage, Localized> {
+    let resolved = access::check(&app, &dir)?;
+    let lang = i18n::current();
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let index = app.state::<folder_index::FolderIndexes>().get(
+            &resolved,
+            lang,
+            |path| folder_preview(&app, path, lang),
+            |_, _| Ok(()),
+        )?;
+        Ok(index.page(
+            offset,
+            limit,
+            sort_by.unwrap_or_default(),
+            &filter.unwrap_or_default(),
+            &app.state::<notes::Notes>(),
+        ))
+    })
+    .await
+    .map_err(|e| format!("Listing failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Export a folder listing to `target` as CSV: every entry matching `filter`, sorted by
+/// `sort_by`, with its date, sender, subject, attachment count, source labels and flags
+#[tauri::command]
+pub async fn export_index_csv(
+    app: AppHandle,
+    dir: String,
+    target: String,
+    sort_by: Option<folder_index::SortBy>,
+    filter: Option<folder_index::ListingFilter>,
+) -> Result<(), Localized> {
+    let resolved = access::check(&app, &dir)?;
+    let target = access::check(&app, &target)?;
+    let lang = i18n::current();
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let index = app.state::<folder_index::FolderIndexes>().get(
+            &resolved,
+            lang,
+            |path| folder_preview(&app, path, lang),
+            |_, _| Ok(()),
+        )?;
+        let csv = index.csv(
+            sort_by.unwrap_or_default(),
+            &filter.unwrap_or_default(),
+            &app.state::<notes::Notes>(),
+        );
+        save::write_atomic(&target, csv.as_bytes())
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Check the message files of `dir` against the baselines recorded by its first
+/// verification: each file is hashed and parsed again, and the report lists the files that
+/// changed, went missing, are new or no longer parse
+///
+/// Files whose size and modification time are unchanged are skipped unless `full: true`.
+/// Changed and missing files keep their baseline until a run with `accept_changes: true`.
+/// With `report_path` the report is also written there, as CSV for a `.csv` file and JSON
+/// otherwise. Runs as a job like `export_mbox`, with a `verify` phase over the files.
+#[tauri::command]
+pub async fn verify_archive(
+    app: AppHandle,
+    dir: String,
+    options: Option<integrity::VerifyOptions>,
+    report_path: Option<String>,
+    job_id: Option<String>,
+) -> Result<integrity::VerifyReport, Localized> {
+    let dir = access::check(&app, &dir)?;
+    let report_path = report_path
+        .map(|path| access::check(&app, &path))
+        .transpose()?;
+    let job = jobs::Job::start(&app, job_id)?;
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let report = app.state::<integrity::Baselines>().verify(
+            &dir,
+            options.unwrap_or_default(),
+            |path| load_message(&app, &path.to_string_lossy()),
+            |current, total| {
+                job.progress("verify", current, total);
+                job.check_cancelled()
+            },
+        )?;
+        job.progress("verify", report.counts.files, report.counts.files);
+        if let Some(target) = &report_path {
+            integrity::write_report(&report, target)?;
+        }
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Verification failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Rename a message file downloaded with a mangled name (`RE%3A%20Invoice.msg`,
+/// `message.msg.msg`) to its readable name, sanitized for this OS and numbered when taken
+///
+/// Open documents of the file move along and get a `document-renamed` event; a clean name
+/// is left as it is (`renamed: false`).
+#[tauri::command]
+pub async fn normalize_filename(
+    app: AppHandle,
+    path: String,
+) -> Result<file_open::NormalizedName, Localized> {
+    let resolved = access::check(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || file_open::normalize_filename(&app, &resolved))
+        .await
+        .map_err(|e| format!("Renaming failed: {}", e))?
+        .map_err(Localized::from)
+}
+
+/// Open what is on the clipboard
+///
+/// Copied .msg/.eml files, or text naming them (plain or quoted paths, `file://` URIs), are
+/// opened as if passed by the OS. Pasted message source (text starting with a header block)
+/// and HTML content, wrapped into a minimal message, are written to the session temp
+/// directory as .eml and opened from there; their parse results have `from_clipboard` set.
+/// Returns the opened paths, which also arrive as `file-open` events.
+#[tauri::command]
+pub async fn open_from_clipboard(app: AppHandle) -> Result<Vec<String>, Localized> {
+    let contents = tauri::async_runtime::spawn_blocking(clipboard::read)
+        .await
+        .map_err(|e| format!("Failed to read the clipboard: {}", e))?;
+    let paths = match clipboard::classify(&contents) {
+        Some(clipboard::Openable::Files(paths)) => paths,
+        Some(clipboard::Openable::Source(data)) => {
+            vec![clipboard::write_source(
+                &app.state::<clipboard::ClipboardFiles>(),
+                &data,
+            )?]
+        }
+        None => return Err(Localized::new("error.nothing_on_clipboard", [])),
+    };
+    for path in &paths {
+        file_open::handle_file_open(&app, path.clone());
+    }
+    Ok(paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Answer a `forwarded-open-request`: files a second instance forwarded are allowed (the
+/// file alone, not its folder) once `accept`ed, and returned for opening; declined ones are
+/// forgotten. Paths that weren't forwarded are ignored.
+#[tauri::command]
+pub fn confirm_forwarded_files(
+    app: AppHandle,
+    state: tauri::State<'_, file_open::Forwards>,
+    paths: Vec<String>,
+    accept: bool,
+) -> Vec<String> {
+    state
+        .answer(&app.state::<access::AccessPolicy>(), &paths, accept)
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Ask the user whether the folder of a denied path may be accessed
+///
+/// Returns true once approved; the approval is kept in settings.
+#[tauri::command]
+pub async fn request_access(app: AppHandle, path: String) -> Result<bool, Localized> {
+    access::request(&app, &path).map_err(Localized::from)
+}
+
+/// Cancel a running job; returns false if it already finished
+#[tauri::command]
+pub fn cancel_job(state: tauri::State<'_, jobs::JobRegistry>, id: String) -> bool {
+    state.cancel(&id)
+}
+
+/// Get files that were passed to the app on startup
+///
+/// On the first call without startup files, the previous session's files are returned instead.
+#[tauri::command]
+pub fn get_pending_files(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, PendingFiles>,
+) -> Vec<String> {
+    let startup = file_open::startup(
+        &app.state::<WindowFiles>(),
+        &state,
+        &app.state::<session::SessionState>(),
+        &app.state::<access::AccessPolicy>(),
+        window.label(),
+        app.state::<SettingsStore>().get().bulk_open_threshold,
+    );
+    session::report_missing(&app, &startup.missing);
+    let files: Vec<String> = startup
+        .files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    // Too many files at once are offered as a bulk open instead
+    if startup.bulk {
+        open_queue::emit_bulk(&app, files);
+        return Vec::new();
+    }
+    files
+}
+
+/// Start watching a folder for new or changed message files
+#[tauri::command]
+pub fn watch_folder(
+    app: AppHandle,
+    state: tauri::State<'_, watcher::FolderWatchers>,
+    path: String,
+) -> Result<(), Localized> {
+    let dir = access::check(&app, &path)?;
+    watcher::watch(&app, &state, dir).map_err(Localized::from)
+}
+
+/// Stop watching a folder
+#[tauri::command]
+pub fn unwatch_folder(state: tauri::State<'_, watcher::FolderWatchers>, path: String) {
+    watcher::unwatch(&state, std::path::Path::new(&path));
+}
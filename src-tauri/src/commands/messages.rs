@@ -0,0 +1,859 @@
+//! Commands that parse messages and render their parts for the viewer
+
+use super::{
+    date_formatter, load_message, load_message_with, open_archive, record_audit,
+    remembered_encoding, EncodingChoice, LoadOptions,
+};
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{
+    access, archive, audit, auto_extract, avatars, bimi, clipboard, documents, export, find,
+    freshness, i18n, jobs, memory, notes, parser, prefetch, preview_view, previews, quarantine,
+    reader, reload, remote, source, view_state,
+};
+use std::path::Path;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Parse a message file and return its contents and attachment metadata
+///
+/// When the display preferences for the sender (or the global setting) allow remote images,
+/// they are fetched and inlined in `body_html` as `data:` URLs; trackers and images that
+/// fail to load keep their remote URL and stay blocked in the view.
+#[tauri::command]
+pub async fn parse_message(app: AppHandle, path: String) -> Result<parser::Message, Localized> {
+    let message = parse_for_view(&app, path.clone(), true).await?;
+    app.state::<documents::OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .set_parsed(Path::new(&path), || reload::Snapshot::of(&message));
+    Ok(message)
+}
+
+/// Prepare a message file before it is opened, while the pointer rests on its row in the
+/// folder listing: `light` caches its listing metadata and snippet, `warm` also parses it
+/// for `parse_message`, which then returns at once
+///
+/// Waits while interactive parses or other prefetches run. Cancel it with `cancel_job`
+/// once the pointer moves on; it then fails with `Cancelled`. `parse_message` returns the
+/// same with or without a prefetch.
+#[tauri::command]
+pub async fn prefetch_message(
+    app: AppHandle,
+    path: String,
+    level: prefetch::Level,
+    job_id: Option<String>,
+) -> Result<(), Localized> {
+    let job = jobs::Job::start(&app, job_id)?;
+    let lang = i18n::current();
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let prefetcher = app.state::<prefetch::Prefetcher>();
+        let _slot = prefetcher.wait_turn(|| job.check_cancelled())?;
+        let resolved = access::check(&app, &path)?;
+        app.state::<previews::PreviewCache>()
+            .get(&resolved, lang, || load_message(&app, &path));
+        if level == prefetch::Level::Warm {
+            job.check_cancelled()?;
+            if let Some(key) = view_key(&app, &path).filter(|key| !prefetcher.contains(key)) {
+                prefetcher.insert(key, load_message_with(&app, &path, &view_options())?);
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Prefetching failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// How `parse_message` loads a file: with the first
+/// [`parser::recipients::INLINE_RECIPIENTS`] of each recipient type
+fn view_options() -> LoadOptions {
+    LoadOptions {
+        recipient_window: Some(parser::recipients::Window {
+            offset: 0,
+            limit: parser::recipients::INLINE_RECIPIENTS,
+        }),
+        ..LoadOptions::default()
+    }
+}
+
+/// The file at `path` as it is now and what else `load_message_with` depends on: the
+/// settings, the charset remembered for the file and whether it came from the clipboard
+fn view_key(app: &AppHandle, path: &str) -> Option<prefetch::Key> {
+    let resolved = access::check(app, path).ok()?;
+    let settings = serde_json::to_string(&app.state::<SettingsStore>().get()).ok()?;
+    let encoding = remembered_encoding(app, &resolved);
+    let from_clipboard = app.state::<clipboard::ClipboardFiles>().contains(&resolved);
+    let fingerprint =
+        parser::sha256_hex(format!("{}\n{:?}\n{}", settings, encoding, from_clipboard).as_bytes());
+    prefetch::Key::of(&resolved, fingerprint)
+}
+
+/// `load_message_with` the view options, taken from the prefetched messages when the file
+/// was prefetched as it is now
+fn load_for_view(app: &AppHandle, path: &str) -> Result<parser::Message, String> {
+    let prefetched = view_key(app, path).and_then(|key| {
+        let message = app.state::<prefetch::Prefetcher>().get(&key)?;
+        Some((key, message))
+    });
+    let Some((key, message)) = prefetched else {
+        return load_message_with(app, path, &view_options());
+    };
+    // What parsing records
+    app.state::<freshness::Stamps>().record(&key.path);
+    app.state::<remote::KnownTrackers>()
+        .record(&message.trackers);
+    Ok(message)
+}
+
+/// Parse a message the way `parse_message` returns it (see [`view_options`]), running the
+/// auto-extraction rules on it when `extract` is set
+async fn parse_for_view(
+    app: &AppHandle,
+    path: String,
+    extract: bool,
+) -> Result<parser::Message, String> {
+    let prefetcher = app.state::<prefetch::Prefetcher>();
+    let _interactive = prefetcher.interactive();
+    let mut message = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || {
+            let message = load_for_view(&app, &path)?;
+            // Auto-extraction writes attachments out, which quarantine mode doesn't allow
+            if extract && !quarantine::active(&app) {
+                run_auto_extract(&app, &path, &message);
+            }
+            Ok::<_, String>(message)
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to read message: {}", e))??;
+    if message.display_preferences.load_remote_images && !quarantine::active(app) {
+        inline_remote_images(app, &mut message).await;
+    }
+    Ok(message)
+}
+
+/// Parse an open document again after its file was edited elsewhere, and tell the
+/// frontend with a `document-reloaded` event what changed since it was last shown
+///
+/// A changed file's cached snippet, source and attachments are dropped first, along with
+/// the contents of a zip archive. When the file no longer parses, the event carries the
+/// error instead and the last parse result stays the one reloads compare with.
+#[tauri::command]
+pub async fn reload_document(
+    app: AppHandle,
+    doc_id: String,
+) -> Result<reload::DocumentReloaded, Localized> {
+    let (path, previous) = app
+        .state::<documents::OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .get(&doc_id)
+        .map(|doc| (doc.path.clone(), doc.parsed.clone()))
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    let path_string = path.to_string_lossy().to_string();
+    let resolved = access::check(&app, &path_string)?;
+    tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        let resolved = resolved.clone();
+        move || match freshness::verify(&app, &resolved) {
+            freshness::Freshness::Fresh => {}
+            // Stale files were invalidated by the check
+            freshness::Freshness::Stale => app.state::<archive::Archives>().forget(&resolved),
+            freshness::Freshness::Unknown => {
+                freshness::invalidate(&app, &resolved);
+                app.state::<archive::Archives>().forget(&resolved);
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Reload failed: {}", e))?;
+
+    let reloaded = match parse_for_view(&app, path_string.clone(), true).await {
+        Ok(message) => {
+            refresh_preview(&app, &doc_id, &message);
+            let snapshot = reload::Snapshot::of(&message);
+            let diff = previous.map(|previous| previous.diff(&snapshot));
+            if diff.as_ref().is_some_and(reload::MessageDiff::is_empty) {
+                log_line!("Reloaded {} without changes", path_string);
+            }
+            let fingerprint = reader::read_all(&resolved)
+                .ok()
+                .map(|data| documents::Fingerprint::of(&data));
+            let documents = app.state::<documents::OpenDocuments>();
+            let mut registry = documents.0.lock().unwrap();
+            registry.set_parsed(&path, || snapshot);
+            if let Some(fingerprint) = fingerprint {
+                registry.set_fingerprint(&doc_id, fingerprint);
+            }
+            drop(registry);
+            reload::DocumentReloaded {
+                doc_id,
+                path: path_string,
+                message: Some(message),
+                diff,
+                error: None,
+            }
+        }
+        Err(error) => {
+            log_line!("Reloading {} failed: {}", path_string, error);
+            reload::DocumentReloaded {
+                doc_id,
+                path: path_string,
+                message: None,
+                diff: None,
+                error: Some(Localized::from(error)),
+            }
+        }
+    };
+    if let Err(e) = app.emit("document-reloaded", &reloaded) {
+        log_line!("Failed to emit document-reloaded: {}", e);
+    }
+    Ok(reloaded)
+}
+
+/// Where a preview webview goes in its window, in logical pixels
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct PreviewBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Show the body of an open document in a sandboxed child webview of the window
+/// `parent_label`, and return the webview's label (`preview-<doc_id>`)
+///
+/// The webview is placed at `bounds`, or stays hidden until `set_preview_bounds` places
+/// it, and replaces an earlier preview of the document. It can't navigate, open windows
+/// or download, and the only command it may invoke is `preview_message`: the content
+/// size and link clicks it reports reach the parent window as `preview-message` events.
+/// When it crashes, create it again.
+#[tauri::command]
+pub async fn create_preview_view(
+    app: AppHandle,
+    doc_id: String,
+    parent_label: String,
+    bounds: Option<PreviewBounds>,
+) -> Result<String, Localized> {
+    let path = app
+        .state::<documents::OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .get(&doc_id)
+        .map(|doc| doc.path.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    let parent = app
+        .get_window(&parent_label)
+        .ok_or_else(|| format!("Unknown window: {}", parent_label))?;
+    let message = parse_for_view(&app, path, false).await?;
+
+    let views = app.state::<preview_view::PreviewViews>();
+    if let Some(old) = views.insert(&doc_id, &parent_label, &message) {
+        close_webview(&app, &old.label);
+    }
+    let label = preview_view::label(&doc_id);
+    let builder = tauri::webview::WebviewBuilder::new(
+        &label,
+        tauri::WebviewUrl::CustomProtocol(preview_view::url(&doc_id)?),
+    )
+    .incognito(true)
+    .focused(false)
+    .on_navigation({
+        let doc_id = doc_id.clone();
+        move |url| preview_view::allowed_navigation(&doc_id, url)
+    })
+    .on_new_window(|_, _| tauri::webview::NewWindowResponse::Deny)
+    .on_download(|_, _| false);
+    let (position, size) = match bounds {
+        Some(b) => ((b.x, b.y), (b.width.max(1.0), b.height.max(1.0))),
+        None => ((0.0, 0.0), (1.0, 1.0)),
+    };
+    let webview = parent
+        .add_child(
+            builder,
+            tauri::LogicalPosition::new(position.0, position.1),
+            tauri::LogicalSize::new(size.0, size.1),
+        )
+        .map_err(|e| {
+            views.remove(&doc_id);
+            format!("Failed to create the preview: {}", e)
+        })?;
+    if bounds.is_none() {
+        let _ = webview.hide();
+    }
+    Ok(label)
+}
+
+/// Move and resize the preview webview of a document, showing it; the frontend calls
+/// this whenever the element standing in for it changes place
+#[tauri::command]
+pub fn set_preview_bounds(
+    app: AppHandle,
+    doc_id: String,
+    bounds: PreviewBounds,
+) -> Result<(), Localized> {
+    let view = app
+        .state::<preview_view::PreviewViews>()
+        .get(&doc_id)
+        .ok_or_else(|| format!("No preview for document: {}", doc_id))?;
+    let webview = app
+        .get_webview(&view.label)
+        .ok_or_else(|| format!("No preview for document: {}", doc_id))?;
+    webview
+        .set_bounds(tauri::Rect {
+            position: tauri::LogicalPosition::new(bounds.x, bounds.y).into(),
+            size: tauri::LogicalSize::new(bounds.width.max(1.0), bounds.height.max(1.0)).into(),
+        })
+        .and_then(|()| webview.show())
+        .map_err(|e| format!("Failed to place the preview: {}", e).into())
+}
+
+/// Close the preview webview of a document; nothing to do when it has none
+#[tauri::command]
+pub fn destroy_preview_view(app: AppHandle, doc_id: String) {
+    if let Some(view) = app.state::<preview_view::PreviewViews>().remove(&doc_id) {
+        close_webview(&app, &view.label);
+    }
+}
+
+/// What a preview page reports, relayed to its parent window as a `preview-message`
+/// event. Invoked by the page's own script only.
+#[tauri::command]
+pub fn preview_message(
+    app: AppHandle,
+    webview: tauri::Webview,
+    message: preview_view::PreviewMessage,
+) -> Result<(), Localized> {
+    let (doc_id, parent) = app
+        .state::<preview_view::PreviewViews>()
+        .by_label(webview.label())
+        .ok_or_else(|| format!("Not a preview: {}", webview.label()))?;
+    app.emit_to(
+        tauri::EventTarget::window(parent),
+        "preview-message",
+        preview_view::PreviewEvent { doc_id, message },
+    )
+    .map_err(|e| format!("Failed to emit preview-message: {}", e).into())
+}
+
+/// Serve the reloaded message to the document's preview webview, if it has one
+fn refresh_preview(app: &AppHandle, doc_id: &str, message: &parser::Message) {
+    let views = app.state::<preview_view::PreviewViews>();
+    let Some(view) = views.get(doc_id) else {
+        return;
+    };
+    views.insert(doc_id, &view.parent, message);
+    match app.get_webview(&view.label) {
+        Some(webview) => {
+            if let Err(e) = webview.reload() {
+                log_line!("Failed to reload {}: {}", view.label, e);
+            }
+        }
+        None => {
+            views.remove(doc_id);
+        }
+    }
+}
+
+/// Close a webview the app no longer tracks; one that crashed may be gone already
+fn close_webview(app: &AppHandle, label: &str) {
+    if let Some(webview) = app.get_webview(label) {
+        if let Err(e) = webview.close() {
+            log_line!("Failed to close {}: {}", label, e);
+        }
+    }
+}
+
+/// Decode the bodies of a message file again with the charset `encoding` (one of
+/// `list_supported_encodings`), or with `null` as declared or detected, like a browser's
+/// View → Encoding menu
+///
+/// Only the bodies and the charset decisions come back; the message as other commands
+/// parse it stays as it was unless `remember` is set. That keeps the choice (or with
+/// `null` drops it) for the file's content, so opening the file again decodes it that way.
+#[tauri::command]
+pub async fn reparse_with_encoding(
+    app: AppHandle,
+    path: String,
+    encoding: Option<String>,
+    remember: Option<bool>,
+) -> Result<parser::RedecodedBodies, Localized> {
+    let encoding = encoding
+        .map(|label| {
+            parser::charset::canonical_name(&label)
+                .ok_or_else(|| format!("Unsupported charset: {}", label.trim()))
+        })
+        .transpose()?;
+    let load = LoadOptions {
+        encoding: match encoding {
+            Some(label) => EncodingChoice::Use(label.to_string()),
+            None => EncodingChoice::Detect,
+        },
+        ..LoadOptions::default()
+    };
+    let mut message = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        let path = path.clone();
+        move || load_message_with(&app, &path, &load)
+    })
+    .await
+    .map_err(|e| format!("Failed to read message: {}", e))??;
+    if message.display_preferences.load_remote_images && !quarantine::active(&app) {
+        inline_remote_images(&app, &mut message).await;
+    }
+
+    if remember.unwrap_or(false) {
+        let resolved = access::check(&app, &path)?;
+        let key = tauri::async_runtime::spawn_blocking({
+            let resolved = resolved.clone();
+            move || notes::content_hash(&resolved)
+        })
+        .await
+        .map_err(|e| format!("Hashing failed: {}", e))??;
+        app.state::<view_state::ViewStates>()
+            .set_encoding(&key, encoding.map(str::to_string))?;
+        // Snippets and synthesized source were decoded the old way
+        app.state::<previews::PreviewCache>().invalidate(&resolved);
+        app.state::<source::SourceCache>().invalidate(&resolved);
+    }
+    Ok(parser::RedecodedBodies::from(message))
+}
+
+/// Charsets `reparse_with_encoding` accepts, by the names the parser reports in
+/// `body_encodings`
+#[tauri::command]
+pub fn list_supported_encodings() -> Vec<&'static str> {
+    parser::charset::supported()
+}
+
+/// Page through the recipients of one type (`to`, `cc` or `bcc`), for messages parsed
+/// with `recipients_truncated`
+///
+/// At most [`parser::recipients::MAX_PAGE`] recipients come back per call, starting at
+/// `offset`; the page's `total` is the length of the whole list.
+#[tauri::command]
+pub async fn get_all_recipients(
+    app: AppHandle,
+    path: String,
+    kind: parser::recipients::RecipientType,
+    offset: usize,
+    limit: usize,
+) -> Result<parser::recipients::RecipientPage, Localized> {
+    let load = LoadOptions {
+        recipient_window: Some(parser::recipients::Window {
+            offset,
+            limit: limit.min(parser::recipients::MAX_PAGE),
+        }),
+        ..LoadOptions::default()
+    };
+    let message = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || load_message_with(&app, &path, &load)
+    })
+    .await
+    .map_err(|e| format!("Failed to read message: {}", e))??;
+    Ok(parser::recipients::page(&message, kind, offset))
+}
+
+/// Apply the enabled auto-extract rules to a message just opened, recording what was
+/// written in the audit log and telling the frontend with an `auto-extracted` event;
+/// failures don't keep the message from opening
+fn run_auto_extract(app: &AppHandle, path: &str, message: &parser::Message) {
+    let settings = app.state::<SettingsStore>().get();
+    let rules: Vec<&auto_extract::AutoExtractRule> = settings
+        .auto_extract_rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .collect();
+    if rules.is_empty() {
+        return;
+    }
+    let Ok(resolved) = access::check(app, path) else {
+        return;
+    };
+    let result = auto_extract::run(&rules, &resolved, message, false, |dir| {
+        access::check(app, &dir.to_string_lossy())
+    });
+    for extraction in &result.extracted {
+        let recorded = record_audit(app, || {
+            audit::AuditEntry::new(
+                audit::AuditAction::AutoExtracted,
+                Some(&resolved),
+                &extraction.file_name,
+                &extraction.sha256,
+            )
+            .to(std::path::Path::new(&extraction.path))
+        });
+        if let Err(e) = recorded {
+            log_line!("Failed to record auto-extracted {}: {}", extraction.path, e);
+        }
+    }
+    for failure in &result.failed {
+        log_line!(
+            "Auto-extract rule {:?} failed: {}",
+            failure.rule,
+            failure.error
+        );
+    }
+    if !result.is_empty() {
+        if let Err(e) = app.emit("auto-extracted", &result) {
+            log_line!("Failed to emit auto-extracted event: {}", e);
+        }
+    }
+}
+
+/// Most remote images fetched for one message
+const MAX_INLINED_IMAGES: usize = 100;
+
+/// Fetch the remote images of the HTML body, except trackers, and inline them
+async fn inline_remote_images(app: &AppHandle, message: &mut parser::Message) {
+    let Some(html) = &message.body_html else {
+        return;
+    };
+    let patterns = app.state::<SettingsStore>().get().tracker_patterns;
+    let fetches: Vec<_> = parser::remote_images::image_urls(html)
+        .into_iter()
+        .filter(|url| {
+            !message.trackers.iter().any(|tracker| tracker.url == *url)
+                && !parser::trackers::is_known_tracker(url, &patterns)
+        })
+        .take(MAX_INLINED_IMAGES)
+        .map(|url| {
+            tauri::async_runtime::spawn(async move {
+                let fetched = remote::fetch_image(&url).await;
+                (url, fetched)
+            })
+        })
+        .collect();
+    let mut data_urls = std::collections::HashMap::new();
+    for fetch in fetches {
+        match fetch.await {
+            Ok((url, Ok(data_url))) => {
+                data_urls.insert(url, data_url);
+            }
+            Ok((url, Err(e))) => log_line!("Failed to inline {}: {}", url, e),
+            Err(e) => log_line!("Failed to inline a remote image: {}", e),
+        }
+    }
+    if !data_urls.is_empty() {
+        message.body_html = Some(parser::remote_images::inline(html, &data_urls));
+    }
+}
+
+/// Return the body of a message file as a semantic structure for screen readers
+///
+/// HTML bodies become headings, paragraphs, lists, tables with the header cells labelling
+/// each cell, links with their checked targets, and quotes with their nesting level, keeping
+/// only what the reading pane's sanitizer lets through; layout tables are flattened. Text
+/// bodies become paragraphs and quotes. Attached messages are addressed through `part_path`
+/// (see `open_nested_message`).
+#[tauri::command]
+pub fn get_accessible_body(
+    app: AppHandle,
+    path: String,
+    part_path: Option<Vec<usize>>,
+) -> Result<Vec<parser::accessible::Block>, Localized> {
+    let message = load_message(&app, &path)?.into_nested(&part_path.unwrap_or_default())?;
+    Ok(parser::accessible::from_message(&message))
+}
+
+/// Report what in the HTML body of a message file renders differently than in Outlook
+///
+/// Covers MSO conditional comments (with what they contain), VML elements, web fonts and
+/// external stylesheets, tables wider than the reading pane, and the elements, attributes
+/// and inline styles the sanitizer removes. The body is analyzed as the message carries
+/// it, before sanitizing; messages without an HTML body report nothing.
+#[tauri::command]
+pub fn analyze_rendering(
+    app: AppHandle,
+    path: String,
+    part_path: Option<Vec<usize>>,
+) -> Result<parser::rendering::RenderingReport, Localized> {
+    let message = load_message(&app, &path)?.into_nested(&part_path.unwrap_or_default())?;
+    Ok(parser::rendering::analyze(
+        message.body_html.as_deref().unwrap_or_default(),
+    ))
+}
+
+/// Return a window of the source of a message file, at most 1 MiB from `offset`
+///
+/// For .eml files the source is the file; for .msg files, which have none, it is a labelled
+/// pseudo-source of the transport headers, the decoded bodies and the attachment list
+/// (`synthesized` is set). Base64 bodies of binary parts show as
+/// `[... N bytes of base64 ...]` unless `raw: true`, and `normalize_line_endings: true` turns
+/// CRLF into LF. Offsets count bytes of the source as presented with these options.
+#[tauri::command]
+pub async fn get_message_source(
+    app: AppHandle,
+    path: String,
+    offset: usize,
+    length: usize,
+    raw: Option<bool>,
+    normalize_line_endings: Option<bool>,
+) -> Result<source::SourceWindow, Localized> {
+    let options = source::SourceOptions {
+        raw: raw.unwrap_or(false),
+        normalize_line_endings: normalize_line_endings.unwrap_or(false),
+    };
+    let source = message_source(app, path, options).await?;
+    Ok(source.window(offset, length))
+}
+
+/// Find `query` in the source of a message file, as `get_message_source` presents it with
+/// the same options; returns the byte offsets of the matches (ignoring ASCII case unless
+/// `match_case: true`)
+#[tauri::command]
+pub async fn search_in_source(
+    app: AppHandle,
+    path: String,
+    query: String,
+    raw: Option<bool>,
+    normalize_line_endings: Option<bool>,
+    match_case: Option<bool>,
+) -> Result<source::SourceMatches, Localized> {
+    let options = source::SourceOptions {
+        raw: raw.unwrap_or(false),
+        normalize_line_endings: normalize_line_endings.unwrap_or(false),
+    };
+    let source = message_source(app, path, options).await?;
+    Ok(source.search(&query, match_case.unwrap_or(false)))
+}
+
+/// Find `query` in an open document: its header fields, both bodies, attachment names and
+/// the subjects of attached messages, including what the view has collapsed
+///
+/// `options` can make the search case sensitive, match whole words only, or take `query`
+/// as a regular expression; an invalid or too complex pattern fails with
+/// `Invalid search pattern`. Matches come field by field in a fixed order, so calling
+/// again on the unchanged document gives the same list for next/previous navigation.
+#[tauri::command]
+pub async fn find_in_message(
+    app: AppHandle,
+    doc_id: String,
+    query: String,
+    options: Option<find::FindOptions>,
+) -> Result<find::FindResult, Localized> {
+    let path = app
+        .state::<documents::OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .get(&doc_id)
+        .map(|doc| doc.path.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let message = load_message(&app, &path)?;
+        find::find(&message, &query, &options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| format!("Search failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+async fn message_source(
+    app: AppHandle,
+    path: String,
+    options: source::SourceOptions,
+) -> Result<std::sync::Arc<source::Source>, String> {
+    let resolved = access::check(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = app
+            .state::<source::SourceCache>()
+            .get(&resolved, options, || load_message(&app, &path));
+        memory::enforce(&app);
+        source
+    })
+    .await
+    .map_err(|e| format!("Failed to read source: {}", e))?
+}
+
+/// Fetch a remote image of a message body as a `data:` URL
+///
+/// Tracking images (reported in `trackers` of a parsed message, or matching a tracker pattern)
+/// stay blocked and yield `None`, even when remote content is allowed, unless `load_trackers` is set.
+#[tauri::command]
+pub async fn fetch_remote_image(
+    app: AppHandle,
+    url: String,
+    load_trackers: Option<bool>,
+) -> Result<Option<String>, Localized> {
+    if !load_trackers.unwrap_or(false) {
+        let patterns = app.state::<SettingsStore>().get().tracker_patterns;
+        if app.state::<remote::KnownTrackers>().contains(&url)
+            || parser::trackers::is_known_tracker(&url, &patterns)
+        {
+            log_line!("Blocked tracking image {}", url);
+            return Ok(None);
+        }
+    }
+    remote::fetch_image(&url)
+        .await
+        .map(Some)
+        .map_err(Localized::from)
+}
+
+/// What to show as the avatar of a message's sender, without network access unless
+/// `allow_remote`: the photo of an attached vCard for the sender, the sender's BIMI logo
+/// (embedded by the receiving server, or fetched with `allow_remote`; only for
+/// authenticated senders and SVG Tiny PS logos), otherwise initials on a color derived
+/// from the address. Results are cached per address.
+#[tauri::command]
+pub async fn get_sender_avatar(
+    app: AppHandle,
+    path: String,
+    allow_remote: Option<bool>,
+) -> Result<avatars::Avatar, Localized> {
+    // Quarantine mode keeps the avatar offline
+    let allow_remote = allow_remote.unwrap_or(false) && !quarantine::active(&app);
+    let message = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || load_message(&app, &path)
+    })
+    .await
+    .map_err(|e| format!("Failed to read message: {}", e))??;
+    let email = avatars::sender_email(&message);
+    let cache = app.state::<avatars::AvatarCache>();
+    if let Some(avatar) = email
+        .as_deref()
+        .and_then(|email| cache.get(email, allow_remote))
+    {
+        return Ok(avatar);
+    }
+
+    let avatar = match avatars::lookup(&message) {
+        avatars::Lookup::Found(avatar) => avatar,
+        avatars::Lookup::FetchLogo(url) if allow_remote => {
+            let logo = remote::fetch_image_bytes(&url, bimi::MAX_SVG_BYTES)
+                .await
+                .and_then(|(_, svg)| bimi::logo_data_url(&svg));
+            match logo {
+                Ok(data_url) => avatars::Avatar::Bimi { data_url },
+                Err(e) => {
+                    log_line!("Ignoring BIMI logo {}: {}", url, e);
+                    avatars::initials(&message)
+                }
+            }
+        }
+        avatars::Lookup::FetchLogo(_) => avatars::initials(&message),
+    };
+    if let Some(email) = &email {
+        cache.insert(email, avatar.clone(), allow_remote);
+    }
+    Ok(avatar)
+}
+
+/// Render the From/Sent/To/Cc/Subject/Attachments block of a message file as `html` or
+/// `text`, as text exports write it, for printing and other exports
+///
+/// Dates follow the date settings; values are escaped in HTML.
+#[tauri::command]
+pub fn render_header_block(
+    app: AppHandle,
+    path: String,
+    format: export::HeaderFormat,
+) -> Result<String, Localized> {
+    let message = load_message(&app, &path)?;
+    let lang = i18n::current();
+    Ok(export::header_block(
+        &message,
+        format,
+        lang,
+        &date_formatter(&app, lang),
+    ))
+}
+
+/// Content hash of a message file, the key of its notes, tags and view state
+#[tauri::command]
+pub async fn get_content_hash(app: AppHandle, path: String) -> Result<String, Localized> {
+    let path = access::check(&app, &path)?;
+    let key = tauri::async_runtime::spawn_blocking({
+        let path = path.clone();
+        move || notes::content_hash(&path)
+    })
+    .await
+    .map_err(|e| format!("Hashing failed: {}", e))??;
+    app.state::<notes::Notes>().record_path(&key, &path);
+    Ok(key)
+}
+
+/// The part structure of a message file: the MIME tree of an EML file (the bodies and
+/// attachments of an MSG file), with content types and parameters, transfer encodings,
+/// dispositions, sizes and content ids, which part is displayed and why
+#[tauri::command]
+pub async fn get_mime_tree(
+    app: AppHandle,
+    path: String,
+) -> Result<parser::mime_tree::MimeTree, Localized> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let resolved = access::check(&app, &path)?;
+        let (format, data) = if archive::is_archive(&resolved) {
+            let unpacked = open_archive(&app, &resolved)?;
+            (unpacked.format, unpacked.data.clone())
+        } else {
+            let format = parser::MessageFormat::from_path(&resolved)
+                .ok_or_else(|| format!("Unsupported file type: {}", resolved.display()))?;
+            (format, reader::read_all(&resolved)?)
+        };
+        parser::mime_tree::build(format, &data, || load_message(&app, &path))
+    })
+    .await
+    .map_err(|e| format!("Reading the structure failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// Where the bytes of a message file go: headers, recipients, each body, each attachment
+/// and the container around them, adding up to the file size (for a message in an archive,
+/// the size unpacked); see `parser::sizes`
+#[tauri::command]
+pub async fn get_size_breakdown(
+    app: AppHandle,
+    path: String,
+) -> Result<parser::sizes::SizeBreakdown, Localized> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let resolved = access::check(&app, &path)?;
+        let (format, data) = if archive::is_archive(&resolved) {
+            let unpacked = open_archive(&app, &resolved)?;
+            (unpacked.format, unpacked.data.clone())
+        } else {
+            let format = parser::MessageFormat::from_path(&resolved)
+                .ok_or_else(|| format!("Unsupported file type: {}", resolved.display()))?;
+            (format, reader::read_all(&resolved)?)
+        };
+        parser::sizes::breakdown(format, &data, || load_message(&app, &path))
+    })
+    .await
+    .map_err(|e| format!("Measuring the message failed: {}", e))?
+    .map_err(Localized::from)
+}
+
+/// The spam and phishing verdicts mail gateways left in a message's headers, normalized
+/// (see `parser::gateway`)
+#[tauri::command]
+pub async fn get_gateway_verdicts(
+    app: AppHandle,
+    path: String,
+) -> Result<Vec<parser::gateway::GatewayVerdict>, Localized> {
+    tauri::async_runtime::spawn_blocking(move || load_message(&app, &path))
+        .await
+        .map_err(|e| format!("Parsing failed: {}", e))?
+        .map(|message| message.gateway_verdicts)
+        .map_err(Localized::from)
+}
+
+/// Whether a message file is unchanged since it was last parsed, to ask before trusting
+/// results cached for it: `fresh`, `stale` (its cached listing metadata, snippet, source and
+/// attachments were dropped and `cache-invalidated` was emitted) or `unknown`
+#[tauri::command]
+pub async fn verify_freshness(
+    app: AppHandle,
+    path: String,
+) -> Result<freshness::Freshness, Localized> {
+    let path = access::check(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || freshness::verify(&app, &path))
+        .await
+        .map_err(|e| Localized::from(format!("Freshness check failed: {}", e)))
+}
@@ -0,0 +1,236 @@
+//! The commands the webview can invoke, by area. `handler` is built from `registry.rs`,
+//! which also assigns every command its permission group (see `permissions`); a command
+//! that isn't listed there can't be called.
+
+mod annotations;
+mod attachments;
+mod diagnostics;
+mod documents;
+mod drafts;
+mod exports;
+mod files;
+mod messages;
+mod senders;
+mod settings;
+mod workspaces;
+
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{
+    access, archive, audit, clipboard, formatting, freshness, i18n, notes, parser, permissions,
+    previews, quarantine, remote, view_state, volumes,
+};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Expands the registry into `GROUPS` and the invoke handler
+macro_rules! registry {
+    ($($group:literal: $description:literal => [$($module:ident::$command:ident),* $(,)?]),* $(,)?) => {
+        /// Permission groups and their commands
+        pub const GROUPS: &[(&str, &[&str])] = &[$(($group, &[$(stringify!($command)),*])),*];
+
+        /// Every command, for `Builder::invoke_handler`; each goes through `gate` first
+        pub fn handler() -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+            gated(tauri::generate_handler![$($($module::$command),*),*])
+        }
+    };
+}
+
+include!("registry.rs");
+
+/// The runtime checks every command is refused by before it runs: those of the gated
+/// permission groups until they are switched on, and those leaving the app in quarantine
+/// mode
+fn gate(app: &AppHandle, command: &str) -> Result<(), String> {
+    permissions::require(app, command)?;
+    quarantine::require(app, command)
+}
+
+/// `commands`, refusing those `gate` refuses without running them
+fn gated(
+    commands: impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let app = invoke.message.webview_ref().app_handle().clone();
+        if let Err(e) = gate(&app, invoke.message.command()) {
+            invoke.resolver.reject(Localized::from(e));
+            return true;
+        }
+        commands(invoke)
+    }
+}
+
+/// Append an audit entry while the `audit_log` setting is on. Runs before the attachment
+/// is handed on, so the entry exists whatever happens next; when it can't be written the
+/// attachment isn't released.
+fn record_audit(app: &AppHandle, entry: impl FnOnce() -> audit::AuditEntry) -> Result<(), String> {
+    if !app.state::<SettingsStore>().get().audit_log {
+        return Ok(());
+    }
+    app.state::<audit::AuditLog>()
+        .append(&entry())
+        .map_err(|e| format!("Audit log: {}", e))
+}
+
+/// Parse a message file, unwrapping journal reports unless disabled in settings
+pub(crate) fn load_message(app: &AppHandle, path: &str) -> Result<parser::Message, String> {
+    load_message_with(app, path, &LoadOptions::default())
+}
+
+/// How [`load_message_with`] parses a file, beyond what the settings say
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoadOptions {
+    /// Keep only this window of each recipient list
+    pub recipient_window: Option<parser::recipients::Window>,
+    pub encoding: EncodingChoice,
+}
+
+/// Which charset the bodies of a loaded message are decoded with
+#[derive(Debug, Clone, Default)]
+pub(crate) enum EncodingChoice {
+    /// The one remembered for the file, else the one preferred for the sender, else the
+    /// declared or detected one
+    #[default]
+    Preferred,
+    /// The declared or detected one, whatever was remembered
+    Detect,
+    Use(String),
+}
+
+/// [`load_message`] with `load` options
+pub(crate) fn load_message_with(
+    app: &AppHandle,
+    path: &str,
+    load: &LoadOptions,
+) -> Result<parser::Message, String> {
+    let settings = app.state::<SettingsStore>().get();
+    let options = parser::ParseOptions {
+        recipient_window: load.recipient_window,
+        trust_declared_charset: settings.trust_declared_charset,
+        keep_mojibake: !settings.repair_mojibake,
+        tracker_patterns: settings.tracker_patterns,
+        annotate_wrapped_links: settings.show_unwrapped_link_titles,
+        limits: parser::limits::Limits {
+            allocation_factor: settings.parse_allocation_factor.max(1),
+            time_budget: std::time::Duration::from_secs(settings.parse_time_budget_secs),
+            ..parser::limits::Limits::default()
+        },
+        ..parser::ParseOptions::default()
+    };
+    let resolved = access::check(app, path)?;
+    app.state::<freshness::Stamps>().record(&resolved);
+    let parse = |options: &parser::ParseOptions| -> Result<parser::Message, String> {
+        let mut message = if archive::is_archive(&resolved) {
+            let unpacked = open_archive(app, &resolved)?;
+            parser::parse_bytes(unpacked.format, &unpacked.data, options)?
+        } else {
+            parser::parse_file(&resolved, options)?
+        };
+        if settings.unwrap_journal_reports {
+            message = parser::journal::unwrap(message, options);
+        }
+        Ok(message)
+    };
+    let chosen = match &load.encoding {
+        EncodingChoice::Preferred => remembered_encoding(app, &resolved),
+        EncodingChoice::Detect => None,
+        EncodingChoice::Use(label) => Some(label.clone()),
+    };
+    // A read failing halfway when a volume goes away is only seen here
+    let mut message = parse(&parser::ParseOptions {
+        charset_override: chosen.clone(),
+        ..options.clone()
+    })
+    .map_err(|e| volumes::report(app, e))?;
+    let preferences = settings.sender_preferences.resolve(
+        &message,
+        parser::DisplayPreferences {
+            load_remote_images: settings.load_remote_images,
+            collapse_quotes: settings.collapse_quotes,
+            ..parser::DisplayPreferences::default()
+        },
+    );
+    // The sender is only known once the message is parsed
+    let preferred = preferences
+        .encoding_override
+        .clone()
+        .filter(|_| chosen.is_none() && matches!(load.encoding, EncodingChoice::Preferred));
+    if let Some(label) = &preferred {
+        message = parse(&parser::ParseOptions {
+            charset_override: Some(label.clone()),
+            ..options.clone()
+        })
+        .map_err(|e| volumes::report(app, e))?;
+    }
+    message.charset_override = chosen.or(preferred);
+    message.display_preferences = preferences;
+    message.from_clipboard = app.state::<clipboard::ClipboardFiles>().contains(&resolved);
+    message.sender_trust = settings.sender_rules.evaluate(&message);
+    message.sender_flags = parser::spoofing::analyze(&message, &settings.sender_checks);
+    app.state::<remote::KnownTrackers>()
+        .record(&message.trackers);
+    Ok(message)
+}
+
+/// The charset remembered for the file at `path` with `reparse_with_encoding`
+fn remembered_encoding(app: &AppHandle, path: &std::path::Path) -> Option<String> {
+    let states = app.state::<view_state::ViewStates>();
+    if !states.has_encodings() {
+        return None;
+    }
+    notes::content_hash(path)
+        .ok()
+        .and_then(|key| states.encoding(&key))
+}
+
+/// The message in a zip archive; for an encrypted one not unlocked yet, asks the frontend
+/// for the password with a `needs-archive-password` event and fails
+fn open_archive(
+    app: &AppHandle,
+    path: &std::path::Path,
+) -> Result<std::sync::Arc<archive::Unpacked>, String> {
+    let archives = app.state::<archive::Archives>();
+    match archives.open(path)? {
+        archive::Opened::Ready(unpacked) => Ok(unpacked),
+        archive::Opened::Locked(entry) => {
+            let doc_id = app
+                .state::<crate::documents::OpenDocuments>()
+                .0
+                .lock()
+                .unwrap()
+                .list()
+                .iter()
+                .find(|doc| doc.path == path)
+                .map(|doc| doc.id.clone());
+            let request = archive::PasswordRequest {
+                path: path.to_string_lossy().to_string(),
+                doc_id,
+                entry: entry.name,
+                encryption: entry.encryption.unwrap_or(archive::Encryption::ZipCrypto),
+                attempts_left: archives.attempts_left(path),
+            };
+            if let Err(e) = app.emit("needs-archive-password", request) {
+                log_line!("Failed to emit needs-archive-password: {}", e);
+            }
+            Err(format!(
+                "{}: {}",
+                archive::PASSWORD_REQUIRED,
+                path.display()
+            ))
+        }
+    }
+}
+
+fn folder_preview(
+    app: &AppHandle,
+    path: &std::path::Path,
+    lang: i18n::Lang,
+) -> previews::MessagePreview {
+    app.state::<previews::PreviewCache>()
+        .get(path, lang, || load_message(app, &path.to_string_lossy()))
+}
+
+/// Date formatting of exports, from the settings
+fn date_formatter(app: &AppHandle, lang: i18n::Lang) -> formatting::DateFormatter {
+    let settings = app.state::<SettingsStore>().get();
+    formatting::DateFormatter::new(settings.date_format, settings.date_time_zone, lang)
+}
@@ -0,0 +1,132 @@
+// The commands the webview can invoke, by permission group. Included by `commands` for the
+// invoke handler and the runtime checks, and by the build script, which generates the ACL
+// permission sets in `permissions/groups.toml` from it.
+registry! {
+    "files-read": "Read message files and folders, and the messages in them" => [
+        files::read_file_as_bytes,
+        files::get_message_previews,
+        files::list_messages_in_dir,
+        files::get_index_page,
+        files::open_from_clipboard,
+        files::confirm_forwarded_files,
+        files::request_access,
+        files::cancel_job,
+        files::get_pending_files,
+        files::watch_folder,
+        files::unwatch_folder,
+        messages::parse_message,
+        messages::prefetch_message,
+        messages::get_accessible_body,
+        messages::analyze_rendering,
+        messages::get_message_source,
+        messages::search_in_source,
+        messages::find_in_message,
+        messages::fetch_remote_image,
+        messages::get_sender_avatar,
+        messages::render_header_block,
+        messages::get_content_hash,
+        messages::get_mime_tree,
+        messages::get_size_breakdown,
+        messages::get_gateway_verdicts,
+        messages::verify_freshness,
+        messages::reload_document,
+        messages::get_all_recipients,
+        messages::reparse_with_encoding,
+        messages::create_preview_view,
+        senders::import_sender_rules,
+        settings::import_settings,
+        workspaces::import_workspace,
+    ],
+    "files-write": "Save and export messages, attachments and reports to files" => [
+        files::save_file_with_dialog,
+        files::export_index_csv,
+        files::verify_archive,
+        files::normalize_filename,
+        drafts::finalize_draft,
+        drafts::generate_calendar_response,
+        attachments::export_attachments,
+        attachments::export_attachments_matching,
+        attachments::save_attachment,
+        exports::export_as_txt,
+        exports::export_html_archive,
+        exports::export_recipients_csv,
+        exports::export_mbox,
+        exports::export_review_report,
+        exports::export_redacted,
+        senders::export_sender_rules,
+        settings::export_settings,
+        workspaces::export_workspace,
+        diagnostics::export_audit_log,
+        diagnostics::create_diagnostics_bundle,
+    ],
+    "attachments-open": "Preview and open attachments, in the app or the system viewer" => [
+        files::open_file_with_system,
+        attachments::get_attachment_data_uri,
+        attachments::preview_auto_extract,
+        attachments::open_nested_message,
+        attachments::dedupe_attachments_report,
+    ],
+    "system-integration": "Hand files to other applications installed on the system" => [
+        files::list_applications_for,
+        files::open_with,
+    ],
+    "crypto": "Decrypt password-protected archives" => [
+        files::provide_archive_password,
+    ],
+    "app": "Settings, documents, drafts, workspaces, notes, tags, sender rules and diagnostics" => [
+        exports::get_date_format_preview,
+        senders::add_trusted_sender,
+        senders::add_blocked_sender,
+        senders::list_sender_rules,
+        senders::set_sender_preference,
+        senders::list_sender_preferences,
+        senders::remove_sender_preference,
+        messages::list_supported_encodings,
+        workspaces::save_workspace,
+        workspaces::list_workspaces,
+        workspaces::delete_workspace,
+        workspaces::open_workspace,
+        documents::open_in_new_window,
+        documents::register_document,
+        documents::close_document,
+        documents::set_active_document,
+        documents::retry_document,
+        documents::list_documents,
+        documents::get_memory_report,
+        documents::unload_document_caches,
+        drafts::create_draft_from,
+        drafts::update_draft,
+        drafts::list_drafts,
+        drafts::discard_draft,
+        messages::set_preview_bounds,
+        messages::destroy_preview_view,
+        settings::get_system_theme,
+        settings::get_settings,
+        settings::update_settings,
+        settings::set_locale,
+        settings::save_view_state,
+        settings::get_view_state,
+        settings::run_cleanup_now,
+        settings::get_storage_paths,
+        settings::create_sample_message,
+        settings::get_platform_capabilities,
+        settings::clear_view_states,
+        settings::set_command_group,
+        settings::choose_temp_dir,
+        settings::get_security_mode,
+        settings::set_quarantine_mode,
+        annotations::set_message_note,
+        annotations::get_message_note,
+        annotations::list_annotated_messages,
+        annotations::add_tag,
+        annotations::tag_many,
+        annotations::remove_tag,
+        annotations::list_tags,
+        annotations::query_by_tags,
+        diagnostics::get_audit_log,
+        diagnostics::run_self_test,
+    ],
+    "preview-bridge": "Report layout and link clicks from a sandboxed message preview" => [
+        messages::preview_message,
+    ],
+}
@@ -0,0 +1,126 @@
+//! Commands for sender rules and per-sender display preferences
+
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{persist, sender_prefs, sender_rules};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+/// Trust senders matching `pattern`: an address (`anna@example.com`) or a whole domain
+/// (`*@example.com`); a blocked pattern moves to the trusted ones
+#[tauri::command]
+pub fn add_trusted_sender(
+    app: AppHandle,
+    pattern: String,
+) -> Result<sender_rules::SenderRules, Localized> {
+    update_sender_rules(&app, |rules| rules.trust(&pattern).map(|_| ()))
+}
+
+/// Block senders matching `pattern`, like `add_trusted_sender`
+#[tauri::command]
+pub fn add_blocked_sender(
+    app: AppHandle,
+    pattern: String,
+) -> Result<sender_rules::SenderRules, Localized> {
+    update_sender_rules(&app, |rules| rules.block(&pattern).map(|_| ()))
+}
+
+/// The allow and block lists behind `sender_trust`
+#[tauri::command]
+pub fn list_sender_rules(app: AppHandle) -> sender_rules::SenderRules {
+    app.state::<SettingsStore>().get().sender_rules
+}
+
+/// Add the rules of a JSON file picked in a native dialog (as written by
+/// `export_sender_rules`) to the lists; `None` if the user cancelled
+#[tauri::command]
+pub async fn import_sender_rules(
+    app: AppHandle,
+) -> Result<Option<sender_rules::SenderRules>, Localized> {
+    use tauri_plugin_dialog::FilePath;
+
+    let picked = app
+        .dialog()
+        .file()
+        .add_filter("JSON", &["json"])
+        .blocking_pick_file();
+    let Some(FilePath::Path(path)) = picked else {
+        return Ok(None);
+    };
+    let data =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let imported: sender_rules::SenderRules = serde_json::from_slice(&data)
+        .map_err(|e| format!("Invalid sender rules in {}: {}", path.display(), e))?;
+    update_sender_rules(&app, |rules| rules.merge(&imported)).map(Some)
+}
+
+/// Save the allow and block lists as a JSON file chosen in a native dialog, to share them
+/// with a team; false if the user cancelled
+#[tauri::command]
+pub async fn export_sender_rules(app: AppHandle) -> Result<bool, Localized> {
+    use tauri_plugin_dialog::FilePath;
+
+    let target = app
+        .dialog()
+        .file()
+        .set_file_name("sender-rules.json")
+        .add_filter("JSON", &["json"])
+        .blocking_save_file();
+    let Some(FilePath::Path(path)) = target else {
+        return Ok(false);
+    };
+    let rules = app.state::<SettingsStore>().get().sender_rules;
+    persist::write_json(&path, &rules)?;
+    Ok(true)
+}
+
+/// Change the sender rules in settings; nothing is stored when `change` fails
+fn update_sender_rules(
+    app: &AppHandle,
+    change: impl FnOnce(&mut sender_rules::SenderRules) -> Result<(), String>,
+) -> Result<sender_rules::SenderRules, Localized> {
+    let store = app.state::<SettingsStore>();
+    let mut rules = store.get().sender_rules;
+    change(&mut rules)?;
+    let settings = store.update(|settings| settings.sender_rules = rules)?;
+    Ok(settings.sender_rules)
+}
+
+/// Set how messages from `sender` are shown: an address (`anna@example.com`) or a whole
+/// domain (`example.com` or `*@example.com`); fields left out fall back to the domain's
+/// preference, then to the global settings. A preference with nothing set is removed.
+#[tauri::command]
+pub fn set_sender_preference(
+    app: AppHandle,
+    sender: String,
+    preference: sender_prefs::SenderPreference,
+) -> Result<Vec<sender_prefs::SenderPreferenceEntry>, Localized> {
+    update_sender_preferences(&app, |prefs| prefs.set(&sender, preference).map(|_| ()))
+}
+
+/// The stored sender preferences, by address or domain
+#[tauri::command]
+pub fn list_sender_preferences(app: AppHandle) -> Vec<sender_prefs::SenderPreferenceEntry> {
+    app.state::<SettingsStore>().get().sender_preferences.list()
+}
+
+/// Drop the preference for `sender`, as accepted by `set_sender_preference`
+#[tauri::command]
+pub fn remove_sender_preference(
+    app: AppHandle,
+    sender: String,
+) -> Result<Vec<sender_prefs::SenderPreferenceEntry>, Localized> {
+    update_sender_preferences(&app, |prefs| prefs.remove(&sender).map(|_| ()))
+}
+
+/// Change the sender preferences in settings; nothing is stored when `change` fails
+fn update_sender_preferences(
+    app: &AppHandle,
+    change: impl FnOnce(&mut sender_prefs::SenderPreferences) -> Result<(), String>,
+) -> Result<Vec<sender_prefs::SenderPreferenceEntry>, Localized> {
+    let store = app.state::<SettingsStore>();
+    let mut prefs = store.get().sender_preferences;
+    change(&mut prefs)?;
+    let settings = store.update(|settings| settings.sender_preferences = prefs)?;
+    Ok(settings.sender_preferences.list())
+}
@@ -0,0 +1,187 @@
+//! Commands for settings, theme, view state and storage
+
+use crate::i18n::Localized;
+use crate::settings::{ImportReport, Settings, SettingsStore};
+use crate::{
+    access, i18n, onboarding, permissions, persist, quarantine, retention, tempdir, theme,
+    view_state,
+};
+use tauri::{AppHandle, Manager};
+
+/// Get the theme resolved at startup (system theme unless overridden in settings)
+#[tauri::command]
+pub fn get_system_theme(app: AppHandle) -> theme::ThemeInfo {
+    theme::current(&app)
+}
+
+/// Get the current settings
+#[tauri::command]
+pub fn get_settings(state: tauri::State<'_, SettingsStore>) -> Settings {
+    state.get()
+}
+
+/// Merge a partial settings object into the stored settings
+#[tauri::command]
+pub fn update_settings(app: AppHandle, patch: serde_json::Value) -> Result<Settings, Localized> {
+    let store = app.state::<SettingsStore>();
+    let previous = store.get();
+    let settings = store.merge(patch)?;
+    apply_changes(&app, &previous, &settings);
+    Ok(settings)
+}
+
+/// Write the settings to `target` as JSON for another machine; the folders allowed, the
+/// command groups switched on, the temp directory and quarantine mode stay behind
+#[tauri::command]
+pub fn export_settings(app: AppHandle, target: String) -> Result<(), Localized> {
+    let target = access::check(&app, &target)?;
+    let settings = app.state::<SettingsStore>().export()?;
+    persist::write_json(&target, &settings).map_err(Localized::from)
+}
+
+/// Take the settings of a file written by `export_settings`, upgraded from its schema
+/// version: over the current settings with `merge`, else over the defaults
+///
+/// Keys this version doesn't know, protected ones and ones with invalid values are left
+/// out and listed in `ignored`.
+#[tauri::command]
+pub fn import_settings(
+    app: AppHandle,
+    source: String,
+    merge: bool,
+) -> Result<ImportReport, Localized> {
+    let source = access::check(&app, &source)?;
+    let data = std::fs::read(&source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let file: serde_json::Value = serde_json::from_slice(&data)
+        .map_err(|e| format!("Invalid settings file {}: {}", source.display(), e))?;
+    let store = app.state::<SettingsStore>();
+    let previous = store.get();
+    let report = store.import(file, merge)?;
+    apply_changes(&app, &previous, &report.settings);
+    Ok(report)
+}
+
+/// Apply what changed in the settings to the running app
+fn apply_changes(app: &AppHandle, previous: &Settings, settings: &Settings) {
+    if settings.theme_override != previous.theme_override {
+        theme::apply(app);
+    }
+    if settings.paranoid_mode != previous.paranoid_mode {
+        retention::apply_paranoid_mode(app, settings.paranoid_mode);
+    }
+    i18n::set_current(settings.locale);
+}
+
+/// Set the language of backend-generated text and return the one used
+///
+/// Covers the `message` of error and warning payloads, plain-text export labels, notifications
+/// and dialogs. Any locale tag is accepted (`de-AT` uses German); languages without a
+/// catalog fall back to English, as do keys a catalog lacks.
+#[tauri::command]
+pub fn set_locale(app: AppHandle, lang: String) -> Result<i18n::Lang, Localized> {
+    let lang = i18n::Lang::from_tag(&lang).unwrap_or_default();
+    app.state::<SettingsStore>()
+        .update(|settings| settings.locale = lang)?;
+    i18n::set_current(lang);
+    Ok(lang)
+}
+
+/// Persist opaque UI state (scroll position, collapsed quotes, ...) for a file's content hash
+#[tauri::command]
+pub fn save_view_state(
+    state: tauri::State<'_, view_state::ViewStates>,
+    path_hash: String,
+    state_json: String,
+) -> Result<(), Localized> {
+    state.save(&path_hash, state_json).map_err(Localized::from)
+}
+
+/// Get the stored UI state for a file's content hash
+#[tauri::command]
+pub fn get_view_state(
+    state: tauri::State<'_, view_state::ViewStates>,
+    path_hash: String,
+) -> Option<String> {
+    state.get(&path_hash)
+}
+
+/// Delete the temp files, view states and log lines the retention policy says are due and
+/// report what went; files kept until exit stay
+#[tauri::command]
+pub async fn run_cleanup_now(app: AppHandle) -> Result<retention::CleanupReport, Localized> {
+    tauri::async_runtime::spawn_blocking(move || retention::run(&app, false))
+        .await
+        .map_err(|e| Localized::from(format!("Cleanup failed: {}", e)))
+}
+
+/// Where settings, notes, caches, the session, logs and temp files are kept, and which of
+/// them are written to disk at the moment (see the `paranoid_mode` setting)
+#[tauri::command]
+pub fn get_storage_paths(app: AppHandle) -> Vec<retention::StorageLocation> {
+    retention::storage_locations(&app)
+}
+
+/// Write the sample message into the app data folder (again, if it was deleted) and open
+/// it like a file from the OS; returns its path
+#[tauri::command]
+pub async fn create_sample_message(app: AppHandle) -> Result<String, Localized> {
+    tauri::async_runtime::spawn_blocking(move || onboarding::create_sample(&app))
+        .await
+        .map_err(|e| format!("Creating the sample failed: {}", e))?
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(Localized::from)
+}
+
+/// What this machine offers, for the first-run suggestions: a default mail client, the
+/// file types registered to the app, `xdg-open` (Linux) and notifications
+#[tauri::command]
+pub async fn get_platform_capabilities(
+    app: AppHandle,
+) -> Result<onboarding::PlatformCapabilities, Localized> {
+    tauri::async_runtime::spawn_blocking(move || onboarding::capabilities(&app))
+        .await
+        .map_err(|e| Localized::from(format!("Probing the platform failed: {}", e)))
+}
+
+/// Switch a gated command group (`system-integration`, `crypto`) on or off and return
+/// whether it is on; switching one on asks the user in a native dialog first
+#[tauri::command]
+pub async fn set_command_group(
+    app: AppHandle,
+    group: String,
+    enabled: bool,
+) -> Result<bool, Localized> {
+    permissions::set_group(&app, &group, enabled).map_err(Localized::from)
+}
+
+/// Whether quarantine mode is on, and whether it was switched on from the command line
+/// (`locked`), which lasts until the app exits
+#[tauri::command]
+pub fn get_security_mode(app: AppHandle) -> quarantine::SecurityMode {
+    quarantine::mode(&app)
+}
+
+/// Switch quarantine mode on or off and return the resulting mode; switching it off asks
+/// the user in a native dialog first and fails when it is `locked`. Changes are announced
+/// with a `security-mode-changed` event and in the window titles.
+#[tauri::command]
+pub async fn set_quarantine_mode(
+    app: AppHandle,
+    enabled: bool,
+) -> Result<quarantine::SecurityMode, Localized> {
+    quarantine::set(&app, enabled).map_err(Localized::from)
+}
+
+/// Pick the directory for extracted files in a native dialog, for when the system temp
+/// directory and the app's cache directory can't be written; false if cancelled
+#[tauri::command]
+pub async fn choose_temp_dir(app: AppHandle) -> Result<bool, Localized> {
+    Ok(tempdir::choose_user_dir(&app)?.is_some())
+}
+
+/// Remove all stored view states
+#[tauri::command]
+pub fn clear_view_states(state: tauri::State<'_, view_state::ViewStates>) -> Result<(), Localized> {
+    state.clear().map(|_| ()).map_err(Localized::from)
+}
@@ -0,0 +1,161 @@
+//! Commands for saved workspaces
+
+use super::folder_preview;
+use crate::i18n::Localized;
+use crate::settings::SettingsStore;
+use crate::{access, filenames, folder_index, i18n, persist, watcher, workspaces};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+/// Save a workspace: the folders of a matter, the order and filter its listings start with,
+/// and its tags with their colors; a workspace of that name is replaced
+///
+/// The folders must be accessible (see `request_access`) and are stored resolved.
+#[tauri::command]
+pub fn save_workspace(
+    app: AppHandle,
+    name: String,
+    definition: workspaces::WorkspaceDefinition,
+) -> Result<workspaces::Workspace, Localized> {
+    let mut definition = definition;
+    definition.folders = definition
+        .folders
+        .iter()
+        .map(|folder| access::check(&app, folder).map(|dir| dir.display().to_string()))
+        .collect::<Result<_, _>>()?;
+    definition.tags = definition
+        .tags
+        .into_iter()
+        .map(|(tag, color)| (tag.trim().to_string(), color))
+        .filter(|(tag, _)| !tag.is_empty())
+        .collect();
+    let name = app
+        .state::<workspaces::Workspaces>()
+        .save(&name, definition.clone())?;
+    Ok(workspaces::Workspace { name, definition })
+}
+
+/// Saved workspaces by name
+#[tauri::command]
+pub fn list_workspaces(app: AppHandle) -> Vec<workspaces::Workspace> {
+    app.state::<workspaces::Workspaces>().list()
+}
+
+/// Forget a saved workspace; false if there was none of that name
+#[tauri::command]
+pub fn delete_workspace(app: AppHandle, name: String) -> Result<bool, Localized> {
+    app.state::<workspaces::Workspaces>()
+        .remove(&name)
+        .map_err(Localized::from)
+}
+
+/// Open a saved workspace: its folders become accessible and watched, their listings are
+/// indexed in the background, and its tag colors go into `tag_colors`. Folders that aren't
+/// there any more are left out and reported in `missing_folders`; the frontend applies
+/// `sort_by` and `filter` to the listings.
+#[tauri::command]
+pub fn open_workspace(
+    app: AppHandle,
+    name: String,
+) -> Result<workspaces::OpenedWorkspace, Localized> {
+    let workspace = app
+        .state::<workspaces::Workspaces>()
+        .get(&name)
+        .ok_or_else(|| format!("No workspace named {:?}", name))?;
+    let missing_folders = workspaces::missing_folders(&workspace.definition);
+    let folders: Vec<PathBuf> = workspace
+        .definition
+        .folders
+        .iter()
+        .filter(|folder| !missing_folders.contains(folder))
+        .map(PathBuf::from)
+        .collect();
+    // Saved from approved folders, or imported through a file the user picked
+    for dir in &folders {
+        app.state::<access::AccessPolicy>().allow_file(dir);
+        if let Err(e) = watcher::watch(&app, &app.state::<watcher::FolderWatchers>(), dir.clone()) {
+            log_line!("Failed to watch workspace folder: {}", e);
+        }
+    }
+    let colors: Vec<(String, String)> = workspace
+        .definition
+        .tags
+        .iter()
+        .filter_map(|(tag, color)| Some((tag.to_lowercase(), color.clone()?)))
+        .collect();
+    if !colors.is_empty() {
+        app.state::<SettingsStore>()
+            .update(|settings| settings.tag_colors.extend(colors))?;
+    }
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let lang = i18n::current();
+        for dir in folders {
+            let indexed = handle.state::<folder_index::FolderIndexes>().get(
+                &dir,
+                lang,
+                |path| folder_preview(&handle, path, lang),
+                |_, _| Ok(()),
+            );
+            if let Err(e) = indexed {
+                log_line!("Failed to index workspace folder {}: {}", dir.display(), e);
+            }
+        }
+    });
+    Ok(workspaces::OpenedWorkspace {
+        workspace,
+        missing_folders,
+    })
+}
+
+/// Save a workspace as a JSON file chosen in a native dialog, to share it; false if the
+/// user cancelled
+#[tauri::command]
+pub async fn export_workspace(app: AppHandle, name: String) -> Result<bool, Localized> {
+    use tauri_plugin_dialog::FilePath;
+
+    let workspace = app
+        .state::<workspaces::Workspaces>()
+        .get(&name)
+        .ok_or_else(|| format!("No workspace named {:?}", name))?;
+    let target = app
+        .dialog()
+        .file()
+        .set_file_name(format!(
+            "{}.json",
+            filenames::sanitize_filename(&workspace.name, filenames::TargetOs::CURRENT)
+        ))
+        .add_filter("JSON", &["json"])
+        .blocking_save_file();
+    let Some(FilePath::Path(path)) = target else {
+        return Ok(false);
+    };
+    persist::write_json(&path, &workspace)?;
+    Ok(true)
+}
+
+/// Add a workspace from a JSON file picked in a native dialog (as written by
+/// `export_workspace`), numbering its name when one of that name exists; `None` if the
+/// user cancelled
+#[tauri::command]
+pub async fn import_workspace(app: AppHandle) -> Result<Option<workspaces::Workspace>, Localized> {
+    use tauri_plugin_dialog::FilePath;
+
+    let picked = app
+        .dialog()
+        .file()
+        .add_filter("JSON", &["json"])
+        .blocking_pick_file();
+    let Some(FilePath::Path(path)) = picked else {
+        return Ok(None);
+    };
+    let data =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let imported: workspaces::Workspace = serde_json::from_slice(&data)
+        .map_err(|e| format!("Invalid workspace in {}: {}", path.display(), e))?;
+    let workspaces = app.state::<workspaces::Workspaces>();
+    let name = workspaces.import(imported)?;
+    Ok(workspaces.get(&name))
+}
@@ -0,0 +1,456 @@
+use crate::filenames::{dedupe_in_dir, sanitize_filename, TargetOs};
+use crate::logging;
+use crate::notes::Notes;
+use crate::parser::{self, MessageFormat};
+use crate::settings::SettingsStore;
+use crate::zip::ZipWriter;
+use crate::{save, self_test, tempdir};
+use serde::Serialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Log lines included in a bundle
+const LOG_LINES: usize = 200;
+
+/// One file inside the bundle, listed in the manifest
+struct BundleEntry {
+    name: String,
+    description: &'static str,
+    data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    name: &'a str,
+    size: usize,
+    description: &'a str,
+}
+
+/// Assemble a diagnostics zip in the session temp directory and return its path
+pub fn create_bundle(
+    app: &AppHandle,
+    include_file: Option<&Path>,
+    include_file_content: bool,
+    include_notes: bool,
+) -> Result<String, String> {
+    let settings = app.state::<SettingsStore>().get();
+    let privacy = settings.privacy_mode;
+    let mut entries = Vec::new();
+
+    let info = app.package_info();
+    entries.push(json_entry(
+        "app.json",
+        "Application version and build information",
+        &json!({
+            "name": info.name,
+            "version": info.version.to_string(),
+            "tauri_version": tauri::VERSION,
+            "debug_build": cfg!(debug_assertions),
+            "identifier": app.config().identifier,
+        }),
+    )?);
+
+    entries.push(json_entry(
+        "system.json",
+        "Operating system details",
+        &json!({
+            "os": std::env::consts::OS,
+            "os_version": os_version(),
+            "arch": std::env::consts::ARCH,
+            "family": std::env::consts::FAMILY,
+        }),
+    )?);
+
+    let mut settings_json = serde_json::to_value(&settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    if privacy {
+        redact_json(&mut settings_json);
+    }
+    entries.push(json_entry(
+        "settings.json",
+        "Current settings (paths and addresses redacted in privacy mode)",
+        &settings_json,
+    )?);
+
+    let self_test = self_test::run_offline(app).text;
+    entries.push(BundleEntry {
+        name: "self-test.txt".to_string(),
+        description: "Self-test results (without the network check)",
+        data: match privacy {
+            true => redact(&self_test),
+            false => self_test,
+        }
+        .into_bytes(),
+    });
+
+    entries.push(log_entry(logging::recent_lines(LOG_LINES), privacy));
+
+    if let Some(path) = include_file {
+        entries.extend(message_entries(path, include_file_content, privacy)?);
+    }
+
+    if include_notes {
+        entries.push(json_entry(
+            "notes.json",
+            "Message notes (explicitly requested)",
+            &json!(app.state::<Notes>().list()),
+        )?);
+    }
+
+    if let Some(crash) = logging::crash_report_path() {
+        if let Ok(data) = std::fs::read(&crash) {
+            entries.push(BundleEntry {
+                name: "crash-report.txt".to_string(),
+                description: "Report written by the last crash",
+                data,
+            });
+        }
+    }
+
+    let target = write_bundle(&tempdir::session_dir()?, &entries)?;
+    Ok(target.to_string_lossy().to_string())
+}
+
+/// The recent log lines, redacted in privacy mode
+fn log_entry(lines: Vec<String>, privacy: bool) -> BundleEntry {
+    let log = lines
+        .into_iter()
+        .map(|line| if privacy { redact(&line) } else { line })
+        .collect::<Vec<_>>()
+        .join("\n");
+    BundleEntry {
+        name: "log.txt".to_string(),
+        description: "Most recent log lines",
+        data: log.into_bytes(),
+    }
+}
+
+/// What a bundle includes about the message at `path`, and with `include_content` the file
+fn message_entries(
+    path: &Path,
+    include_content: bool,
+    privacy: bool,
+) -> Result<Vec<BundleEntry>, String> {
+    let mut entries = vec![json_entry(
+        "message-info.json",
+        "Parse warnings of the included message (not its content)",
+        &message_info(path, privacy),
+    )?];
+
+    if let Some(tree) = mime_tree(path, privacy) {
+        entries.push(json_entry(
+            "mime-tree.json",
+            "Part structure of the included message and how its body was chosen",
+            &tree,
+        )?);
+    }
+
+    if let Some(verdicts) = gateway_verdicts(path, privacy) {
+        entries.push(json_entry(
+                "gateway-verdicts.json",
+                "Spam verdicts in the included message's headers (raw headers left out in privacy mode)",
+                &verdicts,
+            )?);
+    }
+
+    if include_content {
+        let data = std::fs::read(path)
+            .map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+        // Bundles are unpacked anywhere; Windows has the strictest rules
+        let name = path
+            .file_name()
+            .map(|n| sanitize_filename(&n.to_string_lossy(), TargetOs::Windows))
+            .unwrap_or_else(|| "message".to_string());
+        entries.push(BundleEntry {
+            name: format!("message/{}", name),
+            description: "The message file itself (explicitly requested)",
+            data,
+        });
+    }
+    Ok(entries)
+}
+
+/// Zip `entries` behind a manifest listing them into a new file in `dir`
+fn write_bundle(dir: &Path, entries: &[BundleEntry]) -> Result<PathBuf, String> {
+    let manifest: Vec<ManifestEntry> = entries
+        .iter()
+        .map(|entry| ManifestEntry {
+            name: &entry.name,
+            size: entry.data.len(),
+            description: entry.description,
+        })
+        .collect();
+    let manifest = serde_json::to_vec_pretty(&json!({ "files": manifest }))
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+
+    // Two bundles within a second must not overwrite each other
+    let target = dedupe_in_dir(
+        dir,
+        &format!(
+            "msgreader-diagnostics-{}.zip",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ),
+    );
+    save::atomic_output(&target, |file| {
+        let mut zip = ZipWriter::new(std::io::BufWriter::new(file));
+        zip.add_file("manifest.json", &manifest)?;
+        for entry in entries {
+            zip.add_file(&entry.name, &entry.data)?;
+        }
+        zip.finish().map(drop)
+    })?;
+    Ok(target)
+}
+
+fn json_entry(
+    name: &str,
+    description: &'static str,
+    value: &serde_json::Value,
+) -> Result<BundleEntry, String> {
+    let data = serde_json::to_vec_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+    Ok(BundleEntry {
+        name: name.to_string(),
+        description,
+        data,
+    })
+}
+
+/// Structural facts and warnings about a message, never its text
+fn message_info(path: &Path, privacy: bool) -> serde_json::Value {
+    let mut warnings = Vec::new();
+    let size = match std::fs::metadata(path) {
+        Ok(meta) => Some(meta.len()),
+        Err(e) => {
+            warnings.push(format!("Failed to read file metadata: {}", e));
+            None
+        }
+    };
+
+    let format = MessageFormat::from_path(path);
+    if format.is_none() {
+        warnings.push("Unsupported file extension".to_string());
+    } else if let Err(e) = parser::peek_summary(path) {
+        warnings.push(e);
+    }
+
+    let name = if privacy {
+        path.extension()
+            .map(|ext| format!("<redacted>.{}", ext.to_string_lossy()))
+    } else {
+        path.file_name().map(|n| n.to_string_lossy().to_string())
+    };
+
+    let warnings: Vec<String> = if privacy {
+        warnings.iter().map(|w| redact(w)).collect()
+    } else {
+        warnings
+    };
+
+    json!({
+        "file_name": name,
+        "size": size,
+        "format": format,
+        "warnings": warnings,
+    })
+}
+
+/// Part structure of a message (see `get_mime_tree`); names are left out in privacy mode
+fn mime_tree(path: &Path, privacy: bool) -> Option<serde_json::Value> {
+    let format = MessageFormat::from_path(path)?;
+    let data = std::fs::read(path).ok()?;
+    let tree = parser::mime_tree::build(format, &data, || {
+        parser::parse_bytes(format, &data, &parser::ParseOptions::default())
+    })
+    .ok()?;
+    let tree = match privacy {
+        true => tree.without_names(),
+        false => tree,
+    };
+    serde_json::to_value(tree).ok()
+}
+
+/// Gateway verdicts of a message (see `get_gateway_verdicts`); the raw headers name
+/// servers and addresses, so privacy mode leaves them out
+fn gateway_verdicts(path: &Path, privacy: bool) -> Option<serde_json::Value> {
+    let message = parser::parse_file(path, &parser::ParseOptions::default()).ok()?;
+    let mut verdicts = serde_json::to_value(&message.gateway_verdicts).ok()?;
+    if privacy {
+        for verdict in verdicts.as_array_mut()? {
+            verdict.as_object_mut()?.remove("raw");
+        }
+    }
+    Some(verdicts)
+}
+
+/// Replace anything that looks like an absolute filesystem path or an email address
+fn redact(text: &str) -> String {
+    text.split(' ')
+        .map(|token| {
+            let bare = token.trim_matches(|c: char| {
+                matches!(c, '"' | '\'' | '(' | ')' | '<' | '>' | ',' | ';' | ':')
+            });
+            let bytes = bare.as_bytes();
+            let unix = bare.starts_with('/') && bare[1..].contains('/');
+            let windows = bytes.len() > 2
+                && bytes[0].is_ascii_alphabetic()
+                && bytes[1] == b':'
+                && (bytes[2] == b'\\' || bytes[2] == b'/');
+            let unc = bare.starts_with("\\\\");
+            let home = bare.starts_with("~/");
+            let address = bare.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty() && domain.contains('.') && !domain.starts_with('.')
+            });
+            if unix || windows || unc || home {
+                token.replace(bare, "<redacted path>")
+            } else if address {
+                token.replace(bare, "<redacted address>")
+            } else {
+                token.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => *text = redact(text),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+        serde_json::Value::Object(map) => map.values_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// Human-readable OS version from the platform's own tooling
+fn os_version() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let release = std::fs::read_to_string("/etc/os-release").ok()?;
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("sw_vers")
+            .arg("-productVersion")
+            .output()
+            .ok()?;
+        Some(format!(
+            "macOS {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let output = std::process::Command::new("cmd")
+            .args(["/c", "ver"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Names and contents of the files in a zip written by `ZipWriter`
+    fn unzip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |pos: usize| u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        let mut files = Vec::new();
+        let mut pos = 0;
+        while data[pos..].starts_with(b"PK\x03\x04") {
+            let compressed = u32::from_le_bytes(data[pos + 18..pos + 22].try_into().unwrap());
+            let start = pos + 30 + u16_at(pos + 26) + u16_at(pos + 28);
+            let name = String::from_utf8(data[pos + 30..pos + 30 + u16_at(pos + 26)].to_vec());
+            let end = start + compressed as usize;
+            let mut content = Vec::new();
+            flate2::read::DeflateDecoder::new(&data[start..end])
+                .read_to_end(&mut content)
+                .unwrap();
+            files.push((name.unwrap(), content));
+            pos = end;
+        }
+        files
+    }
+
+    #[test]
+    fn bundles_list_their_files_and_redact_the_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let message =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/self-test/minimal.eml");
+        let lines = vec![
+            "Opened /home/ann/Mail/q3.eml in 12 ms".to_string(),
+            "Failed to read file C:\\Users\\ann\\q3.msg: denied".to_string(),
+            "Parse warning for \"~/Mail/q3.eml\" from <ann@example.com>, bob@example.org"
+                .to_string(),
+            "Rendered 3 of 4 parts @ 60fps".to_string(),
+        ];
+        let mut entries = vec![log_entry(lines, true)];
+        entries.extend(message_entries(&message, true, true).unwrap());
+        let bundle = write_bundle(temp.path(), &entries).unwrap();
+        assert_eq!(bundle.parent(), Some(temp.path()));
+
+        let files = unzip(&std::fs::read(&bundle).unwrap());
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "manifest.json",
+                "log.txt",
+                "message-info.json",
+                "mime-tree.json",
+                "gateway-verdicts.json",
+                "message/minimal.eml",
+            ]
+        );
+        let manifest: serde_json::Value = serde_json::from_slice(&files[0].1).unwrap();
+        let listed: Vec<_> = manifest["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                (
+                    file["name"].as_str().unwrap(),
+                    file["size"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        let written: Vec<_> = files[1..]
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.len() as u64))
+            .collect();
+        assert_eq!(listed, written);
+
+        let log = String::from_utf8(files[1].1.clone()).unwrap();
+        for secret in [
+            "/home/ann",
+            "Users",
+            "~/Mail",
+            "ann@example.com",
+            "bob@example.org",
+        ] {
+            assert!(!log.contains(secret), "{secret} in {log}");
+        }
+        assert!(log.contains("Opened <redacted path> in 12 ms"));
+        assert!(log.contains("from <<redacted address>>, <redacted address>"));
+        assert!(log.contains("parts @ 60fps"));
+        let info = String::from_utf8(files[2].1.clone()).unwrap();
+        assert!(info.contains("<redacted>.eml") && !info.contains("minimal"));
+    }
+}
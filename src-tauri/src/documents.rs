@@ -0,0 +1,263 @@
+use crate::filenames;
+use crate::parser::sha256_hex;
+use crate::reload::Snapshot;
+use crate::volumes::Volume;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Error of opening a file whose content is open already, followed by `: <path of the open copy>`
+pub const ALREADY_OPEN: &str = "AlreadyOpen";
+
+/// Files are told apart by the hash of their first bytes and their size; only when those
+/// match is the whole content hashed
+const QUICK_HASH_BYTES: usize = 64 * 1024;
+
+/// A message file the frontend currently has open
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenDocument {
+    pub id: String,
+    pub path: PathBuf,
+    /// The readable form of a mangled file name (`RE%3A%20Invoice.msg`) to show instead of
+    /// the one on disk (see `filenames::readable_name`)
+    pub display_name: Option<String>,
+    /// Label of the window showing the document
+    pub window: String,
+    #[serde(skip)]
+    pub fingerprint: Option<Fingerprint>,
+    /// What the viewer last showed of it, for `reload_document` to compare with
+    #[serde(skip)]
+    pub parsed: Option<Arc<Snapshot>>,
+    /// The volume the file was on when opened (see [`crate::volumes`])
+    #[serde(skip)]
+    pub volume: Option<Volume>,
+    /// Its volume can't be reached; set until the document recovers
+    pub unavailable: bool,
+}
+
+/// Content identity of an opened file
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    size: u64,
+    quick: String,
+    /// SHA-256 of the whole content, upgraded from `quick` the first time it's needed
+    full: Option<String>,
+}
+
+impl Fingerprint {
+    pub fn of(data: &[u8]) -> Self {
+        let quick = sha256_hex(&data[..data.len().min(QUICK_HASH_BYTES)]);
+        Self {
+            size: data.len() as u64,
+            full: (data.len() <= QUICK_HASH_BYTES).then(|| quick.clone()),
+            quick,
+        }
+    }
+
+    fn full(&mut self, hash: impl FnOnce() -> Option<String>) -> Option<&str> {
+        if self.full.is_none() {
+            self.full = hash();
+        }
+        self.full.as_deref()
+    }
+}
+
+/// Registry of open documents, shared by session restore and later per-document features
+#[derive(Default)]
+pub struct OpenDocuments(pub Mutex<Registry>);
+
+#[derive(Debug, Default)]
+pub struct Registry {
+    next_id: u64,
+    documents: Vec<OpenDocument>,
+    active: Option<String>,
+}
+
+impl Registry {
+    /// Register a document, reusing the existing entry if the path is already open in that window
+    pub fn open(&mut self, path: PathBuf, window: &str) -> OpenDocument {
+        if let Some(existing) = self
+            .documents
+            .iter()
+            .find(|doc| doc.path == path && doc.window == window)
+        {
+            return existing.clone();
+        }
+
+        self.next_id += 1;
+        let document = OpenDocument {
+            id: format!("doc-{}", self.next_id),
+            volume: Volume::of(&path),
+            display_name: filenames::display_name(&path),
+            path,
+            window: window.to_string(),
+            fingerprint: None,
+            parsed: None,
+            unavailable: false,
+        };
+        self.documents.push(document.clone());
+        document
+    }
+
+    /// Remember the content identity of a document for `same_content`
+    pub fn set_fingerprint(&mut self, id: &str, fingerprint: Fingerprint) {
+        if let Some(doc) = self.documents.iter_mut().find(|doc| doc.id == id) {
+            doc.fingerprint = Some(fingerprint);
+        }
+    }
+
+    /// Remember the parse result shown for the documents of `path`; `snapshot` is only
+    /// taken when the path is open
+    pub fn set_parsed(&mut self, path: &Path, snapshot: impl FnOnce() -> Snapshot) {
+        let mut documents = self
+            .documents
+            .iter_mut()
+            .filter(|doc| doc.path == path)
+            .peekable();
+        if documents.peek().is_none() {
+            return;
+        }
+        let snapshot = Arc::new(snapshot());
+        for doc in documents {
+            doc.parsed = Some(snapshot.clone());
+        }
+    }
+
+    /// An open document at another path with the same content as `data` (a copy in
+    /// Downloads of a file on a share, say). Full hashes are computed only for documents
+    /// whose size and first bytes match, re-reading their files once.
+    pub fn same_content(
+        &mut self,
+        path: &Path,
+        fingerprint: &mut Fingerprint,
+        data: &[u8],
+    ) -> Option<OpenDocument> {
+        for doc in &mut self.documents {
+            let Some(other) = doc.fingerprint.as_mut() else {
+                continue;
+            };
+            if doc.path == path
+                || other.size != fingerprint.size
+                || other.quick != fingerprint.quick
+            {
+                continue;
+            }
+            let full = fingerprint.full(|| Some(sha256_hex(data)));
+            let doc_path = &doc.path;
+            let other_full = other.full(|| std::fs::read(doc_path).ok().map(|d| sha256_hex(&d)));
+            if other_full.is_some() && other_full == full {
+                return Some(doc.clone());
+            }
+        }
+        None
+    }
+
+    /// Mark the documents at `path` or on the volume at `volume` unavailable, returning
+    /// those that weren't already
+    pub fn mark_unavailable(&mut self, path: &Path, volume: &Path) -> Vec<OpenDocument> {
+        let mut marked = Vec::new();
+        for doc in &mut self.documents {
+            let on_volume = doc.volume.as_ref().is_some_and(|v| v.root == volume);
+            if !doc.unavailable && (doc.path == path || on_volume) {
+                doc.unavailable = true;
+                marked.push(doc.clone());
+            }
+        }
+        marked
+    }
+
+    /// Clear the unavailable mark of a document; returns whether it was set
+    pub fn mark_available(&mut self, id: &str) -> bool {
+        match self.documents.iter_mut().find(|doc| doc.id == id) {
+            Some(doc) => std::mem::replace(&mut doc.unavailable, false),
+            None => false,
+        }
+    }
+
+    /// Point the documents of the file at `from` (resolved) to `to` after it was renamed;
+    /// documents were registered with the path the frontend gave, so theirs are resolved
+    /// through their folder, which is still there
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Vec<OpenDocument> {
+        let resolved = |path: &Path| {
+            let dir = path.parent()?.canonicalize().ok()?;
+            Some(dir.join(path.file_name()?))
+        };
+        let mut renamed = Vec::new();
+        for doc in &mut self.documents {
+            if doc.path == from || resolved(&doc.path).as_deref() == Some(from) {
+                doc.path = to.to_path_buf();
+                doc.display_name = filenames::display_name(to);
+                renamed.push(doc.clone());
+            }
+        }
+        renamed
+    }
+
+    /// Remove a document; returns false if the id is unknown
+    pub fn close(&mut self, id: &str) -> bool {
+        let before = self.documents.len();
+        self.documents.retain(|doc| doc.id != id);
+        if self.active.as_deref() == Some(id) {
+            self.active = None;
+        }
+        self.documents.len() != before
+    }
+
+    /// Mark a document as the one currently displayed
+    pub fn set_active(&mut self, id: &str) -> bool {
+        if self.get(id).is_none() {
+            return false;
+        }
+        self.active = Some(id.to_string());
+        true
+    }
+
+    pub fn get(&self, id: &str) -> Option<&OpenDocument> {
+        self.documents.iter().find(|doc| doc.id == id)
+    }
+
+    pub fn active(&self) -> Option<&OpenDocument> {
+        self.active.as_deref().and_then(|id| self.get(id))
+    }
+
+    /// Documents in the order they were opened
+    pub fn list(&self) -> &[OpenDocument] {
+        &self.documents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_are_found_by_content() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        // Larger than the quick hash, differing only at the end
+        let mut data = vec![b'x'; QUICK_HASH_BYTES + 10];
+        let mut other = data.clone();
+        *other.last_mut().unwrap() = b'y';
+        std::fs::write(dir.join("a.eml"), &data).unwrap();
+
+        let mut registry = Registry::default();
+        let doc = registry.open(dir.join("a.eml"), "main");
+        registry.set_fingerprint(&doc.id, Fingerprint::of(&data));
+
+        let copy = dir.join("copy.eml");
+        let found = registry.same_content(&copy, &mut Fingerprint::of(&data), &data);
+        assert_eq!(found.map(|doc| doc.id), Some(doc.id.clone()));
+        assert!(registry
+            .same_content(&copy, &mut Fingerprint::of(&other), &other)
+            .is_none());
+        // Reopening the same path is no duplicate
+        assert!(registry
+            .same_content(&dir.join("a.eml"), &mut Fingerprint::of(&data), &data)
+            .is_none());
+
+        data.truncate(10);
+        assert!(registry
+            .same_content(&copy, &mut Fingerprint::of(&data), &data)
+            .is_none());
+    }
+}
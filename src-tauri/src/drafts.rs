@@ -0,0 +1,466 @@
+//! Forwards put together before they're written: a draft starts from a message with its
+//! subject and attachments, takes recipients, a note and the choice of attachments, and
+//! becomes an .eml mail clients open as an unsent message (see [`compose`]). Drafts are
+//! written to a file on every change, so a crash doesn't lose one; the retention policy
+//! deletes those left unfinished. Answers to meeting requests go out the same way, written
+//! at once (see [`compose_calendar_reply`]).
+
+use crate::export::{self, HeaderFormat};
+use crate::formatting::DateFormatter;
+use crate::i18n::{self, Lang};
+use crate::parser::{headers, text, Address, Attachment, Message, MessageFormat};
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Header that makes Outlook and Thunderbird open an .eml as a message to send
+const UNSENT_HEADER: &str = "X-Unsent: 1\r\n";
+
+/// Longest note accepted, in characters
+const MAX_NOTE_CHARS: usize = 100_000;
+
+/// A forward being put together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: String,
+    /// The message file forwarded, resolved
+    pub source: String,
+    /// Recipients as written (`Name <address>`)
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: String,
+    /// Text above the forwarded message
+    pub note: String,
+    /// The original's attachments, by index
+    pub attachments: Vec<DraftAttachment>,
+    /// Unix seconds
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DraftAttachment {
+    pub index: usize,
+    pub file_name: String,
+    pub size: usize,
+    /// An image the HTML body shows rather than a file in the attachment list
+    pub inline: bool,
+    pub included: bool,
+}
+
+/// Changes to a draft; fields left out stay as they are
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DraftPatch {
+    pub to: Option<Vec<String>>,
+    pub cc: Option<Vec<String>>,
+    pub subject: Option<String>,
+    pub note: Option<String>,
+    /// Whether to include each attachment, by index
+    pub attachments: BTreeMap<usize, bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DraftFile {
+    next_id: u64,
+    drafts: BTreeMap<String, Draft>,
+}
+
+/// Drafts by id, persisted to a JSON file
+pub struct Drafts {
+    path: Option<PathBuf>,
+    file: Mutex<DraftFile>,
+}
+
+impl Drafts {
+    pub fn load(path: PathBuf) -> Self {
+        let file = persist::read_json(&path).unwrap_or_default();
+        Self {
+            path: Some(path),
+            file: Mutex::new(file),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            file: Mutex::default(),
+        }
+    }
+
+    /// Start a forward of `message`, read from `source`: `Fwd:` subject, no recipients and
+    /// every attachment included
+    pub fn create(&self, source: &Path, message: &Message) -> Result<Draft, String> {
+        let subject = message.subject.as_deref().unwrap_or_default().trim();
+        let forwarded = ["fw:", "fwd:", "wg:"]
+            .iter()
+            .any(|prefix| subject.to_lowercase().starts_with(prefix));
+        let mut file = self.file.lock().unwrap();
+        file.next_id += 1;
+        let now = now();
+        let draft = Draft {
+            id: format!("draft-{}", file.next_id),
+            source: source.display().to_string(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            subject: match forwarded {
+                true => subject.to_string(),
+                false => format!("Fwd: {}", subject).trim_end().to_string(),
+            },
+            note: String::new(),
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| DraftAttachment {
+                    index: attachment.index,
+                    file_name: attachment.file_name.clone(),
+                    size: attachment.size,
+                    inline: attachment.referenced_in_body,
+                    included: true,
+                })
+                .collect(),
+            created_at: now,
+            updated_at: now,
+        };
+        file.drafts.insert(draft.id.clone(), draft.clone());
+        if let Err(e) = self.persist(&file) {
+            file.drafts.remove(&draft.id);
+            return Err(e);
+        }
+        Ok(draft)
+    }
+
+    /// Apply `patch` to a draft; recipients must all be addresses
+    pub fn update(&self, id: &str, patch: DraftPatch) -> Result<Draft, String> {
+        let mut file = self.file.lock().unwrap();
+        let draft = file
+            .drafts
+            .get_mut(id)
+            .ok_or_else(|| format!("No draft {}", id))?;
+        let mut updated = draft.clone();
+        if let Some(to) = patch.to {
+            updated.to = recipients(to)?;
+        }
+        if let Some(cc) = patch.cc {
+            updated.cc = recipients(cc)?;
+        }
+        if let Some(subject) = patch.subject {
+            updated.subject = subject.replace(['\r', '\n'], " ").trim().to_string();
+        }
+        if let Some(note) = patch.note {
+            if note.chars().count() > MAX_NOTE_CHARS {
+                return Err(format!(
+                    "The note is longer than {} characters",
+                    MAX_NOTE_CHARS
+                ));
+            }
+            updated.note = note;
+        }
+        for (index, included) in patch.attachments {
+            let attachment = updated
+                .attachments
+                .iter_mut()
+                .find(|attachment| attachment.index == index)
+                .ok_or_else(|| format!("Attachment {} not found in {}", index, updated.source))?;
+            attachment.included = included;
+        }
+        updated.updated_at = now();
+        let previous = std::mem::replace(draft, updated.clone());
+        if let Err(e) = self.persist(&file) {
+            file.drafts.insert(id.to_string(), previous);
+            return Err(e);
+        }
+        Ok(updated)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Draft> {
+        self.file.lock().unwrap().drafts.get(id).cloned()
+    }
+
+    /// Drafts by id
+    pub fn list(&self) -> Vec<Draft> {
+        self.file.lock().unwrap().drafts.values().cloned().collect()
+    }
+
+    /// Forget a draft, once written or discarded; false if there was none of that id
+    pub fn remove(&self, id: &str) -> Result<bool, String> {
+        let mut file = self.file.lock().unwrap();
+        let Some(removed) = file.drafts.remove(id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.persist(&file) {
+            file.drafts.insert(id.to_string(), removed);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Forget drafts last changed before `cutoff` (Unix seconds); returns how many
+    pub fn remove_updated_before(&self, cutoff: i64) -> Result<usize, String> {
+        let mut file = self.file.lock().unwrap();
+        let before = file.drafts.len();
+        file.drafts.retain(|_, draft| draft.updated_at >= cutoff);
+        let removed = before - file.drafts.len();
+        if removed > 0 {
+            self.persist(&file)?;
+        }
+        Ok(removed)
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn persist(&self, file: &DraftFile) -> Result<(), String> {
+        match &self.path {
+            Some(path) => persist::write_json(path, file),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The forward as a message: the note, the original's header block and body, and the
+/// attachments left included. Attached .msg files go as .eml, like `save_attachment` with
+/// `as_eml`.
+fn forward(draft: &Draft, original: Message, lang: Lang, dates: &DateFormatter) -> Message {
+    let mut message = Message::new(MessageFormat::Eml);
+    message.to = addresses(&draft.to);
+    message.cc = addresses(&draft.cc);
+    message.subject = Some(draft.subject.clone());
+    let separator = format!(
+        "---------- {} ----------",
+        i18n::text(lang, "export.forwarded_message", &[])
+    );
+
+    let original_text = match (&original.body_text, &original.body_html) {
+        (Some(body), _) => body.clone(),
+        (None, Some(html)) => text::html_to_text(html, None),
+        (None, None) => String::new(),
+    };
+    let mut body = String::new();
+    if !draft.note.trim().is_empty() {
+        body.push_str(draft.note.trim_end());
+        body.push_str("\n\n");
+    }
+    body.push_str(&separator);
+    body.push('\n');
+    body.push_str(&export::header_block(
+        &original,
+        HeaderFormat::Text,
+        lang,
+        dates,
+    ));
+    body.push('\n');
+    body.push_str(&original_text);
+    message.body_text = Some(body);
+
+    message.body_html = original.body_html.as_deref().map(|html| {
+        let note = match draft.note.trim().is_empty() {
+            true => String::new(),
+            false => format!(
+                "<p>{}</p>\n",
+                export::escape_html(draft.note.trim_end()).replace('\n', "<br>\n")
+            ),
+        };
+        format!(
+            "{}<p>{}</p>\n{}<div>{}</div>\n",
+            note,
+            export::escape_html(&separator),
+            export::header_block(&original, HeaderFormat::Html, lang, dates),
+            html
+        )
+    });
+
+    message.attachments = original
+        .attachments
+        .into_iter()
+        .filter(|attachment| {
+            draft
+                .attachments
+                .iter()
+                .any(|a| a.index == attachment.index && a.included)
+        })
+        .map(|mut attachment| {
+            if let Some(nested) = attachment.nested.take() {
+                if nested.format == MessageFormat::Msg {
+                    attachment.data = export::message_to_eml(&nested);
+                    attachment.declared_mime = Some("message/rfc822".to_string());
+                    attachment.file_name = Path::new(&attachment.file_name)
+                        .with_extension("eml")
+                        .to_string_lossy()
+                        .to_string();
+                }
+            }
+            attachment
+        })
+        .collect();
+    message
+}
+
+/// The draft as an .eml file mail clients open as a message to send
+pub fn compose(draft: &Draft, original: Message, lang: Lang, dates: &DateFormatter) -> Vec<u8> {
+    let mut eml = UNSENT_HEADER.as_bytes().to_vec();
+    eml.extend(export::message_to_eml(&forward(
+        draft, original, lang, dates,
+    )));
+    eml
+}
+
+/// An answer to a meeting request as an .eml to send: to `organizer`, with the comment as
+/// its text and the iTIP reply attached as `text/calendar` with `method=REPLY`
+pub fn compose_calendar_reply(
+    organizer: &str,
+    subject: &str,
+    comment: Option<&str>,
+    ics: Vec<u8>,
+) -> Vec<u8> {
+    let mut message = Message::new(MessageFormat::Eml);
+    message.to = headers::address_list(organizer);
+    message.subject = Some(subject.to_string());
+    message.body_text = Some(comment.unwrap_or_default().trim().to_string());
+    message.attachments = vec![Attachment::new(
+        0,
+        Some("reply.ics".to_string()),
+        Some("text/calendar; method=REPLY".to_string()),
+        None,
+        ics,
+    )];
+    let mut eml = UNSENT_HEADER.as_bytes().to_vec();
+    eml.extend(export::message_to_eml(&message));
+    eml
+}
+
+/// Trimmed, non-empty recipients, each of which must have an address
+fn recipients(list: Vec<String>) -> Result<Vec<String>, String> {
+    let list: Vec<String> = list
+        .into_iter()
+        .map(|recipient| recipient.replace(['\r', '\n'], " ").trim().to_string())
+        .filter(|recipient| !recipient.is_empty())
+        .collect();
+    for recipient in &list {
+        let parsed = headers::address_list(recipient);
+        if parsed.len() != 1 || parsed[0].email.as_deref().is_none_or(|e| !e.contains('@')) {
+            return Err(format!("Invalid recipient: {}", recipient));
+        }
+    }
+    Ok(list)
+}
+
+fn addresses(list: &[String]) -> Vec<Address> {
+    list.iter()
+        .flat_map(|recipient| headers::address_list(recipient))
+        .collect()
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, ParseOptions};
+
+    #[test]
+    fn drafts_survive_a_restart_and_become_unsent_forwards() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("drafts.json");
+        let source = include_bytes!("../tests/fixtures/onboarding/sample.eml");
+        let original = parse_bytes(MessageFormat::Eml, source, &ParseOptions::default()).unwrap();
+
+        let drafts = Drafts::load(path.clone());
+        let draft = drafts
+            .create(Path::new("/mail/sample.eml"), &original)
+            .unwrap();
+        assert!(
+            draft.subject.starts_with("Fwd: Sample:"),
+            "{}",
+            draft.subject
+        );
+        assert!(draft.attachments.iter().all(|a| a.included));
+        let notes = draft
+            .attachments
+            .iter()
+            .find(|a| a.file_name.ends_with(".txt"))
+            .unwrap()
+            .index;
+
+        assert!(drafts
+            .update(
+                &draft.id,
+                DraftPatch {
+                    to: Some(vec!["not an address".to_string()]),
+                    ..DraftPatch::default()
+                },
+            )
+            .is_err());
+        drafts
+            .update(
+                &draft.id,
+                DraftPatch {
+                    to: Some(vec![" Eve <eve@example.com> ".to_string(), String::new()]),
+                    note: Some("See below.\nThanks".to_string()),
+                    attachments: BTreeMap::from([(notes, false)]),
+                    ..DraftPatch::default()
+                },
+            )
+            .unwrap();
+
+        // A crash later, the draft is still there
+        let drafts = Drafts::load(path);
+        let draft = drafts.get(&draft.id).unwrap();
+        assert_eq!(draft.to, ["Eve <eve@example.com>"]);
+
+        let eml = compose(
+            &draft,
+            original.clone(),
+            Lang::En,
+            &DateFormatter::default(),
+        );
+        assert!(eml.starts_with(b"X-Unsent: 1\r\n"));
+        let forward = parse_bytes(MessageFormat::Eml, &eml, &ParseOptions::default()).unwrap();
+        assert_eq!(forward.subject.as_deref(), Some(draft.subject.as_str()));
+        assert_eq!(forward.to[0].email.as_deref(), Some("eve@example.com"));
+        let body = forward.body_text.unwrap().replace("\r\n", "\n");
+        assert!(body.starts_with("See below.\nThanks\n\n---------- Forwarded message"));
+        assert!(body.contains("sample message"));
+        assert_eq!(forward.attachments.len(), original.attachments.len() - 1);
+        assert!(!forward
+            .attachments
+            .iter()
+            .any(|a| a.file_name.ends_with(".txt")));
+
+        assert_eq!(drafts.remove_updated_before(draft.updated_at).unwrap(), 0);
+        assert_eq!(
+            drafts.remove_updated_before(draft.updated_at + 1).unwrap(),
+            1
+        );
+        assert!(drafts.list().is_empty());
+    }
+
+    #[test]
+    fn calendar_replies_go_to_the_organizer_with_the_reply_attached() {
+        let ics = b"BEGIN:VCALENDAR\r\nMETHOD:REPLY\r\nEND:VCALENDAR\r\n".to_vec();
+        let eml = compose_calendar_reply(
+            "tom@example.com",
+            "Accepted: Review",
+            Some("See you there"),
+            ics.clone(),
+        );
+        assert!(eml.starts_with(b"X-Unsent: 1\r\n"));
+        let reply = parse_bytes(MessageFormat::Eml, &eml, &ParseOptions::default()).unwrap();
+        assert_eq!(reply.to[0].email.as_deref(), Some("tom@example.com"));
+        assert_eq!(reply.subject.as_deref(), Some("Accepted: Review"));
+        assert_eq!(
+            reply.body_text.as_deref().map(str::trim),
+            Some("See you there")
+        );
+        let attachment = &reply.attachments[0];
+        assert_eq!(attachment.declared_mime.as_deref(), Some("text/calendar"));
+        assert_eq!(attachment.data, ics);
+    }
+}
@@ -0,0 +1,851 @@
+use crate::formatting::{self, DateFormatter};
+use crate::i18n::{self, Lang};
+use crate::parser::{
+    headers, mbox, signature, text, Address, Attachment, Importance, Message, MessageFormat,
+    Sensitivity,
+};
+use crate::save;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Header fields describing the MIME structure, which is rebuilt when writing .eml
+const MIME_HEADERS: &[&str] = &[
+    "mime-version",
+    "content-type",
+    "content-transfer-encoding",
+    "content-disposition",
+    "content-id",
+];
+
+/// Longest encoded header word in bytes of UTF-8 (a 75-character encoded word)
+const ENCODED_WORD_CHUNK: usize = 45;
+
+/// Line length of base64 bodies
+const BASE64_LINE: usize = 76;
+
+/// How a message is rendered for a plain-text export
+#[derive(Debug, Clone, Default)]
+pub struct TextExportOptions {
+    /// Wrap body lines at this many characters
+    pub wrap_at: Option<usize>,
+    /// Leave out the detected signature block
+    pub strip_signature: bool,
+    /// Language of the header labels and sizes
+    pub lang: Lang,
+    pub dates: DateFormatter,
+}
+
+/// Output of [`header_block`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderFormat {
+    Html,
+    Text,
+}
+
+/// The From/Sent/To/Cc/Subject block exports and printouts start with, in Outlook's order,
+/// followed by importance, sensitivity and sensitivity label when the sender set them and
+/// the attachments with their sizes (hidden inline images left out). HTML is a
+/// `table.header-block`, with importance, sensitivity and label as `span.badge` elements.
+pub fn header_block(
+    message: &Message,
+    format: HeaderFormat,
+    lang: Lang,
+    dates: &DateFormatter,
+) -> String {
+    let mut rows: Vec<(&str, String, Option<&str>)> = vec![
+        (
+            "export.from",
+            message
+                .from
+                .as_ref()
+                .map(display_address)
+                .unwrap_or_default(),
+            None,
+        ),
+        ("export.sent", dates.message_date(message), None),
+        ("export.to", display_addresses(&message.to), None),
+        ("export.cc", display_addresses(&message.cc), None),
+        (
+            "export.subject",
+            message.subject.clone().unwrap_or_default(),
+            None,
+        ),
+    ];
+    if let Some(importance) = message.importance {
+        let (code, class) = match importance {
+            Importance::High => ("importance.high", "importance-high"),
+            Importance::Low => ("importance.low", "importance-low"),
+        };
+        rows.push((
+            "export.importance",
+            i18n::text(lang, code, &[]),
+            Some(class),
+        ));
+    }
+    if let Some(sensitivity) = message.sensitivity {
+        let (code, class) = match sensitivity {
+            Sensitivity::Personal => ("sensitivity.personal", "sensitivity-personal"),
+            Sensitivity::Private => ("sensitivity.private", "sensitivity-private"),
+            Sensitivity::Confidential => ("sensitivity.confidential", "sensitivity-confidential"),
+        };
+        rows.push((
+            "export.sensitivity",
+            i18n::text(lang, code, &[]),
+            Some(class),
+        ));
+    }
+    if let Some(label) = &message.sensitivity_label {
+        rows.push((
+            "export.sensitivity_label",
+            label.name.clone().unwrap_or_else(|| label.id.clone()),
+            Some("sensitivity-label"),
+        ));
+    }
+    rows.retain(|(_, value, _)| !value.is_empty());
+    let attachments: Vec<String> = message
+        .attachments
+        .iter()
+        .filter(|attachment| !attachment.hidden)
+        .map(|attachment| {
+            format!(
+                "{} ({})",
+                attachment.file_name,
+                format_size(attachment.size, lang)
+            )
+        })
+        .collect();
+    let label = |code: &str| i18n::text(lang, code, &[]);
+
+    let mut out = String::new();
+    match format {
+        HeaderFormat::Text => {
+            for (code, value, _) in &rows {
+                out.push_str(&format!("{}: {}\n", label(code), value));
+            }
+            if !attachments.is_empty() {
+                out.push_str(&format!("{}:\n", label("export.attachments")));
+                for attachment in &attachments {
+                    out.push_str(&format!("  - {}\n", attachment));
+                }
+            }
+        }
+        HeaderFormat::Html => {
+            out.push_str("<table class=\"header-block\">\n");
+            for (code, value, badge) in &rows {
+                let value = match badge {
+                    Some(class) => {
+                        format!(
+                            "<span class=\"badge {}\">{}</span>",
+                            class,
+                            escape_html(value)
+                        )
+                    }
+                    None => escape_html(value),
+                };
+                out.push_str(&format!(
+                    "<tr><th>{}:</th><td>{}</td></tr>\n",
+                    escape_html(&label(code)),
+                    value
+                ));
+            }
+            if !attachments.is_empty() {
+                let names: Vec<String> = attachments.iter().map(|a| escape_html(a)).collect();
+                out.push_str(&format!(
+                    "<tr><th>{}:</th><td>{}</td></tr>\n",
+                    escape_html(&label("export.attachments")),
+                    names.join("; ")
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+    }
+    out
+}
+
+/// Render a message as plain text: header block, attachment list, then the body.
+/// Messages with only an HTML body are converted, keeping lists, tables and links readable.
+pub fn message_to_text(message: &Message, options: TextExportOptions) -> String {
+    let mut out = header_block(message, HeaderFormat::Text, options.lang, &options.dates);
+
+    let body = body_text(message, options.strip_signature);
+    if !body.trim().is_empty() {
+        out.push('\n');
+        let body = match options.wrap_at {
+            Some(width) => text::wrap(&body, width),
+            None => body,
+        };
+        out.push_str(body.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// The text body, or the HTML body converted to text
+pub(crate) fn body_text(message: &Message, strip_signature: bool) -> String {
+    if let Some(body) = message
+        .body_text
+        .as_deref()
+        .filter(|b| !b.trim().is_empty())
+    {
+        if !strip_signature {
+            return body.replace("\r\n", "\n");
+        }
+        // The stored range refers to the body as parsed, before line endings are normalized
+        let body = match message.signature {
+            Some(range) => signature::strip(body, &range),
+            None => body.to_string(),
+        };
+        return body.replace("\r\n", "\n");
+    }
+
+    let Some(html) = message.body_html.as_deref() else {
+        return String::new();
+    };
+    let body = text::html_to_text(html, None);
+    match signature::detect(&body).filter(|_| strip_signature) {
+        Some(range) => signature::strip(&body, &range),
+        None => body,
+    }
+}
+
+/// Outcome of an mbox export; messages are written one by one, so after a failure the
+/// archive holds everything in `exported`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MboxExport {
+    /// Messages the archive already held when appending
+    pub existing: usize,
+    /// Messages written, in order
+    pub exported: Vec<String>,
+    /// Message that stopped the export; the ones after it were not attempted
+    pub failed: Option<FailedExport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedExport {
+    pub path: String,
+    pub error: String,
+}
+
+/// Write messages into an mbox archive at `target`, creating it or appending to it.
+/// .eml files are copied as they are, other formats converted with `message_to_eml`.
+///
+/// `before_each(current, total)` runs before every message; when it returns an error
+/// (a cancelled job), the archive is left as it was before the export and the error returned.
+pub fn export_mbox(
+    paths: &[String],
+    target: &Path,
+    append: bool,
+    load: impl Fn(&str) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<MboxExport, String> {
+    // A new archive only replaces the target once it's complete
+    if !append {
+        return save::atomic_output(target, |file| {
+            write_mbox_entries(
+                file,
+                paths,
+                target,
+                MboxExport::default(),
+                &load,
+                &mut before_each,
+            )
+        });
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(target)
+        .map_err(|e| format!("Failed to open {}: {}", target.display(), e))?;
+    let original_len = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+    let report = MboxExport {
+        existing: prepare_append(&mut file)
+            .map_err(|e| format!("Failed to append to {}: {}", target.display(), e))?,
+        ..MboxExport::default()
+    };
+    let result = write_mbox_entries(&mut file, paths, target, report, &load, &mut before_each);
+    if result.is_err() {
+        // Cut the appended messages off again
+        if let Err(e) = file.set_len(original_len) {
+            log_line!("Failed to clean up {}: {}", target.display(), e);
+        }
+    }
+    let report = result?;
+    file.sync_all()
+        .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    Ok(report)
+}
+
+/// Write `paths` to an mbox archive open at its end, stopping at the first message that
+/// fails; only an error from `before_each` fails the whole export
+fn write_mbox_entries(
+    file: &mut std::fs::File,
+    paths: &[String],
+    target: &Path,
+    mut report: MboxExport,
+    load: &impl Fn(&str) -> Result<Message, String>,
+    before_each: &mut impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<MboxExport, String> {
+    for (current, path) in paths.iter().enumerate() {
+        before_each(current, paths.len())?;
+        let written = mbox_entry(path, load).and_then(|(sender, date, data)| {
+            mbox::write_message(file, sender.as_deref(), date.as_deref(), &data)
+                .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+        });
+        match written {
+            Ok(()) => report.exported.push(path.clone()),
+            Err(error) => {
+                report.failed = Some(FailedExport {
+                    path: path.clone(),
+                    error,
+                });
+                break;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Count the messages already in an archive and make sure the next separator line
+/// follows a blank line; returns an error for files that aren't mbox archives
+fn prepare_append(file: &mut std::fs::File) -> Result<usize, String> {
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let mut existing = 0;
+    for message in mbox::Reader::new(BufReader::new(&mut *file)) {
+        message.map_err(|e| e.to_string())?;
+        existing += 1;
+    }
+    if existing == 0 {
+        return Err("not an mbox file".to_string());
+    }
+
+    let mut tail = [0u8; 2];
+    let tail_len = len.min(2) as usize;
+    file.seek(SeekFrom::End(-(tail_len as i64)))
+        .and_then(|_| file.read_exact(&mut tail[..tail_len]))
+        .map_err(|e| e.to_string())?;
+    let missing = match &tail[..tail_len] {
+        [.., b'\n', b'\n'] => "",
+        [.., b'\n'] => "\n",
+        _ => "\n\n",
+    };
+    file.seek(SeekFrom::End(0))
+        .and_then(|_| file.write_all(missing.as_bytes()))
+        .map_err(|e| e.to_string())?;
+    Ok(existing)
+}
+
+/// Envelope sender, date and RFC 5322 content of one message
+type MboxEntry = (Option<String>, Option<String>, Vec<u8>);
+
+fn mbox_entry(
+    path: &str,
+    load: impl Fn(&str) -> Result<Message, String>,
+) -> Result<MboxEntry, String> {
+    let message = load(path)?;
+    let sender = message.from.as_ref().and_then(|from| from.email.clone());
+    let data = match MessageFormat::from_path(Path::new(path)) {
+        Some(MessageFormat::Eml) => {
+            std::fs::read(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?
+        }
+        // Without the .emlx frame
+        Some(MessageFormat::Emlx) => {
+            let data =
+                std::fs::read(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+            match crate::parser::emlx::split(&data) {
+                Some((message, _)) => message.to_vec(),
+                None => data,
+            }
+        }
+        _ => message_to_eml(&message),
+    };
+    Ok((sender, message.date, data))
+}
+
+/// Render a parsed message as an RFC 5322 message: the original transport headers when
+/// there are any, text and HTML bodies as `multipart/alternative`, images the HTML body
+/// shows in `multipart/related` and the other attachments in `multipart/mixed`
+pub fn message_to_eml(message: &Message) -> Vec<u8> {
+    let mut out = String::new();
+    let mut header = |name: &str, value: &str| {
+        if !value.is_empty() {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    };
+
+    if message.headers.is_empty() {
+        header(
+            "From",
+            &message
+                .from
+                .as_ref()
+                .map(header_address)
+                .unwrap_or_default(),
+        );
+        header("To", &header_addresses(&message.to));
+        header("Cc", &header_addresses(&message.cc));
+        header(
+            "Date",
+            &message
+                .date
+                .as_deref()
+                .map(formatting::rfc2822)
+                .unwrap_or_default(),
+        );
+        header(
+            "Subject",
+            &encode_header_value(message.subject.as_deref().unwrap_or_default()),
+        );
+        header(
+            "Message-ID",
+            message.message_id.as_deref().unwrap_or_default(),
+        );
+    } else {
+        for (name, value) in &message.headers {
+            if !MIME_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                header(name, value);
+            }
+        }
+    }
+    header("MIME-Version", "1.0");
+
+    let (inline, attached): (Vec<&Attachment>, Vec<&Attachment>) = message
+        .attachments
+        .iter()
+        .partition(|attachment| attachment.referenced_in_body && message.body_html.is_some());
+
+    let text = message
+        .body_text
+        .as_deref()
+        .map(|text| text_part("text/plain", text));
+    let html = message.body_html.as_deref().map(|html| {
+        let html = text_part("text/html", html);
+        if inline.is_empty() {
+            return html;
+        }
+        let mut parts = vec![html];
+        parts.extend(
+            inline
+                .iter()
+                .map(|attachment| attachment_part(attachment, true)),
+        );
+        multipart("related", parts)
+    });
+    let body = match (text, html) {
+        (Some(text), Some(html)) => multipart("alternative", vec![text, html]),
+        (Some(part), None) | (None, Some(part)) => part,
+        (None, None) => text_part("text/plain", ""),
+    };
+    let root = if attached.is_empty() {
+        body
+    } else {
+        let mut parts = vec![body];
+        parts.extend(
+            attached
+                .iter()
+                .map(|attachment| attachment_part(attachment, false)),
+        );
+        multipart("mixed", parts)
+    };
+
+    out.push_str(&root);
+    out.into_bytes()
+}
+
+/// Headers and body of a text part, as UTF-8 in base64
+fn text_part(mime: &str, text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+    format!(
+        "Content-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+        mime,
+        base64_lines(text.as_bytes())
+    )
+}
+
+fn attachment_part(attachment: &Attachment, inline: bool) -> String {
+    let mime = attachment
+        .declared_mime
+        .as_deref()
+        .or(attachment.detected_mime.as_deref())
+        .unwrap_or("application/octet-stream");
+    let mut part = format!(
+        "Content-Type: {}; {}\r\nContent-Disposition: {}; {}\r\n",
+        mime,
+        mime_parameter("name", &attachment.file_name),
+        if inline { "inline" } else { "attachment" },
+        mime_parameter("filename", &attachment.file_name),
+    );
+    if let Some(content_id) = &attachment.content_id {
+        part.push_str(&format!(
+            "Content-ID: <{}>\r\n",
+            content_id.trim_matches(['<', '>'])
+        ));
+    }
+    part.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    part.push_str(&base64_lines(&attachment.data));
+    part
+}
+
+/// Assemble `multipart/<subtype>` from complete parts. The boundary can't occur inside
+/// them: bodies are base64 and each subtype appears at most once per message.
+fn multipart(subtype: &str, parts: Vec<String>) -> String {
+    let boundary = format!("=_msgreader_{}", subtype);
+    let mut out = format!(
+        "Content-Type: multipart/{}; boundary=\"{}\"\r\n\r\n",
+        subtype, boundary
+    );
+    for part in parts {
+        out.push_str(&format!("--{}\r\n{}\r\n", boundary, part.trim_end()));
+    }
+    out.push_str(&format!("--{}--\r\n", boundary));
+    out
+}
+
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / BASE64_LINE * 2 + 2);
+    for chunk in encoded.as_bytes().chunks(BASE64_LINE) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// `name="value"`, or the RFC 2231 form for names that aren't plain ASCII
+fn mime_parameter(name: &str, value: &str) -> String {
+    if value.is_ascii() && !value.contains(['"', '\\']) {
+        return format!("{}=\"{}\"", name, value);
+    }
+    let encoded: String = value
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("{}*=utf-8''{}", name, encoded)
+}
+
+/// RFC 2047 encoded words for header text that isn't plain ASCII
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > ENCODED_WORD_CHUNK {
+            words.push(format!("=?utf-8?B?{}?=", STANDARD.encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?utf-8?B?{}?=", STANDARD.encode(&chunk)));
+    }
+    words.join("\r\n ")
+}
+
+fn header_address(address: &Address) -> String {
+    let email = address.email.as_deref().unwrap_or_default();
+    match address.name.as_deref().filter(|name| *name != email) {
+        Some(name) if name.is_ascii() => {
+            let name = name.replace(['"', '\\'], "");
+            format!("\"{}\" <{}>", name, email)
+        }
+        Some(name) => format!("{} <{}>", encode_header_value(name), email),
+        None => email.to_string(),
+    }
+}
+
+fn header_addresses(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .filter(|address| address.email.is_some())
+        .map(header_address)
+        .collect::<Vec<_>>()
+        .join(",\r\n ")
+}
+
+pub(crate) fn format_address(address: &Address) -> String {
+    match (address.name.as_deref(), address.email.as_deref()) {
+        (Some(name), Some(email)) if name != email => format!("{} <{}>", name, email),
+        (_, Some(email)) => email.to_string(),
+        (Some(name), None) => name.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+/// An address as shown in header blocks; names some senders leave RFC 2047 encoded are decoded
+fn display_address(address: &Address) -> String {
+    match address.name.as_deref().filter(|name| name.contains("=?")) {
+        Some(name) => {
+            let mut address = address.clone();
+            address.name = Some(headers::decode_encoded_words(name));
+            format_address(&address)
+        }
+        None => format_address(address),
+    }
+}
+
+fn display_addresses(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .map(display_address)
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Every recipient of `message` as CSV (`type,name,email`), with a byte order mark so
+/// spreadsheet applications read it as UTF-8
+pub fn recipients_csv(message: &Message) -> String {
+    let mut csv = String::from("\u{feff}type,name,email\r\n");
+    for (kind, addresses) in [
+        ("to", &message.to),
+        ("cc", &message.cc),
+        ("bcc", &message.bcc),
+    ] {
+        for address in addresses {
+            let fields = [
+                kind.to_string(),
+                csv_field(address.name.as_deref().unwrap_or_default()),
+                csv_field(address.email.as_deref().unwrap_or_default()),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+    }
+    csv
+}
+
+/// A CSV field, quoted when needed; values a spreadsheet would run as formulas get a
+/// leading `'`
+pub(crate) fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    };
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value,
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn format_addresses(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .map(format_address)
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn format_size(size: usize, lang: Lang) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if size < 1024 {
+        return i18n::text(lang, "size.bytes", &[("size", size.to_string())]);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let value = format!("{:.1}", value).replace('.', &lang.decimal_separator().to_string());
+    format!("{} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{eml, ParseOptions};
+
+    fn msg_message() -> Message {
+        let mut message = Message::new(MessageFormat::Msg);
+        message.subject = Some("Quartalszahlen für Q3".to_string());
+        message.from = Some(Address::new(
+            Some("Jürgen Weiß".to_string()),
+            Some("juergen@example.com".to_string()),
+        ));
+        message.to = vec![Address::new(None, Some("ann@example.com".to_string()))];
+        message.date = Some("2024-03-05T09:07:00+01:00".to_string());
+        message.body_text =
+            Some("Hallo Ann,\r\nFrom now on the figures are final.\r\n".to_string());
+        message.body_html =
+            Some(r#"<p>Hallo Ann,</p><p>From now on <img src="cid:chart@x"></p>"#.to_string());
+        let mut chart = Attachment::new(
+            0,
+            Some("chart.png".to_string()),
+            Some("image/png".to_string()),
+            Some("chart@x".to_string()),
+            b"\x89PNG\r\n\x1a\nchart".to_vec(),
+        );
+        chart.referenced_in_body = true;
+        chart.hidden = true;
+        message.attachments = vec![
+            chart,
+            Attachment::new(
+                1,
+                Some("Übersicht.pdf".to_string()),
+                Some("application/pdf".to_string()),
+                None,
+                b"%PDF-1.4\nFrom here\n".to_vec(),
+            ),
+        ];
+        message
+    }
+
+    #[test]
+    fn mbox_export_round_trips() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let target = dir.join("archive.mbox");
+        let eml_path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/charset/mislabeled-windows-1252.eml"
+        );
+        let options = ParseOptions::default();
+        let load = |path: &str| match path {
+            "missing.msg" => Err("Failed to read file missing.msg".to_string()),
+            "converted.msg" => Ok(msg_message()),
+            _ => crate::parser::parse_file(Path::new(path), &options),
+        };
+
+        let paths = vec!["converted.msg".to_string(), eml_path.to_string()];
+        let report = export_mbox(&paths, &target, false, load, |_, _| Ok(())).unwrap();
+        assert_eq!(report.exported, paths);
+        assert!(report.failed.is_none());
+
+        let more = vec![
+            "converted.msg".to_string(),
+            "missing.msg".to_string(),
+            eml_path.to_string(),
+        ];
+        let report = export_mbox(&more, &target, true, load, |_, _| Ok(())).unwrap();
+        assert_eq!(report.existing, 2);
+        assert_eq!(report.exported, vec!["converted.msg".to_string()]);
+        assert_eq!(report.failed.unwrap().path, "missing.msg");
+
+        let archive = std::fs::read(&target).unwrap();
+        let messages: Vec<mbox::MboxMessage> = mbox::Reader::new(&archive[..])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].envelope_sender, "juergen@example.com");
+
+        let original = msg_message();
+        for data in [&messages[0].data, &messages[2].data] {
+            let parsed = eml::parse(data, &options);
+            assert_eq!(parsed.subject, original.subject);
+            assert_eq!(parsed.from, original.from);
+            assert_eq!(parsed.to, original.to);
+            assert_eq!(parsed.date, original.date);
+            assert_eq!(parsed.body_text, original.body_text);
+            assert_eq!(parsed.body_html, original.body_html);
+            let attachments: Vec<(&str, &[u8], bool)> = parsed
+                .attachments
+                .iter()
+                .map(|a| (a.file_name.as_str(), a.data.as_slice(), a.hidden))
+                .collect();
+            assert_eq!(
+                attachments,
+                vec![
+                    ("chart.png", &original.attachments[0].data[..], true),
+                    ("Übersicht.pdf", &original.attachments[1].data[..], false),
+                ]
+            );
+        }
+
+        let fixture = std::fs::read(eml_path).unwrap();
+        let normalized = String::from_utf8_lossy(&fixture).replace("\r\n", "\n");
+        assert_eq!(
+            String::from_utf8_lossy(&messages[1].data).trim_end(),
+            normalized.trim_end()
+        );
+    }
+
+    #[test]
+    fn cancelled_export_leaves_archive_unchanged() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let target = dir.join("archive.mbox");
+        let paths = vec!["a.msg".to_string(), "b.msg".to_string()];
+        let load = |_: &str| Ok(msg_message());
+        let cancel_second = |current: usize, _: usize| match current {
+            0 => Ok(()),
+            _ => Err(crate::jobs::CANCELLED.to_string()),
+        };
+
+        let result = export_mbox(&paths, &target, false, load, cancel_second);
+        assert_eq!(result.unwrap_err(), crate::jobs::CANCELLED);
+        assert!(!target.exists());
+        assert!(!save::partial_path(&target).exists());
+
+        export_mbox(&paths[..1], &target, false, load, |_, _| Ok(())).unwrap();
+        let before = std::fs::read(&target).unwrap();
+        let result = export_mbox(&paths, &target, true, load, cancel_second);
+        assert_eq!(result.unwrap_err(), crate::jobs::CANCELLED);
+        assert_eq!(std::fs::read(&target).unwrap(), before);
+        // Replacing the archive and cancelling halfway keeps the old one
+        let result = export_mbox(&paths, &target, false, load, cancel_second);
+        assert_eq!(result.unwrap_err(), crate::jobs::CANCELLED);
+        let after = std::fs::read(&target).unwrap();
+        let leftovers = std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(after, before);
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn header_block_snapshot() {
+        let message = eml::parse(
+            include_bytes!("../tests/fixtures/header-block/message.eml"),
+            &ParseOptions::default(),
+        );
+        let dates = DateFormatter::default();
+        let text = header_block(&message, HeaderFormat::Text, Lang::En, &dates);
+        let html = header_block(&message, HeaderFormat::Html, Lang::En, &dates);
+        assert_eq!(
+            text,
+            include_str!("../tests/fixtures/header-block/message.txt")
+        );
+        assert_eq!(
+            html,
+            include_str!("../tests/fixtures/header-block/message.html")
+        );
+    }
+
+    #[test]
+    fn text_export_labels_follow_language() {
+        let message = msg_message();
+        let options = |lang| TextExportOptions {
+            lang,
+            ..TextExportOptions::default()
+        };
+        let english = message_to_text(&message, options(Lang::En));
+        assert!(english.starts_with("From: Jürgen Weiß <juergen@example.com>\nSent: "));
+        assert!(english.contains("\nAttachments:\n  - Übersicht.pdf (19 bytes)\n"));
+
+        let german = message_to_text(&message, options(Lang::De));
+        assert!(german.starts_with("Von: Jürgen Weiß <juergen@example.com>\nGesendet: "));
+        assert!(german.contains("\nBetreff: Quartalszahlen für Q3\n"));
+        assert!(german.contains("\nAnlagen:\n  - Übersicht.pdf (19 Bytes)\n"));
+        assert_eq!(format_size(1536, Lang::De), "1,5 KB");
+    }
+}
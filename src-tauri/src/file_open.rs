@@ -0,0 +1,701 @@
+//! Files reaching the app from outside: command-line arguments at startup, a second
+//! instance forwarding its arguments, drag and drop, and the OS open events handled in
+//! `platform`. Whatever the route, the file is allowed by the access policy before the
+//! webview sees its path; forwarded files only once the user confirmed them, and only
+//! the file itself.
+
+use crate::documents::{OpenDocuments, Registry};
+use crate::open_queue::OpenQueue;
+use crate::parser::sniff;
+use crate::session::SessionState;
+use crate::state::{PendingFiles, WindowFiles};
+use crate::{access, filenames, freshness, open_queue, quarantine, session};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Files forwarded by second instances beyond this many within `FORWARD_WINDOW` are
+/// dropped; a multi-selection opened in Explorer stays well below it
+const FORWARD_LIMIT: usize = 50;
+const FORWARD_WINDOW: Duration = Duration::from_secs(10);
+
+/// Bytes read from a forwarded file to check that its content fits its extension
+const SNIFF_LEN: u64 = 8192;
+
+/// Files forwarded by second instances: any local process can start one, so they are
+/// rate-limited and wait for the user to confirm them before anything is allowed
+#[derive(Default)]
+pub struct Forwards {
+    /// When files were recently forwarded
+    recent: Mutex<VecDeque<Instant>>,
+    /// Forwarded files the user hasn't answered for yet
+    awaiting: Mutex<Vec<PathBuf>>,
+}
+
+/// Payload of the `forwarded-open-request` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardedOpenRequest {
+    pub paths: Vec<String>,
+}
+
+impl Forwards {
+    /// Whether a file forwarded at `now` is within the limit; counted if it is
+    fn admit(&self, now: Instant) -> bool {
+        let mut recent = self.recent.lock().unwrap();
+        while recent
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= FORWARD_WINDOW)
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= FORWARD_LIMIT {
+            return false;
+        }
+        recent.push_back(now);
+        true
+    }
+
+    /// Hold checked files until the user answers for them
+    fn hold(&self, files: &[PathBuf]) {
+        let mut awaiting = self.awaiting.lock().unwrap();
+        for file in files {
+            if !awaiting.contains(file) {
+                awaiting.push(file.clone());
+            }
+        }
+    }
+
+    /// The user's answer for `paths` of a `forwarded-open-request`: the held ones among
+    /// them are released, and allowed (the file alone, not its folder) if accepted.
+    /// Returns the files to open.
+    pub fn answer(
+        &self,
+        policy: &access::AccessPolicy,
+        paths: &[String],
+        accept: bool,
+    ) -> Vec<PathBuf> {
+        let mut awaiting = self.awaiting.lock().unwrap();
+        let mut answered = Vec::new();
+        for path in paths.iter().map(PathBuf::from) {
+            if let Some(position) = awaiting.iter().position(|held| *held == path) {
+                answered.push(awaiting.remove(position));
+            }
+        }
+        drop(awaiting);
+        if !accept {
+            return Vec::new();
+        }
+        for path in &answered {
+            policy.allow_single_file(path);
+        }
+        answered
+    }
+}
+
+/// Result of `normalize_filename`
+#[derive(Debug, Clone, Serialize)]
+pub struct NormalizedName {
+    /// Where the file is now
+    pub path: String,
+    /// Whether it was renamed; false when its name was clean already
+    pub renamed: bool,
+    /// Ids of the open documents of the file, now at `path`
+    pub documents: Vec<String>,
+}
+
+/// Payload of the `document-renamed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentRenamed {
+    pub doc_id: String,
+    pub old_path: String,
+    pub path: String,
+}
+
+/// Extension of the readable name, so mangled downloads (`message.msg.msg`,
+/// `Invoice%2Emsg`) are recognized
+fn extension(path: &Path) -> Option<String> {
+    filenames::effective_extension(path)
+}
+
+/// Message files, the only files taken from the command line at startup
+pub fn is_message(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("msg") | Some("eml"))
+}
+
+/// Files a running app opens: messages, and archives of them
+pub fn is_openable(path: &Path) -> bool {
+    is_message(path) || extension(path).as_deref() == Some("zip")
+}
+
+/// Allow an opened file if the app can open it; false if not
+pub fn accept(policy: &access::AccessPolicy, path: &Path) -> bool {
+    if !is_openable(path) {
+        return false;
+    }
+    policy.allow_file(path);
+    true
+}
+
+/// Handle a file being opened - queue it for the frontend
+///
+/// Files arriving in quick succession are delivered together, see `open_queue`.
+pub fn handle_file_open(app: &AppHandle, path: PathBuf) {
+    let policy = app.state::<access::AccessPolicy>();
+    match queue_open(&policy, &app.state::<OpenQueue>(), path) {
+        Ok(Some(seen)) => open_queue::schedule(app, seen),
+        Ok(None) => {}
+        Err(path) => log_line!("Unsupported file type: {:?}", path),
+    }
+}
+
+/// Allow and queue a file opened through the OS; the number queued when it starts a
+/// burst (see `OpenQueue::add`), and the file back if the app doesn't open it
+fn queue_open(
+    policy: &access::AccessPolicy,
+    queue: &OpenQueue,
+    path: PathBuf,
+) -> Result<Option<usize>, PathBuf> {
+    if !accept(policy, &path) {
+        return Err(path);
+    }
+    Ok(queue.add(vec![path]))
+}
+
+/// Rename the message file at `path` (resolved) to its clean name, in the same folder and
+/// numbered when that is taken. The registry stays locked from before the rename until
+/// its documents point to the new path, so nothing sees the file under both names.
+fn rename_clean(
+    documents: &Mutex<Registry>,
+    path: &Path,
+) -> Result<(PathBuf, Vec<String>), String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Not a file: {}", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let clean = filenames::clean_name(&name);
+    if clean == name {
+        return Ok((path.to_path_buf(), Vec::new()));
+    }
+    if !is_openable(Path::new(&clean)) {
+        return Err(format!("Not a message file: {}", path.display()));
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let target = filenames::dedupe_in_dir(dir, &clean);
+
+    let mut registry = documents.lock().unwrap();
+    std::fs::rename(path, &target)
+        .map_err(|e| format!("Failed to rename {}: {}", path.display(), e))?;
+    let renamed = registry.rename(path, &target);
+    Ok((target, renamed.into_iter().map(|doc| doc.id).collect()))
+}
+
+/// Rename a mangled download (`RE%3A%20Invoice.msg`, `message.msg.msg`) to its readable
+/// name, sanitized for this OS. Open documents follow it, the session is saved with the
+/// new path and a `document-renamed` event tells each window; pins and notes are keyed by
+/// content and stay as they are.
+pub fn normalize_filename(app: &AppHandle, path: &Path) -> Result<NormalizedName, String> {
+    let (target, documents) = rename_clean(&app.state::<OpenDocuments>().0, path)?;
+    let renamed = target != path;
+    if renamed {
+        log_line!("Renamed {} to {}", path.display(), target.display());
+        app.state::<access::AccessPolicy>().allow_file(&target);
+        freshness::invalidate(app, path);
+        session::schedule_save(app);
+        for doc_id in &documents {
+            let payload = DocumentRenamed {
+                doc_id: doc_id.clone(),
+                old_path: path.to_string_lossy().to_string(),
+                path: target.to_string_lossy().to_string(),
+            };
+            if let Err(e) = app.emit("document-renamed", &payload) {
+                log_line!("Failed to emit document-renamed: {}", e);
+            }
+        }
+    }
+    Ok(NormalizedName {
+        path: target.to_string_lossy().to_string(),
+        renamed,
+        documents,
+    })
+}
+
+/// Files a webview starts with (see `get_pending_files`)
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pending {
+    /// The window was opened for these files, and gets them only
+    Window(Vec<PathBuf>),
+    /// Files the app was launched with, or that arrived before the main webview loaded;
+    /// session restore applies when there are none
+    Launch(Vec<PathBuf>),
+}
+
+pub fn take_pending(windows: &WindowFiles, pending: &PendingFiles, label: &str) -> Pending {
+    match windows.take(label) {
+        Some(files) => Pending::Window(files),
+        None => Pending::Launch(pending.take()),
+    }
+}
+
+/// What `get_pending_files` hands a webview
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Startup {
+    pub files: Vec<PathBuf>,
+    /// More files than the bulk open threshold: offered as a bulk open instead
+    pub bulk: bool,
+    /// Files of the restored session that are gone
+    pub missing: Vec<PathBuf>,
+}
+
+/// The files the webview of window `label` starts with: those its window was opened for,
+/// else the launch files, else the previous session's (see `SessionState::take`)
+pub fn startup(
+    windows: &WindowFiles,
+    pending: &PendingFiles,
+    session: &SessionState,
+    policy: &access::AccessPolicy,
+    label: &str,
+    bulk_threshold: usize,
+) -> Startup {
+    let launch = match take_pending(windows, pending, label) {
+        // Windows opened for a file get that file only
+        Pending::Window(files) => {
+            return Startup {
+                files,
+                ..Startup::default()
+            }
+        }
+        Pending::Launch(files) => files,
+    };
+
+    // Files from a file association always win over (and are never mixed with) session restore
+    let restore = session.take(!launch.is_empty());
+    let (files, missing) = match launch.is_empty() {
+        true => (restore.files, restore.missing),
+        false => (launch, Vec::new()),
+    };
+
+    // The previous session's files were opened by the user, so they stay accessible
+    for file in &files {
+        policy.allow_file(file);
+    }
+    Startup {
+        bulk: open_queue::over_threshold(files.len(), bulk_threshold),
+        files,
+        missing,
+    }
+}
+
+/// Queue the message files among the startup arguments for the frontend
+pub fn queue_startup_args(app: &AppHandle, args: impl IntoIterator<Item = String>) {
+    let policy = app.state::<access::AccessPolicy>();
+    let pending = app.state::<PendingFiles>();
+    for path in startup_files(args) {
+        policy.allow_file(&path);
+        pending.push(path);
+    }
+}
+
+fn startup_files(args: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+    args.into_iter()
+        .map(PathBuf::from)
+        .filter(|path| is_message(path))
+        .collect()
+}
+
+/// The file a second instance forwarded as `arg`, resolved against its working directory
+/// and canonicalized, if it exists and is a message or archive by content as well as by
+/// extension
+fn check_forwarded(cwd: &Path, arg: &str) -> Result<PathBuf, String> {
+    if arg.starts_with('-') {
+        return Err("not a file argument".to_string());
+    }
+    let path = cwd
+        .join(arg)
+        .canonicalize()
+        .map_err(|e| format!("can't be resolved ({})", e))?;
+    if !path.is_file() {
+        return Err("not a file".to_string());
+    }
+    if !is_openable(&path) {
+        return Err("unsupported file type".to_string());
+    }
+    let mut sample = Vec::new();
+    std::fs::File::open(&path)
+        .and_then(|file| file.take(SNIFF_LEN).read_to_end(&mut sample))
+        .map_err(|e| format!("can't be read ({})", e))?;
+    let fits = match extension(&path).as_deref() {
+        Some("msg") => sample.starts_with(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1"),
+        Some("zip") => sample.starts_with(b"PK\x03\x04") || sample.starts_with(b"PK\x05\x06"),
+        // A header block with the fields of an RFC 5322 message, not just any text
+        _ => sniff::detect(&sample) == Some("message/rfc822"),
+    };
+    if !fits {
+        return Err("content doesn't match its extension".to_string());
+    }
+    Ok(path)
+}
+
+/// Files a second instance forwarded as `args` (its own executable first), checked with
+/// `check_forwarded` and rate-limited; rejected ones are logged with the arguments
+fn forwarded_files(forwards: &Forwards, args: &[String], cwd: &Path, now: Instant) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for arg in args
+        .iter()
+        .skip(1)
+        .filter(|arg| *arg != quarantine::CLI_FLAG)
+    {
+        let checked = check_forwarded(cwd, arg).and_then(|path| {
+            if forwards.admit(now) {
+                Ok(path)
+            } else {
+                Err("too many files forwarded at once".to_string())
+            }
+        });
+        match checked {
+            Ok(path) => files.push(path),
+            Err(reason) => log_line!(
+                "Rejected file forwarded by a second instance, {}: {:?} (arguments {:?})",
+                reason,
+                arg,
+                args
+            ),
+        }
+    }
+    files
+}
+
+/// A second instance was started (Windows/Linux): open its files here instead
+pub fn on_second_instance(app: &AppHandle, args: Vec<String>, cwd: String) {
+    // Quarantine mode can be switched on this way, never off
+    if quarantine::requested(&args) {
+        quarantine::force(app);
+    }
+    if args.len() > 1 {
+        let forwards = app.state::<Forwards>();
+        let files = forwarded_files(&forwards, &args, Path::new(&cwd), Instant::now());
+        if !files.is_empty() {
+            // Nothing is allowed until the user confirms, see `Forwards::answer`
+            forwards.hold(&files);
+            let paths = files
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            if let Err(e) = app.emit("forwarded-open-request", ForwardedOpenRequest { paths }) {
+                log_line!("Failed to emit forwarded-open-request event: {}", e);
+            }
+        }
+    }
+    // Focus the main window
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_focus();
+    }
+}
+
+/// Files (and folders) dropped on a window
+pub fn on_drop(window: &tauri::Window, paths: &[PathBuf]) {
+    // Dropped files (and folders) are the user's choice, like files opened via the OS
+    let policy = window.state::<access::AccessPolicy>();
+    for path in paths {
+        policy.allow_file(path);
+    }
+    // The frontend opens dropped files itself, except for bulk drops
+    let app = window.app_handle();
+    if open_queue::is_bulk(app, paths.len()) {
+        let paths = paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        open_queue::emit_bulk(app, paths);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Harness;
+
+    #[test]
+    fn mangled_names_are_recognized_and_renamed_with_their_documents() {
+        let harness = Harness::new(
+            "mangled",
+            &[
+                "RE%3A%20Invoice%20%2312345.msg",
+                "message.msg.msg",
+                "Invoice%2Emsg",
+                "clean.eml",
+            ],
+        );
+        assert!(is_message(&harness.path("message.msg.msg")));
+        assert!(is_message(&harness.path("Invoice%2Emsg")));
+
+        let registry = Mutex::new(Registry::default());
+        let mangled = harness.path("RE%3A%20Invoice%20%2312345.msg");
+        let doc = registry.lock().unwrap().open(mangled.clone(), "main");
+        assert_eq!(doc.display_name.as_deref(), Some("RE: Invoice #12345.msg"));
+
+        let resolved = mangled.canonicalize().unwrap();
+        let (target, documents) = rename_clean(&registry, &resolved).unwrap();
+        let clean =
+            filenames::sanitize_filename("RE: Invoice #12345.msg", filenames::TargetOs::CURRENT);
+        assert_eq!(target.file_name().unwrap().to_string_lossy(), clean);
+        assert!(target.exists() && !mangled.exists());
+        assert_eq!(documents, [doc.id.as_str()]);
+        let renamed = registry.lock().unwrap().get(&doc.id).cloned().unwrap();
+        assert_eq!((renamed.path, renamed.display_name), (target.clone(), None));
+
+        // Taken names are numbered, clean ones left alone
+        std::fs::write(harness.path("message (2).msg"), b"").unwrap();
+        std::fs::write(harness.path("message.msg"), b"").unwrap();
+        let (target, _) = rename_clean(&registry, &harness.path("message.msg.msg")).unwrap();
+        assert_eq!(target, harness.path("message (3).msg"));
+        let clean = harness.path("clean.eml");
+        assert_eq!(
+            rename_clean(&registry, &clean).unwrap(),
+            (clean, Vec::new())
+        );
+    }
+
+    #[test]
+    fn windows_get_their_own_files_and_launch_files_are_taken_once() {
+        let harness = Harness::new("pending", &[]);
+        harness.pending.push(PathBuf::from("a.msg"));
+        harness
+            .windows
+            .assign("doc-1", vec![PathBuf::from("b.eml")]);
+
+        assert_eq!(
+            take_pending(&harness.windows, &harness.pending, "doc-1"),
+            Pending::Window(vec![PathBuf::from("b.eml")])
+        );
+        // A reload of that window is treated like the main one
+        assert_eq!(
+            take_pending(&harness.windows, &harness.pending, "doc-1"),
+            Pending::Launch(vec![PathBuf::from("a.msg")])
+        );
+        assert_eq!(
+            take_pending(&harness.windows, &harness.pending, "main"),
+            Pending::Launch(Vec::new())
+        );
+    }
+
+    #[test]
+    fn launch_files_supersede_the_session_and_are_allowed() {
+        let harness = Harness::new("startup", &["launch.msg", "a.eml", "b.msg"]);
+        let session = SessionState::default();
+        let restore = |session: &SessionState| {
+            session.hold(crate::session::Session {
+                files: vec![harness.path("a.eml"), harness.path("gone.msg")],
+                ..Default::default()
+            })
+        };
+        let check =
+            |path: &Path| access::check_with(&harness.policy, &[], &path.to_string_lossy()).is_ok();
+        let startup = |label: &str, session: &SessionState| {
+            startup(
+                &harness.windows,
+                &harness.pending,
+                session,
+                &harness.policy,
+                label,
+                20,
+            )
+        };
+
+        // Launched with a file: the session isn't restored, now or later
+        restore(&session);
+        harness.pending.push(harness.path("launch.msg"));
+        assert_eq!(
+            startup("main", &session),
+            Startup {
+                files: vec![harness.path("launch.msg")],
+                ..Startup::default()
+            }
+        );
+        assert!(check(&harness.path("launch.msg")));
+        assert_eq!(startup("main", &session), Startup::default());
+
+        // Launched without: the session's files, the missing ones reported
+        let session = SessionState::default();
+        restore(&session);
+        assert_eq!(
+            startup("main", &session),
+            Startup {
+                files: vec![harness.path("a.eml")],
+                bulk: false,
+                missing: vec![harness.path("gone.msg")],
+            }
+        );
+        assert!(check(&harness.path("a.eml")));
+
+        // A window opened for a file leaves the session for the main window
+        let session = SessionState::default();
+        restore(&session);
+        harness.windows.assign("doc-1", vec![harness.path("b.msg")]);
+        assert_eq!(startup("doc-1", &session).files, [harness.path("b.msg")]);
+        assert_eq!(startup("main", &session).files, [harness.path("a.eml")]);
+    }
+
+    #[test]
+    fn launch_files_over_the_threshold_are_offered_in_bulk() {
+        let harness = Harness::new("startup-bulk", &[]);
+        let session = SessionState::default();
+        let launch = |count: usize| {
+            for i in 0..count {
+                harness.pending.push(harness.path(&format!("{i}.msg")));
+            }
+            startup(
+                &harness.windows,
+                &harness.pending,
+                &session,
+                &harness.policy,
+                "main",
+                3,
+            )
+        };
+        let at_threshold = launch(3);
+        assert_eq!((at_threshold.files.len(), at_threshold.bulk), (3, false));
+        let over = launch(4);
+        assert_eq!((over.files.len(), over.bulk), (4, true));
+    }
+
+    #[test]
+    fn files_opened_through_the_os_are_allowed_and_queued() {
+        let harness = Harness::new("os-open", &["a.msg", "b.eml", "notes.txt"]);
+        let queue = OpenQueue::default();
+        assert_eq!(
+            queue_open(&harness.policy, &queue, harness.path("notes.txt")),
+            Err(harness.path("notes.txt"))
+        );
+        assert_eq!(
+            queue_open(&harness.policy, &queue, harness.path("a.msg")),
+            Ok(Some(1))
+        );
+        // The burst is already scheduled
+        assert_eq!(
+            queue_open(&harness.policy, &queue, harness.path("b.eml")),
+            Ok(None)
+        );
+        let path = harness.path("b.eml").to_string_lossy().to_string();
+        assert!(access::check_with(&harness.policy, &[], &path).is_ok());
+    }
+
+    #[test]
+    fn opened_files_are_filtered_and_allowed() {
+        let harness = Harness::new(
+            "file-open",
+            &["inbox/a.MSG", "inbox/b.zip", "inbox/c.txt", "other/d.eml"],
+        );
+        let path = |file: &str| harness.path(file).to_string_lossy().to_string();
+        let check = |file: &str| access::check_with(&harness.policy, &[], &path(file));
+
+        assert!(!accept(&harness.policy, &harness.path("inbox/c.txt")));
+        assert!(check("inbox/c.txt").is_err());
+
+        assert!(accept(&harness.policy, &harness.path("inbox/a.MSG")));
+        assert!(accept(&harness.policy, &harness.path("inbox/b.zip")));
+        // Its folder comes along, other folders don't
+        assert!(check("inbox/c.txt").is_ok());
+        assert!(check("other/d.eml").is_err());
+
+        // Archives only open in a running app
+        assert_eq!(
+            startup_files([
+                path("inbox/a.MSG"),
+                path("inbox/b.zip"),
+                path("other/d.eml")
+            ]),
+            vec![harness.path("inbox/a.MSG"), harness.path("other/d.eml")]
+        );
+    }
+
+    #[test]
+    fn forwarded_files_are_checked_and_rate_limited() {
+        let harness = Harness::new("forwarded", &["notes.txt", "fake.msg", "empty.zip"]);
+        std::fs::write(harness.path("fake.msg"), b"MZ\x90\0 not a message").unwrap();
+        std::fs::write(
+            harness.path("text.eml"),
+            b"Just some text\nover two lines\n",
+        )
+        .unwrap();
+        std::fs::write(
+            harness.path("real.msg"),
+            include_bytes!("../tests/fixtures/self-test/minimal.msg"),
+        )
+        .unwrap();
+        std::fs::write(
+            harness.path("real.eml"),
+            include_bytes!("../tests/fixtures/self-test/minimal.eml"),
+        )
+        .unwrap();
+        let forwards = Forwards::default();
+        let now = Instant::now();
+        let forward = |files: &[&str], now: Instant| {
+            let args: Vec<String> = std::iter::once("msg-reader")
+                .chain(files.iter().copied())
+                .map(str::to_string)
+                .collect();
+            forwarded_files(&forwards, &args, &harness.dir, now)
+        };
+        let canonical = |file: &str| harness.path(file).canonicalize().unwrap();
+
+        // Unsupported types, missing files, content not fitting the extension and options
+        // are all rejected
+        assert!(forward(
+            &[
+                "notes.txt",
+                "missing.msg",
+                "fake.msg",
+                "empty.zip",
+                "text.eml",
+                "--devtools"
+            ],
+            now
+        )
+        .is_empty());
+        assert_eq!(
+            forward(&["real.msg", "../forwarded-missing/x.eml", "real.eml"], now),
+            [canonical("real.msg"), canonical("real.eml")]
+        );
+
+        // A flood is cut off at the limit, until the window has passed
+        let flood = vec!["real.eml"; FORWARD_LIMIT * 2];
+        assert_eq!(forward(&flood, now).len(), FORWARD_LIMIT - 2);
+        assert!(forward(&["real.msg"], now + FORWARD_WINDOW / 2).is_empty());
+        assert_eq!(forward(&["real.msg"], now + FORWARD_WINDOW).len(), 1);
+    }
+
+    #[test]
+    fn forwarded_files_are_allowed_alone_once_confirmed() {
+        let harness = Harness::new("forwarded-answer", &["a.eml", "b.eml", "c.eml"]);
+        let path = |file: &str| harness.path(file).canonicalize().unwrap();
+        let arg = |file: &str| path(file).to_string_lossy().to_string();
+        let check = |file: &str| access::check_with(&harness.policy, &[], &arg(file));
+        let forwards = Forwards::default();
+        forwards.hold(&[path("a.eml"), path("b.eml")]);
+
+        // Declined, or never forwarded: nothing is allowed
+        assert!(forwards
+            .answer(&harness.policy, &[arg("a.eml")], false)
+            .is_empty());
+        assert!(forwards
+            .answer(&harness.policy, &[arg("a.eml"), arg("c.eml")], true)
+            .is_empty());
+        assert!(check("a.eml").is_err() && check("c.eml").is_err());
+
+        // Accepted: the file, not the files next to it
+        assert_eq!(
+            forwards.answer(&harness.policy, &[arg("b.eml")], true),
+            [path("b.eml")]
+        );
+        assert!(check("b.eml").is_ok());
+        assert!(check("c.eml").is_err());
+        assert!(forwards
+            .answer(&harness.policy, &[arg("b.eml")], true)
+            .is_empty());
+    }
+}
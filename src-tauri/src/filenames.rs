@@ -0,0 +1,367 @@
+use std::path::{Path, PathBuf};
+
+/// Name used when nothing usable is left of an untrusted name
+const FALLBACK_NAME: &str = "attachment";
+
+/// Longest file name component: 255 bytes on Unix file systems, 255 UTF-16 code units on NTFS
+const MAX_COMPONENT_LENGTH: usize = 255;
+
+/// Extensions longer than this are treated as part of the name when truncating
+const MAX_EXTENSION_LENGTH: usize = 16;
+
+/// Characters Windows rejects in file names, in addition to control characters
+const WINDOWS_RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves regardless of extension (`NUL.txt` is the device too)
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// File system rules a name has to follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetOs {
+    Windows,
+    Unix,
+}
+
+impl TargetOs {
+    /// The OS the app runs on
+    pub const CURRENT: TargetOs = if cfg!(windows) {
+        TargetOs::Windows
+    } else {
+        TargetOs::Unix
+    };
+
+    /// Length of a name as the file system counts it
+    fn length(self, name: &str) -> usize {
+        match self {
+            TargetOs::Windows => name.encode_utf16().count(),
+            TargetOs::Unix => name.len(),
+        }
+    }
+}
+
+/// Turn an untrusted name (attachment, subject, ...) into a valid file name for `target_os`.
+///
+/// Path separators, control and bidi override characters (which can disguise `exe.txt` as
+/// `txt.exe`) become `_`; on Windows also the reserved characters, device names and
+/// trailing dots and spaces. Overlong names are shortened, keeping the extension.
+/// Sanitizing a sanitized name returns it unchanged.
+pub fn sanitize_filename(name: &str, target_os: TargetOs) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            let reserved = match target_os {
+                TargetOs::Windows => WINDOWS_RESERVED_CHARS.contains(&c),
+                TargetOs::Unix => c == '/',
+            };
+            if reserved || c.is_control() || is_bidi_control(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let mut name = trim(&replaced, target_os).to_string();
+    if name.is_empty() || name == "." || name == ".." {
+        return FALLBACK_NAME.to_string();
+    }
+    if target_os == TargetOs::Windows && is_reserved_device(&name) {
+        name.insert(0, '_');
+    }
+
+    let (stem, extension) = split_extension(&name);
+    fit(stem, "", extension, target_os)
+}
+
+/// A path in `dir` for `name` (already sanitized) that doesn't exist yet: the name itself, or
+/// the first free `name (2).ext`, `name (3).ext`, ...
+pub fn dedupe_in_dir(dir: &Path, name: &str) -> PathBuf {
+    dedupe_with(dir, name, |_| false)
+}
+
+/// Like `dedupe_in_dir`, also skipping the paths `taken` reports (planned but not written)
+pub fn dedupe_with(dir: &Path, name: &str, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let free = |candidate: &PathBuf| !candidate.exists() && !taken(candidate);
+    let candidate = dir.join(name);
+    if free(&candidate) {
+        return candidate;
+    }
+    let (stem, extension) = split_extension(name);
+    (2..)
+        .map(|n| {
+            let suffix = format!(" ({})", n);
+            dir.join(fit(stem, &suffix, extension, TargetOs::CURRENT))
+        })
+        .find(free)
+        .expect("unbounded counter")
+}
+
+/// A downloaded file's name as its sender wrote it: `%XX` escapes left by webmail and
+/// ticketing systems decoded (`RE%3A%20Invoice.msg`, when all of them decode to text) and
+/// an extension repeated at the end given once (`message.msg.msg`)
+pub fn readable_name(name: &str) -> String {
+    let mut name = decode_escapes(name).unwrap_or_else(|| name.to_string());
+    loop {
+        let (stem, extension) = split_extension(&name);
+        let (_, previous) = split_extension(stem);
+        if extension.len() <= 1 || !previous.eq_ignore_ascii_case(extension) {
+            return name;
+        }
+        name.truncate(stem.len());
+    }
+}
+
+/// The readable form of the file name of `path`, when it differs from the name on disk
+pub fn display_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    let readable = readable_name(&name);
+    (readable != name).then_some(readable)
+}
+
+/// Lowercased extension of the readable name of `path`, without dot; what the supported
+/// type checks go by, so `Invoice%2Emsg` is a message file
+pub fn effective_extension(path: &Path) -> Option<String> {
+    let name = readable_name(&path.file_name()?.to_string_lossy());
+    let (_, extension) = name.rsplit_once('.')?;
+    (!extension.is_empty() && !extension.contains(['/', '\\'])).then(|| extension.to_lowercase())
+}
+
+/// The name a mangled file is renamed to by `normalize_filename`: its readable name,
+/// sanitized for this OS (an escaped `/` mustn't move it elsewhere)
+pub fn clean_name(name: &str) -> String {
+    sanitize_filename(&readable_name(name), TargetOs::CURRENT)
+}
+
+/// `name` with its `%XX` escapes decoded; `None` without any, or when one doesn't decode
+/// to text, since then the `%` was likely meant (`100%.msg`)
+fn decode_escapes(name: &str) -> Option<String> {
+    let bytes = name.as_bytes();
+    let escape = |i: usize| {
+        let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+        u8::from_str_radix(hex, 16).ok()
+    };
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut escapes = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], escape(i)) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                escapes += 1;
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    (escapes > 0 && !decoded.contains(|c: char| c.is_control())).then_some(decoded)
+}
+
+/// `stem + suffix + extension`, with `stem` shortened until the whole name fits
+fn fit(stem: &str, suffix: &str, extension: &str, target_os: TargetOs) -> String {
+    let fixed = target_os.length(suffix) + target_os.length(extension);
+    let mut stem = stem;
+    while target_os.length(stem) + fixed > MAX_COMPONENT_LENGTH {
+        let mut end = stem.len() - 1;
+        while !stem.is_char_boundary(end) {
+            end -= 1;
+        }
+        stem = &stem[..end];
+    }
+    // Shortening can expose trailing spaces or dots; without an extension they would end
+    // the name
+    let stem = if extension.is_empty() && suffix.is_empty() {
+        trim(stem, target_os)
+    } else {
+        stem.trim_start()
+    };
+    if stem.is_empty() {
+        return format!("{}{}{}", FALLBACK_NAME, suffix, extension);
+    }
+    format!("{}{}{}", stem, suffix, extension)
+}
+
+/// Split off a short extension (with its dot); dot files and long "extensions" have none
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(pos) if pos > 0 && name.len() - pos <= MAX_EXTENSION_LENGTH => name.split_at(pos),
+        _ => (name, ""),
+    }
+}
+
+/// Trim whitespace at both ends; Windows also drops trailing dots
+fn trim(name: &str, target_os: TargetOs) -> &str {
+    let name = name.trim();
+    match target_os {
+        TargetOs::Windows => name.trim_end_matches(|c: char| c == '.' || c.is_whitespace()),
+        TargetOs::Unix => name,
+    }
+}
+
+/// `CON`, `nul.txt`, `Com1.tar.gz`: the part before the first dot names a device
+fn is_reserved_device(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or_default().trim_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| base.eq_ignore_ascii_case(reserved))
+}
+
+/// Invisible characters that reorder the text around them
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Characters that exercise every rule, mixed with ordinary ones
+    const ALPHABET: &[char] = &[
+        'a', 'C', 'o', 'N', 'l', '1', '.', ' ', '/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0',
+        '\t', '\u{7f}', '\u{202e}', 'é', '日', '😀', '-',
+    ];
+
+    /// Whether `name` is accepted as a file name component on `target_os`
+    fn is_valid(name: &str, target_os: TargetOs) -> bool {
+        let common = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.contains(['/', '\0'])
+            && target_os.length(name) <= MAX_COMPONENT_LENGTH;
+        match target_os {
+            TargetOs::Unix => common,
+            TargetOs::Windows => {
+                common
+                    && !name.contains(WINDOWS_RESERVED_CHARS)
+                    && !name.chars().any(|c| c.is_control())
+                    && !name.ends_with(['.', ' '])
+                    && !is_reserved_device(name)
+            }
+        }
+    }
+
+    /// Random names from a fixed seed (xorshift), so failures are reproducible
+    fn random_names(count: usize) -> Vec<String> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let len = (next() % 300) as usize;
+                (0..len)
+                    .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sanitized_names_are_valid_and_stable() {
+        let mut names = random_names(5000);
+        names.extend(
+            [
+                "",
+                ".",
+                "..",
+                "CON",
+                "nul.txt",
+                "com1 .tar.gz",
+                "report. . ",
+                "a/../b",
+            ]
+            .map(str::to_string),
+        );
+        for name in &names {
+            for target_os in [TargetOs::Windows, TargetOs::Unix] {
+                let sanitized = sanitize_filename(name, target_os);
+                assert!(
+                    is_valid(&sanitized, target_os),
+                    "{:?} -> {:?}",
+                    name,
+                    sanitized
+                );
+                assert_eq!(sanitize_filename(&sanitized, target_os), sanitized);
+            }
+        }
+    }
+
+    #[test]
+    fn sanitizing_examples() {
+        let windows = |name| sanitize_filename(name, TargetOs::Windows);
+        assert_eq!(windows("Q3: results?.xlsx"), "Q3_ results_.xlsx");
+        assert_eq!(windows("invoice\u{202e}fdp.exe"), "invoice_fdp.exe");
+        assert_eq!(windows("aux.log"), "_aux.log");
+        assert_eq!(windows("notes..."), "notes");
+        assert_eq!(sanitize_filename("notes...", TargetOs::Unix), "notes...");
+        assert_eq!(sanitize_filename("a\\b:c", TargetOs::Unix), "a\\b:c");
+
+        // Truncation keeps the extension and counts UTF-16 units on Windows
+        let long = format!("{}.pdf", "日".repeat(200));
+        assert_eq!(windows(&long), long);
+        let unix = sanitize_filename(&long, TargetOs::Unix);
+        assert!(unix.len() <= MAX_COMPONENT_LENGTH && unix.ends_with("日.pdf"));
+    }
+
+    #[test]
+    fn dedupe_appends_the_first_free_number() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert_eq!(dedupe_in_dir(dir, "a.txt"), dir.join("a.txt"));
+        std::fs::write(dir.join("a.txt"), b"").unwrap();
+        std::fs::write(dir.join("a (2).txt"), b"").unwrap();
+        assert_eq!(dedupe_in_dir(dir, "a.txt"), dir.join("a (3).txt"));
+
+        let long = format!("{}.txt", "x".repeat(251));
+        std::fs::write(dir.join(&long), b"").unwrap();
+        let deduped = dedupe_in_dir(dir, &long);
+        let name = deduped.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name.len(), MAX_COMPONENT_LENGTH);
+        assert!(name.ends_with("x (2).txt"));
+    }
+
+    #[test]
+    fn mangled_download_names_are_read_back() {
+        assert_eq!(
+            readable_name("RE%3A%20Invoice%20%2312345.msg"),
+            "RE: Invoice #12345.msg"
+        );
+        assert_eq!(readable_name("message.msg.msg"), "message.msg");
+        assert_eq!(readable_name("Report.EML.eml.eml"), "Report.EML");
+        assert_eq!(readable_name("Gr%C3%BC%C3%9Fe.msg.msg"), "Grüße.msg");
+        // A % that isn't an escape, or escapes that aren't text, are meant as they are
+        for name in [
+            "100%.msg",
+            "50%zz.eml",
+            "a%FF%FE.msg",
+            "line%0Abreak.msg",
+            "a.tar.msg",
+        ] {
+            assert_eq!(readable_name(name), name);
+        }
+
+        assert_eq!(
+            display_name(Path::new("/in/message.msg.msg")).as_deref(),
+            Some("message.msg")
+        );
+        assert_eq!(display_name(Path::new("/in/message.msg")), None);
+        assert_eq!(
+            effective_extension(Path::new("Invoice%2EMSG")).as_deref(),
+            Some("msg")
+        );
+        assert_eq!(effective_extension(Path::new("a%2Fb")), None);
+        assert_eq!(
+            sanitize_filename(&readable_name("RE%3A%20a%2Fb.msg"), TargetOs::Windows),
+            "RE_ a_b.msg"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Find within an open message, over what the webview's find-in-page can't see: header
+//! fields, both bodies (quotes collapsed in the view included), attachment names and the
+//! subjects of attached messages. Bodies are searched as text, HTML converted the way
+//! snippets are (`text::html_to_text`). Matches come in a fixed order, field by field, so
+//! repeated calls on an unchanged document number them the same for next/previous.
+
+use crate::export::format_addresses;
+use crate::parser::{text, Message};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Matches beyond this many are dropped
+const MAX_MATCHES: usize = 1000;
+
+/// Longest pattern accepted, in bytes
+const MAX_PATTERN: usize = 1000;
+
+/// Compiled size limit of user-supplied patterns; `regex` runs in linear time, so the
+/// size of the automaton is what a pathological pattern can blow up
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// A search stops (`truncated`) after this long
+const TIME_BUDGET: Duration = Duration::from_secs(2);
+
+/// Characters of context on each side of a match in its snippet
+const SNIPPET_CONTEXT: usize = 40;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    /// Matches must start and end at word boundaries
+    pub whole_word: bool,
+    /// The query is a regular expression (`regex` crate syntax)
+    pub regex: bool,
+}
+
+/// Where a match is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindField {
+    Subject,
+    From,
+    To,
+    Cc,
+    Bcc,
+    BodyText,
+    /// The text of the HTML body
+    BodyHtml,
+    AttachmentName,
+    /// Subject of the attached message at `path`
+    NestedSubject,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FindMatch {
+    pub field: FindField,
+    /// Attachment indices down to the attached message the field belongs to, as in
+    /// `part_path`; empty for the message itself
+    pub path: Vec<usize>,
+    /// Index of the attachment, for `attachment_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<usize>,
+    /// Range of the match in the field's text, in UTF-16 code units like JavaScript
+    /// string indices
+    pub start: usize,
+    pub end: usize,
+    /// The match with some context, on one line
+    pub snippet: String,
+    /// Where the match starts in `snippet`, in UTF-16 code units
+    pub snippet_start: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FindResult {
+    pub matches: Vec<FindMatch>,
+    /// More matches were found than returned, or the search ran out of time
+    pub truncated: bool,
+}
+
+/// Build the matcher for `query`; invalid patterns fail with
+/// `Invalid search pattern: <reason>`
+pub fn pattern(query: &str, options: &FindOptions) -> Result<Regex, String> {
+    if query.len() > MAX_PATTERN {
+        return Err(format!(
+            "Invalid search pattern: longer than {} bytes",
+            MAX_PATTERN
+        ));
+    }
+    let source = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Every match of `query` in `message`
+pub fn find(message: &Message, query: &str, options: &FindOptions) -> Result<FindResult, String> {
+    let mut result = FindResult::default();
+    if query.is_empty() {
+        return Ok(result);
+    }
+    let search = Search {
+        pattern: pattern(query, options)?,
+        whole_word: options.whole_word,
+        deadline: Instant::now() + TIME_BUDGET,
+    };
+
+    let mut fields: Vec<(FindField, Option<usize>, String)> = vec![
+        (
+            FindField::Subject,
+            None,
+            message.subject.clone().unwrap_or_default(),
+        ),
+        (
+            FindField::From,
+            None,
+            format_addresses(message.from.as_slice()),
+        ),
+        (FindField::To, None, format_addresses(&message.to)),
+        (FindField::Cc, None, format_addresses(&message.cc)),
+        (FindField::Bcc, None, format_addresses(&message.bcc)),
+        (
+            FindField::BodyText,
+            None,
+            message.body_text.clone().unwrap_or_default(),
+        ),
+        (
+            FindField::BodyHtml,
+            None,
+            message
+                .body_html
+                .as_deref()
+                .map(|html| text::html_to_text(html, None))
+                .unwrap_or_default(),
+        ),
+    ];
+    fields.extend(message.attachments.iter().map(|attachment| {
+        (
+            FindField::AttachmentName,
+            Some(attachment.index),
+            attachment.file_name.clone(),
+        )
+    }));
+    for (field, attachment, text) in fields {
+        if !search.run(&text, field, &[], attachment, &mut result) {
+            return Ok(result);
+        }
+    }
+    nested_subjects(message, &mut Vec::new(), &search, &mut result);
+    Ok(result)
+}
+
+/// Search the subjects of the attached messages, depth first; false once out of budget
+fn nested_subjects(
+    message: &Message,
+    path: &mut Vec<usize>,
+    search: &Search,
+    result: &mut FindResult,
+) -> bool {
+    for attachment in &message.attachments {
+        let Some(nested) = &attachment.nested else {
+            continue;
+        };
+        path.push(attachment.index);
+        let subject = nested.subject.as_deref().unwrap_or_default();
+        let more = search.run(subject, FindField::NestedSubject, path, None, result)
+            && nested_subjects(nested, path, search, result);
+        path.pop();
+        if !more {
+            return false;
+        }
+    }
+    true
+}
+
+struct Search {
+    pattern: Regex,
+    whole_word: bool,
+    deadline: Instant,
+}
+
+impl Search {
+    /// Add the matches in `text`; false, with the result truncated, once out of budget
+    fn run(
+        &self,
+        text: &str,
+        field: FindField,
+        path: &[usize],
+        attachment: Option<usize>,
+        result: &mut FindResult,
+    ) -> bool {
+        // UTF-16 offset of `counted` bytes into the text, advanced match by match
+        let (mut counted, mut units) = (0, 0);
+        let mut utf16 = |byte: usize| {
+            units += utf16_len(&text[counted..byte]);
+            counted = byte;
+            units
+        };
+        for found in self.pattern.find_iter(text) {
+            if Instant::now() > self.deadline || result.matches.len() == MAX_MATCHES {
+                result.truncated = true;
+                return false;
+            }
+            if found.is_empty() || (self.whole_word && !at_word_bounds(text, found.range())) {
+                continue;
+            }
+            let start = utf16(found.start());
+            let end = utf16(found.end());
+            let (snippet, snippet_start) = snippet(text, found.range());
+            result.matches.push(FindMatch {
+                field,
+                path: path.to_vec(),
+                attachment,
+                start,
+                end,
+                snippet,
+                snippet_start,
+            });
+        }
+        true
+    }
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn at_word_bounds(text: &str, range: std::ops::Range<usize>) -> bool {
+    let before = text[..range.start].chars().next_back();
+    let after = text[range.end..].chars().next();
+    let first = text[range.clone()].chars().next();
+    let last = text[range].chars().next_back();
+    let bound = |outside: Option<char>, inside: Option<char>| {
+        !(outside.is_some_and(is_word) && inside.is_some_and(is_word))
+    };
+    bound(before, first) && bound(after, last)
+}
+
+/// The match with up to `SNIPPET_CONTEXT` characters around it, line breaks as spaces, and
+/// where the match starts in it
+fn snippet(text: &str, range: std::ops::Range<usize>) -> (String, usize) {
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[range.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(text.len(), |(i, _)| range.end + i);
+    let flatten = |part: &str| part.replace(['\r', '\n', '\t'], " ");
+    let before = flatten(&text[start..range.start]);
+    (
+        format!("{}{}", before, flatten(&text[range.start..end])),
+        utf16_len(&before),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    #[test]
+    fn matches_cover_headers_bodies_and_attachments_in_a_stable_order() {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            b"From: Ana <ana@example.com>\r\nTo: Budget Team <budget@example.com>\r\n\
+              Subject: Budget 2025\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+              --b\r\nContent-Type: multipart/alternative; boundary=a\r\n\r\n\
+              --a\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n\
+              See the budget.\r\n> Old budgeting notes\r\n\
+              --a\r\nContent-Type: text/html; charset=utf-8\r\n\r\n\
+              <p>See the <b>budget</b>.</p><blockquote>\xc3\xa9 budget</blockquote>\r\n\
+              --a--\r\n--b\r\nContent-Type: application/pdf\r\n\
+              Content-Disposition: attachment; filename=budget.pdf\r\n\r\nx\r\n\
+              --b\r\nContent-Type: message/rfc822\r\n\r\n\
+              Subject: Re: Budget\r\n\r\nnested\r\n--b--\r\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        let words = FindOptions {
+            whole_word: true,
+            ..FindOptions::default()
+        };
+        let result = find(&message, "budget", &words).unwrap();
+        let fields: Vec<FindField> = result.matches.iter().map(|m| m.field).collect();
+        assert_eq!(
+            fields,
+            [
+                FindField::Subject,
+                FindField::To,
+                FindField::To,
+                FindField::BodyText,
+                FindField::BodyHtml,
+                FindField::BodyHtml,
+                FindField::AttachmentName,
+                // The attached message is named after its subject
+                FindField::AttachmentName,
+                FindField::NestedSubject,
+            ]
+        );
+        // "budgeting" isn't a whole word match, "é budget" counts é as one UTF-16 unit
+        let quoted = &result.matches[5];
+        assert_eq!(quoted.snippet, "See the budget.  > é budget");
+        assert_eq!((quoted.start, quoted.end), (21, 27));
+        assert_eq!(quoted.snippet_start, 21);
+        assert_eq!(result.matches[6].attachment, Some(0));
+        assert_eq!(result.matches[8].path, [1]);
+        assert_eq!(
+            find(&message, "budget", &words).unwrap().matches,
+            result.matches
+        );
+
+        let case = FindOptions {
+            case_sensitive: true,
+            ..FindOptions::default()
+        };
+        assert_eq!(find(&message, "Budget", &case).unwrap().matches.len(), 4);
+        let regex = FindOptions {
+            regex: true,
+            ..FindOptions::default()
+        };
+        assert_eq!(find(&message, r"20\d\d", &regex).unwrap().matches.len(), 1);
+        assert!(find(&message, "(", &regex)
+            .unwrap_err()
+            .starts_with("Invalid search pattern"));
+        assert!(find(&message, &"a{1000}".repeat(100), &regex).is_err());
+    }
+}
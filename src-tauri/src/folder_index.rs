@@ -0,0 +1,497 @@
+use crate::i18n::Lang;
+use crate::notes::{self, Notes, TagMode};
+use crate::previews::MessagePreview;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Largest page served at once
+pub const MAX_PAGE: usize = 1000;
+
+/// Order of a folder listing; ties are broken by path, so pages never overlap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    DateDesc,
+    DateAsc,
+    Sender,
+    Subject,
+    Name,
+}
+
+/// Which files of a folder listing are shown; all fields are optional
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListingFilter {
+    /// Case-insensitive part of the sender's name or address
+    pub sender: Option<String>,
+    /// Case-insensitive part of an address the message was delivered to (`alias+shop@`)
+    pub delivered_to: Option<String>,
+    /// RFC 3339 timestamps or `YYYY-MM-DD` dates (both ends included); files without a
+    /// date are left out once either is set
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub has_attachments: bool,
+    pub tag: Option<String>,
+    /// A label or keyword of the mailbox the file was exported from, in any case
+    pub label: Option<String>,
+    /// Language code of the body (`de`), in any case; `de-AT` matches `de` too
+    pub language: Option<String>,
+    /// Only files that failed to parse
+    pub errors_only: bool,
+}
+
+/// A window of a folder listing
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexPage {
+    /// Files matching the filter
+    pub total: usize,
+    pub offset: usize,
+    /// Changes whenever the folder was indexed anew, after which earlier pages are stale
+    pub generation: u64,
+    pub entries: Vec<MessagePreview>,
+}
+
+/// Listing metadata of a folder's message files as of one moment
+pub struct FolderIndex {
+    generation: u64,
+    lang: Lang,
+    entries: Vec<MessagePreview>,
+    /// Entry positions by sort order, computed once per order
+    orders: Mutex<HashMap<SortBy, Arc<Vec<usize>>>>,
+    /// Content hashes (the key of tags) by entry position, computed for tag filters only
+    hashes: Mutex<HashMap<usize, Option<String>>>,
+}
+
+/// Snapshots of the folders being listed, by canonical path; the watcher drops a folder's
+/// snapshot when its files change
+#[derive(Default)]
+pub struct FolderIndexes {
+    folders: Mutex<HashMap<PathBuf, Arc<FolderIndex>>>,
+    generation: AtomicU64,
+}
+
+impl FolderIndexes {
+    /// The snapshot of `dir` (already checked against the access policy), indexing the
+    /// folder's message files with `preview` unless one is kept for `lang`
+    pub fn get(
+        &self,
+        dir: &Path,
+        lang: Lang,
+        preview: impl FnMut(&Path) -> MessagePreview,
+        before_each: impl FnMut(usize, usize) -> Result<(), String>,
+    ) -> Result<Arc<FolderIndex>, String> {
+        let key = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        if let Some(index) = self
+            .folders
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|index| index.lang == lang)
+        {
+            return Ok(index.clone());
+        }
+        let index = Arc::new(self.build(dir, lang, preview, before_each)?);
+        self.folders.lock().unwrap().insert(key, index.clone());
+        Ok(index)
+    }
+
+    fn build(
+        &self,
+        dir: &Path,
+        lang: Lang,
+        mut preview: impl FnMut(&Path) -> MessagePreview,
+        mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+    ) -> Result<FolderIndex, String> {
+        let paths = crate::attachments::message_files(dir)?;
+        let mut entries = Vec::with_capacity(paths.len());
+        for (current, path) in paths.iter().enumerate() {
+            before_each(current, paths.len())?;
+            entries.push(preview(path));
+        }
+        Ok(FolderIndex {
+            generation: self.generation.fetch_add(1, Ordering::Relaxed) + 1,
+            lang,
+            entries,
+            orders: Mutex::default(),
+            hashes: Mutex::default(),
+        })
+    }
+
+    /// Forget the snapshot of `dir`; the next page request indexes the folder again
+    pub fn invalidate(&self, dir: &Path) {
+        let key = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.folders.lock().unwrap().remove(&key);
+    }
+}
+
+impl FolderIndex {
+    /// Up to `limit` (at most `MAX_PAGE`) entries matching `filter` from `offset` on
+    pub fn page(
+        &self,
+        offset: usize,
+        limit: usize,
+        sort_by: SortBy,
+        filter: &ListingFilter,
+        notes: &Notes,
+    ) -> IndexPage {
+        let order = self.order(sort_by);
+        let matches = Matcher::new(filter, notes);
+        let mut total = 0;
+        let mut entries = Vec::new();
+        for &i in order.iter() {
+            if !matches.entry(self, i) {
+                continue;
+            }
+            if total >= offset && entries.len() < limit.min(MAX_PAGE) {
+                entries.push(self.entries[i].clone());
+            }
+            total += 1;
+        }
+        IndexPage {
+            total,
+            offset,
+            generation: self.generation,
+            entries,
+        }
+    }
+
+    /// Every entry matching `filter` as CSV, with a byte order mark so spreadsheet
+    /// applications read it as UTF-8; labels are separated by `; `, the language is a code
+    pub fn csv(&self, sort_by: SortBy, filter: &ListingFilter, notes: &Notes) -> String {
+        let field = crate::export::csv_field;
+        let matches = Matcher::new(filter, notes);
+        let mut csv = String::from(
+            "\u{feff}file,date,sender,subject,attachments,labels,flags,language,error\r\n",
+        );
+        for &i in self.order(sort_by).iter() {
+            if !matches.entry(self, i) {
+                continue;
+            }
+            let entry = &self.entries[i];
+            let fields = [
+                field(&entry.path),
+                field(entry.date.as_deref().unwrap_or_default()),
+                field(entry.sender.as_deref().unwrap_or_default()),
+                field(entry.subject.as_deref().unwrap_or_default()),
+                entry.attachments.to_string(),
+                field(&entry.source_labels.join("; ")),
+                entry
+                    .source_flags
+                    .map(|flags| flags.names().join(" "))
+                    .unwrap_or_default(),
+                entry
+                    .language
+                    .as_ref()
+                    .map(|language| field(&language.code))
+                    .unwrap_or_default(),
+                field(entry.error.as_deref().unwrap_or_default()),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    fn order(&self, sort_by: SortBy) -> Arc<Vec<usize>> {
+        let mut orders = self.orders.lock().unwrap();
+        if let Some(order) = orders.get(&sort_by) {
+            return order.clone();
+        }
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        let lower = |value: &Option<String>| value.as_deref().unwrap_or("").to_lowercase();
+        let dates: Vec<Option<DateTime<FixedOffset>>> = self
+            .entries
+            .iter()
+            .map(|entry| entry.date.as_deref().and_then(parse_timestamp))
+            .collect();
+        // Entries are in path order, which a stable sort keeps among equal keys
+        match sort_by {
+            SortBy::DateDesc => order.sort_by(|&a, &b| dates[b].cmp(&dates[a])),
+            SortBy::DateAsc => order.sort_by_key(|&i| dates[i]),
+            SortBy::Sender => order.sort_by_cached_key(|&i| lower(&self.entries[i].sender)),
+            SortBy::Subject => order.sort_by_cached_key(|&i| lower(&self.entries[i].subject)),
+            SortBy::Name => {}
+        }
+        let order = Arc::new(order);
+        orders.insert(sort_by, order.clone());
+        order
+    }
+
+    fn content_hash(&self, i: usize) -> Option<String> {
+        if let Some(hash) = self.hashes.lock().unwrap().get(&i) {
+            return hash.clone();
+        }
+        let hash = notes::content_hash(Path::new(&self.entries[i].path)).ok();
+        self.hashes.lock().unwrap().insert(i, hash.clone());
+        hash
+    }
+}
+
+/// A filter with its bounds parsed once per page
+struct Matcher<'a> {
+    filter: &'a ListingFilter,
+    sender: Option<String>,
+    delivered_to: Option<String>,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+    /// Set when a tag is asked for; false when no file carries it at all
+    tag: Option<(String, bool)>,
+    label: Option<String>,
+    language: Option<String>,
+    notes: &'a Notes,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(filter: &'a ListingFilter, notes: &'a Notes) -> Self {
+        let tag = filter
+            .tag
+            .as_deref()
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| (tag.to_string(), notes.any_tagged(&[tag.to_string()])));
+        let lower = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| value.trim().to_lowercase())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            filter,
+            sender: lower(&filter.sender),
+            delivered_to: lower(&filter.delivered_to),
+            label: lower(&filter.label),
+            language: lower(&filter.language)
+                .and_then(|language| language.split(['-', '_']).next().map(str::to_string)),
+            from: filter
+                .date_from
+                .as_deref()
+                .and_then(|d| parse_bound(d, false)),
+            to: filter.date_to.as_deref().and_then(|d| parse_bound(d, true)),
+            tag,
+            notes,
+        }
+    }
+
+    fn entry(&self, index: &FolderIndex, i: usize) -> bool {
+        let entry = &index.entries[i];
+        if self.filter.errors_only && entry.error.is_none() {
+            return false;
+        }
+        if self.filter.has_attachments && entry.attachments == 0 {
+            return false;
+        }
+        if let Some(sender) = &self.sender {
+            let matches = entry
+                .sender
+                .as_deref()
+                .is_some_and(|s| s.to_lowercase().contains(sender.as_str()));
+            if !matches {
+                return false;
+            }
+        }
+        if let Some(delivered_to) = &self.delivered_to {
+            let matches = entry
+                .delivered_to
+                .iter()
+                .any(|address| address.to_lowercase().contains(delivered_to.as_str()));
+            if !matches {
+                return false;
+            }
+        }
+        if let Some(label) = &self.label {
+            if !entry
+                .source_labels
+                .iter()
+                .any(|l| l.to_lowercase() == *label)
+            {
+                return false;
+            }
+        }
+        if let Some(language) = &self.language {
+            if entry
+                .language
+                .as_ref()
+                .is_none_or(|detected| detected.code != *language)
+            {
+                return false;
+            }
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let Some(date) = entry.date.as_deref().and_then(parse_timestamp) else {
+                return false;
+            };
+            if self.from.is_some_and(|from| date < from) || self.to.is_some_and(|to| date > to) {
+                return false;
+            }
+        }
+        if let Some((tag, any_tagged)) = &self.tag {
+            let tagged = *any_tagged
+                && index.content_hash(i).is_some_and(|hash| {
+                    self.notes
+                        .matches(&hash, std::slice::from_ref(tag), TagMode::Any)
+                });
+            if !tagged {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub(crate) fn parse_timestamp(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(date).ok()
+}
+
+/// A range bound: an RFC 3339 timestamp, or a UTC day whose start (or end) it stands for
+pub(crate) fn parse_bound(value: &str, end: bool) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if let Some(date) = parse_timestamp(value) {
+        return Some(date);
+    }
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let time = match end {
+        true => day.and_hms_opt(23, 59, 59)?,
+        false => day.and_hms_opt(0, 0, 0)?,
+    };
+    Some(time.and_utc().fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::grouping::AttachmentSummary;
+    use crate::parser::labels::SourceFlags;
+    use crate::parser::language::Language;
+
+    fn entry(path: &str, sender: &str, date: &str, attachments: usize) -> MessagePreview {
+        MessagePreview {
+            path: path.to_string(),
+            subject: None,
+            sender: Some(sender.to_string()),
+            date: Some(date.to_string()),
+            attachments,
+            attachment_summary: AttachmentSummary::default(),
+            delivered_to: Vec::new(),
+            source_labels: Vec::new(),
+            source_flags: None,
+            preview: String::new(),
+            language: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn pages_are_sorted_and_filtered() {
+        let index = FolderIndex {
+            generation: 1,
+            lang: Lang::En,
+            entries: vec![
+                entry(
+                    "a.eml",
+                    "Ann <ann@example.com>",
+                    "2026-10-01T09:00:00+00:00",
+                    0,
+                ),
+                entry(
+                    "b.eml",
+                    "Bob <bob@example.com>",
+                    "2026-10-03T09:00:00+00:00",
+                    2,
+                ),
+                MessagePreview {
+                    delivered_to: vec!["ann+shop@example.org".to_string()],
+                    source_labels: vec!["Inbox".to_string(), "Clients, 2024".to_string()],
+                    source_flags: Some(SourceFlags {
+                        read: true,
+                        flagged: true,
+                        ..SourceFlags::default()
+                    }),
+                    language: Some(Language {
+                        code: "de".to_string(),
+                        confidence: 0.9,
+                    }),
+                    ..entry("c.eml", "ann@example.org", "2026-10-03T09:00:00+00:00", 1)
+                },
+                entry(
+                    "d.eml",
+                    "Dora <dora@example.com>",
+                    "2026-09-01T09:00:00+02:00",
+                    0,
+                ),
+            ],
+            orders: Mutex::default(),
+            hashes: Mutex::default(),
+        };
+        let notes = Notes::in_memory();
+        let paths = |page: IndexPage| -> Vec<String> {
+            page.entries.into_iter().map(|entry| entry.path).collect()
+        };
+        let all = ListingFilter::default();
+
+        // Equal dates keep path order
+        let first = index.page(0, 2, SortBy::DateDesc, &all, &notes);
+        assert_eq!(first.total, 4);
+        assert_eq!(paths(first), ["b.eml", "c.eml"]);
+        let second = index.page(2, 2, SortBy::DateDesc, &all, &notes);
+        assert_eq!(paths(second), ["a.eml", "d.eml"]);
+
+        let filter = ListingFilter {
+            sender: Some("ANN@".to_string()),
+            date_from: Some("2026-10-02".to_string()),
+            ..ListingFilter::default()
+        };
+        assert_eq!(
+            paths(index.page(0, 10, SortBy::Name, &filter, &notes)),
+            ["c.eml"]
+        );
+        let filter = ListingFilter {
+            delivered_to: Some("Ann+Shop@".to_string()),
+            ..ListingFilter::default()
+        };
+        assert_eq!(
+            paths(index.page(0, 10, SortBy::Name, &filter, &notes)),
+            ["c.eml"]
+        );
+        let filter = ListingFilter {
+            language: Some("DE-at".to_string()),
+            ..ListingFilter::default()
+        };
+        assert_eq!(
+            paths(index.page(0, 10, SortBy::Name, &filter, &notes)),
+            ["c.eml"]
+        );
+        let filter = ListingFilter {
+            label: Some("clients, 2024".to_string()),
+            ..ListingFilter::default()
+        };
+        assert_eq!(
+            paths(index.page(0, 10, SortBy::Name, &filter, &notes)),
+            ["c.eml"]
+        );
+        assert_eq!(
+            index.csv(SortBy::Name, &filter, &notes),
+            "\u{feff}file,date,sender,subject,attachments,labels,flags,language,error\r\n\
+             c.eml,2026-10-03T09:00:00+00:00,ann@example.org,,1,\"Inbox; Clients, 2024\",read flagged,de,\r\n"
+        );
+        let filter = ListingFilter {
+            has_attachments: true,
+            date_to: Some("2026-10-03".to_string()),
+            tag: Some("urgent".to_string()),
+            ..ListingFilter::default()
+        };
+        assert_eq!(index.page(0, 10, SortBy::Name, &filter, &notes).total, 0);
+        let filter = ListingFilter {
+            tag: None,
+            ..filter
+        };
+        assert_eq!(
+            paths(index.page(0, 10, SortBy::Sender, &filter, &notes)),
+            ["c.eml", "b.eml"]
+        );
+    }
+}
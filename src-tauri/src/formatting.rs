@@ -0,0 +1,176 @@
+use crate::i18n::{self, Lang};
+use crate::parser::{headers, Message, MessageFormat};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How dates are written in exports and reports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateFormat {
+    /// `2026-10-14T09:30:00+02:00`
+    #[default]
+    Iso8601,
+    /// The export language's usual form (`date.locale_format` in the catalog)
+    Locale,
+    /// A strftime pattern (`%d.%m.%Y %H:%M`); invalid patterns fall back to ISO 8601
+    Custom(String),
+}
+
+/// Time zone dates are shown in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeZoneChoice {
+    Local,
+    Utc,
+    /// The sender's zone where the headers tell it, UTC otherwise
+    #[default]
+    Original,
+}
+
+/// Formats dates for one export, from the settings and the export language
+#[derive(Debug, Clone, Default)]
+pub struct DateFormatter {
+    pub format: DateFormat,
+    pub time_zone: TimeZoneChoice,
+    pub lang: Lang,
+}
+
+impl DateFormatter {
+    pub fn new(format: DateFormat, time_zone: TimeZoneChoice, lang: Lang) -> Self {
+        Self {
+            format,
+            time_zone,
+            lang,
+        }
+    }
+
+    /// An RFC 3339 timestamp as the parser stores them; missing, unparsable and epoch
+    /// (or earlier) dates, which stand for unset ones, give the `date.unknown` token
+    pub fn format(&self, date: Option<&str>) -> String {
+        match date.and_then(parse) {
+            Some(date) => self.format_datetime(date),
+            None => self.unknown(),
+        }
+    }
+
+    /// The sent date of a message. .msg files store it in UTC; for the original zone, the
+    /// offset comes from the transport headers' `Date` when they have one.
+    pub fn message_date(&self, message: &Message) -> String {
+        let Some(date) = message.date.as_deref().and_then(parse) else {
+            return self.unknown();
+        };
+        let header_offset = (message.format == MessageFormat::Msg
+            && self.time_zone == TimeZoneChoice::Original)
+            .then(|| {
+                message
+                    .headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("Date"))
+                    .and_then(|(_, value)| headers::parse_date(value))
+                    .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+                    .map(|header| *header.offset())
+            })
+            .flatten();
+        match header_offset {
+            Some(offset) => self.format_datetime(date.with_timezone(&offset)),
+            None => self.format_datetime(date),
+        }
+    }
+
+    pub fn now(&self) -> String {
+        self.format_datetime(Local::now().fixed_offset())
+    }
+
+    pub fn format_datetime(&self, date: DateTime<FixedOffset>) -> String {
+        let date = match self.time_zone {
+            TimeZoneChoice::Local => date.with_timezone(&Local).fixed_offset(),
+            TimeZoneChoice::Utc => date.with_timezone(&Utc).fixed_offset(),
+            TimeZoneChoice::Original => date,
+        };
+        let pattern = match &self.format {
+            DateFormat::Iso8601 => None,
+            DateFormat::Locale => Some(i18n::text(self.lang, "date.locale_format", &[])),
+            DateFormat::Custom(pattern) => Some(pattern.clone()),
+        };
+        match pattern.filter(|pattern| validate_pattern(pattern).is_ok()) {
+            Some(pattern) => date.format(&pattern).to_string(),
+            None => date.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        }
+    }
+
+    fn unknown(&self) -> String {
+        i18n::text(self.lang, "date.unknown", &[])
+    }
+}
+
+fn parse(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(date)
+        .ok()
+        .filter(|date| date.timestamp() > 0)
+}
+
+/// Check a custom strftime pattern; chrono can't render invalid ones
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("Empty date format".to_string());
+    }
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format: {}", pattern));
+    }
+    Ok(())
+}
+
+/// An RFC 3339 timestamp in RFC 5322 form, for `Date` headers; these follow the standard
+/// whatever the settings say
+pub fn rfc2822(date: &str) -> String {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.to_rfc2822())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_zones_and_unknown_dates() {
+        let sent = Some("2026-10-14T09:30:00+02:00");
+        let iso = DateFormatter::new(DateFormat::Iso8601, TimeZoneChoice::Original, Lang::En);
+        assert_eq!(iso.format(sent), "2026-10-14T09:30:00+02:00");
+        assert_eq!(iso.format(None), "unknown");
+        assert_eq!(iso.format(Some("1970-01-01T00:00:00+00:00")), "unknown");
+        assert_eq!(iso.format(Some("not a date")), "unknown");
+
+        let utc = DateFormatter::new(
+            DateFormat::Custom("%d.%m.%Y %H:%M %Z".to_string()),
+            TimeZoneChoice::Utc,
+            Lang::De,
+        );
+        assert_eq!(utc.format(sent), "14.10.2026 07:30 +00:00");
+        assert_eq!(utc.format(None), "unbekannt");
+
+        // Invalid custom patterns fall back to ISO 8601 instead of failing the export
+        assert!(validate_pattern("%Q").is_err());
+        let invalid = DateFormatter::new(
+            DateFormat::Custom("%Q".to_string()),
+            TimeZoneChoice::Original,
+            Lang::En,
+        );
+        assert_eq!(invalid.format(sent), "2026-10-14T09:30:00+02:00");
+    }
+
+    #[test]
+    fn msg_dates_take_the_offset_of_the_transport_headers() {
+        let mut message = Message::new(MessageFormat::Msg);
+        message.date = Some("2026-10-14T07:30:00+00:00".to_string());
+        message.headers = vec![(
+            "Date".to_string(),
+            "Wed, 14 Oct 2026 09:30:00 +0200".to_string(),
+        )];
+        let original = DateFormatter::default();
+        assert_eq!(original.message_date(&message), "2026-10-14T09:30:00+02:00");
+        let utc = DateFormatter::new(DateFormat::Iso8601, TimeZoneChoice::Utc, Lang::En);
+        assert_eq!(utc.message_date(&message), "2026-10-14T07:30:00+00:00");
+    }
+}
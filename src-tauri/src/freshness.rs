@@ -0,0 +1,173 @@
+//! Whether a file is still what was parsed. Loading a message records the file's size,
+//! modification time and a hash of its first and last bytes; when a later check finds the
+//! file changed, everything cached for its path is dropped in one place (`invalidate`),
+//! whether the frontend asked (`verify_freshness`) or the folder watcher noticed.
+
+use crate::{media, prefetch, previews, source};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Bytes hashed from each end of a file
+const QUICK_HASH_BYTES: u64 = 64 * 1024;
+
+/// Files whose stamps are kept; the record is emptied when it grows past this
+const MAX_STAMPS: usize = 50_000;
+
+/// What a file looked like when it was parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    modified: Option<SystemTime>,
+    /// SHA-256 of the size and the first and last `QUICK_HASH_BYTES`; catches a file
+    /// overwritten with one of the same size and modification time (copies that keep
+    /// timestamps, coarse file system clocks)
+    quick_hash: String,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Result<Self, String> {
+        let mut file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let meta = file
+            .metadata()
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let size = meta.len();
+        let mut sample = size.to_le_bytes().to_vec();
+        let read_error = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+        (&mut file)
+            .take(QUICK_HASH_BYTES)
+            .read_to_end(&mut sample)
+            .map_err(read_error)?;
+        if size > QUICK_HASH_BYTES {
+            file.seek(SeekFrom::Start(
+                size.saturating_sub(QUICK_HASH_BYTES).max(QUICK_HASH_BYTES),
+            ))
+            .and_then(|_| file.take(QUICK_HASH_BYTES).read_to_end(&mut sample))
+            .map_err(read_error)?;
+        }
+        Ok(Self {
+            size,
+            modified: meta.modified().ok(),
+            quick_hash: crate::parser::sha256_hex(&sample),
+        })
+    }
+}
+
+/// Result of `verify_freshness`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Freshness {
+    /// Unchanged since it was parsed; cached results can be trusted
+    Fresh,
+    /// Changed or gone; its cache entries have been dropped
+    Stale,
+    /// Not parsed in this session, so there is nothing to compare with
+    Unknown,
+}
+
+/// Stamps of parsed files by path (as checked against the access policy)
+#[derive(Default)]
+pub struct Stamps(Mutex<HashMap<PathBuf, Stamp>>);
+
+impl Stamps {
+    /// Remember what the file at `path` looks like now; called before it is parsed, so a
+    /// file changing during the parse reads as stale afterwards
+    pub fn record(&self, path: &Path) {
+        let Ok(stamp) = Stamp::of(path) else {
+            return;
+        };
+        let mut stamps = self.0.lock().unwrap();
+        if stamps.len() >= MAX_STAMPS {
+            stamps.clear();
+        }
+        stamps.insert(path.to_path_buf(), stamp);
+    }
+
+    fn check(&self, path: &Path) -> Freshness {
+        let Some(recorded) = self.0.lock().unwrap().get(path).cloned() else {
+            return Freshness::Unknown;
+        };
+        // Size and modification time settle most cases without reading the file
+        let unchanged = std::fs::metadata(path).is_ok_and(|meta| {
+            meta.len() == recorded.size && meta.modified().ok() == recorded.modified
+        });
+        match unchanged && Stamp::of(path).is_ok_and(|stamp| stamp == recorded) {
+            true => Freshness::Fresh,
+            false => Freshness::Stale,
+        }
+    }
+
+    fn forget(&self, path: &Path) {
+        self.0.lock().unwrap().remove(path);
+    }
+}
+
+/// Payload of the `cache-invalidated` event
+#[derive(Debug, Clone, Serialize)]
+struct CacheInvalidated {
+    path: String,
+}
+
+/// Check the file at `path` against its stamp, invalidating it when it changed
+pub fn verify(app: &AppHandle, path: &Path) -> Freshness {
+    let freshness = app.state::<Stamps>().check(path);
+    if freshness == Freshness::Stale {
+        invalidate(app, path);
+    }
+    freshness
+}
+
+/// Drop the listing metadata and snippet, source, extracted attachments and prefetched
+/// parse cached for `path`, and tell the frontend with a `cache-invalidated` event. Caches keyed by content
+/// hash (attachment digests, view states, notes) don't go stale and are left alone.
+pub fn invalidate(app: &AppHandle, path: &Path) {
+    app.state::<Stamps>().forget(path);
+    app.state::<previews::PreviewCache>().invalidate(path);
+    app.state::<source::SourceCache>().invalidate(path);
+    app.state::<media::AttachmentCache>().invalidate(path);
+    app.state::<prefetch::Prefetcher>().invalidate(path);
+    let payload = CacheInvalidated {
+        path: path.to_string_lossy().to_string(),
+    };
+    if let Err(e) = app.emit("cache-invalidated", &payload) {
+        log_line!("Failed to emit cache-invalidated event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_size_and_time_overwrites_are_caught_by_the_quick_hash() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("message.eml");
+        std::fs::write(&path, b"Subject: draft\r\n\r\nFirst version").unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        let stamps = Stamps::default();
+        assert_eq!(stamps.check(&path), Freshness::Unknown);
+        stamps.record(&path);
+        assert_eq!(stamps.check(&path), Freshness::Fresh);
+
+        std::fs::write(&path, b"Subject: final\r\n\r\nFirst version").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        assert_eq!(stamps.check(&path), Freshness::Stale);
+        assert_eq!(stamps.check(&path), Freshness::Stale);
+    }
+}
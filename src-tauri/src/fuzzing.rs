@@ -0,0 +1,121 @@
+//! Entry points for the fuzz targets in `fuzz/` (`cargo +nightly fuzz run msg` in
+//! `src-tauri`) and the replay of `tests/corpus/`. Each one feeds arbitrary bytes to one
+//! layer of the parser; errors are expected, panics and unbounded resource use are bugs.
+//! Inputs that crashed or hung a target belong minimized into `tests/corpus/<target>/`.
+
+use crate::parser::{self, limits, recovery, storage::CompoundStorage, MessageFormat};
+use std::io::Cursor;
+
+/// A fuzz target
+pub type Target = fn(&[u8]);
+
+/// All targets by name, as in `fuzz/fuzz_targets/` and `tests/corpus/`
+pub const TARGETS: &[(&str, Target)] = &[
+    ("cfb", cfb),
+    ("msg", msg),
+    ("properties", properties),
+    ("rtf", rtf),
+    ("mime", mime),
+    ("mbox", mbox),
+    ("tnef", tnef),
+];
+
+/// Compound file reader: strict open with every stream read, then lenient recovery
+pub fn cfb(data: &[u8]) {
+    if let Ok(mut storage) = CompoundStorage::open(Cursor::new(data)) {
+        read_all(&mut storage, "/", 0);
+    }
+    let budget = limits::Budget::new(&limits::Limits::default(), data.len());
+    let _ = recovery::rebuild(data, &budget);
+}
+
+fn read_all(storage: &mut CompoundStorage<Cursor<&[u8]>>, path: &str, depth: usize) {
+    if depth > 16 {
+        return;
+    }
+    for entry in storage.children(path) {
+        let child = format!("{}/{}", path.trim_end_matches('/'), entry.name);
+        if entry.is_storage {
+            read_all(storage, &child, depth + 1);
+        } else {
+            storage.read_stream(&child);
+        }
+    }
+}
+
+/// .msg files: MAPI property decoding, attachments (including OLE objects) and attached
+/// messages, on top of the compound file layer
+pub fn msg(data: &[u8]) {
+    let _ = parser::parse_bytes(MessageFormat::Msg, data, &parser::ParseOptions::default());
+}
+
+/// MAPI property decoding without a compound file around it: the fixed-size property
+/// table of a `__properties_version1.0` stream, the named property mapping (the input
+/// split into its GUID, entry and string streams) and the voting button stream
+pub fn properties(data: &[u8]) {
+    parser::msg::decode_property_stream(data);
+    let (guids, rest) = data.split_at(data.len() / 3);
+    let (entries, strings) = rest.split_at(rest.len() / 2);
+    parser::named::Mapping::parse(guids, entries, strings);
+    parser::named::voting_options(data);
+}
+
+/// Compressed RTF bodies: decompression, then the HTML encapsulated in them; the input
+/// is also taken as decompressed RTF
+pub fn rtf(data: &[u8]) {
+    let budget = limits::Budget::new(&limits::Limits::default(), data.len());
+    if let Ok(rtf) = parser::rtf::decompress(data, &budget) {
+        parser::rtf::encapsulated_html(&rtf);
+    }
+    parser::rtf::encapsulated_html(data);
+}
+
+/// .eml files: headers, MIME structure, transfer encodings and charsets
+pub fn mime(data: &[u8]) {
+    let _ = parser::parse_bytes(MessageFormat::Eml, data, &parser::ParseOptions::default());
+}
+
+/// mbox archives as written by the mbox export
+pub fn mbox(data: &[u8]) {
+    for message in parser::mbox::Reader::new(data).take(64).flatten() {
+        mime(&message.data);
+    }
+}
+
+/// TNEF (`winmail.dat`) streams: attributes, their MAPI properties and the RTF body
+pub fn tnef(data: &[u8]) {
+    let budget = limits::Budget::new(&limits::Limits::default(), data.len());
+    let _ = parser::tnef::decode(data, &budget);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    /// Replays `tests/corpus/<target>/*` (seeds and minimized inputs that once crashed or hung
+    /// a fuzz target) so regressions show up in `cargo test`
+    #[test]
+    fn corpus_replays_cleanly() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+        let mut replayed = 0;
+        for &(name, target) in super::TARGETS {
+            let Ok(entries) = std::fs::read_dir(corpus.join(name)) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry.unwrap().path();
+                let data = std::fs::read(&path).unwrap();
+                let started = Instant::now();
+                target(&data);
+                assert!(
+                    started.elapsed() < Duration::from_secs(10),
+                    "{} is too slow",
+                    path.display()
+                );
+                replayed += 1;
+            }
+        }
+        assert!(replayed > 0, "no corpus found in {}", corpus.display());
+    }
+}
@@ -0,0 +1,226 @@
+//! A message as one .html file for people without a mail client: header block, sanitized
+//! body with its inline images, the plain-text alternative and the attachments, all
+//! embedded so the file works offline and loads nothing from the network.
+
+use crate::export::{self, escape_html, format_size, HeaderFormat};
+use crate::formatting::DateFormatter;
+use crate::i18n::{self, Lang};
+use crate::parser::{sanitize, Attachment, Message};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+pub struct ArchiveOptions {
+    /// Attachments larger than this are listed without their content
+    pub embed_limit: usize,
+    pub lang: Lang,
+    pub dates: DateFormatter,
+    /// App version for the footer
+    pub version: String,
+}
+
+/// Render `message`, read from the file `source_name`, as a self-contained HTML document
+pub fn render(message: &Message, source_name: &str, options: &ArchiveOptions) -> String {
+    let lang = options.lang;
+    let text = |code: &str, args: &[(&str, String)]| escape_html(&i18n::text(lang, code, args));
+    let subject = message.subject.clone().unwrap_or_default();
+
+    let mut out = String::new();
+    // Only data: URLs, inline styles and no scripts, whatever the body says
+    out.push_str(&format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"Content-Security-Policy\" content=\"default-src 'none'; \
+         img-src data:; style-src 'unsafe-inline'\">\n\
+         <meta name=\"referrer\" content=\"no-referrer\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }}\n\
+         table.header-block th {{ text-align: left; padding-right: 1em; color: #666; }}\n\
+         .badge {{ border: 1px solid #999; border-radius: 3px; padding: 0 0.3em; }}\n\
+         .message-body {{ border-top: 1px solid #ccc; margin-top: 1em; padding-top: 1em; }}\n\
+         pre {{ white-space: pre-wrap; }}\n\
+         .not-embedded {{ color: #666; }}\n\
+         footer {{ border-top: 1px solid #ccc; margin-top: 2em; color: #666; font-size: 0.8em; }}\n\
+         </style>\n</head>\n<body>\n",
+        lang.tag(),
+        escape_html(&subject)
+    ));
+    out.push_str(&export::header_block(
+        message,
+        HeaderFormat::Html,
+        lang,
+        &options.dates,
+    ));
+
+    out.push_str("<div class=\"message-body\">\n");
+    match &message.body_html {
+        Some(html) => out.push_str(&sanitize::sanitize(html, |cid| inline_image(message, cid))),
+        None => out.push_str(&format!(
+            "<pre>{}</pre>",
+            escape_html(&export::body_text(message, false))
+        )),
+    }
+    out.push_str("\n</div>\n");
+    if message.body_html.is_some() {
+        if let Some(body) = message
+            .body_text
+            .as_deref()
+            .filter(|b| !b.trim().is_empty())
+        {
+            out.push_str(&format!(
+                "<details>\n<summary>{}</summary>\n<pre>{}</pre>\n</details>\n",
+                text("archive.plain_text", &[]),
+                escape_html(&body.replace("\r\n", "\n"))
+            ));
+        }
+    }
+
+    let attachments: Vec<&Attachment> = message
+        .attachments
+        .iter()
+        .filter(|attachment| !attachment.hidden && !attachment.referenced_in_body)
+        .collect();
+    if !attachments.is_empty() {
+        out.push_str(&format!(
+            "<h2>{}</h2>\n<ul class=\"attachments\">\n",
+            text("export.attachments", &[])
+        ));
+        for attachment in attachments {
+            let name = escape_html(&attachment.file_name);
+            let size = format_size(attachment.size, lang);
+            if attachment.data.len() <= options.embed_limit {
+                out.push_str(&format!(
+                    "<li><a download=\"{}\" href=\"{}\">{}</a> ({})</li>\n",
+                    name,
+                    data_url(attachment),
+                    name,
+                    size
+                ));
+            } else {
+                out.push_str(&format!(
+                    "<li>{} ({}) <span class=\"not-embedded\">{}</span></li>\n",
+                    name,
+                    size,
+                    text("archive.not_embedded", &[])
+                ));
+            }
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out.push_str(&format!(
+        "<footer>{}</footer>\n</body>\n</html>\n",
+        text(
+            "archive.footer",
+            &[
+                ("version", options.version.clone()),
+                ("file", source_name.to_string()),
+                ("date", options.dates.now()),
+            ]
+        )
+    ));
+    out
+}
+
+/// The `data:` URL of the embedded image with content id `cid`
+pub(crate) fn inline_image(message: &Message, cid: &str) -> Option<String> {
+    let cid = cid.trim_matches(['<', '>']);
+    message
+        .attachments
+        .iter()
+        .find(|attachment| {
+            attachment
+                .content_id
+                .as_deref()
+                .is_some_and(|id| id.trim_matches(['<', '>']).eq_ignore_ascii_case(cid))
+        })
+        .filter(|attachment| mime(attachment).starts_with("image/"))
+        .map(data_url)
+}
+
+fn mime(attachment: &Attachment) -> &str {
+    attachment
+        .detected_mime
+        .as_deref()
+        .or(attachment.declared_mime.as_deref())
+        .unwrap_or("application/octet-stream")
+}
+
+fn data_url(attachment: &Attachment) -> String {
+    format!(
+        "data:{};base64,{}",
+        escape_html(mime(attachment)),
+        STANDARD.encode(&attachment.data)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::{DateFormat, TimeZoneChoice};
+    use crate::parser::{eml, ParseOptions};
+
+    fn options(embed_limit: usize) -> ArchiveOptions {
+        ArchiveOptions {
+            embed_limit,
+            lang: Lang::En,
+            dates: DateFormatter::new(DateFormat::default(), TimeZoneChoice::Utc, Lang::En),
+            version: "1.2.3".to_string(),
+        }
+    }
+
+    #[test]
+    fn archives_embed_everything_and_keep_the_encoding() {
+        let data = include_bytes!("../tests/fixtures/charset/undeclared-shift-jis.eml");
+        let message = eml::parse(data, &ParseOptions::default());
+        let body = message.body_text.clone().unwrap();
+        let archive = render(&message, "会議.eml", &options(1024));
+
+        // Written as UTF-8 and declared so
+        assert!(archive.contains("<meta charset=\"utf-8\">"));
+        assert!(archive.contains(&escape_html(body.replace("\r\n", "\n").trim())));
+        let subject = message.subject.clone().unwrap();
+        assert!(archive.contains(&format!("<title>{}</title>", subject)));
+        assert!(archive.contains("1.2.3") && archive.contains("会議.eml"));
+
+        let mut message = Message::new(crate::parser::MessageFormat::Eml);
+        message.subject = Some("Quarterly figures".to_string());
+        message.body_text = Some("Figures attached.".to_string());
+        message.body_html = Some(
+            r#"<p>Figures <img src="cid:chart@x"> <img src="https://t.test/p.gif"><script>x()</script></p>"#
+                .to_string(),
+        );
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chart = Attachment::new(
+            0,
+            Some("chart.png".to_string()),
+            Some("image/png".to_string()),
+            Some("<chart@x>".to_string()),
+            png.clone(),
+        );
+        chart.referenced_in_body = true;
+        let small = Attachment::new(
+            1,
+            Some("notes.txt".to_string()),
+            None,
+            None,
+            b"hello".to_vec(),
+        );
+        let large = Attachment::new(2, Some("video.mp4".to_string()), None, None, vec![0; 2048]);
+        message.attachments = vec![chart, small, large];
+
+        let archive = render(&message, "figures.msg", &options(1024));
+        assert!(archive.contains(&format!(
+            "<img src=\"data:image/png;base64,{}\">",
+            STANDARD.encode(&png)
+        )));
+        assert!(archive.contains("download=\"notes.txt\" href=\"data:"));
+        assert!(archive.contains(&STANDARD.encode(b"hello")));
+        assert!(archive.contains("video.mp4 (2.0 KB) <span class=\"not-embedded\">"));
+        // Inline images are in the body, not among the downloads
+        assert!(!archive.contains("download=\"chart.png\""));
+        assert!(
+            archive.contains("<details>\n<summary>Plain text</summary>\n<pre>Figures attached.")
+        );
+        // Nothing that runs or loads
+        for gone in ["<script", "https://t.test", "http://", "src=\"cid:"] {
+            assert!(!archive.contains(gone), "{}", gone);
+        }
+    }
+}
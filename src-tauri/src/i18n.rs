@@ -0,0 +1,975 @@
+//! Message catalogs for text the backend produces: errors, parse warnings, export labels,
+//! notifications and dialogs. Errors and warnings reach the frontend as [`Localized`]
+//! payloads (`{ code, args, message }`), so it can translate them itself or show `message`,
+//! which is rendered in the language chosen with `set_locale`.
+
+use crate::parser::limits::{self, Limit};
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Languages with a catalog; English is the fallback for everything else
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+impl Lang {
+    const ALL: [Lang; 2] = [Lang::En, Lang::De];
+
+    /// Language of a locale tag such as `de`, `de-AT` or `de_DE.UTF-8`
+    pub fn from_tag(tag: &str) -> Option<Lang> {
+        let language = tag.split(['-', '_', '.']).next()?.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|lang| lang.tag() == language)
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+        }
+    }
+
+    pub fn decimal_separator(self) -> char {
+        match self {
+            Lang::En => '.',
+            Lang::De => ',',
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::De => DE,
+        }
+    }
+}
+
+impl Serialize for Lang {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.tag())
+    }
+}
+
+/// Unknown tags become English, so a stale settings file still loads
+impl<'de> Deserialize<'de> for Lang {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tag = String::deserialize(deserializer)?;
+        Ok(Lang::from_tag(&tag).unwrap_or_default())
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(Lang::En as u8);
+
+/// Language of generated text, as last set with `set_locale`
+pub fn current() -> Lang {
+    Lang::ALL[CURRENT.load(Ordering::Relaxed) as usize]
+}
+
+pub fn set_current(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+/// Text of `code` in `lang` with its `{name}` placeholders filled in. Keys missing from the
+/// catalog of `lang` fall back to English, unknown keys to the code itself.
+pub fn text(lang: Lang, code: &str, args: &[(&str, String)]) -> String {
+    let lookup = |lang: Lang| {
+        lang.catalog()
+            .iter()
+            .find(|(key, _)| *key == code)
+            .map(|(_, template)| *template)
+    };
+    let Some(template) = lookup(lang).or_else(|| lookup(Lang::En)) else {
+        return code.to_string();
+    };
+    let mut out = template.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{}}}", name), value);
+    }
+    out
+}
+
+/// Shorthand for [`text`] in the current language
+pub fn t(code: &str, args: &[(&str, String)]) -> String {
+    text(current(), code, args)
+}
+
+/// An error or warning as a catalog key with its arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Localized {
+    pub code: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl Localized {
+    pub fn new<const N: usize>(code: &'static str, args: [(&'static str, String); N]) -> Self {
+        Self {
+            code,
+            args: args.into(),
+        }
+    }
+
+    pub fn text(&self, lang: Lang) -> String {
+        text(lang, self.code, &self.args)
+    }
+}
+
+/// English, for logs and diagnostics bundles
+impl std::fmt::Display for Localized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text(Lang::En))
+    }
+}
+
+impl Serialize for Localized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a [(&'static str, String)]);
+        impl Serialize for Args<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(self.0.len()))?;
+                for (name, value) in self.0 {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+
+        let mut payload = serializer.serialize_struct("Localized", 3)?;
+        payload.serialize_field("code", self.code)?;
+        payload.serialize_field("args", &Args(&self.args))?;
+        payload.serialize_field("message", &self.text(current()))?;
+        payload.end()
+    }
+}
+
+/// Errors of commands: the prefixed errors the modules return (`PermissionDenied: <path>`,
+/// `PermissionDenied[<group>]: <command>`, `FileLocked: <path>`, `AccessDenied: <path>`,
+/// `VolumeUnavailable: <path>\n<volume>`, `DiskFull: <needed> <available>`, `Cancelled`,
+/// `ResourceLimit: <limit>: ...`) get their own codes, anything else is passed on as the
+/// `detail` of `error.other`
+impl From<String> for Localized {
+    fn from(error: String) -> Self {
+        if error == crate::jobs::CANCELLED {
+            return Localized::new("error.cancelled", []);
+        }
+        if let Some(path) = error
+            .strip_prefix(crate::access::PERMISSION_DENIED)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new("error.permission_denied", [("path", path.to_string())]);
+        }
+        if let Some((group, command)) = error
+            .strip_prefix(crate::access::PERMISSION_DENIED)
+            .and_then(|rest| rest.strip_prefix('['))
+            .and_then(|rest| rest.split_once("]: "))
+        {
+            return Localized::new(
+                "error.command_group_disabled",
+                [
+                    ("group", group.to_string()),
+                    ("command", command.to_string()),
+                ],
+            );
+        }
+        if let Some(command) = error
+            .strip_prefix(crate::quarantine::QUARANTINE_BLOCKED)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new(
+                "error.quarantine_blocked",
+                [("command", command.to_string())],
+            );
+        }
+        if let Some(path) = error
+            .strip_prefix(crate::archive::PASSWORD_REQUIRED)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new(
+                "error.archive_password_required",
+                [("path", path.to_string())],
+            );
+        }
+        if let Some(left) = error
+            .strip_prefix(crate::archive::WRONG_PASSWORD)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new(
+                "error.wrong_archive_password",
+                [("attempts_left", left.to_string())],
+            );
+        }
+        if let Some(path) = error
+            .strip_prefix(crate::documents::ALREADY_OPEN)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new("error.already_open", [("path", path.to_string())]);
+        }
+        if let Some(rest) = error
+            .strip_prefix(crate::reader::FILE_LOCKED)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return match rest.split_once('\n') {
+                Some((path, process)) => Localized::new(
+                    "error.file_locked_by",
+                    [("path", path.to_string()), ("process", process.to_string())],
+                ),
+                None => Localized::new("error.file_locked", [("path", rest.to_string())]),
+            };
+        }
+        if let Some(path) = error
+            .strip_prefix(crate::reader::ACCESS_DENIED)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new("error.access_denied", [("path", path.to_string())]);
+        }
+        if let Some((path, volume)) = error
+            .strip_prefix(crate::volumes::VOLUME_UNAVAILABLE)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .and_then(|rest| rest.split_once('\n'))
+        {
+            return Localized::new(
+                "error.volume_unavailable",
+                [("path", path.to_string()), ("volume", volume.to_string())],
+            );
+        }
+        if let Some((needed, available)) = error
+            .strip_prefix(crate::tempdir::DISK_FULL)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .and_then(|rest| rest.split_once(' '))
+        {
+            let size =
+                |bytes: &str| crate::export::format_size(bytes.parse().unwrap_or(0), current());
+            return Localized::new(
+                "error.disk_full",
+                [
+                    ("needed", needed.to_string()),
+                    ("available", available.to_string()),
+                    ("needed_size", size(needed)),
+                    ("available_size", size(available)),
+                ],
+            );
+        }
+        if let Some(reasons) = error
+            .strip_prefix(crate::tempdir::TEMP_UNAVAILABLE)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Localized::new("error.temp_unavailable", [("detail", reasons.to_string())]);
+        }
+        if error == crate::archive::ATTEMPTS_EXHAUSTED {
+            return Localized::new("error.archive_attempts_exhausted", []);
+        }
+        if let Some(limit) = limits::tripped(&error) {
+            let code = match limit {
+                Limit::StreamSize => "error.limit.stream_size",
+                Limit::TotalAllocation => "error.limit.total_allocation",
+                Limit::Depth => "error.limit.depth",
+                Limit::Time => "error.limit.time",
+            };
+            return Localized::new(code, [("detail", error)]);
+        }
+        Localized::new("error.other", [("detail", error)])
+    }
+}
+
+const EN: &[(&str, &str)] = &[
+    ("error.other", "{detail}"),
+    ("error.cancelled", "Cancelled"),
+    (
+        "error.nothing_on_clipboard",
+        "Nothing openable on the clipboard: copy a .msg or .eml file, its path, or message source",
+    ),
+    ("error.permission_denied", "Access to {path} is not allowed"),
+    (
+        "error.command_group_disabled",
+        "{command} is not available: the {group} commands are switched off",
+    ),
+    (
+        "error.quarantine_blocked",
+        "{command} is not available in quarantine mode",
+    ),
+    (
+        "error.disk_full",
+        "Not enough disk space for the file: {needed_size} needed, {available_size} available",
+    ),
+    (
+        "error.temp_unavailable",
+        "No folder for temporary files can be written: {detail}",
+    ),
+    (
+        "error.archive_password_required",
+        "{path} is encrypted: enter its password to open the message in it",
+    ),
+    (
+        "error.wrong_archive_password",
+        "Wrong password ({attempts_left} attempts left)",
+    ),
+    (
+        "error.archive_attempts_exhausted",
+        "Too many wrong passwords: close the archive and open it again to retry",
+    ),
+    (
+        "error.already_open",
+        "This message is already open as {path}",
+    ),
+    (
+        "error.file_locked",
+        "{path} is open in another program. Close it there and try again.",
+    ),
+    (
+        "error.file_locked_by",
+        "{path} is open in {process}. Close it there and try again.",
+    ),
+    (
+        "error.access_denied",
+        "You don't have permission to read {path}",
+    ),
+    (
+        "error.volume_unavailable",
+        "{volume} can't be reached, so {path} can't be read. Reconnect the drive or network share.",
+    ),
+    (
+        "error.limit.stream_size",
+        "The file declares a part larger than the whole file and was not opened",
+    ),
+    (
+        "error.limit.total_allocation",
+        "Opening the file would need too much memory",
+    ),
+    (
+        "error.limit.depth",
+        "The file is nested too deeply to be opened",
+    ),
+    (
+        "error.limit.time",
+        "Opening the file took too long and was stopped",
+    ),
+    (
+        "warning.nested_too_deep",
+        "Attached message {name} is nested too deeply to be previewed",
+    ),
+    (
+        "warning.content_protected",
+        "This message is protected with rights management and can only be read in Outlook",
+    ),
+    (
+        "warning.content_protected_label",
+        "This message is protected with rights management (label {label}) and can only be read in Outlook",
+    ),
+    (
+        "warning.malformed_link_wrapper",
+        "The original destination of the protected link {url} could not be decoded",
+    ),
+    (
+        "warning.attached_message_unreadable",
+        "Attached message {name} could not be parsed: {reason}",
+    ),
+    (
+        "warning.unknown_recipient_type",
+        "Unknown recipient type {type} for {name}",
+    ),
+    (
+        "warning.streams_unreadable",
+        "Some streams of the file could not be read",
+    ),
+    (
+        "warning.embedded_message_unreadable",
+        "Embedded message {name} could not be extracted: {reason}",
+    ),
+    (
+        "warning.ole_unreadable",
+        "OLE attachment {name} could not be extracted: {reason}",
+    ),
+    (
+        "warning.ole_unknown_layout",
+        "Unknown OLE object layout in {name}; exposing the raw storage",
+    ),
+    (
+        "warning.rtf_unreadable",
+        "The RTF body could not be decompressed: {reason}",
+    ),
+    (
+        "warning.tnef_unreadable",
+        "The files in {name} could not be extracted: {reason}",
+    ),
+    (
+        "warning.attachment_without_data",
+        "Attachment {name} has no data stream",
+    ),
+    (
+        "warning.unparseable_date",
+        "Unparseable Date header: {date}",
+    ),
+    (
+        "warning.malformed_multipart",
+        "Empty or malformed {mime} part",
+    ),
+    (
+        "warning.header_separator_missing",
+        "No blank line after the header; the body starts at the first line that isn't a header field",
+    ),
+    (
+        "warning.header_bare_cr",
+        "Header lines end in bare carriage returns",
+    ),
+    (
+        "warning.header_field_truncated",
+        "Header {name} is too long and was cut off",
+    ),
+    (
+        "warning.header_truncated",
+        "The header is too long; fields after the first 512 KB were ignored",
+    ),
+    (
+        "warning.duplicate_header",
+        "Header {name} appears more than once; the first one is used",
+    ),
+    (
+        "warning.journal_unwrap_failed",
+        "Journaled message could not be unwrapped: {reason}",
+    ),
+    (
+        "warning.smime_encrypted",
+        "The content is encrypted; the recipient's certificate is needed to show it",
+    ),
+    (
+        "warning.smime_unreadable",
+        "The S/MIME content could not be read: {reason}",
+    ),
+    (
+        "warning.charset_overridden",
+        "The {body} body is declared as {declared} but was decoded as {used}",
+    ),
+    (
+        "warning.charset_detected",
+        "The {body} body declares no charset; decoded as {detected}",
+    ),
+    (
+        "warning.body_repaired",
+        "The {body} body was UTF-8 misread as {charset} and saved again; the double encoding was undone",
+    ),
+    (
+        "warning.recovery.partial_sector",
+        "The file ends in the middle of a sector",
+    ),
+    (
+        "warning.recovery.difat_missing",
+        "Part of the sector allocation table is missing",
+    ),
+    (
+        "warning.recovery.fat_sector_missing",
+        "Allocation table sector {sector} is past the end of the file",
+    ),
+    (
+        "warning.recovery.missing_entry",
+        "Directory of {path} points to a missing entry; entries below it are lost",
+    ),
+    (
+        "warning.recovery.loop",
+        "Directory of {path} contains a loop",
+    ),
+    (
+        "warning.recovery.invalid_name",
+        "Skipped an entry with an invalid name in {path}",
+    ),
+    (
+        "warning.recovery.mini_stream_truncated",
+        "The mini stream holding small streams is truncated",
+    ),
+    (
+        "warning.recovery.stream_truncated",
+        "Stream {path} is truncated ({recovered} of {size} bytes recovered)",
+    ),
+    (
+        "warning.recovery.stream_skipped",
+        "Skipped {path}: {reason}",
+    ),
+    ("export.from", "From"),
+    ("export.to", "To"),
+    ("export.cc", "Cc"),
+    ("export.date", "Date"),
+    ("export.sent", "Sent"),
+    ("export.subject", "Subject"),
+    ("export.importance", "Importance"),
+    ("export.sensitivity", "Sensitivity"),
+    ("export.sensitivity_label", "Sensitivity label"),
+    ("archive.plain_text", "Plain text"),
+    ("archive.not_embedded", "not embedded, too large"),
+    (
+        "archive.footer",
+        "Generated by msgReader {version} from {file} on {date}",
+    ),
+    ("export.attachments", "Attachments"),
+    ("export.forwarded_message", "Forwarded message"),
+    ("calendar.accept", "Accepted: {title}"),
+    ("calendar.decline", "Declined: {title}"),
+    ("calendar.tentative", "Tentative: {title}"),
+    ("importance.high", "High"),
+    ("importance.low", "Low"),
+    ("sensitivity.personal", "Personal"),
+    ("sensitivity.private", "Private"),
+    ("sensitivity.confidential", "Confidential"),
+    ("size.bytes", "{size} bytes"),
+    ("notification.new_message", "New message: {subject}"),
+    (
+        "notification.new_message_from",
+        "New message from {sender}: {subject}",
+    ),
+    ("notification.new_messages", "{count} new messages"),
+    ("access.title", "Allow folder access"),
+    (
+        "access.message",
+        "msgReader wants to access files in\n{dir}\n\nAllow access to this folder?",
+    ),
+    ("access.allow", "Allow"),
+    ("access.deny", "Deny"),
+    ("permissions.title", "Allow commands"),
+    ("temp.choose_title", "Choose a folder for temporary files"),
+    (
+        "permissions.message",
+        "msgReader wants to switch on the {group} commands.\n\nAllow them?",
+    ),
+    ("quarantine.title", "Leave quarantine mode"),
+    (
+        "quarantine.disable_message",
+        "msgReader wants to switch off quarantine mode. Attachments can then be saved and opened in other applications, and remote content loaded.\n\nAllow this?",
+    ),
+    ("quarantine.window_title", "{title} [Quarantine]"),
+    ("clipboard.subject", "Pasted content"),
+    ("report.title", "Review report"),
+    ("report.folder", "Folder: {path}"),
+    ("report.generated", "Generated: {date}"),
+    ("report.total", "Annotated messages"),
+    ("report.untagged", "Untagged"),
+    ("report.no_subject", "(no subject)"),
+    ("report.file", "File"),
+    ("report.tags", "Tags"),
+    ("report.note", "Note"),
+    ("report.missing", "The file no longer exists"),
+    (
+        "report.changed",
+        "The file was changed since it was annotated",
+    ),
+    ("report.unreadable", "The file could not be read: {detail}"),
+    ("preview.image", "[image]"),
+    ("date.unknown", "unknown"),
+    ("date.locale_format", "%b %-d, %Y %H:%M (UTC%:z)"),
+    ("preview.invitation", "Invitation: {title}, {date}"),
+    ("preview.invitation_undated", "Invitation: {title}"),
+    (
+        "gateway.scl.skipped",
+        "Spam filtering was skipped (allowed sender or internal message)",
+    ),
+    ("gateway.scl.not_spam", "Not spam"),
+    ("gateway.scl.spam", "Spam"),
+    ("gateway.scl.high_confidence_spam", "High-confidence spam"),
+    ("gateway.bcl.not_bulk", "Not from a bulk sender"),
+    (
+        "gateway.bcl.few_complaints",
+        "Bulk sender drawing few complaints",
+    ),
+    (
+        "gateway.bcl.mixed_complaints",
+        "Bulk sender drawing mixed complaints",
+    ),
+    (
+        "gateway.bcl.many_complaints",
+        "Bulk sender drawing many complaints",
+    ),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("error.other", "{detail}"),
+    ("error.cancelled", "Abgebrochen"),
+    (
+        "error.nothing_on_clipboard",
+        "Nichts in der Zwischenablage kann geöffnet werden: Kopieren Sie eine .msg- oder .eml-Datei, ihren Pfad oder Nachrichtenquelltext",
+    ),
+    (
+        "error.permission_denied",
+        "Zugriff auf {path} ist nicht erlaubt",
+    ),
+    (
+        "error.command_group_disabled",
+        "{command} ist nicht verfügbar: Die Befehle der Gruppe {group} sind ausgeschaltet",
+    ),
+    (
+        "error.quarantine_blocked",
+        "{command} ist im Quarantänemodus nicht verfügbar",
+    ),
+    (
+        "error.disk_full",
+        "Nicht genug Speicherplatz für die Datei: {needed_size} benötigt, {available_size} verfügbar",
+    ),
+    (
+        "error.temp_unavailable",
+        "In keinen Ordner für temporäre Dateien kann geschrieben werden: {detail}",
+    ),
+    (
+        "error.archive_password_required",
+        "{path} ist verschlüsselt: Geben Sie das Passwort ein, um die Nachricht darin zu öffnen",
+    ),
+    (
+        "error.wrong_archive_password",
+        "Falsches Passwort (noch {attempts_left} Versuche)",
+    ),
+    (
+        "error.archive_attempts_exhausted",
+        "Zu viele falsche Passwörter: Schließen Sie das Archiv und öffnen Sie es erneut",
+    ),
+    ("error.already_open", "Diese Nachricht ist bereits als {path} geöffnet"),
+    (
+        "error.file_locked",
+        "{path} ist in einem anderen Programm geöffnet. Schließen Sie sie dort und versuchen Sie es erneut.",
+    ),
+    (
+        "error.file_locked_by",
+        "{path} ist in {process} geöffnet. Schließen Sie sie dort und versuchen Sie es erneut.",
+    ),
+    (
+        "error.access_denied",
+        "Sie haben keine Berechtigung, {path} zu lesen",
+    ),
+    (
+        "error.volume_unavailable",
+        "{volume} ist nicht erreichbar, daher kann {path} nicht gelesen werden. Verbinden Sie das Laufwerk oder die Netzwerkfreigabe erneut.",
+    ),
+    (
+        "error.limit.stream_size",
+        "Die Datei gibt einen Teil an, der größer ist als die ganze Datei, und wurde nicht \
+         geöffnet",
+    ),
+    (
+        "error.limit.total_allocation",
+        "Das Öffnen der Datei würde zu viel Speicher benötigen",
+    ),
+    (
+        "error.limit.depth",
+        "Die Datei ist zu tief verschachtelt, um geöffnet zu werden",
+    ),
+    (
+        "error.limit.time",
+        "Das Öffnen der Datei hat zu lange gedauert und wurde abgebrochen",
+    ),
+    (
+        "warning.nested_too_deep",
+        "Die angehängte Nachricht {name} ist für eine Vorschau zu tief verschachtelt",
+    ),
+    (
+        "warning.content_protected",
+        "Diese Nachricht ist durch Rechteverwaltung geschützt und kann nur in Outlook gelesen werden",
+    ),
+    (
+        "warning.content_protected_label",
+        "Diese Nachricht ist durch Rechteverwaltung geschützt (Bezeichnung {label}) und kann nur in Outlook gelesen werden",
+    ),
+    (
+        "warning.malformed_link_wrapper",
+        "Das eigentliche Ziel des geschützten Links {url} konnte nicht entschlüsselt werden",
+    ),
+    (
+        "warning.attached_message_unreadable",
+        "Die angehängte Nachricht {name} konnte nicht gelesen werden: {reason}",
+    ),
+    (
+        "warning.unknown_recipient_type",
+        "Unbekannter Empfängertyp {type} für {name}",
+    ),
+    (
+        "warning.streams_unreadable",
+        "Einige Datenströme der Datei konnten nicht gelesen werden",
+    ),
+    (
+        "warning.embedded_message_unreadable",
+        "Die eingebettete Nachricht {name} konnte nicht extrahiert werden: {reason}",
+    ),
+    (
+        "warning.ole_unreadable",
+        "Der OLE-Anhang {name} konnte nicht extrahiert werden: {reason}",
+    ),
+    (
+        "warning.ole_unknown_layout",
+        "Unbekannter Aufbau des OLE-Objekts {name}; der Rohspeicher wird bereitgestellt",
+    ),
+    (
+        "warning.rtf_unreadable",
+        "Der RTF-Text konnte nicht entpackt werden: {reason}",
+    ),
+    (
+        "warning.tnef_unreadable",
+        "Die Dateien in {name} konnten nicht extrahiert werden: {reason}",
+    ),
+    (
+        "warning.attachment_without_data",
+        "Der Anhang {name} hat keinen Datenstrom",
+    ),
+    (
+        "warning.unparseable_date",
+        "Nicht lesbarer Date-Header: {date}",
+    ),
+    (
+        "warning.malformed_multipart",
+        "Leerer oder fehlerhafter {mime}-Teil",
+    ),
+    (
+        "warning.header_separator_missing",
+        "Keine Leerzeile nach dem Header; der Text beginnt mit der ersten Zeile, die kein Header-Feld ist",
+    ),
+    (
+        "warning.header_bare_cr",
+        "Header-Zeilen enden mit einzelnen Wagenrückläufen",
+    ),
+    (
+        "warning.header_field_truncated",
+        "Der Header {name} ist zu lang und wurde abgeschnitten",
+    ),
+    (
+        "warning.header_truncated",
+        "Der Header ist zu lang; Felder nach den ersten 512 KB wurden ignoriert",
+    ),
+    (
+        "warning.duplicate_header",
+        "Der Header {name} kommt mehrfach vor; der erste wird verwendet",
+    ),
+    (
+        "warning.journal_unwrap_failed",
+        "Die journalisierte Nachricht konnte nicht entpackt werden: {reason}",
+    ),
+    (
+        "warning.smime_encrypted",
+        "Der Inhalt ist verschlüsselt; zum Anzeigen wird das Zertifikat des Empfängers benötigt",
+    ),
+    (
+        "warning.smime_unreadable",
+        "Der S/MIME-Inhalt konnte nicht gelesen werden: {reason}",
+    ),
+    (
+        "warning.charset_overridden",
+        "Der {body}-Text ist als {declared} angegeben, wurde aber als {used} dekodiert",
+    ),
+    (
+        "warning.charset_detected",
+        "Der {body}-Text gibt keinen Zeichensatz an; dekodiert als {detected}",
+    ),
+    (
+        "warning.body_repaired",
+        "Der {body}-Text war als {charset} fehlgelesenes und erneut gespeichertes UTF-8; die doppelte Kodierung wurde rückgängig gemacht",
+    ),
+    (
+        "warning.recovery.partial_sector",
+        "Die Datei endet mitten in einem Sektor",
+    ),
+    (
+        "warning.recovery.difat_missing",
+        "Ein Teil der Sektor-Zuordnungstabelle fehlt",
+    ),
+    (
+        "warning.recovery.fat_sector_missing",
+        "Der Sektor {sector} der Zuordnungstabelle liegt hinter dem Dateiende",
+    ),
+    (
+        "warning.recovery.missing_entry",
+        "Das Verzeichnis von {path} verweist auf einen fehlenden Eintrag; die Einträge darunter \
+         sind verloren",
+    ),
+    (
+        "warning.recovery.loop",
+        "Das Verzeichnis von {path} enthält eine Schleife",
+    ),
+    (
+        "warning.recovery.invalid_name",
+        "Ein Eintrag mit ungültigem Namen in {path} wurde übersprungen",
+    ),
+    (
+        "warning.recovery.mini_stream_truncated",
+        "Der Mini-Stream mit den kleinen Datenströmen ist abgeschnitten",
+    ),
+    (
+        "warning.recovery.stream_truncated",
+        "Der Datenstrom {path} ist abgeschnitten ({recovered} von {size} Bytes wiederhergestellt)",
+    ),
+    (
+        "warning.recovery.stream_skipped",
+        "{path} wurde übersprungen: {reason}",
+    ),
+    ("export.from", "Von"),
+    ("export.to", "An"),
+    ("export.cc", "Cc"),
+    ("export.date", "Gesendet"),
+    ("export.sent", "Gesendet"),
+    ("export.subject", "Betreff"),
+    ("export.importance", "Wichtigkeit"),
+    ("export.sensitivity", "Vertraulichkeit"),
+    ("export.sensitivity_label", "Vertraulichkeitsbezeichnung"),
+    ("archive.plain_text", "Nur-Text"),
+    ("archive.not_embedded", "nicht eingebettet, zu groß"),
+    (
+        "archive.footer",
+        "Erstellt von msgReader {version} aus {file} am {date}",
+    ),
+    ("export.attachments", "Anlagen"),
+    ("export.forwarded_message", "Weitergeleitete Nachricht"),
+    ("calendar.accept", "Zugesagt: {title}"),
+    ("calendar.decline", "Abgelehnt: {title}"),
+    ("calendar.tentative", "Mit Vorbehalt: {title}"),
+    ("importance.high", "Hoch"),
+    ("importance.low", "Niedrig"),
+    ("sensitivity.personal", "Persönlich"),
+    ("sensitivity.private", "Privat"),
+    ("sensitivity.confidential", "Vertraulich"),
+    ("size.bytes", "{size} Bytes"),
+    ("notification.new_message", "Neue Nachricht: {subject}"),
+    (
+        "notification.new_message_from",
+        "Neue Nachricht von {sender}: {subject}",
+    ),
+    ("notification.new_messages", "{count} neue Nachrichten"),
+    ("access.title", "Ordnerzugriff erlauben"),
+    (
+        "access.message",
+        "msgReader möchte auf Dateien in\n{dir}\nzugreifen.\n\nZugriff auf diesen Ordner erlauben?",
+    ),
+    ("access.allow", "Erlauben"),
+    ("access.deny", "Ablehnen"),
+    ("permissions.title", "Befehle erlauben"),
+    ("temp.choose_title", "Ordner für temporäre Dateien wählen"),
+    (
+        "permissions.message",
+        "msgReader möchte die Befehle der Gruppe {group} einschalten.\n\nErlauben?",
+    ),
+    ("quarantine.title", "Quarantänemodus verlassen"),
+    (
+        "quarantine.disable_message",
+        "msgReader möchte den Quarantänemodus ausschalten. Anhänge können dann gespeichert und in anderen Anwendungen geöffnet und externe Inhalte geladen werden.\n\nErlauben?",
+    ),
+    ("quarantine.window_title", "{title} [Quarantäne]"),
+    ("clipboard.subject", "Eingefügter Inhalt"),
+    ("report.title", "Prüfbericht"),
+    ("report.folder", "Ordner: {path}"),
+    ("report.generated", "Erstellt: {date}"),
+    ("report.total", "Annotierte Nachrichten"),
+    ("report.untagged", "Ohne Tag"),
+    ("report.no_subject", "(kein Betreff)"),
+    ("report.file", "Datei"),
+    ("report.tags", "Tags"),
+    ("report.note", "Notiz"),
+    ("report.missing", "Die Datei existiert nicht mehr"),
+    ("report.changed", "Die Datei wurde seit der Annotation geändert"),
+    (
+        "report.unreadable",
+        "Die Datei konnte nicht gelesen werden: {detail}",
+    ),
+    ("preview.image", "[Bild]"),
+    ("date.unknown", "unbekannt"),
+    ("date.locale_format", "%d.%m.%Y %H:%M (UTC%:z)"),
+    ("preview.invitation", "Einladung: {title}, {date}"),
+    ("preview.invitation_undated", "Einladung: {title}"),
+    (
+        "gateway.scl.skipped",
+        "Spamfilter übersprungen (zugelassener Absender oder interne Nachricht)",
+    ),
+    ("gateway.scl.not_spam", "Kein Spam"),
+    ("gateway.scl.spam", "Spam"),
+    ("gateway.scl.high_confidence_spam", "Mit hoher Sicherheit Spam"),
+    ("gateway.bcl.not_bulk", "Kein Massenversender"),
+    (
+        "gateway.bcl.few_complaints",
+        "Massenversender mit wenigen Beschwerden",
+    ),
+    (
+        "gateway.bcl.mixed_complaints",
+        "Massenversender mit gemischten Beschwerden",
+    ),
+    (
+        "gateway.bcl.many_complaints",
+        "Massenversender mit vielen Beschwerden",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(template: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = template
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Every translation has an English original with the same placeholders
+    #[test]
+    fn catalogs_match_english() {
+        for &lang in &Lang::ALL {
+            for (key, template) in lang.catalog() {
+                let english = EN.iter().find(|(k, _)| k == key);
+                let (_, english) = english.unwrap_or_else(|| panic!("{} only in {:?}", key, lang));
+                assert_eq!(placeholders(template), placeholders(english), "{}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn texts_fall_back_to_english() {
+        let args = [("count", "3".to_string())];
+        assert_eq!(
+            text(Lang::De, "notification.new_messages", &args),
+            "3 neue Nachrichten"
+        );
+        assert_eq!(
+            text(Lang::En, "notification.new_messages", &args),
+            "3 new messages"
+        );
+        assert_eq!(text(Lang::De, "no.such.key", &[]), "no.such.key");
+
+        assert_eq!(Lang::from_tag("de-AT"), Some(Lang::De));
+        assert_eq!(Lang::from_tag("de_DE.UTF-8"), Some(Lang::De));
+        assert_eq!(Lang::from_tag("fr"), None);
+        let lang: Lang = serde_json::from_str("\"fr-CA\"").unwrap();
+        assert_eq!(lang, Lang::En);
+    }
+
+    #[test]
+    fn errors_become_codes() {
+        let denied = Localized::from(format!("{}: /tmp/x.msg", crate::access::PERMISSION_DENIED));
+        assert_eq!(denied.code, "error.permission_denied");
+        assert_eq!(
+            denied.text(Lang::De),
+            "Zugriff auf /tmp/x.msg ist nicht erlaubt"
+        );
+        let locked = Localized::from(format!(
+            "{}: C:\\Mail\\x.msg\nMicrosoft Outlook",
+            crate::reader::FILE_LOCKED
+        ));
+        assert_eq!(
+            locked.text(Lang::En),
+            "C:\\Mail\\x.msg is open in Microsoft Outlook. Close it there and try again."
+        );
+        assert_eq!(
+            Localized::from(crate::jobs::CANCELLED.to_string()).code,
+            "error.cancelled"
+        );
+        let full = Localized::from(format!("{}: 2048 1024", crate::tempdir::DISK_FULL));
+        assert_eq!(full.code, "error.disk_full");
+        assert_eq!(
+            full.text(Lang::En),
+            "Not enough disk space for the file: 2.0 KB needed, 1.0 KB available"
+        );
+        let limit = Localized::from(Limit::Time.exceeded("parsing took too long"));
+        assert_eq!(limit.code, "error.limit.time");
+
+        let other = Localized::from("Failed to write out.txt: disk full".to_string());
+        assert_eq!(other.code, "error.other");
+        let payload = serde_json::to_value(&other).unwrap();
+        assert_eq!(
+            payload["args"]["detail"],
+            "Failed to write out.txt: disk full"
+        );
+        assert_eq!(payload["message"], "Failed to write out.txt: disk full");
+    }
+}
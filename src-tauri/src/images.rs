@@ -0,0 +1,300 @@
+use serde::Serialize;
+
+/// Default size cap of a previewed image, after downscaling
+pub const DEFAULT_MAX_BYTES: usize = 20 * 1024 * 1024;
+
+/// Longest side of a downscaled image
+const MAX_DIMENSION: u32 = 4096;
+
+/// Image types the webview displays itself; anything else is opened externally
+const DISPLAYABLE: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+];
+
+/// An image attachment prepared for the in-app viewer
+#[derive(Debug, Clone, Serialize)]
+pub struct ImagePreview {
+    pub data_uri: String,
+    pub mime: String,
+    /// Size as displayed, i.e. after the EXIF orientation is applied; unknown for some types
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF orientation (1-8) of JPEG images. The webview applies it (`image-orientation:
+    /// from-image`); `width` and `height` already account for it.
+    pub orientation: u16,
+    /// The image was scaled down to fit the size limits
+    pub downscaled: bool,
+}
+
+/// Prepare image data for the in-app viewer as a `data:` URI. Large PNGs are scaled down;
+/// animated GIFs and PNGs and all other types are passed through unmodified. Images that
+/// still exceed `max_bytes`, and types the webview can't show, fail with an error asking to
+/// open them externally instead of being cut off.
+pub fn preview(mime: &str, data: &[u8], max_bytes: usize) -> Result<ImagePreview, String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    if matches!(mime, "image/heic" | "image/heif") {
+        return Err("HEIC images can't be shown in the app, open the attachment externally".into());
+    }
+    if !DISPLAYABLE.contains(&mime) {
+        return Err(format!(
+            "{} can't be shown in the app, open the attachment externally",
+            mime
+        ));
+    }
+
+    let mut preview = ImagePreview {
+        data_uri: String::new(),
+        mime: mime.to_string(),
+        width: None,
+        height: None,
+        orientation: 1,
+        downscaled: false,
+    };
+    let mut data = std::borrow::Cow::Borrowed(data);
+    match mime {
+        "image/jpeg" => {
+            preview.orientation = jpeg_orientation(&data).unwrap_or(1);
+            if let Some((width, height)) = jpeg_size(&data) {
+                // Orientations 5 to 8 turn the image by 90 degrees
+                let turned = preview.orientation >= 5;
+                preview.width = Some(if turned { height } else { width });
+                preview.height = Some(if turned { width } else { height });
+            }
+        }
+        "image/gif" if data.len() >= 10 && data.starts_with(b"GIF8") => {
+            preview.width = Some(u16::from_le_bytes([data[6], data[7]]).into());
+            preview.height = Some(u16::from_le_bytes([data[8], data[9]]).into());
+        }
+        "image/png" => {
+            let (width, height, animated) = png_info(&data)?;
+            let too_large = data.len() > max_bytes || width.max(height) > MAX_DIMENSION;
+            if too_large && !animated {
+                let (scaled, width, height) = downscale_png(&data, MAX_DIMENSION)?;
+                data = std::borrow::Cow::Owned(scaled);
+                preview.downscaled = true;
+                preview.width = Some(width);
+                preview.height = Some(height);
+            } else {
+                preview.width = Some(width);
+                preview.height = Some(height);
+            }
+        }
+        _ => {}
+    }
+
+    if data.len() > max_bytes {
+        return Err(format!(
+            "The image is too large to show in the app ({} MB), open it externally",
+            data.len().div_ceil(1024 * 1024)
+        ));
+    }
+    preview.data_uri = format!("data:{};base64,{}", mime, STANDARD.encode(&data));
+    Ok(preview)
+}
+
+/// Segments of a JPEG file up to the image data, as (marker, payload)
+fn jpeg_segments(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut pos = 2;
+    std::iter::from_fn(move || {
+        if !data.starts_with(&[0xFF, 0xD8]) || pos + 4 > data.len() || data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Start of scan: the entropy-coded data follows
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let payload = data.get(pos + 4..pos + 2 + len)?;
+        pos += 2 + len;
+        Some((marker, payload))
+    })
+}
+
+/// Width and height from the JPEG frame header
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    jpeg_segments(data)
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        .find(|(marker, _)| matches!(marker, 0xC0..=0xCF) && ![0xC4, 0xC8, 0xCC].contains(marker))
+        .and_then(|(_, sof)| {
+            let height = u16::from_be_bytes([*sof.get(1)?, *sof.get(2)?]);
+            let width = u16::from_be_bytes([*sof.get(3)?, *sof.get(4)?]);
+            Some((width.into(), height.into()))
+        })
+}
+
+/// Orientation tag (0x0112) of the EXIF data in a JPEG's APP1 segment
+fn jpeg_orientation(data: &[u8]) -> Option<u16> {
+    let (_, app1) = jpeg_segments(data)
+        .find(|(marker, payload)| *marker == 0xE1 && payload.starts_with(b"Exif\0\0"))?;
+    let tiff = &app1[6..];
+    let little_endian = match tiff.get(..4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let bytes = [*tiff.get(pos)?, *tiff.get(pos + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let bytes = [
+            *tiff.get(pos)?,
+            *tiff.get(pos + 1)?,
+            *tiff.get(pos + 2)?,
+            *tiff.get(pos + 3)?,
+        ];
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(0x0112))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Width, height and whether the PNG is animated (APNG)
+fn png_info(data: &[u8]) -> Result<(u32, u32, bool), String> {
+    let reader = png::Decoder::new(std::io::Cursor::new(data))
+        .read_info()
+        .map_err(|e| format!("Invalid PNG image: {}", e))?;
+    let info = reader.info();
+    Ok((info.width, info.height, info.animation_control.is_some()))
+}
+
+/// Decode a PNG and scale it down (box filter) so both sides fit `max_side`, halving the
+/// size further until the pixels fit the default size cap. Returns the re-encoded image
+/// and its size.
+fn downscale_png(data: &[u8], max_side: u32) -> Result<(Vec<u8>, u32, u32), String> {
+    let decode_error = |e: png::DecodingError| format!("Invalid PNG image: {}", e);
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(decode_error)?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).map_err(decode_error)?;
+    let channels = frame.color_type.samples();
+    let (width, height) = (frame.width as usize, frame.height as usize);
+
+    // Integer factor keeps the box filter simple; it also reduces the byte size
+    let mut factor = (width.max(height) as u32).div_ceil(max_side).max(2) as usize;
+    let (new_width, new_height) = loop {
+        let size = (width.div_ceil(factor), height.div_ceil(factor));
+        if size.0 * size.1 * channels <= DEFAULT_MAX_BYTES || factor >= width.max(height) {
+            break size;
+        }
+        factor *= 2;
+    };
+
+    let mut scaled = vec![0u8; new_width * new_height * channels];
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let mut sums = [0u32; 4];
+            let mut count = 0;
+            for sy in y * factor..((y + 1) * factor).min(height) {
+                for sx in x * factor..((x + 1) * factor).min(width) {
+                    let pixel = (sy * width + sx) * channels;
+                    for (sum, value) in sums.iter_mut().zip(&pixels[pixel..pixel + channels]) {
+                        *sum += u32::from(*value);
+                    }
+                    count += 1;
+                }
+            }
+            let target = (y * new_width + x) * channels;
+            for (channel, sum) in sums.iter().take(channels).enumerate() {
+                scaled[target + channel] = (sum / count) as u8;
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, new_width as u32, new_height as u32);
+    encoder.set_color(frame.color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&scaled))
+        .map_err(|e| format!("Failed to encode the downscaled image: {}", e))?;
+    Ok((out, new_width as u32, new_height as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels: Vec<u8> = (0..width * height * 3).map(|i| (i % 251) as u8).collect();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&pixels)
+            .unwrap();
+        out
+    }
+
+    /// Minimal JPEG headers: EXIF orientation 6 (turned right) and a 640x480 frame
+    fn rotated_jpeg() -> Vec<u8> {
+        let mut tiff = b"MM\0*\0\0\0\x08".to_vec();
+        tiff.extend([0, 1]); // one entry
+        tiff.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        tiff.extend([0, 0, 0, 0]);
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend(tiff);
+
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        data.extend(((app1.len() + 2) as u16).to_be_bytes());
+        data.extend(app1);
+        data.extend([0xFF, 0xC0, 0, 11, 8, 0x01, 0xE0, 0x02, 0x80, 1, 1, 0x11, 0]);
+        data.extend([0xFF, 0xDA, 0, 2, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn jpeg_size_accounts_for_orientation() {
+        let preview = preview("image/jpeg", &rotated_jpeg(), DEFAULT_MAX_BYTES).unwrap();
+        assert_eq!(preview.orientation, 6);
+        assert_eq!((preview.width, preview.height), (Some(480), Some(640)));
+        assert!(preview.data_uri.starts_with("data:image/jpeg;base64,/9j/"));
+    }
+
+    #[test]
+    fn large_pngs_are_downscaled() {
+        let data = png(5000, 10);
+        let preview = preview("image/png", &data, DEFAULT_MAX_BYTES).unwrap();
+        assert!(preview.downscaled);
+        assert_eq!((preview.width, preview.height), (Some(2500), Some(5)));
+
+        let small = super::preview("image/png", &png(20, 10), DEFAULT_MAX_BYTES).unwrap();
+        assert!(!small.downscaled);
+    }
+
+    #[test]
+    fn oversized_and_unsupported_images_are_rejected() {
+        let gif = [b"GIF89a\x02\0\x03\0".as_slice(), &[0u8; 100]].concat();
+        let passed = preview("image/gif", &gif, DEFAULT_MAX_BYTES).unwrap();
+        assert_eq!((passed.width, passed.height), (Some(2), Some(3)));
+        let error = preview("image/gif", &gif, 50).unwrap_err();
+        assert!(error.contains("too large"));
+        assert!(preview("image/heic", b"....ftypheic", DEFAULT_MAX_BYTES).is_err());
+        assert!(preview("image/svg+xml", b"<svg/>", DEFAULT_MAX_BYTES).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+//! Periodic checks that an archive folder hasn't rotted (`verify_archive`). The first
+//! verification of a folder records a baseline for each message file: its SHA-256, size,
+//! modification time and whether it parsed. Later runs hash and parse the files again and
+//! report the ones that changed, went missing, are new or no longer parse. Files whose size
+//! and modification time match their baseline are skipped unless the run is `full`.
+//! A changed file keeps its baseline, and is reported again every run, until the changes
+//! are accepted (`accept_changes`); new files are added to the baseline as they're found.
+
+use crate::export::csv_field;
+use crate::parser::Message;
+use crate::{persist, save};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What was recorded of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub sha256: String,
+    pub size: u64,
+    /// Modification time in milliseconds since the epoch, when the file system has one
+    pub modified: Option<u128>,
+    pub parsed: bool,
+    /// RFC 3339
+    pub recorded_at: String,
+    /// When the file was last hashed, RFC 3339
+    pub verified_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct VerifyOptions {
+    /// Hash every file, also the ones whose size and modification time are unchanged
+    pub full: bool,
+    /// Take the changed and missing files as they are now into the baseline
+    pub accept_changes: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The content differs from the baseline
+    Changed,
+    /// In the baseline, not in the folder
+    Missing,
+    /// Not in the baseline yet; recorded now
+    New,
+    /// Parsed when recorded, doesn't anymore
+    NewlyUnparseable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// File name within the folder
+    pub file: String,
+    pub expected_sha256: Option<String>,
+    pub actual_sha256: Option<String>,
+    /// Why the file doesn't parse, or couldn't be read
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyCounts {
+    /// Files in the folder
+    pub files: usize,
+    /// Hashed and parsed this run
+    pub checked: usize,
+    /// Left out as unchanged since their last verification
+    pub skipped: usize,
+    pub unchanged: usize,
+    pub changed: usize,
+    pub missing: usize,
+    pub new: usize,
+    pub newly_unparseable: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub dir: String,
+    /// RFC 3339
+    pub verified_at: String,
+    pub full: bool,
+    /// There were no baselines for the folder; this run recorded them
+    pub first_run: bool,
+    pub counts: VerifyCounts,
+    pub issues: Vec<Issue>,
+}
+
+/// Baselines by folder (canonical path) and file name, persisted to a JSON file
+pub struct Baselines {
+    path: Option<PathBuf>,
+    folders: Mutex<BTreeMap<String, BTreeMap<String, Baseline>>>,
+}
+
+impl Baselines {
+    pub fn load(path: PathBuf) -> Self {
+        let folders = persist::read_json(&path).unwrap_or_default();
+        Self {
+            path: Some(path),
+            folders: Mutex::new(folders),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            folders: Mutex::default(),
+        }
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Verify the message files of `dir` (already checked against the access policy).
+    /// `load` parses a file; `before_each(current, total)` runs before every file and an
+    /// error from it (a cancelled job) stops the run without recording anything.
+    pub fn verify(
+        &self,
+        dir: &Path,
+        options: VerifyOptions,
+        load: impl Fn(&Path) -> Result<Message, String>,
+        before_each: impl FnMut(usize, usize) -> Result<(), String>,
+    ) -> Result<VerifyReport, String> {
+        let key = dir
+            .canonicalize()
+            .unwrap_or_else(|_| dir.to_path_buf())
+            .to_string_lossy()
+            .to_string();
+        let mut baselines = self
+            .folders
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .unwrap_or_default();
+        let report = verify_folder(dir, &mut baselines, options, load, before_each)?;
+
+        let mut folders = self.folders.lock().unwrap();
+        folders.insert(key, baselines);
+        if let Some(path) = &self.path {
+            persist::write_json(path, &*folders)?;
+        }
+        Ok(report)
+    }
+}
+
+fn modified_ms(meta: &std::fs::Metadata) -> Option<u128> {
+    let modified = meta.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis())
+}
+
+fn verify_folder(
+    dir: &Path,
+    baselines: &mut BTreeMap<String, Baseline>,
+    options: VerifyOptions,
+    load: impl Fn(&Path) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<VerifyReport, String> {
+    let now = chrono::DateTime::<chrono::Local>::from(SystemTime::now()).to_rfc3339();
+    let first_run = baselines.is_empty();
+    let paths = crate::attachments::message_files(dir)?;
+    let mut counts = VerifyCounts {
+        files: paths.len(),
+        ..VerifyCounts::default()
+    };
+    let mut issues = Vec::new();
+    let mut seen = Vec::with_capacity(paths.len());
+
+    for (current, path) in paths.iter().enumerate() {
+        before_each(current, paths.len())?;
+        let file = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        seen.push(file.clone());
+        let meta = std::fs::metadata(path).ok();
+        let size = meta.as_ref().map_or(0, |meta| meta.len());
+        let modified = meta.as_ref().and_then(modified_ms);
+        let baseline = baselines.get_mut(&file);
+        if let Some(baseline) = &baseline {
+            let untouched =
+                baseline.size == size && modified.is_some() && baseline.modified == modified;
+            if untouched && !options.full {
+                counts.skipped += 1;
+                continue;
+            }
+        }
+
+        counts.checked += 1;
+        let sha256 = match save::file_sha256(path) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                // Listed a moment ago, unreadable now: as good as missing
+                counts.missing += 1;
+                issues.push(Issue {
+                    kind: IssueKind::Missing,
+                    file,
+                    expected_sha256: baseline.map(|baseline| baseline.sha256.clone()),
+                    actual_sha256: None,
+                    error: Some(format!("Failed to read {}: {}", path.display(), e)),
+                });
+                continue;
+            }
+        };
+        let parse_error = load(path).err();
+        let recorded = |recorded_at: &str| Baseline {
+            sha256: sha256.clone(),
+            size,
+            modified,
+            parsed: parse_error.is_none(),
+            recorded_at: recorded_at.to_string(),
+            verified_at: now.clone(),
+        };
+
+        match baseline {
+            None => {
+                counts.new += 1;
+                issues.push(Issue {
+                    kind: IssueKind::New,
+                    file: file.clone(),
+                    expected_sha256: None,
+                    actual_sha256: Some(sha256.clone()),
+                    error: parse_error.clone(),
+                });
+                baselines.insert(file, recorded(&now));
+            }
+            Some(baseline) if baseline.sha256 != sha256 => {
+                counts.changed += 1;
+                issues.push(Issue {
+                    kind: IssueKind::Changed,
+                    file,
+                    expected_sha256: Some(baseline.sha256.clone()),
+                    actual_sha256: Some(sha256.clone()),
+                    error: parse_error.clone(),
+                });
+                if options.accept_changes {
+                    *baseline = recorded(&now);
+                } else {
+                    baseline.verified_at = now.clone();
+                }
+            }
+            Some(baseline) => {
+                if baseline.parsed && parse_error.is_some() {
+                    counts.newly_unparseable += 1;
+                    issues.push(Issue {
+                        kind: IssueKind::NewlyUnparseable,
+                        file,
+                        expected_sha256: Some(baseline.sha256.clone()),
+                        actual_sha256: Some(sha256.clone()),
+                        error: parse_error.clone(),
+                    });
+                } else {
+                    counts.unchanged += 1;
+                }
+                // Same content, so a new modification time or parse result is no change
+                *baseline = recorded(&baseline.recorded_at.clone());
+            }
+        }
+    }
+
+    let missing: Vec<String> = baselines
+        .keys()
+        .filter(|file| !seen.contains(file))
+        .cloned()
+        .collect();
+    for file in missing {
+        counts.missing += 1;
+        let baseline = match options.accept_changes {
+            true => baselines.remove(&file),
+            false => baselines.get(&file).cloned(),
+        };
+        issues.push(Issue {
+            kind: IssueKind::Missing,
+            file,
+            expected_sha256: baseline.map(|baseline| baseline.sha256),
+            actual_sha256: None,
+            error: None,
+        });
+    }
+
+    Ok(VerifyReport {
+        dir: dir.to_string_lossy().to_string(),
+        verified_at: now,
+        full: options.full,
+        first_run,
+        counts,
+        issues,
+    })
+}
+
+/// The issues of a report as CSV, with a byte order mark like the other CSV exports
+pub fn report_csv(report: &VerifyReport) -> String {
+    let mut csv = String::from("\u{feff}kind,file,expected_sha256,actual_sha256,error\r\n");
+    for issue in &report.issues {
+        let kind = serde_json::to_value(issue.kind)
+            .ok()
+            .and_then(|kind| kind.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let fields = [
+            kind,
+            csv_field(&issue.file),
+            issue.expected_sha256.clone().unwrap_or_default(),
+            issue.actual_sha256.clone().unwrap_or_default(),
+            csv_field(issue.error.as_deref().unwrap_or_default()),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Write `report` to `target`, as CSV when its extension is `.csv` and JSON otherwise
+pub fn write_report(report: &VerifyReport, target: &Path) -> Result<(), String> {
+    let csv = target
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    match csv {
+        true => save::write_atomic(target, report_csv(report).as_bytes()),
+        false => persist::write_json(target, report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_file, ParseOptions};
+    use std::fs;
+
+    #[test]
+    fn changes_missing_new_and_unparseable_files_are_reported() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let eml = |subject: &str| {
+            format!(
+                "From: a@example.com\r\nSubject: {}\r\n\r\nBody\r\n",
+                subject
+            )
+        };
+        fs::write(dir.join("a.eml"), eml("A")).unwrap();
+        fs::write(dir.join("b.eml"), eml("B")).unwrap();
+        fs::write(dir.join("c.msg"), b"not yet broken").unwrap();
+        // Stand-in for a structural parse that only fails on the word "broken"
+        let load = |path: &Path| match fs::read(path).unwrap().windows(6).any(|w| w == b"broken") {
+            true => Err("Broken file".to_string()),
+            false => parse_file(path, &ParseOptions::default()),
+        };
+        let store = Baselines::in_memory();
+        let run = |options: VerifyOptions| store.verify(dir, options, load, |_, _| Ok(())).unwrap();
+        let kinds = |report: &VerifyReport| -> Vec<(IssueKind, String)> {
+            report
+                .issues
+                .iter()
+                .map(|issue| (issue.kind, issue.file.clone()))
+                .collect()
+        };
+
+        let first = run(VerifyOptions::default());
+        assert!(first.first_run);
+        assert_eq!(first.counts.new, 3);
+        assert_eq!(first.counts.files, 3);
+        assert_eq!(kinds(&first)[2], (IssueKind::New, "c.msg".to_string()));
+        assert!(first.issues[2].error.is_some());
+
+        // Nothing touched: everything is skipped, or hashed again in a full run
+        let quiet = run(VerifyOptions::default());
+        assert_eq!(quiet.counts.skipped, 3);
+        assert!(quiet.issues.is_empty());
+        let full = run(VerifyOptions {
+            full: true,
+            ..VerifyOptions::default()
+        });
+        assert_eq!((full.counts.checked, full.counts.unchanged), (3, 3));
+
+        fs::write(dir.join("a.eml"), eml("A, edited")).unwrap();
+        fs::remove_file(dir.join("b.eml")).unwrap();
+        fs::write(dir.join("d.eml"), eml("D")).unwrap();
+        let report = run(VerifyOptions::default());
+        assert_eq!(
+            kinds(&report),
+            [
+                (IssueKind::Changed, "a.eml".to_string()),
+                (IssueKind::New, "d.eml".to_string()),
+                (IssueKind::Missing, "b.eml".to_string()),
+            ]
+        );
+        // The baseline stays until the change is accepted
+        assert_eq!(
+            kinds(&run(VerifyOptions::default())),
+            [
+                (IssueKind::Changed, "a.eml".to_string()),
+                (IssueKind::Missing, "b.eml".to_string()),
+            ]
+        );
+        run(VerifyOptions {
+            accept_changes: true,
+            ..VerifyOptions::default()
+        });
+        assert!(run(VerifyOptions {
+            full: true,
+            ..VerifyOptions::default()
+        })
+        .issues
+        .is_empty());
+
+        // Same content that stopped parsing (a parser regression) is newly unparseable
+        let regressed = store
+            .verify(
+                dir,
+                VerifyOptions {
+                    full: true,
+                    ..VerifyOptions::default()
+                },
+                |path| match path.ends_with("d.eml") {
+                    true => Err("Unsupported".to_string()),
+                    false => load(path),
+                },
+                |_, _| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(
+            kinds(&regressed),
+            [(IssueKind::NewlyUnparseable, "d.eml".to_string())]
+        );
+
+        let csv = report_csv(&report);
+        assert!(csv.starts_with("\u{feff}kind,file,"));
+        assert!(csv.contains("\r\nmissing,b.eml,"));
+
+        // A cancelled run records nothing
+        fs::write(dir.join("e.eml"), eml("E")).unwrap();
+        assert!(store
+            .verify(
+                dir,
+                VerifyOptions::default(),
+                load,
+                |current, _| match current {
+                    0 => Ok(()),
+                    _ => Err("Cancelled".to_string()),
+                }
+            )
+            .is_err());
+        assert_eq!(run(VerifyOptions::default()).counts.new, 1);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Error returned by commands whose job was cancelled
+pub const CANCELLED: &str = "Cancelled";
+
+/// Counter for ids of jobs started without one from the frontend
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Running jobs and their cancellation flags
+#[derive(Default)]
+pub struct JobRegistry(Mutex<HashMap<String, Arc<AtomicBool>>>);
+
+impl JobRegistry {
+    /// Ask a job to stop at its next check; false if no such job is running
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.0.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Register a job under `id`, or a generated id without one; returns the id and the
+    /// job's cancellation flag
+    fn register(&self, id: Option<String>) -> Result<(String, Arc<AtomicBool>), String> {
+        let id = id
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| format!("job-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        let mut jobs = self.0.lock().unwrap();
+        if jobs.contains_key(&id) {
+            return Err(format!("Job {} is already running", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        jobs.insert(id.clone(), cancelled.clone());
+        Ok((id, cancelled))
+    }
+
+    fn finish(&self, id: &str) {
+        self.0.lock().unwrap().remove(id);
+    }
+}
+
+/// Payload of the `job-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress<'a> {
+    pub id: &'a str,
+    pub phase: &'a str,
+    pub current: usize,
+    pub total: usize,
+}
+
+/// A long-running command registered for progress reporting and cancellation;
+/// unregistered again when dropped
+pub struct Job {
+    id: String,
+    app: AppHandle,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    /// Register a job under the id chosen by the frontend, so it can cancel it while the
+    /// command runs; without one an id is generated
+    pub fn start(app: &AppHandle, id: Option<String>) -> Result<Self, String> {
+        let (id, cancelled) = app.state::<JobRegistry>().register(id)?;
+        Ok(Self {
+            id,
+            app: app.clone(),
+            cancelled,
+        })
+    }
+
+    /// Emit `job-progress` for the unit of work `current` of `total`
+    pub fn progress(&self, phase: &str, current: usize, total: usize) {
+        let payload = JobProgress {
+            id: &self.id,
+            phase,
+            current,
+            total,
+        };
+        if let Err(e) = self.app.emit("job-progress", &payload) {
+            log_line!("Failed to emit job-progress: {}", e);
+        }
+    }
+
+    /// `Err(CANCELLED)` once the job was cancelled; call between units of work
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        check(&self.cancelled)
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.app.state::<JobRegistry>().finish(&self.id);
+    }
+}
+
+fn check(cancelled: &AtomicBool) -> Result<(), String> {
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(CANCELLED.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_are_cancelled_by_id_while_registered() {
+        let registry = JobRegistry::default();
+        let (id, cancelled) = registry.register(Some("export-1".to_string())).unwrap();
+        assert_eq!(id, "export-1");
+        assert!(registry
+            .register(Some("export-1".to_string()))
+            .unwrap_err()
+            .contains("already running"));
+
+        assert!(check(&cancelled).is_ok());
+        assert!(!registry.cancel("export-2"));
+        assert!(registry.cancel("export-1"));
+        assert_eq!(check(&cancelled), Err(CANCELLED.to_string()));
+
+        // Once finished, the id is free and cancelling it does nothing
+        registry.finish("export-1");
+        assert!(!registry.cancel("export-1"));
+        let (_, again) = registry.register(Some("export-1".to_string())).unwrap();
+        assert!(check(&again).is_ok());
+    }
+
+    #[test]
+    fn jobs_without_an_id_get_one_of_their_own() {
+        let registry = JobRegistry::default();
+        let (first, _) = registry.register(None).unwrap();
+        let (second, _) = registry.register(Some(String::new())).unwrap();
+        assert!(first.starts_with("job-") && second.starts_with("job-"));
+        assert_ne!(first, second);
+    }
+}
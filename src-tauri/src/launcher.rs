@@ -0,0 +1,523 @@
+use crate::settings::SettingsStore;
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// An application registered for a file type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Application {
+    /// Stable identifier to pass to `open_with` and store in settings: the desktop file id on
+    /// Linux, the executable name on Windows, the bundle path on macOS
+    pub id: String,
+    pub name: String,
+}
+
+/// Payload of the `open-with-fallback` event
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenWithFallback {
+    pub app_id: String,
+    pub extension: String,
+    pub message: String,
+}
+
+/// Applications registered for files with `extension` (without dot)
+pub fn applications_for(extension: &str) -> Vec<Application> {
+    platform::candidates(&extension.to_ascii_lowercase())
+        .into_iter()
+        .map(|candidate| candidate.app)
+        .collect()
+}
+
+/// Open a file with the application chosen for its extension in settings, or the OS default
+pub fn open(app: &AppHandle, file: &Path) -> Result<(), String> {
+    let chosen = app
+        .state::<SettingsStore>()
+        .get()
+        .open_with
+        .get(&extension_of(file))
+        .cloned();
+    open_with(app, file, chosen.as_deref())
+}
+
+/// Open a file with the application `app_id`, or the OS default without one. An application
+/// that isn't registered for the file type (anymore) falls back to the default with an
+/// `open-with-fallback` event; only registered handlers can be launched this way.
+pub fn open_with(app: &AppHandle, file: &Path, app_id: Option<&str>) -> Result<(), String> {
+    let Some(app_id) = app_id else {
+        return open_default(file);
+    };
+    let extension = extension_of(file);
+    match platform::candidates(&extension)
+        .into_iter()
+        .find(|candidate| candidate.app.id == app_id)
+    {
+        Some(candidate) => platform::launch(&candidate, file),
+        None => {
+            let message = format!(
+                "{} is not available for .{} files anymore, opened with the default application",
+                app_id, extension
+            );
+            log_line!("{}", message);
+            let payload = OpenWithFallback {
+                app_id: app_id.to_string(),
+                extension,
+                message,
+            };
+            if let Err(e) = app.emit("open-with-fallback", payload) {
+                log_line!("Failed to emit open-with-fallback event: {}", e);
+            }
+            open_default(file)
+        }
+    }
+}
+
+/// Lowercased extension, the key of the per-extension settings
+fn extension_of(file: &Path) -> String {
+    file.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Open a file with the system default application
+pub fn open_default(file: &Path) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(file)
+            .spawn()
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+
+        // Use cmd.exe's start command - more reliable than PowerShell for opening files
+        // The empty "" is required as start interprets the first quoted arg as window title
+        // CREATE_NO_WINDOW prevents the console window from flashing
+        std::process::Command::new("cmd")
+            .args(["/c", "start", ""])
+            .arg(file)
+            .creation_flags(platform::CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(file)
+            .spawn()
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// A registered application and what is needed to launch it
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Candidate {
+    app: Application,
+    /// `Exec` line of the desktop entry (Linux)
+    exec: String,
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Application, Candidate};
+    use crate::parser::sniff::mime_for_extension;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    /// `applications` folders in XDG order: user entries shadow system ones
+    fn application_dirs() -> Vec<PathBuf> {
+        let env_dir = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+        let home = env_dir("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env_dir("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+        let system = env_dir("XDG_DATA_DIRS")
+            .map(|dirs| std::env::split_paths(&dirs).collect::<Vec<_>>())
+            .unwrap_or_else(|| vec!["/usr/local/share".into(), "/usr/share".into()]);
+        home.into_iter()
+            .chain(system)
+            .map(|dir| dir.join("applications"))
+            .collect()
+    }
+
+    pub(super) fn candidates(extension: &str) -> Vec<Candidate> {
+        let Some(mime) = mime_for_extension(extension) else {
+            return Vec::new();
+        };
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for dir in application_dirs() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+                .collect();
+            files.sort();
+            for file in files {
+                let id = file.file_name().unwrap().to_string_lossy().to_string();
+                // The first entry with an id wins, even if it hides the application
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let Ok(text) = std::fs::read_to_string(&file) else {
+                    continue;
+                };
+                if let Some(entry) = parse_desktop_entry(&text) {
+                    if !entry.hidden && entry.mime_types.iter().any(|m| m == mime) {
+                        candidates.push(Candidate {
+                            app: Application {
+                                id,
+                                name: entry.name,
+                            },
+                            exec: entry.exec,
+                        });
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    pub(super) fn launch(candidate: &Candidate, file: &Path) -> Result<(), String> {
+        let command = exec_command(&candidate.exec, file);
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| format!("{} has no command to run", candidate.app.id))?;
+        std::process::Command::new(program)
+            .args(args)
+            .spawn()
+            .map_err(|e| format!("Failed to open file with {}: {}", candidate.app.name, e))?;
+        Ok(())
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    pub(super) struct DesktopEntry {
+        pub name: String,
+        pub exec: String,
+        pub mime_types: Vec<String>,
+        /// `Hidden`, `NoDisplay` or not an application
+        pub hidden: bool,
+    }
+
+    /// The `[Desktop Entry]` group of a .desktop file; entries without `Exec` are skipped
+    pub(super) fn parse_desktop_entry(text: &str) -> Option<DesktopEntry> {
+        let mut entry = DesktopEntry::default();
+        let mut in_group = false;
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_group = line == "[Desktop Entry]";
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if !in_group {
+                continue;
+            }
+            let value = value.trim();
+            match key.trim() {
+                "Name" => entry.name = value.to_string(),
+                "Exec" => entry.exec = value.to_string(),
+                "MimeType" => {
+                    entry.mime_types = value
+                        .split(';')
+                        .filter(|mime| !mime.is_empty())
+                        .map(str::to_ascii_lowercase)
+                        .collect()
+                }
+                "Type" if value != "Application" => entry.hidden = true,
+                "Hidden" | "NoDisplay" if value == "true" => entry.hidden = true,
+                _ => {}
+            }
+        }
+        (!entry.exec.is_empty()).then_some(entry)
+    }
+
+    /// Split an `Exec` value into arguments and put `file` in place of the file field codes,
+    /// or at the end if there is none
+    pub(super) fn exec_command(exec: &str, file: &Path) -> Vec<String> {
+        let file = file.to_string_lossy().to_string();
+        let mut args = Vec::new();
+        let mut has_file = false;
+        for word in split_exec(exec) {
+            match word.as_str() {
+                "%f" | "%F" | "%u" | "%U" => {
+                    args.push(file.clone());
+                    has_file = true;
+                }
+                // Icon, translated name and desktop file location
+                "%i" | "%c" | "%k" => {}
+                _ => {
+                    // Deprecated codes are dropped, `%%` is a literal percent sign
+                    let mut arg = String::new();
+                    let mut chars = word.chars();
+                    while let Some(c) = chars.next() {
+                        if c != '%' {
+                            arg.push(c);
+                        } else if let Some('%') = chars.next() {
+                            arg.push('%');
+                        }
+                    }
+                    args.push(arg);
+                }
+            }
+        }
+        if !has_file {
+            args.push(file);
+        }
+        args
+    }
+
+    /// Words of an `Exec` value, honoring double quotes and backslash escapes inside them
+    fn split_exec(exec: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut in_word = false;
+        let mut quoted = false;
+        let mut chars = exec.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    in_word = true;
+                }
+                '\\' if quoted => word.extend(chars.next()),
+                c if c.is_whitespace() && !quoted => {
+                    if in_word {
+                        words.push(std::mem::take(&mut word));
+                        in_word = false;
+                    }
+                }
+                c => {
+                    word.push(c);
+                    in_word = true;
+                }
+            }
+        }
+        if in_word {
+            words.push(word);
+        }
+        words
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{Application, Candidate};
+    use std::os::windows::process::CommandExt;
+    use std::path::Path;
+
+    pub(super) const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    /// Output of `reg query`, empty if the key doesn't exist
+    fn reg_query(key: &str) -> String {
+        std::process::Command::new("reg")
+            .args(["query", key])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+            .unwrap_or_default()
+    }
+
+    /// Executables from the Explorer "Open with" lists of the user and the system
+    pub(super) fn candidates(extension: &str) -> Vec<Candidate> {
+        let mut executables: Vec<String> = Vec::new();
+        let user = reg_query(&format!(
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Explorer\FileExts\.{}\OpenWithList",
+            extension
+        ));
+        // Values "a", "b", ... name the executables; MRUList orders them
+        for line in user.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if let [name, "REG_SZ", value @ ..] = parts.as_slice() {
+                if *name != "MRUList" && !value.is_empty() {
+                    executables.push(value.join(" "));
+                }
+            }
+        }
+        let system = reg_query(&format!(r"HKCR\.{}\OpenWithList", extension));
+        // Subkeys are the executables
+        for line in system.lines() {
+            if let Some((_, name)) = line.trim().rsplit_once('\\') {
+                if line.trim_start().starts_with("HKEY_") && name.contains('.') {
+                    executables.push(name.to_string());
+                }
+            }
+        }
+
+        let mut candidates: Vec<Candidate> = Vec::new();
+        for executable in executables {
+            if !executable.to_ascii_lowercase().ends_with(".exe")
+                || candidates
+                    .iter()
+                    .any(|c| c.app.id.eq_ignore_ascii_case(&executable))
+            {
+                continue;
+            }
+            // Uninstalled applications leave their list entries behind
+            if reg_query(&format!(r"HKCR\Applications\{}", executable)).is_empty() {
+                continue;
+            }
+            let name = executable[..executable.len() - 4].to_string();
+            candidates.push(Candidate {
+                app: Application {
+                    id: executable,
+                    name,
+                },
+                exec: String::new(),
+            });
+        }
+        candidates
+    }
+
+    pub(super) fn launch(candidate: &Candidate, file: &Path) -> Result<(), String> {
+        // start resolves the executable through App Paths, like Explorer's "Open with"
+        std::process::Command::new("cmd")
+            .args(["/c", "start", "", &candidate.app.id])
+            .arg(file)
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|e| format!("Failed to open file with {}: {}", candidate.app.name, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{Application, Candidate};
+    use crate::parser::sniff::mime_for_extension;
+    use std::path::{Path, PathBuf};
+
+    fn application_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![
+            PathBuf::from("/Applications"),
+            PathBuf::from("/System/Applications"),
+        ];
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(PathBuf::from(home).join("Applications"));
+        }
+        dirs
+    }
+
+    /// Bundle's Info.plist as XML (plutil converts binary ones)
+    fn info_plist(bundle: &Path) -> Option<String> {
+        let output = std::process::Command::new("plutil")
+            .args(["-convert", "xml1", "-o", "-"])
+            .arg(bundle.join("Contents/Info.plist"))
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Bundles declaring the extension or its MIME type among their document types
+    pub(super) fn candidates(extension: &str) -> Vec<Candidate> {
+        let mut wanted = vec![format!("<string>{}</string>", extension)];
+        wanted
+            .extend(mime_for_extension(extension).map(|mime| format!("<string>{}</string>", mime)));
+
+        let mut candidates = Vec::new();
+        for dir in application_dirs() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut bundles: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "app"))
+                .collect();
+            bundles.sort();
+            for bundle in bundles {
+                let Some(plist) = info_plist(&bundle) else {
+                    continue;
+                };
+                let Some(pos) = plist.find("CFBundleDocumentTypes") else {
+                    continue;
+                };
+                let types = plist[pos..].to_ascii_lowercase();
+                if wanted.iter().any(|string| types.contains(string.as_str())) {
+                    let name = bundle.file_stem().unwrap().to_string_lossy().to_string();
+                    candidates.push(Candidate {
+                        app: Application {
+                            id: bundle.to_string_lossy().to_string(),
+                            name,
+                        },
+                        exec: String::new(),
+                    });
+                }
+            }
+        }
+        candidates
+    }
+
+    pub(super) fn launch(candidate: &Candidate, file: &Path) -> Result<(), String> {
+        std::process::Command::new("open")
+            .arg("-a")
+            .arg(&candidate.app.id)
+            .arg(file)
+            .spawn()
+            .map_err(|e| format!("Failed to open file with {}: {}", candidate.app.name, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+mod platform {
+    use super::Candidate;
+    use std::path::Path;
+
+    pub(super) fn candidates(_extension: &str) -> Vec<Candidate> {
+        Vec::new()
+    }
+
+    pub(super) fn launch(_candidate: &Candidate, _file: &Path) -> Result<(), String> {
+        Err("Choosing an application is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::*;
+    use std::path::Path;
+
+    #[test]
+    fn desktop_entries_and_exec_lines() {
+        let entry = parse_desktop_entry(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Document Viewer\n\
+             Exec=evince --fullscreen %U\n\
+             MimeType=application/pdf;image/tiff;\n\
+             [Desktop Action new-window]\n\
+             Exec=evince --new-window\n",
+        )
+        .unwrap();
+        assert_eq!(entry.name, "Document Viewer");
+        assert_eq!(entry.exec, "evince --fullscreen %U");
+        assert_eq!(entry.mime_types, ["application/pdf", "image/tiff"]);
+        assert!(!entry.hidden);
+
+        let file = Path::new("/tmp/my report.pdf");
+        assert_eq!(
+            exec_command("evince --fullscreen %U", file),
+            ["evince", "--fullscreen", "/tmp/my report.pdf"]
+        );
+        assert_eq!(
+            exec_command(r#""/opt/My App/viewer" --title "100%%" %i %k"#, file),
+            [
+                "/opt/My App/viewer",
+                "--title",
+                "100%",
+                "/tmp/my report.pdf"
+            ]
+        );
+        assert!(parse_desktop_entry("[Desktop Entry]\nName=Broken\n").is_none());
+    }
+}
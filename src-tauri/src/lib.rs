@@ -1,145 +1,74 @@
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, Manager};
-use std::io::Write;
-use tauri_plugin_dialog::DialogExt;
-
-/// Store pending file paths for when app is launched via file association
-pub struct PendingFiles(pub Mutex<Vec<PathBuf>>);
-
-/// Read a file from the filesystem and return its bytes
-#[tauri::command]
-fn read_file_as_bytes(path: String) -> Result<Vec<u8>, String> {
-    std::fs::read(&path).map_err(|e| format!("Failed to read file {}: {}", path, e))
-}
-
-/// Save a base64-encoded file to temp directory and open with system viewer
-#[tauri::command]
-fn open_file_with_system(base64_content: String, file_name: String) -> Result<(), String> {
-    use base64::{Engine as _, engine::general_purpose::STANDARD};
-
-    // Decode base64 content
-    let bytes = STANDARD.decode(&base64_content)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
-    // Create temp file path
-    let temp_dir = std::env::temp_dir();
-    let temp_path = temp_dir.join(&file_name);
-
-    // Write to temp file
-    let mut file = std::fs::File::create(&temp_path)
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
-    file.write_all(&bytes)
-        .map_err(|e| format!("Failed to write temp file: {}", e))?;
-
-    // Open with system default application
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&temp_path)
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-
-        // Use cmd.exe's start command - more reliable than PowerShell for opening files
-        // The empty "" is required as start interprets the first quoted arg as window title
-        // CREATE_NO_WINDOW prevents the console window from flashing
-        std::process::Command::new("cmd")
-            .args(["/c", "start", ""])
-            .arg(&temp_path)
-            .creation_flags(CREATE_NO_WINDOW)
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&temp_path)
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
-    }
-
-    Ok(())
-}
-
-/// Save a file with a "Save As" dialog
-#[tauri::command]
-async fn save_file_with_dialog(
-    app: AppHandle,
-    base64_content: String,
-    file_name: String,
-) -> Result<bool, String> {
-    use base64::{Engine as _, engine::general_purpose::STANDARD};
-    use tauri_plugin_dialog::FilePath;
-
-    // Extract file extension for filter
-    let extension = std::path::Path::new(&file_name)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_string();
-
-    // Build the save dialog
-    let file_path = app
-        .dialog()
-        .file()
-        .set_file_name(&file_name)
-        .add_filter("File", &[&extension])
-        .blocking_save_file();
-
-    match file_path {
-        Some(FilePath::Path(path)) => {
-            // Decode base64 content
-            let bytes = STANDARD
-                .decode(&base64_content)
-                .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
-            // Write to the selected file
-            let mut file = std::fs::File::create(&path)
-                .map_err(|e| format!("Failed to create file: {}", e))?;
-            file.write_all(&bytes)
-                .map_err(|e| format!("Failed to write file: {}", e))?;
-
-            Ok(true)
-        }
-        _ => Ok(false), // User cancelled
-    }
-}
-
-/// Get files that were passed to the app on startup
-#[tauri::command]
-fn get_pending_files(state: tauri::State<'_, PendingFiles>) -> Vec<String> {
-    let mut pending = state.0.lock().unwrap();
-    let files: Vec<String> = pending.drain(..).map(|p| p.to_string_lossy().to_string()).collect();
-    files
-}
-
-/// Handle a file being opened - emit event to frontend
-fn handle_file_open(app: &AppHandle, path: PathBuf) {
-    // Validate file extension
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
-
-    match ext.as_deref() {
-        Some("msg") | Some("eml") => {
-            // Emit event to frontend
-            if let Err(e) = app.emit("file-open", path.to_string_lossy().to_string()) {
-                eprintln!("Failed to emit file-open event: {}", e);
-            }
-        }
-        _ => {
-            eprintln!("Unsupported file type: {:?}", path);
-        }
-    }
-}
+use tauri::Manager;
+
+#[macro_use]
+mod logging;
+
+mod access;
+mod aes;
+mod archive;
+mod attachments;
+mod audit;
+mod auto_extract;
+mod avatars;
+#[doc(hidden)]
+pub mod bench;
+mod bimi;
+mod clipboard;
+mod commands;
+mod diagnostics;
+mod documents;
+mod drafts;
+mod export;
+mod file_open;
+mod filenames;
+mod find;
+mod folder_index;
+mod formatting;
+mod freshness;
+#[doc(hidden)]
+pub mod fuzzing;
+mod html_archive;
+mod i18n;
+mod images;
+mod integrity;
+mod jobs;
+mod launcher;
+mod media;
+mod memory;
+mod notes;
+mod notifications;
+mod onboarding;
+mod open_queue;
+mod parser;
+mod pdf;
+mod permissions;
+mod persist;
+mod platform;
+mod prefetch;
+mod preview_view;
+mod previews;
+mod quarantine;
+mod reader;
+mod reload;
+mod remote;
+mod report;
+mod retention;
+mod save;
+mod self_test;
+mod sender_prefs;
+mod sender_rules;
+mod session;
+mod settings;
+mod source;
+mod state;
+mod tempdir;
+mod theme;
+mod view_state;
+mod volumes;
+mod watcher;
+mod window_placement;
+mod workspaces;
+mod zip;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -148,78 +77,95 @@ pub fn run() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_process::init())
-        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
-            // Handle file opened when app is already running (Windows/Linux)
-            if args.len() > 1 {
-                let path = PathBuf::from(&args[1]);
-                handle_file_open(app, path);
+        .plugin(tauri_plugin_single_instance::init(
+            file_open::on_second_instance,
+        ));
+    let builder = state::manage(builder)
+        // Attachments for <audio> and <video>, with range requests for seeking
+        .register_asynchronous_uri_scheme_protocol(media::SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let response =
+                    media::handle(&app, &request, |path| commands::load_message(&app, path));
+                responder.respond(response);
+            });
+        })
+        // Message bodies for the sandboxed preview webviews
+        .register_uri_scheme_protocol(preview_view::SCHEME, |ctx, request| {
+            let app = ctx.app_handle();
+            preview_view::handle(
+                &app.state::<preview_view::PreviewViews>(),
+                ctx.webview_label(),
+                &request,
+            )
+        })
+        .setup(|app| {
+            state::load_settings(app);
+            let paranoid = app.state::<settings::SettingsStore>().get().paranoid_mode;
+
+            match app.path().app_log_dir() {
+                Ok(dir) => logging::init(&dir, !paranoid),
+                Err(e) => log_line!(
+                    "Failed to resolve log directory, logging to stderr only: {}",
+                    e
+                ),
             }
-            // Focus the main window
-            if let Some(window) = app.get_webview_window("main") {
-                let _ = window.set_focus();
+
+            state::load_data(app);
+            tempdir::configure(
+                app.path().app_cache_dir().ok().map(|dir| dir.join("temp")),
+                app.state::<settings::SettingsStore>()
+                    .get()
+                    .temp_dir
+                    .map(std::path::PathBuf::from),
+            );
+            if paranoid {
+                retention::apply_paranoid_mode(app.handle(), true);
             }
-        }))
-        .manage(PendingFiles(Mutex::new(Vec::new())))
-        .setup(|app| {
-            // Check for files passed as command-line arguments on startup (Windows/Linux)
-            let args: Vec<String> = std::env::args().collect();
-            for arg in args.iter().skip(1) {
-                let path = PathBuf::from(arg);
-                let ext = path
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .map(|e| e.to_lowercase());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || retention::run(&handle, false));
+
+            // Paint the themed background before the webview renders to avoid a white flash
+            theme::apply(app.handle());
+
+            session::init(app.handle());
 
-                if matches!(ext.as_deref(), Some("msg") | Some("eml")) {
-                    // Store for later retrieval by frontend
-                    app.state::<PendingFiles>()
-                        .0
-                        .lock()
-                        .unwrap()
-                        .push(path);
-                }
+            // Check for files passed as command-line arguments on startup (Windows/Linux)
+            let args: Vec<String> = std::env::args().skip(1).collect();
+            if quarantine::requested(&args) {
+                quarantine::force(app.handle());
             }
+            quarantine::apply_titles(app.handle());
+            file_open::queue_startup_args(app.handle(), args);
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![read_file_as_bytes, get_pending_files, open_file_with_system, save_file_with_dialog]);
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Finished {
+                settings::announce_reset(webview.app_handle());
+            }
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::ThemeChanged(_) => {
+                theme::on_system_theme_changed(window.app_handle());
+            }
+            tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => {
+                session::schedule_save(window.app_handle());
+            }
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                file_open::on_drop(window, paths);
+            }
+            tauri::WindowEvent::Destroyed => {
+                window
+                    .state::<preview_view::PreviewViews>()
+                    .forget_window(window.label());
+            }
+            _ => {}
+        })
+        .invoke_handler(commands::handler());
 
     builder
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
-        .run(|app, event| {
-            // Handle macOS file open events (double-click on file)
-            // This event only exists on macOS
-            #[cfg(target_os = "macos")]
-            if let tauri::RunEvent::Opened { urls } = &event {
-                for url in urls {
-                    // Convert file:// URL to path
-                    if let Ok(path) = url.to_file_path() {
-                        // Check if app is ready (has windows)
-                        if app.get_webview_window("main").is_some() {
-                            // App is running, emit event
-                            handle_file_open(app, path);
-                        } else {
-                            // App is starting up, store for later
-                            let ext = path
-                                .extension()
-                                .and_then(|e| e.to_str())
-                                .map(|e| e.to_lowercase());
-
-                            if matches!(ext.as_deref(), Some("msg") | Some("eml")) {
-                                app.state::<PendingFiles>()
-                                    .0
-                                    .lock()
-                                    .unwrap()
-                                    .push(path);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Suppress unused variable warning on non-macOS
-            #[cfg(not(target_os = "macos"))]
-            let _ = (app, event);
-        });
+        .run(platform::on_run_event);
 }
@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Lines kept in memory for diagnostics bundles
+const RING_CAPACITY: usize = 500;
+
+/// Log files are truncated on startup once they exceed this size
+const MAX_LOG_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Format of the timestamp that starts each line
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+struct Logger {
+    recent: Mutex<VecDeque<String>>,
+    file: Mutex<Option<std::fs::File>>,
+    dir: Mutex<Option<PathBuf>>,
+    /// Off in paranoid mode: lines only go to stderr and the ring buffer
+    to_disk: AtomicBool,
+}
+
+fn logger() -> &'static Logger {
+    static LOGGER: OnceLock<Logger> = OnceLock::new();
+    LOGGER.get_or_init(|| Logger {
+        recent: Mutex::new(VecDeque::with_capacity(RING_CAPACITY)),
+        file: Mutex::new(None),
+        dir: Mutex::new(None),
+        to_disk: AtomicBool::new(false),
+    })
+}
+
+/// Write a line to stderr, the in-memory ring buffer and the log file
+pub fn log(message: &str) {
+    let line = format!(
+        "{} {}",
+        chrono::Local::now().format(TIMESTAMP_FORMAT),
+        message
+    );
+    eprintln!("{}", message);
+
+    let logger = logger();
+    {
+        let mut recent = logger.recent.lock().unwrap();
+        if recent.len() == RING_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(line.clone());
+    }
+    if let Some(file) = logger.file.lock().unwrap().as_mut() {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Log a formatted message (drop-in replacement for `eprintln!`)
+#[macro_export]
+macro_rules! log_line {
+    ($($arg:tt)*) => {
+        $crate::logging::log(&format!($($arg)*))
+    };
+}
+
+/// Start mirroring the log to `dir/msgreader.log` and record crash reports there; with
+/// `to_disk` off, nothing is written until `set_file_enabled` turns it on
+pub fn init(dir: &Path, to_disk: bool) {
+    *logger().dir.lock().unwrap() = Some(dir.to_path_buf());
+    install_crash_hook(dir.join("crash-report.txt"));
+    set_file_enabled(to_disk);
+}
+
+/// Start or stop writing the log file and crash reports (paranoid mode stops them)
+pub fn set_file_enabled(enabled: bool) {
+    let logger = logger();
+    logger.to_disk.store(enabled, Ordering::Relaxed);
+    let mut file = logger.file.lock().unwrap();
+    if !enabled {
+        *file = None;
+        return;
+    }
+    if file.is_some() {
+        return;
+    }
+    let Some(path) = log_file_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Failed to create log directory {}: {}", dir.display(), e);
+            return;
+        }
+    }
+    let too_big = std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_FILE_BYTES);
+    match open_log_file(&path, too_big) {
+        Ok(opened) => *file = Some(opened),
+        Err(e) => eprintln!("Failed to open log file {}: {}", path.display(), e),
+    }
+}
+
+/// Whether the log file is being written
+pub fn file_enabled() -> bool {
+    logger().to_disk.load(Ordering::Relaxed)
+}
+
+/// Where the log file goes, once the log directory is known
+pub fn log_file_path() -> Option<PathBuf> {
+    let dir = logger().dir.lock().unwrap().clone()?;
+    Some(dir.join("msgreader.log"))
+}
+
+fn open_log_file(path: &Path, truncate: bool) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(!truncate)
+        .write(true)
+        .truncate(truncate)
+        .open(path)
+}
+
+/// Drop the lines written before `cutoff` from the log file; the number of bytes removed
+pub fn trim_before(cutoff: chrono::NaiveDateTime) -> Result<u64, String> {
+    let Some(path) = log_file_path() else {
+        return Ok(0);
+    };
+    // Holding the handle's lock keeps lines from being written meanwhile
+    let mut file = logger().file.lock().unwrap();
+    let Ok(data) = std::fs::read(&path) else {
+        return Ok(0);
+    };
+    let text = String::from_utf8_lossy(&data);
+    // Lines are in time order; a line without a timestamp continues the one before
+    let keep_from = text
+        .split_inclusive('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some((start, line))
+        })
+        .find(|(_, line)| {
+            line.get(..19)
+                .and_then(|stamp| {
+                    chrono::NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok()
+                })
+                .is_some_and(|stamp| stamp >= cutoff)
+        })
+        .map(|(start, _)| start)
+        .unwrap_or(text.len());
+    if keep_from == 0 {
+        return Ok(0);
+    }
+    let reopen = file.take().is_some();
+    let kept = &text.as_bytes()[keep_from..];
+    let result = std::fs::write(&path, kept)
+        .map(|()| data.len().saturating_sub(kept.len()) as u64)
+        .map_err(|e| format!("Failed to trim {}: {}", path.display(), e));
+    if reopen {
+        *file = open_log_file(&path, false).ok();
+    }
+    result
+}
+
+/// Delete the log file and the crash report, returning what was deleted with its size; a
+/// log that is still being written starts over empty
+pub fn delete_files() -> Vec<(PathBuf, u64)> {
+    let Some(path) = log_file_path() else {
+        return Vec::new();
+    };
+    let mut file = logger().file.lock().unwrap();
+    let reopen = file.take().is_some();
+    let mut deleted = Vec::new();
+    for path in [path.clone(), path.with_file_name("crash-report.txt")] {
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if std::fs::remove_file(&path).is_ok() {
+            deleted.push((path, size));
+        }
+    }
+    if reopen {
+        *file = open_log_file(&path, true).ok();
+    }
+    deleted
+}
+
+/// The last `n` log lines, oldest first
+pub fn recent_lines(n: usize) -> Vec<String> {
+    let recent = logger().recent.lock().unwrap();
+    let skip = recent.len().saturating_sub(n);
+    recent.iter().skip(skip).cloned().collect()
+}
+
+/// Path of the crash report written by the panic hook, if one exists
+pub fn crash_report_path() -> Option<PathBuf> {
+    let dir = logger().dir.lock().unwrap().clone()?;
+    let path = dir.join("crash-report.txt");
+    path.is_file().then_some(path)
+}
+
+/// Release builds abort on panic, so the hook is the only chance to record what happened
+fn install_crash_hook(path: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = format!(
+            "msgReader {} crashed at {}\n{}\n\nRecent log:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().to_rfc3339(),
+            info,
+            // The panic may have happened while the ring buffer was locked
+            logger()
+                .recent
+                .try_lock()
+                .map(|recent| recent.iter().cloned().collect::<Vec<_>>().join("\n"))
+                .unwrap_or_default()
+        );
+        if file_enabled() {
+            let _ = std::fs::write(&path, report);
+        }
+        default_hook(info);
+    }));
+}
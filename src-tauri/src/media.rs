@@ -0,0 +1,373 @@
+use crate::documents::OpenDocuments;
+use crate::parser::{self, Attachment};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager};
+
+/// URI scheme serving attachments of open documents: `attach://localhost/<doc-id>/<index>`
+/// (`http://attach.localhost/...` on Windows). Attachments of attached messages add the
+/// indices of the messages before the attachment index, as in `part_path`; inline images
+/// are also addressed by content id as `<doc-id>/cid/<content-id>`.
+/// The frontend builds these URLs with `attachmentStreamUrl` and `inlineImageUrlBuilder`.
+pub const SCHEME: &str = "attach";
+
+/// Total size of extracted attachments kept for the session; the oldest are dropped first
+const CACHE_BUDGET: usize = 256 * 1024 * 1024;
+
+/// An extracted attachment
+pub(crate) struct Extracted {
+    mime: String,
+    sha256: String,
+    pub(crate) data: Vec<u8>,
+}
+
+/// What a request asks for within a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Address {
+    /// Attachment indices, as `part_path` followed by the attachment index
+    Indices(Vec<usize>),
+    /// Attachment of the outer message with this content id
+    ContentId(String),
+}
+
+impl Address {
+    /// Parse the request path after the document id
+    fn parse(rest: &str) -> Option<Self> {
+        if let Some(content_id) = rest.strip_prefix("cid/") {
+            return (!content_id.is_empty()).then(|| Address::ContentId(content_id.to_string()));
+        }
+        let indices = rest
+            .split('/')
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        (!indices.is_empty()).then_some(Address::Indices(indices))
+    }
+}
+
+struct CacheEntry {
+    path: String,
+    address: Address,
+    extracted: Arc<Extracted>,
+    used: Instant,
+}
+
+/// Attachments extracted for the protocol, by document path and index path, oldest first
+#[derive(Default)]
+pub struct AttachmentCache(Mutex<VecDeque<CacheEntry>>);
+
+impl AttachmentCache {
+    fn get(&self, path: &str, address: &Address) -> Option<Arc<Extracted>> {
+        let mut cache = self.0.lock().unwrap();
+        let entry = cache
+            .iter_mut()
+            .find(|entry| entry.path == path && entry.address == *address)?;
+        entry.used = Instant::now();
+        Some(entry.extracted.clone())
+    }
+
+    /// The attachment at `address` of the document at `path`, extracted with `load` unless
+    /// it's cached
+    pub(crate) fn fetch(
+        &self,
+        path: &str,
+        address: &Address,
+        load: impl FnOnce(&str) -> Result<parser::Message, String>,
+    ) -> Result<Arc<Extracted>, String> {
+        if let Some(extracted) = self.get(path, address) {
+            return Ok(extracted);
+        }
+        let extracted = Arc::new(extract(path, address, load)?);
+        self.insert(path.to_string(), address.clone(), extracted.clone());
+        Ok(extracted)
+    }
+
+    fn insert(&self, path: String, address: Address, extracted: Arc<Extracted>) {
+        let mut cache = self.0.lock().unwrap();
+        cache.push_back(CacheEntry {
+            path,
+            address,
+            extracted,
+            used: Instant::now(),
+        });
+        while cache.len() > 1
+            && cache
+                .iter()
+                .map(|entry| entry.extracted.data.len())
+                .sum::<usize>()
+                > CACHE_BUDGET
+        {
+            cache.pop_front();
+        }
+    }
+
+    /// Bytes held and when they were last used, by document path (see `memory`)
+    pub fn held(&self) -> Vec<(PathBuf, usize, Instant)> {
+        let cache = self.0.lock().unwrap();
+        crate::memory::by_path(cache.iter().map(|entry| {
+            (
+                Path::new(&entry.path),
+                entry.extracted.data.len(),
+                entry.used,
+            )
+        }))
+    }
+
+    /// Drop the attachments of the file at `path`; documents are registered with the path
+    /// the frontend gave, so the entries' paths are resolved for the comparison
+    pub fn invalidate(&self, path: &Path) {
+        self.0.lock().unwrap().retain(|entry| {
+            let entry_path = Path::new(&entry.path);
+            entry_path != path && entry_path.canonicalize().ok().as_deref() != Some(path)
+        });
+    }
+}
+
+/// Answer a protocol request. `load` parses a message file like the commands do, so the
+/// access policy applies; documents must be registered (`register_document`).
+pub fn handle(
+    app: &AppHandle,
+    request: &Request<Vec<u8>>,
+    load: impl FnOnce(&str) -> Result<parser::Message, String>,
+) -> Response<Vec<u8>> {
+    // convertFileSrc in the frontend percent-encodes the whole path, slashes included
+    let path = percent_decode(request.uri().path());
+    let (doc_id, rest) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or_default();
+    let Some(address) = Address::parse(rest) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid attachment address");
+    };
+
+    let path = {
+        let documents = app.state::<OpenDocuments>();
+        let registry = documents.0.lock().unwrap();
+        match registry.get(doc_id) {
+            Some(document) => document.path.to_string_lossy().to_string(),
+            None => return error(StatusCode::NOT_FOUND, "Unknown document"),
+        }
+    };
+
+    let extracted = match app.state::<AttachmentCache>().fetch(&path, &address, load) {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            log_line!("Attachment request {} failed: {}", request.uri(), e);
+            return error(StatusCode::NOT_FOUND, &e);
+        }
+    };
+    crate::memory::enforce(app);
+
+    let etag = format!("\"{}\"", extracted.sha256);
+    if request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes() == etag.as_bytes())
+    {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .body(Vec::new())
+            .unwrap();
+    }
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    respond(
+        &extracted,
+        range,
+        request.method() == tauri::http::Method::HEAD,
+    )
+}
+
+fn extract(
+    path: &str,
+    address: &Address,
+    load: impl FnOnce(&str) -> Result<parser::Message, String>,
+) -> Result<Extracted, String> {
+    let message = load(path)?;
+    let attachment: Attachment = match address {
+        Address::Indices(indices) => {
+            let (index, part_path) = indices.split_last().expect("parsed as non-empty");
+            message
+                .into_nested(part_path)?
+                .attachments
+                .into_iter()
+                .nth(*index)
+                .ok_or_else(|| format!("Attachment {} not found", index))?
+        }
+        Address::ContentId(content_id) => {
+            let wanted = normalize_content_id(content_id);
+            message
+                .attachments
+                .into_iter()
+                .find(|attachment| {
+                    attachment
+                        .content_id
+                        .as_deref()
+                        .is_some_and(|id| normalize_content_id(id) == wanted)
+                })
+                .ok_or_else(|| format!("No attachment with content id {}", content_id))?
+        }
+    };
+    Ok(Extracted {
+        mime: attachment
+            .detected_mime
+            .or(attachment.declared_mime)
+            .unwrap_or_else(|| parser::sniff::OCTET_STREAM.to_string()),
+        sha256: attachment.sha256,
+        data: attachment.data,
+    })
+}
+
+/// Content ids compare without angle brackets and case-insensitively
+fn normalize_content_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+/// Decode `%XX` escapes; invalid ones are kept as they are
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// The whole attachment, or the requested byte range of it
+fn respond(extracted: &Extracted, range: Option<&str>, head: bool) -> Response<Vec<u8>> {
+    let len = extracted.data.len();
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, &extracted.mime)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, format!("\"{}\"", extracted.sha256))
+        // Content of an attachment never changes; the webview may keep it for the session
+        .header(header::CACHE_CONTROL, "private, max-age=86400, immutable")
+        .header("X-Content-Type-Options", "nosniff");
+
+    let (status, builder, body) = match range.map(|range| parse_range(range, len)) {
+        None => (StatusCode::OK, builder, &extracted.data[..]),
+        Some(Some((start, end))) => (
+            StatusCode::PARTIAL_CONTENT,
+            builder.header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, len),
+            ),
+            &extracted.data[start..=end],
+        ),
+        Some(None) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Vec::new())
+                .unwrap();
+        }
+    };
+    builder
+        .status(status)
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(if head { Vec::new() } else { body.to_vec() })
+        .unwrap()
+}
+
+/// First range of a `Range: bytes=...` header as inclusive offsets; `None` if it can't be
+/// satisfied. Media elements only ever ask for one range.
+fn parse_range(range: &str, len: usize) -> Option<(usize, usize)> {
+    let spec = range
+        .trim()
+        .strip_prefix("bytes=")?
+        .split(',')
+        .next()?
+        .trim();
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // Suffix range: the last n bytes
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => {
+            let end: usize = end.parse().ok()?;
+            (start.parse().ok()?, end.min(len.checked_sub(1)?))
+        }
+    };
+    (start <= end && end < len).then_some((start, end))
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.as_bytes().to_vec())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_ranges() {
+        assert_eq!(parse_range("bytes=0-", 100), Some((0, 99)));
+        assert_eq!(parse_range("bytes=10-19", 100), Some((10, 19)));
+        assert_eq!(parse_range("bytes=90-200", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=-10", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=100-", 100), None);
+        assert_eq!(parse_range("bytes=20-10", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+
+        assert_eq!(Address::parse("2/0"), Some(Address::Indices(vec![2, 0])));
+        assert_eq!(
+            Address::parse("cid/image001.png@01D9"),
+            Some(Address::ContentId("image001.png@01D9".to_string()))
+        );
+        assert_eq!(Address::parse("x"), None);
+        assert_eq!(Address::parse(""), None);
+        assert_eq!(
+            percent_decode("doc-1%2Fcid%2Flogo%40x%zz"),
+            "doc-1/cid/logo@x%zz"
+        );
+
+        let extracted = Extracted {
+            mime: "audio/mpeg".to_string(),
+            sha256: String::new(),
+            data: (0..100).collect(),
+        };
+        let partial = respond(&extracted, Some("bytes=10-19"), false);
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 10-19/100");
+        assert_eq!(partial.headers()[header::CONTENT_LENGTH], "10");
+        assert_eq!(partial.body(), &(10..20).collect::<Vec<u8>>());
+
+        let full = respond(&extracted, None, false);
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.body().len(), 100);
+        let unsatisfiable = respond(&extracted, Some("bytes=500-"), false);
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+}
@@ -0,0 +1,391 @@
+//! What open documents keep in memory, and giving it back. Besides the snapshot of the
+//! last parse (and the page of its preview webview), a document holds data the caches can
+//! rebuild from the file: extracted attachments, its source, the unpacked contents of an
+//! archive it was opened from. `get_memory_report` shows these per document,
+//! `unload_document_caches` drops them for one document, and once together they go over
+//! `memory_threshold` they're dropped for the documents used least recently, the one in
+//! use aside, with a `memory-pressure` event. Unloaded documents stay open; what they need
+//! next is extracted again. Archives unlocked with a password are never dropped, since
+//! that would mean asking for it again.
+
+use crate::archive::Archives;
+use crate::documents::OpenDocuments;
+use crate::media::AttachmentCache;
+use crate::preview_view::PreviewViews;
+use crate::settings::SettingsStore;
+use crate::source::SourceCache;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentMemory {
+    pub doc_id: String,
+    pub path: String,
+    /// Bytes kept of the last parse and the preview page; not released by unloading
+    pub parsed: usize,
+    /// Bytes of extracted attachments
+    pub attachments: usize,
+    /// Bytes of the message source, as built for windowing and search
+    pub source: usize,
+    /// Bytes unpacked from the archive the document was opened from
+    pub archive: usize,
+    pub total: usize,
+    #[serde(skip)]
+    last_used: Option<Instant>,
+}
+
+impl DocumentMemory {
+    /// Bytes unloading the document releases
+    fn regenerable(&self) -> usize {
+        self.attachments + self.source + self.archive
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryReport {
+    pub documents: Vec<DocumentMemory>,
+    /// Bytes cached for files that are no longer open
+    pub other: usize,
+    /// Bytes the caches could rebuild, counted against `threshold`
+    pub regenerable: usize,
+    pub total: usize,
+    pub threshold: usize,
+}
+
+/// Payload of the `memory-pressure` event
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryPressure {
+    /// Regenerable bytes before and after the eviction
+    pub before: usize,
+    pub after: usize,
+    pub threshold: usize,
+    /// Documents whose caches were dropped, least recently used first
+    pub unloaded: Vec<String>,
+}
+
+/// Sum the bytes of cache entries by path, keeping the latest use of each
+pub fn by_path<'a>(
+    entries: impl Iterator<Item = (&'a Path, usize, Instant)>,
+) -> Vec<(PathBuf, usize, Instant)> {
+    let mut paths: BTreeMap<&Path, (usize, Instant)> = BTreeMap::new();
+    for (path, bytes, used) in entries {
+        let held = paths.entry(path).or_insert((0, used));
+        held.0 += bytes;
+        held.1 = held.1.max(used);
+    }
+    paths
+        .into_iter()
+        .map(|(path, (bytes, used))| (path.to_path_buf(), bytes, used))
+        .collect()
+}
+
+/// An open document, as far as memory goes
+struct Owner {
+    doc_id: String,
+    path: PathBuf,
+    parsed: usize,
+}
+
+/// What the caches hold, by path
+struct Held {
+    attachments: Vec<(PathBuf, usize, Instant)>,
+    source: Vec<(PathBuf, usize, Instant)>,
+    archive: Vec<(PathBuf, usize, Instant)>,
+}
+
+impl Held {
+    fn total(&self) -> usize {
+        [&self.attachments, &self.source, &self.archive]
+            .into_iter()
+            .flatten()
+            .map(|(_, bytes, _)| bytes)
+            .sum()
+    }
+}
+
+struct Caches<'a> {
+    attachments: &'a AttachmentCache,
+    source: &'a SourceCache,
+    archives: &'a Archives,
+}
+
+impl<'a> Caches<'a> {
+    fn of(app: &'a AppHandle) -> Self {
+        Self {
+            attachments: app.state::<AttachmentCache>().inner(),
+            source: app.state::<SourceCache>().inner(),
+            archives: app.state::<Archives>().inner(),
+        }
+    }
+
+    fn held(&self) -> Held {
+        Held {
+            attachments: self.attachments.held(),
+            source: self.source.held(),
+            archive: self.archives.held(),
+        }
+    }
+
+    /// Drop what can be rebuilt for the file at `path`; caches key files by the path the
+    /// frontend gave or the resolved one
+    fn release(&self, path: &Path) {
+        let resolved = path.canonicalize().ok();
+        for path in std::iter::once(path).chain(resolved.as_deref()) {
+            self.attachments.invalidate(path);
+            self.source.invalidate(path);
+            self.archives.release(path);
+        }
+    }
+}
+
+/// The report of `owners` over `held`, and the paths held for no open document
+fn measure(owners: &[Owner], held: &Held, threshold: usize) -> (MemoryReport, Vec<PathBuf>) {
+    let names: Vec<Vec<PathBuf>> = owners
+        .iter()
+        .map(|owner| {
+            let mut names = vec![owner.path.clone()];
+            names.extend(owner.path.canonicalize().ok());
+            names
+        })
+        .collect();
+    let owned = |path: &Path| names.iter().flatten().any(|name| name == path);
+
+    let documents = owners
+        .iter()
+        .zip(&names)
+        .map(|(owner, names)| {
+            let mut last_used = None;
+            let mut sum = |entries: &[(PathBuf, usize, Instant)]| {
+                let mut bytes = 0;
+                for (path, held, used) in entries {
+                    if names.contains(path) {
+                        bytes += held;
+                        last_used = last_used.max(Some(*used));
+                    }
+                }
+                bytes
+            };
+            let attachments = sum(&held.attachments);
+            let source = sum(&held.source);
+            let archive = sum(&held.archive);
+            DocumentMemory {
+                doc_id: owner.doc_id.clone(),
+                path: owner.path.to_string_lossy().to_string(),
+                parsed: owner.parsed,
+                attachments,
+                source,
+                archive,
+                total: owner.parsed + attachments + source + archive,
+                last_used,
+            }
+        })
+        .collect();
+
+    let mut other = 0;
+    let mut unowned = Vec::new();
+    for (path, bytes, _) in [&held.attachments, &held.source, &held.archive]
+        .into_iter()
+        .flatten()
+    {
+        if !owned(path) {
+            other += bytes;
+            unowned.push(path.clone());
+        }
+    }
+    let regenerable = held.total();
+    let report = MemoryReport {
+        documents,
+        other,
+        regenerable,
+        total: regenerable + owners.iter().map(|owner| owner.parsed).sum::<usize>(),
+        threshold,
+    };
+    (report, unowned)
+}
+
+/// Documents to unload to get back under the threshold, least recently used first; the
+/// most recently used one is kept, as it's most likely in use
+fn plan(report: &MemoryReport) -> Vec<String> {
+    let mut candidates: Vec<&DocumentMemory> = report
+        .documents
+        .iter()
+        .filter(|doc| doc.regenerable() > 0)
+        .collect();
+    // Stable, so documents used at the same instant go in the order they were opened
+    candidates.sort_by_key(|doc| doc.last_used);
+    candidates.pop();
+
+    let mut excess = report.regenerable.saturating_sub(report.threshold);
+    let mut unload = Vec::new();
+    for doc in candidates {
+        if excess == 0 {
+            break;
+        }
+        excess = excess.saturating_sub(doc.regenerable());
+        unload.push(doc.doc_id.clone());
+    }
+    unload
+}
+
+/// Drop cached data over `threshold`: first what is held for files no longer open, then
+/// the data of documents as planned. `None` when nothing was over.
+fn evict(caches: &Caches, owners: &[Owner], threshold: usize) -> Option<MemoryPressure> {
+    let before = caches.held().total();
+    if before <= threshold {
+        return None;
+    }
+    let (_, unowned) = measure(owners, &caches.held(), threshold);
+    for path in &unowned {
+        caches.release(path);
+    }
+    let (report, _) = measure(owners, &caches.held(), threshold);
+    let unloaded = plan(&report);
+    for owner in owners
+        .iter()
+        .filter(|owner| unloaded.contains(&owner.doc_id))
+    {
+        caches.release(&owner.path);
+    }
+    Some(MemoryPressure {
+        before,
+        after: caches.held().total(),
+        threshold,
+        unloaded,
+    })
+}
+
+fn owners(app: &AppHandle) -> Vec<Owner> {
+    let previews = app.state::<PreviewViews>();
+    let registry = app.state::<OpenDocuments>();
+    let registry = registry.0.lock().unwrap();
+    registry
+        .list()
+        .iter()
+        .map(|doc| Owner {
+            doc_id: doc.id.clone(),
+            path: doc.path.clone(),
+            parsed: doc.parsed.as_ref().map_or(0, |snapshot| snapshot.size())
+                + previews.page_size(&doc.id),
+        })
+        .collect()
+}
+
+pub fn report(app: &AppHandle) -> MemoryReport {
+    let threshold = app.state::<SettingsStore>().get().memory_threshold;
+    measure(&owners(app), &Caches::of(app).held(), threshold).0
+}
+
+/// Drop what the caches hold for `doc_id`, returning the bytes released; the document
+/// stays open
+pub fn unload(app: &AppHandle, doc_id: &str) -> Result<usize, String> {
+    let owners = owners(app);
+    let owner = owners
+        .iter()
+        .find(|owner| owner.doc_id == doc_id)
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    let caches = Caches::of(app);
+    let before = caches.held().total();
+    caches.release(&owner.path);
+    Ok(before.saturating_sub(caches.held().total()))
+}
+
+/// Evict cached data once it's over the threshold; called after the caches grow
+pub fn enforce(app: &AppHandle) {
+    let threshold = app.state::<SettingsStore>().get().memory_threshold;
+    let caches = Caches::of(app);
+    if caches.held().total() <= threshold {
+        return;
+    }
+    let Some(pressure) = evict(&caches, &owners(app), threshold) else {
+        return;
+    };
+    log_line!(
+        "Memory over {} bytes: released {} of {} bytes, unloaded {} document(s)",
+        threshold,
+        pressure.before - pressure.after,
+        pressure.before,
+        pressure.unloaded.len()
+    );
+    if let Err(e) = app.emit("memory-pressure", &pressure) {
+        log_line!("Failed to emit memory-pressure: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::Address;
+    use crate::parser::{self, ParseOptions};
+    use std::cell::Cell;
+
+    #[test]
+    fn least_recently_used_documents_are_unloaded_and_extracted_again() {
+        let (attachments, source, archives) = Default::default();
+        let caches = Caches {
+            attachments: &attachments,
+            source: &source,
+            archives: &archives,
+        };
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/memory");
+        let owners: Vec<Owner> = ["first", "second", "third"]
+            .iter()
+            .map(|name| Owner {
+                doc_id: name.to_string(),
+                path: PathBuf::from(format!("{}/{}.eml", fixtures, name)),
+                parsed: 100,
+            })
+            .collect();
+
+        let loads = Cell::new(0);
+        let fetch = |owner: &Owner| {
+            caches
+                .attachments
+                .fetch(
+                    &owner.path.to_string_lossy(),
+                    &Address::Indices(vec![0]),
+                    |path| {
+                        loads.set(loads.get() + 1);
+                        parser::parse_file(Path::new(path), &ParseOptions::default())
+                    },
+                )
+                .unwrap()
+        };
+        let extracted: Vec<_> = owners.iter().map(fetch).collect();
+        // Cached: fetching again doesn't parse the file
+        fetch(&owners[2]);
+        assert_eq!(loads.get(), 3);
+
+        let size = extracted[0].data.len();
+        assert!(size > 0);
+        let (report, unowned) = measure(&owners, &caches.held(), size);
+        assert!(unowned.is_empty());
+        assert_eq!(report.regenerable, 3 * size);
+        assert_eq!(report.total, 3 * size + 300);
+        assert!(report.documents.iter().all(|doc| doc.attachments == size));
+
+        // Under the threshold, nothing goes
+        assert!(evict(&caches, &owners, 3 * size).is_none());
+
+        let pressure = evict(&caches, &owners, size).unwrap();
+        assert_eq!(pressure.unloaded, ["first", "second"]);
+        assert_eq!((pressure.before, pressure.after), (3 * size, size));
+        let (report, _) = measure(&owners, &caches.held(), size);
+        assert_eq!(report.documents[0].attachments, 0);
+        assert_eq!(report.documents[0].parsed, 100);
+
+        // Unloaded documents still serve their attachments, extracted again
+        let again = fetch(&owners[0]);
+        assert_eq!(loads.get(), 4);
+        assert_eq!(again.data, extracted[0].data);
+        fetch(&owners[2]);
+        assert_eq!(loads.get(), 4);
+
+        // Data of files no longer open goes first, and alone when that's enough
+        let pressure = evict(&caches, &owners[1..], size).unwrap();
+        assert!(pressure.unloaded.is_empty());
+        assert_eq!(pressure.after, size);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::persist;
+use crate::view_state::validate_key;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Longest note accepted, in characters
+const MAX_NOTE_CHARS: usize = 10_000;
+
+/// Longest tag name accepted, in characters
+const MAX_TAG_CHARS: usize = 64;
+
+/// Reviewer notes and tags on messages, keyed by content hash (see `content_hash`) so they
+/// follow renamed or copied files; the files themselves are never touched. Unlike view
+/// states, annotations aren't evicted.
+pub struct Notes {
+    path: Option<PathBuf>,
+    inner: Mutex<NotesFile>,
+    /// Where files were hashed this run, for annotations created after the hashing
+    seen: Mutex<HashMap<String, PathBuf>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct NotesFile {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Entry {
+    note: String,
+    /// As first spelled; matched case-insensitively
+    tags: Vec<String>,
+    /// RFC 3339
+    updated: String,
+    /// Where the file was last seen, to report annotated files that are gone
+    last_path: Option<PathBuf>,
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.note.is_empty() && self.tags.is_empty()
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|t| t.to_lowercase() == tag.to_lowercase())
+    }
+}
+
+/// A message with a note or tags, as listed by `list_annotated_messages`
+#[derive(Debug, Clone, Serialize)]
+pub struct AnnotatedMessage {
+    pub path_hash: String,
+    pub note: String,
+    pub tags: Vec<String>,
+    pub updated: String,
+    pub last_path: Option<PathBuf>,
+}
+
+/// A tag in use, as listed by `list_tags`
+#[derive(Debug, Clone, Serialize)]
+pub struct TagInfo {
+    pub name: String,
+    /// Messages carrying it
+    pub count: usize,
+    /// From the `tag_colors` setting
+    pub color: Option<String>,
+}
+
+/// How `query_by_tags` combines tags
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum TagMode {
+    Any,
+    All,
+}
+
+/// Key of a message file's annotations: the lowercase hex SHA-256 of its content
+pub fn content_hash(path: &Path) -> Result<String, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(crate::parser::sha256_hex(&data))
+}
+
+/// Trimmed tag name, or an error for empty and oversized ones
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.chars().any(char::is_control) {
+        return Err(format!("Invalid tag: {:?}", tag));
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        return Err(format!("Tags are limited to {} characters", MAX_TAG_CHARS));
+    }
+    Ok(tag.to_string())
+}
+
+impl Notes {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            inner: Mutex::new(persist::read_json(&path).unwrap_or_default()),
+            path: Some(path),
+            seen: Mutex::default(),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            inner: Mutex::new(NotesFile::default()),
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Store the note for a file; an empty note removes it
+    pub fn set(&self, key: &str, note: &str) -> Result<(), String> {
+        validate_key(key)?;
+        if note.chars().count() > MAX_NOTE_CHARS {
+            return Err(format!(
+                "Notes are limited to {} characters",
+                MAX_NOTE_CHARS
+            ));
+        }
+
+        let mut file = self.inner.lock().unwrap();
+        let note = note.trim();
+        if note.is_empty() && !file.entries.contains_key(key) {
+            return Ok(());
+        }
+        let entry = self.entry(&mut file, key);
+        entry.note = note.to_string();
+        entry.updated = chrono::Local::now().to_rfc3339();
+        if entry.is_empty() {
+            file.entries.remove(key);
+        }
+        self.persist(&file)
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let file = self.inner.lock().unwrap();
+        file.entries
+            .get(key)
+            .map(|entry| entry.note.clone())
+            .filter(|note| !note.is_empty())
+    }
+
+    /// Annotations of a file, if it has any
+    pub fn annotation(&self, key: &str) -> Option<AnnotatedMessage> {
+        let file = self.inner.lock().unwrap();
+        file.entries.get(key).map(|entry| annotated(key, entry))
+    }
+
+    /// Every annotated file, most recently updated first
+    pub fn list(&self) -> Vec<AnnotatedMessage> {
+        let file = self.inner.lock().unwrap();
+        let mut notes: Vec<AnnotatedMessage> = file
+            .entries
+            .iter()
+            .map(|(key, entry)| annotated(key, entry))
+            .collect();
+        notes.sort_by(|a, b| {
+            b.updated
+                .cmp(&a.updated)
+                .then(a.path_hash.cmp(&b.path_hash))
+        });
+        notes
+    }
+
+    /// Tag every file in `keys`; returns how many didn't have the tag yet. A tag matching an
+    /// existing one except for case takes that one's spelling.
+    pub fn tag_many(&self, keys: &[String], tag: &str) -> Result<usize, String> {
+        for key in keys {
+            validate_key(key)?;
+        }
+        let tag = normalize_tag(tag)?;
+
+        let mut file = self.inner.lock().unwrap();
+        let tag = file
+            .entries
+            .values()
+            .flat_map(|entry| &entry.tags)
+            .find(|existing| existing.to_lowercase() == tag.to_lowercase())
+            .cloned()
+            .unwrap_or(tag);
+        let now = chrono::Local::now().to_rfc3339();
+        let mut added = 0;
+        for key in keys {
+            let entry = self.entry(&mut file, key);
+            if !entry.has_tag(&tag) {
+                entry.tags.push(tag.clone());
+                entry.updated = now.clone();
+                added += 1;
+            }
+        }
+        if added == 0 {
+            return Ok(0);
+        }
+        self.persist(&file)?;
+        Ok(added)
+    }
+
+    /// Remove a tag from a file; returns false if it didn't have it
+    pub fn remove_tag(&self, key: &str, tag: &str) -> Result<bool, String> {
+        validate_key(key)?;
+        let tag = normalize_tag(tag)?;
+
+        let mut file = self.inner.lock().unwrap();
+        let Some(entry) = file
+            .entries
+            .get_mut(key)
+            .filter(|entry| entry.has_tag(&tag))
+        else {
+            return Ok(false);
+        };
+        entry
+            .tags
+            .retain(|t| t.to_lowercase() != tag.to_lowercase());
+        entry.updated = chrono::Local::now().to_rfc3339();
+        if entry.is_empty() {
+            file.entries.remove(key);
+        }
+        self.persist(&file)?;
+        Ok(true)
+    }
+
+    /// Tags in use with their message counts and colors (keyed by lowercase name), by name
+    pub fn tags(&self, colors: &BTreeMap<String, String>) -> Vec<TagInfo> {
+        let file = self.inner.lock().unwrap();
+        let mut tags: BTreeMap<String, TagInfo> = BTreeMap::new();
+        for tag in file.entries.values().flat_map(|entry| &entry.tags) {
+            let key = tag.to_lowercase();
+            tags.entry(key.clone())
+                .or_insert_with(|| TagInfo {
+                    name: tag.clone(),
+                    count: 0,
+                    color: colors.get(&key).cloned(),
+                })
+                .count += 1;
+        }
+        tags.into_values().collect()
+    }
+
+    /// Whether the annotations of `key` carry any or all of `tags`
+    pub fn matches(&self, key: &str, tags: &[String], mode: TagMode) -> bool {
+        let file = self.inner.lock().unwrap();
+        let Some(entry) = file.entries.get(key) else {
+            return false;
+        };
+        match mode {
+            TagMode::Any => tags.iter().any(|tag| entry.has_tag(tag.trim())),
+            TagMode::All => tags.iter().all(|tag| entry.has_tag(tag.trim())),
+        }
+    }
+
+    /// Whether any file carries one of `tags`, to skip hashing a folder for nothing
+    pub fn any_tagged(&self, tags: &[String]) -> bool {
+        let file = self.inner.lock().unwrap();
+        file.entries
+            .values()
+            .any(|entry| tags.iter().any(|tag| entry.has_tag(tag.trim())))
+    }
+
+    /// Note where the file with content hash `key` is, for annotations of it
+    pub fn record_path(&self, key: &str, path: &Path) {
+        self.seen
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), path.to_path_buf());
+        let mut file = self.inner.lock().unwrap();
+        let Some(entry) = file.entries.get_mut(key) else {
+            return;
+        };
+        if entry.last_path.as_deref() != Some(path) {
+            entry.last_path = Some(path.to_path_buf());
+            if let Err(e) = self.persist(&file) {
+                log_line!("Failed to save notes: {}", e);
+            }
+        }
+    }
+
+    /// The entry of `key`, created on first use with the path the file was seen at
+    fn entry<'a>(&self, file: &'a mut NotesFile, key: &str) -> &'a mut Entry {
+        file.entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                last_path: self.seen.lock().unwrap().get(key).cloned(),
+                ..Entry::default()
+            })
+    }
+
+    /// The file notes are stored in
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn persist(&self, file: &NotesFile) -> Result<(), String> {
+        match &self.path {
+            Some(path) => persist::write_json(path, file),
+            None => Ok(()),
+        }
+    }
+}
+
+fn annotated(key: &str, entry: &Entry) -> AnnotatedMessage {
+    AnnotatedMessage {
+        path_hash: key.to_string(),
+        note: entry.note.clone(),
+        tags: entry.tags.clone(),
+        updated: entry.updated.clone(),
+        last_path: entry.last_path.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_persist_and_empty_notes_are_removed() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("notes.json");
+        let notes = Notes::load(path.clone());
+        notes
+            .set("abc123", "  relevant, forward to legal ")
+            .unwrap();
+        notes.set("def456", "duplicate").unwrap();
+        assert!(notes.set("../etc", "note").is_err());
+        assert!(notes
+            .set("abc123", &"x".repeat(MAX_NOTE_CHARS + 1))
+            .is_err());
+
+        let reloaded = Notes::load(path.clone());
+        assert_eq!(
+            reloaded.get("abc123").as_deref(),
+            Some("relevant, forward to legal")
+        );
+        assert_eq!(reloaded.list().len(), 2);
+
+        reloaded.set("def456", "").unwrap();
+        let listed = Notes::load(path.clone()).list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path_hash, "abc123");
+    }
+
+    #[test]
+    fn tags_match_case_insensitively() {
+        let notes = Notes::in_memory();
+        let keys = ["aa".to_string(), "bb".to_string()];
+        assert_eq!(notes.tag_many(&keys, " Privileged ").unwrap(), 2);
+        assert_eq!(notes.tag_many(&keys[..1], "privileged").unwrap(), 0);
+        notes.tag_many(&keys[1..], "responsive").unwrap();
+        assert!(notes.tag_many(&keys, "  ").is_err());
+
+        let colors = BTreeMap::from([("privileged".to_string(), "#c00".to_string())]);
+        let tags = notes.tags(&colors);
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].name, "Privileged");
+        assert_eq!(tags[0].count, 2);
+        assert_eq!(tags[0].color.as_deref(), Some("#c00"));
+
+        let wanted = ["PRIVILEGED".to_string(), "Responsive".to_string()];
+        assert!(notes.matches("aa", &wanted, TagMode::Any));
+        assert!(!notes.matches("aa", &wanted, TagMode::All));
+        assert!(notes.matches("bb", &wanted, TagMode::All));
+
+        // A tagged file without a note is still annotated, until its last tag goes
+        assert_eq!(notes.get("aa"), None);
+        assert!(notes.remove_tag("aa", "privileged").unwrap());
+        assert!(!notes.remove_tag("aa", "privileged").unwrap());
+        assert_eq!(notes.list().len(), 1);
+    }
+}
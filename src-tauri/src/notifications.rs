@@ -0,0 +1,276 @@
+use crate::file_open;
+use crate::i18n;
+use crate::parser;
+use crate::settings::{QuietHours, SettingsStore};
+use crate::state::PendingFiles;
+use chrono::Timelike;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// Arrivals within this window are collapsed into a single notification
+const COLLAPSE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Minimum gap between two notifications
+const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Debounce queue for new-message notifications
+#[derive(Default)]
+pub struct NotificationState(Mutex<Queue>);
+
+#[derive(Default)]
+struct Queue {
+    arrivals: Vec<PathBuf>,
+    flush_scheduled: bool,
+    last_shown: Option<Instant>,
+}
+
+/// Called by the folder watcher with newly created message files
+pub fn on_files_created(app: &AppHandle, created: &[String]) {
+    if created.is_empty() || !should_notify(app) {
+        return;
+    }
+
+    let state = app.state::<NotificationState>();
+    let mut queue = state.0.lock().unwrap();
+    queue.arrivals.extend(created.iter().map(PathBuf::from));
+    if queue.flush_scheduled {
+        return;
+    }
+    queue.flush_scheduled = true;
+
+    // Wait for the burst to settle and for the rate limit to expire
+    let cooldown = queue
+        .last_shown
+        .map(|shown| MIN_INTERVAL.saturating_sub(shown.elapsed()))
+        .unwrap_or_default();
+    let delay = COLLAPSE_WINDOW.max(cooldown);
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        flush(&app);
+    });
+}
+
+/// Notifications are only useful while the user isn't looking at the app
+fn should_notify(app: &AppHandle) -> bool {
+    let settings = app.state::<SettingsStore>().get();
+    if !settings.notifications_enabled {
+        return false;
+    }
+    if let Some(quiet) = &settings.quiet_hours {
+        let now = chrono::Local::now();
+        if in_quiet_hours(now.hour() * 60 + now.minute(), quiet) {
+            return false;
+        }
+    }
+
+    match app.get_webview_window("main") {
+        Some(window) => {
+            window.is_minimized().unwrap_or(false) || !window.is_visible().unwrap_or(true)
+        }
+        None => true,
+    }
+}
+
+/// Whether `minute_of_day` falls inside the quiet window (which may wrap past midnight)
+fn in_quiet_hours(minute_of_day: u32, quiet: &QuietHours) -> bool {
+    let (Some(start), Some(end)) = (parse_hhmm(&quiet.start), parse_hhmm(&quiet.end)) else {
+        return false;
+    };
+    if start <= end {
+        (start..end).contains(&minute_of_day)
+    } else {
+        minute_of_day >= start || minute_of_day < end
+    }
+}
+
+/// Parse "HH:MM" into minutes since midnight
+fn parse_hhmm(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Post one notification for everything queued since the last flush
+fn flush(app: &AppHandle) {
+    let arrivals = {
+        let state = app.state::<NotificationState>();
+        let mut queue = state.0.lock().unwrap();
+        queue.flush_scheduled = false;
+        queue.last_shown = Some(Instant::now());
+        std::mem::take(&mut queue.arrivals)
+    };
+
+    let (body, target) = match arrivals.as_slice() {
+        [] => return,
+        [path] => (describe_arrival(path), Some(path.clone())),
+        many => (
+            i18n::t(
+                "notification.new_messages",
+                &[("count", many.len().to_string())],
+            ),
+            None,
+        ),
+    };
+
+    // Only the notification's own click opens the file; other launches and activations of
+    // the app can't be told apart from it
+    let app = app.clone();
+    platform::show("msgReader", &body, move || activate(&app, target));
+}
+
+/// "New message from Alice: Q3 invoice", falling back to the file name
+fn describe_arrival(path: &std::path::Path) -> String {
+    let summary = parser::peek_summary(path).unwrap_or_default();
+    let subject = summary
+        .subject
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+    match summary.sender.filter(|s| !s.is_empty()) {
+        Some(sender) => i18n::t(
+            "notification.new_message_from",
+            &[("sender", sender), ("subject", subject)],
+        ),
+        None => i18n::t("notification.new_message", &[("subject", subject)]),
+    }
+}
+
+/// Bring the main window back (recreating it if it was closed) and open the file
+fn activate(app: &AppHandle, target: Option<PathBuf>) {
+    let (window, recreated) = match app.get_webview_window("main") {
+        Some(window) => (Some(window), false),
+        None => (recreate_main_window(app), true),
+    };
+
+    if let Some(window) = &window {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+
+    if let Some(path) = target {
+        if recreated {
+            // A fresh webview picks the file up via get_pending_files once loaded
+            app.state::<PendingFiles>().push(path);
+        } else {
+            file_open::handle_file_open(app, path);
+        }
+    }
+}
+
+fn recreate_main_window(app: &AppHandle) -> Option<tauri::WebviewWindow> {
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == "main")?
+        .clone();
+
+    match tauri::WebviewWindowBuilder::from_config(app, &config).and_then(|b| b.build()) {
+        Ok(window) => Some(window),
+        Err(e) => {
+            log_line!("Failed to recreate main window: {}", e);
+            None
+        }
+    }
+}
+
+/// OS notification backends; `on_click` runs when the platform reports a click, which only
+/// Linux does
+mod platform {
+    #[cfg(target_os = "linux")]
+    pub fn show<F: FnOnce() + Send + 'static>(title: &str, body: &str, on_click: F) {
+        let title = title.to_string();
+        let body = body.to_string();
+        std::thread::spawn(move || {
+            // --wait keeps notify-send alive until the notification is clicked or dismissed
+            let output = std::process::Command::new("notify-send")
+                .args(["--app-name=msgReader", "--action=default=Open", "--wait"])
+                .arg(&title)
+                .arg(&body)
+                .output();
+
+            match output {
+                Ok(out) if out.status.success() => {
+                    if String::from_utf8_lossy(&out.stdout).trim() == "default" {
+                        on_click();
+                    }
+                }
+                // Older libnotify doesn't know --action; show without click support
+                _ => {
+                    if let Err(e) = std::process::Command::new("notify-send")
+                        .arg("--app-name=msgReader")
+                        .arg(&title)
+                        .arg(&body)
+                        .status()
+                    {
+                        log_line!("Failed to show notification: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn show<F: FnOnce() + Send + 'static>(title: &str, body: &str, _on_click: F) {
+        // Text is passed as script arguments so it never needs AppleScript escaping.
+        // Notifications posted by osascript belong to it, so a click never reaches the app.
+        if let Err(e) = std::process::Command::new("osascript")
+            .args([
+                "-e",
+                "on run argv",
+                "-e",
+                "display notification (item 2 of argv) with title (item 1 of argv)",
+                "-e",
+                "end run",
+            ])
+            .arg(title)
+            .arg(body)
+            .spawn()
+        {
+            log_line!("Failed to show notification: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn show<F: FnOnce() + Send + 'static>(title: &str, body: &str, _on_click: F) {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+        // Text travels through environment variables to avoid PowerShell quoting issues.
+        // The toast only informs: nothing registers the app's AUMID or an activator, so a
+        // click can't bring the app back or open the file.
+        const SCRIPT: &str = r#"
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null
+$template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$texts = $template.GetElementsByTagName('text')
+$texts.Item(0).AppendChild($template.CreateTextNode($env:MSGREADER_TITLE)) | Out-Null
+$texts.Item(1).AppendChild($template.CreateTextNode($env:MSGREADER_BODY)) | Out-Null
+$toast = [Windows.UI.Notifications.ToastNotification]::new($template)
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('com.rasalas.msgreader').Show($toast)
+"#;
+
+        if let Err(e) = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .env("MSGREADER_TITLE", title)
+            .env("MSGREADER_BODY", body)
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+        {
+            log_line!("Failed to show notification: {}", e);
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    pub fn show<F: FnOnce() + Send + 'static>(_title: &str, _body: &str, _on_click: F) {}
+}
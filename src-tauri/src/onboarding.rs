@@ -0,0 +1,202 @@
+//! First run: a sample message to try the app with (`create_sample_message`), and what
+//! this machine offers (`get_platform_capabilities`) so the onboarding can suggest what
+//! applies here, like registering the file types or turning on notifications.
+
+use crate::settings::SettingsStore;
+use crate::{file_open, launcher, save};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// The sample: an inline image, an attachment, a meeting request and a subject in several
+/// scripts, marked as sample content in its subject, body and an `X-MsgReader-Sample`
+/// header
+const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/onboarding/sample.eml");
+
+/// File name of the sample in the app data folder
+const SAMPLE_FILE: &str = "sample-message.eml";
+
+/// The file types the app registers for
+const ASSOCIATED_EXTENSIONS: &[&str] = &["msg", "eml"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformCapabilities {
+    /// Name of the application handling `mailto:` links, if there is one
+    pub default_mail_client: Option<String>,
+    /// Whether msgReader is among the applications registered for each file type
+    pub file_associations: BTreeMap<&'static str, bool>,
+    /// `xdg-open` is installed; `None` off Linux, where it doesn't apply
+    pub xdg_open: Option<bool>,
+    /// The tool notifications are shown with is installed
+    pub notifications_available: bool,
+    /// Notifications are turned on in settings
+    pub notifications_enabled: bool,
+}
+
+pub(crate) use platform::default_mail_client;
+
+/// Write the sample to `dir`, again when it was deleted or is from another version
+fn write_sample(dir: &Path) -> Result<PathBuf, String> {
+    let path = dir.join(SAMPLE_FILE);
+    if std::fs::read(&path).is_ok_and(|data| data == SAMPLE) {
+        return Ok(path);
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    save::write_atomic(&path, SAMPLE)?;
+    Ok(path)
+}
+
+/// Write the sample message into the app data folder and open it like a file from the OS
+pub fn create_sample(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+    let path = write_sample(&dir)?;
+    file_open::handle_file_open(app, path.clone());
+    Ok(path)
+}
+
+pub fn capabilities(app: &AppHandle) -> PlatformCapabilities {
+    let product = app.package_info().name.to_ascii_lowercase();
+    let file_associations = ASSOCIATED_EXTENSIONS
+        .iter()
+        .map(|extension| {
+            let registered = launcher::applications_for(extension).iter().any(|app| {
+                app.id.to_ascii_lowercase().contains(&product)
+                    || app.name.to_ascii_lowercase().contains(&product)
+            });
+            (*extension, registered)
+        })
+        .collect();
+    PlatformCapabilities {
+        default_mail_client: default_mail_client(),
+        file_associations,
+        xdg_open: cfg!(target_os = "linux").then(|| on_path("xdg-open")),
+        notifications_available: platform::NOTIFIER.is_some_and(on_path),
+        notifications_enabled: app.state::<SettingsStore>().get().notifications_enabled,
+    }
+}
+
+/// Whether `program` is an executable in one of the `PATH` folders
+fn on_path(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    let names: Vec<String> = if cfg!(windows) {
+        vec![format!("{}.exe", program), program.to_string()]
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .any(|candidate| candidate.is_file())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    /// The program `notifications` shows notifications with
+    pub const NOTIFIER: Option<&str> = Some("notify-send");
+
+    /// The desktop entry `xdg-mime` reports for `mailto:` links
+    pub fn default_mail_client() -> Option<String> {
+        let output = std::process::Command::new("xdg-mime")
+            .args(["query", "default", "x-scheme-handler/mailto"])
+            .output()
+            .ok()?;
+        let entry = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !entry.is_empty())
+            .then(|| entry.trim_end_matches(".desktop").to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    pub const NOTIFIER: Option<&str> = Some("powershell");
+
+    /// The ProgId the user chose for `mailto:` links
+    pub fn default_mail_client() -> Option<String> {
+        let output = std::process::Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Software\Microsoft\Windows\Shell\Associations\UrlAssociations\mailto\UserChoice",
+                "/v",
+                "ProgId",
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("ProgId"))
+            .and_then(|rest| rest.trim().strip_prefix("REG_SZ"))
+            .map(|prog_id| prog_id.trim().to_string())
+            .filter(|prog_id| !prog_id.is_empty())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    pub const NOTIFIER: Option<&str> = Some("osascript");
+
+    /// Mail comes with every macOS; other clients only register through Launch Services,
+    /// which has no command-line query
+    pub fn default_mail_client() -> Option<String> {
+        std::path::Path::new("/System/Applications/Mail.app")
+            .exists()
+            .then(|| "Mail".to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    pub const NOTIFIER: Option<&str> = None;
+
+    pub fn default_mail_client() -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::grouping::AttachmentCategory;
+    use crate::parser::{parse_file, ParseOptions};
+
+    #[test]
+    fn sample_is_rich_marked_and_recreated() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = write_sample(dir).unwrap();
+
+        let message = parse_file(&path, &ParseOptions::default()).unwrap();
+        let subject = message.subject.as_deref().unwrap();
+        assert!(subject.starts_with("Sample:"), "{}", subject);
+        assert!(subject.contains("Grüße") && subject.contains("こんにちは"));
+        assert!(message
+            .body_text
+            .as_deref()
+            .unwrap()
+            .contains("sample message"));
+        let categories: Vec<(AttachmentCategory, bool)> = message
+            .attachments
+            .iter()
+            .map(|a| (a.category, a.content_id.is_some()))
+            .collect();
+        assert!(categories.contains(&(AttachmentCategory::Image, true)));
+        assert!(categories.contains(&(AttachmentCategory::Document, false)));
+        assert!(categories.contains(&(AttachmentCategory::Calendar, false)));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(write_sample(dir).unwrap(), path);
+        std::fs::write(&path, b"edited").unwrap();
+        write_sample(dir).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), SAMPLE);
+    }
+}
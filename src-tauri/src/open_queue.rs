@@ -0,0 +1,149 @@
+use crate::settings::SettingsStore;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Files opened through the OS within this window count as one burst (a multi-selection
+/// in Explorer or Finder arrives as a series of single-instance calls)
+const BURST_WINDOW: Duration = Duration::from_millis(300);
+
+/// Payload of the `bulk-open-request` event
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkOpenRequest {
+    pub paths: Vec<String>,
+}
+
+/// Files waiting for the current burst to end, in arrival order
+#[derive(Default)]
+pub struct OpenQueue(Mutex<Pending>);
+
+#[derive(Default)]
+struct Pending {
+    paths: Vec<PathBuf>,
+    flush_scheduled: bool,
+}
+
+impl OpenQueue {
+    /// Queue files of a burst; when they start one, the number queued so far, for the
+    /// caller to `schedule` the flush
+    pub fn add(&self, paths: Vec<PathBuf>) -> Option<usize> {
+        let mut pending = self.0.lock().unwrap();
+        pending.paths.extend(paths);
+        if pending.flush_scheduled {
+            return None;
+        }
+        pending.flush_scheduled = true;
+        Some(pending.paths.len())
+    }
+
+    /// The burst, if no file arrived since the queue held `seen` files; otherwise `seen`
+    /// is brought up to date to check again a window later
+    fn settle(&self, seen: &mut usize) -> Option<Vec<PathBuf>> {
+        let mut pending = self.0.lock().unwrap();
+        if !stopped_growing(*seen, pending.paths.len()) {
+            *seen = pending.paths.len();
+            return None;
+        }
+        pending.flush_scheduled = false;
+        Some(std::mem::take(&mut pending.paths))
+    }
+}
+
+/// Whether a burst is over: the queue still holds the `seen` files a window later
+fn stopped_growing(seen: usize, queued: usize) -> bool {
+    queued == seen
+}
+
+/// Hand the burst of files opened through the OS to the frontend once no more files
+/// arrive for a moment; `seen` were queued when it started
+pub fn schedule(app: &AppHandle, mut seen: usize) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(BURST_WINDOW);
+        if let Some(paths) = app.state::<OpenQueue>().settle(&mut seen) {
+            dispatch(&app, paths);
+            return;
+        }
+    });
+}
+
+/// Whether a burst of `count` files should be offered as a bulk open instead of
+/// opening each file
+pub fn is_bulk(app: &AppHandle, count: usize) -> bool {
+    over_threshold(
+        count,
+        app.state::<SettingsStore>().get().bulk_open_threshold,
+    )
+}
+
+/// Whether `count` files are more than the bulk open `threshold`
+pub fn over_threshold(count: usize, threshold: usize) -> bool {
+    count > threshold
+}
+
+/// Emit one `bulk-open-request` above the threshold, otherwise a `file-open` per file
+pub fn dispatch(app: &AppHandle, paths: Vec<PathBuf>) {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if is_bulk(app, paths.len()) {
+        emit_bulk(app, paths);
+        return;
+    }
+    for path in paths {
+        if let Err(e) = app.emit("file-open", path) {
+            log_line!("Failed to emit file-open event: {}", e);
+        }
+    }
+}
+
+pub fn emit_bulk(app: &AppHandle, paths: Vec<String>) {
+    log_line!("Bulk open request for {} files", paths.len());
+    if let Err(e) = app.emit("bulk-open-request", BulkOpenRequest { paths }) {
+        log_line!("Failed to emit bulk-open-request event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_single_file_waits_one_window() {
+        let queue = OpenQueue::default();
+        let mut seen = queue.add(vec![PathBuf::from("a.msg")]).unwrap();
+        assert_eq!(seen, 1);
+        assert_eq!(queue.settle(&mut seen), Some(vec![PathBuf::from("a.msg")]));
+    }
+
+    #[test]
+    fn bursts_are_delivered_once_the_queue_stops_growing() {
+        let queue = OpenQueue::default();
+        let path = |name: &str| PathBuf::from(name);
+        let mut seen = queue.add(vec![path("a.msg")]).unwrap();
+        // Files arriving while the flush is scheduled join the burst
+        assert_eq!(queue.add(vec![path("b.msg"), path("c.eml")]), None);
+        assert_eq!(queue.settle(&mut seen), None);
+        assert_eq!(seen, 3);
+        assert_eq!(queue.add(vec![path("d.msg")]), None);
+        assert_eq!(queue.settle(&mut seen), None);
+        assert_eq!(
+            queue.settle(&mut seen),
+            Some(vec![
+                path("a.msg"),
+                path("b.msg"),
+                path("c.eml"),
+                path("d.msg")
+            ])
+        );
+
+        // The next file starts a burst of its own
+        assert_eq!(queue.add(vec![path("e.msg")]), Some(1));
+
+        assert!(stopped_growing(2, 2));
+        assert!(!stopped_growing(1, 2));
+    }
+}
@@ -0,0 +1,790 @@
+//! Semantic structure of a message body for screen readers: headings, paragraphs, lists,
+//! tables with their header cells, links and quote levels, without the visual styling.
+//! Built from the same tree and rules as [`super::text::html_to_text`], and limited to what
+//! the webview's sanitizer lets through, so nothing it removes shows up here.
+
+use super::html::{self, Element, Node};
+use super::links::{self, Link};
+use super::text;
+use super::Message;
+use serde::Serialize;
+
+/// Elements the webview's sanitizer keeps (`ALLOWED_TAGS` in `src/js/sanitizer.js`); others
+/// are unwrapped, keeping their content, as the sanitizer does. A test keeps both in sync.
+pub(super) const ALLOWED_TAGS: &[&str] = &[
+    "p",
+    "br",
+    "div",
+    "span",
+    "b",
+    "i",
+    "u",
+    "strong",
+    "em",
+    "mark",
+    "small",
+    "del",
+    "ins",
+    "sub",
+    "sup",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "li",
+    "dl",
+    "dt",
+    "dd",
+    "table",
+    "tr",
+    "td",
+    "th",
+    "thead",
+    "tbody",
+    "tfoot",
+    "caption",
+    "colgroup",
+    "col",
+    "a",
+    "img",
+    "blockquote",
+    "pre",
+    "code",
+    "hr",
+    "address",
+    "style",
+    "font",
+    "center",
+];
+
+/// Attributes the webview's sanitizer keeps (`ALLOWED_ATTR`), besides `aria-*` ones
+pub(super) const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "class",
+    "id",
+    "style",
+    "title",
+    "href",
+    "target",
+    "rel",
+    "src",
+    "alt",
+    "width",
+    "height",
+    "colspan",
+    "rowspan",
+    "cellpadding",
+    "cellspacing",
+    "border",
+    "align",
+    "valign",
+    "bgcolor",
+    "color",
+    "face",
+    "size",
+];
+
+/// Elements removed together with their content, on top of those plain text never shows
+const DROPPED: &[&str] = &["embed", "textarea", "applet", "audio", "video", "math"];
+
+/// Link schemes the reading pane keeps (`sanitizeURL`); other links lose their target
+pub(super) const LINK_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// Browsers cap `colspan` at this, and so does the table grid here
+const MAX_COLSPAN: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Heading {
+        level: u8,
+        content: Vec<Inline>,
+    },
+    Paragraph {
+        content: Vec<Inline>,
+    },
+    List {
+        ordered: bool,
+        items: Vec<Vec<Block>>,
+    },
+    Table {
+        caption: Vec<Inline>,
+        rows: Vec<Vec<Cell>>,
+    },
+    /// Quoted text; `level` is 1 for the outermost quote
+    Quote {
+        level: usize,
+        content: Vec<Block>,
+    },
+    Preformatted {
+        text: String,
+    },
+    Separator,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Inline {
+    Text {
+        text: String,
+    },
+    /// `target` is `None` for links whose address the sanitizer would remove
+    Link {
+        content: Vec<Inline>,
+        target: Option<Link>,
+    },
+    /// `alt` is `None` for images without a description; decorative ones (`alt=""`) are left out
+    Image {
+        alt: Option<String>,
+    },
+    LineBreak,
+}
+
+/// What a header cell labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Column,
+    Row,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Cell {
+    /// Set for `th` cells: those in rows of only `th` label columns, others their row
+    pub header: Option<Scope>,
+    pub colspan: usize,
+    pub rowspan: usize,
+    /// Text of the header cells labelling this cell, column headers first
+    pub headers: Vec<String>,
+    pub content: Vec<Block>,
+}
+
+/// Structure of a message's HTML body, or of its text body when there is none
+pub fn from_message(message: &Message) -> Vec<Block> {
+    match message
+        .body_html
+        .as_deref()
+        .filter(|b| !b.trim().is_empty())
+    {
+        Some(body) => from_html(body),
+        None => from_text(message.body_text.as_deref().unwrap_or("")),
+    }
+}
+
+pub fn from_html(body: &str) -> Vec<Block> {
+    blocks(&html::parse(body), 0)
+}
+
+/// Paragraphs of a text body, with `>` quoted lines as quotes
+pub fn from_text(body: &str) -> Vec<Block> {
+    let lines: Vec<(usize, &str)> = body
+        .lines()
+        .map(|line| {
+            let mut depth = 0;
+            let mut rest = line;
+            while let Some(inner) = rest.trim_start().strip_prefix('>') {
+                depth += 1;
+                rest = inner;
+            }
+            (depth, rest.trim())
+        })
+        .collect();
+    text_blocks(&lines, 0)
+}
+
+fn text_blocks(lines: &[(usize, &str)], level: usize) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<Inline> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (depth, line) = lines[i];
+        if (depth > level || line.is_empty()) && !paragraph.is_empty() {
+            blocks.push(Block::Paragraph {
+                content: std::mem::take(&mut paragraph),
+            });
+        }
+        if depth > level {
+            let end = lines[i..]
+                .iter()
+                .position(|&(depth, _)| depth <= level)
+                .map_or(lines.len(), |n| i + n);
+            let content = text_blocks(&lines[i..end], level + 1);
+            if !content.is_empty() {
+                blocks.push(Block::Quote {
+                    level: level + 1,
+                    content,
+                });
+            }
+            i = end;
+            continue;
+        }
+        if !line.is_empty() {
+            if !paragraph.is_empty() {
+                paragraph.push(Inline::LineBreak);
+            }
+            paragraph.push(Inline::Text {
+                text: text::collapse(line),
+            });
+        }
+        i += 1;
+    }
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph { content: paragraph });
+    }
+    blocks
+}
+
+fn blocks(nodes: &[Node], quote_level: usize) -> Vec<Block> {
+    let mut builder = Builder {
+        blocks: Vec::new(),
+        inline: Vec::new(),
+        quote_level,
+    };
+    builder.nodes(nodes);
+    builder.flush();
+    builder.blocks
+}
+
+/// Removed with its content: hidden in plain text too, or not rendered by the reading pane
+fn is_dropped(element: &Element) -> bool {
+    let name = element.name.as_str();
+    text::HIDDEN.contains(&name) || DROPPED.contains(&name) || text::is_hidden(element)
+}
+
+fn is_block(name: &str) -> bool {
+    text::PARAGRAPHS.contains(&name) || text::BLOCKS.contains(&name) || name == "li"
+}
+
+/// Collects blocks, gathering inline content into paragraphs between them
+struct Builder {
+    blocks: Vec<Block>,
+    inline: Vec<Inline>,
+    quote_level: usize,
+}
+
+impl Builder {
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => push_text(&mut self.inline, text),
+                Node::Element(element) => self.element(element),
+            }
+        }
+    }
+
+    /// End the paragraph in progress
+    fn flush(&mut self) {
+        let content = tidy(std::mem::take(&mut self.inline));
+        if !content.is_empty() {
+            self.blocks.push(Block::Paragraph { content });
+        }
+    }
+
+    fn element(&mut self, element: &Element) {
+        let name = element.name.as_str();
+        if is_dropped(element) {
+            return;
+        }
+        if !ALLOWED_TAGS.contains(&name) {
+            if is_block(name) {
+                self.flush();
+                self.nodes(&element.children);
+                self.flush();
+            } else {
+                self.nodes(&element.children);
+            }
+            return;
+        }
+
+        match name {
+            "br" => self.inline.push(Inline::LineBreak),
+            "img" => self.inline.extend(image(element)),
+            "a" => self.inline.push(link(element)),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                let content = inlines(&element.children);
+                if !content.is_empty() {
+                    self.blocks.push(Block::Heading {
+                        level: name[1..].parse().unwrap_or(1),
+                        content,
+                    });
+                }
+            }
+            "ul" | "ol" => {
+                self.flush();
+                let items = list_items(element, self.quote_level);
+                if !items.is_empty() {
+                    self.blocks.push(Block::List {
+                        ordered: name == "ol",
+                        items,
+                    });
+                }
+            }
+            "blockquote" => {
+                self.flush();
+                let level = self.quote_level + 1;
+                let content = blocks(&element.children, level);
+                if !content.is_empty() {
+                    self.blocks.push(Block::Quote { level, content });
+                }
+            }
+            "pre" => {
+                self.flush();
+                let text = text::raw_text(element);
+                if !text.trim().is_empty() {
+                    self.blocks.push(Block::Preformatted { text });
+                }
+            }
+            "hr" => {
+                self.flush();
+                self.blocks.push(Block::Separator);
+            }
+            "table" => {
+                self.flush();
+                self.table(element);
+            }
+            _ if is_block(name) => {
+                self.flush();
+                self.nodes(&element.children);
+                self.flush();
+            }
+            _ => self.nodes(&element.children),
+        }
+    }
+
+    fn table(&mut self, table: &Element) {
+        let rows = text::table_rows(table);
+        let has_headers = rows.iter().flatten().any(|cell| cell.name == "th");
+        let is_layout = !has_headers
+            && (rows.len() <= 1
+                || rows.iter().all(|row| row.len() <= 1)
+                || rows.iter().flatten().any(|cell| cell.contains("table")));
+
+        // Tables used for page layout are just containers
+        if is_layout {
+            for cell in rows.iter().flatten() {
+                if !is_dropped(cell) {
+                    let content = blocks(&cell.children, self.quote_level);
+                    self.blocks.extend(content);
+                }
+            }
+            return;
+        }
+
+        let caption = table
+            .children
+            .iter()
+            .find_map(|child| match child {
+                Node::Element(caption) if caption.name == "caption" => {
+                    Some(inlines(&caption.children))
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+        let placed = place_cells(&rows);
+        let headers: Vec<(&Placed, String)> = placed
+            .iter()
+            .filter(|cell| cell.scope.is_some())
+            .map(|cell| {
+                let label = text::collapse(&text::plain_text(&cell.element.children));
+                (cell, label)
+            })
+            .collect();
+
+        let mut grid: Vec<Vec<Cell>> = rows.iter().map(|_| Vec::new()).collect();
+        for cell in &placed {
+            let labels = |scope: Scope| {
+                headers
+                    .iter()
+                    .filter(move |(header, label)| {
+                        header.scope == Some(scope)
+                            && !label.is_empty()
+                            && match scope {
+                                Scope::Column => {
+                                    header.row < cell.row && cell.overlaps_columns(header)
+                                }
+                                Scope::Row => header.col < cell.col && cell.overlaps_rows(header),
+                            }
+                    })
+                    .map(|(_, label)| label.clone())
+            };
+            let content = if is_dropped(cell.element) {
+                Vec::new()
+            } else {
+                blocks(&cell.element.children, self.quote_level)
+            };
+            grid[cell.row].push(Cell {
+                header: cell.scope,
+                colspan: cell.colspan,
+                rowspan: cell.rowspan,
+                headers: labels(Scope::Column).chain(labels(Scope::Row)).collect(),
+                content,
+            });
+        }
+        grid.retain(|row| !row.is_empty());
+        if !grid.is_empty() {
+            self.blocks.push(Block::Table {
+                caption,
+                rows: grid,
+            });
+        }
+    }
+}
+
+/// A table cell at its position in the grid, after earlier cells' row and column spans
+struct Placed<'a> {
+    element: &'a Element,
+    row: usize,
+    col: usize,
+    colspan: usize,
+    rowspan: usize,
+    scope: Option<Scope>,
+}
+
+impl Placed<'_> {
+    fn overlaps_columns(&self, other: &Placed) -> bool {
+        self.col < other.col + other.colspan && other.col < self.col + self.colspan
+    }
+
+    fn overlaps_rows(&self, other: &Placed) -> bool {
+        self.row < other.row + other.rowspan && other.row < self.row + self.rowspan
+    }
+}
+
+fn place_cells<'a>(rows: &[Vec<&'a Element>]) -> Vec<Placed<'a>> {
+    let span = |cell: &Element, name: &str, max: usize| {
+        cell.attribute(name)
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(1)
+            .clamp(1, max.max(1))
+    };
+    // Columns taken in each row by cells spanning down from rows above
+    let mut taken: Vec<Vec<bool>> = rows.iter().map(|_| Vec::new()).collect();
+    let mut placed = Vec::new();
+    for (r, row) in rows.iter().enumerate() {
+        let header_row = row.iter().all(|cell| cell.name == "th");
+        let mut col = 0;
+        for &element in row {
+            while taken[r].get(col).copied().unwrap_or(false) {
+                col += 1;
+            }
+            let colspan = span(element, "colspan", MAX_COLSPAN);
+            let rowspan = span(element, "rowspan", rows.len() - r);
+            for spanned in &mut taken[r..r + rowspan] {
+                if spanned.len() < col + colspan {
+                    spanned.resize(col + colspan, false);
+                }
+                spanned[col..col + colspan].fill(true);
+            }
+            placed.push(Placed {
+                element,
+                row: r,
+                col,
+                colspan,
+                rowspan,
+                scope: (element.name == "th").then_some(if header_row {
+                    Scope::Column
+                } else {
+                    Scope::Row
+                }),
+            });
+            col += colspan;
+        }
+    }
+    placed
+}
+
+fn list_items(list: &Element, quote_level: usize) -> Vec<Vec<Block>> {
+    let mut items = Vec::new();
+    for child in &list.children {
+        let item = match child {
+            Node::Element(item) if item.name == "li" => {
+                if is_dropped(item) {
+                    continue;
+                }
+                blocks(&item.children, quote_level)
+            }
+            // Stray content between items, such as a nested list outside any `li`
+            _ => blocks(std::slice::from_ref(child), quote_level),
+        };
+        if !item.is_empty() {
+            items.push(item);
+        }
+    }
+    items
+}
+
+/// Inline content of headings, captions and links; blocks inside them are run together
+fn inlines(nodes: &[Node]) -> Vec<Inline> {
+    let mut content = Vec::new();
+    collect_inlines(nodes, &mut content);
+    tidy(content)
+}
+
+fn collect_inlines(nodes: &[Node], content: &mut Vec<Inline>) {
+    for node in nodes {
+        let element = match node {
+            Node::Text(text) => {
+                push_text(content, text);
+                continue;
+            }
+            Node::Element(element) => element,
+        };
+        if is_dropped(element) {
+            continue;
+        }
+        match element.name.as_str() {
+            "br" => content.push(Inline::LineBreak),
+            "img" => content.extend(image(element)),
+            "a" => content.push(link(element)),
+            name if is_block(name) => {
+                push_text(content, " ");
+                collect_inlines(&element.children, content);
+                push_text(content, " ");
+            }
+            _ => collect_inlines(&element.children, content),
+        }
+    }
+}
+
+fn link(element: &Element) -> Inline {
+    let target = element
+        .attribute("href")
+        .map(str::trim)
+        .filter(|href| {
+            let lower = href.to_ascii_lowercase();
+            LINK_SCHEMES.iter().any(|scheme| lower.starts_with(scheme))
+        })
+        .map(|href| links::link(href.to_string()));
+    Inline::Link {
+        content: inlines(&element.children),
+        target,
+    }
+}
+
+fn image(element: &Element) -> Option<Inline> {
+    match element.attribute("alt").map(str::trim) {
+        Some("") => None,
+        alt => Some(Inline::Image {
+            alt: alt.map(text::collapse),
+        }),
+    }
+}
+
+/// Append text with whitespace runs collapsed, like a browser shows it
+fn push_text(content: &mut Vec<Inline>, text: &str) {
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            collapsed.push(c);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    if collapsed.is_empty() {
+        return;
+    }
+    match content.last_mut() {
+        Some(Inline::Text { text }) => {
+            if text.ends_with(' ') && collapsed.starts_with(' ') {
+                collapsed.remove(0);
+            }
+            text.push_str(&collapsed);
+        }
+        _ => content.push(Inline::Text { text: collapsed }),
+    }
+}
+
+/// Trim spaces at the ends of the content and around line breaks, and drop line breaks at
+/// the ends
+fn tidy(content: Vec<Inline>) -> Vec<Inline> {
+    let mut out: Vec<Inline> = Vec::with_capacity(content.len());
+    for inline in content {
+        match inline {
+            Inline::Text { text } => {
+                let text = match out.last() {
+                    None | Some(Inline::LineBreak) => text.trim_start().to_string(),
+                    _ => text,
+                };
+                if !text.is_empty() {
+                    out.push(Inline::Text { text });
+                }
+            }
+            Inline::LineBreak => {
+                if let Some(Inline::Text { text }) = out.last_mut() {
+                    text.truncate(text.trim_end().len());
+                }
+                out.push(Inline::LineBreak);
+            }
+            other => out.push(other),
+        }
+    }
+    if let Some(Inline::Text { text }) = out.last_mut() {
+        text.truncate(text.trim_end().len());
+    }
+    out.retain(|inline| !matches!(inline, Inline::Text { text } if text.is_empty()));
+    let start = out
+        .iter()
+        .position(|inline| !matches!(inline, Inline::LineBreak))
+        .unwrap_or(out.len());
+    let end = out
+        .iter()
+        .rposition(|inline| !matches!(inline, Inline::LineBreak))
+        .map_or(start, |i| i + 1);
+    out.drain(start..end).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn tree(html: &str) -> Value {
+        serde_json::to_value(from_html(html)).unwrap()
+    }
+
+    fn text(text: &str) -> Value {
+        json!({ "type": "text", "text": text })
+    }
+
+    #[test]
+    fn structure_of_headings_lists_links_and_quotes() {
+        let html = r#"<div style="font-family:Arial"><h2>Agenda  <span>for Monday</span></h2>
+            <p>See <a href="https://xn--bcher-kva.example/q3">the report</a> or
+            <a href="javascript:alert(1)">this</a>.<br><img src="cid:logo" alt="Logo">
+            <img src="spacer.gif" alt=""></p>
+            <ol><li>One</li><li><p>Two</p><ul><li>Nested</li></ul></li></ol>
+            <script>alert(1)</script><p style="display:none">Hidden</p>
+            <form><input value="x">Kept like the sanitizer does</form>
+            <blockquote><p>Quoted</p><blockquote>Deeper</blockquote></blockquote></div>"#;
+        let tree = tree(html);
+        let blocks = tree.as_array().unwrap();
+        assert_eq!(blocks.len(), 5, "{:#}", tree);
+
+        assert_eq!(
+            blocks[0],
+            json!({ "type": "heading", "level": 2, "content": [text("Agenda for Monday")] })
+        );
+        let paragraph = &blocks[1]["content"];
+        assert_eq!(paragraph[0], text("See "));
+        assert_eq!(paragraph[1]["content"], json!([text("the report")]));
+        assert_eq!(paragraph[1]["target"]["display_host"], "bücher.example");
+        assert_eq!(paragraph[3]["target"], Value::Null);
+        assert_eq!(paragraph[4], text("."));
+        assert_eq!(paragraph[5], json!({ "type": "line_break" }));
+        assert_eq!(paragraph[6], json!({ "type": "image", "alt": "Logo" }));
+        assert_eq!(paragraph.as_array().unwrap().len(), 7);
+
+        assert_eq!(blocks[2]["ordered"], true);
+        assert_eq!(
+            blocks[2]["items"][1][1]["items"][0][0]["content"][0],
+            text("Nested")
+        );
+        assert_eq!(
+            blocks[3]["content"],
+            json!([text("Kept like the sanitizer does")])
+        );
+        assert_eq!(blocks[4]["level"], 1);
+        assert_eq!(blocks[4]["content"][1]["level"], 2);
+        assert_eq!(
+            blocks[4]["content"][1]["content"][0]["content"],
+            json!([text("Deeper")])
+        );
+    }
+
+    #[test]
+    fn tables_associate_headers_and_layout_tables_flatten() {
+        let html = "<table><caption>Q3 <b>figures</b></caption>\
+            <thead><tr><th></th><th colspan=\"2\">Revenue</th></tr></thead>\
+            <tr><th rowspan=\"2\">North</th><td>10</td><td>12</td></tr>\
+            <tr><td>11</td><td>13</td></tr></table>";
+        let tree = tree(html);
+        let table = &tree[0];
+        assert_eq!(table["caption"], json!([text("Q3 figures")]));
+        assert_eq!(table["rows"][0][1]["header"], "column");
+        assert_eq!(table["rows"][1][0]["header"], "row");
+        assert_eq!(table["rows"][1][2]["headers"], json!(["Revenue", "North"]));
+        // The second row starts after the cell spanning down into it
+        assert_eq!(table["rows"][2][0]["headers"], json!(["Revenue", "North"]));
+        assert_eq!(
+            table["rows"][2][1]["content"][0]["content"],
+            json!([text("13")])
+        );
+
+        let layout = "<table width=\"600\"><tr><td><p>Hello</p></td><td>there</td></tr></table>\
+            <table><tr><td>Left</td></tr><tr><td>Below</td></tr></table>";
+        let paragraphs: Vec<Value> = tree_paragraphs(layout);
+        assert_eq!(
+            paragraphs,
+            ["Hello", "there", "Left", "Below"].map(|t| json!([text(t)]))
+        );
+    }
+
+    fn tree_paragraphs(html: &str) -> Vec<Value> {
+        let tree = tree(html);
+        tree.as_array()
+            .unwrap()
+            .iter()
+            .map(|block| {
+                assert_eq!(block["type"], "paragraph");
+                block["content"].clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_bodies_keep_paragraphs_and_quote_levels() {
+        let body =
+            "Hi Ann,\r\nsee below.\r\n\r\nOn Monday Bob wrote:\r\n> Figures?\r\n> > Q3 please\r\n";
+        let tree = serde_json::to_value(from_text(body)).unwrap();
+        assert_eq!(
+            tree[0]["content"],
+            json!([text("Hi Ann,"), { "type": "line_break" }, text("see below.")])
+        );
+        assert_eq!(tree[1]["content"], json!([text("On Monday Bob wrote:")]));
+        assert_eq!(tree[2]["level"], 1);
+        assert_eq!(tree[2]["content"][0]["content"], json!([text("Figures?")]));
+        assert_eq!(tree[2]["content"][1]["level"], 2);
+    }
+
+    /// The element and attribute lists mirror `SANITIZE_CONFIG`, so nothing the webview's
+    /// sanitizer removes reappears in the accessible view
+    #[test]
+    fn lists_match_the_sanitizer() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../src/js/sanitizer.js");
+        let source = std::fs::read_to_string(path).unwrap();
+        let list = |key: &str| -> Vec<String> {
+            let start = source.find(&format!("{}: [", key)).unwrap() + key.len() + 3;
+            let end = start + source[start..].find(']').unwrap();
+            source[start..end]
+                .lines()
+                .map(|line| line.split("//").next().unwrap())
+                .flat_map(|line| line.split(','))
+                .map(|item| item.trim().trim_matches('\'').to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+
+        let mut allowed = list("ALLOWED_TAGS");
+        allowed.sort();
+        let mut ours: Vec<String> = ALLOWED_TAGS.iter().map(|t| t.to_string()).collect();
+        ours.sort();
+        assert_eq!(ours, allowed);
+
+        // Attributes read here, directly or through `text::is_hidden`
+        let mut attributes = list("ALLOWED_ATTR");
+        for attribute in ["href", "alt", "colspan", "rowspan", "style"] {
+            assert!(attributes.iter().any(|a| a == attribute), "{}", attribute);
+        }
+        attributes.sort();
+        let mut ours: Vec<String> = ALLOWED_ATTRIBUTES.iter().map(|a| a.to_string()).collect();
+        ours.sort();
+        assert_eq!(ours, attributes);
+        for forbidden in list("FORBID_TAGS") {
+            assert!(!ALLOWED_TAGS.contains(&forbidden.as_str()), "{}", forbidden);
+        }
+    }
+}
@@ -0,0 +1,627 @@
+//! iCalendar objects (RFC 5545) as far as meeting requests need them: content lines with
+//! their parameters, nested components, and iTIP replies (RFC 5546) to a request
+
+use super::{Attachment, Message};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Lines are folded beyond this many octets (RFC 5545 3.1)
+const LINE_OCTETS: usize = 75;
+
+/// Identifies the app in the `PRODID` of generated objects
+pub const PRODID: &str = "-//msgReader//Calendar Reply//EN";
+
+/// A `BEGIN:`/`END:` block: `VCALENDAR`, `VEVENT`, `VTIMEZONE`, ...
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Uppercase
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub components: Vec<Component>,
+}
+
+/// One content line; the value stays escaped as it was
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    /// Uppercase
+    pub name: String,
+    /// Uppercase names, values unquoted
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+/// How the user answers a meeting request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Response {
+    Accept,
+    Decline,
+    Tentative,
+}
+
+impl Response {
+    /// The attendee's `PARTSTAT` after answering
+    pub fn partstat(self) -> &'static str {
+        match self {
+            Response::Accept => "ACCEPTED",
+            Response::Decline => "DECLINED",
+            Response::Tentative => "TENTATIVE",
+        }
+    }
+}
+
+impl Component {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            properties: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    /// The first property called `name`
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> + 'a {
+        self.properties
+            .iter()
+            .filter(move |property| property.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Child components called `name`
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Component> + 'a {
+        self.components
+            .iter()
+            .filter(move |component| component.name.eq_ignore_ascii_case(name))
+    }
+
+    fn push(&mut self, property: Property) {
+        self.properties.push(property);
+    }
+
+    /// The component as iCalendar text, with CRLF line ends and long lines folded
+    pub fn to_ics(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut String) {
+        fold_into(out, &format!("BEGIN:{}", self.name));
+        for property in &self.properties {
+            fold_into(out, &property.line());
+        }
+        for component in &self.components {
+            component.write(out);
+        }
+        fold_into(out, &format!("END:{}", self.name));
+    }
+}
+
+impl Property {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            params: Vec::new(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Parameter value, matched case-insensitively
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Set a parameter, replacing one of the same name
+    pub fn set_param(&mut self, name: &str, value: &str) {
+        self.params
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.params.push((name.to_string(), value.to_string()));
+    }
+
+    /// The address of a `mailto:` value (`ORGANIZER`, `ATTENDEE`), lowercase
+    pub fn address(&self) -> Option<String> {
+        let value = self.value.trim();
+        let scheme = value.get(..7)?;
+        scheme
+            .eq_ignore_ascii_case("mailto:")
+            .then(|| value[7..].trim().to_lowercase())
+            .filter(|address| !address.is_empty())
+    }
+
+    /// The content line, unfolded
+    fn line(&self) -> String {
+        let mut line = self.name.clone();
+        for (name, value) in &self.params {
+            if value.contains([':', ';', ',']) {
+                line.push_str(&format!(";{}=\"{}\"", name, value.replace('"', "")));
+            } else {
+                line.push_str(&format!(";{}={}", name, value));
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+        line
+    }
+}
+
+/// Join folded lines (RFC 5545 3.1): a line break followed by a space or tab continues
+/// the line
+pub fn unfold(data: &str) -> String {
+    data.replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "")
+}
+
+/// The first `VCALENDAR` object in `data`; lines that aren't content lines are skipped,
+/// components left open are closed at the end
+pub fn parse(data: &str) -> Option<Component> {
+    let mut stack: Vec<Component> = Vec::new();
+    for line in unfold(data).lines() {
+        let Some(property) = parse_line(line) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "BEGIN" => {
+                let name = property.value.trim().to_ascii_uppercase();
+                if stack.is_empty() && name != "VCALENDAR" {
+                    continue;
+                }
+                stack.push(Component::new(&name));
+            }
+            "END" if !stack.is_empty() => {
+                let done = stack.pop()?;
+                match stack.last_mut() {
+                    Some(parent) => parent.components.push(done),
+                    None => return Some(done),
+                }
+            }
+            _ => {
+                if let Some(current) = stack.last_mut() {
+                    current.push(property);
+                }
+            }
+        }
+    }
+    let mut open = stack.pop()?;
+    while let Some(mut parent) = stack.pop() {
+        parent.components.push(open);
+        open = parent;
+    }
+    Some(open)
+}
+
+/// `NAME;PARAM=value;PARAM="quoted: value":value`
+fn parse_line(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let mut colon = None;
+    let mut separators = Vec::new();
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => separators.push(i),
+            ':' if !quoted => {
+                colon = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let colon = colon?;
+    let head = &line[..colon];
+    let name_end = separators.first().copied().unwrap_or(colon);
+    let name = head[..name_end].trim().to_ascii_uppercase();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    let mut params = Vec::new();
+    let mut bounds = separators;
+    bounds.push(colon);
+    for pair in bounds.windows(2) {
+        let param = &line[pair[0] + 1..pair[1]];
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        params.push((key.trim().to_ascii_uppercase(), value.replace('"', "")));
+    }
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+/// Append `line` folded into lines of at most [`LINE_OCTETS`] octets, each ended by CRLF;
+/// continuation lines start with a space
+fn fold_into(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Escape text for a `TEXT` value (RFC 5545 3.3.11)
+pub fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Undo [`escape_text`]
+pub fn unescape_text(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// The iCalendar part of a message: an attachment declared `text/calendar` or named .ics
+pub fn find(message: &Message) -> Option<&Attachment> {
+    message.attachments.iter().find(|a| {
+        a.declared_mime
+            .as_deref()
+            .is_some_and(|mime| mime.to_ascii_lowercase().starts_with("text/calendar"))
+            || a.file_name.to_lowercase().ends_with(".ics")
+    })
+}
+
+/// The event a request is about: its master `VEVENT`, not one changing a single occurrence
+pub fn request_event(request: &Component) -> Option<&Component> {
+    let mut events = request.children("VEVENT");
+    let first = events.next()?;
+    match first.get("RECURRENCE-ID") {
+        None => Some(first),
+        Some(_) => request
+            .children("VEVENT")
+            .find(|event| event.get("RECURRENCE-ID").is_none())
+            .or(Some(first)),
+    }
+}
+
+/// The iTIP `REPLY` of `attendee` to a meeting request: the request's `UID`, `SEQUENCE`,
+/// `DTSTART` and `ORGANIZER` with the attendee's new `PARTSTAT`, and the time zones the
+/// dates refer to
+///
+/// The reply is for the whole series unless `instance` names one occurrence by its
+/// `RECURRENCE-ID` value (in the form of the series' `DTSTART`).
+pub fn reply(
+    request: &Component,
+    attendee: &str,
+    response: Response,
+    comment: Option<&str>,
+    instance: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Component, String> {
+    let method = request
+        .get("METHOD")
+        .map(|method| method.value.trim().to_ascii_uppercase());
+    if method.as_deref() != Some("REQUEST") {
+        return Err(format!(
+            "Not a meeting request (METHOD is {})",
+            method.as_deref().unwrap_or("missing")
+        ));
+    }
+    let master = request_event(request).ok_or("The meeting request has no event")?;
+    let uid = master.get("UID").ok_or("The meeting request has no UID")?;
+    let organizer = master
+        .get("ORGANIZER")
+        .ok_or("The meeting request names no organizer")?;
+    let start = master
+        .get("DTSTART")
+        .ok_or("The meeting request has no start")?;
+
+    let address = attendee.trim().to_lowercase();
+    if !address.contains('@') {
+        return Err(format!("Not an email address: {}", attendee));
+    }
+    let mut attendee = master
+        .all("ATTENDEE")
+        .find(|candidate| candidate.address().as_deref() == Some(address.as_str()))
+        .cloned()
+        .unwrap_or_else(|| Property::new("ATTENDEE", &format!("mailto:{}", address)));
+    attendee
+        .params
+        .retain(|(key, _)| !matches!(key.as_str(), "RSVP" | "PARTSTAT"));
+    attendee.set_param("PARTSTAT", response.partstat());
+
+    // The occurrence answered, as the request changed it if it did
+    let (event, recurrence) = match instance {
+        None => (master, None),
+        Some(id) => {
+            let id = id.trim();
+            if master.get("RRULE").is_none() && master.get("RDATE").is_none() {
+                return Err("The meeting doesn't repeat".to_string());
+            }
+            let all_day = start
+                .param("VALUE")
+                .is_some_and(|v| v.eq_ignore_ascii_case("DATE"))
+                || start.value.trim().len() == 8;
+            let valid = if all_day {
+                chrono::NaiveDate::parse_from_str(id, "%Y%m%d").is_ok()
+            } else {
+                chrono::NaiveDateTime::parse_from_str(id.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+                    .is_ok()
+            };
+            if !valid {
+                return Err(format!("Not an occurrence of the meeting: {}", id));
+            }
+            let mut recurrence = Property::new("RECURRENCE-ID", id);
+            recurrence.params = start.params.clone();
+            let changed = request.children("VEVENT").find(|event| {
+                event
+                    .get("RECURRENCE-ID")
+                    .is_some_and(|rid| rid.value.trim() == id)
+            });
+            (changed.unwrap_or(master), Some(recurrence))
+        }
+    };
+
+    let mut reply = Component::new("VEVENT");
+    reply.push(uid.clone());
+    if let Some(sequence) = event.get("SEQUENCE").or(master.get("SEQUENCE")) {
+        reply.push(sequence.clone());
+    }
+    reply.push(Property::new(
+        "DTSTAMP",
+        &now.format("%Y%m%dT%H%M%SZ").to_string(),
+    ));
+    match (&recurrence, event.get("DTSTART")) {
+        // An occurrence the request didn't change starts when it recurs
+        (Some(recurrence), _) if std::ptr::eq(event, master) => {
+            let mut start = start.clone();
+            start.value = recurrence.value.clone();
+            reply.push(start);
+        }
+        (_, Some(start)) => reply.push(start.clone()),
+        (_, None) => reply.push(start.clone()),
+    }
+    if recurrence.is_none() || !std::ptr::eq(event, master) {
+        if let Some(end) = event.get("DTEND").or(event.get("DURATION")) {
+            reply.push(end.clone());
+        }
+    }
+    if let Some(recurrence) = recurrence {
+        reply.push(recurrence);
+    }
+    if let Some(summary) = event.get("SUMMARY").or(master.get("SUMMARY")) {
+        reply.push(summary.clone());
+    }
+    reply.push(organizer.clone());
+    reply.push(attendee);
+    if let Some(comment) = comment.map(str::trim).filter(|comment| !comment.is_empty()) {
+        reply.push(Property::new("COMMENT", &escape_text(comment)));
+    }
+
+    let mut calendar = Component::new("VCALENDAR");
+    calendar.push(Property::new("PRODID", PRODID));
+    calendar.push(Property::new("VERSION", "2.0"));
+    if let Some(scale) = request.get("CALSCALE") {
+        calendar.push(scale.clone());
+    }
+    calendar.push(Property::new("METHOD", "REPLY"));
+    calendar
+        .components
+        .extend(request.children("VTIMEZONE").cloned());
+    calendar.components.push(reply);
+    Ok(calendar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str =
+        "BEGIN:VCALENDAR\r\nPRODID:-//Microsoft Corporation//Outlook 16.0//EN\r\n\
+        VERSION:2.0\r\nMETHOD:REQUEST\r\nBEGIN:VTIMEZONE\r\nTZID:W. Europe Standard Time\r\n\
+        BEGIN:STANDARD\r\nDTSTART:16011028T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\n\
+        END:STANDARD\r\nEND:VTIMEZONE\r\nBEGIN:VEVENT\r\n\
+        ORGANIZER;CN=\"Tom Organizer\":mailto:tom@example.com\r\n\
+        ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE;CN=\"Anna Sch\u{e4}fer\"\r\n \
+        :mailto:Anna@Example.com\r\nATTENDEE;RSVP=TRUE:mailto:bob@example.com\r\n\
+        UID:040000008200E00074C5B7101A82E0080000000040\r\nSEQUENCE:3\r\n\
+        SUMMARY:Quarterly review\\, Q3\r\nDTSTART;TZID=W. Europe Standard Time:20261014T090000\r\n\
+        DTEND;TZID=W. Europe Standard Time:20261014T100000\r\nRRULE:FREQ=WEEKLY;COUNT=4\r\n\
+        DTSTAMP:20261001T120000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    /// Check a reply against what RFC 5546 3.2.3 requires of a `REPLY`, reading it back
+    /// the way a receiving calendar would
+    fn validate_reply(ics: &str) -> Result<Component, String> {
+        if !ics.ends_with("\r\n") || ics.replace("\r\n", "").contains('\n') {
+            return Err("lines must end in CRLF".to_string());
+        }
+        if let Some(line) = ics.split("\r\n").find(|line| line.len() > LINE_OCTETS) {
+            return Err(format!("line longer than 75 octets: {}", line));
+        }
+        let calendar = parse(ics).ok_or("no VCALENDAR")?;
+        let one = |component: &Component, name: &str| match component.all(name).count() {
+            1 => Ok(()),
+            n => Err(format!("{} has {} {}, expected 1", component.name, n, name)),
+        };
+        for name in ["PRODID", "VERSION", "METHOD"] {
+            one(&calendar, name)?;
+        }
+        if calendar.get("METHOD").unwrap().value != "REPLY" {
+            return Err("METHOD must be REPLY".to_string());
+        }
+        if calendar.get("VERSION").unwrap().value != "2.0" {
+            return Err("VERSION must be 2.0".to_string());
+        }
+        let events: Vec<&Component> = calendar.children("VEVENT").collect();
+        if events.len() != 1 {
+            return Err(format!("{} VEVENTs", events.len()));
+        }
+        let event = events[0];
+        for name in ["ATTENDEE", "DTSTAMP", "ORGANIZER", "UID"] {
+            one(event, name)?;
+        }
+        let attendee = event.get("ATTENDEE").unwrap();
+        if attendee.address().is_none() {
+            return Err("ATTENDEE must be a mailto: address".to_string());
+        }
+        if attendee.param("RSVP").is_some() {
+            return Err("ATTENDEE of a reply has no RSVP".to_string());
+        }
+        match attendee.param("PARTSTAT") {
+            Some("ACCEPTED" | "DECLINED" | "TENTATIVE" | "DELEGATED") => {}
+            other => return Err(format!("PARTSTAT {:?}", other)),
+        }
+        let stamp = &event.get("DTSTAMP").unwrap().value;
+        chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%dT%H%M%SZ")
+            .map_err(|_| format!("DTSTAMP must be UTC: {}", stamp))?;
+        for name in ["SEQUENCE", "RECURRENCE-ID", "DTSTART", "DTEND", "COMMENT"] {
+            if event.all(name).count() > 1 {
+                return Err(format!("more than one {}", name));
+            }
+        }
+        // Every TZID referred to is defined
+        for property in &event.properties {
+            if let Some(tzid) = property.param("TZID") {
+                let defined = calendar
+                    .children("VTIMEZONE")
+                    .any(|zone| zone.get("TZID").is_some_and(|id| id.value == tzid));
+                if !defined {
+                    return Err(format!("TZID {} isn't defined", tzid));
+                }
+            }
+        }
+        Ok(calendar)
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-14T08:30:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn replies_to_the_series_keep_the_request_identity() {
+        let request = parse(REQUEST).unwrap();
+        let event = request_event(&request).unwrap();
+        assert_eq!(
+            unescape_text(&event.get("SUMMARY").unwrap().value),
+            "Quarterly review, Q3"
+        );
+
+        let comment = "Happy to join; I'll bring the numbers, and a long comment that has \
+                       to be folded over more than one line \u{2014} with non-ASCII text.";
+        let reply = reply(
+            &request,
+            "anna@example.com",
+            Response::Accept,
+            Some(comment),
+            None,
+            now(),
+        )
+        .unwrap();
+        let ics = reply.to_ics();
+        let parsed = validate_reply(&ics).unwrap();
+        assert_eq!(parsed, reply);
+
+        let answered = parsed.children("VEVENT").next().unwrap();
+        let value = |name: &str| answered.get(name).unwrap().value.clone();
+        assert_eq!(value("UID"), event.get("UID").unwrap().value);
+        assert_eq!(value("SEQUENCE"), "3");
+        assert_eq!(answered.get("DTSTART"), event.get("DTSTART"));
+        assert!(answered.get("RECURRENCE-ID").is_none());
+        assert_eq!(unescape_text(&value("COMMENT")), comment);
+        let attendee = answered.get("ATTENDEE").unwrap();
+        assert_eq!(attendee.value, "mailto:Anna@Example.com");
+        assert_eq!(attendee.param("CN"), Some("Anna Sch\u{e4}fer"));
+        assert_eq!(attendee.param("PARTSTAT"), Some("ACCEPTED"));
+        assert_eq!(parsed.children("VTIMEZONE").count(), 1);
+
+        // Someone the request didn't invite can still answer
+        let reply = super::reply(
+            &request,
+            "carol@example.com",
+            Response::Decline,
+            None,
+            None,
+            now(),
+        )
+        .unwrap();
+        let parsed = validate_reply(&reply.to_ics()).unwrap();
+        let attendee = parsed
+            .children("VEVENT")
+            .next()
+            .unwrap()
+            .get("ATTENDEE")
+            .unwrap();
+        assert_eq!(
+            (attendee.value.as_str(), attendee.param("PARTSTAT")),
+            ("mailto:carol@example.com", Some("DECLINED"))
+        );
+    }
+
+    #[test]
+    fn single_occurrences_and_other_methods() {
+        let request = parse(REQUEST).unwrap();
+        let reply = reply(
+            &request,
+            "bob@example.com",
+            Response::Tentative,
+            None,
+            Some("20261021T090000"),
+            now(),
+        )
+        .unwrap();
+        let parsed = validate_reply(&reply.to_ics()).unwrap();
+        let event = parsed.children("VEVENT").next().unwrap();
+        let recurrence = event.get("RECURRENCE-ID").unwrap();
+        assert_eq!(recurrence.value, "20261021T090000");
+        assert_eq!(recurrence.param("TZID"), Some("W. Europe Standard Time"));
+        assert_eq!(event.get("DTSTART").unwrap().value, "20261021T090000");
+
+        assert!(super::reply(
+            &request,
+            "bob@example.com",
+            Response::Accept,
+            None,
+            Some("next week"),
+            now()
+        )
+        .is_err());
+        let cancel = parse(&REQUEST.replace("METHOD:REQUEST", "METHOD:CANCEL")).unwrap();
+        assert!(super::reply(
+            &cancel,
+            "bob@example.com",
+            Response::Accept,
+            None,
+            None,
+            now()
+        )
+        .is_err());
+        let single = parse(&REQUEST.replace("RRULE:FREQ=WEEKLY;COUNT=4\r\n", "")).unwrap();
+        assert!(super::reply(
+            &single,
+            "bob@example.com",
+            Response::Accept,
+            None,
+            Some("20261021T090000"),
+            now()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,870 @@
+mod tables;
+
+use crate::i18n::Localized;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Windows-1252 code points for bytes 0x80..=0x9F (the range where it differs from Latin-1)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Encodings tried when the declared one is missing or doesn't fit, in order of preference
+const DETECTION_CANDIDATES: &[Encoding] = &[
+    Encoding::Utf8,
+    Encoding::Windows1252,
+    Encoding::Windows1250,
+    Encoding::Windows1251,
+    Encoding::Koi8R,
+    Encoding::ShiftJis,
+    Encoding::EucJp,
+];
+
+/// Score charged per undecodable byte sequence or control character
+const ERROR_PENALTY: i64 = 50;
+
+/// Charsets UTF-8 text is commonly misread in before being saved as UTF-8 again, in the
+/// order [`repair_mojibake`] tries them. Windows-1252 goes before Latin-1, which only
+/// differs in reading 0x80..=0x9F as control characters.
+const MOJIBAKE_CANDIDATES: &[Encoding] = &[
+    Encoding::Windows1252,
+    Encoding::Latin1,
+    Encoding::Windows1251,
+    Encoding::ShiftJis,
+];
+
+/// Character encodings the parser can decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Latin2,
+    Latin9,
+    Windows1250,
+    Windows1251,
+    Windows1252,
+    Koi8R,
+    ShiftJis,
+    EucJp,
+    Iso2022Jp,
+}
+
+impl Encoding {
+    const ALL: &'static [Self] = &[
+        Self::Utf8,
+        Self::Ascii,
+        Self::Utf16Le,
+        Self::Utf16Be,
+        Self::Latin1,
+        Self::Latin2,
+        Self::Latin9,
+        Self::Windows1250,
+        Self::Windows1251,
+        Self::Windows1252,
+        Self::Koi8R,
+        Self::ShiftJis,
+        Self::EucJp,
+        Self::Iso2022Jp,
+    ];
+
+    fn for_label(label: &str) -> Option<Self> {
+        let encoding = match normalize_label(label).as_str() {
+            "us-ascii" | "ascii" => Self::Ascii,
+            "utf-8" | "utf8" => Self::Utf8,
+            "utf-16le" | "utf-16" => Self::Utf16Le,
+            "utf-16be" => Self::Utf16Be,
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" => Self::Latin1,
+            "iso-8859-2" | "iso8859-2" | "iso_8859-2" | "latin2" | "l2" => Self::Latin2,
+            "iso-8859-15" | "iso8859-15" | "iso_8859-15" | "latin9" => Self::Latin9,
+            "windows-1250" | "cp1250" | "x-cp1250" => Self::Windows1250,
+            "windows-1251" | "cp1251" | "x-cp1251" => Self::Windows1251,
+            "windows-1252" | "cp1252" | "x-cp1252" => Self::Windows1252,
+            "koi8-r" | "koi8" => Self::Koi8R,
+            "shift_jis" | "shift-jis" | "sjis" | "x-sjis" | "windows-31j" | "cp932"
+            | "ms_kanji" => Self::ShiftJis,
+            "euc-jp" | "x-euc-jp" => Self::EucJp,
+            "iso-2022-jp" => Self::Iso2022Jp,
+            _ => return None,
+        };
+        Some(encoding)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ascii => "us-ascii",
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Latin1 => "iso-8859-1",
+            Self::Latin2 => "iso-8859-2",
+            Self::Latin9 => "iso-8859-15",
+            Self::Windows1250 => "windows-1250",
+            Self::Windows1251 => "windows-1251",
+            Self::Windows1252 => "windows-1252",
+            Self::Koi8R => "koi8-r",
+            Self::ShiftJis => "shift_jis",
+            Self::EucJp => "euc-jp",
+            Self::Iso2022Jp => "iso-2022-jp",
+        }
+    }
+
+    fn is_single_byte(self) -> bool {
+        matches!(
+            self,
+            Self::Latin1
+                | Self::Latin2
+                | Self::Latin9
+                | Self::Windows1250
+                | Self::Windows1251
+                | Self::Windows1252
+                | Self::Koi8R
+        )
+    }
+
+    /// Append the bytes of `c` to `out`; `false` if the charset has no bytes for it. Only the
+    /// [`MOJIBAKE_CANDIDATES`] can encode anything but ASCII.
+    fn encode_char(self, c: char, out: &mut Vec<u8>) -> bool {
+        if c.is_ascii() {
+            out.push(c as u8);
+            return true;
+        }
+        let byte = match self {
+            Self::Latin1 => u8::try_from(c as u32).ok(),
+            Self::Windows1252 => (0x80..=0xFF).find(|&b| windows_1252_char(b) == c),
+            Self::Windows1251 => tables::WINDOWS_1251
+                .iter()
+                .position(|&code| code as u32 == c as u32)
+                .map(|i| 0x80 + i as u8),
+            Self::ShiftJis => match encode_shift_jis(c) {
+                Some(bytes) => {
+                    out.extend(bytes);
+                    return true;
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        byte.map(|b| out.push(b)).is_some()
+    }
+
+    /// Decode `bytes`, returning the text and the number of undecodable sequences
+    fn decode(self, bytes: &[u8]) -> (String, usize) {
+        match self {
+            Self::Ascii | Self::Utf8 => decode_utf8(bytes),
+            Self::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Self::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Self::Latin1 => (bytes.iter().map(|&b| b as char).collect(), 0),
+            Self::Windows1252 => (bytes.iter().map(|&b| windows_1252_char(b)).collect(), 0),
+            Self::Latin2 => decode_single_byte(bytes, &tables::ISO_8859_2),
+            Self::Latin9 => decode_single_byte(bytes, &tables::ISO_8859_15),
+            Self::Windows1250 => decode_single_byte(bytes, &tables::WINDOWS_1250),
+            Self::Windows1251 => decode_single_byte(bytes, &tables::WINDOWS_1251),
+            Self::Koi8R => decode_single_byte(bytes, &tables::KOI8_R),
+            Self::ShiftJis => decode_shift_jis(bytes),
+            Self::EucJp => decode_euc_jp(bytes),
+            Self::Iso2022Jp => decode_iso_2022_jp(bytes),
+        }
+    }
+}
+
+/// Which encoding a body was decoded with, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct BodyEncoding {
+    /// `text` or `html`
+    pub body: &'static str,
+    /// Charset named by the message, as written
+    pub declared: Option<String>,
+    /// Charset guessed from the content, if detection ran
+    pub detected: Option<&'static str>,
+    pub used: &'static str,
+    /// Charset the text had been misread in before it was repaired (see [`repair_mojibake`])
+    pub repaired: Option<&'static str>,
+}
+
+/// Text decoded by [`decode_with_detection`] along with the encoding decision
+#[derive(Debug)]
+pub struct Decoded {
+    pub text: String,
+    pub declared: Option<String>,
+    pub detected: Option<&'static str>,
+    pub used: &'static str,
+    /// Set by the caller when [`repair_mojibake`] changed `text`
+    pub repaired: Option<&'static str>,
+}
+
+impl Decoded {
+    /// Encoding record for the given body, plus a warning if the declared charset wasn't used
+    pub fn report(&self, body: &'static str) -> (BodyEncoding, Option<Localized>) {
+        let warning = match (&self.declared, self.detected) {
+            (Some(declared), Some(_)) if self.used != declared_name(declared) => {
+                Some(Localized::new(
+                    "warning.charset_overridden",
+                    [
+                        ("body", body.to_string()),
+                        ("declared", declared.clone()),
+                        ("used", self.used.to_string()),
+                    ],
+                ))
+            }
+            (None, Some(detected)) => Some(Localized::new(
+                "warning.charset_detected",
+                [
+                    ("body", body.to_string()),
+                    ("detected", detected.to_string()),
+                ],
+            )),
+            _ => None,
+        };
+        let record = BodyEncoding {
+            body,
+            declared: self.declared.clone(),
+            detected: self.detected,
+            used: self.used,
+            repaired: self.repaired,
+        };
+        (record, warning)
+    }
+}
+
+/// Decode bytes using a MIME charset label, falling back to lossy UTF-8 for unknown labels
+pub fn decode(bytes: &[u8], label: &str) -> String {
+    Encoding::for_label(label)
+        .unwrap_or(Encoding::Utf8)
+        .decode(bytes)
+        .0
+}
+
+/// Whether `label` names a charset the parser can decode
+pub fn is_supported(label: &str) -> bool {
+    Encoding::for_label(label).is_some()
+}
+
+/// The name the parser uses for the charset `label` (`cp1252` is `windows-1252`)
+pub fn canonical_name(label: &str) -> Option<&'static str> {
+    Encoding::for_label(label).map(Encoding::name)
+}
+
+/// Names of the charsets the parser can decode, for choosing one by hand
+pub fn supported() -> Vec<&'static str> {
+    Encoding::ALL
+        .iter()
+        .map(|encoding| encoding.name())
+        .collect()
+}
+
+/// Decode a message body. Unless `trust_declared` is set, the content is checked against
+/// the declared charset and decoded with a detected one when that is missing, unknown or
+/// produces garbage (replacement or control characters).
+pub fn decode_with_detection(
+    bytes: &[u8],
+    declared: Option<&str>,
+    trust_declared: bool,
+) -> Decoded {
+    let declared_encoding = declared.and_then(Encoding::for_label);
+    let result = |text, detected, used: Encoding| Decoded {
+        text,
+        declared: declared.map(str::to_string),
+        detected,
+        used: used.name(),
+        repaired: None,
+    };
+
+    if trust_declared {
+        let encoding = declared_encoding.unwrap_or(Encoding::Utf8);
+        return result(encoding.decode(bytes).0, None, encoding);
+    }
+
+    let declared_text = match declared_encoding.map(|encoding| (encoding, encoding.decode(bytes))) {
+        Some((encoding, (text, errors))) if is_clean(encoding, bytes, &text, errors) => {
+            return result(text, None, encoding);
+        }
+        other => other,
+    };
+
+    let Some((detected, (text, errors))) = detect(bytes) else {
+        // Plain ASCII: every charset agrees
+        let encoding = declared_encoding.unwrap_or(Encoding::Ascii);
+        return result(String::from_utf8_lossy(bytes).into_owned(), None, encoding);
+    };
+
+    // A declared charset only loses against a detected one that reads better
+    match declared_text {
+        Some((encoding, (declared_text, declared_errors)))
+            if encoding == detected
+                || plausibility(&declared_text, declared_errors) >= plausibility(&text, errors) =>
+        {
+            result(declared_text, Some(detected.name()), encoding)
+        }
+        _ => result(text, Some(detected.name()), detected),
+    }
+}
+
+/// Undo UTF-8 text having been read in a legacy charset and saved as UTF-8 again ("â€™" for
+/// "’", "Ã©" for "é", "РџСЂ" for "Пр"). Returns the repaired text and the charset it had
+/// been misread in. A repair is only made when the text, written back in that charset, is
+/// valid UTF-8 (so no replacement characters appear), hides fewer UTF-8 sequences than
+/// before and reads at least as well per character.
+pub fn repair_mojibake(text: &str) -> Option<(String, &'static str)> {
+    if text.is_ascii() {
+        return None;
+    }
+    MOJIBAKE_CANDIDATES.iter().find_map(|&encoding| {
+        let mut bytes = Vec::with_capacity(text.len());
+        if !text.chars().all(|c| encoding.encode_char(c, &mut bytes)) {
+            return None;
+        }
+        let hidden = hidden_utf8_sequences(&bytes);
+        if hidden == 0 {
+            return None;
+        }
+        let repaired = String::from_utf8(bytes).ok()?;
+        let improved = hidden_utf8_sequences(&encode_lossy(encoding, &repaired)) < hidden
+            && reads_as_well(&repaired, text);
+        improved.then(|| (repaired, encoding.name()))
+    })
+}
+
+/// Decode UTF-16LE bytes as stored in MSG unicode string properties
+pub fn decode_utf16le(bytes: &[u8]) -> String {
+    decode_utf16(bytes, u16::from_le_bytes)
+        .0
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Charset label for a Windows code page (PidTagInternetCodepage, PidTagMessageCodepage)
+pub fn label_for_codepage(codepage: u32) -> Option<&'static str> {
+    match codepage {
+        65001 => Some("utf-8"),
+        1250 => Some("windows-1250"),
+        1251 => Some("windows-1251"),
+        1252 => Some("windows-1252"),
+        932 => Some("shift_jis"),
+        20932 | 51932 => Some("euc-jp"),
+        50220..=50222 => Some("iso-2022-jp"),
+        20866 => Some("koi8-r"),
+        28591 => Some("iso-8859-1"),
+        28592 => Some("iso-8859-2"),
+        28605 => Some("iso-8859-15"),
+        20127 => Some("us-ascii"),
+        1200 => Some("utf-16le"),
+        1201 => Some("utf-16be"),
+        _ => None,
+    }
+}
+
+fn normalize_label(label: &str) -> String {
+    label.trim().trim_matches('"').to_ascii_lowercase()
+}
+
+/// Canonical name for a declared label, or the label itself if it's unknown
+fn declared_name(label: &str) -> &'static str {
+    Encoding::for_label(label).map_or("", Encoding::name)
+}
+
+/// Whether decoding with the declared charset gave text without obvious damage
+fn is_clean(encoding: Encoding, bytes: &[u8], text: &str, errors: usize) -> bool {
+    if encoding == Encoding::Ascii && !bytes.is_ascii() {
+        return false;
+    }
+    // Multi-byte UTF-8 read as a single-byte charset decodes without errors, but as mojibake
+    if encoding.is_single_byte() && !bytes.is_ascii() && std::str::from_utf8(bytes).is_ok() {
+        return false;
+    }
+    // ISO-2022-JP is 7-bit, so it passes for ASCII or UTF-8 apart from its escapes
+    if encoding != Encoding::Iso2022Jp && has_iso_2022_jp_escapes(bytes) {
+        return false;
+    }
+    errors == 0 && !text.chars().any(is_c1_control)
+}
+
+/// `text` in `encoding`, with 0xFF (never part of UTF-8) for characters it can't encode
+fn encode_lossy(encoding: Encoding, text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if !encoding.encode_char(c, &mut bytes) {
+            bytes.push(0xFF);
+        }
+    }
+    bytes
+}
+
+/// Number of multi-byte UTF-8 sequences in `bytes`, the mark of UTF-8 read as another charset
+fn hidden_utf8_sequences(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let len = match bytes[i] {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 1,
+        };
+        match bytes.get(i..i + len) {
+            Some(sequence) if len > 1 && std::str::from_utf8(sequence).is_ok() => {
+                count += 1;
+                i += len;
+            }
+            _ => i += 1,
+        }
+    }
+    count
+}
+
+fn has_iso_2022_jp_escapes(bytes: &[u8]) -> bool {
+    bytes.windows(3).any(|w| matches!(w, b"\x1b$B" | b"\x1b$@"))
+}
+
+/// Most likely encoding of non-ASCII `bytes` and the text it decodes to; `None` for ASCII
+fn detect(bytes: &[u8]) -> Option<(Encoding, (String, usize))> {
+    if bytes.is_ascii() {
+        return has_iso_2022_jp_escapes(bytes)
+            .then(|| (Encoding::Iso2022Jp, Encoding::Iso2022Jp.decode(bytes)));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some((Encoding::Utf8, (text.to_string(), 0)));
+    }
+
+    let mut best: Option<(i64, Encoding, (String, usize))> = None;
+    for &encoding in DETECTION_CANDIDATES {
+        let (text, errors) = encoding.decode(bytes);
+        let score = plausibility(&text, errors);
+        if best
+            .as_ref()
+            .is_none_or(|(best_score, _, _)| score > *best_score)
+        {
+            best = Some((score, encoding, (text, errors)));
+        }
+    }
+    best.map(|(_, encoding, decoded)| (encoding, decoded))
+}
+
+/// How much `text` looks like natural language. Letters continuing a word in the same
+/// script score, symbols and script mixes inside words cost, and decoding errors cost most.
+fn plausibility(text: &str, errors: usize) -> i64 {
+    let mut score = -(errors as i64) * ERROR_PENALTY;
+    let mut prev = ' ';
+    for c in text.chars() {
+        if !c.is_ascii() {
+            score += char_score(c, prev);
+        }
+        prev = c;
+    }
+    score
+}
+
+fn char_score(c: char, prev: char) -> i64 {
+    let case = if c.is_lowercase() { 3 } else { 1 };
+    match c as u32 {
+        0xFFFD => -ERROR_PENALTY,
+        _ if c.is_control() => -ERROR_PENALTY,
+        // Hiragana, katakana and kanji; common runs in Japanese text
+        0x3040..=0x30FF => 8,
+        0x4E00..=0x9FFF => 6,
+        0x3000..=0x303F | 0xFF01..=0xFF5E => 2,
+        // Half-width katakana is rare in mail; mostly a sign of misread Latin letters
+        0xFF61..=0xFF9F => -2,
+        // Cyrillic words are spelled in Cyrillic only and are mostly lowercase
+        0x0400..=0x04FF => {
+            if is_cyrillic(prev) {
+                case + 2
+            } else if prev.is_ascii_alphabetic() {
+                -3
+            } else {
+                case
+            }
+        }
+        // Latin accented letters usually sit between plain ASCII ones
+        _ if c.is_alphabetic() && (c as u32) < 0x0250 => {
+            if prev.is_ascii_alphabetic() {
+                case + 2
+            } else if is_cyrillic(prev) {
+                -3
+            } else {
+                case - 1
+            }
+        }
+        // Inverted marks and guillemets open Spanish and French phrases
+        0x00A1 | 0x00BF | 0x00AB | 0x00BB => 2,
+        // Typographic quotes, dashes, ellipsis, euro sign
+        0x2010..=0x206F | 0x20AC => 1,
+        0x00A0 => 0,
+        // Box drawing and blocks show up when text is read in the wrong DOS/KOI8 code page
+        0x2500..=0x259F => -10,
+        _ => -2,
+    }
+}
+
+/// Whether `a` scores at least as well as `b` per non-ASCII character; mojibake has more of
+/// them, so whole-text scores can't be compared
+fn reads_as_well(a: &str, b: &str) -> bool {
+    let non_ascii = |text: &str| text.chars().filter(|c| !c.is_ascii()).count().max(1) as i64;
+    plausibility(a, 0) * non_ascii(b) >= plausibility(b, 0) * non_ascii(a)
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c as u32, 0x0400..=0x04FF)
+}
+
+fn is_c1_control(c: char) -> bool {
+    matches!(c as u32, 0x80..=0x9F)
+}
+
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> (String, usize) {
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    let errors = match std::str::from_utf8(bytes) {
+        Ok(_) => 0,
+        Err(_) => text
+            .chars()
+            .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+            .count(),
+    };
+    (text, errors)
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> (String, usize) {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    let mut errors = 0;
+    let text = char::decode_utf16(units)
+        .map(|c| {
+            c.unwrap_or_else(|_| {
+                errors += 1;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    (text, errors)
+}
+
+fn decode_single_byte(bytes: &[u8], high: &[u16; 128]) -> (String, usize) {
+    let mut errors = 0;
+    let text = bytes
+        .iter()
+        .map(|&b| {
+            if b < 0x80 {
+                return b as char;
+            }
+            let c = char::from_u32(high[(b - 0x80) as usize] as u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            if c == char::REPLACEMENT_CHARACTER {
+                errors += 1;
+            }
+            c
+        })
+        .collect();
+    (text, errors)
+}
+
+/// JIS X 0208 character at `pointer`, including the private use area Shift_JIS maps to
+fn jis0208_char(pointer: usize) -> Option<char> {
+    if (8836..=10715).contains(&pointer) {
+        return char::from_u32(0xE000 + (pointer - 8836) as u32);
+    }
+    match tables::JIS0208.get(pointer) {
+        Some(&0) | None => None,
+        Some(&code) => char::from_u32(code as u32),
+    }
+}
+
+fn half_width_katakana(byte: u8) -> char {
+    char::from_u32(0xFF61 + (byte - 0xA1) as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Shift_JIS bytes of `c`, the inverse of [`decode_shift_jis`]; characters JIS X 0208 has
+/// twice (NEC and IBM extensions) get the first pointer
+fn encode_shift_jis(c: char) -> Option<Vec<u8>> {
+    static POINTERS: OnceLock<HashMap<char, usize>> = OnceLock::new();
+    match c as u32 {
+        0x80 => return Some(vec![0x80]),
+        0xFF61..=0xFF9F => return Some(vec![(c as u32 - 0xFF61) as u8 + 0xA1]),
+        _ => {}
+    }
+    let pointers = POINTERS.get_or_init(|| {
+        let mut pointers = HashMap::new();
+        for pointer in 0..tables::JIS0208.len() {
+            if let Some(c) = jis0208_char(pointer) {
+                pointers.entry(c).or_insert(pointer);
+            }
+        }
+        pointers
+    });
+    let &pointer = pointers.get(&c)?;
+    let (lead, trail) = (pointer / 188, pointer % 188);
+    let lead = lead + if lead < 0x1F { 0x81 } else { 0xC1 };
+    let trail = trail + if trail < 0x3F { 0x40 } else { 0x41 };
+    Some(vec![lead as u8, trail as u8])
+}
+
+/// Shift_JIS as Windows writes it (code page 932), following the WHATWG decoder
+fn decode_shift_jis(bytes: &[u8]) -> (String, usize) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut errors = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        match b {
+            0x00..=0x80 => text.push(b as char),
+            0xA1..=0xDF => text.push(half_width_katakana(b)),
+            0x81..=0x9F | 0xE0..=0xFC => {
+                let Some(&trail) = bytes.get(i) else {
+                    errors += 1;
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    continue;
+                };
+                let lead_offset = if b < 0xA0 { 0x81 } else { 0xC1 };
+                let trail_offset = if trail < 0x7F { 0x40 } else { 0x41 };
+                let valid_trail = matches!(trail, 0x40..=0x7E | 0x80..=0xFC);
+                let c = valid_trail
+                    .then(|| (b - lead_offset) as usize * 188 + (trail - trail_offset) as usize)
+                    .and_then(jis0208_char);
+                match c {
+                    Some(c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                    None => {
+                        errors += 1;
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        // An ASCII byte after a bad lead starts the next character
+                        if !trail.is_ascii() {
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            _ => {
+                errors += 1;
+                text.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+    }
+    (text, errors)
+}
+
+/// EUC-JP with JIS X 0208 and half-width katakana; JIS X 0212 characters are not mapped
+fn decode_euc_jp(bytes: &[u8]) -> (String, usize) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut errors = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        let trail = bytes.get(i).copied();
+        let c = match (b, trail) {
+            (0x00..=0x7F, _) => Some(b as char),
+            (0x8E, Some(t @ 0xA1..=0xDF)) => {
+                i += 1;
+                Some(half_width_katakana(t))
+            }
+            (0x8F, Some(0xA1..=0xFE)) => {
+                i += 2;
+                None
+            }
+            (0xA1..=0xFE, Some(t @ 0xA1..=0xFE)) => {
+                i += 1;
+                jis0208_char((b - 0xA1) as usize * 94 + (t - 0xA1) as usize)
+            }
+            _ => None,
+        };
+        text.push(c.unwrap_or_else(|| {
+            errors += 1;
+            char::REPLACEMENT_CHARACTER
+        }));
+    }
+    (text, errors)
+}
+
+/// ISO-2022-JP, the 7-bit encoding most Japanese mail is sent in
+fn decode_iso_2022_jp(bytes: &[u8]) -> (String, usize) {
+    #[derive(Clone, Copy)]
+    enum Mode {
+        Ascii,
+        Roman,
+        Katakana,
+        Jis0208,
+    }
+
+    let mut text = String::with_capacity(bytes.len());
+    let mut errors = 0;
+    let mut mode = Mode::Ascii;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == 0x1B {
+            let next = match bytes.get(i + 1..i + 3) {
+                Some(b"(B") => Some(Mode::Ascii),
+                Some(b"(J") => Some(Mode::Roman),
+                Some(b"(I") => Some(Mode::Katakana),
+                Some(b"$@") | Some(b"$B") => Some(Mode::Jis0208),
+                _ => None,
+            };
+            if let Some(next) = next {
+                mode = next;
+                i += 3;
+                continue;
+            }
+        }
+        i += 1;
+
+        // Line breaks are passed through in any mode; some mailers don't switch back first
+        if matches!(b, b'\r' | b'\n') {
+            text.push(b as char);
+            continue;
+        }
+        let c = match mode {
+            Mode::Ascii => Some(b as char),
+            Mode::Roman => Some(match b {
+                0x5C => '\u{00A5}',
+                0x7E => '\u{203E}',
+                _ => b as char,
+            }),
+            Mode::Katakana => matches!(b, 0x21..=0x5F).then(|| half_width_katakana(b + 0x80)),
+            Mode::Jis0208 => match bytes.get(i) {
+                Some(&t @ 0x21..=0x7E) if matches!(b, 0x21..=0x7E) => {
+                    i += 1;
+                    jis0208_char((b - 0x21) as usize * 94 + (t - 0x21) as usize)
+                }
+                _ => None,
+            },
+        };
+        text.push(c.unwrap_or_else(|| {
+            errors += 1;
+            char::REPLACEMENT_CHARACTER
+        }));
+    }
+    (text, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_declared_charset_is_kept() {
+        let decoded = decode_with_detection(b"Gr\xfc\xdfe", Some("windows-1252"), false);
+        assert_eq!(decoded.text, "Grüße");
+        assert_eq!(decoded.used, "windows-1252");
+        assert_eq!(decoded.detected, None);
+    }
+
+    #[test]
+    fn windows_1252_declared_as_latin1() {
+        let decoded =
+            decode_with_detection(b"\x93Quoted\x94 \x96 5 \x80", Some("iso-8859-1"), false);
+        assert_eq!(decoded.text, "\u{201C}Quoted\u{201D} \u{2013} 5 \u{20AC}");
+        assert_eq!(decoded.used, "windows-1252");
+    }
+
+    #[test]
+    fn utf8_declared_as_latin1() {
+        let decoded = decode_with_detection("Résumé".as_bytes(), Some("iso-8859-1"), false);
+        assert_eq!(decoded.text, "Résumé");
+        assert_eq!(decoded.used, "utf-8");
+    }
+
+    #[test]
+    fn cyrillic_code_pages() {
+        let cp1251 = b"\xcf\xf0\xe8\xe2\xe5\xf2, \xec\xe8\xf0";
+        assert_eq!(
+            decode_with_detection(cp1251, None, false).text,
+            "Привет, мир"
+        );
+        let koi8 = b"\xf0\xd2\xc9\xd7\xc5\xd4, \xcd\xc9\xd2";
+        assert_eq!(decode_with_detection(koi8, None, false).text, "Привет, мир");
+    }
+
+    #[test]
+    fn japanese_encodings() {
+        let shift_jis = b"\x82\xa8\x90\xa2\x98\x62\x82\xc9\x82\xc8\x82\xc1\x82\xc4\x82\xa8\x82\xe8\x82\xdc\x82\xb7";
+        let euc_jp = b"\xa4\xaa\xc0\xa4\xcf\xc3\xa4\xcb\xa4\xca\xa4\xc3\xa4\xc6\xa4\xaa\xa4\xea\xa4\xde\xa4\xb9";
+        let iso_2022_jp = b"\x1b$B$*@$OC$K$J$C$F$*$j$^$9\x1b(B";
+        for (bytes, name) in [
+            (&shift_jis[..], "shift_jis"),
+            (&euc_jp[..], "euc-jp"),
+            (&iso_2022_jp[..], "iso-2022-jp"),
+        ] {
+            let decoded = decode_with_detection(bytes, None, false);
+            assert_eq!(decoded.text, "お世話になっております");
+            assert_eq!(decoded.used, name);
+        }
+    }
+
+    #[test]
+    fn trusting_declared_charset_skips_detection() {
+        let decoded = decode_with_detection(b"\x93Hi\x94", Some("iso-8859-1"), true);
+        assert_eq!(decoded.text, "\u{93}Hi\u{94}");
+        assert_eq!(decoded.detected, None);
+    }
+
+    #[test]
+    fn double_encoded_text_is_repaired() {
+        for (mojibake, text, misread_as) in [
+            (
+                "Iâ€™ll send the rÃ©sumÃ©",
+                "I’ll send the résumé",
+                "windows-1252",
+            ),
+            (
+                "l\u{e2}\u{80}\u{99}\u{c3}\u{a9}t\u{c3}\u{a9}",
+                "l’été",
+                "iso-8859-1",
+            ),
+            ("РџСЂРёРІРµС‚, РјРёСЂ", "Привет, мир", "windows-1251"),
+            ("縺薙ｓ縺ｫ縺｡縺ｯ", "こんにちは", "shift_jis"),
+        ] {
+            assert_eq!(
+                repair_mojibake(mojibake),
+                Some((text.to_string(), misread_as))
+            );
+        }
+        // Text that reads fine stays as it is
+        for text in [
+            "Schöne Grüße",
+            "café – 5 €",
+            "Привет, мир",
+            "お世話になっております",
+        ] {
+            assert_eq!(repair_mojibake(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn report_warns_about_overridden_charset() {
+        let decoded = decode_with_detection(b"caf\xe9 \x80", Some("us-ascii"), false);
+        let (record, warning) = decoded.report("text");
+        assert_eq!(record.declared.as_deref(), Some("us-ascii"));
+        assert_eq!(record.used, "windows-1252");
+        assert!(warning
+            .unwrap()
+            .to_string()
+            .contains("declared as us-ascii"));
+    }
+
+    #[test]
+    fn supported_charsets_are_listed_by_their_own_names() {
+        let names = supported();
+        assert_eq!(names.first(), Some(&"utf-8"));
+        for name in &names {
+            assert_eq!(canonical_name(name), Some(*name));
+        }
+        assert_eq!(canonical_name(" CP1252 "), Some("windows-1252"));
+        assert_eq!(canonical_name("x-mac-roman"), None);
+    }
+}
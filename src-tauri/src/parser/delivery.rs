@@ -0,0 +1,132 @@
+//! The addresses a message was actually delivered to. Mail servers record the envelope
+//! recipient in `Delivered-To` (Postfix, Gmail), `X-Original-To` (Postfix, before alias
+//! expansion), `Envelope-To` (Exim) and the `for` clause of `Received`. For aliases, lists
+//! and Bcc copies it differs from the To and Cc the sender wrote.
+
+use super::headers::address_list;
+use super::Message;
+
+/// Headers naming envelope recipients directly
+const DELIVERY_HEADERS: &[&str] = &["Delivered-To", "X-Original-To", "Envelope-To"];
+
+/// Envelope recipients in header order; values that conflict are all kept, only exact
+/// repeats are dropped
+pub fn delivered_to(headers: &[(String, String)]) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    for (name, value) in headers {
+        let found = if DELIVERY_HEADERS
+            .iter()
+            .any(|header| name.eq_ignore_ascii_case(header))
+        {
+            address_list(value)
+                .into_iter()
+                .filter_map(|address| address.email)
+                .collect()
+        } else if name.eq_ignore_ascii_case("Received") {
+            received_for(value).into_iter().collect()
+        } else {
+            Vec::new()
+        };
+        for address in found {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    addresses
+}
+
+/// Fill in `delivered_to` and `was_bcc` from the headers
+pub fn apply(message: &mut Message) {
+    message.delivered_to = delivered_to(&message.headers);
+    message.was_bcc = was_bcc(message);
+}
+
+/// None of the delivered addresses is among To and Cc: most likely a Bcc copy, though
+/// delivery through a list or an alias the sender didn't write looks the same. Unknown, so
+/// false, when only a window of the recipients was kept.
+fn was_bcc(message: &Message) -> bool {
+    let counts = &message.recipient_counts;
+    if counts.to > message.to.len() || counts.cc > message.cc.len() {
+        return false;
+    }
+    let shown: Vec<String> = message
+        .to
+        .iter()
+        .chain(&message.cc)
+        .filter_map(|address| address.email.as_deref())
+        .map(str::to_lowercase)
+        .collect();
+    !message.delivered_to.is_empty()
+        && message
+            .delivered_to
+            .iter()
+            .all(|address| !shown.contains(&address.to_lowercase()))
+}
+
+/// The address of the `for` clause of a `Received` header, such as
+/// `from a by b with ESMTPS id x for <alias@example.com> (tls); Mon, 3 Mar 2025 ...`
+fn received_for(value: &str) -> Option<String> {
+    let clauses = value.rsplit_once(';').map_or(value, |(clauses, _)| clauses);
+    let mut depth = 0usize;
+    let uncommented: String = clauses
+        .chars()
+        .filter(|&c| {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth = depth.saturating_sub(1);
+                    return false;
+                }
+                _ => {}
+            }
+            depth == 0
+        })
+        .collect();
+    let mut words = uncommented.split_whitespace();
+    words.find(|word| word.eq_ignore_ascii_case("for"))?;
+    // `for multiple recipients` and the like name no address
+    let address = words.next()?.trim_matches(['<', '>', ';']);
+    address.contains('@').then(|| address.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    #[test]
+    fn delivered_addresses_come_in_header_order() {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            include_bytes!("../../tests/fixtures/delivery/alias-bcc.eml"),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            message.delivered_to,
+            [
+                "mailbox@example.net",
+                "alias+shop@example.net",
+                "Alias+Shop@example.net",
+                "relay@example.org",
+            ]
+        );
+        assert!(message.was_bcc);
+
+        let mut message = Message::new(MessageFormat::Eml);
+        message.headers = vec![("Delivered-To".to_string(), "me@example.com".to_string())];
+        message.to = address_list("Me <ME@example.com>");
+        apply(&mut message);
+        assert_eq!(message.delivered_to, ["me@example.com"]);
+        assert!(!message.was_bcc);
+        // Nothing known about delivery is no Bcc
+        message.headers.clear();
+        apply(&mut message);
+        assert!(message.delivered_to.is_empty() && !message.was_bcc);
+        assert_eq!(
+            received_for("from a by b id 1 for multiple recipients; Mon, 3 Mar 2025"),
+            None
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use super::headers::{self, Quirk};
+use super::mime::{self, Part};
+use super::recipients::{self, RecipientType};
+use super::{Attachment, Importance, Message, MessageFormat, ParseOptions, Sensitivity};
+use crate::i18n::Localized;
+
+/// Parse an RFC 5322 message with its MIME structure
+pub fn parse(data: &[u8], options: &ParseOptions) -> Message {
+    let root = mime::parse(data);
+    let mut message = Message::new(MessageFormat::Eml);
+
+    let h = &root.headers;
+    message.subject = h.get("Subject").map(headers::decode_encoded_words);
+    message.from = h
+        .get("From")
+        .and_then(|value| headers::address_list(value).into_iter().next());
+    for (kind, name) in [
+        (RecipientType::To, "To"),
+        (RecipientType::Cc, "Cc"),
+        (RecipientType::Bcc, "Bcc"),
+    ] {
+        let addresses = h.get(name).map(headers::address_list).unwrap_or_default();
+        recipients::keep(&mut message, kind, addresses, options.recipient_window);
+    }
+    message.message_id = h.get("Message-ID").map(|id| id.trim().to_string());
+    message.importance = ["Importance", "X-Priority", "Priority"]
+        .iter()
+        .find_map(|name| h.get(name).and_then(Importance::from_header));
+    message.sensitivity = h.get("Sensitivity").and_then(Sensitivity::from_header);
+    if let Some(date) = h.get("Date") {
+        message.date = headers::parse_date(date);
+        if message.date.is_none() {
+            message.warnings.push(Localized::new(
+                "warning.unparseable_date",
+                [("date", date.to_string())],
+            ));
+        }
+    }
+    message.headers = root.headers.0.clone();
+
+    let mut inline = Vec::new();
+    collect(&root, &mut message, &mut inline, options);
+    let mut quirks = Vec::new();
+    collect_quirks(&root, &mut quirks);
+    message
+        .warnings
+        .extend(quirks.into_iter().map(quirk_warning));
+
+    // Outlook hides inline parts the HTML body displays; unreferenced ones stay listed
+    message.mark_referenced_attachments();
+    for (attachment, inline) in message.attachments.iter_mut().zip(inline) {
+        attachment.hidden = inline && attachment.referenced_in_body;
+    }
+    message
+}
+
+/// Walk the MIME tree, picking the first text and HTML bodies and collecting everything else;
+/// `inline` records for each attachment whether it wasn't marked `Content-Disposition: attachment`
+fn collect(part: &Part, message: &mut Message, inline: &mut Vec<bool>, options: &ParseOptions) {
+    if part.content_type.is_multipart() {
+        if part.children.is_empty() {
+            message.warnings.push(Localized::new(
+                "warning.malformed_multipart",
+                [("mime", part.content_type.mime.clone())],
+            ));
+        }
+        for child in &part.children {
+            collect(child, message, inline, options);
+        }
+        return;
+    }
+
+    let is_attachment = part.disposition().is_some_and(|d| d.mime == "attachment");
+    match role(
+        part,
+        message.body_text.is_some(),
+        message.body_html.is_some(),
+    ) {
+        Role::TextBody => {
+            let text = decode_text(part, "text", message, options);
+            message.body_text = Some(text);
+            return;
+        }
+        Role::HtmlBody => {
+            let html = decode_text(part, "html", message, options);
+            message.body_html = Some(html);
+            return;
+        }
+        Role::TextContinuation => {
+            let text = decode_text(part, "text", message, options);
+            if let Some(body) = &mut message.body_text {
+                body.push('\n');
+                body.push_str(&text);
+            }
+            return;
+        }
+        Role::Attachment => {}
+    }
+
+    let file_name = part.file_name().or_else(|| {
+        // Attached messages are usually unnamed; use their subject
+        (part.content_type.mime == "message/rfc822").then(|| {
+            let (block, _) = headers::split_header_block(&part.body);
+            let subject = headers::parse(block)
+                .get("Subject")
+                .map(headers::decode_encoded_words)
+                .unwrap_or_default();
+            format!("{}.eml", subject.trim())
+        })
+    });
+
+    let index = message.attachments.len();
+    inline.push(!is_attachment);
+    message.attachments.push(Attachment::new(
+        index,
+        file_name.filter(|name| name != ".eml"),
+        Some(part.content_type.mime.clone()),
+        part.content_id(),
+        part.body.clone(),
+    ));
+}
+
+/// The quirks of `part` and its children, each once
+fn collect_quirks(part: &Part, quirks: &mut Vec<Quirk>) {
+    for quirk in &part.quirks {
+        if !quirks.contains(quirk) {
+            quirks.push(quirk.clone());
+        }
+    }
+    for child in &part.children {
+        collect_quirks(child, quirks);
+    }
+}
+
+fn quirk_warning(quirk: Quirk) -> Localized {
+    match quirk {
+        Quirk::MissingSeparator => Localized::new("warning.header_separator_missing", []),
+        Quirk::BareCarriageReturns => Localized::new("warning.header_bare_cr", []),
+        Quirk::FieldTruncated(name) => {
+            Localized::new("warning.header_field_truncated", [("name", name)])
+        }
+        Quirk::HeaderTruncated => Localized::new("warning.header_truncated", []),
+        Quirk::DuplicateField(name) => Localized::new("warning.duplicate_header", [("name", name)]),
+    }
+}
+
+/// What `collect` makes of a leaf part, given whether text and HTML bodies were found
+/// before it in document order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Role {
+    /// The first text/plain part that isn't an attachment
+    TextBody,
+    /// The first text/html part that isn't an attachment
+    HtmlBody,
+    /// An unnamed text/plain part after the text body (a footer added by a list server),
+    /// appended to it
+    TextContinuation,
+    Attachment,
+}
+
+pub(super) fn role(part: &Part, has_text: bool, has_html: bool) -> Role {
+    if part.disposition().is_some_and(|d| d.mime == "attachment") {
+        return Role::Attachment;
+    }
+    match part.content_type.mime.as_str() {
+        "text/plain" if !has_text => Role::TextBody,
+        "text/html" if !has_html => Role::HtmlBody,
+        "text/plain" if part.file_name().is_none() => Role::TextContinuation,
+        _ => Role::Attachment,
+    }
+}
+
+/// Decode a body part using its declared charset, or a detected one if that doesn't fit
+fn decode_text(
+    part: &Part,
+    body: &'static str,
+    message: &mut Message,
+    options: &ParseOptions,
+) -> String {
+    let declared = part.content_type.param("charset");
+    let decoded = options.decode_body(&part.body, declared);
+    message.record_encoding(body, decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MISLABELED_1252: &[u8] =
+        include_bytes!("../../tests/fixtures/charset/mislabeled-windows-1252.eml");
+    const UNDECLARED_SHIFT_JIS: &[u8] =
+        include_bytes!("../../tests/fixtures/charset/undeclared-shift-jis.eml");
+
+    fn parse_fixture(name: &str) -> Message {
+        let path = format!(
+            "{}/tests/fixtures/headers/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        parse(&std::fs::read(path).unwrap(), &ParseOptions::default())
+    }
+
+    fn warning_codes(message: &Message) -> Vec<&'static str> {
+        message.warnings.iter().map(|w| w.code).collect()
+    }
+
+    #[test]
+    fn mislabeled_windows_1252_body() {
+        let message = parse(MISLABELED_1252, &ParseOptions::default());
+        let body = message.body_text.unwrap();
+        assert!(body.contains("die Besprechung für das Quartalsergebnis"));
+        assert!(body.contains("mit – die Summe liegt bei 1.200 €."));
+        assert!(body.contains("Schöne Grüße"));
+
+        let encoding = &message.body_encodings[0];
+        assert_eq!(encoding.declared.as_deref(), Some("us-ascii"));
+        assert_eq!(encoding.detected, Some("windows-1252"));
+        assert_eq!(encoding.used, "windows-1252");
+        assert!(message
+            .warnings
+            .iter()
+            .any(|w| w.to_string().contains("windows-1252")));
+    }
+
+    #[test]
+    fn double_encoded_bodies_are_repaired() {
+        for (fixture, expected, misread_as) in [
+            (
+                &include_bytes!("../../tests/fixtures/charset/mojibake-windows-1252.eml")[..],
+                "I’ll send the résumé of Thursday’s workshop – the café near the station was \
+                 perfect.\r\n“See you next week”, as Renée said.",
+                "windows-1252",
+            ),
+            (
+                &include_bytes!("../../tests/fixtures/charset/mojibake-latin1.eml")[..],
+                "Le budget prévu pour l’été est de 1 200 € – à valider avant vendredi.",
+                "iso-8859-1",
+            ),
+        ] {
+            let message = parse(fixture, &ParseOptions::default());
+            assert!(message.body_text.as_deref().unwrap().contains(expected));
+            assert!(message.body_repaired);
+            assert_eq!(message.body_encodings[0].repaired, Some(misread_as));
+            assert_eq!(warning_codes(&message), ["warning.body_repaired"]);
+
+            let options = ParseOptions {
+                keep_mojibake: true,
+                ..ParseOptions::default()
+            };
+            let kept = parse(fixture, &options);
+            assert!(!kept.body_repaired && kept.warnings.is_empty());
+            assert!(kept.body_text.unwrap().contains("Ã©"));
+        }
+    }
+
+    #[test]
+    fn undeclared_shift_jis_body() {
+        let message = parse(UNDECLARED_SHIFT_JIS, &ParseOptions::default());
+        assert_eq!(message.subject.as_deref(), Some("会議案内"));
+        let body = message.body_text.unwrap();
+        assert!(body.contains("お世話になっております。田中です。"));
+        assert!(body.contains("来週の会議の資料を添付いたしますので、ご確認ください。"));
+
+        let encoding = &message.body_encodings[0];
+        assert_eq!(encoding.declared, None);
+        assert_eq!(encoding.used, "shift_jis");
+    }
+
+    #[test]
+    fn header_quirks_are_tolerated_and_reported() {
+        let long = parse_fixture("long-references.eml");
+        let references = long
+            .headers
+            .iter()
+            .find(|(name, _)| name == "References")
+            .unwrap();
+        assert_eq!(references.1.len(), 64 * 1024);
+        assert_eq!(
+            long.body_text.as_deref(),
+            Some("Agreed, let's ship on Friday.\r\n")
+        );
+        assert_eq!(warning_codes(&long), ["warning.header_field_truncated"]);
+
+        let bare_cr = parse_fixture("bare-cr.eml");
+        assert_eq!(bare_cr.subject.as_deref(), Some("Bare CR"));
+        assert!(bare_cr
+            .body_text
+            .as_deref()
+            .unwrap()
+            .starts_with("Lines end in CR only."));
+        assert_eq!(warning_codes(&bare_cr), ["warning.header_bare_cr"]);
+
+        let duplicate = parse_fixture("duplicate-content-type.eml");
+        assert!(duplicate.body_html.is_none());
+        assert!(duplicate.body_text.is_some());
+        assert_eq!(warning_codes(&duplicate), ["warning.duplicate_header"]);
+
+        let missing = parse_fixture("missing-separator.eml");
+        assert_eq!(missing.subject.as_deref(), Some("No blank line"));
+        assert!(missing
+            .body_text
+            .as_deref()
+            .unwrap()
+            .starts_with("Hello Ann,"));
+        assert_eq!(
+            warning_codes(&missing),
+            ["warning.header_separator_missing"]
+        );
+
+        // Fields after the blank line stay body text
+        let smuggled = parse_fixture("headers-after-separator.eml");
+        assert!(smuggled.body_html.is_none());
+        assert!(!smuggled.headers.iter().any(|(name, _)| name == "Bcc"));
+        assert!(smuggled
+            .body_text
+            .unwrap()
+            .starts_with("Content-Type: text/html"));
+        assert!(smuggled.warnings.is_empty());
+    }
+
+    /// A message with an inline image, `wrap` levels of attached messages around it
+    fn nested_message(wrap: usize) -> Vec<u8> {
+        let mut data = b"Subject: Inner\r\n\
+Content-Type: multipart/related; boundary=\"rel\"\r\n\r\n\
+--rel\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo@inner\">\r\n\
+--rel\r\nContent-Type: image/png\r\nContent-ID: <logo@inner>\r\n\r\n\x89PNG\r\n\
+--rel--\r\n"
+            .to_vec();
+        for level in 0..wrap {
+            let mut outer = format!(
+                "Subject: Level {}\r\n\
+Content-Type: multipart/mixed; boundary=\"b{}\"\r\n\r\n\
+--b{}\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo@outer\">\r\n\
+--b{}\r\nContent-Type: message/rfc822\r\n\r\n",
+                level, level, level, level
+            )
+            .into_bytes();
+            outer.extend(data);
+            outer.extend(format!("\r\n--b{}--\r\n", level).into_bytes());
+            data = outer;
+        }
+        data
+    }
+
+    #[test]
+    fn attached_messages_are_parsed_per_level() {
+        let data = nested_message(2);
+        let message =
+            super::super::parse_bytes(MessageFormat::Eml, &data, &ParseOptions::default()).unwrap();
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].file_name, "Level 0.eml");
+
+        let inner = message.clone().into_nested(&[0, 0]).unwrap();
+        assert_eq!(inner.subject.as_deref(), Some("Inner"));
+        let logo = &inner.attachments[0];
+        assert_eq!(logo.content_id.as_deref(), Some("logo@inner"));
+        assert!(logo.referenced_in_body && logo.hidden);
+
+        // The image stays with the inner message
+        let middle = message.clone().into_nested(&[0]).unwrap();
+        assert_eq!(middle.attachments.len(), 1);
+        assert!(middle.attachments[0].nested.is_some());
+        assert!(message.into_nested(&[0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn nesting_depth_is_capped() {
+        use super::super::MAX_NESTING_DEPTH;
+
+        let data = nested_message(MAX_NESTING_DEPTH + 2);
+        let mut message =
+            super::super::parse_bytes(MessageFormat::Eml, &data, &ParseOptions::default()).unwrap();
+        let mut depth = 0;
+        while let Some(nested) = message
+            .attachments
+            .first_mut()
+            .and_then(|a| a.nested.take())
+        {
+            message = *nested;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_NESTING_DEPTH - 1);
+        assert!(message
+            .warnings
+            .iter()
+            .any(|w| w.code == "warning.nested_too_deep"));
+        assert!(message.attachments[0].nested.is_none());
+    }
+
+    #[test]
+    fn trusted_declared_charset_is_not_second_guessed() {
+        let options = ParseOptions {
+            trust_declared_charset: true,
+            ..ParseOptions::default()
+        };
+        let message = parse(MISLABELED_1252, &options);
+        assert!(!message.body_text.unwrap().contains("Grüße"));
+        assert_eq!(message.body_encodings[0].used, "us-ascii");
+        assert!(message.warnings.is_empty());
+    }
+}
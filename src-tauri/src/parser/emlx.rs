@@ -0,0 +1,91 @@
+//! Apple Mail's .emlx files: the length of the message in bytes on the first line, the
+//! message itself, then an XML property list with Mail's flags. The frame is only taken
+//! off files with the .emlx extension (`MessageFormat::Emlx`), never guessed from the
+//! content: an .eml can start with a line of digits. Everything else reads them as .eml
+//! files.
+
+use super::labels::SourceFlags;
+
+/// Bits of the plist's `flags` integer
+const READ: u64 = 1 << 0;
+const ANSWERED: u64 = 1 << 2;
+const FLAGGED: u64 = 1 << 4;
+const DRAFT: u64 = 1 << 6;
+
+/// The message and the property list of .emlx data, or `None` for other data. A message
+/// cut short (a peek at the start of a file) is returned as far as it goes.
+pub fn split(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let line_end = data.iter().take(32).position(|&b| b == b'\n')?;
+    let count = std::str::from_utf8(&data[..line_end]).ok()?.trim();
+    if count.is_empty() || !count.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let length: usize = count.parse().ok()?;
+    let rest = &data[line_end + 1..];
+    Some(rest.split_at(length.min(rest.len())))
+}
+
+/// Mail's flags from the property list, `None` when it has none
+pub fn flags(plist: &[u8]) -> Option<SourceFlags> {
+    let plist = String::from_utf8_lossy(plist);
+    let after_key = plist.split_once("<key>flags</key>")?.1.trim_start();
+    let value = after_key
+        .strip_prefix("<integer>")?
+        .split_once("</integer>")?
+        .0;
+    let bits: u64 = value.trim().parse().ok()?;
+    Some(SourceFlags {
+        read: bits & READ != 0,
+        flagged: bits & FLAGGED != 0,
+        answered: bits & ANSWERED != 0,
+        draft: bits & DRAFT != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, parse_file, MessageFormat, ParseOptions};
+    use std::path::Path;
+
+    #[test]
+    fn emlx_files_read_as_eml_with_their_flags() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/labels/flagged.emlx");
+        let message = parse_file(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Flight details"));
+        assert_eq!(
+            message.body_text.as_deref().map(str::trim),
+            Some("Gate B12.")
+        );
+        assert_eq!(
+            message.source_flags,
+            Some(SourceFlags {
+                read: true,
+                flagged: true,
+                answered: true,
+                draft: false,
+            })
+        );
+        assert!(split(b"From: a@example.com\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn eml_files_starting_with_digits_are_not_unframed() {
+        let harness = crate::state::Harness::new("emlx-digits", &[]);
+        let data =
+            b"20\r\nFrom: ann@example.com\r\nSubject: Digits first\r\n\r\nThe whole body.\r\n";
+        let path = harness.path("digits.eml");
+        std::fs::write(&path, data).unwrap();
+        let message = parse_file(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Digits first"));
+        assert_eq!(
+            message.body_text.as_deref().map(str::trim),
+            Some("The whole body.")
+        );
+
+        // The same bytes from an .emlx are cut at the count
+        let framed = parse_bytes(MessageFormat::Emlx, data, &ParseOptions::default()).unwrap();
+        assert_eq!(framed.subject, None);
+        assert!(!framed.body_text.unwrap_or_default().contains("whole body"));
+    }
+}
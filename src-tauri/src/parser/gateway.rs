@@ -0,0 +1,452 @@
+//! Verdicts mail gateways write into the headers of messages they pass: SpamAssassin's
+//! `X-Spam-Status`, Microsoft 365's `X-Forefront-Antispam-Report` and
+//! `X-Microsoft-Antispam` (with the SCL and BCL levels), Proofpoint's
+//! `X-Proofpoint-Spam-Details` and Mimecast's `X-Mimecast-*` headers. Other headers that
+//! look like a spam verdict are listed as they are, with `vendor: "unknown"`. They come
+//! from a server along the way, so like Authentication-Results they can be forged by
+//! anyone the message passed before it.
+
+use super::Message;
+use crate::i18n::Localized;
+use serde::Serialize;
+
+/// Headers generic to several filters (SpamAssassin, rspamd, ...); a `X-Spam-Status`
+/// already says what they do
+const GENERIC_SPAM_HEADERS: &[&str] = &["X-Spam-Flag", "X-Spam-Score", "X-Spam-Level"];
+
+/// Microsoft headers with encoded data rather than a verdict
+const OPAQUE_HEADERS: &[&str] = &[
+    "X-Microsoft-Antispam-Message-Info",
+    "X-MS-Exchange-AntiSpam-MessageData",
+    "X-MS-Exchange-AntiSpam-MessageData-ChunkCount",
+    "X-MS-Exchange-AntiSpam-MessageData-0",
+    "X-MS-Exchange-AntiSpam-MessageData-Original-0",
+    "X-MS-Exchange-AntiSpam-MessageData-Original-ChunkCount",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Clean,
+    /// Let through by an allow list or a trusted path without filtering
+    Allowed,
+    Bulk,
+    Suspicious,
+    Spam,
+    Phishing,
+    Malware,
+    /// The header names no verdict, or one this parser doesn't know
+    Unknown,
+}
+
+/// A numeric level a vendor defines, such as Microsoft's SCL and BCL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerdictLevel {
+    pub name: &'static str,
+    pub value: i32,
+    pub meaning: Localized,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GatewayVerdict {
+    /// `spamassassin`, `microsoft`, `proofpoint`, `mimecast` or `unknown`
+    pub vendor: &'static str,
+    /// The header the verdict was read from
+    pub header: String,
+    pub verdict: Verdict,
+    /// The vendor's own score; scales differ between vendors
+    pub score: Option<f64>,
+    /// Rules hit, categories and reasons as the vendor names them
+    pub categories: Vec<String>,
+    pub levels: Vec<VerdictLevel>,
+    pub raw: String,
+}
+
+impl GatewayVerdict {
+    fn new(vendor: &'static str, header: &str, raw: &str) -> Self {
+        Self {
+            vendor,
+            header: header.to_string(),
+            verdict: Verdict::Unknown,
+            score: None,
+            categories: Vec::new(),
+            levels: Vec::new(),
+            raw: raw.to_string(),
+        }
+    }
+}
+
+/// The verdicts in `headers`, in header order
+pub fn verdicts(headers: &[(String, String)]) -> Vec<GatewayVerdict> {
+    let has_status = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("X-Spam-Status"));
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = value.trim();
+            let lower = name.to_ascii_lowercase();
+            match lower.as_str() {
+                "x-spam-status" => Some(spam_status(name, value)),
+                "x-forefront-antispam-report" => Some(forefront(name, value)),
+                "x-microsoft-antispam" => Some(microsoft_antispam(name, value)),
+                "x-ms-exchange-organization-scl" => Some(exchange_scl(name, value)),
+                "x-microsoft-antispam-mailbox-delivery" => Some(mailbox_delivery(name, value)),
+                "x-proofpoint-spam-details" => Some(proofpoint(name, value)),
+                _ if lower.starts_with("x-mimecast-") => mimecast(name, value),
+                _ if has_status
+                    && GENERIC_SPAM_HEADERS
+                        .iter()
+                        .any(|header| name.eq_ignore_ascii_case(header)) =>
+                {
+                    None
+                }
+                _ if OPAQUE_HEADERS
+                    .iter()
+                    .any(|header| name.eq_ignore_ascii_case(header)) =>
+                {
+                    None
+                }
+                _ if lower.starts_with("x-") && lower.contains("spam") => {
+                    Some(unknown(name, value))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Fill in `gateway_verdicts` from the headers
+pub fn apply(message: &mut Message) {
+    message.gateway_verdicts = verdicts(&message.headers);
+}
+
+/// `Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE autolearn=no version=3.4.6`
+fn spam_status(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("spamassassin", name, value);
+    let (flag, rest) = value.split_once([',', ' ']).unwrap_or((value, ""));
+    verdict.verdict = match flag.trim().to_ascii_lowercase().as_str() {
+        "yes" => Verdict::Spam,
+        "no" => Verdict::Clean,
+        _ => Verdict::Unknown,
+    };
+    // Long test lists are folded across lines, with the folds at commas
+    let rest = rest.replace(",\t", ",").replace(", ", ",");
+    for (key, value) in rest
+        .split_whitespace()
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "score" | "hits" => verdict.score = value.parse().ok(),
+            "tests" => verdict.categories = list(value, ','),
+            _ => {}
+        }
+    }
+    verdict
+}
+
+/// `CIP:1.2.3.4;CTRY:US;LANG:en;SCL:1;SRV:;IPV:NLI;SFV:NSPM;H:mail.example.com;CAT:NONE;...`
+fn forefront(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("microsoft", name, value);
+    let fields = fields(value);
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+    let scl = field("SCL").and_then(|value| value.parse().ok());
+    verdict.levels.extend(scl.map(scl_level));
+    verdict.levels.extend(
+        field("BCL")
+            .and_then(|value| value.parse().ok())
+            .map(bcl_level),
+    );
+    let category = field("CAT");
+    let filter = field("SFV");
+    verdict.categories = [category, filter]
+        .into_iter()
+        .flatten()
+        .filter(|value| !value.eq_ignore_ascii_case("NONE"))
+        .map(str::to_string)
+        .collect();
+    verdict.verdict = category
+        .and_then(category_verdict)
+        .or_else(|| filter.and_then(filter_verdict))
+        .or_else(|| scl.map(scl_verdict))
+        .unwrap_or(Verdict::Unknown);
+    verdict
+}
+
+/// `BCL:0;` (with more fields in newer messages: `ARA:...;BCL:7;`)
+fn microsoft_antispam(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("microsoft", name, value);
+    if let Some(bcl) = fields(value)
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("BCL"))
+        .and_then(|(_, value)| value.parse().ok())
+    {
+        verdict.levels.push(bcl_level(bcl));
+        verdict.verdict = if bcl >= 4 {
+            Verdict::Bulk
+        } else {
+            Verdict::Unknown
+        };
+    }
+    verdict
+}
+
+/// `5`: Exchange's SCL without the rest of the report
+fn exchange_scl(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("microsoft", name, value);
+    if let Ok(scl) = value.parse() {
+        verdict.levels.push(scl_level(scl));
+        verdict.verdict = scl_verdict(scl);
+    }
+    verdict
+}
+
+/// `ucf:0;jmr:0;auth:0;dest:J;ENG:(...)`: `dest` is the folder it was delivered to
+fn mailbox_delivery(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("microsoft", name, value);
+    let dest = fields(value)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("dest"))
+        .map(|(_, value)| value);
+    verdict.verdict = match dest.as_deref() {
+        Some("J") => Verdict::Spam,
+        Some("I") => Verdict::Clean,
+        _ => Verdict::Unknown,
+    };
+    verdict
+        .categories
+        .extend(dest.map(|dest| format!("dest:{}", dest)));
+    verdict
+}
+
+/// `rule=notspam policy=default score=0 ... phishscore=0 bulkscore=0 spamscore=0 ...`
+fn proofpoint(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("proofpoint", name, value);
+    let mut rule = None;
+    for (key, value) in value
+        .split_whitespace()
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "rule" => rule = Some(value.to_ascii_lowercase()),
+            "score" => verdict.score = value.parse().ok(),
+            // The per-category scores worth naming
+            "spamscore" | "phishscore" | "bulkscore" | "malwarescore" | "suspectscore"
+            | "impostorscore" | "adultscore"
+                if value.parse::<f64>().is_ok_and(|score| score > 0.0) =>
+            {
+                verdict.categories.push(format!("{}={}", key, value));
+            }
+            _ => {}
+        }
+    }
+    verdict.verdict = match rule.as_deref() {
+        Some("notspam") | Some("ok") => Verdict::Clean,
+        Some("safe") | Some("orgsafe") => Verdict::Allowed,
+        Some("bulk") => Verdict::Bulk,
+        Some(rule) if rule.contains("phish") => Verdict::Phishing,
+        Some(rule) if rule.contains("malware") || rule.contains("virus") => Verdict::Malware,
+        Some(rule) if rule.contains("spam") => Verdict::Spam,
+        Some(rule) if rule.contains("suspect") => Verdict::Suspicious,
+        _ => Verdict::Unknown,
+    };
+    verdict
+}
+
+/// The Mimecast headers carrying a verdict; the others (`X-Mimecast-MFC-AGG-ID`, ...) are
+/// routing data
+fn mimecast(name: &str, value: &str) -> Option<GatewayVerdict> {
+    let mut verdict = GatewayVerdict::new("mimecast", name, value);
+    match name.to_ascii_lowercase().as_str() {
+        "x-mimecast-spam-score" => verdict.score = value.parse().ok(),
+        "x-mimecast-spam-signature" | "x-mimecast-bulk-signature" => {
+            let bulk = name.to_ascii_lowercase().contains("bulk");
+            verdict.verdict = match value.to_ascii_lowercase().as_str() {
+                "yes" | "bulk" => Verdict::Bulk,
+                "no" | "" => Verdict::Unknown,
+                _ if bulk => Verdict::Bulk,
+                _ => Verdict::Spam,
+            };
+            if !value.eq_ignore_ascii_case("no") && !value.eq_ignore_ascii_case("yes") {
+                verdict.categories.extend(list(value, ','));
+            }
+        }
+        // `Policy=Default;Similar Internal Domain=false;Newly Observed Domain=true;...`
+        "x-mimecast-impersonation-protect" => {
+            verdict.categories = fields_with(value, '=')
+                .into_iter()
+                .filter(|(_, value)| value.eq_ignore_ascii_case("true"))
+                .map(|(name, _)| name)
+                .collect();
+            verdict.verdict = match verdict.categories.is_empty() {
+                true => Verdict::Clean,
+                false => Verdict::Suspicious,
+            };
+        }
+        _ => return None,
+    }
+    Some(verdict)
+}
+
+/// A spam-looking header of a filter this parser doesn't know; a leading number is taken
+/// as the score, a yes or no as the verdict
+fn unknown(name: &str, value: &str) -> GatewayVerdict {
+    let mut verdict = GatewayVerdict::new("unknown", name, value);
+    let first = value
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+    verdict.score = first.parse().ok();
+    verdict.verdict = match first.to_ascii_lowercase().as_str() {
+        "yes" | "true" | "spam" => Verdict::Spam,
+        "no" | "false" => Verdict::Clean,
+        _ => Verdict::Unknown,
+    };
+    verdict
+}
+
+/// `KEY:value;KEY:value` pairs
+fn fields(value: &str) -> Vec<(String, String)> {
+    fields_with(value, ':')
+}
+
+fn fields_with(value: &str, separator: char) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|field| field.split_once(separator))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn list(value: &str, separator: char) -> Vec<String> {
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty() && !item.eq_ignore_ascii_case("none"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Microsoft's spam confidence level: -1 skipped filtering, 0 and 1 not spam, 5 and 6
+/// spam, 7 to 9 high-confidence spam (2 to 4 aren't used and count as not spam)
+fn scl_level(scl: i32) -> VerdictLevel {
+    let meaning = match scl {
+        ..=-1 => "gateway.scl.skipped",
+        0..=4 => "gateway.scl.not_spam",
+        5 | 6 => "gateway.scl.spam",
+        _ => "gateway.scl.high_confidence_spam",
+    };
+    VerdictLevel {
+        name: "SCL",
+        value: scl,
+        meaning: Localized::new(meaning, []),
+    }
+}
+
+fn scl_verdict(scl: i32) -> Verdict {
+    match scl {
+        ..=-1 => Verdict::Allowed,
+        0..=4 => Verdict::Clean,
+        _ => Verdict::Spam,
+    }
+}
+
+/// Microsoft's bulk complaint level: 0 not bulk, then bulk mail drawing few (1 to 3),
+/// mixed (4 to 7) or many complaints (8 and 9)
+fn bcl_level(bcl: i32) -> VerdictLevel {
+    let meaning = match bcl {
+        ..=0 => "gateway.bcl.not_bulk",
+        1..=3 => "gateway.bcl.few_complaints",
+        4..=7 => "gateway.bcl.mixed_complaints",
+        _ => "gateway.bcl.many_complaints",
+    };
+    VerdictLevel {
+        name: "BCL",
+        value: bcl,
+        meaning: Localized::new(meaning, []),
+    }
+}
+
+/// The `CAT` field of the Forefront report; `NONE` says nothing beyond the other fields
+fn category_verdict(category: &str) -> Option<Verdict> {
+    Some(match category.to_ascii_uppercase().as_str() {
+        "BULK" => Verdict::Bulk,
+        "SPM" | "HSPM" | "OSPM" => Verdict::Spam,
+        "PHSH" | "HPHSH" | "SPOOF" | "GIMP" | "UIMP" | "DIMP" => Verdict::Phishing,
+        "MALW" | "AMP" | "SAP" => Verdict::Malware,
+        _ => return None,
+    })
+}
+
+/// The `SFV` field: what spam filtering decided
+fn filter_verdict(filter: &str) -> Option<Verdict> {
+    Some(match filter.to_ascii_uppercase().as_str() {
+        "NSPM" => Verdict::Clean,
+        "SPM" | "SKS" | "BLK" | "SKB" => Verdict::Spam,
+        "SKN" | "SKA" | "SFE" | "SKI" | "SKQ" => Verdict::Allowed,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    #[test]
+    fn gateway_headers_become_normalized_verdicts() {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            include_bytes!("../../tests/fixtures/gateway/verdicts.eml"),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let summary: Vec<(&str, Verdict, Option<f64>)> = message
+            .gateway_verdicts
+            .iter()
+            .map(|v| (v.vendor, v.verdict, v.score))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("spamassassin", Verdict::Spam, Some(7.2)),
+                ("microsoft", Verdict::Bulk, None),
+                ("microsoft", Verdict::Bulk, None),
+                ("proofpoint", Verdict::Phishing, Some(85.0)),
+                ("mimecast", Verdict::Suspicious, None),
+                ("unknown", Verdict::Unknown, Some(12.5)),
+            ]
+        );
+        let spamassassin = &message.gateway_verdicts[0];
+        assert_eq!(
+            spamassassin.categories,
+            ["BAYES_99", "HTML_MESSAGE", "URIBL_BLACK"]
+        );
+        let forefront = &message.gateway_verdicts[1];
+        assert_eq!(forefront.categories, ["BULK", "NSPM"]);
+        assert_eq!(forefront.levels, [scl_level(1)]);
+        assert_eq!(forefront.levels[0].meaning.code, "gateway.scl.not_spam");
+        assert_eq!(
+            message.gateway_verdicts[2].levels,
+            [VerdictLevel {
+                name: "BCL",
+                value: 7,
+                meaning: Localized::new("gateway.bcl.mixed_complaints", []),
+            }]
+        );
+        assert_eq!(message.gateway_verdicts[3].categories, ["phishscore=85"]);
+        assert_eq!(
+            message.gateway_verdicts[4].categories,
+            ["Newly Observed Domain"]
+        );
+        let unknown = &message.gateway_verdicts[5];
+        assert_eq!(unknown.header, "X-Barracuda-Spam-Score");
+        assert_eq!(unknown.raw, "12.5");
+    }
+}
@@ -0,0 +1,282 @@
+//! What kind of file each attachment is and where it goes in the attachment strip, so the
+//! frontend groups and orders attachments the way Outlook users expect without deciding
+//! it itself: documents first, then images, then everything else, with the attachments
+//! Outlook wouldn't list (inline images, signature logos) last. Ties keep the order of the
+//! message, so the strip is the same every time a file is opened.
+
+use super::{sniff, Attachment, Message};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentCategory {
+    /// Word processor documents, RTF and plain text
+    Document,
+    Spreadsheet,
+    Presentation,
+    Pdf,
+    Image,
+    Archive,
+    /// Attached .msg and .eml messages
+    Message,
+    Calendar,
+    Audio,
+    Video,
+    Other,
+}
+
+/// Rank of each category in the strip, first to last. Changing it changes what every user
+/// sees first; the table in the tests has to change along with it.
+pub const RANKING: &[AttachmentCategory] = &[
+    AttachmentCategory::Document,
+    AttachmentCategory::Pdf,
+    AttachmentCategory::Spreadsheet,
+    AttachmentCategory::Presentation,
+    AttachmentCategory::Image,
+    AttachmentCategory::Message,
+    AttachmentCategory::Calendar,
+    AttachmentCategory::Archive,
+    AttachmentCategory::Audio,
+    AttachmentCategory::Video,
+    AttachmentCategory::Other,
+];
+
+/// Types of zip files, which may also be Office documents
+const ZIP_TYPES: &[&str] = &["application/zip", "application/x-zip-compressed"];
+
+/// What the sniffer reports for any text, CSV and calendar files included
+const PLAIN_TEXT: &str = "text/plain";
+
+/// Attachment totals for listings and statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AttachmentSummary {
+    /// Bytes of all attachments, hidden ones included
+    pub total_size: usize,
+    /// Attachments of each category that has any
+    pub counts: BTreeMap<AttachmentCategory, usize>,
+}
+
+/// Category of a file: from the type found in its content, else its extension, else the
+/// type the sender declared. Generic types don't settle it: `application/octet-stream`
+/// says nothing, Office files are zips too and any text is `text/plain`, so a zip is only
+/// an archive and text only a document when nothing more specific was found.
+pub fn category(
+    file_name: &str,
+    detected_mime: Option<&str>,
+    declared_mime: Option<&str>,
+) -> AttachmentCategory {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|ext| sniff::mime_for_extension(&ext.to_string_lossy()));
+    let mimes = [detected_mime, extension, declared_mime];
+    let any = |types: &[&str]| {
+        mimes
+            .into_iter()
+            .flatten()
+            .any(|mime| types.iter().any(|known| essence(mime) == *known))
+    };
+    mimes
+        .into_iter()
+        .flatten()
+        .map(category_of_mime)
+        .find(|category| *category != AttachmentCategory::Other)
+        .unwrap_or(if any(ZIP_TYPES) {
+            AttachmentCategory::Archive
+        } else if any(&[PLAIN_TEXT]) {
+            AttachmentCategory::Document
+        } else {
+            AttachmentCategory::Other
+        })
+}
+
+/// Lowercased type/subtype without parameters
+fn essence(mime: &str) -> String {
+    mime.split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn category_of_mime(mime: &str) -> AttachmentCategory {
+    let mime = essence(mime);
+    let is = |prefixes: &[&str]| prefixes.iter().any(|prefix| mime.starts_with(prefix));
+    if mime == "application/pdf" {
+        AttachmentCategory::Pdf
+    } else if is(&[
+        "application/msword",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml",
+        "application/vnd.ms-word",
+        "application/vnd.oasis.opendocument.text",
+        "application/rtf",
+        "text/rtf",
+    ]) {
+        AttachmentCategory::Document
+    } else if is(&[
+        "application/vnd.ms-excel",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml",
+        "application/vnd.oasis.opendocument.spreadsheet",
+        "text/csv",
+    ]) {
+        AttachmentCategory::Spreadsheet
+    } else if is(&[
+        "application/vnd.ms-powerpoint",
+        "application/vnd.openxmlformats-officedocument.presentationml",
+        "application/vnd.oasis.opendocument.presentation",
+    ]) {
+        AttachmentCategory::Presentation
+    } else if is(&["image/"]) {
+        AttachmentCategory::Image
+    } else if is(&["message/rfc822", "application/vnd.ms-outlook"]) {
+        AttachmentCategory::Message
+    } else if is(&["text/calendar", "application/ics"]) {
+        AttachmentCategory::Calendar
+    } else if is(&[
+        "application/gzip",
+        "application/x-7z-compressed",
+        "application/vnd.rar",
+        "application/x-rar",
+        "application/x-tar",
+    ]) {
+        AttachmentCategory::Archive
+    } else if is(&["audio/"]) {
+        AttachmentCategory::Audio
+    } else if is(&["video/"]) {
+        AttachmentCategory::Video
+    } else {
+        AttachmentCategory::Other
+    }
+}
+
+/// Sort key of an attachment: listed before hidden, then by category rank
+fn rank(attachment: &Attachment) -> (bool, usize) {
+    let rank = RANKING
+        .iter()
+        .position(|category| *category == attachment.category)
+        .unwrap_or(RANKING.len());
+    (attachment.hidden, rank)
+}
+
+/// Fill in the display order of each attachment, once it's known which are hidden, and the
+/// message's attachment summary
+pub fn apply(message: &mut Message) {
+    let mut order: Vec<usize> = (0..message.attachments.len()).collect();
+    // Stable, so ties keep the order of the message
+    order.sort_by_key(|&i| rank(&message.attachments[i]));
+    for (position, i) in order.into_iter().enumerate() {
+        message.attachments[i].display_order = position;
+    }
+
+    let mut summary = AttachmentSummary::default();
+    for attachment in &message.attachments {
+        summary.total_size += attachment.size;
+        *summary.counts.entry(attachment.category).or_default() += 1;
+    }
+    message.attachment_summary = summary;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+    use AttachmentCategory::*;
+
+    #[test]
+    fn attachments_are_categorized_and_ranked() {
+        // The ranking, first to last; a change here is a change to what users see first
+        assert_eq!(
+            RANKING,
+            [
+                Document,
+                Pdf,
+                Spreadsheet,
+                Presentation,
+                Image,
+                Message,
+                Calendar,
+                Archive,
+                Audio,
+                Video,
+                Other
+            ]
+        );
+        for (file_name, detected, declared, expected) in [
+            ("report.docx", None, None, Document),
+            ("notes.txt", Some("text/plain"), None, Document),
+            ("letter.rtf", Some("application/rtf"), None, Document),
+            (
+                "scan",
+                Some("application/pdf"),
+                Some(sniff::OCTET_STREAM),
+                Pdf,
+            ),
+            ("budget.xlsx", Some("application/zip"), None, Spreadsheet),
+            (
+                "export.csv",
+                Some("text/plain"),
+                Some("text/csv"),
+                Spreadsheet,
+            ),
+            ("invite.ics.txt", Some("text/plain"), None, Document),
+            ("deck.odp", None, None, Presentation),
+            ("logo.bin", Some("image/png"), None, Image),
+            ("photo.JPG", None, Some(sniff::OCTET_STREAM), Image),
+            ("fwd.msg", None, None, Message),
+            ("forwarded", None, Some("message/rfc822"), Message),
+            ("invite.ics", Some("text/plain"), None, Calendar),
+            ("logs.7z", None, None, Archive),
+            ("files.zip", Some("application/zip"), None, Archive),
+            ("voicemail.wav", Some("audio/wav"), None, Audio),
+            ("clip.mov", None, None, Video),
+            ("setup.exe", None, Some("application/x-msdownload"), Other),
+        ] {
+            assert_eq!(
+                category(file_name, detected, declared),
+                expected,
+                "{}",
+                file_name
+            );
+        }
+
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            b"From: a@example.com\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+              --b\r\nContent-Type: multipart/related; boundary=r\r\n\r\n\
+              --r\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo\">\r\n\
+              --r\r\nContent-Type: image/png\r\nContent-ID: <logo>\r\n\
+              Content-Disposition: inline; filename=logo.png\r\n\r\nx\r\n--r--\r\n\
+              --b\r\nContent-Type: application/zip\r\n\
+              Content-Disposition: attachment; filename=files.zip\r\n\r\nzz\r\n\
+              --b\r\nContent-Type: image/jpeg\r\n\
+              Content-Disposition: attachment; filename=photo.jpg\r\n\r\nyyy\r\n\
+              --b\r\nContent-Type: application/pdf\r\n\
+              Content-Disposition: attachment; filename=a.pdf\r\n\r\nx\r\n\
+              --b\r\nContent-Type: application/pdf\r\n\
+              Content-Disposition: attachment; filename=b.pdf\r\n\r\nx\r\n--b--\r\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let strip: Vec<(usize, &str)> = message
+            .attachments
+            .iter()
+            .map(|a| (a.display_order, a.file_name.as_str()))
+            .collect();
+        assert_eq!(
+            strip,
+            [
+                (4, "logo.png"),
+                (3, "files.zip"),
+                (2, "photo.jpg"),
+                (0, "a.pdf"),
+                (1, "b.pdf"),
+            ]
+        );
+        assert_eq!(message.attachment_summary.total_size, 1 + 2 + 3 + 1 + 1);
+        assert_eq!(
+            message.attachment_summary.counts,
+            BTreeMap::from([(Pdf, 2), (Image, 2), (Archive, 1)])
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use super::charset;
+use super::Address;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+/// Ordered list of header fields; names keep their original case
+#[derive(Debug, Clone, Default)]
+pub struct Headers(pub Vec<(String, String)>);
+
+impl Headers {
+    /// First value of a header, matched case-insensitively
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Longest field value kept (unfolded); long `References` chains are cut here
+const MAX_FIELD_BYTES: usize = 64 * 1024;
+
+/// Header bytes read per entity; fields beyond are dropped
+const MAX_HEADER_BYTES: usize = 512 * 1024;
+
+/// Recipient fields, which only the header budget limits: announcements list thousands
+const ADDRESS_FIELDS: &[&str] = &["To", "Cc", "Bcc"];
+
+/// Longest value kept of the field `name`
+fn field_limit(name: &str) -> usize {
+    match ADDRESS_FIELDS
+        .iter()
+        .any(|field| name.eq_ignore_ascii_case(field))
+    {
+        true => MAX_HEADER_BYTES,
+        false => MAX_FIELD_BYTES,
+    }
+}
+
+/// Fields that decide how the body is read; only the first counts when repeated
+const BODY_FIELDS: &[&str] = &[
+    "Content-Type",
+    "Content-Transfer-Encoding",
+    "Content-Disposition",
+];
+
+/// Irregularities tolerated while reading a header block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Quirk {
+    /// No blank line ended the header (see `split_header_block_checked`)
+    MissingSeparator,
+    /// Lines end in a bare CR (classic Mac OS, broken gateways)
+    BareCarriageReturns,
+    /// A field longer than `MAX_FIELD_BYTES` (recipient fields: `MAX_HEADER_BYTES`), cut there
+    FieldTruncated(String),
+    /// Fields after the first `MAX_HEADER_BYTES` were dropped
+    HeaderTruncated,
+    /// A body field given more than once; the first is used
+    DuplicateField(String),
+}
+
+/// Split raw message bytes into the header block and the body at the first blank line
+pub fn split_header_block(data: &[u8]) -> (&[u8], &[u8]) {
+    let (block, body, _) = split_header_block_checked(data);
+    (block, body)
+}
+
+/// Split an entity at the first blank line; lines may end in CRLF, LF or a bare CR. Fields
+/// after the blank line are body text, however much they look like headers: a gateway that
+/// inserts a blank line early loses them, but a body can't smuggle in headers.
+///
+/// Without any blank line the header ends at the first line that is neither a field nor a
+/// continuation, and the third value is true; text that lost its header separator then
+/// still shows as the body. A block made only of fields is all header, as before.
+pub fn split_header_block_checked(data: &[u8]) -> (&[u8], &[u8], bool) {
+    let mut start = 0;
+    let mut first_non_field = None;
+    while start < data.len() {
+        let (end, next) = line_end(data, start);
+        let line = &data[start..end];
+        if line.is_empty() {
+            return (&data[..start], &data[next..], false);
+        }
+        if first_non_field.is_none()
+            && !line.starts_with(b" ")
+            && !line.starts_with(b"\t")
+            && !is_field(line)
+        {
+            first_non_field = Some(start);
+        }
+        start = next;
+    }
+    match first_non_field {
+        Some(at) => (&data[..at], &data[at..], true),
+        None => (data, &[], false),
+    }
+}
+
+/// End of the line starting at `start` and the start of the next one
+fn line_end(data: &[u8], start: usize) -> (usize, usize) {
+    match data[start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+        Some(offset) => {
+            let end = start + offset;
+            match (data[end], data.get(end + 1)) {
+                (b'\r', Some(b'\n')) => (end, end + 2),
+                _ => (end, end + 1),
+            }
+        }
+        None => (data.len(), data.len()),
+    }
+}
+
+/// `Name: value`, the name being printable ASCII (spaces before the colon tolerated)
+fn is_field(line: &[u8]) -> bool {
+    let Some(colon) = line.iter().position(|&b| b == b':') else {
+        return false;
+    };
+    let name = line[..colon].trim_ascii_end();
+    !name.is_empty() && name.iter().all(|&b| (33..=126).contains(&b))
+}
+
+/// Parse a header block, unfolding continuation lines
+pub fn parse(block: &[u8]) -> Headers {
+    parse_checked(block).0
+}
+
+/// Parse a header block like `parse`, reporting what had to be tolerated
+pub fn parse_checked(block: &[u8]) -> (Headers, Vec<Quirk>) {
+    let mut quirks = Vec::new();
+    let text = bytes_to_text(block);
+    let text = match text.replace("\r\n", "\n") {
+        normalized if normalized.contains('\r') => {
+            quirks.push(Quirk::BareCarriageReturns);
+            normalized.replace('\r', "\n")
+        }
+        normalized => normalized,
+    };
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut read = 0;
+
+    for line in text.split('\n') {
+        read += line.len() + 1;
+        if read > MAX_HEADER_BYTES {
+            quirks.push(Quirk::HeaderTruncated);
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            // Continuation of the previous field
+            if let Some((name, value)) = headers.last_mut() {
+                let limit = field_limit(name);
+                if value.len() >= limit {
+                    continue;
+                }
+                value.push(' ');
+                value.push_str(line.trim_start());
+                if value.len() > limit {
+                    truncate(value, limit);
+                    quirks.push(Quirk::FieldTruncated(name.clone()));
+                }
+            }
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if !name.is_empty() && !name.contains(' ') {
+                let mut value = value.trim().to_string();
+                if value.len() > field_limit(name) {
+                    truncate(&mut value, field_limit(name));
+                    quirks.push(Quirk::FieldTruncated(name.to_string()));
+                }
+                headers.push((name.to_string(), value));
+            }
+        }
+    }
+
+    for field in BODY_FIELDS {
+        let count = headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(field))
+            .count();
+        if count > 1 {
+            quirks.push(Quirk::DuplicateField(field.to_string()));
+        }
+    }
+    (Headers(headers), quirks)
+}
+
+/// Cut `text` to at most `max` bytes on a character boundary
+fn truncate(text: &mut String, max: usize) {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
+
+/// Raw 8-bit header bytes: UTF-8 when valid, otherwise Latin-1
+fn bytes_to_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => charset::decode(bytes, "iso-8859-1"),
+    }
+}
+
+/// Decode RFC 2047 encoded words (`=?charset?B?...?=` / `=?charset?Q?...?=`)
+pub fn decode_encoded_words(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    let mut pending_whitespace = String::new();
+    let mut previous_was_encoded = false;
+
+    while !rest.is_empty() {
+        if rest.starts_with("=?") {
+            if let Some((decoded, consumed)) = decode_word(rest) {
+                // Whitespace between adjacent encoded words is dropped
+                if !previous_was_encoded {
+                    result.push_str(&pending_whitespace);
+                }
+                pending_whitespace.clear();
+                result.push_str(&decoded);
+                rest = &rest[consumed..];
+                previous_was_encoded = true;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap();
+        rest = &rest[c.len_utf8()..];
+        if c.is_whitespace() {
+            pending_whitespace.push(c);
+        } else {
+            result.push_str(&pending_whitespace);
+            pending_whitespace.clear();
+            result.push(c);
+            previous_was_encoded = false;
+        }
+    }
+
+    result.push_str(&pending_whitespace);
+    result
+}
+
+/// Decode one encoded word at the start of `input`, returning the text and bytes consumed
+fn decode_word(input: &str) -> Option<(String, usize)> {
+    let inner = &input[2..];
+    let (charset, after_charset) = inner.split_once('?')?;
+    let (encoding, after_encoding) = after_charset.split_once('?')?;
+    let end = after_encoding.find("?=")?;
+    let text = &after_encoding[..end];
+
+    if charset.is_empty() || text.contains(' ') {
+        return None;
+    }
+    let consumed = input.len() - after_encoding.len() + end + 2;
+
+    let bytes = match encoding {
+        "B" | "b" => STANDARD
+            .decode(text)
+            .or_else(|_| STANDARD.decode(text.trim_end_matches('=')))
+            .ok()?,
+        "Q" | "q" => decode_q(text.as_bytes()),
+        _ => return None,
+    };
+
+    // RFC 2231 language suffix: charset*lang
+    let charset = charset.split('*').next().unwrap_or(charset);
+    Some((charset::decode(&bytes, charset), consumed))
+}
+
+/// Q encoding: quoted-printable with `_` meaning space
+fn decode_q(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => out.push(b' '),
+            b'=' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 2;
+                }
+                None => out.push(b'='),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Parse two ASCII hex digits
+pub(crate) fn hex_byte(digits: &[u8]) -> Option<u8> {
+    if digits.len() != 2 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let text = std::str::from_utf8(digits).ok()?;
+    u8::from_str_radix(text, 16).ok()
+}
+
+/// Display part of an address header: `"Alice" <a@example.com>` becomes `Alice`
+pub fn display_name(address: &str) -> String {
+    let decoded = decode_encoded_words(address);
+    let name = match decoded.find('<') {
+        Some(pos) if pos > 0 => decoded[..pos].trim(),
+        _ => decoded.trim_matches(['<', '>']).trim(),
+    };
+    name.trim_matches('"').trim().to_string()
+}
+
+/// Parse an address list header (`To`, `Cc`, ...), flattening groups
+pub fn address_list(value: &str) -> Vec<Address> {
+    split_addresses(value)
+        .into_iter()
+        .filter_map(|item| parse_address(&item))
+        .collect()
+}
+
+/// Split at commas outside quotes, angle brackets and comments; group names are dropped
+fn split_addresses(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut angle = 0usize;
+    let mut comment = 0usize;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => {
+                escaped = true;
+                current.push(c);
+                continue;
+            }
+            '"' if comment == 0 => in_quotes = !in_quotes,
+            '<' if !in_quotes && comment == 0 => angle += 1,
+            '>' if !in_quotes && comment == 0 => angle = angle.saturating_sub(1),
+            '(' if !in_quotes => comment += 1,
+            ')' if !in_quotes => comment = comment.saturating_sub(1),
+            ':' if !in_quotes && angle == 0 && comment == 0 => {
+                // "Group name: member, member;" -- the name isn't an address
+                current.clear();
+                continue;
+            }
+            ',' | ';' if !in_quotes && angle == 0 && comment == 0 => {
+                items.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    items.push(current);
+
+    items
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// `"Name" <user@host>`, `user@host (Name)` or a bare address
+fn parse_address(item: &str) -> Option<Address> {
+    let clean = |text: &str| {
+        let text = decode_encoded_words(text.trim());
+        let text = text.trim().trim_matches('"').replace("\\\"", "\"");
+        let text = text.trim().to_string();
+        (!text.is_empty()).then_some(text)
+    };
+
+    if let (Some(open), Some(close)) = (item.rfind('<'), item.rfind('>')) {
+        if open < close {
+            return Some(Address::new(
+                clean(&item[..open]),
+                clean(&item[open + 1..close]),
+            ));
+        }
+    }
+
+    if let (Some(open), Some(close)) = (item.find('('), item.rfind(')')) {
+        if open < close {
+            let email = clean(&item[..open]).filter(|e| e.contains('@'));
+            let name = clean(&item[open + 1..close]);
+            return Some(Address::new(name, email));
+        }
+    }
+
+    let text = clean(item)?;
+    Some(if text.contains('@') && !text.contains(' ') {
+        Address::new(None, Some(text))
+    } else {
+        Address::new(Some(text), None)
+    })
+}
+
+/// RFC 5322 date header as RFC 3339
+pub fn parse_date(value: &str) -> Option<String> {
+    // Drop trailing comments such as "(UTC)" that chrono doesn't accept
+    let value = match value.find('(') {
+        Some(pos) => value[..pos].trim(),
+        None => value.trim(),
+    };
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_blocks_are_split_unfolded_and_decoded() {
+        let data = b"Subject: =?utf-8?q?Gr=C3=BC=C3=9Fe_?=\r\n =?iso-8859-1?q?aus_K=F6ln?=\r\n\
+From: x@example.com\r\n\r\nbody\r\n";
+        let (block, body) = split_header_block(data);
+        assert_eq!(body, b"body\r\n");
+        let headers = parse(block);
+        assert_eq!(headers.get("from"), Some("x@example.com"));
+        assert_eq!(
+            headers.get("Subject").map(decode_encoded_words).as_deref(),
+            Some("Grüße aus Köln")
+        );
+    }
+
+    #[test]
+    fn address_lists_keep_quoted_commas_and_flatten_groups() {
+        let pairs = |value: &str| -> Vec<(Option<String>, Option<String>)> {
+            address_list(value)
+                .into_iter()
+                .map(|a| (a.name, a.email))
+                .collect()
+        };
+        let s = |text: &str| Some(text.to_string());
+        assert_eq!(
+            pairs(
+                "\"Doe, Jane\" <jane@example.com>, bob@example.com (Bob), team: a@x.org, b@x.org;"
+            ),
+            [
+                (s("Doe, Jane"), s("jane@example.com")),
+                (s("Bob"), s("bob@example.com")),
+                (None, s("a@x.org")),
+                (None, s("b@x.org")),
+            ]
+        );
+        assert_eq!(
+            pairs("=?utf-8?q?J=C3=BCrgen?= <j@example.com>")[0].0,
+            s("Jürgen")
+        );
+        assert_eq!(display_name("\"Alice\" <a@example.com>"), "Alice");
+        assert_eq!(display_name("<a@example.com>"), "a@example.com");
+    }
+
+    #[test]
+    fn dates_are_read_with_trailing_comments() {
+        assert_eq!(
+            parse_date("Tue, 3 Mar 2026 09:15:00 +0100 (CET)").as_deref(),
+            Some("2026-03-03T09:15:00+01:00")
+        );
+        assert_eq!(parse_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,401 @@
+mod entities;
+
+/// Windows-1252 characters that numeric references in the C1 range stand for
+const C1_REPLACEMENTS: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}', '\u{017D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}', '\u{017E}', '\u{0178}',
+];
+
+/// Longest named character reference (`&CounterClockwiseContourIntegral;`)
+const MAX_ENTITY_LEN: usize = 33;
+
+/// Attributes of every `<name ...>` start tag in `html`, with lowercased names and
+/// unquoted, entity-decoded values
+pub fn tags(html: &str, name: &str) -> Vec<Vec<(String, String)>> {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", name.to_ascii_lowercase());
+    let mut tags = Vec::new();
+    let mut pos = 0;
+
+    while let Some(found) = lower[pos..].find(&open) {
+        let start = pos + found + open.len();
+        pos = start;
+        // `<image` is not `<img`
+        if !html[start..].starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            continue;
+        }
+        let end = tag_end(&html[start..]).map_or(html.len(), |end| start + end);
+        tags.push(attributes(&html[start..end]));
+        pos = end;
+    }
+    tags
+}
+
+/// Value of an attribute in a list returned by [`tags`]
+pub fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Declarations of an inline `style` attribute, with lowercased property names
+pub fn style_declarations(style: &str) -> Vec<(String, String)> {
+    style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| {
+            (
+                property.trim().to_ascii_lowercase(),
+                value
+                    .trim()
+                    .trim_end_matches("!important")
+                    .trim()
+                    .to_ascii_lowercase(),
+            )
+        })
+        .collect()
+}
+
+/// Offset of the `>` closing a tag, skipping over quoted attribute values
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            break;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '=' | '/' | '>'))
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let close = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..close], inner.get(close + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = decode_entities(raw);
+            rest = remaining;
+        }
+        if name.is_empty() {
+            // Stray `=` or quote; skip a character to make progress
+            rest = rest.get(1..).unwrap_or_default();
+            continue;
+        }
+        attributes.push((name, value));
+    }
+    attributes
+}
+
+/// Replace named and numeric character references the way browsers do, including the
+/// legacy names accepted without `;`; unknown references are kept as written
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match character_reference(&rest[1..]) {
+            Some((decoded, len)) => {
+                out.push_str(&decoded);
+                rest = &rest[1 + len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decode the reference at the start of `after` (the text following `&`), returning the
+/// replacement and how many bytes it used
+fn character_reference(after: &str) -> Option<(String, usize)> {
+    if let Some(numeric) = after.strip_prefix('#') {
+        let (digits, radix) = match numeric.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (numeric, 10),
+        };
+        let len = digits
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(digits.len());
+        if len == 0 {
+            return None;
+        }
+        let prefix = after.len() - digits.len();
+        let terminated = digits[len..].starts_with(';');
+        let code = u32::from_str_radix(&digits[..len], radix).unwrap_or(u32::MAX);
+        let c = match code {
+            0x80..=0x9F => C1_REPLACEMENTS[(code - 0x80) as usize],
+            0 => char::REPLACEMENT_CHARACTER,
+            _ => char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+        };
+        return Some((c.to_string(), prefix + len + usize::from(terminated)));
+    }
+
+    let name_len = after
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(after.len())
+        .min(MAX_ENTITY_LEN);
+    if name_len == 0 {
+        return None;
+    }
+    if after[name_len..].starts_with(';') {
+        if let Some(value) = lookup_entity(&after[..=name_len]) {
+            return Some((value.to_string(), name_len + 1));
+        }
+    }
+    // Legacy references like `&nbsp` or `&copy2024`: the longest known prefix without `;`
+    (2..=name_len)
+        .rev()
+        .find_map(|len| lookup_entity(&after[..len]).map(|value| (value.to_string(), len)))
+}
+
+fn lookup_entity(name: &str) -> Option<&'static str> {
+    entities::ENTITIES
+        .binary_search_by(|(entity, _)| (*entity).cmp(name))
+        .ok()
+        .map(|index| entities::ENTITIES[index].1)
+}
+
+/// Elements that never have content
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose content is not markup
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "title", "textarea", "xmp"];
+
+/// Block elements that implicitly close an open `<p>`
+const CLOSES_PARAGRAPH: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Node of a parsed HTML document
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element(Element),
+    /// Text with character references decoded
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Element {
+    /// Lowercased tag name
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        attribute(&self.attributes, name)
+    }
+
+    /// Whether a descendant element is named `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.children.iter().any(|child| match child {
+            Node::Element(element) => element.name == name || element.contains(name),
+            Node::Text(_) => false,
+        })
+    }
+}
+
+/// Parse HTML leniently into a tree, the way mail bodies are written rather than the way
+/// the specification wants them: unclosed paragraphs, list items and table cells are closed
+/// by their successors, and stray end tags are ignored
+pub fn parse(html: &str) -> Vec<Node> {
+    let mut stack = vec![Element {
+        name: String::new(),
+        attributes: Vec::new(),
+        children: Vec::new(),
+    }];
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while pos < html.len() {
+        let Some(lt) = html[pos..].find('<').map(|i| pos + i) else {
+            push_text(&mut stack, &html[pos..]);
+            break;
+        };
+        push_text(&mut stack, &html[pos..lt]);
+        let rest = &html[lt..];
+
+        if rest.starts_with("<!--") {
+            pos = rest.find("-->").map_or(html.len(), |end| lt + end + 3);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            pos = rest.find('>').map_or(html.len(), |end| lt + end + 1);
+        } else if let Some(end_tag) = rest.strip_prefix("</") {
+            let name_len = end_tag
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .unwrap_or(end_tag.len());
+            close(&mut stack, &end_tag[..name_len].to_ascii_lowercase());
+            pos = rest.find('>').map_or(html.len(), |end| lt + end + 1);
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let name_len = rest[1..]
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .map_or(rest.len(), |i| i + 1);
+            let name = rest[1..name_len].to_ascii_lowercase();
+            let end = tag_end(&rest[name_len..]).map_or(html.len(), |end| lt + name_len + end);
+            let inner = &html[lt + name_len..end];
+            pos = (end + 1).min(html.len());
+
+            close_implied(&mut stack, &name);
+            let element = Element {
+                attributes: attributes(inner),
+                name,
+                children: Vec::new(),
+            };
+            if VOID_ELEMENTS.contains(&element.name.as_str()) || inner.trim_end().ends_with('/') {
+                append(&mut stack, Node::Element(element));
+            } else if RAW_TEXT_ELEMENTS.contains(&element.name.as_str()) {
+                let close_tag = format!("</{}", element.name);
+                let content_end = lower[pos..]
+                    .find(&close_tag)
+                    .map_or(html.len(), |i| pos + i);
+                let mut element = element;
+                element
+                    .children
+                    .push(Node::Text(html[pos..content_end].to_string()));
+                append(&mut stack, Node::Element(element));
+                pos = html[content_end..]
+                    .find('>')
+                    .map_or(html.len(), |i| content_end + i + 1);
+            } else {
+                stack.push(element);
+            }
+        } else {
+            push_text(&mut stack, "<");
+            pos = lt + 1;
+        }
+    }
+
+    while stack.len() > 1 {
+        pop(&mut stack);
+    }
+    stack.pop().map(|root| root.children).unwrap_or_default()
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if !text.is_empty() {
+        append(stack, Node::Text(decode_entities(text)));
+    }
+}
+
+fn append(stack: &mut [Element], node: Node) {
+    if let Some(current) = stack.last_mut() {
+        current.children.push(node);
+    }
+}
+
+fn pop(stack: &mut Vec<Element>) {
+    if stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        append(stack, Node::Element(element));
+    }
+}
+
+/// Close the innermost open `name` element and everything opened inside it
+fn close(stack: &mut Vec<Element>, name: &str) {
+    if let Some(index) = stack
+        .iter()
+        .skip(1)
+        .rposition(|element| element.name == name)
+    {
+        while stack.len() > index + 1 {
+            pop(stack);
+        }
+    }
+}
+
+/// Close elements that opening `name` ends implicitly
+fn close_implied(stack: &mut Vec<Element>, name: &str) {
+    let open_within = |stack: &[Element], targets: &[&str], boundaries: &[&str]| {
+        stack
+            .iter()
+            .rev()
+            .take_while(|element| !boundaries.contains(&element.name.as_str()))
+            .any(|element| targets.contains(&element.name.as_str()))
+    };
+    let close_to = |stack: &mut Vec<Element>, targets: &[&str]| {
+        while let Some(top) = stack.last() {
+            let done = targets.contains(&top.name.as_str());
+            pop(stack);
+            if done || stack.len() == 1 {
+                break;
+            }
+        }
+    };
+
+    if CLOSES_PARAGRAPH.contains(&name) && stack.last().is_some_and(|top| top.name == "p") {
+        pop(stack);
+    }
+    match name {
+        "li" if open_within(stack, &["li"], &["ul", "ol"]) => close_to(stack, &["li"]),
+        "dt" | "dd" if open_within(stack, &["dt", "dd"], &["dl"]) => close_to(stack, &["dt", "dd"]),
+        "td" | "th" if open_within(stack, &["td", "th"], &["tr", "table"]) => {
+            close_to(stack, &["td", "th"])
+        }
+        "tr" if open_within(stack, &["tr"], &["table"]) => close_to(stack, &["tr"]),
+        "thead" | "tbody" | "tfoot"
+            if open_within(stack, &["thead", "tbody", "tfoot"], &["table"]) =>
+        {
+            close_to(stack, &["thead", "tbody", "tfoot"])
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,199 @@
+use serde::Serialize;
+
+/// Cyrillic and Greek letters that look like Latin ones, with the letter they imitate
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'),
+    ('ь', 'b'),
+    ('с', 'c'),
+    ('ԁ', 'd'),
+    ('е', 'e'),
+    ('һ', 'h'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ӏ', 'l'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('ԛ', 'q'),
+    ('ѕ', 's'),
+    ('ԝ', 'w'),
+    ('х', 'x'),
+    ('у', 'y'),
+    ('α', 'a'),
+    ('ε', 'e'),
+    ('ι', 'i'),
+    ('κ', 'k'),
+    ('ν', 'v'),
+    ('ο', 'o'),
+    ('ρ', 'p'),
+    ('τ', 't'),
+    ('υ', 'u'),
+    ('χ', 'x'),
+    ('γ', 'y'),
+];
+
+/// A host name in display and wire form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostInfo {
+    /// Unicode form, for showing to the user
+    pub display_host: String,
+    /// ASCII form with punycode (`xn--`) labels, as used on the wire
+    pub ascii_host: String,
+    /// Mixes Latin with Cyrillic or Greek in one label, or spells a Latin-looking name
+    /// entirely in lookalike letters (`аррӏе.com`)
+    pub suspicious: bool,
+}
+
+/// Normalize a host name given in either form; `None` if it isn't a valid domain name
+pub fn host_info(host: &str) -> Option<HostInfo> {
+    let host = host.trim().trim_end_matches('.');
+    if host.is_empty() {
+        return None;
+    }
+    let ascii_host = idna::domain_to_ascii(host).ok()?;
+    let (unicode, result) = idna::domain_to_unicode(&ascii_host);
+
+    // Punycode that doesn't decode is never legitimate; show it as is
+    if result.is_err() {
+        return Some(HostInfo {
+            display_host: ascii_host.clone(),
+            ascii_host,
+            suspicious: true,
+        });
+    }
+    Some(HostInfo {
+        suspicious: unicode.split('.').any(is_confusable_label),
+        display_host: unicode,
+        ascii_host,
+    })
+}
+
+/// Host of an email address (the part after the last `@`)
+pub fn email_host(email: &str) -> Option<HostInfo> {
+    let (_, host) = email.rsplit_once('@')?;
+    host_info(host.trim_end_matches('>'))
+}
+
+/// `text` lowercase with lookalike letters replaced by the Latin ones they imitate, so
+/// `Місrоsоft` and `microsoft` compare equal
+pub fn skeleton(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| {
+            CONFUSABLES
+                .iter()
+                .find(|&&(lookalike, _)| lookalike == c)
+                .map_or(c, |&(_, latin)| latin)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Other,
+}
+
+fn script(c: char) -> Option<Script> {
+    let script = match c as u32 {
+        _ if c.is_ascii_alphabetic() => Script::Latin,
+        0x00C0..=0x024F | 0x1E00..=0x1EFF => Script::Latin,
+        0x0400..=0x052F => Script::Cyrillic,
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+        // Digits, hyphens and marks belong to no script
+        _ if !c.is_alphabetic() => return None,
+        _ => Script::Other,
+    };
+    Some(script)
+}
+
+/// Whether a (Unicode) label mixes confusable scripts or is a whole-script lookalike
+fn is_confusable_label(label: &str) -> bool {
+    if label.is_ascii() {
+        return false;
+    }
+    let scripts: Vec<Script> = label.chars().filter_map(script).collect();
+    let has = |wanted: Script| scripts.contains(&wanted);
+
+    // Latin next to CJK is common (`東京bank`); Latin next to Cyrillic or Greek is not
+    let confusable_scripts = [Script::Latin, Script::Cyrillic, Script::Greek]
+        .into_iter()
+        .filter(|&s| has(s))
+        .count();
+    if confusable_scripts > 1 {
+        return true;
+    }
+
+    // A Cyrillic or Greek label made only of letters that have Latin twins
+    !scripts.is_empty()
+        && !has(Script::Latin)
+        && !has(Script::Other)
+        && label
+            .chars()
+            .filter(|&c| script(c).is_some())
+            .all(|c| CONFUSABLES.iter().any(|&(lookalike, _)| lookalike == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_host_is_unchanged() {
+        let info = host_info("Example.COM").unwrap();
+        assert_eq!(info.display_host, "example.com");
+        assert_eq!(info.ascii_host, "example.com");
+        assert!(!info.suspicious);
+    }
+
+    #[test]
+    fn punycode_is_shown_as_unicode() {
+        let info = host_info("xn--mnchen-3ya.de").unwrap();
+        assert_eq!(info.display_host, "münchen.de");
+        assert_eq!(info.ascii_host, "xn--mnchen-3ya.de");
+        assert!(!info.suspicious);
+    }
+
+    #[test]
+    fn genuine_cyrillic_host_is_not_suspicious() {
+        let info = host_info("пример.рф").unwrap();
+        assert_eq!(info.ascii_host, "xn--e1afmkfd.xn--p1ai");
+        assert!(!info.suspicious);
+    }
+
+    #[test]
+    fn whole_script_lookalike_is_suspicious() {
+        // Cyrillic а, р, р, ӏ, е
+        let info = host_info("аррӏе.com").unwrap();
+        assert!(info.ascii_host.starts_with("xn--"));
+        assert_eq!(info.display_host, "аррӏе.com");
+        assert!(info.suspicious);
+
+        let from_punycode = host_info(&info.ascii_host).unwrap();
+        assert_eq!(from_punycode, info);
+    }
+
+    #[test]
+    fn mixed_cyrillic_and_latin_label_is_suspicious() {
+        // Latin "p", "y", "l" and Cyrillic "а" in "paypal"
+        let info = host_info("pаypal.com").unwrap();
+        assert!(info.suspicious);
+        // Cyrillic "о" in an otherwise Latin name
+        assert!(host_info("micrоsoft.com").unwrap().suspicious);
+        // Greek "ο" mixed with Latin
+        assert!(host_info("gοogle.com").unwrap().suspicious);
+    }
+
+    #[test]
+    fn latin_next_to_cjk_is_allowed() {
+        assert!(!host_info("東京bank.jp").unwrap().suspicious);
+    }
+
+    #[test]
+    fn email_host_uses_domain_part() {
+        let info = email_host("ceo@аррӏе.com").unwrap();
+        assert!(info.suspicious);
+        assert!(email_host("no-at-sign").is_none());
+    }
+}
@@ -0,0 +1,260 @@
+use super::{Address, Message, ParseOptions};
+use crate::i18n::Localized;
+use serde::Serialize;
+
+/// Message class Exchange uses for journal reports
+const JOURNAL_REPORT_CLASS: &str = "IPM.Note.JournalReport";
+
+/// Header Exchange adds to journal reports sent over SMTP
+const JOURNAL_REPORT_HEADER: &str = "X-MS-Journal-Report";
+
+/// Envelope data of an unwrapped journal report
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEnvelope {
+    /// Original sender as recorded by the transport (P1 envelope)
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub message_id: Option<String>,
+    /// Envelope recipients, including Bcc and expanded distribution lists
+    pub recipients: Vec<JournalRecipient>,
+    pub on_behalf_of: Option<String>,
+    /// Sender and date of the report itself (usually the journaling agent)
+    pub report_from: Option<Address>,
+    pub report_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalRecipient {
+    pub address: String,
+    /// `to`, `cc`, `bcc` or `recipient` (Exchange 2007+ doesn't distinguish)
+    pub kind: String,
+    /// Distribution list the recipient was expanded from
+    pub expanded_from: Option<String>,
+    /// Address the message was forwarded from
+    pub forwarded_from: Option<String>,
+}
+
+/// Replace a journal report by the message it wraps, keeping the envelope data.
+/// Anything that doesn't look like a journal report is returned unchanged.
+pub fn unwrap(mut outer: Message, options: &ParseOptions) -> Message {
+    let Some(index) = wrapped_message(&outer) else {
+        return outer;
+    };
+    let attachment = &mut outer.attachments[index];
+    let Some(format) = attachment.message_format() else {
+        return outer;
+    };
+
+    // Parsed already unless that failed; parsing again reports why
+    let parsed = match attachment.nested.take() {
+        Some(nested) => Ok(*nested),
+        None => super::parse_bytes(format, &attachment.data, options),
+    };
+    match parsed {
+        Ok(mut inner) => {
+            let mut envelope = parse_envelope(outer.body_text.as_deref().unwrap_or_default());
+            envelope.report_from = outer.from.clone();
+            envelope.report_date = outer.date.clone();
+            inner.journal_envelope = Some(envelope);
+            inner
+        }
+        Err(e) => {
+            outer.warnings.push(Localized::new(
+                "warning.journal_unwrap_failed",
+                [("reason", e)],
+            ));
+            outer
+        }
+    }
+}
+
+/// Index of the wrapped message if `message` is a journal report
+fn wrapped_message(message: &Message) -> Option<usize> {
+    let mut messages = message
+        .attachments
+        .iter()
+        .filter(|attachment| attachment.message_format().is_some());
+    let first = messages.next()?.index;
+
+    let marked = message.message_class.as_deref().is_some_and(|class| {
+        class
+            .to_ascii_lowercase()
+            .starts_with(&JOURNAL_REPORT_CLASS.to_ascii_lowercase())
+    }) || message
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case(JOURNAL_REPORT_HEADER));
+    if marked {
+        return Some(first);
+    }
+
+    // Without a marker, require the exact shape: one attached message and an envelope body
+    let only_attachment = message.attachments.len() == 1;
+    let body = message.body_text.as_deref().unwrap_or_default();
+    (only_attachment && looks_like_envelope(body)).then_some(first)
+}
+
+/// An envelope body starts with `Sender:` and lists recipients
+fn looks_like_envelope(body: &str) -> bool {
+    let mut fields = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, _)| key.trim().to_ascii_lowercase());
+
+    fields.next().as_deref() == Some("sender")
+        && fields.any(|key| matches!(key.as_str(), "recipient" | "to" | "cc" | "bcc"))
+}
+
+/// Parse the `Key: value` lines of an envelope body
+fn parse_envelope(body: &str) -> JournalEnvelope {
+    let mut envelope = JournalEnvelope {
+        sender: None,
+        subject: None,
+        message_id: None,
+        recipients: Vec::new(),
+        on_behalf_of: None,
+        report_from: None,
+        report_date: None,
+    };
+
+    for line in body.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let key = key.trim().to_ascii_lowercase();
+
+        match key.as_str() {
+            "sender" => envelope.sender = Some(value.to_string()),
+            "subject" => envelope.subject = Some(value.to_string()),
+            "message-id" => envelope.message_id = Some(value.to_string()),
+            "on-behalf-of" => envelope.on_behalf_of = Some(value.to_string()),
+            "to" | "cc" | "bcc" | "recipient" => {
+                envelope.recipients.push(parse_recipient(&key, value));
+            }
+            _ => {}
+        }
+    }
+    envelope
+}
+
+/// `user@example.com, Expanded: list@example.com` or `..., Forwarded: other@example.com`
+fn parse_recipient(kind: &str, value: &str) -> JournalRecipient {
+    let mut parts = value.split(',').map(str::trim);
+    let mut recipient = JournalRecipient {
+        address: parts.next().unwrap_or_default().to_string(),
+        kind: kind.to_string(),
+        expanded_from: None,
+        forwarded_from: None,
+    };
+
+    for part in parts {
+        if let Some((key, value)) = part.split_once(':') {
+            let value = Some(value.trim().to_string());
+            match key.trim().to_ascii_lowercase().as_str() {
+                "expanded" => recipient.expanded_from = value,
+                "forwarded" => recipient.forwarded_from = value,
+                _ => {}
+            }
+        }
+    }
+    recipient
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat};
+
+    const REPORT: &[u8] = include_bytes!("../../tests/fixtures/journal/report.eml");
+
+    fn parse(data: &[u8]) -> Message {
+        parse_bytes(MessageFormat::Eml, data, &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn journal_reports_are_replaced_by_the_message_they_wrap() {
+        let message = unwrap(parse(REPORT), &ParseOptions::default());
+        assert_eq!(
+            message.from.as_ref().and_then(|a| a.name.as_deref()),
+            Some("Ann")
+        );
+        assert_eq!(
+            message.body_text.as_deref(),
+            Some("The figures are attached.")
+        );
+
+        let envelope = message.journal_envelope.unwrap();
+        assert_eq!(envelope.sender.as_deref(), Some("ann@example.com"));
+        assert_eq!(envelope.message_id.as_deref(), Some("<q3@example.com>"));
+        assert_eq!(
+            envelope.on_behalf_of.as_deref(),
+            Some("assistant@example.com")
+        );
+        let recipients: Vec<_> = envelope
+            .recipients
+            .iter()
+            .map(|r| {
+                (
+                    r.kind.as_str(),
+                    r.address.as_str(),
+                    r.expanded_from.as_deref(),
+                    r.forwarded_from.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            recipients,
+            [
+                ("to", "bob@example.com", None, None),
+                ("cc", "team@example.com", Some("all@example.com"), None),
+                ("bcc", "carol@example.com", None, Some("dave@example.com")),
+            ]
+        );
+        assert_eq!(
+            envelope.report_from.and_then(|a| a.email).as_deref(),
+            Some("journal@example.com")
+        );
+        assert_eq!(
+            envelope.report_date.as_deref(),
+            Some("2026-03-03T09:15:00+01:00")
+        );
+
+        // Without the header, the envelope body alone marks a report
+        let unmarked = String::from_utf8_lossy(REPORT).replace("X-MS-Journal-Report:\r\n", "");
+        let message = unwrap(parse(unmarked.as_bytes()), &ParseOptions::default());
+        assert!(message.journal_envelope.is_some());
+    }
+
+    #[test]
+    fn other_messages_pass_through_unchanged() {
+        // A forwarded message whose body happens to start with a "Sender:" line, next to
+        // a second attachment
+        let forward = String::from_utf8_lossy(REPORT)
+            .replace("X-MS-Journal-Report:\r\n", "")
+            .replace(
+                "--journal--",
+                "--journal\r\nContent-Type: text/plain; name=\"notes.txt\"\r\n\r\nnotes\r\n--journal--",
+            );
+        for data in [
+            forward.as_bytes(),
+            include_bytes!("../../tests/corpus/mime/multipart.eml"),
+        ] {
+            let message = parse(data);
+            let (subject, attachments) = (message.subject.clone(), message.attachments.len());
+            let message = unwrap(message, &ParseOptions::default());
+            assert!(message.journal_envelope.is_none());
+            assert_eq!(message.subject, subject);
+            assert_eq!(message.attachments.len(), attachments);
+        }
+        assert!(looks_like_envelope("Sender: a@x\nTo: b@x"));
+        assert!(!looks_like_envelope(
+            "Note: see below\nSender: a@x\nTo: b@x"
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+//! Labels and flags the mail client a message was exported from kept with it. Gmail writes
+//! its labels to `X-Gmail-Labels` in Takeout mbox files, Dovecot and Thunderbird keywords
+//! end up in `X-Keywords`, and Apple Mail keeps flags in the property list after an .emlx
+//! message (see [`super::emlx`]). Labels are opaque: shown as written, never translated.
+
+use super::headers::decode_encoded_words;
+use super::Message;
+use serde::Serialize;
+
+/// Headers listing labels or keywords, comma-separated
+const LABEL_HEADERS: &[&str] = &["X-Gmail-Labels", "X-Keywords"];
+
+/// Flags of the mailbox a message was exported from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SourceFlags {
+    pub read: bool,
+    pub flagged: bool,
+    pub answered: bool,
+    pub draft: bool,
+}
+
+impl SourceFlags {
+    /// Names of the flags set, for exports
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.read, "read"),
+            (self.flagged, "flagged"),
+            (self.answered, "answered"),
+            (self.draft, "draft"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
+/// Labels and keywords in header order, each once
+pub fn source_labels(headers: &[(String, String)]) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for (_, value) in headers.iter().filter(|(name, _)| {
+        LABEL_HEADERS
+            .iter()
+            .any(|header| name.eq_ignore_ascii_case(header))
+    }) {
+        for label in split_labels(value) {
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+    }
+    labels
+}
+
+/// Fill in `source_labels` from the headers
+pub fn apply(message: &mut Message) {
+    message.source_labels = source_labels(&message.headers);
+}
+
+/// Split a comma-separated list; Gmail quotes labels with commas in them
+/// (`Inbox,"Clients, 2024",Important`)
+fn split_labels(value: &str) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => current.extend(chars.next()),
+            ',' if !quoted => labels.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    labels.push(current);
+    labels
+        .iter()
+        .map(|label| decode_encoded_words(label.trim()))
+        .filter(|label| !label.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    #[test]
+    fn labels_are_split_outside_quotes_and_kept_once() {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            include_bytes!("../../tests/fixtures/labels/gmail-takeout.eml"),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            message.source_labels,
+            [
+                "Inbox",
+                "Clients, 2024",
+                "Geöffnet",
+                "Important",
+                "$Label1",
+                "NonJunk"
+            ]
+        );
+        assert_eq!(message.source_flags, None);
+        assert_eq!(split_labels(r#""say \"hi\"", ,x"#), ["say \"hi\"", "x"]);
+    }
+}
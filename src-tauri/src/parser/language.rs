@@ -0,0 +1,448 @@
+//! The language a message's body is written in, for filtering a mixed folder and for the
+//! `lang` attribute of the rendered body. A `Content-Language` header wins when it fits the
+//! body's script; otherwise the text is classified by its script, and Latin or Cyrillic
+//! text by its commonest words. Quoted lines are left out, so a German reply to an English
+//! thread counts as German.
+
+use super::{text, Message};
+use serde::Serialize;
+
+/// Fewer letters than this are too little to tell; no guess is made
+const MIN_LETTERS: usize = 40;
+
+/// Common words a Latin-script body needs at least this many of to be classified
+const MIN_WORDS: usize = 3;
+
+/// Tags that name no language
+const NOT_LANGUAGES: &[&str] = &["und", "mul", "mis", "zxx"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Language {
+    /// ISO 639-1 code, or the primary subtag of `Content-Language` as written (lowercased)
+    pub code: String,
+    /// 1 for a header; for detection, how clearly the text points to this language
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+const SCRIPTS: [Script; 10] = [
+    Script::Latin,
+    Script::Cyrillic,
+    Script::Greek,
+    Script::Arabic,
+    Script::Hebrew,
+    Script::Devanagari,
+    Script::Thai,
+    Script::Hangul,
+    Script::Kana,
+    Script::Han,
+];
+
+/// Frequent words of the languages written in Latin script, picked to tell them apart
+const LATIN_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "that", "for", "with", "you", "this", "are", "have",
+            "we", "will", "please", "thanks", "be", "your", "it", "not",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "mit", "ich", "sie", "wir", "ein", "eine",
+            "zu", "den", "für", "auf", "auch", "bitte", "danke", "sind", "haben", "wird",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "des", "une", "pour", "que", "dans", "nous", "vous",
+            "pas", "sur", "avec", "merci", "ce", "sont", "du", "au",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "es", "que", "en", "una", "para", "por", "con", "del", "se",
+            "gracias", "su", "muy", "está", "lo", "al", "pero",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "che", "di", "per", "non", "una", "con", "sono", "gli", "del", "della", "grazie",
+            "questo", "ho", "un", "le", "si", "anche", "alla",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "niet", "dat", "ik", "voor", "met", "op", "zijn",
+            "wij", "jullie", "bedankt", "graag", "ook", "naar", "maar",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "que", "de", "não", "uma", "para", "com", "em", "por", "obrigado", "você", "do",
+            "da", "são", "mas", "como", "isso", "ao",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "är", "som", "på", "för", "jag", "inte", "med", "vi", "till",
+            "har", "tack", "av", "ett", "kan",
+        ],
+    ),
+    (
+        "da",
+        &[
+            "og", "at", "det", "er", "som", "på", "for", "jeg", "ikke", "med", "vi", "til", "har",
+            "tak", "af", "et", "kan",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "nie",
+            "się",
+            "na",
+            "jest",
+            "że",
+            "do",
+            "to",
+            "dla",
+            "jak",
+            "dziękuję",
+            "proszę",
+            "oraz",
+            "będzie",
+            "czy",
+            "ten",
+        ],
+    ),
+    (
+        "cs",
+        &[
+            "je", "se", "na", "že", "to", "pro", "jsem", "není", "jak", "děkuji", "prosím", "také",
+            "bude", "jsou", "ale",
+        ],
+    ),
+    (
+        "fi",
+        &[
+            "ja", "on", "ei", "että", "se", "olen", "kiitos", "mutta", "tämä", "ovat", "myös",
+            "kanssa", "voi", "ole",
+        ],
+    ),
+];
+
+/// Frequent words of the languages written in Cyrillic script
+const CYRILLIC_WORDS: &[(&str, &[&str])] = &[
+    (
+        "ru",
+        &[
+            "и",
+            "в",
+            "не",
+            "что",
+            "на",
+            "я",
+            "с",
+            "он",
+            "как",
+            "это",
+            "по",
+            "но",
+            "мы",
+            "вы",
+            "спасибо",
+            "пожалуйста",
+            "для",
+        ],
+    ),
+    (
+        "uk",
+        &[
+            "і",
+            "в",
+            "не",
+            "що",
+            "на",
+            "я",
+            "з",
+            "це",
+            "як",
+            "та",
+            "але",
+            "ми",
+            "ви",
+            "дякую",
+            "будь",
+            "для",
+            "є",
+        ],
+    ),
+    (
+        "bg",
+        &[
+            "и",
+            "в",
+            "не",
+            "че",
+            "на",
+            "аз",
+            "се",
+            "това",
+            "как",
+            "за",
+            "но",
+            "ние",
+            "вие",
+            "благодаря",
+            "да",
+            "от",
+        ],
+    ),
+];
+
+/// The language of `message`'s body: from `Content-Language` when it names one the body
+/// could be in, otherwise detected from the text
+pub fn language(message: &Message) -> Option<Language> {
+    let body = match (&message.body_text, &message.body_html) {
+        (Some(text), _) if !text.trim().is_empty() => text.clone(),
+        (_, Some(html)) => text::html_to_text(html, None),
+        _ => String::new(),
+    };
+    let body = unquoted(&body);
+    let detected = detect(&body);
+    let declared = message
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Language"))
+        .and_then(|(_, value)| from_header(value));
+    match (declared, detected) {
+        (Some(declared), Some(detected))
+            if script_of(&declared.code)
+                .zip(script_of(&detected.code))
+                .is_some_and(|(a, b)| a != b) =>
+        {
+            Some(detected)
+        }
+        (Some(declared), _) => Some(declared),
+        (None, detected) => detected,
+    }
+}
+
+/// Fill in `language` from the headers and body
+pub fn apply(message: &mut Message) {
+    message.language = language(message);
+}
+
+/// The first language `Content-Language` names (`de-DE, en` → `de`)
+pub fn from_header(value: &str) -> Option<Language> {
+    let tag = value.split(',').next()?.trim();
+    let primary = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+    let plausible = (2..=3).contains(&primary.len())
+        && primary.bytes().all(|b| b.is_ascii_lowercase())
+        && !NOT_LANGUAGES.contains(&primary.as_str());
+    plausible.then_some(Language {
+        code: primary,
+        confidence: 1.0,
+    })
+}
+
+/// The language `text` is most likely written in, or `None` when it's too short or
+/// matches none clearly enough
+pub fn detect(text: &str) -> Option<Language> {
+    let mut counts = [0usize; SCRIPTS.len()];
+    let mut letters = 0;
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        if let Some(script) = script(c) {
+            counts[SCRIPTS.iter().position(|s| *s == script).unwrap()] += 1;
+        }
+    }
+    if letters < MIN_LETTERS {
+        return None;
+    }
+    let count = |script: Script| counts[SCRIPTS.iter().position(|s| *s == script).unwrap()];
+    // Japanese mixes kana with Han characters; Han alone is Chinese
+    let cjk = count(Script::Kana) + count(Script::Han);
+    let (script, found) = SCRIPTS
+        .iter()
+        .map(|&script| match script {
+            Script::Kana | Script::Han => (script, cjk),
+            _ => (script, count(script)),
+        })
+        .max_by_key(|&(_, found)| found)?;
+    let share = found as f32 / letters as f32;
+    let code = match script {
+        Script::Latin => return by_words(text, LATIN_WORDS, share),
+        Script::Cyrillic => return by_words(text, CYRILLIC_WORDS, share),
+        Script::Greek => "el",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Devanagari => "hi",
+        Script::Thai => "th",
+        Script::Hangul => "ko",
+        Script::Kana | Script::Han if count(Script::Kana) * 10 >= cjk => "ja",
+        Script::Kana | Script::Han => "zh",
+    };
+    Some(Language {
+        code: code.to_string(),
+        confidence: round(share),
+    })
+}
+
+/// The language whose common words occur most often; confidence falls with the share of
+/// the runner-up's and with text in other scripts
+fn by_words(text: &str, languages: &[(&str, &[&str])], share: f32) -> Option<Language> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut hits: Vec<(&str, usize)> = languages
+        .iter()
+        .map(|(code, common)| {
+            let found = words
+                .iter()
+                .filter(|word| common.contains(&word.as_str()))
+                .count();
+            (*code, found)
+        })
+        .collect();
+    // Stable, so ties keep the order of the list
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.1));
+    let (code, best) = hits[0];
+    let runner_up = hits.get(1).map_or(0, |hit| hit.1);
+    if best < MIN_WORDS {
+        return None;
+    }
+    Some(Language {
+        code: code.to_string(),
+        confidence: round(share * best as f32 / (best + runner_up) as f32),
+    })
+}
+
+/// The script languages with this code are written in, for the ones detection knows
+fn script_of(code: &str) -> Option<Script> {
+    if LATIN_WORDS.iter().any(|(known, _)| *known == code) {
+        return Some(Script::Latin);
+    }
+    if CYRILLIC_WORDS.iter().any(|(known, _)| *known == code) {
+        return Some(Script::Cyrillic);
+    }
+    Some(match code {
+        "el" => Script::Greek,
+        "ar" | "fa" | "ur" => Script::Arabic,
+        "he" | "yi" => Script::Hebrew,
+        "hi" | "mr" | "ne" => Script::Devanagari,
+        "th" => Script::Thai,
+        "ko" => Script::Hangul,
+        "ja" => Script::Kana,
+        "zh" => Script::Han,
+        _ => return None,
+    })
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c as u32 {
+        0x41..=0x24F | 0x1E00..=0x1EFF => Script::Latin,
+        0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+        0x400..=0x52F => Script::Cyrillic,
+        0x590..=0x5FF => Script::Hebrew,
+        0x600..=0x6FF | 0x750..=0x77F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Script::Arabic,
+        0x900..=0x97F => Script::Devanagari,
+        0xE00..=0xE7F => Script::Thai,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Script::Han,
+        _ => return None,
+    })
+}
+
+/// `text` without quoted lines (`> ...`)
+fn unquoted(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('>'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn round(confidence: f32) -> f32 {
+    (confidence * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::MessageFormat;
+
+    fn message(body: &str, content_language: Option<&str>) -> Message {
+        let mut message = Message::new(MessageFormat::Eml);
+        message.body_text = Some(body.to_string());
+        if let Some(value) = content_language {
+            message
+                .headers
+                .push(("Content-Language".to_string(), value.to_string()));
+        }
+        message
+    }
+
+    #[test]
+    fn detects_bodies_and_prefers_a_plausible_header() {
+        let code =
+            |body: &str, header: Option<&str>| language(&message(body, header)).map(|l| l.code);
+        let german = "Hallo zusammen, anbei die Unterlagen für das Treffen. Bitte gebt mir \
+            bis Freitag Bescheid, ob ihr auch dabei sein könnt. Danke und viele Grüße!";
+        let english = "Hi all, please find attached the documents for the meeting. Let me \
+            know by Friday if you will be able to join us. Thanks and best regards!";
+        assert_eq!(code(german, None).as_deref(), Some("de"));
+        assert_eq!(code(english, None).as_deref(), Some("en"));
+        assert_eq!(
+            code("Здравствуйте! Спасибо за письмо, мы получили документы и ответим вам на этой неделе.", None)
+                .as_deref(),
+            Some("ru")
+        );
+        assert_eq!(
+            code("会議の資料を添付しました。ご確認のほど、よろしくお願いいたします。金曜日までにご返信ください。ありがとうございます。", None)
+                .as_deref(),
+            Some("ja")
+        );
+
+        // Quoted text doesn't count, and short bodies aren't guessed at
+        let reply = format!("{}\n\n> {}\n> {}", german, english, english);
+        assert_eq!(code(&reply, None).as_deref(), Some("de"));
+        assert_eq!(code("Thanks, see you then!", None), None);
+
+        // The header wins, and names a language even for a short body, unless it names
+        // one written in another script than the body
+        let declared = language(&message("Danke!", Some("de-CH, en"))).unwrap();
+        assert_eq!((declared.code.as_str(), declared.confidence), ("de", 1.0));
+        assert_eq!(code(english, Some("fr-FR")).as_deref(), Some("fr"));
+        assert_eq!(
+            code("Здравствуйте! Спасибо за письмо, мы получили документы и ответим вам на этой неделе.", Some("en-US"))
+                .as_deref(),
+            Some("ru")
+        );
+        assert_eq!(code(english, Some("und")).as_deref(), Some("en"));
+        assert_eq!(code(english, Some("x-klingon-ish")).as_deref(), Some("en"));
+    }
+}
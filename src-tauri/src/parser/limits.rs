@@ -0,0 +1,230 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Prefix of the error returned when parsing runs into a resource limit; followed by the
+/// [`Limit::name`] of the limit and a description
+pub const RESOURCE_LIMIT: &str = "ResourceLimit";
+
+/// Total allocations are never capped below this, so small files with many parts still parse
+const MIN_TOTAL_ALLOCATION: u64 = 64 * 1024 * 1024;
+
+/// Nor is a single stream, so tiny files can't trip the limit with valid content
+const MIN_STREAM_SIZE: u64 = 1024 * 1024;
+
+/// The limit a parse ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// A single stream or part declared larger than the input
+    StreamSize,
+    /// All allocations of one parse together
+    TotalAllocation,
+    /// Nesting of storages in a compound file
+    Depth,
+    /// Wall-clock time of one parse
+    Time,
+}
+
+impl Limit {
+    pub fn name(self) -> &'static str {
+        match self {
+            Limit::StreamSize => "stream_size",
+            Limit::TotalAllocation => "total_allocation",
+            Limit::Depth => "depth",
+            Limit::Time => "time",
+        }
+    }
+
+    /// Error message for this limit
+    pub fn exceeded(self, detail: impl std::fmt::Display) -> String {
+        format!("{}: {}: {}", RESOURCE_LIMIT, self.name(), detail)
+    }
+}
+
+/// The limit behind an error returned by the parser, if it was one
+pub fn tripped(error: &str) -> Option<Limit> {
+    let name = error
+        .strip_prefix(RESOURCE_LIMIT)?
+        .strip_prefix(": ")?
+        .split(':')
+        .next()?;
+    [
+        Limit::StreamSize,
+        Limit::TotalAllocation,
+        Limit::Depth,
+        Limit::Time,
+    ]
+    .into_iter()
+    .find(|limit| limit.name() == name)
+}
+
+/// Configurable resource limits of a parse
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Everything one parse allocates may be this many times the size of the input.
+    /// A single stream may be as large as the input: only compressed RTF bodies expand,
+    /// and they are small.
+    pub allocation_factor: u64,
+    /// How deep attached messages are parsed into [`super::Attachment::nested`]
+    pub max_depth: usize,
+    /// Wall-clock time one parse may take
+    pub time_budget: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            allocation_factor: 16,
+            max_depth: super::MAX_NESTING_DEPTH,
+            time_budget: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Counters {
+    max_stream: u64,
+    max_total: u64,
+    used: AtomicU64,
+    deadline: Instant,
+}
+
+/// What is left of the limits during one parse, shared by all nested parts of it.
+/// The default budget is unlimited (quick peeks, content sniffing).
+#[derive(Debug, Clone, Default)]
+pub struct Budget(Option<Arc<Counters>>);
+
+impl Budget {
+    /// Budget for parsing `input_len` bytes, starting now
+    pub fn new(limits: &Limits, input_len: usize) -> Self {
+        let input_len = input_len as u64;
+        Self(Some(Arc::new(Counters {
+            max_stream: input_len.max(MIN_STREAM_SIZE),
+            max_total: input_len
+                .saturating_mul(limits.allocation_factor)
+                .max(MIN_TOTAL_ALLOCATION),
+            used: AtomicU64::new(0),
+            deadline: Instant::now() + limits.time_budget,
+        })))
+    }
+
+    /// Account for allocating `len` bytes for `what` before doing so
+    pub fn reserve(&self, what: &str, len: u64) -> Result<(), String> {
+        let Some(counters) = &self.0 else {
+            return Ok(());
+        };
+        if len > counters.max_stream {
+            return Err(Limit::StreamSize.exceeded(format_args!(
+                "{} declares {} bytes, more than the {} allowed",
+                what, len, counters.max_stream
+            )));
+        }
+        counters
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(len)
+                    .filter(|&total| total <= counters.max_total)
+            })
+            .map(|_| ())
+            .map_err(|used| {
+                Limit::TotalAllocation.exceeded(format_args!(
+                    "reading {} would use {} bytes, more than the {} allowed",
+                    what,
+                    used.saturating_add(len),
+                    counters.max_total
+                ))
+            })
+    }
+
+    /// `Err` once the time budget is used up; call between units of work
+    pub fn check_time(&self) -> Result<(), String> {
+        match &self.0 {
+            Some(counters) if Instant::now() > counters.deadline => {
+                Err(Limit::Time.exceeded("parsing took too long and was stopped"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{msg, recovery, ParseOptions};
+
+    fn used(budget: &Budget) -> u64 {
+        budget.0.as_ref().unwrap().used.load(Ordering::Relaxed)
+    }
+
+    fn options(limits: Limits, input_len: usize) -> ParseOptions {
+        ParseOptions {
+            budget: Budget::new(&limits, input_len),
+            limits,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn limits_trip_with_structured_errors() {
+        let budget = Budget::new(&Limits::default(), 10);
+        let error = budget.reserve("stream", u32::MAX as u64).unwrap_err();
+        assert_eq!(tripped(&error), Some(Limit::StreamSize));
+        for _ in 0..64 {
+            budget.reserve("stream", MIN_STREAM_SIZE).unwrap();
+        }
+        let error = budget.reserve("stream", 1).unwrap_err();
+        assert_eq!(tripped(&error), Some(Limit::TotalAllocation));
+        assert_eq!(tripped("Not a valid compound file"), None);
+        assert!(Budget::default().reserve("stream", u64::MAX).is_ok());
+
+        let data = recovery::tests::sample_msg();
+        let expired = Limits {
+            time_budget: Duration::ZERO,
+            ..Limits::default()
+        };
+        let error = msg::parse_bytes(&data, &options(expired, data.len())).unwrap_err();
+        assert_eq!(tripped(&error), Some(Limit::Time));
+    }
+
+    /// Stream sizes patched to 4 GB and randomly corrupted bytes: parsing may fail, but
+    /// never reserves more than the budget allows
+    #[test]
+    fn crafted_headers_stay_within_budget() {
+        let original = recovery::tests::sample_msg();
+        let cap = original.len() as u64 * Limits::default().allocation_factor;
+        let cap = cap.max(MIN_TOTAL_ALLOCATION);
+
+        let mut inflated = original.clone();
+        let name = recovery::tests::utf16("__substg1.0_37010102");
+        let entry = inflated
+            .windows(name.len())
+            .position(|window| window == name.as_slice())
+            .unwrap();
+        inflated[entry + 120..entry + 124].copy_from_slice(&u32::MAX.to_le_bytes());
+        let options = options(Limits::default(), inflated.len());
+        match msg::parse_bytes(&inflated, &options) {
+            Ok(message) => assert!(message.attachments.iter().all(|a| a.size <= 40_000)),
+            Err(e) => assert_eq!(tripped(&e), Some(Limit::StreamSize), "{}", e),
+        }
+        assert!(used(&options.budget) <= cap);
+
+        // Seeded xorshift, so failures reproduce
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..200 {
+            let mut data = original.clone();
+            for _ in 0..1 + next() % 16 {
+                let pos = (next() % data.len() as u64) as usize;
+                data[pos] = next() as u8;
+            }
+            let options = self::options(Limits::default(), data.len());
+            let _ = msg::parse_bytes(&data, &options);
+            assert!(used(&options.budget) <= cap);
+        }
+    }
+}
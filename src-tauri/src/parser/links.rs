@@ -0,0 +1,151 @@
+use super::idn;
+use super::safelinks::{self, Wrapper};
+use serde::Serialize;
+
+/// URL schemes picked up from plain text bodies
+const TEXT_SCHEMES: &[&str] = &["https://", "http://"];
+
+/// A link in the message body; the host fields describe where it leads, which for wrapped
+/// links is `unwrapped`
+#[derive(Debug, Clone, Serialize)]
+pub struct Link {
+    pub url: String,
+    /// Mail security gateway that rewrote the link into a redirect through its service
+    pub wrapper: Option<Wrapper>,
+    /// Original destination of a wrapped link, decoded from `url`; `None` if the wrapper is
+    /// malformed
+    pub unwrapped: Option<String>,
+    /// Host in Unicode, for display
+    pub display_host: Option<String>,
+    /// Host in punycode form
+    pub ascii_host: Option<String>,
+    /// The host looks like an impersonation through lookalike letters
+    pub suspicious: bool,
+}
+
+/// Links from the `href`s of the HTML body and the URLs of the text body, without duplicates
+pub fn extract(text: Option<&str>, html: Option<&str>) -> Vec<Link> {
+    let mut urls: Vec<String> = Vec::new();
+    let hrefs = html.map(html_hrefs).unwrap_or_default();
+    let plain = text.map(text_urls).unwrap_or_default();
+    for url in hrefs.into_iter().chain(plain) {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls.into_iter().map(link).collect()
+}
+
+/// Link to `url` with its host checked
+pub(super) fn link(url: String) -> Link {
+    let wrapper = safelinks::detect(&url);
+    let unwrapped = wrapper.and_then(|wrapper| safelinks::decode(wrapper, &url));
+    let info = host(unwrapped.as_deref().unwrap_or(&url)).and_then(idn::host_info);
+    Link {
+        wrapper,
+        unwrapped,
+        suspicious: info.as_ref().is_some_and(|info| info.suspicious),
+        display_host: info.as_ref().map(|info| info.display_host.clone()),
+        ascii_host: info.map(|info| info.ascii_host),
+        url,
+    }
+}
+
+/// Host part of an absolute URL (`scheme://user@host:port/path`)
+pub(crate) fn host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    // IPv6 literals have no domain to check
+    if host.starts_with('[') {
+        return None;
+    }
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+/// Values of `href` attributes pointing to http(s) or mail addresses
+fn html_hrefs(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut urls = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find("href") {
+        let start = pos + found + 4;
+        pos = start;
+        let rest = &html[start..];
+        let Some(rest) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next(),
+            _ => rest.split(|c: char| c.is_whitespace() || c == '>').next(),
+        };
+        let Some(value) = value.map(|v| v.trim().replace("&amp;", "&")) else {
+            continue;
+        };
+        let scheme = value.to_ascii_lowercase();
+        if scheme.starts_with("http://") || scheme.starts_with("https://") {
+            urls.push(value);
+        }
+    }
+    urls
+}
+
+/// http(s) URLs written out in plain text
+fn text_urls(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+        .filter_map(|word| {
+            let lower = word.to_ascii_lowercase();
+            let start = TEXT_SCHEMES
+                .iter()
+                .filter_map(|scheme| lower.find(scheme))
+                .min()?;
+            // Trailing punctuation belongs to the sentence, not the URL
+            let url = word[start..].trim_end_matches(['.', ',', ';', ':', ')', '!', '?', '\'']);
+            Some(url.to_string())
+        })
+        .filter(|url| host(url).is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookalike_link_hosts_are_flagged() {
+        let html = r#"<a href="https://xn--80ak6aa92e.com/login?a=1&amp;b=2">Sign in</a>
+            <A HREF='http://example.org/'>ok</A>"#;
+        let text = "See https://pаypal.com/verify, or http://example.org/.";
+        let links = extract(Some(text), Some(html));
+
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].url, "https://xn--80ak6aa92e.com/login?a=1&b=2");
+        assert_eq!(links[0].display_host.as_deref(), Some("аррӏе.com"));
+        assert!(links[0].suspicious);
+        assert_eq!(links[1].ascii_host.as_deref(), Some("example.org"));
+        assert!(!links[1].suspicious);
+        assert_eq!(links[2].url, "https://pаypal.com/verify");
+        assert!(links[2].suspicious);
+    }
+
+    #[test]
+    fn wrapped_links_are_checked_at_their_destination() {
+        let html = r#"<a href="https://eur03.safelinks.protection.outlook.com/?url=https%3A%2F%2Fxn--80ak6aa92e.com%2F&amp;data=1">x</a>
+            <a href="https://urldefense.com/v3/__https://example.org/a*b__;!!x$">y</a>"#;
+        let links = extract(None, Some(html));
+
+        assert_eq!(links[0].wrapper, Some(Wrapper::SafeLinks));
+        assert_eq!(
+            links[0].unwrapped.as_deref(),
+            Some("https://xn--80ak6aa92e.com/")
+        );
+        assert_eq!(links[0].display_host.as_deref(), Some("аррӏе.com"));
+        assert!(links[0].suspicious);
+        // Malformed: the `*` has no replacement; the host is the gateway's
+        assert_eq!(links[1].wrapper, Some(Wrapper::UrlDefenseV3));
+        assert_eq!(links[1].unwrapped, None);
+        assert_eq!(links[1].ascii_host.as_deref(), Some("urldefense.com"));
+    }
+}
@@ -0,0 +1,180 @@
+use std::io::{BufRead, Write};
+
+/// Envelope sender used when a message has no usable From address
+const UNKNOWN_SENDER: &str = "MAILER-DAEMON";
+
+/// Append one message to an mbox stream (mboxrd flavor): a `From ` separator line with the
+/// envelope sender and date, the message with `From ` lines quoted, and a blank line
+pub fn write_message<W: Write>(
+    out: &mut W,
+    sender: Option<&str>,
+    date: Option<&str>,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let sender = sender
+        .map(str::trim)
+        .filter(|sender| !sender.is_empty() && !sender.contains(char::is_whitespace))
+        .unwrap_or(UNKNOWN_SENDER);
+    writeln!(out, "From {} {}", sender, envelope_date(date))?;
+
+    // Line endings become LF like in the separator lines; the last line always ends in one
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if is_quoted_from(line) {
+            out.write_all(b">")?;
+        }
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    out.write_all(b"\n")
+}
+
+/// Envelope date in the `asctime` form of `From ` lines, in UTC
+fn envelope_date(date: Option<&str>) -> String {
+    let date = date
+        .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .unwrap_or_else(chrono::Utc::now);
+    date.format("%a %b %e %H:%M:%S %Y").to_string()
+}
+
+/// `From ` or an already quoted `>From `, which both get one more `>`
+fn is_quoted_from(line: &[u8]) -> bool {
+    let unquoted = line.iter().position(|&b| b != b'>').unwrap_or(line.len());
+    line[unquoted..].starts_with(b"From ")
+}
+
+/// One message read from an mbox file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MboxMessage {
+    /// Sender from the `From ` separator line
+    pub envelope_sender: String,
+    /// Message with the separator removed and `>From ` quoting undone
+    pub data: Vec<u8>,
+}
+
+/// Streaming reader over the messages of an mbox file. A `From ` line starts a new message
+/// at the beginning of the file or after a blank line, so unquoted `From ` lines inside
+/// mboxo bodies usually survive as text.
+pub struct Reader<R: BufRead> {
+    input: R,
+    /// Separator line of the next message, already read
+    next_separator: Option<Vec<u8>>,
+    started: bool,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            next_separator: None,
+            started: false,
+        }
+    }
+
+    fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = std::io::Result<MboxMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let separator = match self.next_separator.take() {
+            Some(separator) => separator,
+            None if self.started => return None,
+            None => loop {
+                // Skip anything before the first separator
+                match self.read_line() {
+                    Ok(Some(line)) if line.starts_with(b"From ") => break line,
+                    Ok(Some(_)) => continue,
+                    Ok(None) => return None,
+                    Err(e) => return Some(Err(e)),
+                }
+            },
+        };
+        self.started = true;
+
+        let mut data: Vec<u8> = Vec::new();
+        let mut previous_blank = false;
+        loop {
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => return Some(Err(e)),
+            };
+            if previous_blank && line.starts_with(b"From ") {
+                self.next_separator = Some(line);
+                break;
+            }
+            let content = line
+                .strip_suffix(b"\n")
+                .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                .unwrap_or(&line);
+            previous_blank = content.is_empty();
+            if content.starts_with(b">") && is_quoted_from(content) {
+                data.extend_from_slice(&line[1..]);
+            } else {
+                data.extend_from_slice(&line);
+            }
+        }
+
+        // The blank line before the next separator belongs to the mbox format
+        if data.ends_with(b"\r\n\r\n") {
+            data.truncate(data.len() - 2);
+        } else if data.ends_with(b"\n\n") {
+            data.pop();
+        }
+
+        let separator = String::from_utf8_lossy(&separator);
+        let envelope_sender = separator["From ".len()..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Some(Ok(MboxMessage {
+            envelope_sender,
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting_round_trips() {
+        let first = b"Subject: One\r\n\r\nFrom the start\r\n>From quoted\r\nend\r\n";
+        let second = b"Subject: Two\n\nno newline at the end";
+        let mut mbox = Vec::new();
+        write_message(
+            &mut mbox,
+            Some("ann@example.com"),
+            Some("2024-03-05T09:07:00+01:00"),
+            first,
+        )
+        .unwrap();
+        write_message(&mut mbox, None, None, second).unwrap();
+
+        let text = String::from_utf8_lossy(&mbox);
+        assert!(text.starts_with("From ann@example.com Tue Mar  5 08:07:00 2024\n"));
+        assert!(text.contains("\n>From the start\n>>From quoted\n"));
+
+        let messages: Vec<MboxMessage> = Reader::new(&mbox[..]).map(Result::unwrap).collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].envelope_sender, "ann@example.com");
+        assert_eq!(
+            messages[0].data,
+            b"Subject: One\n\nFrom the start\n>From quoted\nend\n"
+        );
+        assert_eq!(messages[1].envelope_sender, "MAILER-DAEMON");
+        assert_eq!(messages[1].data, b"Subject: Two\n\nno newline at the end\n");
+    }
+}
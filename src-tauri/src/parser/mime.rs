@@ -0,0 +1,380 @@
+use super::headers::{self, Headers, Quirk};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+/// Multipart nesting deeper than this is treated as opaque content
+pub(super) const MAX_DEPTH: usize = 32;
+
+/// One MIME entity; leaf bodies are already transfer-decoded
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub headers: Headers,
+    pub content_type: ContentType,
+    pub body: Vec<u8>,
+    pub children: Vec<Part>,
+    /// What reading this part's header tolerated (not its children's)
+    pub quirks: Vec<Quirk>,
+}
+
+/// Parsed `Content-Type` (or `Content-Disposition`) value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    /// Lowercased `type/subtype` (or disposition type)
+    pub mime: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    /// Parameter value, matched case-insensitively
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_multipart(&self) -> bool {
+        self.mime.starts_with("multipart/")
+    }
+}
+
+impl Part {
+    /// Parsed `Content-Disposition` (`inline`, `attachment`), if declared
+    pub fn disposition(&self) -> Option<ContentType> {
+        self.headers
+            .get("Content-Disposition")
+            .map(parse_parameters)
+    }
+
+    /// File name from the disposition, falling back to the content type's `name`
+    pub fn file_name(&self) -> Option<String> {
+        self.disposition()
+            .and_then(|d| d.param("filename").map(str::to_string))
+            .or_else(|| self.content_type.param("name").map(str::to_string))
+            .map(|name| headers::decode_encoded_words(&name))
+            .filter(|name| !name.trim().is_empty())
+    }
+
+    /// Content-ID without the angle brackets
+    pub fn content_id(&self) -> Option<String> {
+        self.headers
+            .get("Content-ID")
+            .map(|id| {
+                id.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+            .filter(|id| !id.is_empty())
+    }
+}
+
+/// Parse a complete entity (a message or a body part)
+pub fn parse(data: &[u8]) -> Part {
+    parse_at_depth(data, 0)
+}
+
+fn parse_at_depth(data: &[u8], depth: usize) -> Part {
+    let (block, body, missing_separator) = headers::split_header_block_checked(data);
+    let (headers, mut quirks) = headers::parse_checked(block);
+    if missing_separator {
+        quirks.insert(0, Quirk::MissingSeparator);
+    }
+    let content_type = headers
+        .get("Content-Type")
+        .map(parse_parameters)
+        .filter(|ct| ct.mime.contains('/'))
+        .unwrap_or_else(|| ContentType {
+            mime: "text/plain".to_string(),
+            params: Vec::new(),
+        });
+
+    if content_type.is_multipart() && depth < MAX_DEPTH {
+        if let Some(boundary) = content_type.param("boundary").map(str::to_string) {
+            let children = split_multipart(body, &boundary)
+                .into_iter()
+                .map(|part| parse_at_depth(part, depth + 1))
+                .collect();
+            return Part {
+                headers,
+                content_type,
+                body: Vec::new(),
+                children,
+                quirks,
+            };
+        }
+    }
+
+    let encoding = headers
+        .get("Content-Transfer-Encoding")
+        .unwrap_or("7bit")
+        .trim()
+        .to_ascii_lowercase();
+    Part {
+        body: decode_transfer(body, &encoding),
+        headers,
+        content_type,
+        children: Vec::new(),
+        quirks,
+    }
+}
+
+/// Parse `value; key=value; key*=charset''encoded` with RFC 2231 continuations
+pub fn parse_parameters(value: &str) -> ContentType {
+    let mut pieces = split_unquoted(value, ';').into_iter();
+    let mime = pieces
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    // RFC 2231 sections (`name*0`, `name*1*`) are collected and joined in order
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut sections: Vec<(String, u32, bool, String)> = Vec::new();
+
+    for piece in pieces {
+        let Some((key, raw)) = piece.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = unquote(raw.trim());
+
+        let (name, extended) = match key.strip_suffix('*') {
+            Some(name) => (name.to_string(), true),
+            None => (key.clone(), false),
+        };
+        match name.split_once('*') {
+            Some((base, index)) if index.parse::<u32>().is_ok() => {
+                sections.push((base.to_string(), index.parse().unwrap(), extended, value));
+            }
+            _ if extended => params.push((name, decode_extended(&value))),
+            _ => params.push((name, value)),
+        }
+    }
+
+    sections.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+    let mut joined: Vec<(String, Vec<u8>, String)> = Vec::new();
+    for (name, index, extended, value) in sections {
+        if joined.last().map(|(n, _, _)| n != &name).unwrap_or(true) {
+            joined.push((name.clone(), Vec::new(), "us-ascii".to_string()));
+        }
+        let (_, bytes, charset) = joined.last_mut().unwrap();
+        if extended {
+            let text = if index == 0 {
+                // Only the first section carries charset'language'
+                let mut fields = value.splitn(3, '\'');
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some(cs), Some(_), Some(rest)) => {
+                        if !cs.is_empty() {
+                            *charset = cs.to_string();
+                        }
+                        rest.to_string()
+                    }
+                    _ => value,
+                }
+            } else {
+                value
+            };
+            bytes.extend(percent_decode(&text));
+        } else {
+            bytes.extend(value.as_bytes());
+        }
+    }
+    for (name, bytes, charset) in joined {
+        if !params.iter().any(|(key, _)| key == &name) {
+            params.push((name, super::charset::decode(&bytes, &charset)));
+        }
+    }
+
+    ContentType { mime, params }
+}
+
+/// Decode `charset'language'percent-encoded` (RFC 2231 single-section form)
+fn decode_extended(value: &str) -> String {
+    let mut fields = value.splitn(3, '\'');
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some(charset), Some(_), Some(text)) => {
+            let charset = if charset.is_empty() {
+                "us-ascii"
+            } else {
+                charset
+            };
+            super::charset::decode(&percent_decode(text), charset)
+        }
+        _ => String::from_utf8_lossy(&percent_decode(value)).into_owned(),
+    }
+}
+
+pub(crate) fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = bytes.get(i + 1..i + 3).and_then(headers::hex_byte) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Split at `separator` outside double quotes
+fn split_unquoted(value: &str, separator: char) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' && in_quotes {
+            current.push(c);
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+            current.push(c);
+        } else if c == separator && !in_quotes {
+            pieces.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    pieces.push(current);
+    pieces
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    if let Some(next) = chars.next() {
+                        out.push(next);
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Body parts between `--boundary` delimiter lines (preamble and epilogue dropped)
+pub(super) fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let line_end = body[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|i| pos + i + 1)
+            .unwrap_or(body.len());
+        let line = &body[pos..line_end];
+
+        if line.starts_with(delimiter) {
+            let rest = trim_line_end(&line[delimiter.len()..]);
+            let closing = rest.starts_with(b"--");
+            if closing || rest.iter().all(|b| b.is_ascii_whitespace()) {
+                if let Some(s) = start {
+                    parts.push(strip_final_newline(&body[s..pos]));
+                }
+                if closing {
+                    return parts;
+                }
+                start = Some(line_end);
+            }
+        }
+        pos = line_end;
+    }
+
+    // Unterminated multipart: keep the last part
+    if let Some(s) = start {
+        parts.push(&body[s..]);
+    }
+    parts
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|b| !matches!(b, b'\r' | b'\n'))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    &line[..end]
+}
+
+/// The line break before a delimiter belongs to the delimiter
+fn strip_final_newline(part: &[u8]) -> &[u8] {
+    let part = part.strip_suffix(b"\n").unwrap_or(part);
+    part.strip_suffix(b"\r").unwrap_or(part)
+}
+
+/// Undo base64 or quoted-printable; other encodings are passed through
+pub fn decode_transfer(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
+        "base64" => decode_base64_lenient(body),
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    }
+}
+
+/// Base64 ignoring whitespace and garbage, as mail clients do
+fn decode_base64_lenient(body: &[u8]) -> Vec<u8> {
+    let mut clean: Vec<u8> = body
+        .iter()
+        .copied()
+        .filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/'))
+        .collect();
+    // Drop a dangling sextet that can't form a byte, then re-pad
+    if clean.len() % 4 == 1 {
+        clean.pop();
+    }
+    while !clean.len().is_multiple_of(4) {
+        clean.push(b'=');
+    }
+    STANDARD.decode(&clean).unwrap_or_default()
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        if body[i] == b'=' {
+            // Soft line break: "=\r\n" or "=\n", possibly with trailing whitespace
+            let mut j = i + 1;
+            while j < body.len() && matches!(body[j], b' ' | b'\t') {
+                j += 1;
+            }
+            if body.get(j) == Some(&b'\n') {
+                i = j + 1;
+                continue;
+            }
+            if body.get(j) == Some(&b'\r') && body.get(j + 1) == Some(&b'\n') {
+                i = j + 2;
+                continue;
+            }
+            if let Some(byte) = body.get(i + 1..i + 3).and_then(headers::hex_byte) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(body[i]);
+        i += 1;
+    }
+    out
+}
@@ -0,0 +1,333 @@
+use super::eml::{self, Role};
+use super::mime::{self, ContentType, Part};
+use super::{Message, MessageFormat};
+use serde::Serialize;
+
+/// The part structure of a message file as the parser saw it, for diagnosing messages that
+/// render wrong
+#[derive(Debug, Clone, Serialize)]
+pub struct MimeTree {
+    pub root: MimeNode,
+    /// Id of the part shown as the body, if any
+    pub displayed: Option<String>,
+    /// How the bodies were chosen, one sentence each
+    pub reasoning: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MimeNode {
+    /// `1` for the message itself, `1.2` for its second part, `1.2.1` for the first part of
+    /// that, ...
+    pub id: String,
+    pub content_type: String,
+    pub params: Vec<(String, String)>,
+    pub transfer_encoding: Option<String>,
+    pub disposition: Option<String>,
+    pub file_name: Option<String>,
+    /// Decoded size in bytes
+    pub size: usize,
+    pub content_id: Option<String>,
+    pub role: Option<NodeRole>,
+    pub children: Vec<MimeNode>,
+}
+
+/// What the parser made of a part
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRole {
+    TextBody,
+    HtmlBody,
+    TextContinuation,
+    Attachment,
+    /// Attachment the message hides (inline image shown by the body, or marked hidden)
+    HiddenAttachment,
+}
+
+/// The tree of the message in `data`: the MIME hierarchy of an EML file, the bodies and
+/// attachments of an MSG file (which has no MIME structure of its own, so it is parsed with
+/// `parse`)
+pub fn build(
+    format: MessageFormat,
+    data: &[u8],
+    parse: impl FnOnce() -> Result<Message, String>,
+) -> Result<MimeTree, String> {
+    match format {
+        MessageFormat::Eml => Ok(eml_tree(data)),
+        MessageFormat::Emlx => Ok(eml_tree(
+            super::emlx::split(data).map_or(data, |(message, _)| message),
+        )),
+        MessageFormat::Msg => Ok(msg_tree(&parse()?)),
+    }
+}
+
+impl MimeTree {
+    /// The tree without file names and content ids, for privacy mode diagnostics
+    pub fn without_names(mut self) -> Self {
+        fn redact(node: &mut MimeNode) {
+            let redacted = || "<redacted>".to_string();
+            node.file_name = node.file_name.take().map(|_| redacted());
+            node.content_id = node.content_id.take().map(|_| redacted());
+            for (key, value) in &mut node.params {
+                if key.eq_ignore_ascii_case("name") || key.eq_ignore_ascii_case("filename") {
+                    *value = redacted();
+                }
+            }
+            node.children.iter_mut().for_each(redact);
+        }
+        redact(&mut self.root);
+        self
+    }
+}
+
+/// Body selection state while walking a MIME tree in document order, as `eml::collect` does
+#[derive(Default)]
+struct Walk {
+    text: Option<String>,
+    html: Option<String>,
+    continuations: Vec<String>,
+    reasoning: Vec<String>,
+}
+
+fn eml_tree(data: &[u8]) -> MimeTree {
+    let mut walk = Walk::default();
+    let root = eml_node(&mime::parse(data), "1".to_string(), &mut walk);
+
+    let mut reasoning = walk.reasoning;
+    let displayed = walk.html.clone().or_else(|| walk.text.clone());
+    match (&walk.html, &walk.text) {
+        (Some(html), Some(text)) => {
+            let container = common_parent(html, text)
+                .and_then(|id| find(&root, &id))
+                .map(|node| format!("{} ({})", node.content_type, node.id));
+            reasoning.insert(
+                0,
+                format!(
+                    "{}: chose text/html ({}) over text/plain ({}); the first part of each type \
+                     is used, whatever their order",
+                    container.unwrap_or_else(|| "message".to_string()),
+                    html,
+                    text
+                ),
+            );
+        }
+        (Some(html), None) => reasoning.insert(
+            0,
+            format!("Showing text/html ({}); there is no plain text body", html),
+        ),
+        (None, Some(text)) => reasoning.insert(
+            0,
+            format!("Showing text/plain ({}); there is no HTML body", text),
+        ),
+        (None, None) => reasoning.insert(0, "No part qualifies as a body".to_string()),
+    }
+    if !walk.continuations.is_empty() {
+        reasoning.push(format!(
+            "Unnamed text/plain parts {} are appended to the plain text body",
+            walk.continuations.join(", ")
+        ));
+    }
+    MimeTree {
+        root,
+        displayed,
+        reasoning,
+    }
+}
+
+fn eml_node(part: &Part, id: String, walk: &mut Walk) -> MimeNode {
+    let disposition = part.disposition();
+    let mut node = MimeNode {
+        id: id.clone(),
+        content_type: part.content_type.mime.clone(),
+        params: part.content_type.params.clone(),
+        transfer_encoding: part
+            .headers
+            .get("Content-Transfer-Encoding")
+            .map(|encoding| encoding.trim().to_ascii_lowercase()),
+        disposition: disposition.as_ref().map(|d: &ContentType| d.mime.clone()),
+        file_name: part.file_name(),
+        size: part.body.len(),
+        content_id: part.content_id(),
+        role: None,
+        children: Vec::new(),
+    };
+
+    if part.content_type.is_multipart() {
+        if part.children.is_empty() {
+            walk.reasoning.push(format!(
+                "{} ({}) has no parts (missing or wrong boundary); its content is lost",
+                node.content_type, id
+            ));
+        }
+        node.children = part
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, child)| eml_node(child, format!("{}.{}", id, i + 1), walk))
+            .collect();
+        return node;
+    }
+
+    let role = eml::role(part, walk.text.is_some(), walk.html.is_some());
+    node.role = Some(match role {
+        Role::TextBody => {
+            walk.text = Some(id);
+            NodeRole::TextBody
+        }
+        Role::HtmlBody => {
+            walk.html = Some(id);
+            NodeRole::HtmlBody
+        }
+        Role::TextContinuation => {
+            walk.continuations.push(id);
+            NodeRole::TextContinuation
+        }
+        Role::Attachment => {
+            let declared_attachment = disposition.is_some_and(|d| d.mime == "attachment");
+            let first = match node.content_type.as_str() {
+                "text/plain" => walk.text.as_deref(),
+                "text/html" => walk.html.as_deref(),
+                _ => None,
+            };
+            if declared_attachment && node.content_type.starts_with("text/") {
+                walk.reasoning.push(format!(
+                    "{} ({}) is not a body: Content-Disposition: attachment",
+                    node.content_type, id
+                ));
+            } else if let Some(first) = first {
+                walk.reasoning.push(format!(
+                    "{} ({}) is an attachment: {} is the body of that type",
+                    node.content_type, id, first
+                ));
+            }
+            NodeRole::Attachment
+        }
+    });
+    node
+}
+
+/// Id of the closest multipart containing both parts
+fn common_parent(a: &str, b: &str) -> Option<String> {
+    let a: Vec<&str> = a.split('.').collect();
+    let b: Vec<&str> = b.split('.').collect();
+    let shared = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let shared = shared.min(a.len() - 1).min(b.len() - 1);
+    (shared > 0).then(|| a[..shared].join("."))
+}
+
+fn find<'a>(node: &'a MimeNode, id: &str) -> Option<&'a MimeNode> {
+    if node.id == id {
+        return Some(node);
+    }
+    node.children.iter().find_map(|child| find(child, id))
+}
+
+fn msg_tree(message: &Message) -> MimeTree {
+    let mut children = Vec::new();
+    let mut body = |content_type: &str, text: &Option<String>, role: NodeRole| {
+        let text = text.as_ref()?;
+        let id = format!("1.{}", children.len() + 1);
+        children.push(leaf(id.clone(), content_type, text.len(), role));
+        Some(id)
+    };
+    let text = body("text/plain", &message.body_text, NodeRole::TextBody);
+    let html = body("text/html", &message.body_html, NodeRole::HtmlBody);
+    for attachment in &message.attachments {
+        let mut node = leaf(
+            format!("1.{}", children.len() + 1),
+            attachment
+                .detected_mime
+                .as_deref()
+                .or(attachment.declared_mime.as_deref())
+                .unwrap_or("application/octet-stream"),
+            attachment.size,
+            match attachment.hidden {
+                true => NodeRole::HiddenAttachment,
+                false => NodeRole::Attachment,
+            },
+        );
+        node.file_name = Some(attachment.file_name.clone());
+        node.content_id = attachment.content_id.clone();
+        children.push(node);
+    }
+
+    let reasoning = vec![match (&html, &text) {
+        (Some(html), _) => format!(
+            "Showing the HTML body ({}, PidTagHtml); the plain text body (PidTagBody) is {}",
+            html,
+            match text {
+                Some(_) => "kept for text exports",
+                None => "missing",
+            }
+        ),
+        (None, Some(text)) => format!(
+            "Showing the plain text body ({}); there is no HTML body",
+            text
+        ),
+        (None, None) => "The message has neither a plain text nor an HTML body".to_string(),
+    }];
+    let mut root = leaf(
+        "1".to_string(),
+        "application/vnd.ms-outlook",
+        0,
+        NodeRole::Attachment,
+    );
+    root.role = None;
+    root.size = children.iter().map(|child| child.size).sum();
+    root.children = children;
+    MimeTree {
+        root,
+        displayed: html.or(text),
+        reasoning,
+    }
+}
+
+fn leaf(id: String, content_type: &str, size: usize, role: NodeRole) -> MimeNode {
+    MimeNode {
+        id,
+        content_type: content_type.to_string(),
+        params: Vec::new(),
+        transfer_encoding: None,
+        disposition: None,
+        file_name: None,
+        size,
+        content_id: None,
+        role: Some(role),
+        children: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternative_bodies_and_attachments_are_explained() {
+        let eml = b"Content-Type: multipart/mixed; boundary=outer\r\n\r\n\
+            --outer\r\nContent-Type: multipart/alternative; boundary=inner\r\n\r\n\
+            --inner\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nHello\r\n\
+            --inner\r\nContent-Type: text/html; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\r\n<p>Hello</p>\r\n\
+            --inner--\r\n\
+            --outer\r\nContent-Type: text/html; name=\"page.html\"\r\n\
+            Content-Disposition: attachment; filename=\"page.html\"\r\n\r\n<p>Saved</p>\r\n\
+            --outer--\r\n";
+        let tree = eml_tree(eml);
+        assert_eq!(tree.displayed.as_deref(), Some("1.1.2"));
+        assert_eq!(tree.root.children.len(), 2);
+        let alternative = &tree.root.children[0];
+        assert_eq!(alternative.content_type, "multipart/alternative");
+        assert_eq!(alternative.children[0].role, Some(NodeRole::TextBody));
+        assert_eq!(
+            alternative.children[1].transfer_encoding.as_deref(),
+            Some("quoted-printable")
+        );
+        let attachment = &tree.root.children[1];
+        assert_eq!(attachment.role, Some(NodeRole::Attachment));
+        assert_eq!(attachment.disposition.as_deref(), Some("attachment"));
+        assert_eq!(attachment.file_name.as_deref(), Some("page.html"));
+        assert!(tree.reasoning[0].starts_with(
+            "multipart/alternative (1.1): chose text/html (1.1.2) over text/plain (1.1.1)"
+        ));
+        assert!(tree.reasoning[1].contains("1.2"));
+    }
+}
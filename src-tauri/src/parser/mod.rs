@@ -0,0 +1,776 @@
+pub mod accessible;
+pub mod calendar;
+pub mod charset;
+pub mod delivery;
+pub mod eml;
+pub mod emlx;
+pub mod gateway;
+pub mod grouping;
+pub mod headers;
+pub mod html;
+pub mod idn;
+pub mod journal;
+pub mod labels;
+pub mod language;
+pub mod limits;
+pub mod links;
+pub mod mbox;
+pub mod mime;
+pub mod mime_tree;
+pub mod msg;
+pub mod named;
+pub mod ole;
+pub mod preview;
+pub mod protection;
+pub mod recipients;
+pub mod recovery;
+pub mod redact;
+pub mod remote_images;
+pub mod rendering;
+pub mod rtf;
+pub mod safelinks;
+pub mod sanitize;
+pub mod signature;
+pub mod sizes;
+pub mod smime;
+pub mod sniff;
+pub mod spoofing;
+pub mod storage;
+pub mod text;
+pub mod tnef;
+pub mod trackers;
+
+use crate::i18n::Localized;
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+/// How much of an .eml file is read when only headers are needed
+const PEEK_HEADER_LIMIT: u64 = 64 * 1024;
+
+/// How deep attached messages are parsed into [`Attachment::nested`] by default (see
+/// [`limits::Limits::max_depth`]); deeper ones stay plain attachments
+pub const MAX_NESTING_DEPTH: usize = 8;
+
+/// Supported message container formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    Msg,
+    Eml,
+    /// Apple Mail's .emlx: an .eml in a frame (see `emlx`), only ever from the extension;
+    /// parsed messages have the `Eml` format
+    Emlx,
+}
+
+impl MessageFormat {
+    /// Format implied by a file extension (case-insensitive), that of the readable name for
+    /// mangled downloads (see `filenames::readable_name`)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = crate::filenames::effective_extension(path)?;
+        match ext.as_str() {
+            "msg" => Some(MessageFormat::Msg),
+            "eml" => Some(MessageFormat::Eml),
+            "emlx" => Some(MessageFormat::Emlx),
+            _ => None,
+        }
+    }
+}
+
+/// Importance the sender set, when other than normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Importance {
+    Low,
+    High,
+}
+
+impl Importance {
+    /// From `Importance`, `X-Priority` or `Priority` header values
+    pub fn from_header(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "high" | "urgent" => Some(Importance::High),
+            "low" | "non-urgent" => Some(Importance::Low),
+            // X-Priority: "1 (Highest)" to "5 (Lowest)"
+            _ => match value.chars().next() {
+                Some('1' | '2') => Some(Importance::High),
+                Some('4' | '5') => Some(Importance::Low),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Whether the sender is on the user's allow or block list (see `sender_rules`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SenderTrust {
+    Trusted,
+    Blocked,
+    #[default]
+    Unknown,
+}
+
+/// How the message view shows a message, from the sender's preferences (see
+/// `sender_prefs`) over the global settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DisplayPreferences {
+    /// Remote images are fetched and inlined as `data:` URLs when the message is opened
+    /// (see `parse_message`); trackers stay blocked
+    pub load_remote_images: bool,
+    /// Start with quoted text collapsed
+    pub collapse_quotes: bool,
+    /// Charset the bodies were decoded with instead of the declared one
+    pub encoding_override: Option<String>,
+    /// Sender preference that applied (`anna@example.com` or `*@example.com`), if any
+    pub matched: Option<String>,
+}
+
+/// Sensitivity the sender set, when other than normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sensitivity {
+    Personal,
+    Private,
+    Confidential,
+}
+
+impl Sensitivity {
+    /// From the `Sensitivity` header (RFC 4021: Personal, Private, Company-Confidential)
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "personal" => Some(Sensitivity::Personal),
+            "private" => Some(Sensitivity::Private),
+            "company-confidential" | "confidential" => Some(Sensitivity::Confidential),
+            _ => None,
+        }
+    }
+}
+
+/// Options affecting how message content is decoded
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Decode bodies with their declared charset even when the content suggests another
+    pub trust_declared_charset: bool,
+    /// Tracker URL fragments in addition to [`trackers::KNOWN_TRACKER_PATTERNS`]
+    pub tracker_patterns: Vec<String>,
+    /// Give links wrapped by a mail security gateway a `title` with their original
+    /// destination in the HTML body (see [`safelinks::annotate_titles`])
+    pub annotate_wrapped_links: bool,
+    /// Decode 8-bit bodies with this charset whatever they declare (see
+    /// `preferred_encoding_override` of sender preferences)
+    pub charset_override: Option<String>,
+    /// Leave bodies that were double-encoded as they decode instead of repairing them (see
+    /// [`charset::repair_mojibake`])
+    pub keep_mojibake: bool,
+    /// Keep only these recipients of each type; the others are counted (see
+    /// [`recipients::INLINE_RECIPIENTS`])
+    pub recipient_window: Option<recipients::Window>,
+    /// Resource limits protecting against crafted files
+    pub limits: limits::Limits,
+    /// Resources left to the current parse, shared by its nested parts; set by [`parse_bytes`]
+    pub budget: limits::Budget,
+}
+
+impl ParseOptions {
+    /// Decode a body declared as `declared`, honoring `charset_override`, and repair it if it
+    /// was double-encoded
+    pub(crate) fn decode_body(&self, bytes: &[u8], declared: Option<&str>) -> charset::Decoded {
+        let mut decoded = match &self.charset_override {
+            Some(label) => charset::decode_with_detection(bytes, Some(label), true),
+            None => charset::decode_with_detection(bytes, declared, self.trust_declared_charset),
+        };
+        if !self.keep_mojibake {
+            if let Some((text, misread_as)) = charset::repair_mojibake(&decoded.text) {
+                decoded.text = text;
+                decoded.repaired = Some(misread_as);
+            }
+        }
+        decoded
+    }
+}
+
+/// The bodies of a message decoded again with a charset chosen by hand
+#[derive(Debug, Clone, Serialize)]
+pub struct RedecodedBodies {
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+    pub body_encodings: Vec<charset::BodyEncoding>,
+    pub body_repaired: bool,
+    pub charset_override: Option<String>,
+}
+
+impl From<Message> for RedecodedBodies {
+    fn from(message: Message) -> Self {
+        Self {
+            body_text: message.body_text,
+            body_html: message.body_html,
+            body_encodings: message.body_encodings,
+            body_repaired: message.body_repaired,
+            charset_override: message.charset_override,
+        }
+    }
+}
+
+/// Minimal subject/sender extracted without parsing bodies or attachments
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MessageSummary {
+    pub subject: Option<String>,
+    pub sender: Option<String>,
+}
+
+/// Name and email of a sender or recipient
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Address {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Domain of `email` in Unicode, for display
+    pub display_host: Option<String>,
+    /// Domain of `email` in punycode form
+    pub ascii_host: Option<String>,
+    /// The domain looks like an impersonation through lookalike letters
+    pub suspicious: bool,
+}
+
+impl Address {
+    /// Build an address, resolving the host of `email` in both forms
+    pub fn new(name: Option<String>, email: Option<String>) -> Self {
+        let host = email.as_deref().and_then(idn::email_host);
+        Self {
+            name,
+            email,
+            suspicious: host.as_ref().is_some_and(|host| host.suspicious),
+            display_host: host.as_ref().map(|host| host.display_host.clone()),
+            ascii_host: host.map(|host| host.ascii_host),
+        }
+    }
+}
+
+/// Fully parsed message; attachment content stays in the backend
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub format: MessageFormat,
+    /// MAPI message class (`IPM.Note`, ...); only known for .msg files
+    pub message_class: Option<String>,
+    pub subject: Option<String>,
+    pub from: Option<Address>,
+    pub to: Vec<Address>,
+    pub cc: Vec<Address>,
+    pub bcc: Vec<Address>,
+    /// Recipients of each type, including any left out of `to`, `cc` and `bcc`
+    pub recipient_counts: recipients::RecipientCounts,
+    pub total_recipients: usize,
+    /// Only a window of the recipients was kept; `get_all_recipients` pages through them
+    pub recipients_truncated: bool,
+    /// Envelope recipients from the delivery headers (see [`delivery::delivered_to`])
+    pub delivered_to: Vec<String>,
+    /// None of `delivered_to` is in To or Cc
+    pub was_bcc: bool,
+    /// Total size and count per category of the attachments (see [`grouping`])
+    pub attachment_summary: grouping::AttachmentSummary,
+    /// Spam and phishing verdicts mail gateways left in the headers (see [`gateway`])
+    pub gateway_verdicts: Vec<gateway::GatewayVerdict>,
+    /// Labels and keywords of the mailbox the message was exported from (see [`labels`])
+    pub source_labels: Vec<String>,
+    /// Read, flagged, answered and draft flags, when the file recorded them (.emlx)
+    pub source_flags: Option<labels::SourceFlags>,
+    /// RFC 3339 timestamp
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+    pub importance: Option<Importance>,
+    pub sensitivity: Option<Sensitivity>,
+    /// Purview sensitivity label (`msip_labels`)
+    pub sensitivity_label: Option<protection::LabelInfo>,
+    /// Set for rights-managed messages, whose content can't be shown
+    pub content_protected: Option<protection::ContentProtected>,
+    /// Voting buttons of Outlook workflow messages; only known for .msg files
+    pub voting: Option<named::Voting>,
+    /// Fields of custom Outlook forms and user-defined fields; only known for .msg files
+    pub custom_fields: Vec<named::CustomField>,
+    pub headers: Vec<(String, String)>,
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+    /// A body was stored double-encoded and has been repaired; `body_encodings` and
+    /// `warnings` say which
+    pub body_repaired: bool,
+    /// Links found in the bodies, with their hosts checked for lookalikes
+    pub links: Vec<links::Link>,
+    /// Remote images in the HTML body that look like tracking pixels
+    pub trackers: Vec<trackers::TrackerInfo>,
+    /// Share of the HTML body's area that remote images take up (see
+    /// [`remote_images::area_ratio`]), to offer loading them for image-only messages
+    pub remote_image_area_ratio: f32,
+    /// Signature block at the end of `body_text`, for exports that leave it out
+    pub signature: Option<signature::SignatureRange>,
+    /// Language of the body, declared or detected (see [`language`])
+    pub language: Option<language::Language>,
+    /// Charset decisions for the bodies that were decoded from legacy encodings
+    pub body_encodings: Vec<charset::BodyEncoding>,
+    /// Charset the bodies were decoded with whatever they declare: chosen for the file or
+    /// its sender, or asked for with `reparse_with_encoding`
+    pub charset_override: Option<String>,
+    pub attachments: Vec<Attachment>,
+    /// Problems that didn't prevent parsing (missing streams, undecodable parts, ...)
+    pub warnings: Vec<Localized>,
+    /// Recovered from a damaged file; `warnings` describe what was lost
+    pub partial: bool,
+    /// Envelope of the journal report this message was unwrapped from
+    pub journal_envelope: Option<journal::JournalEnvelope>,
+    /// Set when the message came wrapped in an S/MIME blob (see [`smime::unwrap`])
+    pub smime: Option<smime::SmimeWrapping>,
+    /// Pasted content the app wrote to a temp file (see `open_from_clipboard`)
+    pub from_clipboard: bool,
+    /// The sender checked against the allow and block lists when opened
+    pub sender_trust: SenderTrust,
+    /// Signs of a spoofed sender, from the checks enabled in settings
+    pub sender_flags: Vec<spoofing::SenderFlag>,
+    pub display_preferences: DisplayPreferences,
+}
+
+impl Message {
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            message_class: None,
+            subject: None,
+            from: None,
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            recipient_counts: recipients::RecipientCounts::default(),
+            total_recipients: 0,
+            recipients_truncated: false,
+            delivered_to: Vec::new(),
+            was_bcc: false,
+            attachment_summary: grouping::AttachmentSummary::default(),
+            gateway_verdicts: Vec::new(),
+            source_labels: Vec::new(),
+            source_flags: None,
+            date: None,
+            message_id: None,
+            importance: None,
+            sensitivity: None,
+            sensitivity_label: None,
+            content_protected: None,
+            voting: None,
+            custom_fields: Vec::new(),
+            headers: Vec::new(),
+            body_text: None,
+            body_html: None,
+            body_repaired: false,
+            links: Vec::new(),
+            trackers: Vec::new(),
+            remote_image_area_ratio: 0.0,
+            signature: None,
+            language: None,
+            body_encodings: Vec::new(),
+            charset_override: None,
+            attachments: Vec::new(),
+            warnings: Vec::new(),
+            partial: false,
+            journal_envelope: None,
+            smime: None,
+            from_clipboard: false,
+            sender_trust: SenderTrust::Unknown,
+            sender_flags: Vec::new(),
+            display_preferences: DisplayPreferences::default(),
+        }
+    }
+}
+
+/// Attachment metadata, with the type declared by the sender and the one found in the content
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub index: usize,
+    /// Name as stored in the message
+    pub file_name: String,
+    pub declared_mime: Option<String>,
+    pub detected_mime: Option<String>,
+    /// `file_name` with an extension matching the detected content
+    pub suggested_filename: String,
+    pub size: usize,
+    /// SHA-256 of the content (hex), to verify saved copies
+    pub sha256: String,
+    pub content_id: Option<String>,
+    /// What kind of file this is, for grouping (see [`grouping`])
+    pub category: grouping::AttachmentCategory,
+    /// Position in the attachment strip; hidden attachments come last
+    pub display_order: usize,
+    /// Outlook wouldn't list this attachment (inline image, signature logo, ...)
+    pub hidden: bool,
+    /// The HTML body refers to this attachment through its content id
+    pub referenced_in_body: bool,
+    /// The attached message (.msg, .eml, `message/rfc822`), parsed with its own attachments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested: Option<Box<Message>>,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    /// Build an attachment and sniff its content; unnamed attachments get a generated name
+    pub fn new(
+        index: usize,
+        file_name: Option<String>,
+        declared_mime: Option<String>,
+        content_id: Option<String>,
+        data: Vec<u8>,
+    ) -> Self {
+        let file_name = file_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("attachment-{}", index + 1));
+        let declared_mime = declared_mime.filter(|mime| !mime.trim().is_empty());
+        let sniffed = sniff::sniff(&file_name, declared_mime.as_deref(), &data);
+        let category =
+            grouping::category(&file_name, sniffed.detected_mime, declared_mime.as_deref());
+
+        Self {
+            index,
+            file_name,
+            declared_mime,
+            detected_mime: sniffed.detected_mime.map(str::to_string),
+            suggested_filename: sniffed.suggested_filename,
+            size: data.len(),
+            sha256: sha256_hex(&data),
+            content_id,
+            category,
+            display_order: index,
+            hidden: false,
+            referenced_in_body: false,
+            nested: None,
+            data,
+        }
+    }
+}
+
+impl Attachment {
+    /// Format of an attached message, if this attachment is one
+    pub fn message_format(&self) -> Option<MessageFormat> {
+        let is = |mime: &str| {
+            self.declared_mime.as_deref() == Some(mime)
+                || self.detected_mime.as_deref() == Some(mime)
+        };
+        if is("application/vnd.ms-outlook") {
+            Some(MessageFormat::Msg)
+        } else if is("message/rfc822") {
+            Some(MessageFormat::Eml)
+        } else {
+            MessageFormat::from_path(Path::new(&self.suggested_filename))
+        }
+    }
+}
+
+impl Message {
+    /// The message attached at `part_path`, a list of attachment indices from this message
+    /// inwards; an empty path is the message itself
+    pub fn into_nested(self, part_path: &[usize]) -> Result<Message, String> {
+        let mut message = self;
+        for &index in part_path {
+            message = message
+                .attachments
+                .into_iter()
+                .nth(index)
+                .and_then(|attachment| attachment.nested)
+                .map(|nested| *nested)
+                .ok_or_else(|| format!("No attached message at {:?}", part_path))?;
+        }
+        Ok(message)
+    }
+
+    /// Keep the charset decision for a decoded body and return its text
+    pub(crate) fn record_encoding(
+        &mut self,
+        body: &'static str,
+        decoded: charset::Decoded,
+    ) -> String {
+        let (record, warning) = decoded.report(body);
+        self.body_encodings.push(record);
+        self.warnings.extend(warning);
+        if let Some(misread_as) = decoded.repaired {
+            self.body_repaired = true;
+            self.warnings.push(Localized::new(
+                "warning.body_repaired",
+                [
+                    ("body", body.to_string()),
+                    ("charset", misread_as.to_string()),
+                ],
+            ));
+        }
+        decoded.text
+    }
+
+    /// Set `referenced_in_body` for attachments whose content id the HTML body uses
+    pub(crate) fn mark_referenced_attachments(&mut self) {
+        let Some(html) = &self.body_html else {
+            return;
+        };
+        let referenced = referenced_content_ids(html);
+        for attachment in &mut self.attachments {
+            attachment.referenced_in_body = attachment
+                .content_id
+                .as_ref()
+                .is_some_and(|id| referenced.contains(&id.to_lowercase()));
+        }
+    }
+}
+
+/// Content ids used as `cid:` URLs in an HTML body, lowercased
+fn referenced_content_ids(html: &str) -> std::collections::HashSet<String> {
+    let lower = html.to_lowercase();
+    let mut ids = std::collections::HashSet::new();
+    let mut rest = lower.as_str();
+    while let Some(pos) = rest.find("cid:") {
+        rest = &rest[pos + 4..];
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ')' | '>' | '<'))
+            .unwrap_or(rest.len());
+        let id = rest[..end].trim_start_matches('<').trim_end_matches('>');
+        if !id.is_empty() {
+            ids.insert(id.replace("%40", "@"));
+        }
+        rest = &rest[end..];
+    }
+    ids
+}
+
+/// Lowercase hex SHA-256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Parse a message file completely
+pub fn parse_file(path: &Path, options: &ParseOptions) -> Result<Message, String> {
+    let format = MessageFormat::from_path(path)
+        .ok_or_else(|| format!("Unsupported file type: {}", path.display()))?;
+    let data = crate::reader::read_all(path)?;
+    parse_bytes(format, &data, options)
+}
+
+/// Parse message bytes of a known format, within the resource limits of `options`
+pub fn parse_bytes(
+    format: MessageFormat,
+    data: &[u8],
+    options: &ParseOptions,
+) -> Result<Message, String> {
+    let options = ParseOptions {
+        budget: limits::Budget::new(&options.limits, data.len()),
+        ..options.clone()
+    };
+    parse_at_depth(format, data, &options, 0)
+}
+
+fn parse_at_depth(
+    format: MessageFormat,
+    data: &[u8],
+    options: &ParseOptions,
+    depth: usize,
+) -> Result<Message, String> {
+    options.budget.check_time()?;
+    let mut message = match format {
+        MessageFormat::Msg => msg::parse_bytes(data, options)?,
+        MessageFormat::Eml | MessageFormat::Emlx => {
+            let (data, plist) = match format {
+                MessageFormat::Emlx => emlx::split(data).unwrap_or((data, &[])),
+                _ => (data, &[][..]),
+            };
+            let mut message = eml::parse(data, options);
+            message.source_flags = emlx::flags(plist);
+            for attachment in &message.attachments {
+                options
+                    .budget
+                    .reserve(&attachment.file_name, attachment.size as u64)?;
+            }
+            message
+        }
+    };
+    tnef::apply(&mut message, options)?;
+    message.links = links::extract(message.body_text.as_deref(), message.body_html.as_deref());
+    for link in &message.links {
+        if link.wrapper.is_some() && link.unwrapped.is_none() {
+            message.warnings.push(Localized::new(
+                "warning.malformed_link_wrapper",
+                [("url", link.url.clone())],
+            ));
+        }
+    }
+    if options.annotate_wrapped_links {
+        message.body_html = message.body_html.as_deref().map(safelinks::annotate_titles);
+    }
+    if let Some(html) = &message.body_html {
+        let recipients: Vec<String> = message
+            .to
+            .iter()
+            .chain(&message.cc)
+            .chain(&message.bcc)
+            .filter_map(|address| address.email.clone())
+            .collect();
+        message.trackers = trackers::find(html, &options.tracker_patterns, &recipients);
+        message.remote_image_area_ratio = remote_images::area_ratio(html);
+    }
+    message.signature = message.body_text.as_deref().and_then(signature::detect);
+    protection::apply(&mut message);
+    delivery::apply(&mut message);
+    labels::apply(&mut message);
+    gateway::apply(&mut message);
+    language::apply(&mut message);
+    parse_attached_messages(&mut message, options, depth)?;
+    if depth + 1 < options.limits.max_depth {
+        message = smime::unwrap(message, |content| {
+            parse_at_depth(MessageFormat::Eml, content, options, depth + 1)
+        })?;
+    }
+    grouping::apply(&mut message);
+    recipients::summarize(&mut message);
+    Ok(message)
+}
+
+/// Parse attached messages into [`Attachment::nested`]. Each level keeps its own attachments
+/// and content ids, so the inline images of an inner message never show up in the outer one.
+/// Only running into a resource limit fails the whole parse.
+fn parse_attached_messages(
+    message: &mut Message,
+    options: &ParseOptions,
+    depth: usize,
+) -> Result<(), String> {
+    for attachment in &mut message.attachments {
+        let Some(format) = attachment.message_format() else {
+            continue;
+        };
+        if depth + 1 >= options.limits.max_depth {
+            message.warnings.push(Localized::new(
+                "warning.nested_too_deep",
+                [("name", attachment.file_name.clone())],
+            ));
+            continue;
+        }
+        match parse_at_depth(format, &attachment.data, options, depth + 1) {
+            Ok(nested) => attachment.nested = Some(Box::new(nested)),
+            Err(e) if limits::tripped(&e).is_some() => return Err(e),
+            Err(e) => message.warnings.push(Localized::new(
+                "warning.attached_message_unreadable",
+                [("name", attachment.file_name.clone()), ("reason", e)],
+            )),
+        }
+    }
+    Ok(())
+}
+
+/// Quick partial parse of a message file for notifications and listings
+pub fn peek_summary(path: &Path) -> Result<MessageSummary, String> {
+    let format = MessageFormat::from_path(path)
+        .ok_or_else(|| format!("Unsupported file type: {}", path.display()))?;
+    let file = crate::reader::open(path)?;
+
+    match format {
+        MessageFormat::Msg => msg::peek(file),
+        MessageFormat::Eml | MessageFormat::Emlx => {
+            let mut head = Vec::new();
+            file.take(PEEK_HEADER_LIMIT)
+                .read_to_end(&mut head)
+                .map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+
+            let head = match format {
+                MessageFormat::Emlx => emlx::split(&head).map_or(&head[..], |(message, _)| message),
+                _ => &head[..],
+            };
+            let (block, _) = headers::split_header_block(head);
+            let headers = headers::parse(block);
+            Ok(MessageSummary {
+                subject: headers.get("Subject").map(headers::decode_encoded_words),
+                sender: headers.get("From").map(headers::display_name),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+    }
+
+    #[test]
+    fn msg_files_give_their_headers_bodies_and_attachments() {
+        let path = fixture("tests/fixtures/self-test/minimal.msg");
+        let message = parse_file(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(message.format, MessageFormat::Msg);
+        assert_eq!(message.subject.as_deref(), Some("Quarterly figures"));
+        let from = message.from.unwrap();
+        assert_eq!(from.name.as_deref(), Some("Ann Example"));
+        assert_eq!(from.email.as_deref(), Some("ann@example.com"));
+        assert!(message
+            .body_text
+            .unwrap()
+            .starts_with("The quarterly figures are attached"));
+        assert!(message
+            .body_html
+            .unwrap()
+            .contains("<img src=\"cid:image0@x\""));
+        let attachments: Vec<_> = message
+            .attachments
+            .iter()
+            .map(|a| (a.file_name.as_str(), a.size, a.hidden))
+            .collect();
+        assert_eq!(attachments, [("report-0.pdf", 64, false)]);
+        assert!(message.warnings.is_empty());
+
+        let summary = peek_summary(&path).unwrap();
+        assert_eq!(summary.subject.as_deref(), Some("Quarterly figures"));
+        assert_eq!(summary.sender.as_deref(), Some("Ann Example"));
+    }
+
+    #[test]
+    fn eml_files_give_their_headers_bodies_and_attachments() {
+        let path = fixture("tests/corpus/mime/multipart.eml");
+        let message = parse_file(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(message.format, MessageFormat::Eml);
+        assert_eq!(message.subject.as_deref(), Some("Quartal figures"));
+        assert_eq!(message.to[0].name.as_deref(), Some("Jürgen"));
+        assert_eq!(message.body_text.as_deref(), Some("Hallo ä soft break"));
+        assert_eq!(
+            message.body_html.as_deref(),
+            Some("<p>Hallo <img src=\"cid:logo@x\"></p>")
+        );
+        let attachments: Vec<_> = message
+            .attachments
+            .iter()
+            .map(|a| {
+                (
+                    a.file_name.as_str(),
+                    a.detected_mime.as_deref(),
+                    a.content_id.as_deref(),
+                    a.hidden,
+                )
+            })
+            .collect();
+        assert_eq!(
+            attachments,
+            [
+                ("logo.png", Some("image/png"), Some("logo@x"), true),
+                ("inner mail.eml", Some("message/rfc822"), None, false),
+            ]
+        );
+        let nested = message.attachments[1].nested.as_deref().unwrap();
+        assert_eq!(nested.subject.as_deref(), Some("inner"));
+        assert!(message.warnings.is_empty());
+
+        let summary = peek_summary(&path).unwrap();
+        assert_eq!(summary.subject.as_deref(), Some("Quartal figures"));
+        assert_eq!(summary.sender.as_deref(), Some("Ann"));
+
+        assert!(parse_file(
+            &fixture("tests/fixtures/archive/aes256.zip"),
+            &ParseOptions::default()
+        )
+        .unwrap_err()
+        .starts_with("Unsupported file type"));
+    }
+}
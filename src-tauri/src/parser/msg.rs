@@ -0,0 +1,570 @@
+use super::charset;
+use super::headers;
+use super::limits;
+use super::named::{self, NAMEID_STORAGE};
+use super::ole;
+use super::protection;
+use super::recipients::{self, RecipientType};
+use super::recovery;
+use super::rtf;
+use super::storage::CompoundStorage;
+use super::{
+    Address, Attachment, Importance, Message, MessageFormat, MessageSummary, ParseOptions,
+    Sensitivity,
+};
+use crate::i18n::Localized;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+const PROP_TYPE_LONG: u16 = 0x0003;
+const PROP_TYPE_DOUBLE: u16 = 0x0005;
+const PROP_TYPE_BOOLEAN: u16 = 0x000B;
+pub(super) const PROP_TYPE_STRING8: u16 = 0x001E;
+pub(super) const PROP_TYPE_UNICODE: u16 = 0x001F;
+const PROP_TYPE_SYSTIME: u16 = 0x0040;
+const PROP_TYPE_OBJECT: u16 = 0x000D;
+pub(super) const PROP_TYPE_BINARY: u16 = 0x0102;
+
+pub const PID_TAG_IMPORTANCE: u16 = 0x0017;
+pub const PID_TAG_MESSAGE_CLASS: u16 = 0x001A;
+pub const PID_TAG_SENSITIVITY: u16 = 0x0036;
+pub const PID_TAG_SUBJECT: u16 = 0x0037;
+pub const PID_TAG_CLIENT_SUBMIT_TIME: u16 = 0x0039;
+pub const PID_TAG_TRANSPORT_MESSAGE_HEADERS: u16 = 0x007D;
+pub const PID_TAG_SENDER_NAME: u16 = 0x0C1A;
+pub const PID_TAG_SENDER_EMAIL_ADDRESS: u16 = 0x0C1F;
+pub const PID_TAG_RECIPIENT_TYPE: u16 = 0x0C15;
+pub const PID_TAG_MESSAGE_DELIVERY_TIME: u16 = 0x0E06;
+pub const PID_TAG_BODY: u16 = 0x1000;
+pub const PID_TAG_RTF_COMPRESSED: u16 = 0x1009;
+pub const PID_TAG_HTML: u16 = 0x1013;
+pub const PID_TAG_INTERNET_MESSAGE_ID: u16 = 0x1035;
+pub const PID_TAG_DISPLAY_NAME: u16 = 0x3001;
+pub const PID_TAG_EMAIL_ADDRESS: u16 = 0x3003;
+pub const PID_TAG_ATTACH_DATA_BINARY: u16 = 0x3701;
+pub const PID_TAG_ATTACH_FILENAME: u16 = 0x3704;
+pub const PID_TAG_ATTACH_METHOD: u16 = 0x3705;
+pub const PID_TAG_ATTACH_LONG_FILENAME: u16 = 0x3707;
+pub const PID_TAG_ATTACH_MIME_TAG: u16 = 0x370E;
+pub const PID_TAG_ATTACH_CONTENT_ID: u16 = 0x3712;
+pub const PID_TAG_ATTACH_FLAGS: u16 = 0x3714;
+pub const PID_TAG_SMTP_ADDRESS: u16 = 0x39FE;
+pub const PID_TAG_INTERNET_CODEPAGE: u16 = 0x3FDE;
+pub const PID_TAG_MESSAGE_CODEPAGE: u16 = 0x3FFD;
+pub const PID_TAG_SENDER_SMTP_ADDRESS: u16 = 0x5D01;
+pub const PID_TAG_ATTACHMENT_HIDDEN: u16 = 0x7FFE;
+
+/// Attachment stored as a binary stream
+const ATTACH_BY_VALUE: u32 = 1;
+/// Attachment that is itself a message (stored as a sub-storage)
+const ATTACH_EMBEDDED_MSG: u32 = 5;
+/// Attachment stored as an OLE object storage
+const ATTACH_OLE: u32 = 6;
+
+/// PidTagAttachFlags bit for attachments rendered as part of the (RTF) body
+const ATT_RENDERED_IN_BODY: u32 = 0x4;
+
+/// Recipient types (PidTagRecipientType)
+const MAPI_TO: u32 = 1;
+const MAPI_CC: u32 = 2;
+const MAPI_BCC: u32 = 3;
+
+/// Size of the `__properties_version1.0` header before the first entry
+const TOP_LEVEL_PROPERTIES_HEADER: usize = 32;
+const EMBEDDED_MESSAGE_PROPERTIES_HEADER: usize = 24;
+const SUB_OBJECT_PROPERTIES_HEADER: usize = 8;
+
+/// Path of a property stream inside `storage` (e.g. `/__substg1.0_0037001F`)
+fn property_stream(storage: &str, id: u16, prop_type: u16) -> String {
+    format!(
+        "{}/__substg1.0_{:04X}{:04X}",
+        storage.trim_end_matches('/'),
+        id,
+        prop_type
+    )
+}
+
+/// Read a string property, preferring the unicode variant over the ANSI one
+pub fn string_property<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    path: &str,
+    id: u16,
+) -> Option<String> {
+    if let Some(data) = storage.read_stream(&property_stream(path, id, PROP_TYPE_UNICODE)) {
+        return Some(charset::decode_utf16le(&data));
+    }
+    storage
+        .read_stream(&property_stream(path, id, PROP_TYPE_STRING8))
+        .map(|data| {
+            charset::decode(&data, "windows-1252")
+                .trim_end_matches('\0')
+                .to_string()
+        })
+}
+
+/// Fixed-size properties (integers, times) from a `__properties_version1.0` stream,
+/// keyed by property id with their raw 8-byte values
+fn fixed_properties<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    path: &str,
+    header_len: usize,
+) -> HashMap<u16, (u16, [u8; 8])> {
+    let stream = format!("{}/__properties_version1.0", path.trim_end_matches('/'));
+    let Some(data) = storage.read_stream(&stream) else {
+        return HashMap::new();
+    };
+    decode_fixed_properties(&data, header_len)
+}
+
+/// The entries of `__properties_version1.0` data after its `header_len`-byte header; a
+/// trailing partial entry is ignored
+fn decode_fixed_properties(data: &[u8], header_len: usize) -> HashMap<u16, (u16, [u8; 8])> {
+    data.get(header_len..)
+        .unwrap_or_default()
+        .chunks_exact(16)
+        .map(|entry| {
+            let prop_type = u16::from_le_bytes([entry[0], entry[1]]);
+            let id = u16::from_le_bytes([entry[2], entry[3]]);
+            let mut value = [0u8; 8];
+            value.copy_from_slice(&entry[8..16]);
+            (id, (prop_type, value))
+        })
+        .collect()
+}
+
+fn bool_property(props: &HashMap<u16, (u16, [u8; 8])>, id: u16) -> Option<bool> {
+    match props.get(&id) {
+        Some((PROP_TYPE_BOOLEAN, value)) => Some(value[0] != 0),
+        _ => None,
+    }
+}
+
+fn long_property(props: &HashMap<u16, (u16, [u8; 8])>, id: u16) -> Option<u32> {
+    match props.get(&id) {
+        Some((PROP_TYPE_LONG, value)) => {
+            Some(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        }
+        _ => None,
+    }
+}
+
+/// FILETIME property as RFC 3339
+fn time_property(props: &HashMap<u16, (u16, [u8; 8])>, id: u16) -> Option<String> {
+    let (PROP_TYPE_SYSTIME, value) = props.get(&id)? else {
+        return None;
+    };
+    // 100ns intervals since 1601-01-01
+    let ticks = u64::from_le_bytes(*value);
+    let unix_seconds = (ticks / 10_000_000).checked_sub(11_644_473_600)?;
+    let nanos = (ticks % 10_000_000) * 100;
+    chrono::DateTime::from_timestamp(unix_seconds as i64, nanos as u32).map(|t| t.to_rfc3339())
+}
+
+/// `__properties_version1.0` data decoded with each header size and every value read as
+/// each fixed-size type, for the `properties` fuzz target; returns the entries decoded
+pub(crate) fn decode_property_stream(data: &[u8]) -> usize {
+    let mut decoded = 0;
+    for header_len in [
+        TOP_LEVEL_PROPERTIES_HEADER,
+        EMBEDDED_MESSAGE_PROPERTIES_HEADER,
+        SUB_OBJECT_PROPERTIES_HEADER,
+    ] {
+        let props = decode_fixed_properties(data, header_len);
+        for id in props.keys() {
+            let _ = (
+                bool_property(&props, *id),
+                long_property(&props, *id),
+                time_property(&props, *id),
+            );
+        }
+        decoded += props.len();
+    }
+    decoded
+}
+
+/// Voting buttons and custom form fields, which Outlook stores as named properties
+fn read_named_properties<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    props: &HashMap<u16, (u16, [u8; 8])>,
+    message: &mut Message,
+) {
+    let mapping = named::Mapping::read(storage);
+    let options = mapping
+        .id(&named::PSETID_COMMON, named::PID_LID_VERB_STREAM)
+        .and_then(|id| storage.read_stream(&property_stream("/", id, PROP_TYPE_BINARY)))
+        .map(|data| named::voting_options(&data))
+        .unwrap_or_default();
+    let response = mapping
+        .id(&named::PSETID_COMMON, named::PID_LID_VERB_RESPONSE)
+        .and_then(|id| string_property(storage, "/", id))
+        .filter(|response| !response.is_empty());
+    if !options.is_empty() || response.is_some() {
+        message.voting = Some(named::Voting { options, response });
+    }
+    message.sensitivity_label = mapping
+        .named(&named::PS_INTERNET_HEADERS, protection::MSIP_LABELS)
+        .and_then(|id| string_property(storage, "/", id))
+        .and_then(|labels| protection::parse_labels(&labels));
+
+    for (id, name) in mapping.user_fields() {
+        let value = match props.get(&id) {
+            Some((PROP_TYPE_LONG, _)) => long_property(props, id).map(|v| (v as i32).to_string()),
+            Some((PROP_TYPE_BOOLEAN, _)) => bool_property(props, id).map(|v| v.to_string()),
+            Some((PROP_TYPE_SYSTIME, _)) => time_property(props, id),
+            Some((PROP_TYPE_DOUBLE, value)) => Some(f64::from_le_bytes(*value).to_string()),
+            // Multi-valued and binary fields aren't shown
+            _ => string_property(storage, "/", id),
+        };
+        if let Some(value) = value {
+            message.custom_fields.push(named::CustomField {
+                name: name.to_string(),
+                value,
+            });
+        }
+    }
+}
+
+/// Subject and sender from the top-level message properties only
+pub fn peek<F: Read + Seek>(inner: F) -> Result<MessageSummary, String> {
+    let mut storage = CompoundStorage::open(inner)?;
+    let subject = string_property(&mut storage, "/", PID_TAG_SUBJECT);
+    let sender = string_property(&mut storage, "/", PID_TAG_SENDER_NAME)
+        .filter(|name| !name.is_empty())
+        .or_else(|| string_property(&mut storage, "/", PID_TAG_SENDER_SMTP_ADDRESS))
+        .or_else(|| string_property(&mut storage, "/", PID_TAG_SENDER_EMAIL_ADDRESS));
+
+    Ok(MessageSummary { subject, sender })
+}
+
+/// Parse a complete .msg file
+pub fn parse<F: Read + Seek>(inner: F, options: &ParseOptions) -> Result<Message, String> {
+    let mut storage = CompoundStorage::open(inner)?;
+    storage.set_budget(options.budget.clone());
+    let mut message = Message::new(MessageFormat::Msg);
+    let props = fixed_properties(&mut storage, "/", TOP_LEVEL_PROPERTIES_HEADER);
+
+    message.message_class = string_property(&mut storage, "/", PID_TAG_MESSAGE_CLASS);
+    message.subject = string_property(&mut storage, "/", PID_TAG_SUBJECT);
+    message.message_id = string_property(&mut storage, "/", PID_TAG_INTERNET_MESSAGE_ID);
+    message.date = time_property(&props, PID_TAG_CLIENT_SUBMIT_TIME)
+        .or_else(|| time_property(&props, PID_TAG_MESSAGE_DELIVERY_TIME));
+    message.importance = match long_property(&props, PID_TAG_IMPORTANCE) {
+        Some(0) => Some(Importance::Low),
+        Some(2) => Some(Importance::High),
+        _ => None,
+    };
+    message.sensitivity = match long_property(&props, PID_TAG_SENSITIVITY) {
+        Some(1) => Some(Sensitivity::Personal),
+        Some(2) => Some(Sensitivity::Private),
+        Some(3) => Some(Sensitivity::Confidential),
+        _ => None,
+    };
+
+    if let Some(block) = string_property(&mut storage, "/", PID_TAG_TRANSPORT_MESSAGE_HEADERS) {
+        message.headers = headers::parse(block.as_bytes()).0;
+    }
+
+    let sender_name =
+        string_property(&mut storage, "/", PID_TAG_SENDER_NAME).filter(|s| !s.is_empty());
+    let sender_email = string_property(&mut storage, "/", PID_TAG_SENDER_SMTP_ADDRESS)
+        .or_else(|| string_property(&mut storage, "/", PID_TAG_SENDER_EMAIL_ADDRESS))
+        .filter(|email| email.contains('@'));
+    if sender_name.is_some() || sender_email.is_some() {
+        message.from = Some(Address::new(sender_name, sender_email));
+    }
+
+    message.body_text = text_body(&mut storage, &props, &mut message, options);
+    message.body_html = match html_body(&mut storage, &props, &mut message, options) {
+        Some(html) => Some(html),
+        None => rtf_html(&mut storage, &mut message, options)?,
+    };
+
+    let children = storage.children("/");
+    let mut recipients: Vec<&str> = children
+        .iter()
+        .filter(|entry| entry.is_storage && entry.name.starts_with("__recip_version1.0_"))
+        .map(|entry| entry.name.as_str())
+        .collect();
+    recipients.sort_unstable();
+    for name in recipients {
+        let path = format!("/{}", name);
+        let recipient_props = fixed_properties(&mut storage, &path, SUB_OBJECT_PROPERTIES_HEADER);
+        let kind = match long_property(&recipient_props, PID_TAG_RECIPIENT_TYPE) {
+            Some(MAPI_CC) => RecipientType::Cc,
+            Some(MAPI_BCC) => RecipientType::Bcc,
+            Some(MAPI_TO) | None => RecipientType::To,
+            Some(other) => {
+                message.warnings.push(Localized::new(
+                    "warning.unknown_recipient_type",
+                    [("type", other.to_string()), ("name", name.to_string())],
+                ));
+                RecipientType::To
+            }
+        };
+        // Recipients outside the window are counted without reading their names
+        let position = message.recipient_counts.add(kind);
+        if options
+            .recipient_window
+            .is_some_and(|window| !window.contains(position))
+        {
+            continue;
+        }
+        let address = Address::new(
+            string_property(&mut storage, &path, PID_TAG_DISPLAY_NAME).filter(|s| !s.is_empty()),
+            string_property(&mut storage, &path, PID_TAG_SMTP_ADDRESS)
+                .or_else(|| string_property(&mut storage, &path, PID_TAG_EMAIL_ADDRESS))
+                .filter(|s| !s.is_empty()),
+        );
+        recipients::list_mut(&mut message, kind).push(address);
+    }
+
+    let mut attachments: Vec<&str> = children
+        .iter()
+        .filter(|entry| entry.is_storage && entry.name.starts_with("__attach_version1.0_"))
+        .map(|entry| entry.name.as_str())
+        .collect();
+    attachments.sort_unstable();
+    for name in attachments {
+        options.budget.check_time()?;
+        let path = format!("/{}", name);
+        read_attachment(&mut storage, &path, &mut message);
+    }
+
+    // Files without named properties skip the mapping
+    let has_named = props.keys().any(|id| *id >= 0x8000)
+        || children
+            .iter()
+            .any(|entry| entry.name.starts_with("__substg1.0_8"));
+    if has_named && children.iter().any(|entry| entry.name == NAMEID_STORAGE) {
+        read_named_properties(&mut storage, &props, &mut message);
+    }
+
+    // Inline images referenced by the HTML body are hidden as well, like Outlook does
+    message.mark_referenced_attachments();
+    for attachment in &mut message.attachments {
+        attachment.hidden |= attachment.referenced_in_body;
+    }
+
+    if let Some(e) = storage.limit_error() {
+        return Err(e.to_string());
+    }
+    if storage.is_damaged() {
+        message.partial = true;
+        message
+            .warnings
+            .push(Localized::new("warning.streams_unreadable", []));
+    }
+    Ok(message)
+}
+
+/// Parse .msg bytes; files too damaged to open are rebuilt from whatever streams are
+/// still readable and come back `partial`
+pub fn parse_bytes(data: &[u8], options: &ParseOptions) -> Result<Message, String> {
+    let strict = parse(Cursor::new(data), options);
+    if strict.as_ref().is_ok_and(|message| !message.partial) {
+        return strict;
+    }
+    if strict.as_ref().is_err_and(|e| limits::tripped(e).is_some()) {
+        return strict;
+    }
+    // Keep the strict result when there is nothing to recover
+    let recovered = match recovery::rebuild(data, &options.budget) {
+        Ok(recovered) => recovered,
+        Err(e) if limits::tripped(&e).is_some() => return Err(e),
+        Err(_) => return strict,
+    };
+    let mut message = parse(Cursor::new(recovered.data), options)?;
+    message.partial = true;
+    message.warnings.splice(0..0, recovered.warnings);
+    Ok(message)
+}
+
+/// Plain text body; the ANSI variant is in the message code page, which Outlook doesn't
+/// always set correctly
+fn text_body<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    props: &HashMap<u16, (u16, [u8; 8])>,
+    message: &mut Message,
+    options: &ParseOptions,
+) -> Option<String> {
+    if let Some(data) = storage.read_stream(&property_stream("/", PID_TAG_BODY, PROP_TYPE_UNICODE))
+    {
+        return Some(charset::decode_utf16le(&data));
+    }
+    let data = storage.read_stream(&property_stream("/", PID_TAG_BODY, PROP_TYPE_STRING8))?;
+    let label = long_property(props, PID_TAG_MESSAGE_CODEPAGE)
+        .or_else(|| long_property(props, PID_TAG_INTERNET_CODEPAGE))
+        .and_then(charset::label_for_codepage);
+    let decoded = options.decode_body(&data, label);
+    let text = message.record_encoding("text", decoded);
+    Some(text.trim_end_matches('\0').to_string())
+}
+
+/// HTML body, stored as binary in the message's internet code page (or as a string)
+fn html_body<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    props: &HashMap<u16, (u16, [u8; 8])>,
+    message: &mut Message,
+    options: &ParseOptions,
+) -> Option<String> {
+    if let Some(data) = storage.read_stream(&property_stream("/", PID_TAG_HTML, PROP_TYPE_BINARY)) {
+        let label =
+            long_property(props, PID_TAG_INTERNET_CODEPAGE).and_then(charset::label_for_codepage);
+        let decoded = options.decode_body(&data, label);
+        let html = message.record_encoding("html", decoded);
+        return Some(html.trim_end_matches('\0').to_string());
+    }
+    string_property(storage, "/", PID_TAG_HTML)
+}
+
+/// The HTML Outlook encapsulated in the compressed RTF body, for messages saved without an
+/// HTML body of their own; a body that doesn't decompress is left out with a warning
+fn rtf_html<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    message: &mut Message,
+    options: &ParseOptions,
+) -> Result<Option<String>, String> {
+    let stream = property_stream("/", PID_TAG_RTF_COMPRESSED, PROP_TYPE_BINARY);
+    let Some(data) = storage.read_stream(&stream) else {
+        return Ok(None);
+    };
+    match rtf::decompress(&data, &options.budget) {
+        Ok(rtf) => Ok(rtf::encapsulated_html(&rtf)),
+        Err(e) if limits::tripped(&e).is_some() => Err(e),
+        Err(e) => {
+            message
+                .warnings
+                .push(Localized::new("warning.rtf_unreadable", [("reason", e)]));
+            Ok(None)
+        }
+    }
+}
+
+fn read_attachment<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    path: &str,
+    message: &mut Message,
+) {
+    let props = fixed_properties(storage, path, SUB_OBJECT_PROPERTIES_HEADER);
+    let method = long_property(&props, PID_TAG_ATTACH_METHOD).unwrap_or(ATTACH_BY_VALUE);
+    let file_name = string_property(storage, path, PID_TAG_ATTACH_LONG_FILENAME)
+        .filter(|s| !s.is_empty())
+        .or_else(|| string_property(storage, path, PID_TAG_ATTACH_FILENAME))
+        .filter(|s| !s.is_empty())
+        .or_else(|| string_property(storage, path, PID_TAG_DISPLAY_NAME));
+
+    // Explicitly hidden, or rendered into an RTF body (which uses no content ids)
+    let content_id = string_property(storage, path, PID_TAG_ATTACH_CONTENT_ID);
+    let rendered_in_body = long_property(&props, PID_TAG_ATTACH_FLAGS)
+        .is_some_and(|flags| flags & ATT_RENDERED_IN_BODY != 0);
+    let hidden = bool_property(&props, PID_TAG_ATTACHMENT_HIDDEN).unwrap_or(false)
+        || (rendered_in_body && content_id.is_none());
+
+    if method == ATTACH_EMBEDDED_MSG {
+        let object = property_stream(path, PID_TAG_ATTACH_DATA_BINARY, PROP_TYPE_OBJECT);
+        let file_name = file_name.or_else(|| string_property(storage, &object, PID_TAG_SUBJECT));
+        match export_embedded_message(storage, &object) {
+            Ok(data) => {
+                let index = message.attachments.len();
+                let mut attachment = Attachment::new(
+                    index,
+                    file_name,
+                    Some("application/vnd.ms-outlook".to_string()),
+                    content_id,
+                    data,
+                );
+                attachment.hidden = hidden;
+                message.attachments.push(attachment);
+            }
+            Err(e) => message.warnings.push(Localized::new(
+                "warning.embedded_message_unreadable",
+                [
+                    ("name", file_name.as_deref().unwrap_or(path).to_string()),
+                    ("reason", e),
+                ],
+            )),
+        }
+        return;
+    }
+
+    if method == ATTACH_OLE {
+        let object = property_stream(path, PID_TAG_ATTACH_DATA_BINARY, PROP_TYPE_OBJECT);
+        let index = message.attachments.len();
+        let fallback = file_name.unwrap_or_else(|| format!("attachment-{}", index + 1));
+        match ole::extract(storage, &object, &fallback) {
+            Ok(extracted) => {
+                message.warnings.extend(extracted.warning);
+                // The declared type describes the OLE wrapper, not the extracted file
+                let mut attachment = Attachment::new(
+                    index,
+                    Some(extracted.file_name),
+                    None,
+                    content_id,
+                    extracted.data,
+                );
+                attachment.hidden = hidden;
+                message.attachments.push(attachment);
+            }
+            Err(e) => message.warnings.push(Localized::new(
+                "warning.ole_unreadable",
+                [("name", fallback), ("reason", e)],
+            )),
+        }
+        return;
+    }
+
+    let Some(data) = storage.read_stream(&property_stream(
+        path,
+        PID_TAG_ATTACH_DATA_BINARY,
+        PROP_TYPE_BINARY,
+    )) else {
+        message.warnings.push(Localized::new(
+            "warning.attachment_without_data",
+            [("name", file_name.as_deref().unwrap_or(path).to_string())],
+        ));
+        return;
+    };
+
+    let index = message.attachments.len();
+    let mut attachment = Attachment::new(
+        index,
+        file_name,
+        string_property(storage, path, PID_TAG_ATTACH_MIME_TAG),
+        content_id,
+        data,
+    );
+    attachment.hidden = hidden;
+    message.attachments.push(attachment);
+}
+
+/// Turn an embedded message storage into a standalone .msg file: the properties header
+/// grows to the top-level size and the outer file's named property mapping is copied in
+fn export_embedded_message<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    path: &str,
+) -> Result<Vec<u8>, String> {
+    let nameid = format!("/{}", NAMEID_STORAGE);
+    let mut mapping = vec![(path, "/")];
+    if storage
+        .children("/")
+        .iter()
+        .any(|entry| entry.is_storage && entry.name == NAMEID_STORAGE)
+    {
+        mapping.push((nameid.as_str(), nameid.as_str()));
+    }
+
+    storage.export_storages(&mapping, |target, data| {
+        if target != Path::new("/__properties_version1.0")
+            || data.len() < EMBEDDED_MESSAGE_PROPERTIES_HEADER
+        {
+            return data;
+        }
+        let padding = TOP_LEVEL_PROPERTIES_HEADER - EMBEDDED_MESSAGE_PROPERTIES_HEADER;
+        let mut fixed = Vec::with_capacity(data.len() + padding);
+        fixed.extend_from_slice(&data[..EMBEDDED_MESSAGE_PROPERTIES_HEADER]);
+        fixed.extend(std::iter::repeat_n(0u8, padding));
+        fixed.extend_from_slice(&data[EMBEDDED_MESSAGE_PROPERTIES_HEADER..]);
+        fixed
+    })
+}
@@ -0,0 +1,383 @@
+//! Named properties of .msg files (MS-OXMSG 2.2.3): which property set and name the ids
+//! from 0x8000 up stand for, and the Outlook features stored in them
+
+use super::charset;
+use super::storage::CompoundStorage;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+/// Named property mapping, only present at the root of a file
+pub const NAMEID_STORAGE: &str = "__nameid_version1.0";
+
+/// A GUID in the byte order of .msg files (the first three fields little-endian)
+pub type Guid = [u8; 16];
+
+const fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Guid {
+    let a = data1.to_le_bytes();
+    let b = data2.to_le_bytes();
+    let c = data3.to_le_bytes();
+    [
+        a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], data4[0], data4[1], data4[2], data4[3],
+        data4[4], data4[5], data4[6], data4[7],
+    ]
+}
+
+/// Last eight bytes of the GUIDs of the Office property sets
+const OFFICE: [u8; 8] = [0xC0, 0, 0, 0, 0, 0, 0, 0x46];
+
+pub const PS_MAPI: Guid = guid(0x0002_0328, 0, 0, OFFICE);
+/// Where Outlook keeps user-defined fields and those of custom forms
+pub const PS_PUBLIC_STRINGS: Guid = guid(0x0002_0329, 0, 0, OFFICE);
+pub const PSETID_COMMON: Guid = guid(0x0006_2008, 0, 0, OFFICE);
+/// Internet headers Outlook keeps as properties (`msip_labels`, ...), named after the header
+pub const PS_INTERNET_HEADERS: Guid = guid(0x0002_0386, 0, 0, OFFICE);
+
+/// Property sets of Outlook's own features; string-named properties of other sets come
+/// from custom forms and add-ins
+const BUILT_IN_SETS: &[Guid] = &[
+    PS_MAPI,
+    PSETID_COMMON,
+    PS_INTERNET_HEADERS,
+    // PSETID_Appointment, _Task, _Address, _Log, _Note, _Sharing, _PostRss
+    guid(0x0006_2002, 0, 0, OFFICE),
+    guid(0x0006_2003, 0, 0, OFFICE),
+    guid(0x0006_2004, 0, 0, OFFICE),
+    guid(0x0006_200A, 0, 0, OFFICE),
+    guid(0x0006_200E, 0, 0, OFFICE),
+    guid(0x0006_2040, 0, 0, OFFICE),
+    guid(0x0006_2041, 0, 0, OFFICE),
+    // PSETID_Meeting
+    guid(
+        0x6ED8_DA90,
+        0x450B,
+        0x101B,
+        [0x98, 0xDA, 0x00, 0xAA, 0x00, 0x3F, 0x13, 0x05],
+    ),
+    // PSETID_Messaging
+    guid(
+        0x41F2_8F13,
+        0x83F4,
+        0x4114,
+        [0xA5, 0x84, 0xEE, 0xDB, 0x5A, 0x6B, 0x0B, 0xFF],
+    ),
+    // PSETID_UnifiedMessaging
+    guid(
+        0x4442_858E,
+        0xA9E3,
+        0x4E80,
+        [0xB9, 0x00, 0x31, 0x7A, 0x21, 0x0C, 0xC1, 0x5B],
+    ),
+    // PSETID_AirSync
+    guid(
+        0x7103_5549,
+        0x0739,
+        0x4DCB,
+        [0x91, 0x63, 0x00, 0xF0, 0x58, 0x0D, 0xBB, 0xDF],
+    ),
+    // PSETID_Attachment
+    guid(
+        0x9635_7F7F,
+        0x59E1,
+        0x47D0,
+        [0x99, 0xA7, 0x46, 0x51, 0x5C, 0x18, 0x3B, 0x54],
+    ),
+    // PSETID_CalendarAssistant
+    guid(
+        0x1100_0E07,
+        0xB51B,
+        0x40D6,
+        [0xAF, 0x21, 0xCA, 0xA8, 0x5E, 0xDA, 0xB1, 0xD0],
+    ),
+    // PSETID_XmlExtractedEntities
+    guid(
+        0x2323_9608,
+        0x685D,
+        0x4732,
+        [0x9C, 0x55, 0x4C, 0x95, 0xCB, 0x4E, 0x8E, 0x33],
+    ),
+];
+
+/// PidLidVerbStream (binary): the voting buttons of a message
+pub const PID_LID_VERB_STREAM: u32 = 0x8520;
+/// PidLidVerbResponse (string): the button a response was sent with
+pub const PID_LID_VERB_RESPONSE: u32 = 0x8524;
+
+/// Verb ids of Reply, Reply All, Forward and Reply To Folder, which precede the voting
+/// options in verb streams
+const STANDARD_VERBS: std::ops::RangeInclusive<u32> = 1..=4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Name {
+    Id(u32),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct NamedProperty {
+    pub guid: Guid,
+    pub name: Name,
+}
+
+/// Voting buttons of a request, and the one chosen for a response
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Voting {
+    pub options: Vec<String>,
+    pub response: Option<String>,
+}
+
+/// A field of a custom Outlook form or a user-defined field
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+}
+
+/// Named properties of a message by property id
+#[derive(Debug, Default)]
+pub struct Mapping(HashMap<u16, NamedProperty>);
+
+impl Mapping {
+    /// Read the mapping from the root of a .msg file
+    pub fn read<F: Read + Seek>(storage: &mut CompoundStorage<F>) -> Self {
+        let stream = |n: u16| format!("/{}/__substg1.0_{:04X}0102", NAMEID_STORAGE, n);
+        let (Some(guids), Some(entries)) = (
+            storage.read_stream(&stream(2)),
+            storage.read_stream(&stream(3)),
+        ) else {
+            return Self::default();
+        };
+        let strings = storage.read_stream(&stream(4)).unwrap_or_default();
+        Self::parse(&guids, &entries, &strings)
+    }
+
+    /// From the GUID, entry and string streams; entries pointing outside them are skipped
+    pub(crate) fn parse(guids: &[u8], entries: &[u8], strings: &[u8]) -> Self {
+        let mut mapping = HashMap::new();
+        for entry in entries.chunks_exact(8) {
+            let name_or_offset = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+            let index_and_kind = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            let guid = match ((index_and_kind & 0xFFFF) >> 1) as usize {
+                0 => continue,
+                1 => PS_MAPI,
+                2 => PS_PUBLIC_STRINGS,
+                n => match guids
+                    .get((n - 3) * 16..(n - 2) * 16)
+                    .and_then(|g| Guid::try_from(g).ok())
+                {
+                    Some(guid) => guid,
+                    None => continue,
+                },
+            };
+            let name = if index_and_kind & 1 == 1 {
+                let offset = name_or_offset as usize;
+                let Some(len) = strings
+                    .get(offset..offset + 4)
+                    .map(|len| u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize)
+                else {
+                    continue;
+                };
+                let Some(name) = strings.get(offset + 4..offset + 4 + len) else {
+                    continue;
+                };
+                Name::String(charset::decode_utf16le(name))
+            } else {
+                Name::Id(name_or_offset)
+            };
+            let Some(id) = 0x8000u16.checked_add((index_and_kind >> 16) as u16) else {
+                continue;
+            };
+            mapping.insert(id, NamedProperty { guid, name });
+        }
+        Self(mapping)
+    }
+
+    /// Property id of a numbered property of a set
+    pub fn id(&self, guid: &Guid, lid: u32) -> Option<u16> {
+        self.0
+            .iter()
+            .find(|(_, prop)| &prop.guid == guid && prop.name == Name::Id(lid))
+            .map(|(id, _)| *id)
+    }
+
+    /// Property id of a string-named property of a set; header names ignore case
+    pub fn named(&self, guid: &Guid, name: &str) -> Option<u16> {
+        self.0
+            .iter()
+            .find(|(_, prop)| {
+                &prop.guid == guid
+                    && matches!(&prop.name, Name::String(n) if n.eq_ignore_ascii_case(name))
+            })
+            .map(|(id, _)| *id)
+    }
+
+    /// Properties with string names in PS_PUBLIC_STRINGS and in the sets of custom forms,
+    /// sorted by name
+    pub fn user_fields(&self) -> Vec<(u16, &str)> {
+        let mut fields: Vec<(u16, &str)> = self
+            .0
+            .iter()
+            .filter(|(_, prop)| !BUILT_IN_SETS.contains(&prop.guid))
+            .filter_map(|(id, prop)| match &prop.name {
+                Name::String(name) => Some((*id, name.as_str())),
+                Name::Id(_) => None,
+            })
+            // Exchange keeps schema properties (`urn:schemas:...`, `http://schemas...`) here too
+            .filter(|(_, name)| !name.starts_with("urn:") && !name.contains("://"))
+            .collect();
+        fields.sort_by(|a, b| a.1.cmp(b.1));
+        fields
+    }
+}
+
+/// Labels of the voting buttons in a PidLidVerbStream (MS-OXOMSG 2.2.1.74), without the
+/// reply and forward verbs. The Unicode labels following the ANSI ones are preferred.
+pub fn voting_options(data: &[u8]) -> Vec<String> {
+    let mut verbs = Vec::new();
+    // Streams of older clients end before the Unicode labels, damaged ones anywhere
+    read_verbs(&mut Reader { data, pos: 0 }, &mut verbs);
+    verbs
+        .into_iter()
+        .filter(|(id, label)| !STANDARD_VERBS.contains(id) && !label.is_empty())
+        .map(|(_, label)| label)
+        .collect()
+}
+
+/// Verb ids and labels, as far as the stream goes
+fn read_verbs(reader: &mut Reader, verbs: &mut Vec<(u32, String)>) -> Option<()> {
+    reader.take(2)?;
+    let count = reader.u32()?;
+    for _ in 0..count {
+        reader.take(4)?; // VerbType
+        let label = reader.ansi()?;
+        reader.ansi()?; // MsgClsName
+        reader.ansi()?; // Internal1String
+        reader.ansi()?; // DisplayNameRepeat
+        reader.take(4 + 1 + 4 + 4 + 4 + 4)?;
+        let id = reader.u32()?;
+        reader.take(4)?;
+        verbs.push((id, label));
+    }
+    // Version 0x0104: a Unicode label (and its repeat) per verb
+    reader.take(2)?;
+    for verb in verbs.iter_mut() {
+        let label = reader.utf16()?;
+        reader.utf16()?;
+        if !label.is_empty() {
+            verb.1 = label;
+        }
+    }
+    Some(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A string of windows-1252 bytes after its length byte
+    fn ansi(&mut self) -> Option<String> {
+        let len = *self.take(1)?.first()? as usize;
+        self.take(len).map(|b| charset::decode(b, "windows-1252"))
+    }
+
+    /// A UTF-16LE string after its length byte, which counts characters
+    fn utf16(&mut self) -> Option<String> {
+        let len = *self.take(1)?.first()? as usize;
+        self.take(len * 2).map(charset::decode_utf16le)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verb(id: u32, label: &str) -> Vec<u8> {
+        let mut data = 4u32.to_le_bytes().to_vec();
+        for text in [label, "IPM.Note", "", label] {
+            data.push(text.len() as u8);
+            data.extend_from_slice(text.as_bytes());
+        }
+        data.extend_from_slice(&[0; 21]);
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let mut data = vec![units.len() as u8];
+        data.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+        data
+    }
+
+    #[test]
+    fn voting_options_prefer_unicode_labels() {
+        let verbs = [
+            (1, "Reply"),
+            (2, "Reply All"),
+            (5, "Approve"),
+            (6, "Reject"),
+        ];
+        let mut data = vec![0x02, 0x01];
+        data.extend_from_slice(&(verbs.len() as u32).to_le_bytes());
+        for (id, label) in verbs {
+            data.extend(verb(id, label));
+        }
+        assert_eq!(voting_options(&data), ["Approve", "Reject"]);
+
+        data.extend_from_slice(&[0x04, 0x01]);
+        for (_, label) in verbs {
+            let label = label.replace("Approve", "Genehmigen ✓");
+            data.extend(utf16(&label));
+            data.extend(utf16(&label));
+        }
+        assert_eq!(voting_options(&data), ["Genehmigen ✓", "Reject"]);
+        assert!(voting_options(&data[..5]).is_empty());
+    }
+
+    #[test]
+    fn mapping_resolves_sets_and_string_names() {
+        let custom = guid(0x1234_5678, 1, 2, [3; 8]);
+        let mut strings = Vec::new();
+        let name: Vec<u8> = "Cost center"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        strings.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        strings.extend(name);
+        let entry = |name: u32, guid_index: u32, string: bool, index: u32| {
+            let mut entry = name.to_le_bytes().to_vec();
+            entry.extend_from_slice(
+                &((index << 16) | (guid_index << 1) | string as u32).to_le_bytes(),
+            );
+            entry
+        };
+        let mut entries = entry(PID_LID_VERB_STREAM, 4, false, 0);
+        entries.extend(entry(0, 3, true, 1));
+        entries.extend(entry(0, 9, true, 2)); // no such GUID
+        let mut guids = custom.to_vec();
+        guids.extend_from_slice(&PSETID_COMMON);
+
+        let mapping = Mapping::parse(&guids, &entries, &strings);
+        assert_eq!(
+            mapping.id(&PSETID_COMMON, PID_LID_VERB_STREAM),
+            Some(0x8000)
+        );
+        assert_eq!(mapping.user_fields(), [(0x8001, "Cost center")]);
+        assert_eq!(mapping.named(&custom, "cost CENTER"), Some(0x8001));
+        assert_eq!(mapping.named(&PS_INTERNET_HEADERS, "Cost center"), None);
+    }
+}
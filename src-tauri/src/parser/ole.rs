@@ -0,0 +1,320 @@
+use super::charset;
+use super::storage::CompoundStorage;
+use crate::i18n::Localized;
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+/// OLE 1.0 native data stream; holds the packaged file for "Package" objects
+pub(super) const OLE10_NATIVE: &str = "\u{1}Ole10Native";
+
+/// Streams marking a complete Office document stored as the object's storage
+const DOCUMENT_STREAMS: &[&str] = &["WordDocument", "Workbook", "Book", "PowerPoint Document"];
+
+/// Package format id for an embedded (not linked) file
+const PACKAGE_EMBEDDED: u16 = 3;
+
+/// The file recovered from an OLE attachment
+#[derive(Debug)]
+pub struct OleObject {
+    pub file_name: String,
+    pub data: Vec<u8>,
+    pub warning: Option<Localized>,
+}
+
+/// Extract the payload of an `ATTACH_OLE` attachment whose object storage is at `path`;
+/// `fallback_name` names results that carry no name of their own
+pub fn extract<F: Read + Seek>(
+    storage: &mut CompoundStorage<F>,
+    path: &str,
+    fallback_name: &str,
+) -> Result<OleObject, String> {
+    let children: Vec<String> = storage
+        .children(path)
+        .into_iter()
+        .filter(|entry| !entry.is_storage)
+        .map(|entry| entry.name)
+        .collect();
+    let has = |name: &str| children.iter().any(|child| child == name);
+    let stream = |name: &str| format!("{}/{}", path.trim_end_matches('/'), name);
+
+    if has(OLE10_NATIVE) {
+        if let Some(data) = storage.read_stream(&stream(OLE10_NATIVE)) {
+            if let Some((name, payload)) = parse_package(&data) {
+                return Ok(OleObject {
+                    file_name: name.unwrap_or_else(|| fallback_name.to_string()),
+                    data: payload,
+                    warning: None,
+                });
+            }
+            if let Some(payload) = parse_raw_native(&data) {
+                return Ok(OleObject {
+                    file_name: fallback_name.to_string(),
+                    data: payload,
+                    warning: None,
+                });
+            }
+        }
+    }
+
+    // Word/Excel/PowerPoint objects are whole documents; copying the storage makes them openable
+    if DOCUMENT_STREAMS.iter().any(|name| has(name)) {
+        return Ok(OleObject {
+            file_name: fallback_name.to_string(),
+            data: storage.export_storage(path)?,
+            warning: None,
+        });
+    }
+
+    // OOXML documents keep the zip package in a stream; Acrobat and others use CONTENTS
+    for name in ["Package", "CONTENTS"] {
+        if has(name) {
+            if let Some(data) = storage.read_stream(&stream(name)) {
+                return Ok(OleObject {
+                    file_name: fallback_name.to_string(),
+                    data,
+                    warning: None,
+                });
+            }
+        }
+    }
+
+    Ok(OleObject {
+        file_name: format!("{}.bin", fallback_name),
+        data: storage.export_storage(path)?,
+        warning: Some(Localized::new(
+            "warning.ole_unknown_layout",
+            [("name", fallback_name.to_string())],
+        )),
+    })
+}
+
+/// Little-endian reader over a byte slice
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// NUL-terminated ANSI string
+    fn cstring(&mut self) -> Option<String> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        self.pos += len + 1;
+        Some(charset::decode(&rest[..len], "windows-1252"))
+    }
+
+    /// Length-prefixed UTF-16 string (length in characters)
+    fn unicode(&mut self) -> Option<String> {
+        let chars = self.u32()? as usize;
+        let bytes = self.take(chars.checked_mul(2)?)?;
+        Some(charset::decode_utf16le(bytes))
+    }
+}
+
+/// Parse a Package object: label, original path, temp path and the embedded file.
+/// Newer writers append unicode copies of the names, which are preferred when present.
+fn parse_package(data: &[u8]) -> Option<(Option<String>, Vec<u8>)> {
+    let mut cursor = Cursor { data, pos: 0 };
+    let (label, original_path, payload) = package_header(&mut cursor)?;
+    let payload = data[payload].to_vec();
+
+    let unicode_label = cursor
+        .unicode()
+        .and_then(|_temp_path| cursor.unicode())
+        .filter(|name| !name.is_empty());
+
+    let name = unicode_label
+        .or_else(|| (!label.is_empty()).then_some(label))
+        .or_else(|| {
+            original_path
+                .rsplit(['\\', '/'])
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        });
+    Some((name, payload))
+}
+
+/// Label, original path and where the embedded file lies, leaving `cursor` behind it
+fn package_header(cursor: &mut Cursor) -> Option<(String, String, Range<usize>)> {
+    let _total_size = cursor.u32()?;
+    if cursor.u16()? != 2 {
+        return None;
+    }
+    let label = cursor.cstring()?;
+    let original_path = cursor.cstring()?;
+    let _reserved = cursor.u16()?;
+    if cursor.u16()? != PACKAGE_EMBEDDED {
+        return None;
+    }
+    let temp_path_len = cursor.u32()? as usize;
+    cursor.take(temp_path_len)?;
+    let payload_len = cursor.u32()? as usize;
+    let start = cursor.pos;
+    cursor.take(payload_len)?;
+    Some((label, original_path, start..cursor.pos))
+}
+
+/// OLE 1.0 objects other than packages: a size prefix followed by the native data
+fn parse_raw_native(data: &[u8]) -> Option<Vec<u8>> {
+    raw_native(data).map(|payload| data[payload].to_vec())
+}
+
+fn raw_native(data: &[u8]) -> Option<Range<usize>> {
+    let mut cursor = Cursor { data, pos: 0 };
+    let len = cursor.u32()? as usize;
+    (len > 0).then_some(())?;
+    cursor.take(len)?;
+    Some(4..cursor.pos)
+}
+
+/// Where the file inside an `\u{1}Ole10Native` stream lies, as `extract` would find it
+pub(super) fn native_payload(data: &[u8]) -> Option<Range<usize>> {
+    package_header(&mut Cursor { data, pos: 0 })
+        .map(|(_, _, payload)| payload)
+        .or_else(|| raw_native(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A compound file whose `/object` storage holds the given streams
+    fn object(streams: &[(&str, &[u8])]) -> CompoundStorage<std::io::Cursor<Vec<u8>>> {
+        let mut file = cfb::CompoundFile::create(std::io::Cursor::new(Vec::new())).unwrap();
+        file.create_storage("/object").unwrap();
+        for (name, data) in streams {
+            file.create_stream(format!("/object/{name}"))
+                .unwrap()
+                .write_all(data)
+                .unwrap();
+        }
+        CompoundStorage::open(std::io::Cursor::new(file.into_inner().into_inner())).unwrap()
+    }
+
+    /// Ole10Native stream of a Package object; `unicode` appends the newer name copies
+    fn package(label: &[u8], path: &[u8], payload: &[u8], unicode: Option<&str>) -> Vec<u8> {
+        let mut body = 2u16.to_le_bytes().to_vec();
+        body.extend([label, b"\0", path, b"\0"].concat());
+        body.extend(0u16.to_le_bytes());
+        body.extend(PACKAGE_EMBEDDED.to_le_bytes());
+        body.extend(4u32.to_le_bytes());
+        body.extend(b"C:\\t");
+        body.extend((payload.len() as u32).to_le_bytes());
+        body.extend(payload);
+        if let Some(name) = unicode {
+            for text in ["C:\\temp\\x", name] {
+                let units: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                body.extend((units.len() as u32 / 2).to_le_bytes());
+                body.extend(units);
+            }
+        }
+        [(body.len() as u32).to_le_bytes().to_vec(), body].concat()
+    }
+
+    fn extracted(streams: &[(&str, &[u8])]) -> OleObject {
+        extract(&mut object(streams), "/object", "Attachment 1").unwrap()
+    }
+
+    #[test]
+    fn packages_give_the_embedded_file_and_its_name() {
+        let ansi = package(
+            b"Budget.xlsx",
+            b"C:\\Users\\ann\\Budget.xlsx",
+            b"PK\x03\x04",
+            None,
+        );
+        let object = extracted(&[(OLE10_NATIVE, &ansi)]);
+        assert_eq!(object.file_name, "Budget.xlsx");
+        assert_eq!(object.data, b"PK\x03\x04");
+        assert!(object.warning.is_none());
+        assert_eq!(
+            native_payload(&ansi).map(|range| &ansi[range]),
+            Some(&b"PK\x03\x04"[..])
+        );
+
+        // The unicode label wins, then the file name of the original path
+        let unicode = package(b"Pr?fung.pdf", b"", b"%PDF", Some("Prüfung.pdf"));
+        assert_eq!(
+            extracted(&[(OLE10_NATIVE, &unicode)]).file_name,
+            "Prüfung.pdf"
+        );
+        let unlabelled = package(b"", b"C:\\Users\\ann\\notes.txt", b"hi", None);
+        assert_eq!(
+            extracted(&[(OLE10_NATIVE, &unlabelled)]).file_name,
+            "notes.txt"
+        );
+        let nameless = package(b"", b"", b"hi", None);
+        assert_eq!(
+            extracted(&[(OLE10_NATIVE, &nameless)]).file_name,
+            "Attachment 1"
+        );
+    }
+
+    #[test]
+    fn damaged_packages_fall_back_to_the_raw_native_data() {
+        let good = package(b"a.txt", b"", b"payload", None);
+
+        // A payload length past the end, or a label without its terminator: the stream is
+        // taken as plain native data behind its size prefix
+        let mut oversize = good.clone();
+        let len_at = good.len() - b"payload".len() - 4;
+        oversize[len_at..len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut unterminated = (12u32).to_le_bytes().to_vec();
+        unterminated.extend(2u16.to_le_bytes());
+        unterminated.extend(b"aaaaaaaaaa");
+        for data in [&oversize, &unterminated] {
+            let object = extracted(&[(OLE10_NATIVE, data)]);
+            assert_eq!(object.file_name, "Attachment 1");
+            assert_eq!(object.data, data[4..]);
+            assert!(object.warning.is_none());
+        }
+
+        // Cut inside the header, nothing in the stream can be trusted and the whole
+        // object storage is kept
+        let truncated = &good[..10];
+        let object = extracted(&[(OLE10_NATIVE, truncated)]);
+        assert_eq!(object.file_name, "Attachment 1.bin");
+        assert_eq!(
+            object.warning.map(|w| w.code),
+            Some("warning.ole_unknown_layout")
+        );
+        let mut copy = CompoundStorage::open(std::io::Cursor::new(object.data)).unwrap();
+        assert_eq!(
+            copy.read_stream(&format!("/{OLE10_NATIVE}")).unwrap(),
+            truncated
+        );
+        assert_eq!(native_payload(truncated), None);
+    }
+
+    #[test]
+    fn other_objects_keep_their_document_or_contents() {
+        let object = extracted(&[("Package", b"PK\x03\x04docx")]);
+        assert_eq!(object.data, b"PK\x03\x04docx");
+        let object = extracted(&[("CONTENTS", b"%PDF-1.4")]);
+        assert_eq!(
+            (object.file_name.as_str(), &object.data[..]),
+            ("Attachment 1", &b"%PDF-1.4"[..])
+        );
+
+        let object = extracted(&[("WordDocument", b"doc")]);
+        let mut document = CompoundStorage::open(std::io::Cursor::new(object.data)).unwrap();
+        assert_eq!(document.read_stream("/WordDocument").unwrap(), b"doc");
+    }
+}
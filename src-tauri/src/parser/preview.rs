@@ -0,0 +1,260 @@
+//! Reading-pane previews: the start of what the sender wrote, without greetings, quoted
+//! replies, signatures and disclaimers.
+
+use super::{calendar, signature, text, Message};
+use crate::i18n::{self, Lang};
+
+/// Preview length in characters
+pub const PREVIEW_CHARS: usize = 200;
+
+/// Lines this short that open with a greeting are taken for salutations only
+const MAX_GREETING_CHARS: usize = 40;
+
+const GREETINGS: &[&str] = &[
+    "hi",
+    "hello",
+    "hey",
+    "dear",
+    "good morning",
+    "good afternoon",
+    "good evening",
+    "hallo",
+    "liebe",
+    "lieber",
+    "sehr geehrte",
+    "sehr geehrter",
+    "guten tag",
+    "moin",
+    "bonjour",
+    "hola",
+];
+
+/// Preview text of a message: meeting invitations are summarized, image-only bodies show
+/// as "[image]", anything else gives the start of the sender's own text on one line
+pub fn snippet(message: &Message, lang: Lang) -> String {
+    if let Some(invitation) = invitation(message, lang) {
+        return invitation;
+    }
+
+    // The parser found the signature of text bodies already
+    let (body, signature) = match (&message.body_text, &message.body_html) {
+        (Some(text), _) if !text.trim().is_empty() => (text.clone(), message.signature),
+        (_, Some(html)) => {
+            let text = text::html_to_text(html, None);
+            let signature = signature::detect(&text);
+            (text, signature)
+        }
+        _ => (String::new(), None),
+    };
+    let own = signature
+        .and_then(|range| body.get(..range.start))
+        .unwrap_or(&body);
+
+    let mut words = String::new();
+    for line in own.lines() {
+        let line = line.trim();
+        if signature::is_quote_marker(line) || signature::is_valediction(line) {
+            break;
+        }
+        if line.is_empty()
+            || line.starts_with('>')
+            || is_greeting(line)
+            || signature::is_disclaimer(line)
+        {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            if !words.is_empty() {
+                words.push(' ');
+            }
+            words.push_str(word);
+        }
+        if words.chars().count() > PREVIEW_CHARS {
+            break;
+        }
+    }
+
+    if words.is_empty() && shows_images(message) {
+        return i18n::text(lang, "preview.image", &[]);
+    }
+    truncate(words)
+}
+
+fn is_greeting(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    lower.chars().count() <= MAX_GREETING_CHARS
+        && (lower.ends_with(',') || lower.ends_with('!') || !lower.contains(' '))
+        && GREETINGS.iter().any(|greeting| {
+            lower
+                .strip_prefix(greeting)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', ',', '!']))
+        })
+}
+
+/// Whether the body, lacking text, consists of images
+fn shows_images(message: &Message) -> bool {
+    message
+        .body_html
+        .as_deref()
+        .is_some_and(|html| html.to_ascii_lowercase().contains("<img"))
+        || message
+            .attachments
+            .iter()
+            .any(|a| a.referenced_in_body || a.hidden)
+}
+
+/// At most `PREVIEW_CHARS` characters, cut at a word boundary
+fn truncate(text: String) -> String {
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text;
+    }
+    let mut cut: String = text.chars().take(PREVIEW_CHARS).collect();
+    if let Some(space) = cut.rfind(' ') {
+        cut.truncate(space);
+    }
+    cut.push('…');
+    cut
+}
+
+/// "Invitation: {title}, {date}" for meeting requests, from their iCalendar part; .msg
+/// meeting requests carry no such part and are summarized without the date
+fn invitation(message: &Message, lang: Lang) -> Option<String> {
+    if let Some(calendar) = calendar::find(message) {
+        let data = String::from_utf8_lossy(&calendar.data);
+        // Only requests are invitations; replies and cancellations read like mail
+        let event = Calendar::parse(&data);
+        if event.method.as_deref().is_none_or(|m| m == "REQUEST") && event.summary.is_some() {
+            let title = event.summary.unwrap_or_default();
+            return Some(match event.start {
+                Some(date) => i18n::text(
+                    lang,
+                    "preview.invitation",
+                    &[("title", title), ("date", date)],
+                ),
+                None => i18n::text(lang, "preview.invitation_undated", &[("title", title)]),
+            });
+        }
+    }
+    let is_request = message
+        .message_class
+        .as_deref()
+        .is_some_and(|class| class.eq_ignore_ascii_case("IPM.Schedule.Meeting.Request"));
+    is_request.then(|| {
+        i18n::text(
+            lang,
+            "preview.invitation_undated",
+            &[("title", message.subject.clone().unwrap_or_default())],
+        )
+    })
+}
+
+/// What a preview needs from an iCalendar object
+#[derive(Default)]
+struct Calendar {
+    method: Option<String>,
+    summary: Option<String>,
+    /// Formatted for display
+    start: Option<String>,
+}
+
+impl Calendar {
+    fn parse(data: &str) -> Self {
+        let mut calendar = Calendar::default();
+        let mut in_event = false;
+        for line in calendar::unfold(data).lines() {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (name, _params) = name.split_once(';').unwrap_or((name, ""));
+            match name.to_ascii_uppercase().as_str() {
+                "METHOD" => calendar.method = Some(value.trim().to_ascii_uppercase()),
+                "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => in_event = true,
+                "END" if value.eq_ignore_ascii_case("VEVENT") => break,
+                "SUMMARY" if in_event => {
+                    calendar.summary = Some(
+                        value
+                            .replace("\\,", ",")
+                            .replace("\\;", ";")
+                            .replace("\\n", " ")
+                            .replace("\\N", " ")
+                            .replace("\\\\", "\\"),
+                    )
+                }
+                "DTSTART" if in_event => calendar.start = format_ical_date(value.trim()),
+                _ => {}
+            }
+        }
+        calendar
+    }
+}
+
+/// `20261014T090000Z` as `2026-10-14 09:00 UTC`, `20261014` as `2026-10-14`; times without
+/// `Z` are in the event's own time zone and shown as they are
+fn format_ical_date(value: &str) -> Option<String> {
+    let date = chrono::NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+    let Some(time) = value.get(9..15) else {
+        return Some(date.format("%Y-%m-%d").to_string());
+    };
+    let time = chrono::NaiveTime::parse_from_str(time, "%H%M%S").ok()?;
+    let zone = if value.ends_with('Z') { " UTC" } else { "" };
+    Some(format!(
+        "{} {}{}",
+        date.format("%Y-%m-%d"),
+        time.format("%H:%M"),
+        zone
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Attachment, MessageFormat};
+
+    fn message(text: &str) -> Message {
+        let mut message = Message::new(MessageFormat::Eml);
+        message.body_text = Some(text.to_string());
+        message.signature = signature::detect(text);
+        message
+    }
+
+    #[test]
+    fn previews_skip_greetings_quotes_and_signatures() {
+        let text = "Hi Anna,\n\nthe   contract is attached.\n> earlier question\nPlease sign by Friday.\n\n\
+                    Best regards,\nTom\nPhone: +49 30 1234\nMobile: +49 170 1234\n\n\
+                    On Mon, 12 Oct 2026, Anna wrote:\n> Where is the contract?\n";
+        assert_eq!(
+            snippet(&message(text), Lang::En),
+            "the contract is attached. Please sign by Friday."
+        );
+
+        let long = format!("Hello!\n{}", "word ".repeat(100));
+        let preview = snippet(&message(&long), Lang::En);
+        assert!(preview.ends_with("word…"));
+        assert!(preview.chars().count() <= PREVIEW_CHARS + 1);
+        assert!(!is_greeting(
+            "Hi there, the report is ready and attached for review"
+        ));
+    }
+
+    #[test]
+    fn images_and_invitations() {
+        let mut image = Message::new(MessageFormat::Eml);
+        image.body_html = Some("<p><img src=\"cid:scan\"></p>".to_string());
+        assert_eq!(snippet(&image, Lang::En), "[image]");
+
+        let mut invite = message("You have been invited.");
+        invite.attachments.push(Attachment::new(
+            0,
+            Some("invite.ics".to_string()),
+            Some("text/calendar".to_string()),
+            None,
+            b"BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nSUMMARY:Quarterly\r\n  review\\, Q3\r\n\
+              DTSTART;TZID=Europe/Berlin:20261014T090000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+                .to_vec(),
+        ));
+        assert_eq!(
+            snippet(&invite, Lang::En),
+            "Invitation: Quarterly review, Q3, 2026-10-14 09:00"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Microsoft Purview sensitivity labels and rights-managed (IRM) messages. Labels are read
+//! from the `msip_labels` header, which Outlook also keeps as a named property of .msg
+//! files. Rights-managed content comes as an encrypted `message.rpmsg` attachment; it isn't
+//! decrypted, the message is only reported as protected.
+
+use super::{Message, MessageFormat};
+use crate::i18n::Localized;
+use serde::Serialize;
+
+/// Header (and PS_INTERNET_HEADERS property name) carrying the labels
+pub const MSIP_LABELS: &str = "msip_labels";
+
+/// Bit of `ContentBits` for labels that encrypt the content
+const CONTENT_BITS_ENCRYPTION: u32 = 0x8;
+
+/// Attachment holding the encrypted message of rights-managed mail
+const RPMSG_NAME: &str = "message.rpmsg";
+const RPMSG_MIME: &str = "application/x-microsoft-rpmsg-message";
+
+/// The sensitivity label applied to a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LabelInfo {
+    /// Label GUID, lowercase
+    pub id: String,
+    /// Display name; older clients only record the GUID
+    pub name: Option<String>,
+    /// Tenant the label belongs to
+    pub site_id: Option<String>,
+    /// `Standard` (applied by policy or default) or `Privileged` (chosen by the user)
+    pub method: Option<String>,
+    pub set_date: Option<String>,
+    /// The label encrypts the content
+    pub encrypted: bool,
+}
+
+/// Content that is only readable with the rights-management key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContentProtected {
+    /// Name or, lacking one, GUID of the label, if any
+    pub label: Option<String>,
+    /// Index of the encrypted `message.rpmsg` attachment
+    pub attachment: Option<usize>,
+}
+
+/// The enabled label of an `msip_labels` value such as
+/// `MSIP_Label_<guid>_Enabled=true; MSIP_Label_<guid>_Name=Confidential; ...`
+pub fn parse_labels(value: &str) -> Option<LabelInfo> {
+    let mut labels: Vec<(LabelInfo, bool)> = Vec::new();
+    for field in value.split(';') {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        let Some((id, property)) = key
+            .trim()
+            .strip_prefix("MSIP_Label_")
+            .and_then(|rest| rest.rsplit_once('_'))
+        else {
+            continue;
+        };
+        let id = id.to_ascii_lowercase();
+        let index = match labels.iter().position(|(label, _)| label.id == id) {
+            Some(index) => index,
+            None => {
+                labels.push((
+                    LabelInfo {
+                        id,
+                        name: None,
+                        site_id: None,
+                        method: None,
+                        set_date: None,
+                        encrypted: false,
+                    },
+                    false,
+                ));
+                labels.len() - 1
+            }
+        };
+        let (label, enabled) = &mut labels[index];
+        let value = value.trim();
+        let text = Some(value.to_string()).filter(|value| !value.is_empty());
+        match property.to_ascii_lowercase().as_str() {
+            "enabled" => *enabled = value.eq_ignore_ascii_case("true"),
+            "name" => label.name = text,
+            "siteid" => label.site_id = text,
+            "method" => label.method = text,
+            "setdate" => label.set_date = text,
+            "contentbits" => {
+                label.encrypted = value
+                    .parse::<u32>()
+                    .is_ok_and(|bits| bits & CONTENT_BITS_ENCRYPTION != 0)
+            }
+            _ => {}
+        }
+    }
+    labels
+        .into_iter()
+        .find(|(_, enabled)| *enabled)
+        .map(|(label, _)| label)
+}
+
+/// Fill in the label from the headers (unless the .msg properties had it) and report
+/// rights-managed content
+pub fn apply(message: &mut Message) {
+    if message.sensitivity_label.is_none() {
+        message.sensitivity_label = message
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(MSIP_LABELS))
+            .find_map(|(_, value)| parse_labels(value));
+    }
+
+    let attachment = message
+        .attachments
+        .iter()
+        .find(|attachment| {
+            attachment.file_name.eq_ignore_ascii_case(RPMSG_NAME)
+                || attachment
+                    .declared_mime
+                    .as_deref()
+                    .is_some_and(|mime| mime.eq_ignore_ascii_case(RPMSG_MIME))
+        })
+        .map(|attachment| attachment.index);
+    let class = match message.format {
+        MessageFormat::Msg => message.message_class.clone(),
+        MessageFormat::Eml | MessageFormat::Emlx => message
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Class"))
+            .map(|(_, value)| value.clone()),
+    };
+    // IPM.Note.rpmsg.* in .msg files, rpmsg.message over SMTP
+    let rights_managed = class.is_some_and(|class| {
+        let class = class.trim().to_ascii_lowercase();
+        class.starts_with("ipm.note.rpmsg") || class == "rpmsg.message"
+    });
+    if attachment.is_none() && !rights_managed {
+        return;
+    }
+
+    let label = message
+        .sensitivity_label
+        .as_ref()
+        .map(|label| label.name.clone().unwrap_or_else(|| label.id.clone()));
+    message.warnings.push(match &label {
+        Some(label) => Localized::new(
+            "warning.content_protected_label",
+            [("label", label.clone())],
+        ),
+        None => Localized::new("warning.content_protected", []),
+    });
+    if let Some(label) = &mut message.sensitivity_label {
+        label.encrypted = true;
+    }
+    message.content_protected = Some(ContentProtected { label, attachment });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, ParseOptions};
+
+    const GUID: &str = "4e8f2b1c-9a7d-4c3e-b6f5-0d1e2f3a4b5c";
+
+    fn parse(data: &[u8]) -> Message {
+        parse_bytes(MessageFormat::Eml, data, &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn labels_are_read_from_the_header() {
+        let message = parse(include_bytes!(
+            "../../tests/fixtures/protection/labelled.eml"
+        ));
+        let label = message.sensitivity_label.unwrap();
+        assert_eq!(label.id, GUID);
+        assert_eq!(label.name.as_deref(), Some("Confidential - Finance"));
+        assert_eq!(label.method.as_deref(), Some("Privileged"));
+        assert_eq!(
+            label.site_id.as_deref(),
+            Some("72f988bf-86f1-41af-91ab-2d7cd011db47")
+        );
+        // Header and footer marking only
+        assert!(!label.encrypted);
+        assert_eq!(message.content_protected, None);
+        assert!(message.body_text.is_some());
+
+        // Disabled (removed) labels don't count
+        let removed = format!(
+            "MSIP_Label_{0}_Enabled=false; MSIP_Label_{0}_Name=Public",
+            GUID
+        );
+        assert_eq!(parse_labels(&removed), None);
+    }
+
+    #[test]
+    fn rights_managed_messages_are_reported_as_protected() {
+        let message = parse(include_bytes!(
+            "../../tests/fixtures/protection/rights-managed.eml"
+        ));
+        let label = message.sensitivity_label.as_ref().unwrap();
+        assert_eq!(label.name.as_deref(), Some("Highly Confidential"));
+        assert!(label.encrypted);
+        assert_eq!(
+            message.content_protected,
+            Some(ContentProtected {
+                label: Some("Highly Confidential".to_string()),
+                attachment: Some(0),
+            })
+        );
+        assert!(message
+            .warnings
+            .iter()
+            .any(|warning| warning.code == "warning.content_protected_label"));
+    }
+}
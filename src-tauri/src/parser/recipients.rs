@@ -0,0 +1,185 @@
+//! Recipient lists of company-wide announcements run to thousands. The viewer gets the
+//! first [`INLINE_RECIPIENTS`] of each type with the message and pages through the rest,
+//! so the parse result stays small; parsers keep only the [`Window`] asked for and count
+//! the others. .msg files don't even read the names and addresses outside the window.
+
+use super::{Address, Message};
+use serde::{Deserialize, Serialize};
+
+/// Recipients of each type returned with the message to the viewer
+pub const INLINE_RECIPIENTS: usize = 100;
+
+/// Largest page of `get_all_recipients`
+pub const MAX_PAGE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipientType {
+    To,
+    Cc,
+    Bcc,
+}
+
+/// Positions of the recipients kept of each type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Window {
+    pub fn contains(&self, position: usize) -> bool {
+        position >= self.offset && position - self.offset < self.limit
+    }
+}
+
+/// How many recipients of each type the message has, kept or not
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RecipientCounts {
+    pub to: usize,
+    pub cc: usize,
+    pub bcc: usize,
+}
+
+impl RecipientCounts {
+    pub fn get(&self, kind: RecipientType) -> usize {
+        match kind {
+            RecipientType::To => self.to,
+            RecipientType::Cc => self.cc,
+            RecipientType::Bcc => self.bcc,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.to + self.cc + self.bcc
+    }
+
+    /// Count one more recipient of `kind`, returning its position
+    pub fn add(&mut self, kind: RecipientType) -> usize {
+        let count = match kind {
+            RecipientType::To => &mut self.to,
+            RecipientType::Cc => &mut self.cc,
+            RecipientType::Bcc => &mut self.bcc,
+        };
+        *count += 1;
+        *count - 1
+    }
+}
+
+/// A page of `get_all_recipients`
+#[derive(Debug, Clone, Serialize)]
+pub struct RecipientPage {
+    pub kind: RecipientType,
+    /// Recipients of this type in the message
+    pub total: usize,
+    pub offset: usize,
+    pub recipients: Vec<Address>,
+}
+
+pub fn list(message: &Message, kind: RecipientType) -> &[Address] {
+    match kind {
+        RecipientType::To => &message.to,
+        RecipientType::Cc => &message.cc,
+        RecipientType::Bcc => &message.bcc,
+    }
+}
+
+pub fn list_mut(message: &mut Message, kind: RecipientType) -> &mut Vec<Address> {
+    match kind {
+        RecipientType::To => &mut message.to,
+        RecipientType::Cc => &mut message.cc,
+        RecipientType::Bcc => &mut message.bcc,
+    }
+}
+
+/// Store a completely parsed list (a header), counted, keeping only `window` of it
+pub fn keep(
+    message: &mut Message,
+    kind: RecipientType,
+    addresses: Vec<Address>,
+    window: Option<Window>,
+) {
+    let mut kept = Vec::new();
+    for address in addresses {
+        let position = message.recipient_counts.add(kind);
+        if window.is_none_or(|window| window.contains(position)) {
+            kept.push(address);
+        }
+    }
+    *list_mut(message, kind) = kept;
+}
+
+/// Set `total_recipients` and `recipients_truncated` once the lists are final. Lists
+/// filled without counting (attached messages built by hand) count as complete.
+pub fn summarize(message: &mut Message) {
+    let counts = &mut message.recipient_counts;
+    counts.to = counts.to.max(message.to.len());
+    counts.cc = counts.cc.max(message.cc.len());
+    counts.bcc = counts.bcc.max(message.bcc.len());
+    message.total_recipients = counts.total();
+    message.recipients_truncated =
+        message.total_recipients > message.to.len() + message.cc.len() + message.bcc.len();
+}
+
+/// `kind` of a message parsed with a window starting at `offset`
+pub fn page(message: &Message, kind: RecipientType, offset: usize) -> RecipientPage {
+    RecipientPage {
+        kind,
+        total: message.recipient_counts.get(kind),
+        offset,
+        recipients: list(message, kind).to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    fn announcement() -> Vec<u8> {
+        let to: Vec<String> = (0..2500)
+            .map(|i| format!("Employee {0} <employee{0}@example.com>", i))
+            .collect();
+        format!(
+            "From: ceo@example.com\r\nTo: {}\r\nCc: board@example.com\r\n\
+             Subject: All hands\r\n\r\nSee you there.\r\n",
+            to.join(",\r\n ")
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn long_lists_are_kept_a_window_at_a_time() {
+        let data = announcement();
+        let parse = |window| {
+            let options = ParseOptions {
+                recipient_window: window,
+                ..ParseOptions::default()
+            };
+            parse_bytes(MessageFormat::Eml, &data, &options).unwrap()
+        };
+
+        let full = parse(None);
+        assert_eq!(full.to.len(), 2500);
+        assert_eq!(full.total_recipients, 2501);
+        assert!(!full.recipients_truncated);
+
+        let inline = parse(Some(Window {
+            offset: 0,
+            limit: INLINE_RECIPIENTS,
+        }));
+        assert_eq!(inline.to.len(), INLINE_RECIPIENTS);
+        assert_eq!(inline.cc.len(), 1);
+        assert_eq!(inline.total_recipients, 2501);
+        assert!(inline.recipients_truncated);
+
+        let window = Window {
+            offset: 2450,
+            limit: 100,
+        };
+        let page = page(&parse(Some(window)), RecipientType::To, window.offset);
+        assert_eq!(page.total, 2500);
+        assert_eq!(page.recipients.len(), 50);
+        assert_eq!(page.recipients, full.to[2450..]);
+    }
+}
@@ -0,0 +1,472 @@
+use super::limits::{Budget, Limit};
+use crate::i18n::Localized;
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const HEADER_LEN: usize = 512;
+const HEADER_DIFAT_ENTRIES: usize = 109;
+const DIR_ENTRY_LEN: usize = 128;
+const MINI_SECTOR_LEN: usize = 64;
+
+/// Sector ids above this are markers (end of chain, free, FAT/DIFAT sector)
+const MAX_REGULAR_SECTOR: u32 = 0xFFFF_FFFA;
+/// Unused sibling/child pointer of a directory entry
+const NO_STREAM: u32 = 0xFFFF_FFFF;
+
+const OBJ_STORAGE: u8 = 1;
+const OBJ_STREAM: u8 = 2;
+const OBJ_ROOT: u8 = 5;
+
+/// Storages nested deeper than this are treated as a crafted file; real messages stay far
+/// below it even with several levels of attached messages
+const MAX_STORAGE_DEPTH: usize = 64;
+
+/// A damaged compound file rewritten as a valid one, with what couldn't be recovered
+pub struct Recovered {
+    pub data: Vec<u8>,
+    pub warnings: Vec<Localized>,
+}
+
+struct DirEntry {
+    name: String,
+    kind: u8,
+    left: u32,
+    right: u32,
+    child: u32,
+    clsid: [u8; 16],
+    start: u32,
+    size: u64,
+}
+
+/// Lenient reader for compound files that fail strict validation (truncated files, broken
+/// chains, bad directory pointers); reads whatever is reachable instead of failing
+struct Reader<'a> {
+    data: &'a [u8],
+    sector_len: usize,
+    mini_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    warnings: Vec<Localized>,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn u32s(data: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    data.chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+impl<'a> Reader<'a> {
+    /// A sector's content; shorter for a cut-off last sector, `None` past the end of the file
+    fn sector(&self, id: u32) -> Option<&'a [u8]> {
+        let start = (id as usize).checked_add(1)?.checked_mul(self.sector_len)?;
+        let available = self.data.get(start..)?;
+        (!available.is_empty()).then(|| &available[..available.len().min(self.sector_len)])
+    }
+
+    /// A sector that is fully present
+    fn whole_sector(&self, id: u32) -> Option<&'a [u8]> {
+        self.sector(id)
+            .filter(|sector| sector.len() == self.sector_len)
+    }
+
+    /// Sectors of the chain starting at `start`, up to a loop or the end of the file
+    fn chain(&self, start: u32, table: &[u32], sector_exists: impl Fn(u32) -> bool) -> Vec<u32> {
+        let mut sectors = Vec::new();
+        let mut seen = HashSet::new();
+        let mut id = start;
+        while id <= MAX_REGULAR_SECTOR && sector_exists(id) && seen.insert(id) {
+            sectors.push(id);
+            id = table.get(id as usize).copied().unwrap_or(NO_STREAM);
+        }
+        sectors
+    }
+
+    /// Regular sectors of a chain, concatenated up to the first one that is cut off
+    fn read_chain(&self, start: u32) -> Vec<u8> {
+        let exists = |id: u32| self.sector(id).is_some();
+        let mut data = Vec::new();
+        for id in self.chain(start, &self.fat, exists) {
+            let sector = self.sector(id).unwrap_or_default();
+            data.extend_from_slice(sector);
+            if sector.len() < self.sector_len {
+                break;
+            }
+        }
+        data
+    }
+
+    /// Data of a stream; shorter than its size if part of it is lost
+    fn read_stream(&self, entry: &DirEntry) -> Vec<u8> {
+        let mut data = if entry.size < self.mini_cutoff {
+            let mini_sectors = self.mini_stream.len() / MINI_SECTOR_LEN;
+            self.chain(entry.start, &self.mini_fat, |id| {
+                (id as usize) < mini_sectors
+            })
+            .into_iter()
+            .flat_map(|id| {
+                let start = id as usize * MINI_SECTOR_LEN;
+                self.mini_stream[start..start + MINI_SECTOR_LEN]
+                    .iter()
+                    .copied()
+            })
+            .collect()
+        } else {
+            self.read_chain(entry.start)
+        };
+        data.truncate(entry.size.min(usize::MAX as u64) as usize);
+        data
+    }
+
+    /// FAT sector ids from the header and the DIFAT chain
+    fn fat_sectors(&mut self, header: &[u8]) -> Vec<u32> {
+        let mut ids: Vec<u32> = u32s(&header[0x4C..0x4C + HEADER_DIFAT_ENTRIES * 4]).collect();
+        let mut next = u32_at(header, 0x44);
+        let mut seen = HashSet::new();
+        while next <= MAX_REGULAR_SECTOR && seen.insert(next) {
+            let Some(sector) = self.whole_sector(next) else {
+                self.warnings
+                    .push(Localized::new("warning.recovery.difat_missing", []));
+                break;
+            };
+            let (entries, link) = sector.split_at(self.sector_len - 4);
+            ids.extend(u32s(entries));
+            next = u32_at(link, 0);
+        }
+        ids.retain(|&id| id <= MAX_REGULAR_SECTOR);
+        ids.truncate(u32_at(header, 0x2C) as usize);
+        ids
+    }
+
+    fn directory(&mut self, first: u32, major_version: u16) -> Vec<DirEntry> {
+        let data = self.read_chain(first);
+        data.chunks_exact(DIR_ENTRY_LEN)
+            .map(|raw| {
+                let name_len = (u16_at(raw, 64) as usize).min(64);
+                let units: Vec<u16> = raw[..name_len]
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                    .take_while(|&unit| unit != 0)
+                    .collect();
+                let mut clsid = [0u8; 16];
+                clsid.copy_from_slice(&raw[80..96]);
+                let size = u64::from_le_bytes(raw[120..128].try_into().unwrap());
+                DirEntry {
+                    name: String::from_utf16_lossy(&units),
+                    kind: raw[66],
+                    left: u32_at(raw, 68),
+                    right: u32_at(raw, 72),
+                    child: u32_at(raw, 76),
+                    clsid,
+                    start: u32_at(raw, 116),
+                    // Version 3 files may leave garbage in the high half
+                    size: if major_version == 3 {
+                        size & 0xFFFF_FFFF
+                    } else {
+                        size
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Entries below the storage `entries[parent]` with their paths, parents first.
+    /// Pointers to entries that don't exist are skipped with everything below them.
+    fn walk(
+        &mut self,
+        entries: &[DirEntry],
+        parent: usize,
+        parent_path: &Path,
+        seen: &mut HashSet<u32>,
+        out: &mut Vec<(PathBuf, usize)>,
+    ) -> Result<(), String> {
+        if parent_path.components().count() > MAX_STORAGE_DEPTH {
+            return Err(Limit::Depth.exceeded(format_args!(
+                "storages are nested more than {} levels deep",
+                MAX_STORAGE_DEPTH
+            )));
+        }
+        let mut pending = vec![entries[parent].child];
+        while let Some(id) = pending.pop() {
+            if id == NO_STREAM {
+                continue;
+            }
+            let valid = entries
+                .get(id as usize)
+                .is_some_and(|entry| matches!(entry.kind, OBJ_STORAGE | OBJ_STREAM));
+            if !valid {
+                self.warnings.push(Localized::new(
+                    "warning.recovery.missing_entry",
+                    [("path", parent_path.display().to_string())],
+                ));
+                continue;
+            }
+            if !seen.insert(id) {
+                self.warnings.push(Localized::new(
+                    "warning.recovery.loop",
+                    [("path", parent_path.display().to_string())],
+                ));
+                continue;
+            }
+            let entry = &entries[id as usize];
+            pending.push(entry.left);
+            pending.push(entry.right);
+            if entry.name.is_empty() || entry.name.contains(['/', '\\']) {
+                self.warnings.push(Localized::new(
+                    "warning.recovery.invalid_name",
+                    [("path", parent_path.display().to_string())],
+                ));
+                continue;
+            }
+            let path = parent_path.join(&entry.name);
+            out.push((path.clone(), id as usize));
+            if entry.kind == OBJ_STORAGE {
+                self.walk(entries, id as usize, &path, seen, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read what is readable from a damaged compound file into a new, valid one. Recovered
+/// streams count against `budget`: cross-linked chains can make many streams share the
+/// same sectors.
+pub fn rebuild(data: &[u8], budget: &Budget) -> Result<Recovered, String> {
+    if data.len() < HEADER_LEN || data[..8] != MAGIC {
+        return Err("Not a compound file".to_string());
+    }
+    let header = &data[..HEADER_LEN];
+    let major_version = u16_at(header, 0x1A);
+    let sector_len = match u16_at(header, 0x1E) {
+        9 => 512,
+        12 => 4096,
+        shift => return Err(format!("Unsupported sector size 2^{}", shift)),
+    };
+    let mut reader = Reader {
+        data,
+        sector_len,
+        mini_cutoff: match u32_at(header, 0x38) {
+            0 => 4096,
+            cutoff => cutoff as u64,
+        },
+        fat: Vec::new(),
+        mini_fat: Vec::new(),
+        mini_stream: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let cut = data.len().saturating_sub(sector_len) % sector_len;
+    if data.len() < sector_len || cut != 0 {
+        reader
+            .warnings
+            .push(Localized::new("warning.recovery.partial_sector", []));
+    }
+
+    for id in reader.fat_sectors(header) {
+        match reader.whole_sector(id) {
+            Some(sector) => reader.fat.extend(u32s(sector)),
+            None => {
+                reader.warnings.push(Localized::new(
+                    "warning.recovery.fat_sector_missing",
+                    [("sector", id.to_string())],
+                ));
+                // Everything it describes counts as free, keeping later entries in place
+                reader
+                    .fat
+                    .extend(std::iter::repeat_n(NO_STREAM, sector_len / 4));
+            }
+        }
+    }
+    reader.mini_fat = u32s(&reader.read_chain(u32_at(header, 0x3C))).collect();
+
+    let entries = reader.directory(u32_at(header, 0x30), major_version);
+    let Some(root) = entries.first().filter(|root| root.kind == OBJ_ROOT) else {
+        return Err("The root directory entry is missing".to_string());
+    };
+    let mut mini_stream = reader.read_chain(root.start);
+    if (mini_stream.len() as u64) < root.size {
+        reader
+            .warnings
+            .push(Localized::new("warning.recovery.mini_stream_truncated", []));
+    }
+    mini_stream.truncate(root.size.min(usize::MAX as u64) as usize);
+    reader.mini_stream = mini_stream;
+
+    let mut found = Vec::new();
+    let mut seen = HashSet::from([0]);
+    reader.walk(&entries, 0, Path::new("/"), &mut seen, &mut found)?;
+    if found.is_empty() {
+        return Err("No readable entries left in the compound file".to_string());
+    }
+
+    let fail = |e: std::io::Error| format!("Failed to rebuild compound file: {}", e);
+    let mut out = cfb::CompoundFile::create(Cursor::new(Vec::new())).map_err(fail)?;
+    out.set_storage_clsid("/", uuid_from(&root.clsid))
+        .map_err(fail)?;
+    for (path, id) in found {
+        let entry = &entries[id];
+        let result = if entry.kind == OBJ_STORAGE {
+            out.create_storage(&path)
+                .and_then(|()| out.set_storage_clsid(&path, uuid_from(&entry.clsid)))
+        } else {
+            // A stream is at most as long as the whole file
+            budget.reserve(&path.to_string_lossy(), entry.size.min(data.len() as u64))?;
+            budget.check_time()?;
+            let data = reader.read_stream(entry);
+            if (data.len() as u64) < entry.size {
+                reader.warnings.push(Localized::new(
+                    "warning.recovery.stream_truncated",
+                    [
+                        ("path", path.display().to_string()),
+                        ("recovered", data.len().to_string()),
+                        ("size", entry.size.to_string()),
+                    ],
+                ));
+            }
+            out.create_stream(&path)
+                .and_then(|mut stream| stream.write_all(&data))
+        };
+        if let Err(e) = result {
+            reader.warnings.push(Localized::new(
+                "warning.recovery.stream_skipped",
+                [
+                    ("path", path.display().to_string()),
+                    ("reason", e.to_string()),
+                ],
+            ));
+        }
+    }
+    out.flush().map_err(fail)?;
+
+    Ok(Recovered {
+        data: out.into_inner().into_inner(),
+        warnings: reader.warnings,
+    })
+}
+
+/// CLSIDs are stored as little-endian GUIDs
+fn uuid_from(clsid: &[u8; 16]) -> uuid::Uuid {
+    uuid::Uuid::from_bytes_le(*clsid)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::{msg, ParseOptions};
+
+    const ATTACHMENT: &str = "/__attach_version1.0_#00000000";
+
+    pub(crate) fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// A message whose small property streams come first and a large attachment last
+    pub(crate) fn sample_msg() -> Vec<u8> {
+        let mut file =
+            cfb::CompoundFile::create_with_version(cfb::Version::V3, Cursor::new(Vec::new()))
+                .unwrap();
+        let stream = |file: &mut cfb::CompoundFile<_>, path: &str, data: &[u8]| {
+            file.create_stream(path).unwrap().write_all(data).unwrap();
+        };
+        stream(&mut file, "/__properties_version1.0", &[0; 32]);
+        stream(
+            &mut file,
+            "/__substg1.0_0037001F",
+            &utf16("Quarterly report"),
+        );
+        stream(&mut file, "/__substg1.0_0C1A001F", &utf16("Ann Example"));
+        stream(
+            &mut file,
+            "/__substg1.0_5D01001F",
+            &utf16("ann@example.com"),
+        );
+        file.create_storage(ATTACHMENT).unwrap();
+        let mut props = vec![0; 8];
+        props.extend([0x03, 0x00, 0x05, 0x37, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        stream(
+            &mut file,
+            &format!("{}/__properties_version1.0", ATTACHMENT),
+            &props,
+        );
+        stream(
+            &mut file,
+            &format!("{}/__substg1.0_3707001F", ATTACHMENT),
+            &utf16("figures.pdf"),
+        );
+        let content: Vec<u8> = (0..40_000).map(|i| (i % 251) as u8).collect();
+        stream(
+            &mut file,
+            &format!("{}/__substg1.0_37010102", ATTACHMENT),
+            &content,
+        );
+        file.flush().unwrap();
+        file.into_inner().into_inner()
+    }
+
+    fn assert_header_fields(data: &[u8]) -> crate::parser::Message {
+        let message = msg::parse_bytes(data, &ParseOptions::default()).unwrap();
+        assert!(message.partial);
+        assert!(!message.warnings.is_empty());
+        assert_eq!(message.subject.as_deref(), Some("Quarterly report"));
+        let from = message.from.clone().unwrap();
+        assert_eq!(from.name.as_deref(), Some("Ann Example"));
+        assert_eq!(from.email.as_deref(), Some("ann@example.com"));
+        message
+    }
+
+    #[test]
+    fn truncated_files_keep_surviving_streams() {
+        let full = sample_msg();
+        let intact = msg::parse_bytes(&full, &ParseOptions::default()).unwrap();
+        assert!(!intact.partial);
+        assert_eq!(intact.attachments[0].size, 40_000);
+        // Cutting off only the padding of the last sector loses nothing
+        let padded = msg::parse_bytes(&full[..full.len() - 100], &ParseOptions::default());
+        assert!(!padded.unwrap().partial);
+
+        for cut in [449, 1000, 10_000, 30_000, 39_000] {
+            let message = assert_header_fields(&full[..full.len() - cut]);
+            if let Some(attachment) = message.attachments.first() {
+                assert!(attachment.size < 40_000, "cut {}", cut);
+            }
+            assert!(
+                message
+                    .warnings
+                    .iter()
+                    .any(|w| w.to_string().contains("truncated")),
+                "cut {}: {:?}",
+                cut,
+                message.warnings
+            );
+        }
+
+        assert!(msg::parse_bytes(&full[..400], &ParseOptions::default()).is_err());
+        assert!(rebuild(b"not a compound file", &Budget::default()).is_err());
+    }
+
+    #[test]
+    fn invalid_directory_pointers_skip_the_subtree() {
+        let mut data = sample_msg();
+        let name = utf16(ATTACHMENT.trim_start_matches('/'));
+        let entry = data
+            .windows(name.len())
+            .position(|window| window == name.as_slice())
+            .unwrap();
+        data[entry + 76..entry + 80].copy_from_slice(&0x00FF_FFF0u32.to_le_bytes());
+
+        let message = assert_header_fields(&data);
+        assert!(message.attachments.is_empty());
+        assert!(message
+            .warnings
+            .iter()
+            .any(|w| w.code == "warning.recovery.missing_entry"));
+    }
+}
@@ -0,0 +1,1025 @@
+//! Redacted copies of message files, for attaching a problem message to a bug report
+//! without its content. Text keeps its length, line structure and encoding: letters become
+//! `x`/`X`, digits `0`, other characters the same number of bytes of a placeholder letter.
+//! Addresses become stand-ins derived from a per-copy secret, the same address always
+//! getting the same stand-in, and attachment payloads become random bytes of the same
+//! size. Everything the parser goes by is kept: header names, MIME boundaries and
+//! parameters, transfer encodings, HTML markup, property streams and their layout, the
+//! property tables with the codepages and the named property mapping. S/MIME blobs keep
+//! their DER structure, with only their printable strings redacted.
+
+use super::headers::{self, Headers};
+use super::msg::{self, PROP_TYPE_BINARY, PROP_TYPE_STRING8, PROP_TYPE_UNICODE};
+use super::named::NAMEID_STORAGE;
+use super::storage::CompoundStorage;
+use super::{mime, ole, parse_bytes, rtf, MessageFormat, ParseOptions};
+use crate::i18n::Localized;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::Path;
+
+/// Flag of multi-valued property types; their values are streams with a `-NNNNNNNN` suffix
+const MV_FLAG: u16 = 0x1000;
+
+const PID_TAG_SENDER_ADDRESS_TYPE: u16 = 0x0C1E;
+const PID_TAG_SENT_REPRESENTING_ADDRESS_TYPE: u16 = 0x0064;
+const PID_TAG_ADDRESS_TYPE: u16 = 0x3002;
+const PID_TAG_ATTACH_EXTENSION: u16 = 0x3703;
+
+/// String properties that hold structure rather than content
+const KEPT_PROPERTIES: &[u16] = &[
+    msg::PID_TAG_MESSAGE_CLASS,
+    msg::PID_TAG_ATTACH_MIME_TAG,
+    PID_TAG_SENDER_ADDRESS_TYPE,
+    PID_TAG_SENT_REPRESENTING_ADDRESS_TYPE,
+    PID_TAG_ADDRESS_TYPE,
+    PID_TAG_ATTACH_EXTENSION,
+];
+
+/// Header fields kept as they are: they describe the structure, or (`Date`) are needed to
+/// reproduce how a message is sorted and shown
+const KEPT_FIELDS: &[&str] = &["mime-version", "content-transfer-encoding", "date"];
+
+/// HTML attributes whose values are presentation, not content
+const KEPT_ATTRIBUTES: &[&str] = &[
+    "align",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+    "charset",
+    "class",
+    "color",
+    "content",
+    "dir",
+    "face",
+    "height",
+    "http-equiv",
+    "lang",
+    "name",
+    "rel",
+    "size",
+    "style",
+    "type",
+    "valign",
+    "width",
+];
+
+/// Printable runs at least this long in binary properties are treated as text
+const MIN_BINARY_RUN: usize = 4;
+
+/// Whether redacting changed how the parser sees the message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Verification {
+    /// The copy parses with the same warnings, or fails with the same error
+    pub reproduces: bool,
+    /// Warning codes of the original, or `error:` and the code it failed with
+    pub original: Vec<String>,
+    pub redacted: Vec<String>,
+}
+
+/// A redacted copy of the message file `data`
+pub fn redact(format: MessageFormat, data: &[u8]) -> Result<Vec<u8>, String> {
+    let redactor = Redactor::new()?;
+    match format {
+        MessageFormat::Eml => Ok(redactor.entity(data, 0)),
+        // The message redacted in its frame, with the byte count it has now
+        MessageFormat::Emlx => Ok(match super::emlx::split(data) {
+            Some((message, plist)) => {
+                let message = redactor.entity(message, 0);
+                [format!("{}\n", message.len()).as_bytes(), &message, plist].concat()
+            }
+            None => redactor.entity(data, 0),
+        }),
+        MessageFormat::Msg => redactor.compound_file(data),
+    }
+}
+
+/// Parse `original` and its redacted copy and compare what the parser reported
+pub fn verify(format: MessageFormat, original: &[u8], redacted: &[u8]) -> Verification {
+    let outcome = |data: &[u8]| match parse_bytes(format, data, &ParseOptions::default()) {
+        Ok(message) => message
+            .warnings
+            .iter()
+            .map(|warning| warning.code.to_string())
+            .collect::<Vec<_>>(),
+        Err(e) => vec![format!("error: {}", Localized::from(e).code)],
+    };
+    let original = outcome(original);
+    let redacted = outcome(redacted);
+    Verification {
+        reproduces: original == redacted,
+        original,
+        redacted,
+    }
+}
+
+/// Where an address was and what replaces it
+type StandIn = (Range<usize>, Vec<u8>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Text,
+    Html,
+    /// Keeps the extension, which decides how an attachment is treated
+    FileName,
+}
+
+struct Redactor {
+    random: SystemRandom,
+    /// Mixed into the address stand-ins, so they can't be matched against guessed addresses
+    secret: [u8; 16],
+}
+
+impl Redactor {
+    fn new() -> Result<Self, String> {
+        let random = SystemRandom::new();
+        let mut secret = [0; 16];
+        random
+            .fill(&mut secret)
+            .map_err(|_| "No random numbers available".to_string())?;
+        Ok(Self { random, secret })
+    }
+
+    /// Random bytes of the same length, for attachment payloads
+    fn payload(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0; data.len()];
+        // The system generator doesn't fail once it produced the secret
+        let _ = self.random.fill(&mut out);
+        out
+    }
+
+    /// Same-length stand-in for an address: `@` and dots stay, the rest are letters
+    fn stand_in(&self, address: &[u32]) -> Vec<u8> {
+        let text: String = address.iter().filter_map(|&u| char::from_u32(u)).collect();
+        let mut hasher = Sha256::new();
+        hasher.update(self.secret);
+        hasher.update(text.to_lowercase().as_bytes());
+        let digest = hasher.finalize();
+        address
+            .iter()
+            .enumerate()
+            .map(|(i, &u)| match u {
+                0x40 | 0x2E => u as u8,
+                _ => b'a' + digest[i % digest.len()] % 26,
+            })
+            .collect()
+    }
+
+    /// Positions to keep as they are, with the stand-ins for the addresses among them
+    fn plan(&self, units: &[u32], mode: Mode) -> (Vec<bool>, Vec<StandIn>) {
+        let mut keep = match mode {
+            Mode::Html => markup(units),
+            Mode::Text => vec![false; units.len()],
+            Mode::FileName => {
+                let mut keep = vec![false; units.len()];
+                if let Some(dot) = units.iter().rposition(|&u| u == 0x2E).filter(|&i| i > 0) {
+                    keep[dot..].fill(true);
+                }
+                keep
+            }
+        };
+        let addresses: Vec<_> = address_spans(units)
+            .into_iter()
+            .filter(|span| !keep[span.clone()].iter().all(|&kept| kept))
+            .map(|span| {
+                let stand_in = self.stand_in(&units[span.clone()]);
+                (span, stand_in)
+            })
+            .collect();
+        for (span, _) in &addresses {
+            keep[span.clone()].fill(true);
+        }
+        (keep, addresses)
+    }
+
+    /// Text in an 8-bit charset: UTF-8 character by character when it is valid UTF-8, else
+    /// byte by byte with `é` in Latin-1 (also a valid lead or trail byte in the common
+    /// double-byte charsets)
+    fn bytes(&self, data: &[u8], mode: Mode) -> Vec<u8> {
+        let units: Vec<u32> = data.iter().map(|&b| b as u32).collect();
+        let (keep, addresses) = self.plan(&units, mode);
+        let mut out = data.to_vec();
+        match std::str::from_utf8(data) {
+            Ok(text) => {
+                for (i, c) in text.char_indices() {
+                    if !keep[i] {
+                        let mut buffer = [0; 4];
+                        let encoded = placeholder(c).encode_utf8(&mut buffer);
+                        out[i..i + c.len_utf8()].copy_from_slice(encoded.as_bytes());
+                    }
+                }
+            }
+            Err(_) => {
+                for (i, byte) in out.iter_mut().enumerate() {
+                    if !keep[i] {
+                        *byte = match *byte {
+                            0x80.. => 0xE9,
+                            ascii => placeholder(ascii as char) as u8,
+                        };
+                    }
+                }
+            }
+        }
+        for (span, stand_in) in addresses {
+            out[span].copy_from_slice(&stand_in);
+        }
+        out
+    }
+
+    /// UTF-16 text, unit for unit
+    fn wide(&self, data: &[u16], mode: Mode) -> Vec<u16> {
+        let units: Vec<u32> = data.iter().map(|&u| u as u32).collect();
+        let (keep, addresses) = self.plan(&units, mode);
+        let mut out = data.to_vec();
+        let mut i = 0;
+        while i < out.len() {
+            let len = match char::decode_utf16(data[i..].iter().copied()).next() {
+                Some(Ok(c)) => {
+                    if !keep[i] {
+                        placeholder(c).encode_utf16(&mut out[i..i + c.len_utf16()]);
+                    }
+                    c.len_utf16()
+                }
+                // Unpaired surrogates stay, they may be what the parser trips over
+                _ => 1,
+            };
+            i += len;
+        }
+        for (span, stand_in) in addresses {
+            for (unit, byte) in out[span].iter_mut().zip(stand_in) {
+                *unit = byte as u16;
+            }
+        }
+        out
+    }
+
+    /// Printable runs in binary data, as ASCII or UTF-16LE, to catch names and addresses in
+    /// entry ids and OLE property sets without moving a byte of their structure
+    fn binary(&self, data: &[u8]) -> Vec<u8> {
+        let printable = |b: u8| (0x20..0x7F).contains(&b);
+        let mut out = data.to_vec();
+        let mut i = 0;
+        while i < data.len() {
+            let wide_end = (i..)
+                .step_by(2)
+                .find(|&j| j + 1 >= data.len() || !printable(data[j]) || data[j + 1] != 0)
+                .unwrap_or(data.len());
+            if (wide_end - i) / 2 >= MIN_BINARY_RUN {
+                let units: Vec<u16> = data[i..wide_end]
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                for (j, unit) in self.wide(&units, Mode::Text).into_iter().enumerate() {
+                    out[i + 2 * j..i + 2 * j + 2].copy_from_slice(&unit.to_le_bytes());
+                }
+                i = wide_end;
+                continue;
+            }
+            let end = (i..data.len())
+                .find(|&j| !printable(data[j]))
+                .unwrap_or(data.len());
+            if end - i >= MIN_BINARY_RUN {
+                out[i..end].copy_from_slice(&self.bytes(&data[i..end], Mode::Text));
+            }
+            i = end.max(i + 1);
+        }
+        out
+    }
+
+    /// A MIME entity: redacted header fields, then the body by its type and encoding
+    fn entity(&self, data: &[u8], depth: usize) -> Vec<u8> {
+        let (block, body, _) = headers::split_header_block_checked(data);
+        let separator = &data[block.len()..data.len() - body.len()];
+        let fields = headers::parse(block);
+        let content_type = fields
+            .get("Content-Type")
+            .map(mime::parse_parameters)
+            .filter(|ct| ct.mime.contains('/'));
+        let encoding = fields
+            .get("Content-Transfer-Encoding")
+            .unwrap_or("7bit")
+            .trim()
+            .to_ascii_lowercase();
+
+        let mut out = self.header_block(block);
+        out.extend_from_slice(separator);
+        let boundary = content_type
+            .as_ref()
+            .filter(|ct| ct.is_multipart() && depth < mime::MAX_DEPTH)
+            .and_then(|ct| ct.param("boundary"));
+        out.extend(match boundary {
+            Some(boundary) => self.multipart(body, boundary, depth),
+            None => {
+                let mime = content_type.as_ref().map_or("text/plain", |ct| &ct.mime);
+                self.leaf(body, mime, &encoding, &fields, depth)
+            }
+        });
+        out
+    }
+
+    /// Delimiter lines stay; preamble and epilogue are text, the parts entities
+    fn multipart(&self, body: &[u8], boundary: &str, depth: usize) -> Vec<u8> {
+        let delimiter = format!("--{}", boundary);
+        let mut out = Vec::with_capacity(body.len());
+        let mut segment_start = 0;
+        let mut in_part = false;
+        let mut closed = false;
+        let mut pos = 0;
+        while pos < body.len() && !closed {
+            let line_end = body[pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(body.len(), |i| pos + i + 1);
+            let line = &body[pos..line_end];
+            if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+                closed = rest.starts_with(b"--");
+                if closed || rest.iter().all(|b| b.is_ascii_whitespace()) {
+                    out.extend(self.segment(&body[segment_start..pos], in_part, depth));
+                    out.extend_from_slice(line);
+                    segment_start = line_end;
+                    in_part = true;
+                }
+            }
+            pos = line_end;
+        }
+        let rest = &body[segment_start..];
+        out.extend(match closed {
+            true => self.bytes(rest, Mode::Text),
+            false => self.segment(rest, in_part, depth),
+        });
+        out
+    }
+
+    fn segment(&self, segment: &[u8], is_part: bool, depth: usize) -> Vec<u8> {
+        if !is_part {
+            return self.bytes(segment, Mode::Text);
+        }
+        // The line break before a delimiter belongs to the delimiter
+        let content = segment.strip_suffix(b"\n").unwrap_or(segment);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let mut out = self.entity(content, depth + 1);
+        out.extend_from_slice(&segment[content.len()..]);
+        out
+    }
+
+    fn leaf(
+        &self,
+        body: &[u8],
+        mime: &str,
+        encoding: &str,
+        fields: &Headers,
+        depth: usize,
+    ) -> Vec<u8> {
+        let mode = match mime {
+            "text/html" => Mode::Html,
+            _ => Mode::Text,
+        };
+        let is_message = matches!(mime, "message/rfc822" | "message/global");
+        let is_text = mime.starts_with("text/") || fields.get("Content-Type").is_none();
+        // Signatures and encrypted content are structures the parser reads
+        let file_name = ["Content-Disposition", "Content-Type"]
+            .into_iter()
+            .filter_map(|field| fields.get(field).map(mime::parse_parameters))
+            .find_map(|ct| {
+                ct.param("filename")
+                    .or(ct.param("name"))
+                    .map(str::to_lowercase)
+            })
+            .unwrap_or_default();
+        let is_smime =
+            mime.contains("pkcs7") || file_name.ends_with(".p7m") || file_name.ends_with(".p7s");
+        match encoding {
+            "base64" => {
+                let decoded = mime::decode_transfer(body, "base64");
+                if decoded.is_empty() {
+                    return self.bytes(body, Mode::Text);
+                }
+                let redacted = match (is_message, is_text) {
+                    (true, _) => self.entity(&decoded, depth + 1),
+                    (false, true) => self.bytes(&decoded, mode),
+                    // DER, or (from some gateways) the MIME entity it would wrap
+                    (false, false) if is_smime => match decoded.first() {
+                        Some(0x30) => self.binary(&decoded),
+                        _ => self.entity(&decoded, depth + 1),
+                    },
+                    (false, false) => self.payload(&decoded),
+                };
+                base64_like(body, &redacted)
+            }
+            "quoted-printable" => self.escaped(body, false, mode),
+            _ if is_message => self.entity(body, depth + 1),
+            _ => self.bytes(body, mode),
+        }
+    }
+
+    /// Header fields with their continuation lines; names and folding stay
+    fn header_block(&self, block: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(block.len());
+        let mut start = 0;
+        while start < block.len() {
+            let mut end = next_line(block, start);
+            while end < block.len() && matches!(block[end], b' ' | b'\t') {
+                end = next_line(block, end);
+            }
+            let field = &block[start..end];
+            match field.iter().position(|&b| b == b':') {
+                Some(colon) if !field[..colon].contains(&b'\n') => {
+                    let name = String::from_utf8_lossy(&field[..colon]);
+                    out.extend_from_slice(&field[..=colon]);
+                    out.extend(self.field_value(name.trim(), &field[colon + 1..]));
+                }
+                _ => out.extend(self.bytes(field, Mode::Text)),
+            }
+            start = end;
+        }
+        out
+    }
+
+    fn field_value(&self, name: &str, value: &[u8]) -> Vec<u8> {
+        let name = name.to_ascii_lowercase();
+        if KEPT_FIELDS.contains(&name.as_str()) {
+            return value.to_vec();
+        }
+        if name == "content-type" || name == "content-disposition" {
+            return self.parameters(value);
+        }
+        self.with_encoded_words(value, Mode::Text)
+    }
+
+    /// `type/subtype; key=value`: only file names are content
+    fn parameters(&self, value: &[u8]) -> Vec<u8> {
+        let mut out = value.to_vec();
+        let mut quoted = false;
+        let mut piece_start = 0;
+        for i in 0..=value.len() {
+            match value.get(i) {
+                Some(b'"') => quoted = !quoted,
+                Some(b';') | None if !quoted => {
+                    let piece = &value[piece_start..i];
+                    if let Some(eq) = piece.iter().position(|&b| b == b'=') {
+                        let key = String::from_utf8_lossy(&piece[..eq]).trim().to_lowercase();
+                        let key = key.split('*').next().unwrap_or_default();
+                        if key == "name" || key == "filename" {
+                            let start = piece_start + eq + 1;
+                            let redacted = self.parameter_value(&value[start..i]);
+                            out[start..i].copy_from_slice(&redacted);
+                        }
+                    }
+                    piece_start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// A file name parameter: quoted, encoded words, or RFC 2231 `charset'lang'%XX`
+    fn parameter_value(&self, value: &[u8]) -> Vec<u8> {
+        if let Some(quote) = value.iter().position(|&b| b == b'"') {
+            let end = value.iter().rposition(|&b| b == b'"').unwrap_or(quote);
+            if end > quote {
+                let mut out = value.to_vec();
+                let inner = self.with_encoded_words(&value[quote + 1..end], Mode::FileName);
+                out[quote + 1..end].copy_from_slice(&inner);
+                return out;
+            }
+        }
+        let mut out = value.to_vec();
+        let Some(second) = value
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'\'')
+            .nth(1)
+            .map(|(i, _)| i)
+        else {
+            return self.with_encoded_words(value, Mode::FileName);
+        };
+        // Leave the percent escapes to keep the charset's byte structure
+        let text = &value[second + 1..];
+        let mut redacted = self.bytes(text, Mode::FileName);
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] == b'%' && text.get(i + 1..i + 3).and_then(headers::hex_byte).is_some() {
+                redacted[i..i + 3].copy_from_slice(&text[i..i + 3]);
+                i += 3;
+            } else {
+                i += 1;
+            }
+        }
+        out[second + 1..].copy_from_slice(&redacted);
+        out
+    }
+
+    /// Text that may hold `=?charset?B|Q?...?=` words, whose content is redacted decoded
+    fn with_encoded_words(&self, value: &[u8], mode: Mode) -> Vec<u8> {
+        let mut out = self.bytes(value, mode);
+        let mut pos = 0;
+        while let Some(word) = encoded_word(value, pos) {
+            out[word.prefix.clone()].copy_from_slice(&value[word.prefix.clone()]);
+            out[word.payload.end..word.payload.end + 2]
+                .copy_from_slice(&value[word.payload.end..word.payload.end + 2]);
+            let payload = &value[word.payload.clone()];
+            let redacted = match word.base64 {
+                true => match STANDARD.decode(payload) {
+                    Ok(decoded) => STANDARD.encode(self.bytes(&decoded, mode)).into_bytes(),
+                    Err(_) => self.bytes(payload, Mode::Text),
+                },
+                false => self.escaped(payload, true, mode),
+            };
+            if redacted.len() == payload.len() {
+                out[word.payload.clone()].copy_from_slice(&redacted);
+            }
+            pos = word.payload.end + 2;
+        }
+        out
+    }
+
+    /// Quoted-printable (or the `Q` encoding of headers, with `q_encoding`), redacted
+    /// decoded and written back with the same escapes and soft line breaks
+    fn escaped(&self, data: &[u8], q_encoding: bool, mode: Mode) -> Vec<u8> {
+        enum Token {
+            Verbatim(Range<usize>),
+            Byte { value: u8, escaped: bool },
+        }
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            if data[i] == b'=' {
+                if let Some(byte) = data.get(i + 1..i + 3).and_then(headers::hex_byte) {
+                    tokens.push(Token::Byte {
+                        value: byte,
+                        escaped: true,
+                    });
+                    i += 3;
+                    continue;
+                }
+                if !q_encoding {
+                    let mut j = i + 1;
+                    while j < data.len() && matches!(data[j], b' ' | b'\t') {
+                        j += 1;
+                    }
+                    if data.get(j) == Some(&b'\r') {
+                        j += 1;
+                    }
+                    if data.get(j) == Some(&b'\n') {
+                        tokens.push(Token::Verbatim(i..j + 1));
+                        i = j + 1;
+                        continue;
+                    }
+                }
+            }
+            tokens.push(match (q_encoding, data[i]) {
+                // `_` is a space, and stays one
+                (true, b'_') => Token::Verbatim(i..i + 1),
+                (_, value) => Token::Byte {
+                    value,
+                    escaped: false,
+                },
+            });
+            i += 1;
+        }
+
+        let decoded: Vec<u8> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Byte { value, .. } => Some(*value),
+                Token::Verbatim(_) => None,
+            })
+            .collect();
+        let mut redacted = self.bytes(&decoded, mode).into_iter();
+        let mut out = Vec::with_capacity(data.len());
+        for token in tokens {
+            match token {
+                Token::Verbatim(range) => out.extend_from_slice(&data[range]),
+                Token::Byte { escaped, .. } => {
+                    let byte = redacted.next().unwrap_or_default();
+                    match escaped {
+                        true => out.extend_from_slice(format!("={:02X}", byte).as_bytes()),
+                        false => out.push(byte),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// A copy of the compound file with every stream redacted by what its property holds
+    fn compound_file(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut storage = CompoundStorage::open(Cursor::new(data))?;
+        storage.export_storages(&[("/", "/")], |path, data| self.stream(path, data))
+    }
+
+    fn stream(&self, path: &Path, data: Vec<u8>) -> Vec<u8> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if name == "__properties_version1.0"
+            || path
+                .components()
+                .any(|component| component.as_os_str() == NAMEID_STORAGE)
+        {
+            return data;
+        }
+        let Some(tag) = name.strip_prefix("__substg1.0_") else {
+            return self.object_stream(&name, &data);
+        };
+        let (tag, is_value) = match tag.split_once('-') {
+            Some((tag, _)) => (tag, true),
+            None => (tag, false),
+        };
+        let (Some(id), Some(kind)) = (
+            tag.get(..4).and_then(|id| u16::from_str_radix(id, 16).ok()),
+            tag.get(4..8)
+                .and_then(|kind| u16::from_str_radix(kind, 16).ok()),
+        ) else {
+            return self.binary(&data);
+        };
+        // A multi-valued property's own stream holds the value lengths
+        if kind & MV_FLAG != 0 && !is_value {
+            return data;
+        }
+        let mode = match id {
+            msg::PID_TAG_ATTACH_FILENAME | msg::PID_TAG_ATTACH_LONG_FILENAME => Mode::FileName,
+            _ => Mode::Text,
+        };
+        match kind & !MV_FLAG {
+            _ if KEPT_PROPERTIES.contains(&id) => data,
+            PROP_TYPE_UNICODE => {
+                let units: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                let redacted = match id {
+                    msg::PID_TAG_TRANSPORT_MESSAGE_HEADERS => {
+                        let text = String::from_utf16_lossy(&units);
+                        let block = self.header_block(text.as_bytes());
+                        String::from_utf8_lossy(&block).encode_utf16().collect()
+                    }
+                    _ => self.wide(&units, mode),
+                };
+                let mut out: Vec<u8> = redacted.iter().flat_map(|u| u.to_le_bytes()).collect();
+                // An odd trailing byte is kept, as the parser sees it
+                out.extend_from_slice(&data[units.len() * 2..]);
+                out
+            }
+            PROP_TYPE_STRING8 => match id {
+                msg::PID_TAG_TRANSPORT_MESSAGE_HEADERS => self.header_block(&data),
+                _ => self.bytes(&data, mode),
+            },
+            PROP_TYPE_BINARY => match id {
+                msg::PID_TAG_ATTACH_DATA_BINARY => self.payload(&data),
+                // An empty document of the same length, rather than rendering the HTML in it again
+                msg::PID_TAG_RTF_COMPRESSED => rtf::blank(data.len()),
+                msg::PID_TAG_HTML => self.bytes(&data, Mode::Html),
+                _ => self.binary(&data),
+            },
+            _ => data,
+        }
+    }
+
+    /// Streams of OLE attachments: `\u{1}`-prefixed ones describe the object (property sets
+    /// with titles and authors), the rest are the object's data
+    fn object_stream(&self, name: &str, data: &[u8]) -> Vec<u8> {
+        let mut out = self.binary(data);
+        if name == ole::OLE10_NATIVE {
+            if let Some(payload) = ole::native_payload(data) {
+                out[payload.clone()].copy_from_slice(&self.payload(&data[payload]));
+            }
+            return out;
+        }
+        match name.starts_with(|c: char| c.is_control()) {
+            true => out,
+            false => self.payload(data),
+        }
+    }
+}
+
+/// Letters become `x`/`X`, digits `0`, other letters a placeholder of the same encoded
+/// length; whitespace and punctuation stay
+fn placeholder(c: char) -> char {
+    match c {
+        'a'..='z' => 'x',
+        'A'..='Z' => 'X',
+        '0'..='9' => '0',
+        c if !c.is_alphanumeric() => c,
+        c => match c.len_utf8() {
+            2 => 'é',
+            3 => 'あ',
+            _ => '𝑥',
+        },
+    }
+}
+
+/// `local@domain.tld` runs, found by their `@`
+fn address_spans(units: &[u32]) -> Vec<Range<usize>> {
+    let ascii = |u: u32| char::from_u32(u).filter(char::is_ascii);
+    let is_local =
+        |u: u32| ascii(u).is_some_and(|c| c.is_ascii_alphanumeric() || "._%+-".contains(c));
+    let is_domain =
+        |u: u32| ascii(u).is_some_and(|c| c.is_ascii_alphanumeric() || ".-".contains(c));
+    let mut spans = Vec::new();
+    let mut floor = 0;
+    for at in 0..units.len() {
+        if units[at] != 0x40 || at < floor {
+            continue;
+        }
+        let mut start = at;
+        while start > floor && is_local(units[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at + 1;
+        while end < units.len() && is_domain(units[end]) {
+            end += 1;
+        }
+        // A full stop after an address ends the sentence
+        while end > at + 1 && units[end - 1] == 0x2E {
+            end -= 1;
+        }
+        if start < at && units[at + 1..end].contains(&0x2E) {
+            spans.push(start..end);
+            floor = end;
+        }
+    }
+    spans
+}
+
+/// Positions of HTML markup: tags with their attribute names, comments, entities and the
+/// content of `style` and `script` elements. Attribute values are content unless they are
+/// presentation (`KEPT_ATTRIBUTES`); URL schemes stay.
+fn markup(units: &[u32]) -> Vec<bool> {
+    let at = |i: usize| {
+        units
+            .get(i)
+            .and_then(|&u| char::from_u32(u))
+            .unwrap_or('\0')
+    };
+    let starts_with = |i: usize, text: &str| {
+        text.chars()
+            .enumerate()
+            .all(|(j, c)| at(i + j).eq_ignore_ascii_case(&c))
+    };
+    let mut keep = vec![false; units.len()];
+    let mut i = 0;
+    while i < units.len() {
+        if starts_with(i, "<!--") {
+            let end = (i + 4..units.len())
+                .find(|&j| starts_with(j, "-->"))
+                .map_or(units.len(), |j| j + 3);
+            keep[i..end].fill(true);
+            i = end;
+        } else if at(i) == '<' && (at(i + 1).is_ascii_alphabetic() || "/!?".contains(at(i + 1))) {
+            let (end, element) = tag(units, i, &mut keep);
+            i = end;
+            if element == "style" || element == "script" {
+                let close = format!("</{}", element);
+                let end = (i..units.len())
+                    .find(|&j| starts_with(j, &close))
+                    .unwrap_or(units.len());
+                keep[i..end].fill(true);
+                i = end;
+            }
+        } else if at(i) == '&' {
+            let end = (i + 1..units.len().min(i + 34))
+                .find(|&j| !(at(j).is_ascii_alphanumeric() || at(j) == '#'))
+                .filter(|&j| j > i + 1 && at(j) == ';');
+            match end {
+                Some(end) => {
+                    keep[i..=end].fill(true);
+                    i = end + 1;
+                }
+                None => i += 1,
+            }
+        } else {
+            i += 1;
+        }
+    }
+    keep
+}
+
+/// Mark the tag starting at `start`; returns where it ends and the (opening) element name
+fn tag(units: &[u32], start: usize, keep: &mut [bool]) -> (usize, String) {
+    let at = |i: usize| {
+        units
+            .get(i)
+            .and_then(|&u| char::from_u32(u))
+            .unwrap_or('\0')
+    };
+    let name_end = (start + 1..units.len())
+        .find(|&j| !(at(j).is_ascii_alphanumeric() || "-:".contains(at(j))))
+        .unwrap_or(units.len());
+    let element: String = (start + 1..name_end)
+        .map(|j| at(j).to_ascii_lowercase())
+        .collect();
+    let mut i = start;
+    let mut attribute = String::new();
+    while i < units.len() && at(i) != '>' {
+        keep[i] = true;
+        let c = at(i);
+        if c.is_ascii_alphanumeric() || c == '-' {
+            attribute.push(c.to_ascii_lowercase());
+        } else if c == '=' {
+            i += 1;
+            while i < units.len() && at(i).is_ascii_whitespace() {
+                keep[i] = true;
+                i += 1;
+            }
+            let quote = Some(at(i)).filter(|c| *c == '"' || *c == '\'');
+            if quote.is_some() {
+                keep[i] = true;
+                i += 1;
+            }
+            let value_start = i;
+            while i < units.len()
+                && match quote {
+                    Some(q) => at(i) != q,
+                    None => !at(i).is_ascii_whitespace() && at(i) != '>',
+                }
+            {
+                i += 1;
+            }
+            let kept = match KEPT_ATTRIBUTES.contains(&attribute.as_str()) {
+                true => i,
+                false => value_start + scheme_len(units, value_start, i),
+            };
+            keep[value_start..kept].fill(true);
+            attribute.clear();
+            if quote.is_some() && i < units.len() {
+                keep[i] = true;
+                i += 1;
+            }
+            continue;
+        } else {
+            attribute.clear();
+        }
+        i += 1;
+    }
+    if i < units.len() {
+        keep[i] = true;
+        i += 1;
+    }
+    (i, element)
+}
+
+/// Length of a `scheme:` prefix such as `https:`, `mailto:` or `cid:`
+fn scheme_len(units: &[u32], start: usize, end: usize) -> usize {
+    let scheme = (start..end.min(start + 16))
+        .take_while(|&j| {
+            char::from_u32(units[j]).is_some_and(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        })
+        .count();
+    match units.get(start + scheme) {
+        Some(0x3A) if scheme > 0 && start + scheme < end => scheme + 1,
+        _ => 0,
+    }
+}
+
+struct EncodedWord {
+    /// `=?charset?B?`
+    prefix: Range<usize>,
+    payload: Range<usize>,
+    base64: bool,
+}
+
+/// The next `=?charset?B|Q?payload?=` at or after `from`
+fn encoded_word(value: &[u8], from: usize) -> Option<EncodedWord> {
+    let mut search = from;
+    loop {
+        let start = search + find(&value[search..], b"=?")?;
+        let charset_end = start + 2 + value[start + 2..].iter().position(|&b| b == b'?')?;
+        let encoding = value.get(charset_end + 1).map(u8::to_ascii_uppercase);
+        let payload_start = charset_end + 3;
+        if matches!(encoding, Some(b'B' | b'Q')) && value.get(charset_end + 2) == Some(&b'?') {
+            if let Some(len) = value
+                .get(payload_start..)
+                .and_then(|rest| find(rest, b"?="))
+            {
+                return Some(EncodedWord {
+                    prefix: start..payload_start,
+                    payload: payload_start..payload_start + len,
+                    base64: encoding == Some(b'B'),
+                });
+            }
+        }
+        search = start + 2;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn next_line(data: &[u8], start: usize) -> usize {
+    data[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| start + i + 1)
+}
+
+/// `data` base64-encoded with the line length and line breaks of `original`
+fn base64_like(original: &[u8], data: &[u8]) -> Vec<u8> {
+    let encoded = STANDARD.encode(data);
+    let first_line = original
+        .iter()
+        .position(|&b| b == b'\n')
+        .unwrap_or(original.len());
+    let line_break: &[u8] = match original[..first_line].ends_with(b"\r") {
+        true => b"\r\n",
+        false => b"\n",
+    };
+    let width = original[..first_line].trim_ascii_end().len();
+    if width == 0 || width >= encoded.len() {
+        let mut out = encoded.into_bytes();
+        if first_line < original.len() {
+            out.extend_from_slice(line_break);
+        }
+        return out;
+    }
+    let lines: Vec<&[u8]> = encoded.as_bytes().chunks(width).collect();
+    let mut out = lines.join(line_break);
+    if original.ends_with(b"\n") {
+        out.extend_from_slice(line_break);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EML: &[u8] = b"From: Anna Berg <anna.berg@example.com>\r\n\
+To: bob@example.org\r\n\
+Subject: =?utf-8?Q?Gr=C3=BC=C3=9Fe?= and plans\r\n\
+Date: Tue, 1 Oct 2024 09:30:00 +0200\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<p style=3D\"color:red\">Hi Bob, write to <a href=3D\"mailto:anna.berg@example.com\">=\r\n\
+anna.berg@example.com</a> &amp; sch=C3=B6n</p>\r\n\
+--b1\r\n\
+Content-Type: application/pdf; name=\"secret plan.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQKc2VjcmV0IGNvbnRlbnQ=\r\n\
+--b1--\r\n";
+
+    #[test]
+    fn content_goes_and_structure_stays() {
+        let redacted = redact(MessageFormat::Eml, EML).unwrap();
+        assert_eq!(redacted.len(), EML.len());
+        let text = String::from_utf8_lossy(&redacted);
+        for secret in ["Anna", "anna.berg", "Bob", "plans", "secret", "sch=C3=B6n"] {
+            assert!(!text.contains(secret), "{} left in {}", secret, text);
+        }
+        for kept in [
+            "Subject: =?utf-8?Q?Xx=C3=A9=C3=A9x?= xxx xxxxx\r\n",
+            "Date: Tue, 1 Oct 2024 09:30:00 +0200\r\n",
+            "boundary=\"b1\"",
+            "<p style=3D\"color:red\">Xx Xxx, xxxxx xx <a href=3D\"mailto:",
+            "</a> &amp; xxx=C3=A9x</p>",
+            "name=\"xxxxxx xxxx.pdf\"",
+        ] {
+            assert!(text.contains(kept), "{} missing from {}", kept, text);
+        }
+
+        // The same address gets the same stand-in, across headers, links and text
+        let original = parse_bytes(MessageFormat::Eml, EML, &ParseOptions::default()).unwrap();
+        let parsed = parse_bytes(MessageFormat::Eml, &redacted, &ParseOptions::default()).unwrap();
+        let from = parsed.from.unwrap().email.unwrap();
+        assert_ne!(from, "anna.berg@example.com");
+        assert_eq!(from.len(), "anna.berg@example.com".len());
+        assert_eq!(text.matches(from.as_str()).count(), 3);
+        assert_eq!(parsed.attachments.len(), 1);
+        let attachment = &parsed.attachments[0];
+        assert_eq!(attachment.data.len(), original.attachments[0].data.len());
+        assert_ne!(attachment.data, original.attachments[0].data);
+
+        let verification = verify(MessageFormat::Eml, EML, &redacted);
+        assert!(verification.reproduces, "{:?}", verification);
+    }
+
+    #[test]
+    fn msg_streams_are_redacted_by_property() {
+        let original = crate::bench::msg(80, &[64]);
+        let redacted = redact(MessageFormat::Msg, &original).unwrap();
+        let message = parse_bytes(MessageFormat::Msg, &redacted, &ParseOptions::default()).unwrap();
+        let subject = message.subject.unwrap();
+        assert!(!subject.contains("Quarterly"));
+        assert_eq!(subject.len(), "Quarterly figures".len());
+        let attachment = &message.attachments[0];
+        assert_eq!(attachment.file_name, "xxxxxx-0.pdf");
+        assert_eq!(attachment.data.len(), 64);
+        assert!(verify(MessageFormat::Msg, &original, &redacted).reproduces);
+    }
+}
@@ -0,0 +1,243 @@
+use super::html::{self, Element, Node};
+use super::{safelinks, trackers};
+use std::collections::HashMap;
+
+/// Size browsers give images without declared dimensions, in pixels
+const DEFAULT_IMAGE_SIZE: (f32, f32) = (300.0, 150.0);
+
+/// Area a character of body text takes up, in square pixels (about 8 by 18 at the usual
+/// mail font sizes)
+const CHAR_AREA: f32 = 144.0;
+
+/// Elements whose text isn't laid out
+const INVISIBLE: &[&str] = &["head", "style", "script", "title", "template"];
+
+/// Share of the laid-out area of `html` (0 to 1) that remote images would cover: what a
+/// message designed around loaded images looks like with them blocked. Images count with
+/// their declared size (attributes or inline style), text by its length; `src`, `srcset`
+/// and background images all count as remote when they point to a web server.
+pub fn area_ratio(html: &str) -> f32 {
+    let mut area = Area::default();
+    for node in html::parse(html) {
+        area.add(&node);
+    }
+    let total = area.remote + area.embedded + area.text;
+    match total > 0.0 {
+        true => area.remote / total,
+        false => 0.0,
+    }
+}
+
+#[derive(Default)]
+struct Area {
+    remote: f32,
+    embedded: f32,
+    text: f32,
+}
+
+impl Area {
+    fn add(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) => {
+                let chars = text.split_whitespace().map(|word| word.chars().count() + 1);
+                self.text += chars.sum::<usize>() as f32 * CHAR_AREA;
+            }
+            Node::Element(element) => {
+                if INVISIBLE.contains(&element.name.as_str()) || is_hidden(element) {
+                    return;
+                }
+                if element.name == "img" {
+                    let remote = element.attribute("src").is_some_and(trackers::is_remote)
+                        || element.attribute("srcset").is_some_and(|set| {
+                            set.split(',')
+                                .filter_map(|candidate| candidate.split_whitespace().next())
+                                .any(trackers::is_remote)
+                        });
+                    let (width, height) = declared_size(element).unwrap_or(DEFAULT_IMAGE_SIZE);
+                    match remote {
+                        true => self.remote += width * height,
+                        false => self.embedded += width * height,
+                    }
+                } else if has_remote_background(element) {
+                    // Text on top of the background doesn't add to the area
+                    let (width, height) = declared_size(element).unwrap_or(DEFAULT_IMAGE_SIZE);
+                    self.remote += width * height;
+                    return;
+                }
+                for child in &element.children {
+                    self.add(child);
+                }
+            }
+        }
+    }
+}
+
+/// `http(s)` URLs of the images in `html`, entity-decoded, in order and without duplicates
+pub fn image_urls(html: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for tag in html::tags(html, "img") {
+        let Some(src) = html::attribute(&tag, "src").map(str::trim) else {
+            continue;
+        };
+        let lower = src.to_ascii_lowercase();
+        if (lower.starts_with("http://") || lower.starts_with("https://"))
+            && !urls.iter().any(|url| url == src)
+        {
+            urls.push(src.to_string());
+        }
+    }
+    urls
+}
+
+/// `html` with the `src` of every image listed in `data_urls` (by its URL as returned by
+/// [`image_urls`]) replaced with the `data:` URL; their `srcset` is dropped so the view
+/// doesn't pick a remote candidate instead
+pub fn inline(html: &str, data_urls: &HashMap<String, String>) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find("<img") {
+        pos += found + 4;
+        if !lower[pos..].starts_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let Some(end) = safelinks::tag_end(html, pos) else {
+            break;
+        };
+        let attributes = safelinks::attributes(&html[pos..end]);
+        let data_url = attributes
+            .iter()
+            .find(|attribute| attribute.name.eq_ignore_ascii_case("src"))
+            .and_then(|src| data_urls.get(html::decode_entities(src.value).trim()));
+        if let Some(data_url) = data_url {
+            out.push_str(&html[copied..pos]);
+            for attribute in attributes.iter().filter(|attribute| {
+                !attribute.name.eq_ignore_ascii_case("src")
+                    && !attribute.name.eq_ignore_ascii_case("srcset")
+            }) {
+                out.push(' ');
+                out.push_str(&html[pos..end][attribute.span.clone()]);
+            }
+            out.push_str(&format!(
+                " src=\"{}\"",
+                safelinks::escape_attribute(data_url)
+            ));
+            out.push_str(match html[..end].ends_with('/') {
+                true => "/>",
+                false => ">",
+            });
+            copied = end + 1;
+        }
+        pos = end + 1;
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+fn is_hidden(element: &Element) -> bool {
+    element
+        .attribute("style")
+        .map(html::style_declarations)
+        .unwrap_or_default()
+        .iter()
+        .any(|(property, value)| {
+            (property == "display" && value == "none")
+                || (property == "visibility" && value == "hidden")
+        })
+}
+
+/// A `background` attribute or a `background`/`background-image` style with a remote URL
+fn has_remote_background(element: &Element) -> bool {
+    if element
+        .attribute("background")
+        .is_some_and(trackers::is_remote)
+    {
+        return true;
+    }
+    element
+        .attribute("style")
+        .map(html::style_declarations)
+        .unwrap_or_default()
+        .iter()
+        .filter(|(property, _)| property == "background" || property == "background-image")
+        .any(|(_, value)| css_urls(value).any(trackers::is_remote))
+}
+
+/// Targets of the `url(...)` functions in a CSS value
+fn css_urls(value: &str) -> impl Iterator<Item = &str> {
+    value.split("url(").skip(1).filter_map(|rest| {
+        let url = rest.split(')').next()?;
+        Some(url.trim().trim_matches(['"', '\'']).trim())
+    })
+}
+
+/// Width and height in pixels from attributes or the inline style, when both are given
+fn declared_size(element: &Element) -> Option<(f32, f32)> {
+    let style = element
+        .attribute("style")
+        .map(html::style_declarations)
+        .unwrap_or_default();
+    let size = |name: &str| {
+        style
+            .iter()
+            .find(|(property, _)| property == name)
+            .and_then(|(_, value)| pixels(value))
+            .or_else(|| element.attribute(name).and_then(pixels))
+    };
+    Some((size("width")?, size("height")?))
+}
+
+/// `600`, `600px` or `600.5px`; relative sizes can't be resolved here
+fn pixels(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let size: f32 = value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .trim()
+        .parse()
+        .ok()?;
+    (size.is_finite() && size >= 0.0).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_newsletters_are_mostly_remote_area() {
+        let hero = r#"<html><head><style>p { margin: 0 }</style></head><body>
+            <img src="https://cdn.example.com/hero.jpg" width="600" height="400" alt="Autumn sale">
+            <td style="background-image: url('https://cdn.example.com/bg.png'); width: 600px; height: 300px">
+              <p>Up to 50% off</p></td>
+            <img srcset="https://cdn.example.com/a.png 1x, https://cdn.example.com/a@2x.png 2x">
+            <p>Unsubscribe</p></body></html>"#;
+        assert!(area_ratio(hero) > 0.95);
+
+        let letter = r#"<p>Hello Ann,</p><p>the figures for the third quarter are attached.
+            Let me know if anything is missing before Friday's meeting.</p>
+            <img src="cid:logo" width="120" height="40"><img src="https://example.com/x" style="display:none">"#;
+        assert_eq!(area_ratio(letter), 0.0);
+        assert_eq!(area_ratio(""), 0.0);
+    }
+
+    #[test]
+    fn fetched_images_replace_their_remote_source() {
+        let html = r#"<img src="https://cdn.example.com/a.png?x=1&amp;y=2" srcset="https://cdn.example.com/a@2x.png 2x" alt="A">
+            <IMG SRC='https://cdn.example.com/a.png?x=1&y=2'/><img src="https://cdn.example.com/b.png"><img src="cid:logo">"#;
+        let urls = image_urls(html);
+        assert_eq!(
+            urls,
+            [
+                "https://cdn.example.com/a.png?x=1&y=2",
+                "https://cdn.example.com/b.png"
+            ]
+        );
+        let fetched = HashMap::from([(urls[0].clone(), "data:image/png;base64,AAAA".to_string())]);
+        assert_eq!(
+            inline(html, &fetched),
+            r#"<img alt="A" src="data:image/png;base64,AAAA">
+            <IMG src="data:image/png;base64,AAAA"/><img src="https://cdn.example.com/b.png"><img src="cid:logo">"#
+        );
+    }
+}
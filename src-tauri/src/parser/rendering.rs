@@ -0,0 +1,429 @@
+//! Features of an HTML body that make it look different in the reading pane than in
+//! Outlook: conditional comments, VML, web fonts, layouts wider than the pane, and what the
+//! webview's sanitizer removes. Runs on the body as the message carries it, before
+//! sanitizing, so removed features are still counted.
+
+use super::accessible::{ALLOWED_ATTRIBUTES, ALLOWED_TAGS};
+use super::html::{self, Element, Node};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Tables wider than this (in pixels) don't fit the reading pane next to the message list
+pub const VIEWPORT_WIDTH: u32 = 800;
+
+/// Characters of text kept from each conditional block
+const SNIPPET_CHARS: usize = 120;
+
+/// Elements the sanitizer unwraps without changing the look
+const DOCUMENT_ELEMENTS: &[&str] = &["html", "head", "body"];
+
+/// Namespace prefixes of VML (`v:`), Office (`o:`) and Word (`w:`) elements
+const OFFICE_PREFIXES: &[&str] = &["v:", "o:", "w:"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RenderingReport {
+    /// `<!--[if mso]>` blocks and their counterparts for other clients
+    pub conditional_blocks: Vec<ConditionalBlock>,
+    /// VML and Office elements by name, inside conditional blocks or not
+    pub vml_elements: Vec<Count>,
+    /// `@font-face` rules
+    pub web_fonts: Vec<WebFont>,
+    /// `<link>` and `@import` stylesheets, which the reading pane doesn't load
+    pub external_stylesheets: Vec<String>,
+    /// Tables and cells with a fixed width over [`VIEWPORT_WIDTH`]
+    pub wide_tables: Vec<WideTable>,
+    /// Inline style properties the sanitizer removes (positioning), by name
+    pub stripped_css: Vec<Count>,
+    /// `mso-` properties that stay, but mean nothing outside Outlook
+    pub mso_css: Vec<Count>,
+    /// Elements the sanitizer removes or unwraps, by name
+    pub stripped_elements: Vec<Count>,
+    /// Attributes the sanitizer removes, by name
+    pub stripped_attributes: Vec<Count>,
+    /// Something above is likely to change how the message looks
+    pub may_differ_from_outlook: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConditionalBlock {
+    /// As written, e.g. `gte mso 9` or `!mso`
+    pub condition: String,
+    /// Inside the comment, so only Outlook shows it; false for the downlevel-revealed
+    /// `<!--[if !mso]><!-->` blocks other clients show and Outlook skips
+    pub outlook_only: bool,
+    /// Elements inside, by name
+    pub elements: Vec<Count>,
+    /// Start of the text inside
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WebFont {
+    pub family: Option<String>,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WideTable {
+    pub element: String,
+    pub width: u32,
+}
+
+/// Report the rendering features `body` (HTML, unsanitized) relies on
+pub fn analyze(body: &str) -> RenderingReport {
+    let mut scan = Scan::default();
+    let mut conditional_blocks = Vec::new();
+    for (condition, outlook_only, content) in conditionals(body) {
+        let nodes = html::parse(content);
+        let mut elements = BTreeMap::new();
+        let mut text = String::new();
+        for node in &nodes {
+            collect_block(node, &mut elements, &mut text);
+        }
+        // The tree below holds revealed blocks; hidden ones are comments to it
+        if outlook_only {
+            for node in &nodes {
+                scan.node(node, true);
+            }
+        }
+        conditional_blocks.push(ConditionalBlock {
+            condition,
+            outlook_only,
+            elements: counts(elements),
+            text: text.trim().chars().take(SNIPPET_CHARS).collect(),
+        });
+    }
+    for node in &html::parse(body) {
+        scan.node(node, false);
+    }
+
+    let mut report = RenderingReport {
+        vml_elements: counts(scan.vml),
+        web_fonts: scan.web_fonts,
+        external_stylesheets: scan.external_stylesheets,
+        wide_tables: scan.wide_tables,
+        stripped_css: counts(scan.stripped_css),
+        mso_css: counts(scan.mso_css),
+        stripped_elements: counts(scan.stripped_elements),
+        stripped_attributes: counts(scan.stripped_attributes),
+        may_differ_from_outlook: false,
+        conditional_blocks,
+    };
+    report.may_differ_from_outlook = report
+        .conditional_blocks
+        .iter()
+        .any(|block| block.outlook_only)
+        || !report.vml_elements.is_empty()
+        || !report.web_fonts.is_empty()
+        || !report.external_stylesheets.is_empty()
+        || !report.wide_tables.is_empty()
+        || !report.stripped_css.is_empty()
+        || !report.stripped_elements.is_empty();
+    report
+}
+
+/// Conditional comments as `(condition, outlook_only, content)`: `<!--[if mso]>...<![endif]-->`
+/// hides its content in a comment, `<!--[if !mso]><!-->...<!--<![endif]-->` leaves it out
+fn conditionals(body: &str) -> Vec<(String, bool, &str)> {
+    const OPEN: &str = "<!--[if";
+    const END: &str = "<![endif]";
+    const REVEALED: &str = "<!-->";
+    let lower = body.to_ascii_lowercase();
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find(OPEN) {
+        let start = pos + found + OPEN.len();
+        let Some(close) = lower[start..].find("]>") else {
+            break;
+        };
+        let condition = body[start..start + close].trim().to_string();
+        let mut content_start = start + close + 2;
+        let revealed = lower[content_start..].starts_with(REVEALED);
+        if revealed {
+            content_start += REVEALED.len();
+        }
+        let Some(end) = lower[content_start..].find(END) else {
+            break;
+        };
+        let mut content = &body[content_start..content_start + end];
+        if revealed {
+            content = content.strip_suffix("<!--").unwrap_or(content);
+        }
+        blocks.push((condition, !revealed, content));
+        pos = content_start + end + END.len();
+    }
+    blocks
+}
+
+fn collect_block(node: &Node, elements: &mut BTreeMap<String, usize>, text: &mut String) {
+    match node {
+        Node::Text(t) => {
+            for word in t.split_whitespace() {
+                if text.len() > SNIPPET_CHARS * 4 {
+                    return;
+                }
+                text.push_str(word);
+                text.push(' ');
+            }
+        }
+        Node::Element(element) => {
+            *elements.entry(element.name.clone()).or_default() += 1;
+            if element.name == "style" {
+                return;
+            }
+            for child in &element.children {
+                collect_block(child, elements, text);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scan {
+    vml: BTreeMap<String, usize>,
+    web_fonts: Vec<WebFont>,
+    external_stylesheets: Vec<String>,
+    wide_tables: Vec<WideTable>,
+    stripped_css: BTreeMap<String, usize>,
+    mso_css: BTreeMap<String, usize>,
+    stripped_elements: BTreeMap<String, usize>,
+    stripped_attributes: BTreeMap<String, usize>,
+}
+
+impl Scan {
+    /// `outlook_only` content never reaches the sanitizer, so it adds nothing stripped
+    fn node(&mut self, node: &Node, outlook_only: bool) {
+        let Node::Element(element) = node else {
+            return;
+        };
+        let name = element.name.as_str();
+        if OFFICE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            *self.vml.entry(element.name.clone()).or_default() += 1;
+        } else if !outlook_only
+            && !ALLOWED_TAGS.contains(&name)
+            && !DOCUMENT_ELEMENTS.contains(&name)
+        {
+            *self
+                .stripped_elements
+                .entry(element.name.clone())
+                .or_default() += 1;
+        }
+        match name {
+            "style" => {
+                for child in &element.children {
+                    if let Node::Text(css) = child {
+                        self.stylesheet(css);
+                    }
+                }
+            }
+            "link" => {
+                let stylesheet = element
+                    .attribute("rel")
+                    .is_some_and(|rel| rel.to_ascii_lowercase().contains("stylesheet"));
+                if let Some(href) = element.attribute("href").filter(|_| stylesheet) {
+                    self.external_stylesheets.push(href.to_string());
+                }
+            }
+            "table" | "td" | "th" | "col" => {
+                if let Some(width) = fixed_width(element).filter(|&w| w > VIEWPORT_WIDTH) {
+                    self.wide_tables.push(WideTable {
+                        element: element.name.clone(),
+                        width,
+                    });
+                }
+            }
+            _ => {}
+        }
+        for (attribute, value) in &element.attributes {
+            if !outlook_only
+                && !ALLOWED_ATTRIBUTES.contains(&attribute.as_str())
+                && !attribute.starts_with("aria-")
+            {
+                *self
+                    .stripped_attributes
+                    .entry(attribute.clone())
+                    .or_default() += 1;
+            }
+            if attribute == "style" {
+                self.inline_style(value);
+            }
+        }
+        for child in &element.children {
+            self.node(child, outlook_only);
+        }
+    }
+
+    /// Declarations the sanitizer removes (see `cleanMsoStyles`), and other `mso-` ones
+    fn inline_style(&mut self, style: &str) {
+        for (property, value) in html::style_declarations(style) {
+            let positioned = property == "position" && (value == "absolute" || value == "fixed");
+            if positioned || property.starts_with("mso-position") {
+                *self.stripped_css.entry(property).or_default() += 1;
+            } else if property.starts_with("mso-") {
+                *self.mso_css.entry(property).or_default() += 1;
+            }
+        }
+    }
+
+    fn stylesheet(&mut self, css: &str) {
+        let lower = css.to_ascii_lowercase();
+        for (at, _) in lower.match_indices("@font-face") {
+            let rule = &css[at..];
+            let Some(open) = rule.find('{') else {
+                continue;
+            };
+            let body = &rule[open + 1..rule.find('}').unwrap_or(rule.len()).max(open + 1)];
+            // Family names and URLs keep their case
+            let family = body
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .find(|(property, _)| property.trim().eq_ignore_ascii_case("font-family"))
+                .map(|(_, value)| value.trim().trim_matches(['"', '\'']).to_string());
+            let sources: Vec<String> = body
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .filter(|(property, _)| property.trim().eq_ignore_ascii_case("src"))
+                .flat_map(|(_, value)| css_urls(value))
+                .collect();
+            if family.is_some() || !sources.is_empty() {
+                self.web_fonts.push(WebFont { family, sources });
+            }
+        }
+        for (at, _) in lower.match_indices("@import") {
+            let rule = &css[at + "@import".len()..];
+            let rule = &rule[..rule.find(';').unwrap_or(rule.len())];
+            let url = css_urls(rule)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| rule.trim().trim_matches(['"', '\'']).to_string());
+            if !url.is_empty() {
+                self.external_stylesheets.push(url);
+            }
+        }
+    }
+}
+
+/// Targets of the `url(...)` functions in a CSS value, with `:` inside URLs kept
+fn css_urls(value: &str) -> Vec<String> {
+    value
+        .split("url(")
+        .skip(1)
+        .filter_map(|rest| {
+            let url = rest.split(')').next()?.trim().trim_matches(['"', '\'']);
+            (!url.is_empty()).then(|| url.to_string())
+        })
+        .collect()
+}
+
+/// Width in pixels from the `width` attribute or a `width`/`min-width` style
+fn fixed_width(element: &Element) -> Option<u32> {
+    let style = element
+        .attribute("style")
+        .map(html::style_declarations)
+        .unwrap_or_default();
+    style
+        .iter()
+        .filter(|(property, _)| property == "width" || property == "min-width")
+        .filter_map(|(_, value)| pixels(value))
+        .max()
+        .or_else(|| element.attribute("width").and_then(pixels))
+}
+
+/// `640` or `640px`; percentages and other relative sizes adapt to the pane
+fn pixels(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let size: f32 = value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .trim()
+        .parse()
+        .ok()?;
+    (size.is_finite() && size >= 0.0).then(|| size.round() as u32)
+}
+
+fn counts(map: BTreeMap<String, usize>) -> Vec<Count> {
+    map.into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_outlook_specific_features() {
+        let body = r#"<html xmlns:v="urn:schemas-microsoft-com:vml"><head>
+            <style>@import url("https://fonts.example.com/css?family=Lato");
+            @font-face { font-family: 'Brand Sans'; src: url(https://cdn.example.com/brand.woff2) format('woff2'); }</style>
+            </head><body>
+            <!--[if gte mso 9]><v:rect fill="true" style="width:600px"><v:fill src="https://cdn.example.com/bg.jpg"/>
+              <v:textbox><p>Outlook gets a background</p></v:textbox></v:rect><![endif]-->
+            <!--[if !mso]><!--><div style="position: absolute; top: 0">Everyone else</div><!--<![endif]-->
+            <table width="900" background="bg.jpg"><tr><td style="mso-line-height-rule: exactly; width: 100%">
+              <form><input value="x"></form><o:p></o:p></td></tr></table>
+            </body></html>"#;
+        let report = analyze(body);
+        assert!(report.may_differ_from_outlook);
+
+        assert_eq!(report.conditional_blocks.len(), 2);
+        let outlook = &report.conditional_blocks[0];
+        assert_eq!(outlook.condition, "gte mso 9");
+        assert!(outlook.outlook_only);
+        assert_eq!(outlook.text, "Outlook gets a background");
+        assert!(outlook.elements.iter().any(|e| e.name == "v:fill"));
+        let others = &report.conditional_blocks[1];
+        assert!(!others.outlook_only);
+        assert_eq!(others.text, "Everyone else");
+
+        let vml: Vec<_> = report
+            .vml_elements
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(vml, ["o:p", "v:fill", "v:rect", "v:textbox"]);
+        assert_eq!(
+            report.web_fonts,
+            [WebFont {
+                family: Some("Brand Sans".to_string()),
+                sources: vec!["https://cdn.example.com/brand.woff2".to_string()],
+            }]
+        );
+        assert_eq!(
+            report.external_stylesheets,
+            ["https://fonts.example.com/css?family=Lato"]
+        );
+        assert_eq!(
+            report.wide_tables,
+            [WideTable {
+                element: "table".to_string(),
+                width: 900,
+            }]
+        );
+        assert_eq!(report.stripped_css[0].name, "position");
+        assert_eq!(report.mso_css[0].name, "mso-line-height-rule");
+        let stripped: Vec<_> = report
+            .stripped_elements
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(stripped, ["form", "input"]);
+        let attributes: Vec<_> = report
+            .stripped_attributes
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(attributes, ["background", "value", "xmlns:v"]);
+
+        assert!(!analyze("<p>Plain <b>letter</b></p>").may_differ_from_outlook);
+    }
+}
@@ -0,0 +1,413 @@
+//! Compressed RTF (MS-OXRTFCP), the form .msg files and TNEF store RTF bodies in, and the
+//! HTML Outlook encapsulates in such bodies (MS-OXRTFEX, `\fromhtml1`), which is shown for
+//! messages that have no HTML body of their own. Other RTF isn't rendered.
+
+use super::charset;
+use super::limits::Budget;
+
+/// `COMPTYPE` of LZFu-compressed data
+const COMPRESSED: u32 = 0x7546_5A4C;
+/// `COMPTYPE` of data stored as it is
+const UNCOMPRESSED: u32 = 0x414C_454D;
+
+/// Header before the data: compressed size, raw size, type and CRC
+const HEADER_LEN: usize = 16;
+
+/// The dictionary LZFu starts with
+const PRELOAD: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \
+\\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier\
+{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+const DICTIONARY_LEN: usize = 4096;
+
+/// The most one input byte can expand to: a control byte and 8 references of 2 bytes
+/// make up to 8 × 17 bytes
+const MAX_EXPANSION: usize = 8;
+
+/// Destinations whose text isn't part of the encapsulated HTML
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "object",
+    "header",
+    "footer",
+    "listtable",
+    "listoverridetable",
+    "rsidtbl",
+    "generator",
+    "filetbl",
+    "revtbl",
+];
+
+/// Decompress a PidTagRtfCompressed value. The CRC isn't checked: a damaged body
+/// decompresses as far as it goes.
+pub fn decompress(data: &[u8], budget: &Budget) -> Result<Vec<u8>, String> {
+    let header = data
+        .get(..HEADER_LEN)
+        .ok_or("Compressed RTF is shorter than its header")?;
+    let field = |at: usize| {
+        u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]]) as usize
+    };
+    // The compressed size counts from after its own field
+    let body = &data[HEADER_LEN..];
+    let body = &body[..body.len().min(field(0).saturating_sub(HEADER_LEN - 4))];
+    let raw_size = field(4);
+    let kind = field(8) as u32;
+    match kind {
+        UNCOMPRESSED => {
+            let rtf = &body[..body.len().min(raw_size)];
+            budget.reserve("compressed RTF", rtf.len() as u64)?;
+            Ok(rtf.to_vec())
+        }
+        COMPRESSED => {
+            let len = raw_size.min(body.len().saturating_mul(MAX_EXPANSION));
+            budget.reserve("compressed RTF", len as u64)?;
+            Ok(lzfu(body, len))
+        }
+        _ => Err(format!("Unknown compressed RTF type {:08X}", kind)),
+    }
+}
+
+/// LZFu: each control byte says for the eight items after it, lowest bit first, whether
+/// it is a literal byte or a 2-byte reference into the dictionary (12-bit offset, 4-bit
+/// length less 2). A reference to the write position ends the data.
+fn lzfu(data: &[u8], raw_size: usize) -> Vec<u8> {
+    let mut dictionary = [0u8; DICTIONARY_LEN];
+    dictionary[..PRELOAD.len()].copy_from_slice(PRELOAD);
+    let mut out = Vec::with_capacity(raw_size);
+    // The write cursor follows the output, starting just past the preload
+    let cursor = |out: &Vec<u8>| (PRELOAD.len() + out.len()) % DICTIONARY_LEN;
+    let put = |out: &mut Vec<u8>, dictionary: &mut [u8; DICTIONARY_LEN], byte: u8| {
+        dictionary[cursor(out)] = byte;
+        out.push(byte);
+    };
+
+    let mut items = data.iter().copied();
+    while let Some(control) = items.next() {
+        for bit in 0..8 {
+            if out.len() >= raw_size {
+                out.truncate(raw_size);
+                return out;
+            }
+            if control & (1 << bit) == 0 {
+                let Some(byte) = items.next() else {
+                    return out;
+                };
+                put(&mut out, &mut dictionary, byte);
+                continue;
+            }
+            let (Some(high), Some(low)) = (items.next(), items.next()) else {
+                return out;
+            };
+            let reference = u16::from_be_bytes([high, low]) as usize;
+            let offset = reference >> 4;
+            if offset == cursor(&out) {
+                return out;
+            }
+            for i in 0..(reference & 0xF) + 2 {
+                let byte = dictionary[(offset + i) % DICTIONARY_LEN];
+                put(&mut out, &mut dictionary, byte);
+            }
+        }
+    }
+    out.truncate(raw_size);
+    out
+}
+
+/// An uncompressed PidTagRtfCompressed value of exactly `len` bytes holding an empty
+/// document, to stand in for a body; shorter lengths get spaces
+pub(crate) fn blank(len: usize) -> Vec<u8> {
+    let Some(body_len) = len.checked_sub(HEADER_LEN) else {
+        return vec![b' '; len];
+    };
+    let mut out = Vec::with_capacity(len);
+    for field in [(len - 4) as u32, body_len as u32, UNCOMPRESSED, 0] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out.extend(b"{\\rtf1}".iter().take(body_len));
+    out.resize(len, b' ');
+    out
+}
+
+/// What a group contributes to the HTML, inherited by the groups inside it
+#[derive(Clone, Copy, Default)]
+struct Group {
+    /// A destination whose text is left out
+    skip: bool,
+    /// Between `\htmlrtf` and `\htmlrtf0`: RTF only, not part of the HTML
+    rtf_only: bool,
+    /// Nothing but the opening brace and maybe `\*` seen yet
+    fresh: bool,
+    ignorable: bool,
+}
+
+/// The HTML encapsulated in an RTF body, `None` for RTF that doesn't encapsulate any.
+/// The text of `\*\htmltag` groups and the text outside `\htmlrtf` blocks make up the
+/// HTML; bytes are in the `\ansicpg` code page.
+pub fn encapsulated_html(rtf: &[u8]) -> Option<String> {
+    let head = &rtf[..rtf.len().min(1024)];
+    if !head.windows(10).any(|window| window == b"\\fromhtml1") {
+        return None;
+    }
+    let mut html = Html {
+        text: String::new(),
+        pending: Vec::new(),
+        label: "windows-1252",
+    };
+    let mut groups = vec![Group::default()];
+    let mut skip_chars = 0usize;
+    let mut unicode_skip = 1usize;
+    let mut pos = 0;
+    while pos < rtf.len() {
+        let current = *groups.last().unwrap();
+        let emits = !current.skip && !current.rtf_only;
+        match rtf[pos] {
+            b'{' => {
+                pos += 1;
+                groups.last_mut().unwrap().fresh = false;
+                groups.push(Group {
+                    fresh: true,
+                    ignorable: false,
+                    ..current
+                });
+            }
+            b'}' => {
+                pos += 1;
+                if groups.len() > 1 {
+                    groups.pop();
+                }
+            }
+            b'\\' => {
+                let (word, param, next) = control(rtf, pos + 1);
+                pos = next;
+                let group = groups.last_mut().unwrap();
+                let fresh = std::mem::replace(&mut group.fresh, false);
+                match word {
+                    Control::Symbol(b'*') if fresh => {
+                        group.ignorable = true;
+                        group.fresh = true;
+                    }
+                    Control::Word("htmltag") if fresh => {
+                        // Tags are HTML even inside `\htmlrtf` blocks
+                        group.skip = false;
+                        group.rtf_only = false;
+                    }
+                    Control::Word(word)
+                        if fresh && (group.ignorable || SKIPPED_DESTINATIONS.contains(&word)) =>
+                    {
+                        group.skip = true;
+                    }
+                    Control::Word("bin") => {
+                        pos = pos.saturating_add(param.unwrap_or(0).max(0) as usize);
+                    }
+                    Control::Word("htmlrtf") => group.rtf_only = param != Some(0),
+                    Control::Word("ansicpg") => {
+                        if let Some(label) = param
+                            .and_then(|cp| u32::try_from(cp).ok())
+                            .and_then(charset::label_for_codepage)
+                        {
+                            html.flush();
+                            html.label = label;
+                        }
+                    }
+                    Control::Word("uc") => unicode_skip = param.unwrap_or(1).max(0) as usize,
+                    Control::Word("u") if emits => {
+                        // Negative values are the upper half of the 16-bit range
+                        let unit = param.unwrap_or(0) as u16 as u32;
+                        html.push(char::from_u32(unit).unwrap_or('\u{FFFD}'));
+                        skip_chars = unicode_skip;
+                        continue;
+                    }
+                    Control::Hex(byte) if emits => {
+                        if skip_chars > 0 {
+                            skip_chars -= 1;
+                        } else {
+                            html.pending.push(byte);
+                        }
+                        continue;
+                    }
+                    _ if !emits => {}
+                    Control::Word("par" | "line") => html.push_str("\r\n"),
+                    Control::Word("tab") => html.push('\t'),
+                    Control::Word("lquote") => html.push('\u{2018}'),
+                    Control::Word("rquote") => html.push('\u{2019}'),
+                    Control::Word("ldblquote") => html.push('\u{201C}'),
+                    Control::Word("rdblquote") => html.push('\u{201D}'),
+                    Control::Word("bullet") => html.push('\u{2022}'),
+                    Control::Word("endash") => html.push('\u{2013}'),
+                    Control::Word("emdash") => html.push('\u{2014}'),
+                    Control::Symbol(b'~') => html.push('\u{A0}'),
+                    Control::Symbol(symbol @ (b'\\' | b'{' | b'}')) => html.pending.push(symbol),
+                    _ => {}
+                }
+                skip_chars = 0;
+            }
+            b'\r' | b'\n' => pos += 1,
+            byte => {
+                pos += 1;
+                groups.last_mut().unwrap().fresh = false;
+                if skip_chars > 0 {
+                    skip_chars -= 1;
+                } else if emits {
+                    html.pending.push(byte);
+                }
+            }
+        }
+    }
+    html.flush();
+    Some(html.text)
+}
+
+/// The HTML decoded so far and the bytes still to decode
+struct Html {
+    text: String,
+    pending: Vec<u8>,
+    label: &'static str,
+}
+
+impl Html {
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.text
+                .push_str(&charset::decode(&self.pending, self.label));
+            self.pending.clear();
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.flush();
+        self.text.push(c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.flush();
+        self.text.push_str(s);
+    }
+}
+
+enum Control<'a> {
+    Word(&'a str),
+    /// `\'hh`
+    Hex(u8),
+    Symbol(u8),
+}
+
+/// The control word, symbol or escaped byte after a backslash at `start`, its numeric
+/// parameter and where the next token begins (after the space delimiting a word)
+fn control(rtf: &[u8], start: usize) -> (Control<'_>, Option<i32>, usize) {
+    let Some(&first) = rtf.get(start) else {
+        return (Control::Symbol(b'\\'), None, start);
+    };
+    if first == b'\'' {
+        let hex = rtf
+            .get(start + 1..start + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        return match hex {
+            Some(byte) => (Control::Hex(byte), None, start + 3),
+            None => (Control::Symbol(b'\''), None, start + 1),
+        };
+    }
+    if !first.is_ascii_alphabetic() {
+        return (Control::Symbol(first), None, start + 1);
+    }
+    let word_end = rtf[start..]
+        .iter()
+        .position(|b| !b.is_ascii_alphabetic())
+        .map_or(rtf.len(), |len| start + len);
+    // Only ASCII letters, so this can't fail
+    let word = std::str::from_utf8(&rtf[start..word_end]).unwrap_or_default();
+    let mut end = word_end;
+    if rtf.get(end) == Some(&b'-') {
+        end += 1;
+    }
+    let digits_end = rtf[end..]
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .map_or(rtf.len(), |len| end + len);
+    let param = match digits_end > end {
+        true => std::str::from_utf8(&rtf[word_end..digits_end])
+            .ok()
+            .and_then(|number| number.parse::<i64>().ok())
+            .map(|number| number.clamp(i32::MIN as i64, i32::MAX as i64) as i32),
+        false => None,
+    };
+    let mut next = if param.is_some() {
+        digits_end
+    } else {
+        word_end
+    };
+    if rtf.get(next) == Some(&b' ') {
+        next += 1;
+    }
+    (Control::Word(word), param, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first example of MS-OXRTFCP 4.1
+    const EXAMPLE: &[u8] = &[
+        0x2d, 0x00, 0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x4c, 0x5a, 0x46, 0x75, 0xf1, 0xc5, 0xc7,
+        0xa7, 0x03, 0x00, 0x0a, 0x00, 0x72, 0x63, 0x70, 0x67, 0x31, 0x32, 0x35, 0x42, 0x32, 0x0a,
+        0xf3, 0x20, 0x68, 0x65, 0x6c, 0x09, 0x00, 0x20, 0x62, 0x77, 0x05, 0xb0, 0x6c, 0x64, 0x7d,
+        0x0a, 0x80, 0x0f, 0xa0,
+    ];
+
+    #[test]
+    fn compressed_rtf_decompresses_with_references_into_the_preload() {
+        let budget = Budget::default();
+        assert_eq!(
+            decompress(EXAMPLE, &budget).unwrap(),
+            b"{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n"
+        );
+
+        // Stored as they are
+        assert_eq!(
+            decompress(&blank(64), &budget).unwrap(),
+            [b"{\\rtf1}" as &[u8], &[b' '; 41]].concat()
+        );
+        assert_eq!(
+            decompress(&blank(10), &budget).unwrap_err(),
+            "Compressed RTF is shorter than its header"
+        );
+
+        // Truncated data decompresses as far as it goes; a declared raw size is no
+        // reason to allocate
+        assert_eq!(
+            decompress(&EXAMPLE[..24], &budget).unwrap(),
+            b"{\\rtf1\\ansi\\ansicpg"
+        );
+        let mut huge = EXAMPLE.to_vec();
+        huge[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decompress(&huge, &budget).unwrap().len(), 43);
+
+        let mut unknown = EXAMPLE.to_vec();
+        unknown[8..12].copy_from_slice(b"ABCD");
+        assert!(decompress(&unknown, &budget)
+            .unwrap_err()
+            .starts_with("Unknown"));
+    }
+
+    #[test]
+    fn encapsulated_html_is_taken_from_tags_and_text_outside_rtf_blocks() {
+        let rtf = b"{\\rtf1\\ansi\\ansicpg1252\\fromhtml1 \\deff0{\\fonttbl{\\f0\\fswiss Arial;}}\r\n\
+{\\*\\htmltag19 <html>}{\\*\\htmltag34 <head>}{\\*\\htmltag41 <title>}Caf\\'e9{\\*\\htmltag49 </title>}\r\n\
+{\\*\\htmltag50 <body>}\\htmlrtf {\\f0 RTF only\\par}\\htmlrtf0 \r\n\
+Tom\\rquote s \\{x\\} \\u8364?{\\*\\htmltag84 <br>}\\par Line\\tab two\r\n\
+{\\*\\mhtmltag84 <img src=\"cid:a\">}{\\*\\htmltag84 <img src=\"cid:a\">}\r\n\
+{\\*\\htmltag58 </body>}{\\*\\htmltag27 </html>}}";
+        assert_eq!(
+            encapsulated_html(rtf).unwrap(),
+            "<html><head><title>Café</title><body>Tom\u{2019}s {x} €<br>\r\nLine\ttwo\
+<img src=\"cid:a\"></body></html>"
+        );
+
+        // Plain RTF encapsulates nothing
+        assert_eq!(encapsulated_html(b"{\\rtf1\\ansi Hello\\par}"), None);
+    }
+}
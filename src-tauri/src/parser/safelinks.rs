@@ -0,0 +1,327 @@
+//! Link wrappers of mail security gateways (Microsoft SafeLinks, Proofpoint URLDefense),
+//! which rewrite every URL into a redirect through their service. The original destination
+//! is decoded from the wrapped URL itself; nothing is ever fetched.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::Serialize;
+
+/// SafeLinks hosts: `<region>.safelinks.protection.outlook.com` and the government clouds
+const SAFELINKS_HOSTS: &[&str] = &[
+    ".safelinks.protection.outlook.com",
+    ".safelinks.protection.office365.us",
+];
+
+/// Characters naming the length of a `**x` run in URLDefense v3, for lengths 2, 3, ...
+const V3_RUN_LENGTHS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrapper {
+    SafeLinks,
+    UrlDefenseV1,
+    UrlDefenseV2,
+    UrlDefenseV3,
+}
+
+/// The wrapper of `url`, if it is a wrapped link
+pub fn detect(url: &str) -> Option<Wrapper> {
+    let host = super::links::host(url)?.to_ascii_lowercase();
+    let path = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|slash| &rest[slash..]))
+        .unwrap_or("/");
+    if SAFELINKS_HOSTS.iter().any(|suffix| host.ends_with(suffix)) {
+        return Some(Wrapper::SafeLinks);
+    }
+    match host.as_str() {
+        "urldefense.proofpoint.com" if path.starts_with("/v1/") => Some(Wrapper::UrlDefenseV1),
+        "urldefense.proofpoint.com" if path.starts_with("/v2/") => Some(Wrapper::UrlDefenseV2),
+        "urldefense.com" if path.starts_with("/v3/") => Some(Wrapper::UrlDefenseV3),
+        _ => None,
+    }
+}
+
+/// The original URL inside a link wrapped by `wrapper`; `None` if it is malformed or
+/// doesn't hold an http(s) URL
+pub fn decode(wrapper: Wrapper, url: &str) -> Option<String> {
+    let decoded = match wrapper {
+        Wrapper::SafeLinks => percent_decode(query_param(url, "url")?)?,
+        Wrapper::UrlDefenseV1 => percent_decode(query_param(url, "u")?)?,
+        // `-` stands for `%` and `_` for `/`
+        Wrapper::UrlDefenseV2 => {
+            percent_decode(&query_param(url, "u")?.replace('-', "%").replace('_', "/"))?
+        }
+        Wrapper::UrlDefenseV3 => decode_v3(url)?,
+    };
+    let scheme = decoded.to_ascii_lowercase();
+    let web = scheme.starts_with("http://") || scheme.starts_with("https://");
+    (web && super::links::host(&decoded).is_some()).then_some(decoded)
+}
+
+/// Raw value of a query parameter
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let query = url.split_once('?')?.1.split('#').next()?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// `%XX` escapes decoded; `None` for invalid escapes or bytes that aren't UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// `https://urldefense.com/v3/__<url>__;<base64>!!<signature>$`: characters Proofpoint
+/// doesn't keep in the URL are replaced by `*` (one character) or `**x` (a run whose length
+/// `x` encodes) and stored in order in the base64 part
+fn decode_v3(url: &str) -> Option<String> {
+    let rest = url.split_once("/v3/__")?.1;
+    let (embedded, rest) = rest.split_once("__;")?;
+    let encoded = rest.split('!').next()?.trim_end_matches('=');
+    let replacements: Vec<char> = if encoded.is_empty() {
+        Vec::new()
+    } else {
+        String::from_utf8(URL_SAFE_NO_PAD.decode(encoded).ok()?)
+            .ok()?
+            .chars()
+            .collect()
+    };
+
+    let mut replacements = replacements.into_iter();
+    let mut out = String::with_capacity(embedded.len());
+    let mut chars = embedded.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '*' {
+            out.push(c);
+            continue;
+        }
+        let run = if chars.peek() == Some(&'*') {
+            chars.next();
+            V3_RUN_LENGTHS.find(chars.next()?)? + 2
+        } else {
+            1
+        };
+        for _ in 0..run {
+            out.push(replacements.next()?);
+        }
+    }
+    // Every stored character must have been used
+    replacements.next().is_none().then_some(out)
+}
+
+/// Give every `<a>` whose `href` is a wrapped link a `title` with the original destination,
+/// so hovering shows where it leads; the `href` itself is left alone. Titles the sender set
+/// on such links are replaced.
+pub fn annotate_titles(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find("<a") {
+        let start = pos + found;
+        pos = start + 2;
+        if !lower[pos..].starts_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let Some(end) = tag_end(html, pos) else {
+            break;
+        };
+        let attributes = attributes(&html[pos..end]);
+        let target = attributes
+            .iter()
+            .find(|attribute| attribute.name.eq_ignore_ascii_case("href"))
+            .and_then(|href| {
+                let url = href.value.replace("&amp;", "&");
+                decode(detect(&url)?, &url)
+            });
+        if let Some(target) = target {
+            out.push_str(&html[copied..pos]);
+            for attribute in attributes
+                .iter()
+                .filter(|attribute| !attribute.name.eq_ignore_ascii_case("title"))
+            {
+                out.push(' ');
+                out.push_str(&html[pos..end][attribute.span.clone()]);
+            }
+            out.push_str(&format!(" title=\"{}\"", escape_attribute(&target)));
+            let closing = if html[..end].ends_with('/') {
+                "/>"
+            } else {
+                ">"
+            };
+            out.push_str(closing);
+            copied = end + 1;
+        }
+        pos = end + 1;
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+pub(super) struct Attribute<'a> {
+    pub(super) name: &'a str,
+    pub(super) value: &'a str,
+    /// Source text of the whole attribute within the tag
+    pub(super) span: std::ops::Range<usize>,
+}
+
+/// Index of the `>` closing a tag whose attributes start at `from`, skipping quoted values
+pub(super) fn tag_end(html: &str, from: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html[from..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(from + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Attributes of a tag, from the text between its name and `>`
+pub(super) fn attributes(tag: &str) -> Vec<Attribute<'_>> {
+    let mut attributes = Vec::new();
+    let bytes = tag.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() || bytes[i] == b'/' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && !matches!(bytes[i], b'=' | b'/') && !bytes[i].is_ascii_whitespace()
+        {
+            i += 1;
+        }
+        let name = &tag[start..i];
+        let mut value = "";
+        let mut j = i;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if bytes.get(j) == Some(&b'=') {
+            j += 1;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            match bytes.get(j) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let close = tag[j + 1..]
+                        .find(quote as char)
+                        .map_or(tag.len(), |close| j + 1 + close);
+                    value = &tag[j + 1..close];
+                    i = (close + 1).min(tag.len());
+                }
+                _ => {
+                    let start = j;
+                    while j < bytes.len() && !bytes[j].is_ascii_whitespace() {
+                        j += 1;
+                    }
+                    value = &tag[start..j];
+                    i = j;
+                }
+            }
+        }
+        attributes.push(Attribute {
+            name,
+            value,
+            span: start..i,
+        });
+    }
+    attributes
+}
+
+pub(super) fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_wrapped_links() {
+        let cases = [
+            (
+                "https://eur03.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1%26c%3D2&data=05%7C01&reserved=0",
+                Wrapper::SafeLinks,
+                "https://example.com/a?b=1&c=2",
+            ),
+            (
+                "https://urldefense.proofpoint.com/v1/url?u=http://example.com/x%20y&k=abc",
+                Wrapper::UrlDefenseV1,
+                "http://example.com/x y",
+            ),
+            (
+                "https://urldefense.proofpoint.com/v2/url?u=https-3A__example.com_path-3Fq-3D1&d=DwMFaQ&c=x",
+                Wrapper::UrlDefenseV2,
+                "https://example.com/path?q=1",
+            ),
+            (
+                // `*` for "?", `**A` for the two characters "=&" ("?=&" in base64 is Pz0m)
+                "https://urldefense.com/v3/__https://example.com/page*id**A1__;Pz0m!!ABC!def$",
+                Wrapper::UrlDefenseV3,
+                "https://example.com/page?id=&1",
+            ),
+            (
+                "https://urldefense.com/v3/__https://example.com/plain__;!!ABC!def$",
+                Wrapper::UrlDefenseV3,
+                "https://example.com/plain",
+            ),
+        ];
+        for (url, wrapper, original) in cases {
+            assert_eq!(detect(url), Some(wrapper), "{}", url);
+            assert_eq!(decode(wrapper, url).as_deref(), Some(original), "{}", url);
+        }
+
+        assert_eq!(detect("https://example.com/?url=https%3A%2F%2Fx.com"), None);
+        // Missing parameter, bad escapes, replacements that don't add up, no web URL
+        for url in [
+            "https://eur03.safelinks.protection.outlook.com/?data=05",
+            "https://eur03.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%ZZ",
+            "https://urldefense.com/v3/__https://example.com/page*id__;!!ABC$",
+            "https://urldefense.com/v3/__https://example.com/page__;Pz0m!!ABC$",
+            "https://urldefense.proofpoint.com/v2/url?u=javascript-3Aalert-281-29",
+        ] {
+            assert_eq!(decode(detect(url).unwrap(), url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn titles_show_the_original_destination() {
+        let html = concat!(
+            r#"<p><A class="x" title="Click" HREF="https://nam02.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2F%3Fa%3D1&amp;data=1">go</A>"#,
+            r#" <a href="https://example.org/">plain</a>"#,
+            r#" <a href='https://eur03.safelinks.protection.outlook.com/?url=bad'>bad</a>"#,
+            r#" <abbr title="x">y</abbr></p>"#,
+        );
+        assert_eq!(
+            annotate_titles(html),
+            concat!(
+                r#"<p><A class="x" HREF="https://nam02.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2F%3Fa%3D1&amp;data=1" title="https://example.com/?a=1">go</A>"#,
+                r#" <a href="https://example.org/">plain</a>"#,
+                r#" <a href='https://eur03.safelinks.protection.outlook.com/?url=bad'>bad</a>"#,
+                r#" <abbr title="x">y</abbr></p>"#,
+            )
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! HTML bodies made safe and offline for files viewed outside the app: the elements and
+//! attributes of the webview's sanitizer only (see [`super::accessible::ALLOWED_TAGS`]),
+//! links to web and mail addresses only, and nothing loaded from the network. Embedded
+//! images stay as `data:` URLs, with `cid:` images resolved by the caller.
+
+use super::accessible::{ALLOWED_ATTRIBUTES, ALLOWED_TAGS, LINK_SCHEMES};
+use super::html::{self, Element, Node};
+
+/// Elements removed with their content; other elements the sanitizer doesn't allow are
+/// unwrapped
+const DROPPED: &[&str] = &[
+    "script", "iframe", "object", "embed", "applet", "form", "input", "button", "select",
+    "textarea", "noscript", "template", "title", "head", "svg", "math", "audio", "video",
+];
+
+/// Sanitize `body`; `resolve(cid)` gives the `data:` URL of an embedded image, images it
+/// doesn't know and all remote ones are left out
+pub fn sanitize(body: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    for node in html::parse(body) {
+        write_node(&mut out, &node, &resolve);
+    }
+    out
+}
+
+fn write_node(out: &mut String, node: &Node, resolve: &impl Fn(&str) -> Option<String>) {
+    let element = match node {
+        Node::Text(text) => {
+            out.push_str(&escape_text(text));
+            return;
+        }
+        Node::Element(element) => element,
+    };
+    let name = element.name.as_str();
+    if DROPPED.contains(&name) {
+        return;
+    }
+    if !ALLOWED_TAGS.contains(&name) {
+        for child in &element.children {
+            write_node(out, child, resolve);
+        }
+        return;
+    }
+    if name == "style" {
+        let css: String = element
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect();
+        // Closing the element early is the only way out of raw text
+        out.push_str(&format!(
+            "<style>{}</style>",
+            offline_css(&css).replace("</", "<\\/")
+        ));
+        return;
+    }
+    let attributes = attributes(element, resolve);
+    if name == "img" && !attributes.iter().any(|(name, _)| name == "src") {
+        // Nothing would show; the alt text keeps what the image said
+        if let Some(alt) = element
+            .attribute("alt")
+            .filter(|alt| !alt.trim().is_empty())
+        {
+            out.push_str(&escape_text(alt));
+        }
+        return;
+    }
+
+    out.push('<');
+    out.push_str(name);
+    for (name, value) in &attributes {
+        out.push_str(&format!(" {}=\"{}\"", name, escape_attribute(value)));
+    }
+    out.push('>');
+    if matches!(name, "br" | "hr" | "img" | "col") {
+        return;
+    }
+    for child in &element.children {
+        write_node(out, child, resolve);
+    }
+    out.push_str(&format!("</{}>", name));
+}
+
+/// The attributes the sanitizer keeps, with offline-safe values
+fn attributes(
+    element: &Element,
+    resolve: &impl Fn(&str) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut kept = Vec::new();
+    for (name, value) in &element.attributes {
+        if !ALLOWED_ATTRIBUTES.contains(&name.as_str()) && !name.starts_with("aria-") {
+            continue;
+        }
+        let trimmed = value.trim();
+        let lower = trimmed.to_ascii_lowercase();
+        let value = match name.as_str() {
+            "href" => {
+                if !LINK_SCHEMES.iter().any(|scheme| lower.starts_with(scheme))
+                    && !trimmed.starts_with('#')
+                {
+                    continue;
+                }
+                trimmed.to_string()
+            }
+            "src" => {
+                let embedded = match lower.strip_prefix("cid:") {
+                    Some(_) => resolve(&trimmed[4..]),
+                    None => lower
+                        .starts_with("data:image/")
+                        .then(|| trimmed.to_string()),
+                };
+                match embedded {
+                    Some(url) => url,
+                    None => continue,
+                }
+            }
+            "style" => offline_style(value),
+            _ => value.clone(),
+        };
+        kept.push((name.clone(), value));
+    }
+    if element.name == "a" && kept.iter().any(|(name, _)| name == "href") {
+        kept.retain(|(name, _)| name != "target" && name != "rel");
+        kept.push(("target".to_string(), "_blank".to_string()));
+        kept.push(("rel".to_string(), "noopener noreferrer".to_string()));
+    }
+    kept
+}
+
+/// Declarations of a `style` attribute without remote images and without the positioning
+/// the reading pane removes as well (`cleanMsoStyles`)
+fn offline_style(style: &str) -> String {
+    split_declarations(style)
+        .into_iter()
+        .filter(|declaration| {
+            let Some((property, value)) = declaration.split_once(':') else {
+                return false;
+            };
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim().to_ascii_lowercase();
+            !has_remote_url(&value)
+                && !property.starts_with("mso-position")
+                && !(property == "position"
+                    && (value.starts_with("absolute") || value.starts_with("fixed")))
+        })
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A style sheet without imports, and with remote `url(...)`s replaced by `none`
+fn offline_css(css: &str) -> String {
+    let mut out = String::new();
+    let lower = css.to_ascii_lowercase();
+    let mut pos = 0;
+    while pos < css.len() {
+        let next_import = lower[pos..].find("@import").map(|i| pos + i);
+        let next_url = lower[pos..].find("url(").map(|i| pos + i);
+        let Some(start) = [next_import, next_url].into_iter().flatten().min() else {
+            out.push_str(&css[pos..]);
+            break;
+        };
+        out.push_str(&css[pos..start]);
+        if Some(start) == next_import {
+            pos = lower[start..]
+                .find(';')
+                .map_or(css.len(), |i| start + i + 1);
+        } else {
+            let end = lower[start..]
+                .find(')')
+                .map_or(css.len(), |i| start + i + 1);
+            if has_remote_url(&lower[start..end]) {
+                out.push_str("none");
+            } else {
+                out.push_str(&css[start..end]);
+            }
+            pos = end;
+        }
+    }
+    out
+}
+
+/// `url(...)` pointing anywhere but into the document itself
+fn has_remote_url(value: &str) -> bool {
+    let mut rest = value;
+    while let Some(pos) = rest.find("url(") {
+        rest = &rest[pos + 4..];
+        let target = rest.trim_start().trim_start_matches(['"', '\'']);
+        if !target.starts_with("data:") && !target.starts_with('#') {
+            return true;
+        }
+    }
+    false
+}
+
+/// Split at `;` outside parentheses, which `data:` URLs contain
+fn split_declarations(style: &str) -> Vec<&str> {
+    let mut declarations = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in style.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => {
+                declarations.push(&style[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    declarations.push(&style[start..]);
+    declarations.retain(|declaration| !declaration.trim().is_empty());
+    declarations
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_keep_allowed_markup_and_load_nothing() {
+        let body = r#"<html><head><title>T</title><meta http-equiv="refresh" content="0"></head>
+            <body onload="steal()"><style>@import url(https://evil.test/a.css);
+            p { background: url('https://evil.test/bg.png'); color: red }</style>
+            <p style="color: blue; background-image: url(http://t.test/x.gif); position: absolute">Hi
+            <a href="javascript:alert(1)" onclick="x()">bad</a> <a href="https://example.com" target="_self">good</a>
+            <img src="cid:logo@x" alt="Logo"><img src="https://t.test/pixel.gif" alt="">
+            <img src="cid:unknown" alt="Chart"><script>alert(1)</script><form><input></form>
+            <custom-tag>kept text</custom-tag></p></body></html>"#;
+        let clean = sanitize(body, |cid| {
+            (cid == "logo@x").then(|| "data:image/png;base64,AAAA".to_string())
+        });
+
+        for gone in [
+            "script",
+            "onload",
+            "onclick",
+            "javascript:",
+            "evil.test",
+            "t.test",
+            "meta",
+            "title",
+            "input",
+            "custom-tag",
+            "_self",
+            "position",
+        ] {
+            assert!(!clean.contains(gone), "{} in {}", gone, clean);
+        }
+        assert!(clean.contains(r#"<p style="color: blue">"#), "{}", clean);
+        assert!(clean.contains("background: none; color: red"), "{}", clean);
+        assert!(clean.contains(r#"<img src="data:image/png;base64,AAAA" alt="Logo">"#));
+        assert!(clean.contains(
+            r#"<a href="https://example.com" target="_blank" rel="noopener noreferrer">good</a>"#
+        ));
+        assert!(clean.contains("<a>bad</a>"));
+        assert!(clean.contains("Chart") && clean.contains("kept text"));
+        // Clean output stays as it is
+        assert_eq!(sanitize(&clean, |_| None), clean);
+    }
+}
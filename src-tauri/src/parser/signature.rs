@@ -0,0 +1,327 @@
+use serde::Serialize;
+
+/// Share of the body (from the end) a signature may cover at most
+const MAX_SIGNATURE_SHARE: f64 = 0.4;
+
+/// Lines that end the author's own text and start the quoted original in replies
+const QUOTE_MARKERS: &[&str] = &[
+    "-----original message-----",
+    "----- original message -----",
+    "-----ursprüngliche nachricht-----",
+    "________________________________",
+];
+
+/// Closing lines after which a signature usually starts
+const VALEDICTIONS: &[&str] = &[
+    "best regards",
+    "kind regards",
+    "warm regards",
+    "regards",
+    "best",
+    "cheers",
+    "thanks",
+    "thank you",
+    "many thanks",
+    "sincerely",
+    "yours sincerely",
+    "mit freundlichen grüßen",
+    "viele grüße",
+    "beste grüße",
+    "cordialement",
+    "saludos",
+];
+
+/// Phrases typical of legal disclaimers appended by mail servers
+const DISCLAIMER_PHRASES: &[&str] = &[
+    "this email and any attachments",
+    "this e-mail and any attachments",
+    "this message and any attachments",
+    "this email is confidential",
+    "this e-mail is confidential",
+    "intended solely for",
+    "intended only for the use",
+    "if you have received this",
+    "if you are not the intended recipient",
+    "please notify the sender immediately",
+    "may contain confidential",
+    "may contain privileged",
+    "legally privileged",
+    "please consider the environment before printing",
+    "diese e-mail enthält vertrauliche",
+    "wenn sie nicht der richtige adressat sind",
+];
+
+/// Words marking a contact line (phone, fax, postal address, social profiles)
+const CONTACT_WORDS: &[&str] = &[
+    "tel", "phone", "mobile", "mob", "cell", "fax", "direct", "office", "street", "str.", "suite",
+    "floor", "avenue", "road", "linkedin", "twitter",
+];
+
+/// How the signature was recognized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureKind {
+    /// Starts at a `-- ` separator line
+    Separator,
+    /// Trailing block made mostly of contact details
+    ContactBlock,
+    /// Legal disclaimer paragraph
+    Disclaimer,
+}
+
+/// Byte range of a signature in a body; the body itself is never modified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SignatureRange {
+    pub start: usize,
+    pub end: usize,
+    pub kind: SignatureKind,
+}
+
+/// Find the signature block (including disclaimers) at the end of the author's own text;
+/// text quoted from earlier messages in a reply is left out of the search
+pub fn detect(text: &str) -> Option<SignatureRange> {
+    let lines = lines(text);
+    let own_end = lines
+        .iter()
+        .find(|line| is_quote_marker(line.text))
+        .map_or(text.len(), |line| line.start);
+    let own: Vec<&Line> = lines.iter().filter(|line| line.start < own_end).collect();
+
+    // Trailing blank lines don't count towards the body length
+    let content_end = own
+        .iter()
+        .rev()
+        .find(|line| !line.text.trim().is_empty())
+        .map(|line| line.start + line.text.len())?;
+    let earliest = (content_end as f64 * (1.0 - MAX_SIGNATURE_SHARE)).ceil() as usize;
+
+    // Disclaimers come last; the contact block is looked for above them
+    let disclaimer = disclaimer(&own);
+    let above_disclaimer: Vec<&Line> = own
+        .iter()
+        .copied()
+        .filter(|line| disclaimer.is_none_or(|start| line.start < start))
+        .collect();
+    let candidates = [
+        separator(&own).map(|start| (start, SignatureKind::Separator)),
+        contact_block(&above_disclaimer).map(|start| (start, SignatureKind::ContactBlock)),
+        disclaimer.map(|start| (start, SignatureKind::Disclaimer)),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter(|&(start, _)| start >= earliest && start < content_end)
+        .min_by_key(|&(start, _)| start)
+        .map(|(start, kind)| SignatureRange {
+            start,
+            end: own_end,
+            kind,
+        })
+}
+
+/// `text` with the signature range cut out
+pub fn strip(text: &str, range: &SignatureRange) -> String {
+    let (Some(before), Some(after)) = (text.get(..range.start), text.get(range.end..)) else {
+        return text.to_string();
+    };
+    let mut stripped = before.trim_end().to_string();
+    if !after.trim().is_empty() {
+        stripped.push_str("\n\n");
+        stripped.push_str(after.trim_start_matches(['\r', '\n']));
+    } else {
+        stripped.push('\n');
+    }
+    stripped
+}
+
+struct Line<'a> {
+    start: usize,
+    text: &'a str,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut start = 0;
+    text.split_inclusive('\n')
+        .map(|raw| {
+            let line = Line {
+                start,
+                text: raw.trim_end_matches(['\r', '\n']),
+            };
+            start += raw.len();
+            line
+        })
+        .collect()
+}
+
+/// Whether a line contains typical disclaimer language
+pub(super) fn is_disclaimer(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    DISCLAIMER_PHRASES
+        .iter()
+        .any(|phrase| lower.contains(phrase))
+}
+
+/// Whether a line introduces the quoted original of a reply
+pub(super) fn is_quote_marker(line: &str) -> bool {
+    let lower = line.trim().to_lowercase();
+    QUOTE_MARKERS.iter().any(|marker| lower.starts_with(marker))
+        || (lower.starts_with("on ") && lower.ends_with("wrote:"))
+        || (lower.starts_with("am ") && lower.ends_with("schrieb:"))
+}
+
+/// Last `-- ` line (RFC 3676 signature separator; the trailing space is often lost)
+fn separator(lines: &[&Line]) -> Option<usize> {
+    lines
+        .iter()
+        .rev()
+        .find(|line| line.text == "-- " || line.text == "--")
+        .map(|line| line.start)
+}
+
+/// Start of the first paragraph from which on the text is disclaimer language
+fn disclaimer(lines: &[&Line]) -> Option<usize> {
+    let hit = lines.iter().position(|line| is_disclaimer(line.text))?;
+    // Back up to the start of the paragraph containing the phrase
+    let start = lines[..hit]
+        .iter()
+        .rposition(|line| line.text.trim().is_empty())
+        .map_or(0, |blank| blank + 1);
+    Some(lines[start].start)
+}
+
+/// Start of a trailing block dominated by contact lines. The block grows upwards while
+/// at least 40% of its lines are contact details, up to a closing line such as "Best regards,".
+fn contact_block(lines: &[&Line]) -> Option<usize> {
+    let mut start = None;
+    let mut contact_lines = 0;
+    let mut other_lines = 0;
+
+    for line in lines.iter().rev() {
+        let text = line.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if is_valediction(text) {
+            break;
+        }
+        if is_contact_line(text) {
+            contact_lines += 1;
+        } else if text.chars().count() <= 60 {
+            // Names, job titles, company names
+            other_lines += 1;
+        } else {
+            break;
+        }
+        if contact_lines >= 2 && contact_lines * 5 >= (contact_lines + other_lines) * 2 {
+            start = Some(line.start);
+        }
+    }
+    start
+}
+
+/// Whether a line is a closing such as "Best regards,"
+pub(super) fn is_valediction(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    let lower = lower.trim_end_matches([',', '!', '.', ' ']);
+    VALEDICTIONS.contains(&lower)
+}
+
+fn is_contact_line(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    if lower.contains("http://")
+        || lower.contains("https://")
+        || lower.contains("www.")
+        || is_email_line(&lower)
+    {
+        return true;
+    }
+    let digits = line.chars().filter(char::is_ascii_digit).count();
+    let phone_like = digits >= 7
+        && line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .all(|c| c.is_ascii_digit() || "+-()/.:|".contains(c) || c.is_alphabetic());
+    let labeled = CONTACT_WORDS.iter().any(|word| {
+        lower
+            .split(|c: char| !c.is_alphanumeric() && c != '.')
+            .any(|token| token == *word)
+    });
+    // "T: +49 30 1234567", "Suite 400, 123 Main Street", "10115 Berlin"
+    phone_like || (labeled && digits > 0) || is_postal_line(line)
+}
+
+fn is_email_line(lower: &str) -> bool {
+    lower.split_whitespace().any(|word| {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        word.split_once('@')
+            .is_some_and(|(user, host)| !user.is_empty() && host.contains('.'))
+    })
+}
+
+/// A short line starting with a postal code followed by a place name (`10115 Berlin`)
+fn is_postal_line(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let (Some(code), Some(place)) = (words.next(), words.next()) else {
+        return false;
+    };
+    let code = code.trim_start_matches(|c: char| c.is_ascii_uppercase() || c == '-');
+    (4..=5).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_digit())
+        && place.starts_with(char::is_uppercase)
+        && line.split_whitespace().count() <= 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTLOOK: &str = include_str!("../../tests/fixtures/signatures/outlook.txt");
+    const OUTLOOK_REPLY: &str = include_str!("../../tests/fixtures/signatures/outlook-reply.txt");
+    const GMAIL: &str = include_str!("../../tests/fixtures/signatures/gmail.txt");
+
+    #[test]
+    fn outlook_contact_block_and_disclaimer() {
+        let range = detect(OUTLOOK).unwrap();
+        assert_eq!(range.kind, SignatureKind::ContactBlock);
+        assert!(OUTLOOK[range.start..].starts_with("Julia Schneider"));
+        assert_eq!(range.end, OUTLOOK.len());
+
+        let stripped = strip(OUTLOOK, &range);
+        assert!(stripped.trim_end().ends_with("Kind regards,"));
+        assert!(!stripped.contains("confidential"));
+        assert!(!stripped.contains("+49"));
+    }
+
+    #[test]
+    fn outlook_reply_keeps_quoted_original() {
+        let range = detect(OUTLOOK_REPLY).unwrap();
+        assert!(OUTLOOK_REPLY[range.start..].starts_with("Mark Evans"));
+        assert!(OUTLOOK_REPLY[range.end..].starts_with("-----Original Message-----"));
+
+        let stripped = strip(OUTLOOK_REPLY, &range);
+        assert!(!stripped.contains("Mark Evans | Account Director"));
+        assert!(stripped.contains("-----Original Message-----"));
+        assert!(stripped.contains("Could you send the updated figures?"));
+    }
+
+    #[test]
+    fn gmail_separator() {
+        let range = detect(GMAIL).unwrap();
+        assert_eq!(range.kind, SignatureKind::Separator);
+        assert!(GMAIL[range.start..].starts_with("-- \n"));
+        let stripped = strip(GMAIL, &range);
+        assert!(stripped.trim_end().ends_with("Talk soon,\nPriya"));
+    }
+
+    #[test]
+    fn never_more_than_the_trailing_share() {
+        let text = "Thanks!\n-- \nBob Example\nExample Corp\n+1 555 123 4567\nwww.example.com\n";
+        assert_eq!(detect(text), None);
+    }
+
+    #[test]
+    fn plain_message_has_no_signature() {
+        let text = "Hi Anna,\n\nthe meeting moved to 3pm, room 4.12.\nSee you there.\n\nTom\n";
+        assert_eq!(detect(text), None);
+    }
+}
@@ -0,0 +1,407 @@
+//! Where the bytes of a message file go, to answer "why is this mail so big": the bodies,
+//! each attachment, the recipients, the headers and the container around them. The figures
+//! add up to the file size exactly; what can't be attributed to anything is `unaccounted`
+//! rather than being spread over the rest.
+//!
+//! For EML every byte belongs somewhere, and `unaccounted` is 0. For MSG, streams are
+//! counted with the sectors they occupy, the container is the CFB header, allocation tables,
+//! directory and the padding of the mini stream, and `unaccounted` is what's left: free
+//! sectors (space deleted streams left behind), normally no more than a few sectors.
+
+use super::eml::{self, Role};
+use super::{emlx, headers, mime, Message, MessageFormat};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Fields counted as `recipients` in an EML header
+const RECIPIENT_HEADERS: &[&str] = &["to", "cc", "bcc"];
+
+/// Base64 line length of the re-encoding `optimized_total` assumes
+const BASE64_LINE: u64 = 76;
+
+/// Streams below this size live in the mini stream, in 64-byte mini sectors
+const MINI_STREAM_CUTOFF: u64 = 4096;
+const MINI_SECTOR: u64 = 64;
+const DIRECTORY_ENTRY: u64 = 128;
+
+/// MSG property streams by the property tag they start with
+const TEXT_PROPERTY: &str = "__substg1.0_1000";
+const HTML_PROPERTY: &str = "__substg1.0_1013";
+const RTF_PROPERTY: &str = "__substg1.0_1009";
+const HEADERS_PROPERTY: &str = "__substg1.0_007D";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SizeBreakdown {
+    /// Size of the file; the fields below add up to it
+    pub total: u64,
+    /// Header fields other than the recipients (EML), the transport headers kept with the
+    /// message (MSG)
+    pub headers: u64,
+    /// To, Cc and Bcc fields (EML), the recipient tables (MSG)
+    pub recipients: u64,
+    pub body_text: u64,
+    pub body_html: u64,
+    /// Compressed RTF body; EML only has one as a `text/rtf` attachment
+    pub body_rtf: u64,
+    pub attachments: Vec<AttachmentSize>,
+    /// The remaining MAPI properties and named property mappings (MSG)
+    pub properties: u64,
+    /// MIME boundaries, preambles and multipart headers (EML); CFB header, allocation
+    /// tables, directory and mini stream padding (MSG)
+    pub container: u64,
+    pub unaccounted: u64,
+    /// What an EML file would take with attachments base64-encoded in 76-character lines
+    /// where they now take more
+    pub optimized_total: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttachmentSize {
+    pub index: usize,
+    pub file_name: String,
+    /// Bytes of the file taken up: the part with its headers and transfer encoding (EML),
+    /// the streams of the attachment's storage in whole sectors (MSG)
+    pub stored: u64,
+    /// Size of the attachment itself
+    pub content: u64,
+    /// Encoded body over content (EML): about 1.37 for base64, 1 for none
+    pub inflation: Option<f32>,
+    /// `stored` beyond `content` (MSG): the attachment's properties and sector padding
+    pub container_overhead: Option<u64>,
+}
+
+impl SizeBreakdown {
+    /// Everything but `total`, which it equals
+    pub fn sum(&self) -> u64 {
+        self.headers
+            + self.recipients
+            + self.body_text
+            + self.body_html
+            + self.body_rtf
+            + self.attachments.iter().map(|a| a.stored).sum::<u64>()
+            + self.properties
+            + self.container
+            + self.unaccounted
+    }
+}
+
+/// The breakdown of the message in `data`; MSG attachment names come from `parse`
+pub fn breakdown(
+    format: MessageFormat,
+    data: &[u8],
+    parse: impl FnOnce() -> Result<Message, String>,
+) -> Result<SizeBreakdown, String> {
+    match format {
+        MessageFormat::Eml => Ok(eml_breakdown(data, false)),
+        MessageFormat::Emlx => Ok(eml_breakdown(data, true)),
+        MessageFormat::Msg => msg_breakdown(data, &parse()?),
+    }
+}
+
+fn eml_breakdown(data: &[u8], framed: bool) -> SizeBreakdown {
+    let mut sizes = SizeBreakdown {
+        total: data.len() as u64,
+        ..SizeBreakdown::default()
+    };
+    // The .emlx byte count before the message and the property list after it
+    let message = match emlx::split(data).filter(|_| framed) {
+        Some((message, _)) => {
+            sizes.container += (data.len() - message.len()) as u64;
+            message
+        }
+        None => data,
+    };
+    let (block, body) = headers::split_header_block(message);
+    let header_len = (message.len() - body.len()) as u64;
+    sizes.recipients = recipient_bytes(block);
+    sizes.headers = header_len - sizes.recipients;
+
+    let root = mime::parse(message);
+    let mut walk = EmlWalk::default();
+    match multipart_children(&root, body) {
+        Some(children) => {
+            sizes.container +=
+                (body.len() - children.iter().map(|c| c.len()).sum::<usize>()) as u64;
+            for child in children {
+                walk.entity(child, 1, &mut sizes);
+            }
+        }
+        None => walk.leaf(&root, body.len(), body.len() as u64, &mut sizes),
+    }
+
+    let savings: u64 = walk
+        .encoded
+        .iter()
+        .zip(&sizes.attachments)
+        .map(|(&encoded, attachment)| encoded.saturating_sub(base64_len(attachment.content)))
+        .sum();
+    sizes.optimized_total = Some(sizes.total - savings);
+    sizes
+}
+
+/// Body text and HTML found so far, and the encoded body size of each attachment
+#[derive(Default)]
+struct EmlWalk {
+    has_text: bool,
+    has_html: bool,
+    encoded: Vec<u64>,
+}
+
+impl EmlWalk {
+    /// Account a body part (with its headers) at MIME depth `depth`
+    fn entity(&mut self, data: &[u8], depth: usize, sizes: &mut SizeBreakdown) {
+        let part = mime::parse(data);
+        let (_, body) = headers::split_header_block(data);
+        if depth < mime::MAX_DEPTH {
+            if let Some(children) = multipart_children(&part, body) {
+                let inner: usize = children.iter().map(|c| c.len()).sum();
+                sizes.container += (data.len() - inner) as u64;
+                for child in children {
+                    self.entity(child, depth + 1, sizes);
+                }
+                return;
+            }
+        }
+        self.leaf(&part, body.len(), data.len() as u64, sizes);
+    }
+
+    /// Account a leaf part taking `stored` bytes of the file, `encoded` of them its body
+    fn leaf(&mut self, part: &mime::Part, encoded: usize, stored: u64, sizes: &mut SizeBreakdown) {
+        match eml::role(part, self.has_text, self.has_html) {
+            Role::TextBody => {
+                self.has_text = true;
+                sizes.body_text += stored;
+            }
+            Role::TextContinuation => sizes.body_text += stored,
+            Role::HtmlBody => {
+                self.has_html = true;
+                sizes.body_html += stored;
+            }
+            Role::Attachment if part.content_type.mime == "text/rtf" && sizes.body_rtf == 0 => {
+                sizes.body_rtf = stored;
+            }
+            Role::Attachment => {
+                let index = sizes.attachments.len();
+                let content = part.body.len() as u64;
+                sizes.attachments.push(AttachmentSize {
+                    index,
+                    file_name: part
+                        .file_name()
+                        .unwrap_or_else(|| format!("attachment-{}", index + 1)),
+                    stored,
+                    content,
+                    inflation: (content > 0).then(|| round(encoded as f32 / content as f32)),
+                    container_overhead: None,
+                });
+                self.encoded.push(encoded as u64);
+            }
+        }
+    }
+}
+
+/// The parts of a multipart entity whose body is `body`, as `mime::parse` splits them
+fn multipart_children<'a>(part: &mime::Part, body: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+    if !part.content_type.is_multipart() {
+        return None;
+    }
+    let boundary = part.content_type.param("boundary")?;
+    Some(mime::split_multipart(body, boundary))
+}
+
+/// Bytes of the To, Cc and Bcc fields in a header block, continuation lines included
+fn recipient_bytes(block: &[u8]) -> u64 {
+    let mut total = 0;
+    let mut in_recipients = false;
+    for line in block.split_inclusive(|&b| b == b'\n') {
+        let continuation = line.first().is_some_and(|b| *b == b' ' || *b == b'\t');
+        if !continuation {
+            in_recipients = line
+                .iter()
+                .position(|&b| b == b':')
+                .map(|colon| {
+                    String::from_utf8_lossy(&line[..colon])
+                        .trim()
+                        .to_lowercase()
+                })
+                .is_some_and(|name| RECIPIENT_HEADERS.contains(&name.as_str()));
+        }
+        if in_recipients {
+            total += line.len() as u64;
+        }
+    }
+    total
+}
+
+/// Base64 in lines of [`BASE64_LINE`] characters ending in CRLF
+fn base64_len(content: u64) -> u64 {
+    let encoded = content.div_ceil(3) * 4;
+    encoded + encoded.div_ceil(BASE64_LINE) * 2
+}
+
+fn msg_breakdown(data: &[u8], message: &Message) -> Result<SizeBreakdown, String> {
+    let file = cfb::CompoundFile::open(Cursor::new(data))
+        .map_err(|e| format!("Not a valid compound file: {}", e))?;
+    let sector = file.version().sector_len() as u64;
+    let total = data.len() as u64;
+    let mut sizes = SizeBreakdown {
+        total,
+        ..SizeBreakdown::default()
+    };
+
+    let mut attachments: Vec<(String, u64, u64)> = Vec::new();
+    let mut entries = 0u64;
+    let mut mini_sectors = 0u64;
+    let mut mini_stream = 0u64;
+    for entry in file.walk() {
+        entries += 1;
+        if entry.is_root() {
+            mini_stream = entry.len();
+            continue;
+        }
+        if !entry.is_stream() {
+            continue;
+        }
+        let len = entry.len();
+        let allocated = if len < MINI_STREAM_CUTOFF {
+            mini_sectors += len.div_ceil(MINI_SECTOR);
+            len.div_ceil(MINI_SECTOR) * MINI_SECTOR
+        } else {
+            len.div_ceil(sector) * sector
+        };
+        let path = entry.path().to_string_lossy().replace('\\', "/");
+        let mut components = path.trim_start_matches('/').split('/');
+        let top = components.next().unwrap_or_default();
+        let name = entry.name();
+        if top.starts_with("__attach_version1.0_") {
+            let content = if is_attachment_data(&path) { len } else { 0 };
+            match attachments
+                .iter_mut()
+                .find(|(storage, _, _)| storage == top)
+            {
+                Some((_, stored, data)) => {
+                    *stored += allocated;
+                    *data += content;
+                }
+                None => attachments.push((top.to_string(), allocated, content)),
+            }
+        } else if top.starts_with("__recip_version1.0_") {
+            sizes.recipients += allocated;
+        } else if name.starts_with(TEXT_PROPERTY) {
+            sizes.body_text += allocated;
+        } else if name.starts_with(HTML_PROPERTY) {
+            sizes.body_html += allocated;
+        } else if name.starts_with(RTF_PROPERTY) {
+            sizes.body_rtf += allocated;
+        } else if name.starts_with(HEADERS_PROPERTY) {
+            sizes.headers += allocated;
+        } else {
+            sizes.properties += allocated;
+        }
+    }
+
+    // Same order as the parser reads them in
+    attachments.sort_by(|a, b| a.0.cmp(&b.0));
+    sizes.attachments = attachments
+        .into_iter()
+        .enumerate()
+        .map(|(index, (storage, stored, content))| AttachmentSize {
+            index,
+            file_name: message
+                .attachments
+                .get(index)
+                .map(|attachment| attachment.file_name.clone())
+                .unwrap_or(storage),
+            stored,
+            content,
+            inflation: None,
+            container_overhead: Some(stored - content),
+        })
+        .collect();
+
+    let header = sector.max(512);
+    let sectors = total.saturating_sub(header).div_ceil(sector);
+    let per_sector = sector / 4;
+    let fat = sectors.div_ceil(per_sector);
+    let difat = fat.saturating_sub(109).div_ceil(per_sector - 1);
+    let mini_fat = mini_sectors.div_ceil(per_sector);
+    let directory = (entries * DIRECTORY_ENTRY).div_ceil(sector);
+    let mini_padding =
+        (mini_stream.div_ceil(sector) * sector).saturating_sub(mini_sectors * MINI_SECTOR);
+    let container = header + (fat + difat + mini_fat + directory) * sector + mini_padding;
+
+    let attributed = sizes.sum();
+    sizes.container = container.min(total.saturating_sub(attributed));
+    sizes.unaccounted = total.saturating_sub(attributed + sizes.container);
+    Ok(sizes)
+}
+
+/// The stream holding an attachment's data: binary data or an embedded message's storage
+fn is_attachment_data(path: &str) -> bool {
+    let mut components = path.trim_start_matches('/').split('/').skip(1);
+    match components.next() {
+        Some(name) if name.starts_with("__substg1.0_37010102") => true,
+        Some(name) if name.starts_with("__substg1.0_3701000D") => true,
+        _ => false,
+    }
+}
+
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eml_sizes_add_up_and_show_the_base64_inflation() {
+        let data = include_bytes!("../../tests/fixtures/onboarding/sample.eml");
+        let sizes = breakdown(MessageFormat::Eml, data, || unreachable!()).unwrap();
+        assert_eq!(sizes.sum(), sizes.total);
+        assert_eq!(sizes.total, data.len() as u64);
+        assert_eq!(sizes.unaccounted, 0);
+        assert!(sizes.headers > 0 && sizes.recipients > 0 && sizes.body_text > 0);
+        assert!(sizes.container > 0);
+        assert_eq!(sizes.attachments.len(), 3, "{:?}", sizes.attachments);
+        for attachment in &sizes.attachments {
+            assert!(attachment.stored > attachment.content);
+        }
+        assert!(sizes.optimized_total.unwrap() <= sizes.total);
+    }
+
+    #[test]
+    fn msg_sizes_add_up_with_few_free_sectors() {
+        for data in [
+            &include_bytes!("../../tests/fixtures/self-test/minimal.msg")[..],
+            &include_bytes!("../../tests/corpus/msg/sample.msg")[..],
+        ] {
+            let message = super::super::parse_bytes(
+                MessageFormat::Msg,
+                data,
+                &super::super::ParseOptions::default(),
+            )
+            .unwrap();
+            let names: Vec<String> = message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.file_name.clone())
+                .collect();
+            let has_body = message.body_text.is_some();
+            let sizes = breakdown(MessageFormat::Msg, data, || Ok(message)).unwrap();
+            assert_eq!(sizes.sum(), sizes.total);
+            assert_eq!(sizes.body_text > 0, has_body);
+            let measured: Vec<String> = sizes
+                .attachments
+                .iter()
+                .map(|a| a.file_name.clone())
+                .collect();
+            assert_eq!(measured, names);
+            for attachment in &sizes.attachments {
+                assert!(attachment.stored >= attachment.content);
+            }
+            assert!(sizes.container >= 512);
+            // Free sectors only; these files have none to speak of
+            assert!(sizes.unaccounted <= 4 * 512, "{:?}", sizes);
+        }
+    }
+}
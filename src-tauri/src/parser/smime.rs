@@ -0,0 +1,701 @@
+//! Messages wrapped whole into an `smime.p7m` attachment, as mail gateways and Outlook's
+//! signed or encrypted .msg files carry them. Signed ones are unwrapped and their signature
+//! checked against the certificate that came with it; encrypted ones are reported as such.
+
+use super::{headers, limits, mime, Message};
+use crate::i18n::Localized;
+use ring::{digest, signature};
+use serde::Serialize;
+
+/// DER-encoded object identifiers (content bytes of the OID element)
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_ENVELOPED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x03];
+const OID_AUTH_ENVELOPED_DATA: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x17,
+];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_EMAIL_ADDRESS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_RSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+/// DER tags
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+const OCTET_STRING: u8 = 0x04;
+const CONSTRUCTED_OCTET_STRING: u8 = 0x24;
+const BIT_STRING: u8 = 0x03;
+const INTEGER: u8 = 0x02;
+/// `[0]`, constructed
+const CONTEXT_0: u8 = 0xa0;
+
+/// BER nesting deeper than this isn't read
+const MAX_DER_DEPTH: usize = 32;
+
+/// RSA keys shorter than this can't be checked
+const MIN_RSA_BITS: usize = 2048;
+
+/// What the wrapping says about the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmimeStatus {
+    /// The signature matches the content and the certificate that came with it; that
+    /// certificate isn't checked against trusted roots
+    SignatureValid,
+    /// The content was changed after signing, or the signature doesn't match it
+    SignatureInvalid,
+    /// Signed with an algorithm or key this app can't check, or without the certificate
+    SignatureUnchecked,
+    /// Encrypted for the recipient's certificate, which is needed to show the content
+    Encrypted,
+}
+
+/// How a message came wrapped in S/MIME
+#[derive(Debug, Clone, Serialize)]
+pub struct SmimeWrapping {
+    pub status: SmimeStatus,
+    /// Address (or else name) in the signer's certificate
+    pub signer: Option<String>,
+    /// The message the wrapped one was delivered in, with its own body; `None` for
+    /// encrypted content, where the outer message stays the one shown
+    pub stub: Option<Box<Message>>,
+}
+
+/// Replace a message whose only attachment is a signed `smime.p7m` by the message inside,
+/// which keeps the outer one as `smime.stub`; `parse` reads the inner message. Encrypted
+/// content leaves the message as it is, marked encrypted. Anything else is returned
+/// unchanged, and a blob that can't be read adds a warning.
+pub fn unwrap(
+    mut outer: Message,
+    parse: impl FnOnce(&[u8]) -> Result<Message, String>,
+) -> Result<Message, String> {
+    let Some(index) = wrapped_blob(&outer) else {
+        return Ok(outer);
+    };
+    let unreadable = |outer: &mut Message, reason: String| {
+        outer.warnings.push(Localized::new(
+            "warning.smime_unreadable",
+            [("reason", reason)],
+        ));
+    };
+    let (content, status, signer) = match read(&outer.attachments[index].data) {
+        Ok(Blob::Signed {
+            content,
+            status,
+            signer,
+        }) => (content, status, signer),
+        Ok(Blob::Encrypted) => {
+            outer
+                .warnings
+                .push(Localized::new("warning.smime_encrypted", []));
+            outer.smime = Some(SmimeWrapping {
+                status: SmimeStatus::Encrypted,
+                signer: None,
+                stub: None,
+            });
+            return Ok(outer);
+        }
+        Err(reason) => {
+            unreadable(&mut outer, reason);
+            return Ok(outer);
+        }
+    };
+    let mut inner = match parse(&content) {
+        Ok(inner) => inner,
+        Err(e) if limits::tripped(&e).is_some() => return Err(e),
+        Err(e) => {
+            unreadable(&mut outer, e);
+            return Ok(outer);
+        }
+    };
+    inherit_envelope(&mut inner, &outer);
+    for attachment in &mut inner.attachments {
+        let mime = attachment.declared_mime.as_deref().unwrap_or_default();
+        if mime.ends_with("pkcs7-signature") {
+            attachment.hidden = true;
+        }
+    }
+    // The blob lives on as the inner message
+    outer.attachments[index].data = Vec::new();
+    inner.smime = Some(SmimeWrapping {
+        status,
+        signer,
+        stub: Some(Box::new(outer)),
+    });
+    Ok(inner)
+}
+
+/// Index of the attachment if it is the message's only one and an S/MIME blob
+fn wrapped_blob(message: &Message) -> Option<usize> {
+    let [attachment] = message.attachments.as_slice() else {
+        return None;
+    };
+    let mime = attachment.declared_mime.as_deref().unwrap_or_default();
+    let blob = attachment.file_name.to_ascii_lowercase().ends_with(".p7m")
+        || mime == "application/pkcs7-mime"
+        || mime == "application/x-pkcs7-mime";
+    blob.then_some(attachment.index)
+}
+
+/// Opaque-signed content is a MIME entity, not always a whole message: what it lacks comes
+/// from the outer message
+fn inherit_envelope(inner: &mut Message, outer: &Message) {
+    if inner.from.is_none() && inner.subject.is_none() {
+        let own = std::mem::take(&mut inner.headers);
+        inner.headers = outer.headers.clone();
+        inner
+            .headers
+            .retain(|(name, _)| !name.to_ascii_lowercase().starts_with("content-"));
+        inner.headers.extend(own);
+    }
+    inner.from = inner.from.take().or_else(|| outer.from.clone());
+    inner.subject = inner.subject.take().or_else(|| outer.subject.clone());
+    inner.date = inner.date.take().or_else(|| outer.date.clone());
+    inner.message_id = inner.message_id.take().or_else(|| outer.message_id.clone());
+    inner.importance = inner.importance.or(outer.importance);
+    inner.sensitivity = inner.sensitivity.or(outer.sensitivity);
+    let counts = &mut inner.recipient_counts;
+    for (own, outer, own_count, outer_count) in [
+        (
+            &mut inner.to,
+            &outer.to,
+            &mut counts.to,
+            outer.recipient_counts.to,
+        ),
+        (
+            &mut inner.cc,
+            &outer.cc,
+            &mut counts.cc,
+            outer.recipient_counts.cc,
+        ),
+        (
+            &mut inner.bcc,
+            &outer.bcc,
+            &mut counts.bcc,
+            outer.recipient_counts.bcc,
+        ),
+    ] {
+        if *own_count == 0 && own.is_empty() {
+            own.clone_from(outer);
+            *own_count = outer_count;
+        }
+    }
+}
+
+enum Blob {
+    Signed {
+        content: Vec<u8>,
+        status: SmimeStatus,
+        signer: Option<String>,
+    },
+    Encrypted,
+}
+
+/// A CMS structure (DER or BER), or a `multipart/signed` entity as Outlook stores signed
+/// messages
+fn read(data: &[u8]) -> Result<Blob, String> {
+    if data.first() != Some(&SEQUENCE) {
+        return read_multipart_signed(data);
+    }
+    match read_cms(data)? {
+        Cms::Encrypted => Ok(Blob::Encrypted),
+        Cms::Signed(signed) => {
+            let content = signed
+                .content
+                .clone()
+                .ok_or("The signed data carries no content")?;
+            let (status, signer) = signed.verify(&content);
+            Ok(Blob::Signed {
+                content,
+                status,
+                signer,
+            })
+        }
+    }
+}
+
+/// `multipart/signed` (RFC 8551 3.5): the first part is signed as it stands in the
+/// entity, by the detached signature in the second
+fn read_multipart_signed(data: &[u8]) -> Result<Blob, String> {
+    let not_smime = || "Neither CMS data nor a multipart/signed entity".to_string();
+    let (block, body) = headers::split_header_block(data);
+    let content_type = headers::parse(block)
+        .get("Content-Type")
+        .map(mime::parse_parameters)
+        .filter(|content_type| content_type.mime == "multipart/signed")
+        .ok_or_else(not_smime)?;
+    let boundary = content_type.param("boundary").ok_or_else(not_smime)?;
+    let signed_part = first_part(body, boundary).ok_or("The signed part is missing")?;
+    let signature = mime::parse(data)
+        .children
+        .into_iter()
+        .find(|part| part.content_type.mime.ends_with("pkcs7-signature"))
+        .ok_or("The signature part is missing")?
+        .body;
+    let Cms::Signed(signed) = read_cms(&signature)? else {
+        return Err("The signature part holds no signature".to_string());
+    };
+    let (mut status, mut signer) = signed.verify(signed_part);
+    // Signatures are made over CRLF line ends, which the file may have lost
+    if status == SmimeStatus::SignatureInvalid && !signed_part.contains(&b'\r') {
+        let canonical = String::from_utf8_lossy(signed_part).replace('\n', "\r\n");
+        (status, signer) = signed.verify(canonical.as_bytes());
+    }
+    Ok(Blob::Signed {
+        content: data.to_vec(),
+        status,
+        signer,
+    })
+}
+
+/// Content of the first body part, without the line break that belongs to the next
+/// boundary
+fn first_part<'a>(body: &'a [u8], boundary: &str) -> Option<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let at_line_start = |i: usize| i == 0 || body[i - 1] == b'\n';
+    let open = (0..body.len()).find(|&i| at_line_start(i) && body[i..].starts_with(delimiter))?;
+    let start = open + body[open..].iter().position(|&b| b == b'\n')? + 1;
+    let close =
+        (start..body.len()).find(|&i| body[i] == b'\n' && body[i + 1..].starts_with(delimiter))?;
+    let end = match close > start && body[close - 1] == b'\r' {
+        true => close - 1,
+        false => close,
+    };
+    body.get(start..end.max(start))
+}
+
+enum Cms<'a> {
+    Signed(SignedData<'a>),
+    Encrypted,
+}
+
+struct SignedData<'a> {
+    /// Encapsulated content; `None` for detached signatures
+    content: Option<Vec<u8>>,
+    certificates: Vec<Element<'a>>,
+    signer: Option<Element<'a>>,
+}
+
+/// `ContentInfo` (RFC 5652 3)
+fn read_cms(data: &[u8]) -> Result<Cms<'_>, String> {
+    let malformed = || "Malformed CMS data".to_string();
+    let (info, _) = element(data, 0)
+        .filter(|(e, _)| e.tag == SEQUENCE)
+        .ok_or_else(malformed)?;
+    let fields = info.children().ok_or_else(malformed)?;
+    let content_type = fields
+        .first()
+        .filter(|e| e.tag == OID)
+        .ok_or_else(malformed)?;
+    if content_type.content == OID_ENVELOPED_DATA || content_type.content == OID_AUTH_ENVELOPED_DATA
+    {
+        return Ok(Cms::Encrypted);
+    }
+    if content_type.content != OID_SIGNED_DATA {
+        return Err("CMS data is neither signed nor encrypted".to_string());
+    }
+    let signed = fields
+        .get(1)
+        .filter(|e| e.tag == CONTEXT_0)
+        .and_then(|e| e.children())
+        .and_then(|children| children.into_iter().next())
+        .filter(|e| e.tag == SEQUENCE)
+        .ok_or_else(malformed)?;
+
+    // version, digestAlgorithms, encapContentInfo, [0] certificates, [1] crls, signerInfos
+    let fields = signed.children().ok_or_else(malformed)?;
+    let encapsulated = fields
+        .get(2)
+        .and_then(|e| e.children())
+        .ok_or_else(malformed)?;
+    let content = encapsulated
+        .get(1)
+        .filter(|e| e.tag == CONTEXT_0)
+        .and_then(|e| e.children())
+        .and_then(|children| children.into_iter().next())
+        .and_then(|e| e.octets());
+    let certificates = fields
+        .iter()
+        .skip(3)
+        .find(|e| e.tag == CONTEXT_0)
+        .and_then(|e| e.children())
+        .unwrap_or_default();
+    let signer = fields
+        .last()
+        .filter(|e| e.tag == SET)
+        .and_then(|e| e.children())
+        .and_then(|infos| infos.into_iter().next());
+    Ok(Cms::Signed(SignedData {
+        content,
+        certificates,
+        signer,
+    }))
+}
+
+impl SignedData<'_> {
+    /// Check the first signer's signature over `content`, with whichever certificate in the
+    /// message holds the key
+    fn verify(&self, content: &[u8]) -> (SmimeStatus, Option<String>) {
+        let fallback_signer = self.certificates.first().and_then(subject_name);
+        let unchecked = (SmimeStatus::SignatureUnchecked, fallback_signer.clone());
+        let Some(fields) = self.signer.and_then(|info| info.children()) else {
+            return unchecked;
+        };
+        // version, sid, digestAlgorithm, [0] signedAttrs, signatureAlgorithm, signature
+        let Some(hash) = fields.get(2).and_then(algorithm).and_then(Hash::from_oid) else {
+            return unchecked;
+        };
+        let attributes = fields.get(3).filter(|e| e.tag == CONTEXT_0);
+        let rest = &fields[if attributes.is_some() { 4 } else { 3 }..];
+        let (Some(signature_algorithm), Some(signature)) = (
+            rest.first().and_then(algorithm),
+            rest.get(1).filter(|e| e.tag == OCTET_STRING),
+        ) else {
+            return unchecked;
+        };
+        if signature_algorithm == OID_RSA_PSS {
+            return unchecked;
+        }
+
+        let digest = digest::digest(hash.algorithm(), content);
+        let signed: Vec<u8> = match attributes {
+            Some(attributes) => {
+                if message_digest(attributes).as_deref() != Some(digest.as_ref()) {
+                    return (SmimeStatus::SignatureInvalid, fallback_signer);
+                }
+                // Signed as the SET they are, not with the implicit [0] tag
+                let mut raw = attributes.raw.to_vec();
+                raw[0] = SET;
+                raw
+            }
+            None => content.to_vec(),
+        };
+
+        let mut checkable = false;
+        for certificate in &self.certificates {
+            let Some(verification) = public_key(certificate).and_then(|key| key.algorithm(hash))
+            else {
+                continue;
+            };
+            checkable = true;
+            let (algorithm, key) = verification;
+            if signature::UnparsedPublicKey::new(algorithm, key)
+                .verify(&signed, signature.content)
+                .is_ok()
+            {
+                return (SmimeStatus::SignatureValid, subject_name(certificate));
+            }
+        }
+        match checkable {
+            true => (SmimeStatus::SignatureInvalid, fallback_signer),
+            false => unchecked,
+        }
+    }
+}
+
+/// The `messageDigest` value among signed attributes
+fn message_digest(attributes: &Element) -> Option<Vec<u8>> {
+    attributes.children()?.into_iter().find_map(|attribute| {
+        let fields = attribute.children()?;
+        if fields.first()?.content != OID_MESSAGE_DIGEST {
+            return None;
+        }
+        fields.get(1)?.children()?.first()?.octets()
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Hash {
+    fn from_oid(oid: &[u8]) -> Option<Self> {
+        match oid {
+            OID_SHA1 => Some(Hash::Sha1),
+            OID_SHA256 => Some(Hash::Sha256),
+            OID_SHA384 => Some(Hash::Sha384),
+            OID_SHA512 => Some(Hash::Sha512),
+            _ => None,
+        }
+    }
+
+    fn algorithm(self) -> &'static digest::Algorithm {
+        match self {
+            Hash::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            Hash::Sha256 => &digest::SHA256,
+            Hash::Sha384 => &digest::SHA384,
+            Hash::Sha512 => &digest::SHA512,
+        }
+    }
+}
+
+enum PublicKey<'a> {
+    /// `RSAPublicKey` DER with the modulus size in bits
+    Rsa(&'a [u8], usize),
+    /// Uncompressed points
+    P256(&'a [u8]),
+    P384(&'a [u8]),
+}
+
+impl<'a> PublicKey<'a> {
+    fn algorithm(
+        &self,
+        hash: Hash,
+    ) -> Option<(&'static dyn signature::VerificationAlgorithm, &'a [u8])> {
+        use signature::*;
+        Some(match (self, hash) {
+            (PublicKey::Rsa(_, bits), _) if *bits < MIN_RSA_BITS => return None,
+            (PublicKey::Rsa(key, _), Hash::Sha1) => {
+                (&RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY, key)
+            }
+            (PublicKey::Rsa(key, _), Hash::Sha256) => (&RSA_PKCS1_2048_8192_SHA256, key),
+            (PublicKey::Rsa(key, _), Hash::Sha384) => (&RSA_PKCS1_2048_8192_SHA384, key),
+            (PublicKey::Rsa(key, _), Hash::Sha512) => (&RSA_PKCS1_2048_8192_SHA512, key),
+            (PublicKey::P256(key), Hash::Sha256) => (&ECDSA_P256_SHA256_ASN1, key),
+            (PublicKey::P256(key), Hash::Sha384) => (&ECDSA_P256_SHA384_ASN1, key),
+            (PublicKey::P384(key), Hash::Sha256) => (&ECDSA_P384_SHA256_ASN1, key),
+            (PublicKey::P384(key), Hash::Sha384) => (&ECDSA_P384_SHA384_ASN1, key),
+            _ => return None,
+        })
+    }
+}
+
+/// `tbsCertificate` fields from the serial number on: serialNumber, signature, issuer,
+/// validity, subject, subjectPublicKeyInfo, ...
+fn certificate_fields<'a>(certificate: &Element<'a>) -> Option<Vec<Element<'a>>> {
+    let tbs = certificate.children()?.into_iter().next()?;
+    let mut fields = tbs.children()?;
+    if fields.first()?.tag == CONTEXT_0 {
+        fields.remove(0);
+    }
+    (fields.first()?.tag == INTEGER).then_some(fields)
+}
+
+fn public_key<'a>(certificate: &Element<'a>) -> Option<PublicKey<'a>> {
+    let info = certificate_fields(certificate)?.get(5)?.children()?;
+    let algorithm_fields = info.first()?.children()?;
+    let key = info.get(1).filter(|e| e.tag == BIT_STRING)?.content;
+    // No unused bits in keys
+    let key = key.strip_prefix(&[0])?;
+    match algorithm_fields.first()?.content {
+        OID_RSA => {
+            let (rsa, _) = element(key, 0)?;
+            let modulus = rsa.children()?.into_iter().next()?.content;
+            let modulus = modulus.strip_prefix(&[0]).unwrap_or(modulus);
+            Some(PublicKey::Rsa(key, modulus.len() * 8))
+        }
+        OID_EC_PUBLIC_KEY => match algorithm_fields.get(1)?.content {
+            OID_P256 => Some(PublicKey::P256(key)),
+            OID_P384 => Some(PublicKey::P384(key)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The subject's email address, or else its common name
+fn subject_name(certificate: &Element) -> Option<String> {
+    let subject = certificate_fields(certificate)?.get(4)?.children()?;
+    let attributes: Vec<(&[u8], String)> = subject
+        .iter()
+        .filter_map(|set| set.children())
+        .flatten()
+        .filter_map(|attribute| {
+            let fields = attribute.children()?;
+            let value = fields.get(1)?;
+            let text = match value.tag {
+                // BMPString
+                0x1e => {
+                    let units: Vec<u16> = value
+                        .content
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect();
+                    String::from_utf16_lossy(&units)
+                }
+                _ => String::from_utf8_lossy(value.content).into_owned(),
+            };
+            Some((fields.first()?.content, text))
+        })
+        .collect();
+    [OID_EMAIL_ADDRESS, OID_COMMON_NAME].iter().find_map(|oid| {
+        attributes
+            .iter()
+            .find(|(attribute, _)| attribute == oid)
+            .map(|(_, text)| text.clone())
+    })
+}
+
+/// The OID of an `AlgorithmIdentifier`
+fn algorithm<'a>(identifier: &Element<'a>) -> Option<&'a [u8]> {
+    identifier
+        .children()?
+        .first()
+        .filter(|e| e.tag == OID)
+        .map(|e| e.content)
+}
+
+/// One DER or BER element
+#[derive(Debug, Clone, Copy)]
+struct Element<'a> {
+    tag: u8,
+    content: &'a [u8],
+    /// Header and content
+    raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    fn children(&self) -> Option<Vec<Element<'a>>> {
+        if self.tag & 0x20 == 0 {
+            return None;
+        }
+        let mut children = Vec::new();
+        let mut rest = self.content;
+        while !rest.is_empty() {
+            let (child, after) = element(rest, 0)?;
+            children.push(child);
+            rest = after;
+        }
+        Some(children)
+    }
+
+    /// Content of an OCTET STRING, joined from its segments when constructed (BER)
+    fn octets(&self) -> Option<Vec<u8>> {
+        match self.tag {
+            OCTET_STRING => Some(self.content.to_vec()),
+            CONSTRUCTED_OCTET_STRING => {
+                let mut joined = Vec::new();
+                for segment in self.children()? {
+                    joined.extend(segment.octets()?);
+                }
+                Some(joined)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The element at the start of `data` and what follows it
+fn element(data: &[u8], depth: usize) -> Option<(Element<'_>, &[u8])> {
+    let tag = *data.first()?;
+    // High tag numbers don't occur in CMS
+    if tag & 0x1f == 0x1f || depth > MAX_DER_DEPTH {
+        return None;
+    }
+    let length = *data.get(1)?;
+    let (header, len) = match length {
+        // Indefinite length (BER): constructed content up to an end-of-contents marker
+        0x80 => {
+            if tag & 0x20 == 0 {
+                return None;
+            }
+            let mut rest = data.get(2..)?;
+            let mut len = 0;
+            while !rest.starts_with(&[0, 0]) {
+                let (child, after) = element(rest, depth + 1)?;
+                len += child.raw.len();
+                rest = after;
+            }
+            let raw = data.get(..2 + len + 2)?;
+            let element = Element {
+                tag,
+                content: &raw[2..2 + len],
+                raw,
+            };
+            return Some((element, &data[raw.len()..]));
+        }
+        short if short < 0x80 => (2, short as usize),
+        long => {
+            let count = (long & 0x7f) as usize;
+            if count > 4 {
+                return None;
+            }
+            let bytes = data.get(2..2 + count)?;
+            let len = bytes.iter().fold(0usize, |len, &b| len << 8 | b as usize);
+            (2 + count, len)
+        }
+    };
+    let raw = data.get(..header.checked_add(len)?)?;
+    let element = Element {
+        tag,
+        content: &raw[header..],
+        raw,
+    };
+    Some((element, &data[raw.len()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    fn parse(data: &[u8]) -> Message {
+        parse_bytes(MessageFormat::Eml, data, &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn signed_blobs_are_unwrapped_and_checked() {
+        let message = parse(include_bytes!("../../tests/fixtures/smime/signed-stub.eml"));
+        let smime = message.smime.as_ref().unwrap();
+        assert_eq!(smime.status, SmimeStatus::SignatureValid);
+        assert_eq!(smime.signer.as_deref(), Some("ann@example.com"));
+        assert_eq!(
+            message.body_text.as_deref().map(str::trim),
+            Some("The quarterly figures are final.")
+        );
+        assert_eq!(
+            message.from.as_ref().unwrap().name.as_deref(),
+            Some("Ann Signer")
+        );
+        let stub = smime.stub.as_ref().unwrap();
+        assert!(stub
+            .body_text
+            .as_deref()
+            .unwrap()
+            .contains("wrapped by the mail gateway"));
+
+        // One changed letter in the content
+        let mut tampered = include_bytes!("../../tests/fixtures/smime/signed.p7m").to_vec();
+        let at = tampered
+            .windows(9)
+            .position(|window| window == b"quarterly")
+            .unwrap();
+        tampered[at] = b'Q';
+        let Ok(Blob::Signed { status, .. }) = read(&tampered) else {
+            panic!("not read as signed data");
+        };
+        assert_eq!(status, SmimeStatus::SignatureInvalid);
+    }
+
+    #[test]
+    fn detached_signatures_and_encrypted_content() {
+        // Outlook keeps signed messages as a multipart/signed entity, here with LF line ends
+        let message = parse(include_bytes!(
+            "../../tests/fixtures/smime/detached-stub.eml"
+        ));
+        let smime = message.smime.as_ref().unwrap();
+        assert_eq!(smime.status, SmimeStatus::SignatureValid);
+        assert_eq!(message.subject.as_deref(), Some("Signed figures"));
+        assert!(message.attachments.iter().all(|a| a.hidden));
+
+        let message = parse(include_bytes!("../../tests/fixtures/smime/encrypted.eml"));
+        let smime = message.smime.as_ref().unwrap();
+        assert_eq!(smime.status, SmimeStatus::Encrypted);
+        assert!(smime.stub.is_none());
+        assert_eq!(message.subject.as_deref(), Some("Encrypted figures"));
+        assert_eq!(message.warnings[0].code, "warning.smime_encrypted");
+    }
+}
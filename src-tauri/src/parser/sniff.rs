@@ -0,0 +1,676 @@
+use super::storage::CompoundStorage;
+use std::io::Cursor;
+
+/// Generic type reported by mail clients when they don't know better
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const PPTX: &str = "application/vnd.openxmlformats-officedocument.presentationml.presentation";
+
+/// Known extensions and their MIME types; the first extension listed for a type is preferred
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("jpe", "image/jpeg"),
+    ("jfif", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("bmp", "image/bmp"),
+    ("webp", "image/webp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("ico", "image/x-icon"),
+    ("svg", "image/svg+xml"),
+    ("docx", DOCX),
+    ("docm", DOCX),
+    ("dotx", DOCX),
+    ("xlsx", XLSX),
+    ("xlsm", XLSX),
+    ("xltx", XLSX),
+    ("pptx", PPTX),
+    ("pptm", PPTX),
+    ("potx", PPTX),
+    ("ppsx", PPTX),
+    ("doc", "application/msword"),
+    ("dot", "application/msword"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlt", "application/vnd.ms-excel"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pps", "application/vnd.ms-powerpoint"),
+    ("msg", "application/vnd.ms-outlook"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    ("rtf", "application/rtf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("xml", "application/xml"),
+    ("eml", "message/rfc822"),
+    ("csv", "text/csv"),
+    ("ics", "text/calendar"),
+    ("vcf", "text/vcard"),
+    ("json", "application/json"),
+];
+
+/// Extensions of files that run code when opened
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "com", "scr", "pif", "cpl", "msi", "msp", "dll", "bat", "cmd", "ps1", "psm1", "vbs",
+    "vbe", "js", "jse", "wsf", "wsh", "hta", "lnk", "reg", "jar", "app", "command", "sh", "run",
+    "appimage", "deb", "rpm", "pkg", "iso", "img",
+];
+
+/// Extensions that carry no type information and may always be replaced
+const MEANINGLESS_EXTENSIONS: &[&str] = &["dat", "bin", "tmp", "att", "mime"];
+
+/// Result of comparing an attachment's declared type with its content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sniffed {
+    pub detected_mime: Option<&'static str>,
+    pub suggested_filename: String,
+}
+
+/// MIME type registered for a file extension (case-insensitive)
+pub fn mime_for_extension(ext: &str) -> Option<&'static str> {
+    let ext = ext.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// Preferred extension for a MIME type
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    let mime = essence(mime);
+    EXTENSIONS
+        .iter()
+        .find(|(_, known)| *known == mime)
+        .map(|(ext, _)| *ext)
+}
+
+/// Lowercased type/subtype without parameters
+fn essence(mime: &str) -> String {
+    mime.split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Identify content by its magic bytes
+pub fn detect(data: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| data.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    if starts(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if starts(b"\xFF\xD8\xFF") {
+        return Some("image/jpeg");
+    }
+    if starts(b"GIF87a") || starts(b"GIF89a") {
+        return Some("image/gif");
+    }
+    if starts(b"BM") && data.len() >= 26 && at(6, b"\0\0\0\0") {
+        return Some("image/bmp");
+    }
+    if starts(b"II*\0") || starts(b"MM\0*") {
+        return Some("image/tiff");
+    }
+    if starts(b"\0\0\x01\0") && data.len() >= 22 {
+        return Some("image/x-icon");
+    }
+    if starts(b"RIFF") {
+        if at(8, b"WEBP") {
+            return Some("image/webp");
+        }
+        if at(8, b"WAVE") {
+            return Some("audio/wav");
+        }
+        if at(8, b"AVI ") {
+            return Some("video/x-msvideo");
+        }
+    }
+    if at(4, b"ftyp") {
+        return Some(match data.get(8..12) {
+            Some(b"M4A ") | Some(b"M4B ") => "audio/mp4",
+            Some(b"qt  ") => "video/quicktime",
+            _ => "video/mp4",
+        });
+    }
+    if starts(b"ID3")
+        || (data.len() > 2 && data[0] == 0xFF && matches!(data[1], 0xFB | 0xF3 | 0xF2))
+    {
+        return Some("audio/mpeg");
+    }
+    if starts(b"OggS") {
+        return Some("audio/ogg");
+    }
+    if starts(b"fLaC") {
+        return Some("audio/flac");
+    }
+    if starts(b"MThd") {
+        return Some("audio/midi");
+    }
+    if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") {
+        return Some(detect_zip(data));
+    }
+    if starts(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+        return Some(detect_compound(data));
+    }
+    if starts(b"{\\rtf") {
+        return Some("application/rtf");
+    }
+    if starts(b"\x1F\x8B") {
+        return Some("application/gzip");
+    }
+    if starts(b"7z\xBC\xAF\x27\x1C") {
+        return Some("application/x-7z-compressed");
+    }
+    if starts(b"Rar!\x1A\x07") {
+        return Some("application/vnd.rar");
+    }
+    detect_text(data)
+}
+
+/// Distinguish Office Open XML and OpenDocument files from plain zip archives
+fn detect_zip(data: &[u8]) -> &'static str {
+    // OpenDocument stores an uncompressed `mimetype` entry first
+    if data.get(30..38) == Some(b"mimetype") {
+        let name_len = u16::from_le_bytes([data[26], data[27]]) as usize;
+        let extra_len = data
+            .get(28..30)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .unwrap_or(0);
+        let start = 30 + name_len + extra_len;
+        if let Some(content) = data.get(start..(start + 64).min(data.len())) {
+            for mime in [
+                "application/vnd.oasis.opendocument.text",
+                "application/vnd.oasis.opendocument.spreadsheet",
+                "application/vnd.oasis.opendocument.presentation",
+            ] {
+                if content.starts_with(mime.as_bytes()) {
+                    return mime;
+                }
+            }
+        }
+    }
+
+    let names = zip_entry_names(data);
+    if names.iter().any(|name| name == "[Content_Types].xml") {
+        if names.iter().any(|name| name.starts_with("word/")) {
+            return DOCX;
+        }
+        if names.iter().any(|name| name.starts_with("xl/")) {
+            return XLSX;
+        }
+        if names.iter().any(|name| name.starts_with("ppt/")) {
+            return PPTX;
+        }
+    }
+    "application/zip"
+}
+
+/// Entry names from the zip central directory
+fn zip_entry_names(data: &[u8]) -> Vec<String> {
+    let read_u16 = |pos: usize| {
+        data.get(pos..pos + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let read_u32 = |pos: usize| {
+        data.get(pos..pos + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    // The end record sits within the last 64 KiB (its comment is at most that long)
+    let search_from = data.len().saturating_sub(65_557);
+    let Some(eocd) = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| data[pos..].starts_with(b"PK\x05\x06"))
+    else {
+        return Vec::new();
+    };
+
+    let (Some(count), Some(offset)) = (read_u16(eocd + 10), read_u32(eocd + 16)) else {
+        return Vec::new();
+    };
+
+    let mut names = Vec::new();
+    let mut pos = offset as usize;
+    for _ in 0..count {
+        if data.get(pos..pos + 4) != Some(b"PK\x01\x02") {
+            break;
+        }
+        let (Some(name_len), Some(extra_len), Some(comment_len)) =
+            (read_u16(pos + 28), read_u16(pos + 30), read_u16(pos + 32))
+        else {
+            break;
+        };
+        let start = pos + 46;
+        let Some(name) = data.get(start..start + name_len as usize) else {
+            break;
+        };
+        names.push(String::from_utf8_lossy(name).into_owned());
+        pos = start + name_len as usize + extra_len as usize + comment_len as usize;
+    }
+    names
+}
+
+/// Tell Outlook messages apart from legacy Word, Excel and PowerPoint files
+fn detect_compound(data: &[u8]) -> &'static str {
+    let Ok(storage) = CompoundStorage::open(Cursor::new(data)) else {
+        return OCTET_STREAM;
+    };
+    let names: Vec<String> = storage
+        .children("/")
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    let has = |name: &str| names.iter().any(|n| n == name);
+
+    if has("__properties_version1.0") || has("__nameid_version1.0") {
+        "application/vnd.ms-outlook"
+    } else if has("WordDocument") {
+        "application/msword"
+    } else if has("Workbook") || has("Book") {
+        "application/vnd.ms-excel"
+    } else if has("PowerPoint Document") {
+        "application/vnd.ms-powerpoint"
+    } else {
+        OCTET_STREAM
+    }
+}
+
+/// HTML, XML, message/rfc822 or plain text when the sample looks like text
+fn detect_text(data: &[u8]) -> Option<&'static str> {
+    let sample = &data[..data.len().min(8192)];
+    if sample.is_empty() {
+        return None;
+    }
+    if sample.starts_with(b"\xFF\xFE") || sample.starts_with(b"\xFE\xFF") {
+        return Some("text/plain");
+    }
+    if sample.contains(&0) {
+        return None;
+    }
+
+    // The sample may end in the middle of a UTF-8 sequence
+    let text = match std::str::from_utf8(sample) {
+        Ok(text) => text,
+        Err(e) if e.valid_up_to() + 4 >= sample.len() => {
+            std::str::from_utf8(&sample[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => {
+            // 8-bit legacy text is fine as long as control characters are rare
+            let controls = sample
+                .iter()
+                .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C))
+                .count();
+            return (controls * 100 < sample.len()).then_some("text/plain");
+        }
+    };
+
+    let trimmed = text.trim_start_matches('\u{FEFF}').trim_start();
+    let lower: String = trimmed
+        .chars()
+        .take(64)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        return Some("text/html");
+    }
+    if lower.starts_with("<?xml") {
+        return Some(if lower.contains("<svg") || text.contains("<svg") {
+            "image/svg+xml"
+        } else {
+            "application/xml"
+        });
+    }
+    if lower.starts_with("<svg") {
+        return Some("image/svg+xml");
+    }
+    if looks_like_message(trimmed) {
+        return Some("message/rfc822");
+    }
+
+    let controls = text
+        .chars()
+        .filter(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\u{0C}'))
+        .count();
+    (controls * 100 < text.len().max(1)).then_some("text/plain")
+}
+
+/// A header block containing the fields every RFC 5322 message carries
+fn looks_like_message(text: &str) -> bool {
+    let mut fields = 0;
+    for line in text.lines().take(50) {
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+        let Some((name, _)) = line.split_once(':') else {
+            return false;
+        };
+        if name.is_empty() || name.contains(' ') {
+            return false;
+        }
+        if [
+            "from",
+            "date",
+            "subject",
+            "received",
+            "message-id",
+            "mime-version",
+        ]
+        .contains(&name.to_ascii_lowercase().as_str())
+        {
+            fields += 1;
+        }
+    }
+    fields >= 2
+}
+
+/// Detections that only identify a container or a text encoding, not a specific file type
+fn is_weak(mime: &str) -> bool {
+    matches!(
+        mime,
+        "text/plain"
+            | "text/html"
+            | "application/xml"
+            | "message/rfc822"
+            | "application/zip"
+            | OCTET_STREAM
+    )
+}
+
+/// Compare name, declared type and content, and suggest a file name whose extension
+/// matches what the content actually is
+pub fn sniff(file_name: &str, declared_mime: Option<&str>, data: &[u8]) -> Sniffed {
+    let detected = detect(data);
+    let declared = declared_mime
+        .map(essence)
+        .filter(|mime| !mime.is_empty() && mime != OCTET_STREAM);
+
+    let (stem, ext) = split_extension(file_name);
+    let ext_mime = ext.and_then(mime_for_extension);
+
+    let wanted_ext = match detected {
+        // A specific detection overrides a missing or contradicting extension
+        Some(mime) if !is_weak(mime) => {
+            if ext_mime == Some(mime) {
+                None
+            } else {
+                extension_for_mime(mime)
+            }
+        }
+        // Weak detections only fill in a missing extension, preferring the declared type
+        weak => {
+            if ext.is_some() {
+                None
+            } else {
+                declared
+                    .as_deref()
+                    .and_then(extension_for_mime)
+                    .or_else(|| weak.and_then(extension_for_mime))
+            }
+        }
+    };
+
+    let suggested_filename = match (wanted_ext, ext) {
+        (None, _) => file_name.to_string(),
+        // Replace a known (but wrong) or meaningless extension, otherwise append
+        (Some(new_ext), Some(old_ext))
+            if mime_for_extension(old_ext).is_some()
+                || MEANINGLESS_EXTENSIONS.contains(&old_ext.to_ascii_lowercase().as_str()) =>
+        {
+            format!("{}.{}", stem, new_ext)
+        }
+        (Some(new_ext), _) => format!("{}.{}", file_name, new_ext),
+    };
+
+    Sniffed {
+        detected_mime: detected,
+        suggested_filename,
+    }
+}
+
+/// Whether an attachment runs code when opened, by its name or its content (PE, ELF and
+/// Mach-O binaries, scripts with a shebang)
+pub fn is_executable(file_name: &str, data: &[u8]) -> bool {
+    let name = file_name.trim_end_matches(['.', ' ']).to_ascii_lowercase();
+    let by_name = name
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| EXECUTABLE_EXTENSIONS.contains(&ext));
+    let by_content = data.starts_with(b"MZ")
+        || data.starts_with(b"\x7fELF")
+        || data.starts_with(b"#!")
+        || [
+            [0xFE, 0xED, 0xFA, 0xCE],
+            [0xFE, 0xED, 0xFA, 0xCF],
+            [0xCE, 0xFA, 0xED, 0xFE],
+            [0xCF, 0xFA, 0xED, 0xFE],
+        ]
+        .iter()
+        .any(|magic| data.starts_with(magic));
+    by_name || by_content
+}
+
+/// Split `name.ext`; dot files and names without a dot have no extension
+fn split_extension(file_name: &str) -> (&str, Option<&str>) {
+    match file_name.rfind('.') {
+        Some(pos) if pos > 0 && pos + 1 < file_name.len() => {
+            let ext = &file_name[pos + 1..];
+            if ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+                (&file_name[..pos], Some(ext))
+            } else {
+                (file_name, None)
+            }
+        }
+        _ => (file_name, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip(names: &[&str]) -> Vec<u8> {
+        let mut zip = crate::zip::ZipWriter::new(Vec::new());
+        for name in names {
+            zip.add_file(name, b"<x/>").unwrap();
+        }
+        zip.finish().unwrap()
+    }
+
+    /// A compound file with one empty stream at the root
+    fn compound(stream: &str) -> Vec<u8> {
+        let mut file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        file.create_stream(format!("/{stream}"))
+            .unwrap()
+            .write_all(b"x")
+            .unwrap();
+        file.into_inner().into_inner()
+    }
+
+    /// OpenDocument archive: the stored `mimetype` entry comes first
+    fn open_document(mime: &str) -> Vec<u8> {
+        let mut data = b"PK\x03\x04".to_vec();
+        data.extend([0; 22]);
+        data.extend(8u16.to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data.extend(b"mimetype");
+        data.extend(mime.as_bytes());
+        data
+    }
+
+    #[test]
+    fn content_is_identified_by_its_magic_bytes() {
+        let riff = |kind: &[u8]| [b"RIFF\0\0\0\0" as &[u8], kind].concat();
+        let ftyp = |brand: &[u8]| [b"\0\0\0\x18ftyp" as &[u8], brand].concat();
+        let bmp = [b"BM\0\0\0\0\0\0\0\0" as &[u8], &[0; 16]].concat();
+        let ico = [b"\0\0\x01\0" as &[u8], &[0; 18]].concat();
+        let cases: Vec<(Vec<u8>, Option<&str>)> = vec![
+            (b"%PDF-1.7".to_vec(), Some("application/pdf")),
+            (b"\x89PNG\r\n\x1a\n".to_vec(), Some("image/png")),
+            (b"\xFF\xD8\xFF\xE0".to_vec(), Some("image/jpeg")),
+            (b"GIF89a".to_vec(), Some("image/gif")),
+            (bmp, Some("image/bmp")),
+            (b"II*\0".to_vec(), Some("image/tiff")),
+            (ico, Some("image/x-icon")),
+            (riff(b"WEBP"), Some("image/webp")),
+            (riff(b"WAVE"), Some("audio/wav")),
+            (riff(b"AVI "), Some("video/x-msvideo")),
+            (ftyp(b"isom"), Some("video/mp4")),
+            (ftyp(b"M4A "), Some("audio/mp4")),
+            (ftyp(b"qt  "), Some("video/quicktime")),
+            (b"ID3\x04".to_vec(), Some("audio/mpeg")),
+            (b"\xFF\xFB\x90".to_vec(), Some("audio/mpeg")),
+            (b"OggS".to_vec(), Some("audio/ogg")),
+            (b"fLaC".to_vec(), Some("audio/flac")),
+            (b"MThd".to_vec(), Some("audio/midi")),
+            (zip(&["a.txt"]), Some("application/zip")),
+            (
+                zip(&["[Content_Types].xml", "word/document.xml"]),
+                Some(DOCX),
+            ),
+            (zip(&["[Content_Types].xml", "xl/workbook.xml"]), Some(XLSX)),
+            (
+                zip(&["[Content_Types].xml", "ppt/presentation.xml"]),
+                Some(PPTX),
+            ),
+            (
+                open_document("application/vnd.oasis.opendocument.spreadsheet"),
+                Some("application/vnd.oasis.opendocument.spreadsheet"),
+            ),
+            (
+                compound("__properties_version1.0"),
+                Some("application/vnd.ms-outlook"),
+            ),
+            (compound("WordDocument"), Some("application/msword")),
+            (compound("Workbook"), Some("application/vnd.ms-excel")),
+            (
+                compound("PowerPoint Document"),
+                Some("application/vnd.ms-powerpoint"),
+            ),
+            (compound("Other"), Some(OCTET_STREAM)),
+            (b"{\\rtf1\\ansi}".to_vec(), Some("application/rtf")),
+            (b"\x1F\x8B\x08".to_vec(), Some("application/gzip")),
+            (
+                b"7z\xBC\xAF\x27\x1C".to_vec(),
+                Some("application/x-7z-compressed"),
+            ),
+            (b"Rar!\x1A\x07\x01".to_vec(), Some("application/vnd.rar")),
+            (b"<!DOCTYPE html><p>".to_vec(), Some("text/html")),
+            (
+                b"<?xml version=\"1.0\"?><svg/>".to_vec(),
+                Some("image/svg+xml"),
+            ),
+            (
+                b"<?xml version=\"1.0\"?><a/>".to_vec(),
+                Some("application/xml"),
+            ),
+            (
+                b"\xEF\xBB\xBF<svg xmlns=\"x\"/>".to_vec(),
+                Some("image/svg+xml"),
+            ),
+            (
+                b"Received: by mx\r\nFrom: a@example.com\r\nSubject: Hi\r\n\r\nbody".to_vec(),
+                Some("message/rfc822"),
+            ),
+            // One header field isn't enough to tell a message from a note
+            (b"Subject: Hi\n\nbody".to_vec(), Some("text/plain")),
+            (b"Quarterly figures\n".to_vec(), Some("text/plain")),
+            (b"\xFF\xFEh\0i\0".to_vec(), Some("text/plain")),
+            (b"Gr\xFC\xDFe".to_vec(), Some("text/plain")),
+            (b"\x01\x02\x03\x04\x05x".to_vec(), None),
+            (b"a\0b".to_vec(), None),
+            // Too short for the magic they start with
+            (b"BM".to_vec(), Some("text/plain")),
+            (b"\0\0\x01\0".to_vec(), None),
+            (b"%PDF".to_vec(), Some("text/plain")),
+            (Vec::new(), None),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(
+                detect(&data),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(&data)
+            );
+        }
+    }
+
+    #[test]
+    fn spoofed_and_missing_extensions_are_corrected() {
+        let png = b"\x89PNG\r\n\x1a\n";
+        let eml = b"From: a@example.com\r\nDate: Tue, 3 Mar 2026 09:15:00 +0100\r\n\r\nbody";
+        for (name, declared, data, suggested) in [
+            ("photo.png", Some("image/png"), &png[..], "photo.png"),
+            ("invoice.pdf", Some("application/pdf"), png, "invoice.png"),
+            ("photo", None, png, "photo.png"),
+            ("photo.dat", Some(OCTET_STREAM), png, "photo.png"),
+            ("photo.backup", None, png, "photo.backup.png"),
+            // Weak detections keep an extension the sender gave
+            ("notes.md", None, b"plain text", "notes.md"),
+            (
+                "notes",
+                Some("text/csv; charset=utf-8"),
+                b"a,b",
+                "notes.csv",
+            ),
+            ("notes", None, b"plain text", "notes.txt"),
+            ("forwarded", None, eml, "forwarded.eml"),
+            ("forwarded.txt", None, eml, "forwarded.txt"),
+            ("unknown", None, b"\x01\x02\x03\x04\x05x", "unknown"),
+        ] {
+            assert_eq!(
+                sniff(name, declared, data).suggested_filename,
+                suggested,
+                "{name}"
+            );
+        }
+        assert_eq!(
+            sniff("forwarded", None, eml).detected_mime,
+            Some("message/rfc822")
+        );
+        assert_eq!(mime_for_extension("JPG"), Some("image/jpeg"));
+        assert_eq!(extension_for_mime("Image/JPEG; q=1"), Some("jpg"));
+    }
+
+    #[test]
+    fn executables_are_recognised_by_name_or_content() {
+        assert!(is_executable("setup.EXE", b""));
+        assert!(is_executable("invoice.pdf.exe. ", b""));
+        assert!(is_executable("invoice.pdf", b"MZ\x90\0"));
+        assert!(is_executable("run", b"#!/bin/sh\n"));
+        assert!(is_executable("tool", b"\xCF\xFA\xED\xFE"));
+        assert!(!is_executable("invoice.pdf", b"%PDF-1.7"));
+    }
+}
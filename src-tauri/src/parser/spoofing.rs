@@ -0,0 +1,395 @@
+//! Sender spoofing heuristics: a display name showing another address or a well-known
+//! brand the address doesn't belong to, replies going to another domain, and senders
+//! whose domain imitates the recipient's own. Each check can be switched off in settings.
+
+use super::{headers, idn, Address, Message};
+use serde::{Deserialize, Serialize};
+
+/// Brands phishing mail commonly pretends to be, with the domains they send from
+const KNOWN_BRANDS: &[(&str, &[&str])] = &[
+    (
+        "Microsoft",
+        &[
+            "microsoft.com",
+            "office.com",
+            "office365.com",
+            "outlook.com",
+            "live.com",
+            "microsoftonline.com",
+            "sharepoint.com",
+        ],
+    ),
+    (
+        "Office 365",
+        &["microsoft.com", "office.com", "office365.com"],
+    ),
+    ("Outlook", &["microsoft.com", "outlook.com"]),
+    (
+        "OneDrive",
+        &["microsoft.com", "onedrive.com", "sharepoint.com"],
+    ),
+    ("Apple", &["apple.com", "icloud.com"]),
+    ("iCloud", &["apple.com", "icloud.com"]),
+    ("Google", &["google.com", "gmail.com", "youtube.com"]),
+    (
+        "Amazon",
+        &["amazon.com", "amazon.de", "amazon.co.uk", "amazonses.com"],
+    ),
+    ("PayPal", &["paypal.com", "paypal.de"]),
+    ("Netflix", &["netflix.com"]),
+    ("DocuSign", &["docusign.com", "docusign.net"]),
+    ("Dropbox", &["dropbox.com", "dropboxmail.com"]),
+    ("Adobe", &["adobe.com", "adobesign.com"]),
+    ("LinkedIn", &["linkedin.com"]),
+    (
+        "Facebook",
+        &["facebook.com", "facebookmail.com", "meta.com"],
+    ),
+    ("DHL", &["dhl.com", "dhl.de"]),
+    ("FedEx", &["fedex.com"]),
+    ("UPS", &["ups.com"]),
+];
+
+/// Shortest domain name (before the top-level domain) compared for lookalikes; one edit
+/// turns too many short names into others
+const MIN_LOOKALIKE_LEN: usize = 5;
+
+/// Which checks run on opened messages; all of them by default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SenderChecks {
+    /// The display name contains an address other than the sender's
+    pub display_name_address: bool,
+    /// The display name is a brand in [`KNOWN_BRANDS`] the sender's domain isn't one of
+    pub brand_name: bool,
+    /// Replies go to another domain than the sender's
+    pub reply_to: bool,
+    /// The sender's domain imitates a recipient's one
+    pub lookalike_domain: bool,
+}
+
+impl Default for SenderChecks {
+    fn default() -> Self {
+        Self {
+            display_name_address: true,
+            brand_name: true,
+            reply_to: true,
+            lookalike_domain: true,
+        }
+    }
+}
+
+/// A reason to distrust the sender, for the warning banner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SenderFlag {
+    /// `"ceo@acme.com" <someone@elsewhere.test>`
+    DisplayNameAddress { shown: String, actual: String },
+    /// `Microsoft Support <totally-not-ms@gmail.com>`
+    BrandName { brand: String, domain: String },
+    /// `Reply-To` on another domain
+    ReplyTo { reply_to: String, from: String },
+    /// `examp1e.com` writing to `example.com`
+    LookalikeDomain { domain: String, resembles: String },
+}
+
+/// Run the enabled checks on the sender of `message`
+pub fn analyze(message: &Message, checks: &SenderChecks) -> Vec<SenderFlag> {
+    let Some(from) = &message.from else {
+        return Vec::new();
+    };
+    let Some(email) = from.email.as_deref().map(str::to_lowercase) else {
+        return Vec::new();
+    };
+    let Some(domain) = domain_of(&email) else {
+        return Vec::new();
+    };
+    let name = from.name.as_deref().unwrap_or_default();
+
+    let mut flags = Vec::new();
+    if checks.display_name_address {
+        if let Some(shown) = shown_addresses(name).find(|shown| *shown != email) {
+            flags.push(SenderFlag::DisplayNameAddress {
+                shown,
+                actual: email.clone(),
+            });
+        }
+    }
+    if checks.brand_name {
+        if let Some(brand) = impersonated_brand(name, &domain) {
+            flags.push(SenderFlag::BrandName {
+                brand: brand.to_string(),
+                domain: domain.clone(),
+            });
+        }
+    }
+    if checks.reply_to {
+        let reply_to = header_addresses(message, &["Reply-To"])
+            .into_iter()
+            .find(|reply_to| domain_of(reply_to).is_some_and(|d| !same_site(&d, &domain)));
+        if let Some(reply_to) = reply_to {
+            flags.push(SenderFlag::ReplyTo {
+                reply_to,
+                from: email.clone(),
+            });
+        }
+    }
+    if checks.lookalike_domain {
+        let recipients = message
+            .to
+            .iter()
+            .filter_map(|address| address.email.as_deref().map(str::to_lowercase))
+            .chain(header_addresses(
+                message,
+                &["Delivered-To", "X-Original-To"],
+            ));
+        let mut seen = Vec::new();
+        for recipient in recipients.filter_map(|recipient| domain_of(&recipient)) {
+            if seen.contains(&recipient) {
+                continue;
+            }
+            if is_lookalike(&domain, &recipient) {
+                flags.push(SenderFlag::LookalikeDomain {
+                    domain: domain.clone(),
+                    resembles: recipient.clone(),
+                });
+                break;
+            }
+            seen.push(recipient);
+        }
+    }
+    flags
+}
+
+/// Unicode form of an address's domain, lowercase
+fn domain_of(email: &str) -> Option<String> {
+    idn::email_host(email).map(|host| host.display_host.to_lowercase())
+}
+
+/// Lowercase email addresses of some headers
+fn header_addresses(message: &Message, names: &[&str]) -> Vec<String> {
+    message
+        .headers
+        .iter()
+        .filter(|(name, _)| names.iter().any(|wanted| name.eq_ignore_ascii_case(wanted)))
+        .flat_map(|(_, value)| headers::address_list(value))
+        .filter_map(|Address { email, .. }| email.map(|email| email.to_lowercase()))
+        .collect()
+}
+
+/// Things looking like addresses in a display name
+fn shown_addresses(name: &str) -> impl Iterator<Item = String> + '_ {
+    name.split(|c: char| c.is_whitespace() || "<>()[]\"',;:".contains(c))
+        .filter(|word| {
+            word.split_once('@')
+                .is_some_and(|(local, host)| !local.is_empty() && host.contains('.'))
+        })
+        .map(|word| word.trim_end_matches('.').to_lowercase())
+}
+
+/// The brand a display name claims to be when `domain` isn't one of the brand's; names
+/// spelled with lookalike letters count
+fn impersonated_brand(name: &str, domain: &str) -> Option<&'static str> {
+    let words: Vec<String> = idn::skeleton(name)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    let domain = idn::skeleton(domain);
+    KNOWN_BRANDS
+        .iter()
+        .find(|(brand, domains)| {
+            let brand: Vec<String> = brand
+                .to_lowercase()
+                .split(' ')
+                .map(str::to_string)
+                .collect();
+            words
+                .windows(brand.len())
+                .any(|window| window == brand.as_slice())
+                && !domains.iter().any(|own| is_within(&domain, own))
+        })
+        .map(|(brand, _)| *brand)
+}
+
+/// `host` is `domain` or one of its subdomains
+fn is_within(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// The registrable part of a host, roughly: the last two labels, or three for second-level
+/// country domains like `co.uk` and `com.au`
+fn base_domain(host: &str) -> &str {
+    let labels: Vec<&str> = host.rsplitn(4, '.').collect();
+    let count = match labels.as_slice() {
+        [tld, second, _, ..] if tld.len() == 2 && second.len() <= 3 => 3,
+        _ => 2,
+    };
+    let keep: usize = labels.iter().take(count).map(|label| label.len() + 1).sum();
+    &host[host.len().saturating_sub(keep - 1)..]
+}
+
+fn same_site(a: &str, b: &str) -> bool {
+    base_domain(a) == base_domain(b)
+}
+
+/// Whether `domain` is a different domain that reads like `own`: the same once lookalike
+/// letters (`rn` for `m`, Cyrillic `а` for `a`, ...) are folded, or one typo away
+fn is_lookalike(domain: &str, own: &str) -> bool {
+    let (domain, own) = (base_domain(domain), base_domain(own));
+    if domain == own {
+        return false;
+    }
+    let (domain, own) = (fold(domain), fold(own));
+    if domain == own {
+        return true;
+    }
+    let name = |base: &str| -> String { base.split('.').next().unwrap_or_default().to_string() };
+    let (name_a, name_b) = (name(&domain), name(&own));
+    name_a.chars().count().min(name_b.chars().count()) >= MIN_LOOKALIKE_LEN
+        && domain[name_a.len()..] == own[name_b.len()..]
+        && within_one_edit(&name_a, &name_b)
+}
+
+/// [`idn::skeleton`] plus the ASCII lookalikes
+fn fold(domain: &str) -> String {
+    idn::skeleton(domain)
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .replace('0', "o")
+        .replace('1', "l")
+}
+
+/// One character inserted, removed, replaced, or two neighbours swapped
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    if short.len() == long.len() {
+        let swapped = prefix + 1 < short.len()
+            && short[prefix] == long[prefix + 1]
+            && short[prefix + 1] == long[prefix]
+            && short[prefix + 2..] == long[prefix + 2..];
+        swapped || short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    fn flags(data: &[u8], checks: &SenderChecks) -> Vec<SenderFlag> {
+        let message = parse_bytes(MessageFormat::Eml, data, &ParseOptions::default()).unwrap();
+        analyze(&message, checks)
+    }
+
+    #[test]
+    fn spoofed_senders_are_flagged() {
+        let checks = SenderChecks::default();
+        assert_eq!(
+            flags(
+                include_bytes!("../../tests/fixtures/spoofing/brand.eml"),
+                &checks
+            ),
+            [
+                SenderFlag::BrandName {
+                    brand: "Microsoft".to_string(),
+                    domain: "gmail.com".to_string(),
+                },
+                SenderFlag::ReplyTo {
+                    reply_to: "recovery-desk@protonmail.com".to_string(),
+                    from: "totally-not-ms@gmail.com".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            flags(
+                include_bytes!("../../tests/fixtures/spoofing/display-address.eml"),
+                &checks
+            ),
+            [SenderFlag::DisplayNameAddress {
+                shown: "ceo@example.com".to_string(),
+                actual: "ceo.office@mail-relay.test".to_string(),
+            }]
+        );
+        assert_eq!(
+            flags(
+                include_bytes!("../../tests/fixtures/spoofing/lookalike.eml"),
+                &checks
+            ),
+            [SenderFlag::LookalikeDomain {
+                domain: "examp1e-corp.com".to_string(),
+                resembles: "example-corp.com".to_string(),
+            }]
+        );
+
+        // Switched off, each check stays quiet
+        let none = SenderChecks {
+            display_name_address: false,
+            brand_name: false,
+            reply_to: false,
+            lookalike_domain: false,
+        };
+        for fixture in [
+            &include_bytes!("../../tests/fixtures/spoofing/brand.eml")[..],
+            include_bytes!("../../tests/fixtures/spoofing/display-address.eml"),
+            include_bytes!("../../tests/fixtures/spoofing/lookalike.eml"),
+        ] {
+            assert_eq!(flags(fixture, &none), []);
+        }
+        let brand_only = SenderChecks {
+            reply_to: false,
+            ..SenderChecks::default()
+        };
+        assert_eq!(
+            flags(
+                include_bytes!("../../tests/fixtures/spoofing/brand.eml"),
+                &brand_only
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn genuine_senders_are_not_flagged() {
+        // Microsoft from a Microsoft subdomain, its own address as the display name,
+        // replies to a sibling subdomain, and a recipient at the same company
+        assert_eq!(
+            flags(
+                include_bytes!("../../tests/fixtures/spoofing/genuine.eml"),
+                &SenderChecks::default()
+            ),
+            []
+        );
+
+        // Homoglyph brand names and domains are caught too
+        assert_eq!(
+            impersonated_brand("Mіcrоsоft Account Team", "ms-alerts.test"),
+            Some("Microsoft")
+        );
+        assert_eq!(impersonated_brand("Appleton Ltd", "appleton.test"), None);
+        assert!(is_lookalike("exarnple.com", "example.com"));
+        assert!(is_lookalike("ехample.com", "example.com"));
+        assert!(is_lookalike("example.co.uk", "exmaple.co.uk"));
+        assert!(!is_lookalike("mail.example.com", "example.com"));
+        assert!(!is_lookalike("acme.com", "acne.com"));
+        assert!(!is_lookalike("example.com", "example.org"));
+        assert_eq!(base_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(base_domain("example.com"), "example.com");
+    }
+}
@@ -0,0 +1,164 @@
+use super::limits::Budget;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+
+/// A direct child of a storage
+#[derive(Debug, Clone)]
+pub struct StorageEntry {
+    pub name: String,
+    pub is_storage: bool,
+}
+
+/// Read-only view of an OLE compound file (the container format of .msg files)
+pub struct CompoundStorage<F> {
+    file: cfb::CompoundFile<F>,
+    /// A stream that exists couldn't be read, e.g. because the file is truncated
+    damaged: bool,
+    /// Streams are only read while they fit into this
+    budget: Budget,
+    /// First resource limit a read ran into
+    limit_error: Option<String>,
+}
+
+impl<F: Read + Seek> CompoundStorage<F> {
+    /// Open a compound file from any seekable reader
+    pub fn open(inner: F) -> Result<Self, String> {
+        let file = cfb::CompoundFile::open(inner)
+            .map_err(|e| format!("Not a valid compound file: {}", e))?;
+        Ok(Self {
+            file,
+            damaged: false,
+            budget: Budget::default(),
+            limit_error: None,
+        })
+    }
+
+    /// Account streams read from now on to `budget`
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+    }
+
+    /// Read a whole stream, returning `None` if it doesn't exist or can't be read
+    pub fn read_stream(&mut self, path: &str) -> Option<Vec<u8>> {
+        if self.limit_error.is_some() {
+            return None;
+        }
+        let mut data = Vec::new();
+        let budget = &self.budget;
+        let mut limit_error = None;
+        let result = self.file.open_stream(path).and_then(|mut stream| {
+            // Checked before reading: the declared length can be anything
+            if let Err(e) = budget.reserve(path, stream.len()) {
+                limit_error = Some(e);
+                return Ok(0);
+            }
+            stream.read_to_end(&mut data)?;
+            Ok(stream.len())
+        });
+        if limit_error.is_some() {
+            self.limit_error = limit_error;
+            return None;
+        }
+        match result {
+            Ok(len) => {
+                // Streams of a cut-off file end early without an error
+                self.damaged |= (data.len() as u64) < len;
+                Some(data)
+            }
+            Err(e) => {
+                self.damaged |= e.kind() != std::io::ErrorKind::NotFound;
+                None
+            }
+        }
+    }
+
+    /// Whether reading a stream failed for another reason than it not existing
+    pub fn is_damaged(&self) -> bool {
+        self.damaged
+    }
+
+    /// The resource limit a read ran into; later reads all return `None`
+    pub fn limit_error(&self) -> Option<&str> {
+        self.limit_error.as_deref()
+    }
+
+    /// Direct children of a storage; empty if the path doesn't exist
+    pub fn children(&self, path: &str) -> Vec<StorageEntry> {
+        match self.file.read_storage(path) {
+            Ok(entries) => entries
+                .map(|entry| StorageEntry {
+                    name: entry.name().to_string(),
+                    is_storage: entry.is_storage(),
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Copy a sub-storage with everything below it into a standalone compound file,
+    /// e.g. to turn an embedded Word document into an openable .doc
+    pub fn export_storage(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        self.export_storages(&[(path, "/")], |_, data| data)
+    }
+
+    /// Copy several sub-storages into one new compound file. `mapping` pairs each source
+    /// storage with its target path (the first one usually `/`); `rewrite` may change
+    /// stream contents on the way, given the target path.
+    pub fn export_storages<R>(
+        &mut self,
+        mapping: &[(&str, &str)],
+        rewrite: R,
+    ) -> Result<Vec<u8>, String>
+    where
+        R: Fn(&Path, Vec<u8>) -> Vec<u8>,
+    {
+        let mut out = cfb::CompoundFile::create(Cursor::new(Vec::new()))
+            .map_err(|e| format!("Failed to create compound file: {}", e))?;
+
+        for &(source_root, target_root) in mapping {
+            let fail =
+                |e: std::io::Error| format!("Failed to export storage {}: {}", source_root, e);
+            let root = Path::new(source_root);
+            let entries: Vec<_> = self
+                .file
+                .walk_storage(root)
+                .map_err(fail)?
+                .map(|entry| {
+                    (
+                        entry.path().to_path_buf(),
+                        entry.is_storage(),
+                        *entry.clsid(),
+                    )
+                })
+                .collect();
+
+            for (source, is_storage, clsid) in entries {
+                let Ok(relative) = source.strip_prefix(root) else {
+                    continue;
+                };
+                let target = Path::new(target_root).join(relative);
+
+                if is_storage {
+                    if target != Path::new("/") {
+                        out.create_storage(&target).map_err(fail)?;
+                    }
+                    out.set_storage_clsid(&target, clsid).map_err(fail)?;
+                } else {
+                    let mut data = Vec::new();
+                    let mut stream = self.file.open_stream(&source).map_err(fail)?;
+                    self.budget
+                        .reserve(&source.to_string_lossy(), stream.len())?;
+                    stream.read_to_end(&mut data).map_err(fail)?;
+                    let data = rewrite(&target, data);
+                    out.create_stream(&target)
+                        .and_then(|mut stream| stream.write_all(&data))
+                        .map_err(fail)?;
+                }
+            }
+        }
+
+        out.flush()
+            .map_err(|e| format!("Failed to write compound file: {}", e))?;
+        Ok(out.into_inner().into_inner())
+    }
+}
@@ -0,0 +1,619 @@
+use super::html::{self, Element, Node};
+
+/// Tables up to this width are drawn as aligned columns when no wrap width is given
+const DEFAULT_TABLE_WIDTH: usize = 80;
+
+/// Gap between aligned table columns
+const COLUMN_GAP: &str = "  ";
+
+/// Elements rendered as their own paragraph (blank line before and after)
+pub(super) const PARAGRAPHS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "dl",
+    "pre",
+    "table",
+    "blockquote",
+    "figure",
+];
+
+/// Elements that start and end a line
+pub(super) const BLOCKS: &[&str] = &[
+    "div",
+    "address",
+    "article",
+    "aside",
+    "center",
+    "details",
+    "dt",
+    "dd",
+    "fieldset",
+    "figcaption",
+    "footer",
+    "form",
+    "header",
+    "main",
+    "nav",
+    "section",
+    "summary",
+    "caption",
+    "tr",
+];
+
+/// Elements whose content is never shown
+pub(super) const HIDDEN: &[&str] = &[
+    "head", "script", "style", "title", "template", "noscript", "iframe", "object", "svg", "select",
+];
+
+/// Convert an HTML body to readable plain text. Used for text exports, search and previews,
+/// so all of them see the same text.
+pub fn html_to_text(html: &str, wrap_at: Option<usize>) -> String {
+    let nodes = html::parse(html);
+    let mut writer = Writer::new(wrap_at.unwrap_or(DEFAULT_TABLE_WIDTH));
+    render_nodes(&nodes, &mut writer);
+    let text = writer.finish();
+    match wrap_at {
+        Some(width) => wrap(&text, width),
+        None => text,
+    }
+}
+
+/// Wrap lines longer than `width` at spaces. Quote markers (`> `) and list bullets are
+/// repeated or indented on continuation lines; words longer than a line are left whole.
+pub fn wrap(text: &str, width: usize) -> String {
+    let width = width.max(10);
+    let mut out = String::with_capacity(text.len() + text.len() / width);
+    for line in text.lines() {
+        if line.chars().count() <= width {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        let (first_prefix, next_prefix, content) = split_prefix(line);
+        let mut current = first_prefix.clone();
+        let mut current_len = current.chars().count();
+        let mut has_word = false;
+        for word in content.split(' ').filter(|word| !word.is_empty()) {
+            let word_len = word.chars().count();
+            if has_word && current_len + 1 + word_len > width {
+                out.push_str(current.trim_end());
+                out.push('\n');
+                current = next_prefix.clone();
+                current_len = current.chars().count();
+                has_word = false;
+            }
+            if has_word {
+                current.push(' ');
+                current_len += 1;
+            }
+            current.push_str(word);
+            current_len += word_len;
+            has_word = true;
+        }
+        out.push_str(current.trim_end());
+        out.push('\n');
+    }
+    if !text.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// Prefix of the first line, prefix for continuation lines, and the remaining content
+fn split_prefix(line: &str) -> (String, String, &str) {
+    let quote_len = line.len() - line.trim_start_matches(['>', ' ']).len();
+    let (quote, rest) = line.split_at(quote_len);
+    let indent_len = rest.len() - rest.trim_start().len();
+    let (indent, rest) = rest.split_at(indent_len);
+
+    let marker_len = if rest.starts_with("- ") || rest.starts_with("* ") {
+        2
+    } else {
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        if digits > 0 && rest[digits..].starts_with(". ") {
+            digits + 2
+        } else {
+            0
+        }
+    };
+    let (marker, content) = rest.split_at(marker_len);
+    let first = format!("{}{}{}", quote, indent, marker);
+    let next = format!("{}{}{}", quote, indent, " ".repeat(marker.len()));
+    (first, next, content)
+}
+
+/// Accumulates text with block structure, collapsing whitespace like a browser
+struct Writer {
+    text: String,
+    /// Line breaks owed before the next text
+    pending: usize,
+    /// Prefix in effect when the owed line breaks were requested
+    break_prefix: String,
+    /// A space is owed before the next text on the same line
+    space: bool,
+    at_line_start: bool,
+    /// Quote markers and list indentation, outermost first
+    prefixes: Vec<String>,
+    /// List bullet to put into the next line's indentation
+    marker: Option<String>,
+    /// Nesting depth of list items; nested lists don't get blank lines around them
+    list_depth: usize,
+    table_width: usize,
+}
+
+impl Writer {
+    fn new(table_width: usize) -> Self {
+        Self {
+            text: String::new(),
+            pending: 0,
+            break_prefix: String::new(),
+            space: false,
+            at_line_start: true,
+            prefixes: Vec::new(),
+            marker: None,
+            list_depth: 0,
+            table_width,
+        }
+    }
+
+    /// Require at least `lines` line breaks (2 = blank line) before the next text
+    fn block(&mut self, lines: usize) {
+        if !self.text.is_empty() {
+            self.owe(self.pending.max(lines));
+        }
+        self.space = false;
+    }
+
+    fn line_break(&mut self) {
+        self.owe((self.pending + 1).min(2));
+        self.space = false;
+    }
+
+    fn owe(&mut self, lines: usize) {
+        if self.pending == 0 {
+            self.break_prefix = self.prefixes.concat();
+        }
+        self.pending = lines;
+    }
+
+    /// Text with whitespace runs collapsed into single spaces
+    fn inline(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() && c != '\u{A0}' {
+                self.space = !self.at_line_start || self.pending > 0 && self.text.is_empty();
+                continue;
+            }
+            self.start_text();
+            if self.space {
+                self.text.push(' ');
+                self.space = false;
+            }
+            self.text.push(if c == '\u{A0}' { ' ' } else { c });
+        }
+    }
+
+    /// Text whose line breaks and spacing are kept (`<pre>`, tables)
+    fn preformatted(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.line_break();
+            }
+            if !line.is_empty() {
+                self.start_text();
+                self.text.push_str(line.trim_end_matches('\r'));
+            }
+        }
+    }
+
+    /// Emit owed line breaks and the line prefix before text
+    fn start_text(&mut self) {
+        if self.pending > 0 && !self.text.is_empty() {
+            // Blank lines only carry the prefix shared by the lines around them
+            let prefix = self.prefixes.concat();
+            let shared: String = prefix
+                .chars()
+                .zip(self.break_prefix.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c)
+                .collect();
+            for _ in 1..self.pending {
+                self.text.push('\n');
+                self.text.push_str(shared.trim_end());
+            }
+            self.text.push('\n');
+            self.at_line_start = true;
+        }
+        self.pending = 0;
+        if self.at_line_start {
+            if let Some(marker) = self.marker.take() {
+                let outer = self.prefixes.len().saturating_sub(1);
+                self.text.push_str(&self.prefixes[..outer].concat());
+                self.text.push_str(&marker);
+            } else {
+                self.text.push_str(&self.prefixes.concat());
+            }
+            self.at_line_start = false;
+            self.space = false;
+        }
+    }
+
+    fn finish(self) -> String {
+        let lines: Vec<&str> = self.text.lines().map(str::trim_end).collect();
+        let mut out = String::with_capacity(self.text.len());
+        let mut blank = 0;
+        for line in lines {
+            if line.trim_start_matches(['>', ' ']).is_empty() {
+                blank += 1;
+                if blank > 1 {
+                    continue;
+                }
+            } else {
+                blank = 0;
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.trim_matches('\n').to_string()
+    }
+}
+
+fn render_nodes(nodes: &[Node], writer: &mut Writer) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => writer.inline(text),
+            Node::Element(element) => render_element(element, writer),
+        }
+    }
+}
+
+fn render_element(element: &Element, writer: &mut Writer) {
+    let name = element.name.as_str();
+    if HIDDEN.contains(&name) || is_hidden(element) {
+        return;
+    }
+
+    match name {
+        "br" => writer.line_break(),
+        "hr" => {
+            writer.block(1);
+            writer.preformatted("----------");
+            writer.block(1);
+        }
+        "img" => render_image(element, writer),
+        "a" => render_link(element, writer),
+        "li" => {
+            writer.block(1);
+            writer.marker = Some("- ".to_string());
+            writer.prefixes.push("  ".to_string());
+            writer.list_depth += 1;
+            render_nodes(&element.children, writer);
+            writer.list_depth -= 1;
+            writer.prefixes.pop();
+            writer.marker = None;
+            writer.block(1);
+        }
+        "blockquote" => {
+            writer.block(2);
+            writer.prefixes.push("> ".to_string());
+            render_nodes(&element.children, writer);
+            writer.prefixes.pop();
+            writer.block(2);
+        }
+        "pre" => {
+            writer.block(2);
+            writer.preformatted(&raw_text(element));
+            writer.block(2);
+        }
+        "table" => render_table(element, writer),
+        "td" | "th" => {
+            render_nodes(&element.children, writer);
+            writer.space = true;
+        }
+        _ if PARAGRAPHS.contains(&name) => {
+            let lines = if writer.list_depth > 0 && (name == "ul" || name == "ol") {
+                1
+            } else {
+                2
+            };
+            writer.block(lines);
+            render_nodes(&element.children, writer);
+            writer.block(lines);
+        }
+        _ if BLOCKS.contains(&name) => {
+            writer.block(1);
+            render_nodes(&element.children, writer);
+            writer.block(1);
+        }
+        _ => render_nodes(&element.children, writer),
+    }
+}
+
+/// `display: none` content isn't shown by mail clients either
+pub(super) fn is_hidden(element: &Element) -> bool {
+    element.attribute("hidden").is_some()
+        || element.attribute("style").is_some_and(|style| {
+            html::style_declarations(style)
+                .iter()
+                .any(|(property, value)| property == "display" && value == "none")
+        })
+}
+
+/// `[image: alt]`; images with an empty `alt` are decorative and left out
+fn render_image(element: &Element, writer: &mut Writer) {
+    match element.attribute("alt").map(str::trim) {
+        Some("") => {}
+        Some(alt) => writer.inline(&format!(" [image: {}] ", alt)),
+        None => writer.inline(" [image] "),
+    }
+}
+
+/// `text <url>`, or just the text when it already shows the address
+fn render_link(element: &Element, writer: &mut Writer) {
+    render_nodes(&element.children, writer);
+    let Some(href) = element.attribute("href").map(str::trim) else {
+        return;
+    };
+    let lower = href.to_ascii_lowercase();
+    if href.is_empty()
+        || href.starts_with('#')
+        || lower.starts_with("javascript:")
+        || lower.starts_with("cid:")
+    {
+        return;
+    }
+
+    let text = collapse(&plain_text(&element.children));
+    let target = href
+        .strip_prefix("mailto:")
+        .unwrap_or(href)
+        .trim_end_matches('/');
+    let shown = text.trim().trim_end_matches('/');
+    if shown.eq_ignore_ascii_case(target) || shown.eq_ignore_ascii_case(href) {
+        return;
+    }
+    if text.trim().is_empty() {
+        writer.inline(&format!(" {} ", href));
+    } else {
+        writer.inline(&format!(" <{}>", href));
+    }
+}
+
+fn render_table(table: &Element, writer: &mut Writer) {
+    let rows = table_rows(table);
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell_text(cell, writer.table_width))
+                .collect()
+        })
+        .collect();
+    let is_layout = rows.iter().any(|row| row.len() == 1)
+        || rows.iter().flatten().any(|cell| cell.contains("table"))
+        || cells.iter().flatten().any(|cell| cell.contains('\n'));
+
+    // Tables used for page layout are just containers
+    if is_layout {
+        writer.block(1);
+        for row in &rows {
+            writer.block(1);
+            for cell in row {
+                render_nodes(&cell.children, writer);
+                writer.block(1);
+            }
+        }
+        writer.block(1);
+        return;
+    }
+
+    let cells: Vec<Vec<String>> = cells
+        .into_iter()
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .collect();
+    if cells.is_empty() {
+        return;
+    }
+
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let total = widths.iter().sum::<usize>() + COLUMN_GAP.len() * columns.saturating_sub(1);
+
+    let mut out = String::new();
+    if total <= writer.table_width {
+        for row in &cells {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| {
+                    let pad = width.saturating_sub(cell.chars().count());
+                    format!("{}{}", cell, " ".repeat(pad))
+                })
+                .collect();
+            out.push_str(line.join(COLUMN_GAP).trim_end());
+            out.push('\n');
+        }
+    } else {
+        // Too wide to align: one `header: value` line per cell
+        let has_header = rows
+            .first()
+            .is_some_and(|row| row.iter().all(|cell| cell.name == "th"))
+            || columns > 2;
+        let (header, body) = if has_header {
+            (cells.first().cloned().unwrap_or_default(), &cells[1..])
+        } else {
+            (Vec::new(), &cells[..])
+        };
+        for (i, row) in body.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            if header.is_empty() && row.len() == 2 {
+                out.push_str(&format!("{}: {}\n", row[0], row[1]));
+                continue;
+            }
+            for (column, cell) in row.iter().enumerate() {
+                match header.get(column).filter(|key| !key.is_empty()) {
+                    Some(key) => out.push_str(&format!("{}: {}\n", key, cell)),
+                    None => out.push_str(&format!("{}\n", cell)),
+                }
+            }
+        }
+    }
+
+    writer.block(2);
+    writer.preformatted(out.trim_end());
+    writer.block(2);
+}
+
+/// Cells of a table's rows, looking through `thead`/`tbody`/`tfoot` but not nested tables
+pub(super) fn table_rows(table: &Element) -> Vec<Vec<&Element>> {
+    let mut rows = Vec::new();
+    for child in &table.children {
+        let Node::Element(element) = child else {
+            continue;
+        };
+        match element.name.as_str() {
+            "tr" => rows.push(row_cells(element)),
+            "thead" | "tbody" | "tfoot" => rows.extend(table_rows(element)),
+            _ => {}
+        }
+    }
+    rows
+}
+
+fn row_cells(row: &Element) -> Vec<&Element> {
+    row.children
+        .iter()
+        .filter_map(|child| match child {
+            Node::Element(cell) if cell.name == "td" || cell.name == "th" => Some(cell),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rendered text of a table cell; more than one line means the table is used for layout
+fn cell_text(cell: &Element, table_width: usize) -> String {
+    let mut writer = Writer::new(table_width);
+    render_nodes(&cell.children, &mut writer);
+    writer.finish()
+}
+
+/// Text content without any rendering, for comparisons
+pub(super) fn plain_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(element) => text.push_str(&plain_text(&element.children)),
+        }
+    }
+    text
+}
+
+/// Text of a `<pre>` element, keeping `<br>`s as line breaks
+pub(super) fn raw_text(element: &Element) -> String {
+    let mut text = String::new();
+    for node in &element.children {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(child) if child.name == "br" => text.push('\n'),
+            Node::Element(child) => text.push_str(&raw_text(child)),
+        }
+    }
+    text.trim_matches('\n').to_string()
+}
+
+pub(super) fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_lists_and_line_breaks() {
+        let html = "<html><head><title>T</title><style>p{}</style></head><body>\
+            <h1>Agenda</h1><p>First line<br>second   line</p>\
+            <ul><li>One</li><li>Two<ul><li>Nested</li></ul></li></ul><div>Done</div></body></html>";
+        assert_eq!(
+            html_to_text(html, None),
+            "Agenda\n\nFirst line\nsecond line\n\n- One\n- Two\n  - Nested\n\nDone"
+        );
+    }
+
+    #[test]
+    fn links_images_and_entities() {
+        let html = r#"<p>See <a href="https://example.com/report">the report</a>,
+            <a href="https://example.com">https://example.com</a> and
+            <a href="mailto:ann@example.com">ann@example.com</a>.
+            <img src="cid:logo" alt="Company logo"><img src="spacer.gif" alt="">
+            Caf&eacute; &amp; bar &ndash; &#8364;5 &#x2713; &copy2024 &notanentity;</p>"#;
+        assert_eq!(
+            html_to_text(html, None),
+            "See the report <https://example.com/report>, https://example.com and \
+             ann@example.com. [image: Company logo] Café & bar – €5 ✓ ©2024 ¬anentity;"
+        );
+    }
+
+    #[test]
+    fn narrow_table_is_aligned() {
+        let html = "<table><tr><th>Item</th><th>Qty</th></tr>\
+            <tr><td>Apples</td><td>3</td></tr><tr><td>Kiwi</td><td>12</td></tr></table>";
+        assert_eq!(
+            html_to_text(html, None),
+            "Item    Qty\nApples  3\nKiwi    12"
+        );
+    }
+
+    #[test]
+    fn wide_table_becomes_key_value_lines() {
+        let long = "a description that is much too long to fit into an aligned column layout";
+        let html = format!(
+            "<table><tr><th>Name</th><th>Role</th><th>Notes</th></tr>\
+             <tr><td>Ann</td><td>Lead</td><td>{long}</td></tr>\
+             <tr><td>Bob</td><td>Dev</td><td>{long}</td></tr></table>"
+        );
+        assert_eq!(
+            html_to_text(&html, None),
+            format!("Name: Ann\nRole: Lead\nNotes: {long}\n\nName: Bob\nRole: Dev\nNotes: {long}")
+        );
+    }
+
+    #[test]
+    fn layout_tables_and_quotes() {
+        let html =
+            "<table><tr><td><p>Hello</p><table><tr><td>Inner</td></tr></table></td></tr></table>\
+            <blockquote><p>Quoted</p><p>Text</p></blockquote>";
+        assert_eq!(
+            html_to_text(html, None),
+            "Hello\n\nInner\n\n> Quoted\n>\n> Text"
+        );
+    }
+
+    #[test]
+    fn wrapping_keeps_prefixes() {
+        let text = "> - a quoted bullet point that goes on for quite a while\nshort";
+        assert_eq!(
+            wrap(text, 24),
+            "> - a quoted bullet\n>   point that goes on\n>   for quite a while\nshort"
+        );
+    }
+}
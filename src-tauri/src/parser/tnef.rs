@@ -0,0 +1,547 @@
+//! TNEF (MS-OXTNEF), the `winmail.dat` attachment Outlook sends rich text mail over SMTP
+//! in. The files inside it become attachments of the message, listed after the
+//! `winmail.dat`, which is hidden; its HTML body, or the HTML encapsulated in its RTF body
+//! (see `rtf`), is shown when the message has no HTML body of its own.
+
+use super::charset;
+use super::limits::{self, Budget};
+use super::msg::{
+    PID_TAG_ATTACH_CONTENT_ID, PID_TAG_ATTACH_DATA_BINARY, PID_TAG_ATTACH_LONG_FILENAME,
+    PID_TAG_ATTACH_MIME_TAG, PID_TAG_BODY, PID_TAG_HTML, PID_TAG_INTERNET_CODEPAGE,
+    PID_TAG_RTF_COMPRESSED, PROP_TYPE_BINARY, PROP_TYPE_STRING8, PROP_TYPE_UNICODE,
+};
+use super::{rtf, Attachment, Message, ParseOptions};
+use crate::i18n::Localized;
+
+/// First four bytes of every TNEF stream
+const SIGNATURE: u32 = 0x223E_9F78;
+
+const LEVEL_MESSAGE: u8 = 1;
+const LEVEL_ATTACHMENT: u8 = 2;
+
+/// Attribute ids, without the type in their upper half
+const ATT_BODY: u16 = 0x800C;
+const ATT_ATTACH_DATA: u16 = 0x800F;
+const ATT_ATTACH_TITLE: u16 = 0x8010;
+const ATT_ATTACH_REND_DATA: u16 = 0x9002;
+const ATT_MSG_PROPS: u16 = 0x9003;
+const ATT_ATTACHMENT: u16 = 0x9005;
+const ATT_OEM_CODEPAGE: u16 = 0x9007;
+
+/// Flag of multi-valued property types
+const MV_FLAG: u16 = 0x1000;
+const PROP_TYPE_LONG: u16 = 0x0003;
+const PROP_TYPE_OBJECT: u16 = 0x000D;
+const PROP_TYPE_CLSID: u16 = 0x0048;
+
+/// What a TNEF stream holds
+#[derive(Debug, Default)]
+pub struct Tnef {
+    pub body_text: Option<String>,
+    /// The HTML body, or that encapsulated in the RTF body
+    pub body_html: Option<String>,
+    pub attachments: Vec<TnefAttachment>,
+}
+
+#[derive(Debug, Default)]
+pub struct TnefAttachment {
+    pub file_name: Option<String>,
+    pub mime: Option<String>,
+    pub content_id: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Replace the TNEF attachments of `message` by what they hold. Only running into a
+/// resource limit fails; an unreadable `winmail.dat` stays a plain attachment.
+pub fn apply(message: &mut Message, options: &ParseOptions) -> Result<(), String> {
+    let wrappers: Vec<usize> = message
+        .attachments
+        .iter()
+        .filter(|attachment| is_tnef(attachment))
+        .map(|attachment| attachment.index)
+        .collect();
+    for index in wrappers {
+        let tnef = match decode(&message.attachments[index].data, &options.budget) {
+            Ok(tnef) => tnef,
+            Err(e) if limits::tripped(&e).is_some() => return Err(e),
+            Err(e) => {
+                message.warnings.push(Localized::new(
+                    "warning.tnef_unreadable",
+                    [
+                        ("name", message.attachments[index].file_name.clone()),
+                        ("reason", e),
+                    ],
+                ));
+                continue;
+            }
+        };
+        message.attachments[index].hidden = true;
+        message.body_text = message.body_text.take().or(tnef.body_text);
+        message.body_html = message.body_html.take().or(tnef.body_html);
+        for file in tnef.attachments {
+            let index = message.attachments.len();
+            let name = file.file_name.as_deref().unwrap_or("winmail.dat");
+            options.budget.reserve(name, file.data.len() as u64)?;
+            let attachment =
+                Attachment::new(index, file.file_name, file.mime, file.content_id, file.data);
+            message.attachments.push(attachment);
+        }
+        // Inline images of the body are hidden, like those of .msg files
+        message.mark_referenced_attachments();
+        for attachment in &mut message.attachments[index + 1..] {
+            attachment.hidden |= attachment.referenced_in_body;
+        }
+    }
+    Ok(())
+}
+
+fn is_tnef(attachment: &Attachment) -> bool {
+    let declared = attachment
+        .declared_mime
+        .as_deref()
+        .map(str::to_ascii_lowercase);
+    (matches!(
+        declared.as_deref(),
+        Some("application/ms-tnef" | "application/vnd.ms-tnef")
+    ) || attachment.file_name.eq_ignore_ascii_case("winmail.dat"))
+        && attachment.data.starts_with(&SIGNATURE.to_le_bytes())
+}
+
+/// Little-endian reader over a byte slice
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    /// `len` bytes and the padding to the next multiple of 4 after them
+    fn take_padded(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        Some(slice)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// Decode a TNEF stream; a truncated one gives what was complete. The files in it are
+/// copies of parts of `data`, so they take no more than its size together.
+pub fn decode(data: &[u8], budget: &Budget) -> Result<Tnef, String> {
+    let mut cursor = Cursor { data, pos: 0 };
+    if cursor.u32() != Some(SIGNATURE) {
+        return Err("Not a TNEF stream".to_string());
+    }
+    let _key = cursor.u16().ok_or("TNEF stream is truncated")?;
+
+    let mut tnef = Tnef::default();
+    let mut label = "windows-1252";
+    let mut properties = Vec::new();
+    let mut complete = 0;
+    while !cursor.is_empty() {
+        budget.check_time()?;
+        let Some((level, id, value)) = attribute(&mut cursor) else {
+            if complete == 0 {
+                return Err("TNEF stream is truncated".to_string());
+            }
+            break;
+        };
+        complete += 1;
+        match (level, id) {
+            (_, ATT_OEM_CODEPAGE) => {
+                if let Some(found) = value
+                    .get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .and_then(charset::label_for_codepage)
+                {
+                    label = found;
+                }
+            }
+            (LEVEL_MESSAGE, ATT_BODY) => {
+                tnef.body_text = Some(text(value, label));
+            }
+            (LEVEL_MESSAGE, ATT_MSG_PROPS) => properties = mapi_properties(value),
+            (LEVEL_ATTACHMENT, ATT_ATTACH_REND_DATA) => {
+                tnef.attachments.push(TnefAttachment::default());
+            }
+            (LEVEL_ATTACHMENT, ATT_ATTACH_TITLE | ATT_ATTACH_DATA | ATT_ATTACHMENT) => {
+                if tnef.attachments.is_empty() {
+                    tnef.attachments.push(TnefAttachment::default());
+                }
+                let attachment = tnef.attachments.last_mut().unwrap();
+                match id {
+                    ATT_ATTACH_TITLE => {
+                        attachment.file_name = Some(text(value, label));
+                    }
+                    ATT_ATTACH_DATA => attachment.data = value.to_vec(),
+                    _ => apply_attachment_properties(attachment, &mapi_properties(value), label),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tnef.body_text = find_string(&properties, PID_TAG_BODY, label).or(tnef.body_text);
+    if let Some(html) = find(&properties, PID_TAG_HTML, PROP_TYPE_BINARY) {
+        let label = find(&properties, PID_TAG_INTERNET_CODEPAGE, PROP_TYPE_LONG)
+            .and_then(|value| value.get(..4))
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .and_then(charset::label_for_codepage)
+            .unwrap_or(label);
+        tnef.body_html = Some(
+            charset::decode(html, label)
+                .trim_end_matches('\0')
+                .to_string(),
+        );
+    } else if let Some(compressed) = find(&properties, PID_TAG_RTF_COMPRESSED, PROP_TYPE_BINARY) {
+        // An RTF body that doesn't decompress leaves the message without HTML
+        match rtf::decompress(compressed, budget) {
+            Ok(rtf) => tnef.body_html = rtf::encapsulated_html(&rtf),
+            Err(e) if limits::tripped(&e).is_some() => return Err(e),
+            Err(_) => {}
+        }
+    }
+    tnef.attachments
+        .retain(|attachment| !attachment.data.is_empty() || attachment.file_name.is_some());
+    Ok(tnef)
+}
+
+/// One attribute: level, id and value; `None` when the stream ends inside it
+fn attribute<'a>(cursor: &mut Cursor<'a>) -> Option<(u8, u16, &'a [u8])> {
+    let level = cursor.u8()?;
+    let id = cursor.u32()? as u16;
+    let len = cursor.u32()? as usize;
+    let value = cursor.take(len)?;
+    let _checksum = cursor.u16()?;
+    Some((level, id, value))
+}
+
+/// An 8-bit string attribute, without its terminating NUL
+fn text(value: &[u8], label: &str) -> String {
+    charset::decode(value, label)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// A MAPI property: id, type (without the multi-value flag) and first value
+type Property<'a> = (u16, u16, &'a [u8]);
+
+/// The properties of an `attMsgProps` or `attAttachment` value, as far as they decode
+fn mapi_properties(value: &[u8]) -> Vec<Property<'_>> {
+    let mut cursor = Cursor {
+        data: value,
+        pos: 0,
+    };
+    let mut properties = Vec::new();
+    let Some(count) = cursor.u32() else {
+        return properties;
+    };
+    for _ in 0..count {
+        match mapi_property(&mut cursor) {
+            Some(property) => properties.extend(property),
+            None => break,
+        }
+    }
+    properties
+}
+
+/// The next property; `Some(None)` for one whose value isn't kept
+fn mapi_property<'a>(cursor: &mut Cursor<'a>) -> Option<Option<Property<'a>>> {
+    let kind = cursor.u16()?;
+    let id = cursor.u16()?;
+    if id >= 0x8000 {
+        // Named: the property set and a number or a name
+        cursor.take(16)?;
+        match cursor.u32()? {
+            0 => {
+                cursor.u32()?;
+            }
+            _ => {
+                let len = cursor.u32()? as usize;
+                cursor.take_padded(len)?;
+            }
+        }
+    }
+    let base = kind & !MV_FLAG;
+    let fixed = match base {
+        0x0001..=0x0004 | 0x000A | 0x000B => Some(4),
+        0x0005..=0x0007 | 0x0014 | 0x0040 => Some(8),
+        PROP_TYPE_CLSID => Some(16),
+        PROP_TYPE_STRING8 | PROP_TYPE_UNICODE | PROP_TYPE_BINARY | PROP_TYPE_OBJECT => None,
+        _ => return None,
+    };
+    let first = match fixed {
+        Some(len) if kind & MV_FLAG == 0 => cursor.take_padded(len)?,
+        Some(len) => {
+            let count = cursor.u32()? as usize;
+            let values = cursor.take(count.checked_mul(len)?)?;
+            values.get(..len).unwrap_or_default()
+        }
+        None => {
+            let count = cursor.u32()?;
+            let mut first = None;
+            for _ in 0..count {
+                let len = cursor.u32()? as usize;
+                first.get_or_insert(cursor.take_padded(len)?);
+            }
+            first.unwrap_or_default()
+        }
+    };
+    Some(Some((id, base, first)))
+}
+
+fn find<'a>(properties: &[Property<'a>], id: u16, kind: u16) -> Option<&'a [u8]> {
+    properties
+        .iter()
+        .find(|(found, found_kind, _)| *found == id && *found_kind == kind)
+        .map(|(_, _, value)| *value)
+}
+
+/// A string property, the unicode variant preferred
+fn find_string(properties: &[Property<'_>], id: u16, label: &str) -> Option<String> {
+    if let Some(value) = find(properties, id, PROP_TYPE_UNICODE) {
+        return Some(charset::decode_utf16le(value));
+    }
+    find(properties, id, PROP_TYPE_STRING8).map(|value| text(value, label))
+}
+
+/// Name, type, content id and data from an attachment's `attAttachment` properties; the
+/// long file name wins over the 8.3 `attAttachTitle`
+fn apply_attachment_properties(
+    attachment: &mut TnefAttachment,
+    properties: &[Property<'_>],
+    label: &str,
+) {
+    if let Some(name) =
+        find_string(properties, PID_TAG_ATTACH_LONG_FILENAME, label).filter(|n| !n.is_empty())
+    {
+        attachment.file_name = Some(name);
+    }
+    attachment.mime = find_string(properties, PID_TAG_ATTACH_MIME_TAG, label);
+    attachment.content_id = find_string(properties, PID_TAG_ATTACH_CONTENT_ID, label);
+    if attachment.data.is_empty() {
+        if let Some(data) = find(properties, PID_TAG_ATTACH_DATA_BINARY, PROP_TYPE_BINARY) {
+            attachment.data = data.to_vec();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat};
+
+    /// A TNEF stream of `attributes` (level, id with type, value)
+    pub(crate) fn stream(attributes: &[(u8, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = SIGNATURE.to_le_bytes().to_vec();
+        out.extend_from_slice(&0x0001u16.to_le_bytes());
+        for (level, id, value) in attributes {
+            out.push(*level);
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value);
+            let checksum = value
+                .iter()
+                .fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+            out.extend_from_slice(&checksum.to_le_bytes());
+        }
+        out
+    }
+
+    /// MAPI properties of variable-length `(id, type, value)`
+    pub(crate) fn properties(values: &[(u16, u16, &[u8])]) -> Vec<u8> {
+        let mut out = (values.len() as u32).to_le_bytes().to_vec();
+        for (id, kind, value) in values {
+            out.extend_from_slice(&kind.to_le_bytes());
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    /// `winmail.dat` with an HTML body and a report attached under its long name, and an
+    /// inline image
+    pub(crate) fn sample() -> Vec<u8> {
+        let html = b"<p>Quarterly <img src=\"cid:chart@x\"></p>".to_vec();
+        // A single-valued long and a named property come before the body and are skipped
+        let mut message = properties(&[(PID_TAG_HTML, PROP_TYPE_BINARY, &html)]);
+        message[..4].copy_from_slice(&3u32.to_le_bytes());
+        let mut skipped = PROP_TYPE_LONG.to_le_bytes().to_vec();
+        skipped.extend_from_slice(&0x3FDEu16.to_le_bytes());
+        skipped.extend_from_slice(&1252u32.to_le_bytes());
+        skipped.extend_from_slice(&PROP_TYPE_LONG.to_le_bytes());
+        skipped.extend_from_slice(&0x8005u16.to_le_bytes());
+        skipped.extend_from_slice(&[0x29; 16]);
+        skipped.extend_from_slice(&0u32.to_le_bytes());
+        skipped.extend_from_slice(&0x8233u32.to_le_bytes());
+        skipped.extend_from_slice(&7u32.to_le_bytes());
+        message.splice(4..4, skipped);
+
+        let report_name = utf16("Quarterly report 2026.pdf");
+        let report = properties(&[
+            (
+                PID_TAG_ATTACH_LONG_FILENAME,
+                PROP_TYPE_UNICODE,
+                &report_name,
+            ),
+            (
+                PID_TAG_ATTACH_MIME_TAG,
+                PROP_TYPE_STRING8,
+                b"application/pdf\0",
+            ),
+        ]);
+        let chart = properties(&[(PID_TAG_ATTACH_CONTENT_ID, PROP_TYPE_STRING8, b"chart@x\0")]);
+        // Attribute ids carry their type in the upper half
+        let id = |id: u16, kind: u32| (kind << 16) | id as u32;
+        stream(&[
+            (
+                LEVEL_MESSAGE,
+                id(ATT_OEM_CODEPAGE, 6),
+                [1252u32.to_le_bytes(), [0; 4]].concat(),
+            ),
+            (LEVEL_MESSAGE, id(ATT_BODY, 2), b"Quarterly\0".to_vec()),
+            (LEVEL_MESSAGE, id(ATT_MSG_PROPS, 6), message),
+            (LEVEL_ATTACHMENT, id(ATT_ATTACH_REND_DATA, 6), vec![0; 14]),
+            (
+                LEVEL_ATTACHMENT,
+                id(ATT_ATTACH_TITLE, 1),
+                b"QUARTE~1.PDF\0".to_vec(),
+            ),
+            (
+                LEVEL_ATTACHMENT,
+                id(ATT_ATTACH_DATA, 6),
+                b"%PDF-1.7\n".to_vec(),
+            ),
+            (LEVEL_ATTACHMENT, id(ATT_ATTACHMENT, 6), report),
+            (LEVEL_ATTACHMENT, id(ATT_ATTACH_REND_DATA, 6), vec![0; 14]),
+            (
+                LEVEL_ATTACHMENT,
+                id(ATT_ATTACH_TITLE, 1),
+                b"chart.png\0".to_vec(),
+            ),
+            (
+                LEVEL_ATTACHMENT,
+                id(ATT_ATTACH_DATA, 6),
+                b"\x89PNG\r\n\x1a\n".to_vec(),
+            ),
+            (LEVEL_ATTACHMENT, id(ATT_ATTACHMENT, 6), chart),
+        ])
+    }
+
+    #[test]
+    fn tnef_streams_give_their_body_and_files() {
+        let tnef = decode(&sample(), &Budget::default()).unwrap();
+        assert_eq!(tnef.body_text.as_deref(), Some("Quarterly"));
+        assert_eq!(
+            tnef.body_html.as_deref(),
+            Some("<p>Quarterly <img src=\"cid:chart@x\"></p>")
+        );
+        let files: Vec<_> = tnef
+            .attachments
+            .iter()
+            .map(|a| (a.file_name.as_deref(), a.mime.as_deref(), a.data.len()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (
+                    Some("Quarterly report 2026.pdf"),
+                    Some("application/pdf"),
+                    9
+                ),
+                (Some("chart.png"), None, 8),
+            ]
+        );
+
+        // Cut inside an attribute, the complete ones are kept
+        let sample = sample();
+        let truncated = decode(&sample[..sample.len() - 20], &Budget::default()).unwrap();
+        assert_eq!(truncated.attachments.len(), 2);
+        assert_eq!(truncated.attachments[1].content_id, None);
+        assert!(decode(b"PK\x03\x04", &Budget::default()).is_err());
+    }
+
+    #[test]
+    fn winmail_dat_attachments_are_replaced_by_their_contents() {
+        let winmail = sample();
+        let eml = [
+            b"From: a@example.com\r\nSubject: Report\r\nMIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nQuarterly\r\n--b\r\n\
+Content-Type: application/ms-tnef; name=\"winmail.dat\"\r\n\
+Content-Transfer-Encoding: base64\r\n\r\n" as &[u8],
+            {
+                use base64::Engine as _;
+                base64::engine::general_purpose::STANDARD
+                    .encode(&winmail)
+                    .as_bytes()
+            },
+            b"\r\n--b--\r\n",
+        ]
+        .concat();
+        let message = parse_bytes(MessageFormat::Eml, &eml, &ParseOptions::default()).unwrap();
+        assert_eq!(message.body_text.as_deref(), Some("Quarterly"));
+        assert!(message.body_html.unwrap().contains("cid:chart@x"));
+        let attachments: Vec<_> = message
+            .attachments
+            .iter()
+            .map(|a| (a.file_name.as_str(), a.hidden))
+            .collect();
+        assert_eq!(
+            attachments,
+            [
+                ("winmail.dat", true),
+                ("Quarterly report 2026.pdf", false),
+                ("chart.png", true),
+            ]
+        );
+        assert_eq!(
+            message.attachments[1].detected_mime.as_deref(),
+            Some("application/pdf")
+        );
+
+        // One that doesn't decode stays as it is
+        let broken = [&winmail[..6], b"\x01\xff\xff\xff\xff"].concat();
+        let mut message = Message::new(MessageFormat::Eml);
+        message.attachments.push(Attachment::new(
+            0,
+            Some("winmail.dat".to_string()),
+            None,
+            None,
+            broken,
+        ));
+        apply(&mut message, &ParseOptions::default()).unwrap();
+        assert_eq!(message.attachments.len(), 1);
+        assert!(!message.attachments[0].hidden);
+    }
+}
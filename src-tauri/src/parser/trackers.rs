@@ -0,0 +1,248 @@
+use super::{html, idn, links, mime};
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use serde::Serialize;
+
+/// URL fragments used by common mailing and tracking services for open-tracking pixels.
+/// Users can add their own through the `tracker_patterns` setting.
+pub const KNOWN_TRACKER_PATTERNS: &[&str] = &[
+    "/wf/open",
+    "/track/open",
+    "/trk/open",
+    "/e/o/",
+    "/o.gif",
+    "/open.gif",
+    "/open.aspx",
+    "/pixel.gif",
+    "/tracking/pixel",
+    "/beacon",
+    "mailtrack.io/trace",
+    "list-manage.com/track",
+    "mandrillapp.com/track",
+    "sendgrid.net/wf",
+    "hubspotemail.net/e2t/to",
+    "mailchimp.com/track",
+    "emltrk.com",
+    "pixel.watch",
+];
+
+/// Largest width or height (in pixels) of an image that can't be meant for viewing
+const MAX_PIXEL_SIZE: u32 = 2;
+
+/// Query values at least this long, mixing letters and digits, are taken for tokens that
+/// identify the recipient
+const MIN_TOKEN_CHARS: usize = 24;
+
+/// Why an image was reported as a tracker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerReason {
+    /// Width or height of at most two pixels
+    TinyImage,
+    /// Styled invisible with `display: none` or `visibility: hidden`
+    HiddenImage,
+    /// URL matches a known tracking service
+    KnownTracker,
+    /// URL carries the recipient's address or a per-recipient token, so loading the image
+    /// tells the sender that this recipient read the message, like a read receipt
+    ReadTracking,
+}
+
+/// A remote image that reports back when the message is opened
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackerInfo {
+    pub url: String,
+    /// Host the image would be loaded from, in display form
+    pub domain: Option<String>,
+    pub reason: TrackerReason,
+}
+
+/// Remote images in `html` that look like tracking pixels, or that identify one of
+/// `recipients` (addresses) whatever their size. Embedded (`cid:`, `data:`) images can't
+/// report anything and are never included.
+pub fn find(html: &str, extra_patterns: &[String], recipients: &[String]) -> Vec<TrackerInfo> {
+    let mut trackers: Vec<TrackerInfo> = Vec::new();
+    for attributes in html::tags(html, "img") {
+        let Some(url) = html::attribute(&attributes, "src").map(str::trim) else {
+            continue;
+        };
+        if !is_remote(url) || trackers.iter().any(|tracker| tracker.url == url) {
+            continue;
+        }
+        let Some(reason) = reason(url, &attributes, extra_patterns, recipients) else {
+            continue;
+        };
+        trackers.push(TrackerInfo {
+            url: url.to_string(),
+            domain: links::host(url)
+                .and_then(idn::host_info)
+                .map(|info| info.display_host),
+            reason,
+        });
+    }
+    trackers
+}
+
+/// Whether `url` matches a known tracker pattern (built in or from `extra_patterns`)
+pub fn is_known_tracker(url: &str, extra_patterns: &[String]) -> bool {
+    let url = url.to_ascii_lowercase();
+    KNOWN_TRACKER_PATTERNS
+        .iter()
+        .copied()
+        .chain(extra_patterns.iter().map(String::as_str))
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .any(|pattern| url.contains(&pattern.to_ascii_lowercase()))
+}
+
+pub(crate) fn is_remote(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("//")
+}
+
+fn reason(
+    url: &str,
+    attributes: &[(String, String)],
+    extra_patterns: &[String],
+    recipients: &[String],
+) -> Option<TrackerReason> {
+    if identifies_recipient(url, recipients) {
+        return Some(TrackerReason::ReadTracking);
+    }
+    let style = html::attribute(attributes, "style")
+        .map(html::style_declarations)
+        .unwrap_or_default();
+    let css = |property: &str| {
+        style
+            .iter()
+            .find(|(name, _)| name == property)
+            .map(|(_, value)| value.as_str())
+    };
+
+    if css("display") == Some("none") || css("visibility") == Some("hidden") {
+        return Some(TrackerReason::HiddenImage);
+    }
+    let tiny = |attribute: &str| {
+        html::attribute(attributes, attribute)
+            .and_then(pixels)
+            .or_else(|| css(attribute).and_then(pixels))
+            .is_some_and(|size| size <= MAX_PIXEL_SIZE)
+    };
+    if tiny("width") || tiny("height") {
+        return Some(TrackerReason::TinyImage);
+    }
+    is_known_tracker(url, extra_patterns).then_some(TrackerReason::KnownTracker)
+}
+
+/// Whether `url` spells out one of the recipients' addresses (plainly, percent-encoded or
+/// in base64) or has a query value that looks like a token made for the recipient
+fn identifies_recipient(url: &str, recipients: &[String]) -> bool {
+    let decoded = String::from_utf8_lossy(&mime::percent_decode(url)).to_lowercase();
+    let names_recipient = recipients
+        .iter()
+        .map(|address| address.trim().to_lowercase())
+        .filter(|address| address.contains('@'))
+        .any(|address| {
+            decoded.contains(&address)
+                || url.contains(&STANDARD_NO_PAD.encode(&address))
+                || url.contains(&URL_SAFE_NO_PAD.encode(&address))
+        });
+    let query = url.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let query = query.split('#').next().unwrap_or("");
+    names_recipient
+        || query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(_, value)| is_token(value))
+}
+
+/// Long strings of letters and digits mixed (hex digests, random ids), as opposed to
+/// words, sizes and version numbers
+fn is_token(value: &str) -> bool {
+    value.len() >= MIN_TOKEN_CHARS
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && value.chars().any(|c| c.is_ascii_digit())
+        && value.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// Pixel size from `1`, `1px` or `1.0px`; relative sizes (`%`, `em`) don't count
+fn pixels(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let number = value.strip_suffix("px").unwrap_or(value).trim();
+    let size: f32 = number.parse().ok()?;
+    (size >= 0.0).then(|| size.ceil() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_and_hidden_images_are_trackers() {
+        let html = r#"<p>Hi</p>
+            <img src="https://news.example.com/logo.png" width="120" height="40">
+            <IMG SRC="https://t.example.net/u/abc" WIDTH=1 HEIGHT=1>
+            <img style="display:none" src="https://stats.example.org/x.png">
+            <img src='https://img.example.com/spacer' style="width: 1px; height: 1px">"#;
+        let trackers = find(html, &[], &[]);
+
+        assert_eq!(trackers.len(), 3);
+        assert_eq!(trackers[0].url, "https://t.example.net/u/abc");
+        assert_eq!(trackers[0].domain.as_deref(), Some("t.example.net"));
+        assert_eq!(trackers[0].reason, TrackerReason::TinyImage);
+        assert_eq!(trackers[1].reason, TrackerReason::HiddenImage);
+        assert_eq!(trackers[2].reason, TrackerReason::TinyImage);
+    }
+
+    #[test]
+    fn known_patterns_and_extra_patterns() {
+        let html = r#"<img src="https://u123.ct.sendgrid.net/wf/open?upn=xyz">
+            <img src="https://mail.example.com/img/r.png?id=42">"#;
+        let trackers = find(html, &[], &[]);
+        assert_eq!(trackers.len(), 1);
+        assert_eq!(trackers[0].reason, TrackerReason::KnownTracker);
+
+        let extra = vec!["/img/r.png".to_string()];
+        assert_eq!(find(html, &extra, &[]).len(), 2);
+    }
+
+    #[test]
+    fn embedded_small_icons_are_not_trackers() {
+        let html = r#"<img src="cid:icon-phone@sig" width="1" height="1">
+            <img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" width="1" height="1">
+            <img src="https://cdn.example.com/icons/phone.png" width="16" height="16">"#;
+        assert!(find(html, &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn images_naming_the_recipient_are_read_tracking() {
+        let message = crate::parser::parse_bytes(
+            crate::parser::MessageFormat::Eml,
+            include_bytes!("../../tests/fixtures/read-tracking/newsletter.eml"),
+            &crate::parser::ParseOptions::default(),
+        )
+        .unwrap();
+        let found: Vec<(&str, TrackerReason)> = message
+            .trackers
+            .iter()
+            .map(|tracker| (tracker.url.rsplit('/').next().unwrap(), tracker.reason))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "banner.png?r=ann%40example.com",
+                    TrackerReason::ReadTracking
+                ),
+                (
+                    "header.jpg?uid=8f3a9c2be71d4f06a5c3e9b1d2047e6f",
+                    TrackerReason::ReadTracking
+                ),
+                ("YW5uQGV4YW1wbGUuY29t.png", TrackerReason::ReadTracking),
+                ("spacer.gif", TrackerReason::TinyImage),
+            ]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+/// Average Helvetica glyph width in em, on the wide side so wrapped lines stay on the page
+const AVERAGE_GLYPH_WIDTH: f32 = 0.55;
+
+/// Fixed object numbers; pages are numbered from `FIRST_PAGE_OBJECT` on
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const FONT_REGULAR: usize = 3;
+const FONT_BOLD: usize = 4;
+const FIRST_PAGE_OBJECT: usize = 5;
+
+/// Text styles, all in the standard Helvetica fonts every viewer has
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Title,
+    Heading,
+    Subheading,
+    Body,
+    Small,
+}
+
+impl Style {
+    fn size(self) -> f32 {
+        match self {
+            Style::Title => 18.0,
+            Style::Heading => 14.0,
+            Style::Subheading => 11.0,
+            Style::Body => 10.0,
+            Style::Small => 8.5,
+        }
+    }
+
+    fn font(self) -> &'static str {
+        match self {
+            Style::Title | Style::Heading | Style::Subheading => "F2",
+            Style::Body | Style::Small => "F1",
+        }
+    }
+}
+
+/// Minimal streaming PDF writer for text documents (reports): wrapped lines flowing over
+/// A4 pages, each page written out once full. Text outside Windows-1252 shows as `?`.
+pub struct PdfWriter<W: Write> {
+    out: W,
+    offset: u64,
+    /// Byte offset of each object, by number - 1
+    offsets: Vec<u64>,
+    pages: Vec<usize>,
+    content: String,
+    /// Baseline of the next line
+    y: f32,
+}
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(out: W) -> std::io::Result<Self> {
+        let mut writer = Self {
+            out,
+            offset: 0,
+            offsets: vec![0; FIRST_PAGE_OBJECT - 1],
+            pages: Vec::new(),
+            content: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        };
+        // The binary comment marks the file as binary for transfer tools
+        writer.write(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n")?;
+        for (number, name) in [(FONT_REGULAR, "Helvetica"), (FONT_BOLD, "Helvetica-Bold")] {
+            writer.object(
+                number,
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                    name
+                )
+                .as_bytes(),
+            )?;
+        }
+        Ok(writer)
+    }
+
+    /// Add text in `style`, wrapped to the page width; line breaks in `text` are kept
+    pub fn text(&mut self, style: Style, text: &str) -> std::io::Result<()> {
+        let size = style.size();
+        let max_chars = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * AVERAGE_GLYPH_WIDTH)) as usize;
+        for line in text.lines() {
+            for part in crate::parser::text::wrap(line, max_chars).lines() {
+                self.line(style, part)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Vertical space in points
+    pub fn space(&mut self, points: f32) {
+        self.y -= points;
+    }
+
+    fn line(&mut self, style: Style, text: &str) -> std::io::Result<()> {
+        let leading = style.size() * 1.3;
+        if self.y - leading < MARGIN {
+            self.finish_page()?;
+        }
+        self.y -= style.size();
+        self.content.push_str(&format!(
+            "BT /{} {} Tf {} {:.1} Td ({}) Tj ET\n",
+            style.font(),
+            style.size(),
+            MARGIN,
+            self.y,
+            escape(text)
+        ));
+        self.y -= leading - style.size();
+        Ok(())
+    }
+
+    fn finish_page(&mut self) -> std::io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&encode(&std::mem::take(&mut self.content)))?;
+        let stream = encoder.finish()?;
+
+        let page = self.offsets.len() + 1;
+        let content = page + 1;
+        self.offsets.extend([0, 0]);
+        self.object(
+            page,
+            format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R \
+                 /Resources << /Font << /F1 {} 0 R /F2 {} 0 R >> >> >>",
+                PAGES, PAGE_WIDTH, PAGE_HEIGHT, content, FONT_REGULAR, FONT_BOLD
+            )
+            .as_bytes(),
+        )?;
+        let mut body = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            stream.len()
+        )
+        .into_bytes();
+        body.extend(stream);
+        body.extend(b"\nendstream");
+        self.object(content, &body)?;
+        self.pages.push(page);
+        self.y = PAGE_HEIGHT - MARGIN;
+        Ok(())
+    }
+
+    /// Write the last page, the page tree and the cross-reference table
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if !self.content.is_empty() || self.pages.is_empty() {
+            self.finish_page()?;
+        }
+        let kids: Vec<String> = self
+            .pages
+            .iter()
+            .map(|page| format!("{} 0 R", page))
+            .collect();
+        self.object(
+            PAGES,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                kids.len()
+            )
+            .as_bytes(),
+        )?;
+        self.object(
+            CATALOG,
+            format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES).as_bytes(),
+        )?;
+
+        let xref = self.offset;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            CATALOG,
+            xref
+        ));
+        self.write(table.as_bytes())?;
+        Ok(self.out)
+    }
+
+    fn object(&mut self, number: usize, body: &[u8]) -> std::io::Result<()> {
+        self.offsets[number - 1] = self.offset;
+        self.write(format!("{} 0 obj\n", number).as_bytes())?;
+        self.write(body)?;
+        self.write(b"\nendobj\n")
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.out.write_all(data)?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Escape a string literal; runs before `encode`, so only ASCII needs care here
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\t' => out.push(' '),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Content stream text in Windows-1252, the fonts' encoding
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7e}' | '\n' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            '„' => 0x84,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '™' => 0x99,
+            _ => b'?',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_pages_and_cross_references() {
+        let mut pdf = PdfWriter::new(Vec::new()).unwrap();
+        pdf.text(Style::Title, "Review (draft) – Übersicht")
+            .unwrap();
+        for i in 0..200 {
+            pdf.text(Style::Body, &format!("Line {} of the report", i))
+                .unwrap();
+        }
+        let data = pdf.finish().unwrap();
+        let text = String::from_utf8_lossy(&data);
+
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.trim_end().ends_with("%%EOF"));
+        // 200 lines of 13pt leading don't fit on one page
+        assert!(text.contains("/Count 4 "), "{}", text);
+
+        // Every entry of the cross-reference table points at its object
+        let xref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let table = std::str::from_utf8(&data[xref..]).unwrap();
+        for (number, line) in table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .enumerate()
+        {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(data[offset..].starts_with(format!("{} 0 obj", number + 1).as_bytes()));
+        }
+        assert_eq!(escape("a(b)\\"), "a\\(b\\)\\\\");
+        assert_eq!(encode("Ü–x字"), vec![0xDC, 0x96, b'x', b'?']);
+    }
+}
@@ -0,0 +1,132 @@
+//! Runtime checks for the sensitive command groups. The Tauri ACL (generated from
+//! `commands/registry.rs`) decides which windows may invoke a group at all; on top of that,
+//! the gated groups below are off until the user switches them on in settings (asked in a
+//! native dialog, see `set_group`), and the invoke handler refuses their commands while
+//! they are (see `commands::handler`).
+
+use crate::access::PERMISSION_DENIED;
+use crate::commands::GROUPS;
+use crate::i18n;
+use crate::settings::{Settings, SettingsStore};
+use tauri::{AppHandle, Manager};
+
+/// Groups whose commands only run once switched on
+const GATED: &[&str] = &["system-integration", "crypto"];
+
+/// The registry group of a command
+fn group_of(command: &str) -> Option<&'static str> {
+    GROUPS
+        .iter()
+        .find(|(_, commands)| commands.contains(&command))
+        .map(|(group, _)| *group)
+}
+
+/// Whether a group's commands may run; groups that aren't gated always may
+pub fn enabled(settings: &Settings, group: &str) -> bool {
+    !GATED.contains(&group) || settings.command_groups.get(group) == Some(&true)
+}
+
+/// Fails with `PermissionDenied[<group>]: <command>` unless `command`'s group is enabled
+pub fn check(settings: &Settings, command: &str) -> Result<(), String> {
+    let group = group_of(command).ok_or_else(|| format!("Unknown command: {}", command))?;
+    if enabled(settings, group) {
+        return Ok(());
+    }
+    log_line!("Denied {}: the {} commands are disabled", command, group);
+    Err(format!("{}[{}]: {}", PERMISSION_DENIED, group, command))
+}
+
+/// `check` against the current settings
+pub fn require(app: &AppHandle, command: &str) -> Result<(), String> {
+    check(&app.state::<SettingsStore>().get(), command)
+}
+
+/// Switch a gated group on or off and return whether it is on. Switching one on asks the
+/// user in a native dialog first, so the webview can't do it on its own.
+pub fn set_group(app: &AppHandle, group: &str, enable: bool) -> Result<bool, String> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    if !GATED.contains(&group) {
+        return Err(format!("Not a gated command group: {}", group));
+    }
+    let store = app.state::<SettingsStore>();
+    if enable && !enabled(&store.get(), group) {
+        let approved = app
+            .dialog()
+            .message(i18n::t(
+                "permissions.message",
+                &[("group", group.to_string())],
+            ))
+            .title(i18n::t("permissions.title", &[]))
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                i18n::t("access.allow", &[]),
+                i18n::t("access.deny", &[]),
+            ))
+            .blocking_show();
+        if !approved {
+            return Ok(false);
+        }
+    }
+    store.update(|settings| {
+        settings.command_groups.insert(group.to_string(), enable);
+    })?;
+    Ok(enable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gated_commands_are_refused_until_opted_in() {
+        let mut settings = Settings::default();
+        let denied = check(&settings, "provide_archive_password").unwrap_err();
+        assert_eq!(
+            denied,
+            format!("{}[crypto]: provide_archive_password", PERMISSION_DENIED)
+        );
+        assert_eq!(
+            i18n::Localized::from(denied).code,
+            "error.command_group_disabled"
+        );
+        assert!(check(&settings, "format_hard_drive").is_err());
+
+        // The invoke handler checks every registered command; only the gated groups' are
+        // refused, until their group is on
+        let refused = |settings: &Settings| -> Vec<&str> {
+            GROUPS
+                .iter()
+                .flat_map(|(_, commands)| commands.iter().copied())
+                .filter(|command| check(settings, command).is_err())
+                .collect()
+        };
+        assert_eq!(
+            refused(&settings),
+            [
+                "list_applications_for",
+                "open_with",
+                "provide_archive_password"
+            ]
+        );
+        assert_eq!(check(&settings, "open_file_with_system"), Ok(()));
+
+        // Opted in, one group at a time; ungated groups can't be switched off
+        settings.command_groups.insert("crypto".to_string(), true);
+        settings
+            .command_groups
+            .insert("files-read".to_string(), false);
+        assert_eq!(refused(&settings), ["list_applications_for", "open_with"]);
+        settings
+            .command_groups
+            .insert("system-integration".to_string(), true);
+        assert!(refused(&settings).is_empty());
+        settings.command_groups.insert("crypto".to_string(), false);
+        assert_eq!(refused(&settings), ["provide_archive_password"]);
+
+        // Every gated group exists in the registry
+        for group in GATED {
+            assert!(GROUPS.iter().any(|(name, _)| name == group), "{}", group);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! Files arrive as command-line arguments here, forwarded to the running instance by the
+//! single-instance plugin (see `file_open::on_second_instance`)
+
+use tauri::{AppHandle, RunEvent};
+
+pub fn on_run_event(_app: &AppHandle, _event: &RunEvent) {}
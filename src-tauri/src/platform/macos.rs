@@ -0,0 +1,24 @@
+//! macOS delivers opened files as run events rather than arguments
+
+use crate::file_open;
+use crate::state::PendingFiles;
+use tauri::{AppHandle, Manager, RunEvent};
+
+pub fn on_run_event(app: &AppHandle, event: &RunEvent) {
+    // Handle macOS file open events (double-click on file)
+    if let RunEvent::Opened { urls } = event {
+        for url in urls {
+            // Convert file:// URL to path
+            if let Ok(path) = url.to_file_path() {
+                // Check if app is ready (has windows)
+                if app.get_webview_window("main").is_some() {
+                    // App is running, emit event
+                    file_open::handle_file_open(app, path);
+                } else if file_open::is_message(&path) {
+                    // App is starting up, store for later
+                    app.state::<PendingFiles>().push(path);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! App lifecycle events, with the OS-specific ones in a module per platform
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use linux as os;
+#[cfg(target_os = "macos")]
+use macos as os;
+#[cfg(windows)]
+use windows as os;
+
+use crate::{retention, session};
+use tauri::{AppHandle, RunEvent};
+
+/// Handler for `App::run`
+pub fn on_run_event(app: &AppHandle, event: RunEvent) {
+    if let RunEvent::ExitRequested { .. } = &event {
+        session::save_now(app);
+    }
+    if let RunEvent::Exit = &event {
+        retention::run(app, true);
+    }
+    os::on_run_event(app, &event);
+}
@@ -0,0 +1,266 @@
+//! Messages parsed ahead of a click: hovering a row of the folder listing can ask for its
+//! listing metadata (`light`) or a full parse kept for `parse_message` (`warm`, see
+//! `prefetch_message`). Prefetches go one at a time and only while no interactive parse
+//! runs, and a result is only used for a parse that would have produced the same thing:
+//! same file, same settings, same charset choice.
+
+use crate::parser::Message;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+/// Parsed messages kept at most
+const MAX_ENTRIES: usize = 8;
+
+/// Approximate bytes the kept messages may take together; a larger one isn't kept
+const MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// How often a waiting prefetch looks whether it may run
+const POLL: Duration = Duration::from_millis(10);
+
+/// How much `prefetch_message` prepares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// The listing metadata and snippet (see `get_message_previews`)
+    Light,
+    /// That, and the message as `parse_message` returns it
+    Warm,
+}
+
+/// What a parse for the view depends on: the file as it is now, and a fingerprint of the
+/// settings and charset choice it is parsed with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub fingerprint: String,
+}
+
+impl Key {
+    /// The key of the file at `path` (resolved) now; `None` when it can't be read
+    pub fn of(path: &Path, fingerprint: String) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            fingerprint,
+        })
+    }
+}
+
+struct Entry {
+    key: Key,
+    bytes: usize,
+    message: Message,
+}
+
+/// Prefetched messages, least recently used first, and the turn taking between
+/// prefetches and interactive parses
+#[derive(Default)]
+pub struct Prefetcher {
+    cache: Mutex<VecDeque<Entry>>,
+    /// Interactive parses running; prefetches wait until there are none
+    interactive: AtomicUsize,
+    /// Held by the prefetch running
+    slot: Mutex<()>,
+}
+
+/// An interactive parse running, until dropped
+pub struct Interactive<'a>(&'a Prefetcher);
+
+impl Drop for Interactive<'_> {
+    fn drop(&mut self) {
+        self.0.interactive.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Prefetcher {
+    /// Mark an interactive parse as running, holding off prefetches until it is done
+    pub fn interactive(&self) -> Interactive<'_> {
+        self.interactive.fetch_add(1, Ordering::SeqCst);
+        Interactive(self)
+    }
+
+    /// Wait until no interactive parse runs and no other prefetch does; the prefetch runs
+    /// while the guard is held. `check_cancelled` is called while waiting and its error
+    /// returned.
+    pub fn wait_turn(
+        &self,
+        check_cancelled: impl Fn() -> Result<(), String>,
+    ) -> Result<MutexGuard<'_, ()>, String> {
+        loop {
+            check_cancelled()?;
+            if self.interactive.load(Ordering::SeqCst) == 0 {
+                if let Ok(slot) = self.slot.try_lock() {
+                    if self.interactive.load(Ordering::SeqCst) == 0 {
+                        return Ok(slot);
+                    }
+                }
+            }
+            std::thread::sleep(POLL);
+        }
+    }
+
+    /// The message prefetched for `key`, if there is one
+    pub fn get(&self, key: &Key) -> Option<Message> {
+        let mut cache = self.cache.lock().unwrap();
+        let position = cache.iter().position(|entry| entry.key == *key)?;
+        let entry = cache.remove(position)?;
+        let message = entry.message.clone();
+        cache.push_back(entry);
+        Some(message)
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.cache
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.key == *key)
+    }
+
+    /// Keep `message` for `key`, dropping the least recently used over the bounds
+    pub fn insert(&self, key: Key, message: Message) {
+        let bytes = approximate_size(&message);
+        if bytes > MAX_BYTES {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|entry| entry.key.path != key.path);
+        cache.push_back(Entry {
+            key,
+            bytes,
+            message,
+        });
+        while cache.len() > MAX_ENTRIES
+            || cache.iter().map(|entry| entry.bytes).sum::<usize>() > MAX_BYTES
+        {
+            cache.pop_front();
+        }
+    }
+
+    pub fn invalidate(&self, path: &Path) {
+        self.cache
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.key.path != path);
+    }
+}
+
+/// Bytes a parsed message takes, about: its fields as JSON and the attachment contents
+fn approximate_size(message: &Message) -> usize {
+    fn attachment_bytes(message: &Message) -> usize {
+        message
+            .attachments
+            .iter()
+            .map(|attachment| {
+                attachment.data.len() + attachment.nested.as_deref().map_or(0, attachment_bytes)
+            })
+            .sum()
+    }
+    serde_json::to_vec(message).map_or(0, |json| json.len()) + attachment_bytes(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, MessageFormat, ParseOptions};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    fn parse(data: &[u8]) -> Message {
+        parser::parse_bytes(MessageFormat::Eml, data, &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn hits_are_the_parse_they_stand_for_within_the_bounds() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let data = include_bytes!("../tests/fixtures/onboarding/sample.eml");
+        let path = dir.join("sample.eml");
+        std::fs::write(&path, data).unwrap();
+
+        let prefetcher = Prefetcher::default();
+        let key = Key::of(&path, "settings".to_string()).unwrap();
+        assert!(prefetcher.get(&key).is_none());
+        prefetcher.insert(key.clone(), parse(data));
+
+        // A hit gives what parsing again gives, attachment contents included
+        let hit = prefetcher.get(&key).unwrap();
+        let miss = parse(data);
+        assert_eq!(
+            serde_json::to_value(&hit).unwrap(),
+            serde_json::to_value(&miss).unwrap()
+        );
+        let contents = |message: &Message| -> Vec<Vec<u8>> {
+            message.attachments.iter().map(|a| a.data.clone()).collect()
+        };
+        assert_eq!(contents(&hit), contents(&miss));
+
+        // Other settings or a changed file miss
+        let other = Key::of(&path, "other settings".to_string()).unwrap();
+        assert!(prefetcher.get(&other).is_none());
+        std::fs::write(&path, [&data[..], b"\r\n"].concat()).unwrap();
+        assert!(prefetcher
+            .get(&Key::of(&path, "settings".to_string()).unwrap())
+            .is_none());
+
+        // The least recently used go first
+        for i in 0..MAX_ENTRIES {
+            let key = Key {
+                path: dir.join(format!("{}.eml", i)),
+                ..key.clone()
+            };
+            prefetcher.insert(key, parse(data));
+        }
+        assert!(prefetcher.get(&key).is_none());
+        assert_eq!(prefetcher.cache.lock().unwrap().len(), MAX_ENTRIES);
+        prefetcher.invalidate(&dir.join("0.eml"));
+        assert_eq!(prefetcher.cache.lock().unwrap().len(), MAX_ENTRIES - 1);
+    }
+
+    #[test]
+    fn prefetches_wait_for_interactive_parses_and_can_be_cancelled() {
+        let prefetcher = Arc::new(Prefetcher::default());
+        let interactive = prefetcher.interactive();
+        let started = Arc::new(AtomicBool::new(false));
+        let waiting = std::thread::spawn({
+            let (prefetcher, started) = (prefetcher.clone(), started.clone());
+            move || {
+                let _slot = prefetcher.wait_turn(|| Ok(())).unwrap();
+                started.store(true, Ordering::SeqCst);
+            }
+        });
+        std::thread::sleep(POLL * 5);
+        assert!(!started.load(Ordering::SeqCst));
+        drop(interactive);
+        waiting.join().unwrap();
+        assert!(started.load(Ordering::SeqCst));
+
+        // One at a time, and a cancelled one stops waiting
+        let _slot = prefetcher.wait_turn(|| Ok(())).unwrap();
+        let cancelled = AtomicBool::new(false);
+        let result = std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| {
+                prefetcher
+                    .wait_turn(|| {
+                        if cancelled.load(Ordering::SeqCst) {
+                            return Err(crate::jobs::CANCELLED.to_string());
+                        }
+                        Ok(())
+                    })
+                    .map(drop)
+            });
+            std::thread::sleep(POLL * 5);
+            cancelled.store(true, Ordering::SeqCst);
+            waiting.join().unwrap()
+        });
+        assert_eq!(result, Err(crate::jobs::CANCELLED.to_string()));
+    }
+}
@@ -0,0 +1,279 @@
+//! Message bodies shown in a child webview of their own rather than in the viewer's, so
+//! the page runs under a strict CSP and can't reach the app's commands. The body is
+//! sanitized as for HTML archives and served by the `msgpreview` scheme, only to the
+//! webview created for its document (`create_preview_view`). The one script allowed to
+//! run reports the content size and link clicks through `preview_message`, the only
+//! command `preview-*` webviews are granted (capabilities/preview.json); the parent window
+//! receives them as `preview-message` events and lays the webview out like an iframe.
+//!
+//! A preview webview that crashes or hangs takes nothing else with it: the registry below
+//! is all the app keeps of it, and creating the preview again replaces the entry.
+
+use crate::export::{self, escape_html};
+use crate::html_archive::inline_image;
+use crate::parser::{sanitize, sha256_hex, Message};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use tauri::http::{header, Request, Response, StatusCode};
+
+/// URI scheme of preview pages: `msgpreview://localhost/<doc-id>`
+/// (`http://msgpreview.localhost/<doc-id>` on Windows)
+pub const SCHEME: &str = "msgpreview";
+
+/// Labels of preview webviews, followed by the document id
+pub const LABEL_PREFIX: &str = "preview-";
+
+/// What the page's script reports, forwarded to the parent window
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum PreviewMessage {
+    /// A link was clicked; the page never navigates itself
+    Link { url: String },
+    /// The laid-out size of the content in CSS pixels, sent whenever it changes
+    ContentSize { width: f64, height: f64 },
+}
+
+/// Payload of the `preview-message` event
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewEvent {
+    pub doc_id: String,
+    #[serde(flatten)]
+    pub message: PreviewMessage,
+}
+
+/// A preview webview the app created
+#[derive(Debug, Clone)]
+pub struct PreviewView {
+    pub label: String,
+    /// Label of the window the webview is a child of
+    pub parent: String,
+    page: Arc<String>,
+    nonce: String,
+}
+
+/// Preview webviews by document id
+#[derive(Default)]
+pub struct PreviewViews(Mutex<HashMap<String, PreviewView>>);
+
+impl PreviewViews {
+    /// Register the page of `doc_id`, returning the preview it replaces
+    pub fn insert(&self, doc_id: &str, parent: &str, message: &Message) -> Option<PreviewView> {
+        let nonce = nonce(doc_id);
+        let view = PreviewView {
+            label: label(doc_id),
+            parent: parent.to_string(),
+            page: Arc::new(page(message, &nonce)),
+            nonce,
+        };
+        self.0.lock().unwrap().insert(doc_id.to_string(), view)
+    }
+
+    pub fn remove(&self, doc_id: &str) -> Option<PreviewView> {
+        self.0.lock().unwrap().remove(doc_id)
+    }
+
+    pub fn get(&self, doc_id: &str) -> Option<PreviewView> {
+        self.0.lock().unwrap().get(doc_id).cloned()
+    }
+
+    /// Bytes of the page served to the preview of `doc_id`
+    pub fn page_size(&self, doc_id: &str) -> usize {
+        self.0
+            .lock()
+            .unwrap()
+            .get(doc_id)
+            .map_or(0, |view| view.page.len())
+    }
+
+    /// The document and parent window of the preview webview labelled `label`
+    pub fn by_label(&self, label: &str) -> Option<(String, String)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, view)| view.label == label)
+            .map(|(doc_id, view)| (doc_id.clone(), view.parent.clone()))
+    }
+
+    /// Forget the previews of a closed window; its child webviews went with it
+    pub fn forget_window(&self, window: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|_, view| view.parent != window);
+    }
+}
+
+pub fn label(doc_id: &str) -> String {
+    format!("{}{}", LABEL_PREFIX, doc_id)
+}
+
+/// The URL a preview webview loads
+pub fn url(doc_id: &str) -> Result<tauri::Url, String> {
+    let url = if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", SCHEME, doc_id)
+    } else {
+        format!("{}://localhost/{}", SCHEME, doc_id)
+    };
+    tauri::Url::parse(&url).map_err(|e| format!("Invalid preview URL: {}", e))
+}
+
+/// Whether a preview webview may navigate to `url`: only to its own page
+pub fn allowed_navigation(doc_id: &str, url: &tauri::Url) -> bool {
+    self::url(doc_id).is_ok_and(|own| own == *url)
+}
+
+/// The policy preview pages are served with: embedded images and inline styles, the
+/// bridge script and the IPC endpoint, nothing else
+pub fn csp(nonce: &str) -> String {
+    format!(
+        "default-src 'none'; img-src data:; style-src 'unsafe-inline'; \
+         script-src 'nonce-{}'; connect-src ipc: http://ipc.localhost; \
+         base-uri 'none'; form-action 'none'; frame-src 'none'",
+        nonce
+    )
+}
+
+/// A nonce the page's content can't predict
+fn nonce(doc_id: &str) -> String {
+    let random = |salt: &str| {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write(salt.as_bytes());
+        hasher.finish().to_le_bytes()
+    };
+    sha256_hex(&[random(doc_id), random("nonce")].concat())[..32].to_string()
+}
+
+/// The preview page of `message`: the sanitized HTML body with its inline images, else
+/// the text body, and the bridge script
+pub fn page(message: &Message, nonce: &str) -> String {
+    let body = match &message.body_html {
+        Some(html) => sanitize::sanitize(html, |cid| inline_image(message, cid)),
+        None => format!(
+            "<pre>{}</pre>",
+            escape_html(&export::body_text(message, false))
+        ),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"referrer\" content=\"no-referrer\">\n<style>\n\
+         body {{ margin: 0; padding: 8px; font-family: sans-serif; overflow-wrap: anywhere; }}\n\
+         img {{ max-width: 100%; height: auto; }}\n\
+         pre {{ white-space: pre-wrap; }}\n\
+         </style>\n</head>\n<body>\n{}\n<script nonce=\"{}\">\n{}</script>\n</body>\n</html>\n",
+        body, nonce, BRIDGE_SCRIPT
+    )
+}
+
+/// Reports link clicks and content size changes to `preview_message`
+const BRIDGE_SCRIPT: &str = r#"(() => {
+  const send = message =>
+    window.__TAURI_INTERNALS__.invoke('preview_message', { message }).catch(() => {});
+  document.addEventListener('click', event => {
+    const link = event.target instanceof Element ? event.target.closest('a[href]') : null;
+    if (!link) return;
+    event.preventDefault();
+    send({ kind: 'link', url: link.href });
+  }, true);
+  let last = '';
+  new ResizeObserver(() => {
+    const root = document.documentElement;
+    const size = `${root.scrollWidth}x${root.scrollHeight}`;
+    if (size === last) return;
+    last = size;
+    send({ kind: 'content-size', width: root.scrollWidth, height: root.scrollHeight });
+  }).observe(document.body);
+})();
+"#;
+
+/// Answer a protocol request from the webview labelled `webview`; pages are served only
+/// to the preview webview of their document
+pub fn handle(
+    views: &PreviewViews,
+    webview: &str,
+    request: &Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let doc_id = request.uri().path().trim_start_matches('/');
+    let Some(view) = views.get(doc_id).filter(|view| view.label == webview) else {
+        return error(StatusCode::NOT_FOUND, "Unknown preview");
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CONTENT_SECURITY_POLICY, csp(&view.nonce))
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(header::REFERRER_POLICY, "no-referrer")
+        .header("X-Content-Type-Options", "nosniff")
+        .body(view.page.as_bytes().to_vec())
+        .unwrap()
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.as_bytes().to_vec())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    #[test]
+    fn pages_are_served_only_to_their_preview_webview() {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            b"From: a@example.com\r\nContent-Type: text/html\r\n\r\n\
+              <p onclick=\"x()\">Hi</p><script>alert(1)</script>\
+              <img src=\"https://tracker.example/p.gif\">\r\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let views = PreviewViews::default();
+        assert!(views.insert("doc-1", "main", &message).is_none());
+        let request = |path: &str| Request::builder().uri(path).body(Vec::new()).unwrap();
+
+        let response = handle(
+            &views,
+            "preview-doc-1",
+            &request("msgpreview://localhost/doc-1"),
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = String::from_utf8(response.body().clone()).unwrap();
+        let view = views.get("doc-1").unwrap();
+        assert!(page.contains("<p>Hi</p>"));
+        assert!(!page.contains("alert(1)") && !page.contains("onclick"));
+        assert!(!page.contains("tracker.example"));
+        assert_eq!(page.matches("<script").count(), 1);
+        assert!(page.contains(&format!("<script nonce=\"{}\">", view.nonce)));
+        assert_eq!(
+            response.headers()[header::CONTENT_SECURITY_POLICY],
+            csp(&view.nonce).as_str()
+        );
+
+        for (webview, path) in [
+            ("main", "msgpreview://localhost/doc-1"),
+            ("preview-doc-2", "msgpreview://localhost/doc-1"),
+            ("preview-doc-2", "msgpreview://localhost/doc-2"),
+        ] {
+            assert_eq!(
+                handle(&views, webview, &request(path)).status(),
+                StatusCode::NOT_FOUND
+            );
+        }
+        assert_eq!(
+            views.by_label("preview-doc-1"),
+            Some(("doc-1".to_string(), "main".to_string()))
+        );
+        views.forget_window("main");
+        assert!(views.get("doc-1").is_none());
+        assert!(allowed_navigation("doc-1", &url("doc-1").unwrap()));
+        assert!(!allowed_navigation(
+            "doc-1",
+            &tauri::Url::parse("https://example.com/").unwrap()
+        ));
+    }
+}
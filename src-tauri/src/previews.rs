@@ -0,0 +1,124 @@
+use crate::export;
+use crate::i18n::Lang;
+use crate::parser::grouping::AttachmentSummary;
+use crate::parser::labels::SourceFlags;
+use crate::parser::language::Language;
+use crate::parser::{preview, Message};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Files whose listing metadata is kept; the cache is emptied when it grows past this
+const MAX_CACHED_FILES: usize = 50_000;
+
+/// What a folder listing shows of a message file
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagePreview {
+    pub path: String,
+    pub subject: Option<String>,
+    pub sender: Option<String>,
+    /// RFC 3339
+    pub date: Option<String>,
+    pub attachments: usize,
+    /// Total size and count per category of the attachments
+    pub attachment_summary: AttachmentSummary,
+    /// Envelope recipients, for filtering by the alias a message came to
+    pub delivered_to: Vec<String>,
+    /// Labels and flags of the mailbox the file was exported from
+    pub source_labels: Vec<String>,
+    pub source_flags: Option<SourceFlags>,
+    /// Reading-pane snippet (see `parser::preview::snippet`)
+    pub preview: String,
+    /// Language of the body (see `parser::language`)
+    pub language: Option<Language>,
+    /// Why the file couldn't be read; the other fields are then empty
+    pub error: Option<String>,
+}
+
+struct CacheEntry {
+    modified: Option<SystemTime>,
+    lang: Lang,
+    preview: MessagePreview,
+}
+
+/// Listing metadata by file, valid while the file's modification time is unchanged
+#[derive(Default)]
+pub struct PreviewCache(Mutex<HashMap<PathBuf, CacheEntry>>);
+
+impl PreviewCache {
+    /// Metadata of the file at `path` (already checked against the access policy), parsed
+    /// with `load` unless cached
+    pub fn get(
+        &self,
+        path: &Path,
+        lang: Lang,
+        load: impl FnOnce() -> Result<Message, String>,
+    ) -> MessagePreview {
+        let modified = std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if let Some(entry) = self
+            .0
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|entry| entry.modified == modified && entry.lang == lang)
+        {
+            return entry.preview.clone();
+        }
+
+        let path_string = path.to_string_lossy().to_string();
+        let preview = match load() {
+            Ok(message) => MessagePreview {
+                path: path_string,
+                sender: message.from.as_ref().map(export::format_address),
+                preview: preview::snippet(&message, lang),
+                subject: message.subject,
+                date: message.date,
+                attachments: message.attachments.len(),
+                attachment_summary: message.attachment_summary,
+                delivered_to: message.delivered_to,
+                source_labels: message.source_labels,
+                source_flags: message.source_flags,
+                language: message.language,
+                error: None,
+            },
+            // Failures aren't cached: the file may still be being written
+            Err(error) => {
+                return MessagePreview {
+                    path: path_string,
+                    subject: None,
+                    sender: None,
+                    date: None,
+                    attachments: 0,
+                    attachment_summary: AttachmentSummary::default(),
+                    delivered_to: Vec::new(),
+                    source_labels: Vec::new(),
+                    source_flags: None,
+                    preview: String::new(),
+                    language: None,
+                    error: Some(error),
+                }
+            }
+        };
+        let mut cache = self.0.lock().unwrap();
+        if cache.len() >= MAX_CACHED_FILES {
+            cache.clear();
+        }
+        cache.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                modified,
+                lang,
+                preview: preview.clone(),
+            },
+        );
+        preview
+    }
+
+    pub fn invalidate(&self, path: &Path) {
+        self.0.lock().unwrap().remove(path);
+    }
+}
@@ -0,0 +1,232 @@
+//! Quarantine mode for incident response: nothing leaves the app. Commands handing content
+//! to other applications, fetching from the network, saving attachments or exporting
+//! messages refuse to run, while parsing, previews, hashes and inspection keep working.
+//! The mode is switched on in settings or with `--quarantine` on the command line;
+//! switched on that way, it lasts until the app exits.
+
+use crate::i18n;
+use crate::settings::{Settings, SettingsStore};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewWindow};
+
+/// Error prefix of commands refused in quarantine mode, `QuarantineBlocked: <command>`
+pub const QUARANTINE_BLOCKED: &str = "QuarantineBlocked";
+
+/// Command-line argument switching quarantine mode on, also from a second instance
+pub const CLI_FLAG: &str = "--quarantine";
+
+/// Commands that write attachments or message content out, hand files to other
+/// applications or go online
+const BLOCKED: &[&str] = &[
+    "open_file_with_system",
+    "open_with",
+    "fetch_remote_image",
+    "save_attachment",
+    "export_attachments",
+    "export_attachments_matching",
+    "save_file_with_dialog",
+    "finalize_draft",
+    "generate_calendar_response",
+    "export_as_txt",
+    "export_html_archive",
+    "export_recipients_csv",
+    "export_mbox",
+    "export_review_report",
+    "export_redacted",
+    "export_index_csv",
+];
+
+/// Set when the command line asked for quarantine mode
+#[derive(Default)]
+pub struct Quarantine(AtomicBool);
+
+/// What `get_security_mode` reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SecurityMode {
+    pub quarantine: bool,
+    /// Switched on from the command line; the webview can't switch it off
+    pub locked: bool,
+}
+
+impl SecurityMode {
+    pub fn new(settings: &Settings, forced: bool) -> Self {
+        Self {
+            quarantine: forced || settings.quarantine_mode,
+            locked: forced,
+        }
+    }
+
+    /// Fails with `QuarantineBlocked: <command>` for the commands quarantine mode stops
+    pub fn check(&self, command: &str) -> Result<(), String> {
+        if self.quarantine && BLOCKED.contains(&command) {
+            log_line!("Blocked {} in quarantine mode", command);
+            return Err(format!("{}: {}", QUARANTINE_BLOCKED, command));
+        }
+        Ok(())
+    }
+}
+
+pub fn mode<R: Runtime>(app: &AppHandle<R>) -> SecurityMode {
+    SecurityMode::new(
+        &app.state::<SettingsStore>().get(),
+        app.state::<Quarantine>().0.load(Ordering::SeqCst),
+    )
+}
+
+/// Whether quarantine mode is on; remote content and auto-extraction are skipped then
+pub fn active<R: Runtime>(app: &AppHandle<R>) -> bool {
+    mode(app).quarantine
+}
+
+/// `check` against the current mode; the invoke handler runs it before every command
+pub fn require(app: &AppHandle, command: &str) -> Result<(), String> {
+    mode(app).check(command)
+}
+
+/// Whether command-line arguments ask for quarantine mode
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == CLI_FLAG)
+}
+
+/// Switch quarantine mode on for the rest of the session (command-line flag)
+pub fn force<R: Runtime>(app: &AppHandle<R>) {
+    if !app.state::<Quarantine>().0.swap(true, Ordering::SeqCst) {
+        log_line!("Quarantine mode switched on from the command line");
+        announce(app);
+    }
+}
+
+/// Switch the quarantine setting and return the resulting mode. Switching it off asks the
+/// user in a native dialog first, and is refused when the command line switched it on.
+pub fn set(app: &AppHandle, enable: bool) -> Result<SecurityMode, String> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let current = mode(app);
+    if !enable && current.locked {
+        return Err(format!(
+            "Quarantine mode was switched on with {} and lasts until the app exits",
+            CLI_FLAG
+        ));
+    }
+    if !enable && current.quarantine {
+        let approved = app
+            .dialog()
+            .message(i18n::t("quarantine.disable_message", &[]))
+            .title(i18n::t("quarantine.title", &[]))
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                i18n::t("access.allow", &[]),
+                i18n::t("access.deny", &[]),
+            ))
+            .blocking_show();
+        if !approved {
+            return Ok(current);
+        }
+    }
+    app.state::<SettingsStore>()
+        .update(|settings| settings.quarantine_mode = enable)?;
+    let mode = mode(app);
+    if mode != current {
+        announce(app);
+    }
+    Ok(mode)
+}
+
+/// Mark the window titles and tell the webviews (`security-mode-changed`)
+fn announce<R: Runtime>(app: &AppHandle<R>) {
+    apply_titles(app);
+    if let Err(e) = app.emit("security-mode-changed", mode(app)) {
+        log_line!("Failed to emit security-mode-changed: {}", e);
+    }
+}
+
+/// [`apply_title`] for every window
+pub fn apply_titles<R: Runtime>(app: &AppHandle<R>) {
+    for window in app.webview_windows().values() {
+        apply_title(window);
+    }
+}
+
+/// Title a window from the app's configured one, marked while in quarantine mode
+pub fn apply_title<R: Runtime>(window: &WebviewWindow<R>) {
+    let app = window.app_handle();
+    let base = app
+        .config()
+        .app
+        .windows
+        .first()
+        .map(|config| config.title.clone())
+        .unwrap_or_default();
+    let title = match active(app) {
+        true => i18n::t("quarantine.window_title", &[("title", base)]),
+        false => base,
+    };
+    if let Err(e) = window.set_title(&title) {
+        log_line!("Failed to set the title of {}: {}", window.label(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantine_blocks_only_what_leaves_the_app() {
+        let mut settings = Settings::default();
+        let off = SecurityMode::new(&settings, false);
+        assert_eq!(off.check("save_attachment"), Ok(()));
+
+        settings.quarantine_mode = true;
+        let on = SecurityMode::new(&settings, false);
+        assert!(!on.locked);
+        let error = on.check("save_attachment").unwrap_err();
+        assert_eq!(
+            error,
+            format!("{}: {}", QUARANTINE_BLOCKED, "save_attachment")
+        );
+        assert_eq!(
+            i18n::Localized::from(error).code,
+            "error.quarantine_blocked"
+        );
+
+        // Commands writing files or handing them on that keep working: they write no
+        // message content (diagnostics bundles leave it out in quarantine mode)
+        const KEPT: &[&str] = &[
+            "list_applications_for",
+            "verify_archive",
+            "normalize_filename",
+            "export_sender_rules",
+            "export_settings",
+            "export_workspace",
+            "export_audit_log",
+            "create_diagnostics_bundle",
+        ];
+        let registered = |command: &&str| {
+            crate::commands::GROUPS
+                .iter()
+                .any(|(_, commands)| commands.contains(command))
+        };
+        for command in BLOCKED.iter().chain(KEPT) {
+            assert!(registered(command), "{}", command);
+        }
+        for (group, commands) in crate::commands::GROUPS {
+            for command in *commands {
+                let blocked = BLOCKED.contains(command);
+                assert_eq!(on.check(command).is_err(), blocked, "{}", command);
+                assert_eq!(off.check(command), Ok(()), "{}", command);
+                if !blocked && matches!(*group, "files-write" | "system-integration") {
+                    assert!(KEPT.contains(command), "{} isn't blocked", command);
+                }
+            }
+        }
+
+        // The command line wins over the setting
+        settings.quarantine_mode = false;
+        let forced = SecurityMode::new(&settings, true);
+        assert!(forced.quarantine && forced.locked);
+        assert!(forced.check("open_with").is_err());
+        assert!(requested(&["a.msg".to_string(), CLI_FLAG.to_string()]));
+        assert!(!requested(&["--quarantined.msg".to_string()]));
+    }
+}
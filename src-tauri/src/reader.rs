@@ -0,0 +1,450 @@
+use serde::Serialize;
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Files up to this size that are not cloud placeholders are read in one call
+const FAST_PATH_MAX: u64 = 1024 * 1024;
+
+/// Size of the chunks slow reads are split into
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Reads finishing within this time don't report progress at all
+const PROGRESS_DELAY: Duration = Duration::from_millis(300);
+
+/// Minimum time between two progress events of the same read
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Prefix of the error for a file another process keeps locked; followed by `: `, the
+/// path and, when known, a line with the names of the processes holding it
+pub const FILE_LOCKED: &str = "FileLocked";
+
+/// Prefix of the error for a file the user isn't allowed to read; followed by `: ` and
+/// the path
+pub const ACCESS_DENIED: &str = "AccessDenied";
+
+/// Payload of the `read-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadProgress<'a> {
+    pub path: &'a str,
+    pub read: u64,
+    pub total: u64,
+    /// The file is a cloud placeholder whose content is being downloaded
+    pub hydrating: bool,
+}
+
+/// Whether the file's content is not stored locally and will be downloaded on access
+/// (OneDrive files on demand, iCloud dataless files)
+pub fn is_placeholder(metadata: &Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+        const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+        const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+        metadata.file_attributes()
+            & (FILE_ATTRIBUTE_OFFLINE
+                | FILE_ATTRIBUTE_RECALL_ON_OPEN
+                | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+            != 0
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        const SF_DATALESS: u32 = 0x40000000;
+        metadata.st_flags() & SF_DATALESS != 0
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+/// Open a file for reading. A file another process holds locked (Outlook keeps open .msg
+/// files that way) is retried on Windows with all sharing modes, then with backup
+/// semantics; when that fails as well, the error starts with `FILE_LOCKED` and names the
+/// processes Restart Manager reports. A file without read permission fails with
+/// `ACCESS_DENIED`, a volume that is gone with `volumes::VOLUME_UNAVAILABLE`; other
+/// errors read `Failed to read file ...`.
+pub fn open(path: &Path) -> Result<File, String> {
+    let error = match File::open(path) {
+        Ok(file) => return Ok(file),
+        Err(e) => e,
+    };
+    if !platform::is_locked(&error) {
+        return Err(open_error(path, error));
+    }
+    if let Some(file) = platform::open_shared(path) {
+        return Ok(file);
+    }
+    log_line!("{} is locked: {}", path.display(), error);
+    Err(locked_error(path, &platform::locking_processes(path)))
+}
+
+/// The error for a file that couldn't be opened and isn't locked
+fn open_error(path: &Path, error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            format!("{}: {}", ACCESS_DENIED, path.to_string_lossy())
+        }
+        _ => read_error(path, error),
+    }
+}
+
+/// The error for a file still locked by `holders` (names, when known)
+fn locked_error(path: &Path, holders: &[String]) -> String {
+    match holders.is_empty() {
+        true => format!("{}: {}", FILE_LOCKED, path.to_string_lossy()),
+        false => format!(
+            "{}: {}\n{}",
+            FILE_LOCKED,
+            path.to_string_lossy(),
+            holders.join(", ")
+        ),
+    }
+}
+
+/// Read a whole file (see `open`)
+pub fn read_all(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = open(path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|e| read_error(path, e))?;
+    Ok(bytes)
+}
+
+/// `Failed to read file ...`, or `VolumeUnavailable` for an error of a device or share
+/// that is gone
+fn read_error(path: &Path, error: std::io::Error) -> String {
+    crate::volumes::classify(path, &error, None)
+        .unwrap_or_else(|| format!("Failed to read file {}: {}", path.to_string_lossy(), error))
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::c_void;
+    use std::fs::{File, OpenOptions};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    const ERROR_MORE_DATA: u32 = 234;
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const FILE_SHARE_READ_WRITE_DELETE: u32 = 0x1 | 0x2 | 0x4;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const CCH_RM_SESSION_KEY: usize = 32;
+    const CCH_RM_MAX_APP_NAME: usize = 255;
+    const CCH_RM_MAX_SVC_NAME: usize = 63;
+
+    /// Attempts with all sharing modes, for a lock that is about to be released
+    const SHARED_ATTEMPTS: u32 = 3;
+    const RETRY_DELAY: Duration = Duration::from_millis(150);
+
+    #[repr(C)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[repr(C)]
+    struct RmUniqueProcess {
+        process_id: u32,
+        start_time: FileTime,
+    }
+
+    #[repr(C)]
+    struct RmProcessInfo {
+        process: RmUniqueProcess,
+        app_name: [u16; CCH_RM_MAX_APP_NAME + 1],
+        service_short_name: [u16; CCH_RM_MAX_SVC_NAME + 1],
+        application_type: i32,
+        app_status: u32,
+        ts_session_id: u32,
+        restartable: i32,
+    }
+
+    #[link(name = "rstrtmgr")]
+    extern "system" {
+        fn RmStartSession(session: *mut u32, flags: u32, key: *mut u16) -> u32;
+        fn RmRegisterResources(
+            session: u32,
+            files: u32,
+            file_names: *const *const u16,
+            applications: u32,
+            processes: *const c_void,
+            services: u32,
+            service_names: *const *const u16,
+        ) -> u32;
+        fn RmGetList(
+            session: u32,
+            needed: *mut u32,
+            count: *mut u32,
+            info: *mut RmProcessInfo,
+            reasons: *mut u32,
+        ) -> u32;
+        fn RmEndSession(session: u32) -> u32;
+    }
+
+    pub(super) fn is_locked(error: &std::io::Error) -> bool {
+        matches!(
+            error.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+    }
+
+    /// Read-only access that tolerates every kind of sharing the holder allows, then the
+    /// same with backup semantics (which skips access checks for users holding the backup
+    /// privilege)
+    pub(super) fn open_shared(path: &Path) -> Option<File> {
+        let mut options = OpenOptions::new();
+        options
+            .access_mode(GENERIC_READ)
+            .share_mode(FILE_SHARE_READ_WRITE_DELETE);
+        for attempt in 0..SHARED_ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(RETRY_DELAY);
+            }
+            match options.open(path) {
+                Ok(file) => return Some(file),
+                Err(e) if is_locked(&e) => continue,
+                Err(_) => break,
+            }
+        }
+        options
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)
+            .ok()
+    }
+
+    /// Names of the applications Restart Manager reports as using `path`
+    pub(super) fn locking_processes(path: &Path) -> Vec<String> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        let mut session = 0;
+        let mut key = [0u16; CCH_RM_SESSION_KEY + 1];
+        // SAFETY: the pointers refer to live buffers of the sizes the API documents, and
+        // the session is ended before returning
+        unsafe {
+            if RmStartSession(&mut session, 0, key.as_mut_ptr()) != 0 {
+                return Vec::new();
+            }
+            let files = [wide.as_ptr()];
+            let mut names = Vec::new();
+            if RmRegisterResources(
+                session,
+                1,
+                files.as_ptr(),
+                0,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+            ) == 0
+            {
+                let mut needed = 0;
+                let mut count = 0;
+                let mut reasons = 0;
+                let mut result = RmGetList(
+                    session,
+                    &mut needed,
+                    &mut count,
+                    std::ptr::null_mut(),
+                    &mut reasons,
+                );
+                if result == ERROR_MORE_DATA && needed > 0 {
+                    let mut infos: Vec<RmProcessInfo> =
+                        (0..needed).map(|_| std::mem::zeroed()).collect();
+                    count = needed;
+                    result = RmGetList(
+                        session,
+                        &mut needed,
+                        &mut count,
+                        infos.as_mut_ptr(),
+                        &mut reasons,
+                    );
+                    if result == 0 {
+                        for info in infos.iter().take(count as usize) {
+                            let len = info
+                                .app_name
+                                .iter()
+                                .position(|&c| c == 0)
+                                .unwrap_or(info.app_name.len());
+                            let name = String::from_utf16_lossy(&info.app_name[..len]);
+                            if !name.is_empty() && !names.contains(&name) {
+                                names.push(name);
+                            }
+                        }
+                    }
+                }
+            }
+            RmEndSession(session);
+            names
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use std::fs::File;
+    use std::path::Path;
+
+    const ETXTBSY: i32 = 26;
+
+    /// `EBUSY` and `ETXTBSY`, from files held by mandatory locks or network file systems
+    pub(super) fn is_locked(error: &std::io::Error) -> bool {
+        error.kind() == std::io::ErrorKind::ResourceBusy || error.raw_os_error() == Some(ETXTBSY)
+    }
+
+    pub(super) fn open_shared(_path: &Path) -> Option<File> {
+        None
+    }
+
+    /// Finding the processes holding a file takes root on most systems
+    pub(super) fn locking_processes(_path: &Path) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Read a whole file. Small local files are read at once; others in chunks, calling
+/// `on_progress` once the read takes noticeably long (right away for placeholders) and
+/// `check_cancelled` between chunks.
+pub fn read(
+    path: &Path,
+    mut on_progress: impl FnMut(ReadProgress),
+    check_cancelled: impl Fn() -> Result<(), String>,
+) -> Result<Vec<u8>, String> {
+    let display = path.to_string_lossy();
+    let fail = |e: std::io::Error| read_error(path, e);
+
+    let mut file = open(path)?;
+    let metadata = file.metadata().map_err(fail)?;
+    let hydrating = is_placeholder(&metadata);
+    let total = metadata.len();
+    if !hydrating && total <= FAST_PATH_MAX {
+        let mut bytes = Vec::with_capacity(total as usize);
+        file.read_to_end(&mut bytes).map_err(fail)?;
+        return Ok(bytes);
+    }
+
+    let started = Instant::now();
+    let mut last_report: Option<Instant> = None;
+    let mut report = |read: u64, force: bool| {
+        let now = Instant::now();
+        let due = match last_report {
+            None => hydrating || now - started >= PROGRESS_DELAY,
+            Some(last) => force || now - last >= PROGRESS_INTERVAL,
+        };
+        if due {
+            last_report = Some(now);
+            on_progress(ReadProgress {
+                path: &display,
+                read,
+                total,
+                hydrating,
+            });
+        }
+    };
+
+    report(0, false);
+    let mut bytes = Vec::with_capacity(total as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        check_cancelled()?;
+        let n = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(fail(e)),
+        };
+        bytes.extend_from_slice(&chunk[..n]);
+        report(bytes.len() as u64, false);
+    }
+    // Only reads that reported progress report their end
+    report(bytes.len() as u64, true);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_reads_and_cancellation() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let small = dir.join("small.eml");
+        let large = dir.join("large.eml");
+        std::fs::write(&small, b"Subject: x\r\n\r\nbody").unwrap();
+        let content: Vec<u8> = (0..CHUNK_SIZE * 3 + 17).map(|i| i as u8).collect();
+        std::fs::write(&large, &content).unwrap();
+
+        let no_progress = |_: ReadProgress| panic!("fast reads report no progress");
+        assert_eq!(
+            read(&small, no_progress, || Ok(())).unwrap(),
+            b"Subject: x\r\n\r\nbody"
+        );
+        assert_eq!(read(&large, |_| {}, || Ok(())).unwrap(), content);
+
+        let checks = std::cell::Cell::new(0);
+        let cancelled = read(
+            &large,
+            |_| {},
+            || {
+                checks.set(checks.get() + 1);
+                if checks.get() > 1 {
+                    Err(crate::jobs::CANCELLED.to_string())
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(cancelled, Err(crate::jobs::CANCELLED.to_string()));
+        assert_eq!(checks.get(), 2);
+
+        assert!(read(&dir.join("missing.eml"), |_| {}, || Ok(())).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_and_locked_files_get_their_own_errors() {
+        use crate::i18n::{Lang, Localized};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("private.msg");
+        std::fs::write(&path, b"x").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let shown = path.to_string_lossy();
+        let denied = format!("{}: {}", ACCESS_DENIED, shown);
+        // Root reads the file all the same
+        if File::open(&path).is_err() {
+            assert_eq!(open(&path).unwrap_err(), denied);
+            assert_eq!(read_all(&path).unwrap_err(), denied);
+        }
+
+        let eacces = std::io::Error::from_raw_os_error(libc::EACCES);
+        assert_eq!(open_error(&path, eacces), denied);
+        let localized = Localized::from(denied);
+        assert_eq!(localized.code, "error.access_denied");
+        assert_eq!(
+            localized.text(Lang::En),
+            format!("You don't have permission to read {}", shown)
+        );
+
+        for errno in [libc::EBUSY, libc::ETXTBSY] {
+            assert!(platform::is_locked(&std::io::Error::from_raw_os_error(
+                errno
+            )));
+        }
+        assert!(!platform::is_locked(&std::io::Error::from_raw_os_error(
+            libc::EACCES
+        )));
+        let locked = Localized::from(locked_error(&path, &[]));
+        assert_eq!(locked.code, "error.file_locked");
+        let locked = Localized::from(locked_error(&path, &["Outlook".to_string()]));
+        assert_eq!(locked.code, "error.file_locked_by");
+    }
+}
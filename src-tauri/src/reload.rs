@@ -0,0 +1,181 @@
+//! Reloading an open document after the file was edited elsewhere (a header stripped with
+//! a script, the message re-saved by another client). What the viewer last showed of each
+//! document is kept as a [`Snapshot`], so a reload can tell the frontend which fields and
+//! attachments changed and it can highlight them instead of rebuilding the view.
+
+use crate::i18n::Localized;
+use crate::parser::{sha256_hex, Message};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Fingerprint of a parse result: a hash of every field as the frontend receives it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    fields: BTreeMap<String, String>,
+    attachments: Vec<AttachmentRef>,
+}
+
+/// An attachment as seen by a reload, matched by name and content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AttachmentRef {
+    pub index: usize,
+    pub file_name: String,
+    pub size: usize,
+    pub sha256: String,
+}
+
+/// What changed between two parses of a document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MessageDiff {
+    /// Top-level `Message` fields with a different value, attachments aside
+    pub changed_fields: Vec<String>,
+    pub added_attachments: Vec<AttachmentRef>,
+    pub removed_attachments: Vec<AttachmentRef>,
+}
+
+impl MessageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed_fields.is_empty()
+            && self.added_attachments.is_empty()
+            && self.removed_attachments.is_empty()
+    }
+}
+
+/// Payload of the `document-reloaded` event, also returned by `reload_document`
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentReloaded {
+    pub doc_id: String,
+    pub path: String,
+    /// The new parse result; `None` when the file no longer parses and the old view stays
+    pub message: Option<Message>,
+    /// `None` when the document wasn't parsed before in this session, or on failure
+    pub diff: Option<MessageDiff>,
+    /// Why the file couldn't be parsed again
+    pub error: Option<Localized>,
+}
+
+impl Snapshot {
+    pub fn of(message: &Message) -> Self {
+        let fields = match serde_json::to_value(message) {
+            Ok(serde_json::Value::Object(fields)) => fields
+                .into_iter()
+                .filter(|(name, _)| name != "attachments")
+                .map(|(name, value)| (name, sha256_hex(value.to_string().as_bytes())))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        let attachments = message
+            .attachments
+            .iter()
+            .map(|attachment| AttachmentRef {
+                index: attachment.index,
+                file_name: attachment.file_name.clone(),
+                size: attachment.size,
+                sha256: attachment.sha256.clone(),
+            })
+            .collect();
+        Self {
+            fields,
+            attachments,
+        }
+    }
+
+    /// Approximate bytes held, for the memory report
+    pub fn size(&self) -> usize {
+        let fields: usize = self
+            .fields
+            .iter()
+            .map(|(name, hash)| name.len() + hash.len())
+            .sum();
+        let attachments: usize = self
+            .attachments
+            .iter()
+            .map(|attachment| {
+                std::mem::size_of::<AttachmentRef>()
+                    + attachment.file_name.len()
+                    + attachment.sha256.len()
+            })
+            .sum();
+        std::mem::size_of::<Self>() + fields + attachments
+    }
+
+    /// Changes from `self` to `new`; an attachment renamed or with new content counts as
+    /// removed and added
+    pub fn diff(&self, new: &Snapshot) -> MessageDiff {
+        let changed_fields = self
+            .fields
+            .keys()
+            .chain(
+                new.fields
+                    .keys()
+                    .filter(|name| !self.fields.contains_key(*name)),
+            )
+            .filter(|name| self.fields.get(*name) != new.fields.get(*name))
+            .cloned()
+            .collect();
+
+        let same = |a: &AttachmentRef, b: &AttachmentRef| {
+            a.sha256 == b.sha256 && a.file_name == b.file_name
+        };
+        // Matched one to one, so a second copy of an attachment counts as added
+        let mut unmatched: Vec<&AttachmentRef> = self.attachments.iter().collect();
+        let mut added_attachments = Vec::new();
+        for attachment in &new.attachments {
+            match unmatched.iter().position(|old| same(old, attachment)) {
+                Some(i) => {
+                    unmatched.remove(i);
+                }
+                None => added_attachments.push(attachment.clone()),
+            }
+        }
+        MessageDiff {
+            changed_fields,
+            added_attachments,
+            removed_attachments: unmatched.into_iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_bytes, MessageFormat, ParseOptions};
+
+    fn parse(data: &str) -> Snapshot {
+        let message = parse_bytes(
+            MessageFormat::Eml,
+            data.as_bytes(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        Snapshot::of(&message)
+    }
+
+    #[test]
+    fn reloads_report_changed_fields_and_attachments() {
+        let original = "From: a@example.com\r\nSubject: Draft\r\nX-Bad: \x01\r\n\
+            Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+            Content-Type: text/plain\r\n\r\nHello\r\n--b\r\n\
+            Content-Type: text/plain; name=a.txt\r\nContent-Disposition: attachment; filename=a.txt\r\n\r\n\
+            first\r\n--b--\r\n";
+        let edited = original
+            .replace("Subject: Draft", "Subject: Final")
+            .replace("X-Bad: \x01\r\n", "")
+            .replace(
+                "filename=a.txt\r\n\r\nfirst",
+                "filename=b.txt\r\n\r\nsecond",
+            );
+
+        let before = parse(original);
+        assert!(before.diff(&parse(original)).is_empty());
+        let diff = before.diff(&parse(&edited));
+        assert!(diff.changed_fields.contains(&"subject".to_string()));
+        assert!(diff.changed_fields.contains(&"headers".to_string()));
+        assert!(!diff.changed_fields.contains(&"from".to_string()));
+        assert!(!diff.changed_fields.contains(&"body_text".to_string()));
+        assert_eq!(diff.removed_attachments.len(), 1);
+        assert_eq!(diff.removed_attachments[0].file_name, "a.txt");
+        assert_eq!(diff.added_attachments.len(), 1);
+        assert_eq!(diff.added_attachments[0].file_name, "b.txt");
+    }
+}
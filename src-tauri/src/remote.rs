@@ -0,0 +1,109 @@
+use crate::parser::trackers::TrackerInfo;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Largest remote image that will be downloaded
+const MAX_IMAGE_SIZE: usize = 10 * 1024 * 1024;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Tracker URLs reported for parsed messages; loading them needs explicit consent
+#[derive(Default)]
+pub struct KnownTrackers(Mutex<HashSet<String>>);
+
+impl KnownTrackers {
+    pub fn record(&self, trackers: &[TrackerInfo]) {
+        let mut known = self.0.lock().unwrap();
+        known.extend(trackers.iter().map(|tracker| tracker.url.clone()));
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.0.lock().unwrap().contains(url.trim())
+    }
+}
+
+/// Download an image and return it as a `data:` URL for the message view
+pub async fn fetch_image(url: &str) -> Result<String, String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let (content_type, data) = fetch_image_bytes(url, MAX_IMAGE_SIZE).await?;
+    Ok(format!(
+        "data:{};base64,{}",
+        content_type,
+        STANDARD.encode(&data)
+    ))
+}
+
+/// Download an image of at most `max_size` bytes; returns its content type and data
+pub async fn fetch_image_bytes(url: &str, max_size: usize) -> Result<(String, Vec<u8>), String> {
+    let lower = url.to_ascii_lowercase();
+    if !lower.starts_with("http://") && !lower.starts_with("https://") {
+        return Err(format!("Not a remote image URL: {}", url));
+    }
+
+    let mut response = client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+    if !content_type.starts_with("image/") {
+        return Err(format!("{} is not an image ({})", url, content_type));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_size as u64)
+    {
+        return Err(format!("Image {} is too large", url));
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+    {
+        data.extend_from_slice(&chunk);
+        if data.len() > max_size {
+            return Err(format!("Image {} is too large", url));
+        }
+    }
+    Ok((content_type, data))
+}
+
+/// Whether `url` answers at all; returns the HTTP status, after redirects
+pub async fn check_reachable(url: &str) -> Result<u16, String> {
+    client()?
+        .head(url)
+        .send()
+        .await
+        .map(|response| response.status().as_u16())
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    if rustls::crypto::CryptoProvider::get_default().is_none() {
+        // Another thread may have installed it in the meantime; either provider is fine
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    }
+    reqwest::Client::builder()
+        .user_agent(concat!("msgReader/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
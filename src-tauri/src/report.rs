@@ -0,0 +1,453 @@
+use crate::export::{self, escape_html};
+use crate::formatting::DateFormatter;
+use crate::i18n::{self, Lang};
+use crate::notes::{self, Notes};
+use crate::parser::{Message, MessageFormat};
+use crate::pdf::{PdfWriter, Style};
+use crate::save;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Body excerpt length in characters
+const EXCERPT_CHARS: usize = 300;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Html,
+    Pdf,
+}
+
+/// Outcome of a review report export
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewReport {
+    /// Annotated messages listed
+    pub messages: usize,
+    /// Of those, the ones whose file is gone or changed
+    pub missing: usize,
+}
+
+/// One annotated message; only the excerpt of its body is kept
+struct Item {
+    path: PathBuf,
+    subject: Option<String>,
+    from: Option<String>,
+    date: Option<String>,
+    tags: Vec<String>,
+    note: String,
+    excerpt: String,
+    /// Why the message itself couldn't be shown
+    problem: Option<String>,
+}
+
+/// Tag groups in name order, then the messages with only a note
+struct Groups {
+    tagged: Vec<(String, Vec<usize>)>,
+    untagged: Vec<usize>,
+}
+
+/// Write a report of every annotated message in `dir` to `target`: metadata, tags, note and
+/// a body excerpt, grouped by tag, with counts per tag. Annotated files last seen in `dir`
+/// that are gone (or were changed, so their hash no longer matches) are listed as such.
+///
+/// Dates are written with `dates`, in its language like the rest of the report.
+/// Messages are loaded one at a time, keeping only excerpts. `before_each(current, total)`
+/// runs before every file of the folder; an error from it (a cancelled job) stops the export
+/// before anything is written.
+pub fn export_review_report(
+    dir: &Path,
+    target: &Path,
+    format: ReportFormat,
+    dates: &DateFormatter,
+    notes: &Notes,
+    load: impl Fn(&Path) -> Result<Message, String>,
+    mut before_each: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<ReviewReport, String> {
+    let lang = dates.lang;
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && MessageFormat::from_path(path).is_some())
+        .collect();
+    paths.sort();
+
+    let mut items = Vec::new();
+    let mut found = HashSet::new();
+    for (current, path) in paths.iter().enumerate() {
+        before_each(current, paths.len())?;
+        let Ok(key) = notes::content_hash(path) else {
+            continue;
+        };
+        notes.record_path(&key, path);
+        let Some(annotation) = notes.annotation(&key) else {
+            continue;
+        };
+        let mut item = Item {
+            path: path.clone(),
+            subject: None,
+            from: None,
+            date: None,
+            tags: annotation.tags,
+            note: annotation.note,
+            excerpt: String::new(),
+            problem: None,
+        };
+        match load(path) {
+            Ok(message) => {
+                item.subject = message.subject.clone();
+                item.from = message.from.as_ref().map(export::format_address);
+                item.date = Some(dates.message_date(&message));
+                item.excerpt = excerpt(&export::body_text(&message, true));
+            }
+            Err(error) => {
+                item.problem = Some(i18n::text(lang, "report.unreadable", &[("detail", error)]))
+            }
+        }
+        items.push(item);
+        found.insert(key);
+    }
+
+    let listed = items.len();
+    for annotation in notes.list() {
+        let Some(path) = annotation.last_path else {
+            continue;
+        };
+        if found.contains(&annotation.path_hash) || path.parent() != Some(dir) {
+            continue;
+        }
+        let code = if path.exists() {
+            "report.changed"
+        } else {
+            "report.missing"
+        };
+        items.push(Item {
+            problem: Some(i18n::text(lang, code, &[])),
+            path,
+            subject: None,
+            from: None,
+            date: None,
+            tags: annotation.tags,
+            note: annotation.note,
+            excerpt: String::new(),
+        });
+    }
+
+    let groups = group(&items);
+    let generated = dates.now();
+    let data = match format {
+        ReportFormat::Html => render_html(dir, &generated, &items, &groups, lang),
+        ReportFormat::Pdf => render_pdf(dir, &generated, &items, &groups, lang)
+            .map_err(|e| format!("Failed to render report: {}", e))?,
+    };
+    save::write_atomic(target, &data)?;
+
+    Ok(ReviewReport {
+        messages: items.len(),
+        missing: items.len() - listed,
+    })
+}
+
+/// The start of a body on one line
+fn excerpt(body: &str) -> String {
+    let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= EXCERPT_CHARS {
+        return collapsed;
+    }
+    let mut excerpt: String = collapsed.chars().take(EXCERPT_CHARS).collect();
+    if let Some(space) = excerpt.rfind(' ') {
+        excerpt.truncate(space);
+    }
+    excerpt.push('…');
+    excerpt
+}
+
+/// Messages by tag (case-insensitive, in the spelling first met); a message with several
+/// tags is listed under each
+fn group(items: &[Item]) -> Groups {
+    let mut tagged: BTreeMap<String, (String, Vec<usize>)> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        if item.tags.is_empty() {
+            untagged.push(index);
+        }
+        for tag in &item.tags {
+            tagged
+                .entry(tag.to_lowercase())
+                .or_insert_with(|| (tag.clone(), Vec::new()))
+                .1
+                .push(index);
+        }
+    }
+    Groups {
+        tagged: tagged.into_values().collect(),
+        untagged,
+    }
+}
+
+impl Groups {
+    /// Every group with its heading
+    fn iter(&self, lang: Lang) -> impl Iterator<Item = (String, &[usize])> {
+        let untagged = (!self.untagged.is_empty())
+            .then(|| (i18n::text(lang, "report.untagged", &[]), &self.untagged[..]));
+        self.tagged
+            .iter()
+            .map(|(tag, items)| (tag.clone(), &items[..]))
+            .chain(untagged)
+    }
+}
+
+fn title(item: &Item, lang: Lang) -> String {
+    item.subject
+        .clone()
+        .filter(|subject| !subject.trim().is_empty())
+        .or_else(|| {
+            item.path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| i18n::text(lang, "report.no_subject", &[]))
+}
+
+/// Label/value lines of an item, in display order
+fn fields(item: &Item, lang: Lang) -> Vec<(String, String)> {
+    let label = |code: &str| i18n::text(lang, code, &[]);
+    let mut fields = Vec::new();
+    if let Some(from) = &item.from {
+        fields.push((label("export.from"), from.clone()));
+    }
+    if let Some(date) = &item.date {
+        fields.push((label("export.date"), date.clone()));
+    }
+    fields.push((label("report.file"), item.path.display().to_string()));
+    if !item.tags.is_empty() {
+        fields.push((label("report.tags"), item.tags.join(", ")));
+    }
+    if !item.note.is_empty() {
+        fields.push((label("report.note"), item.note.clone()));
+    }
+    fields
+}
+
+fn render_html(
+    dir: &Path,
+    generated: &str,
+    items: &[Item],
+    groups: &Groups,
+    lang: Lang,
+) -> Vec<u8> {
+    let text = |code: &str, args: &[(&str, String)]| escape_html(&i18n::text(lang, code, args));
+    let mut out = String::new();
+    out.push_str(&format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }}\n\
+         section {{ border-left: 3px solid #ccc; padding-left: 1em; margin: 1.5em 0; }}\n\
+         section.missing {{ border-color: #c00; }}\n\
+         .problem {{ color: #c00; font-weight: bold; }}\n\
+         dt {{ float: left; clear: left; width: 8em; color: #666; }}\n\
+         dd {{ margin-left: 9em; white-space: pre-wrap; }}\n\
+         blockquote {{ color: #555; margin: 0.5em 0; }}\n\
+         td, th {{ text-align: left; padding: 0.2em 1em 0.2em 0; }}\n\
+         </style>\n</head>\n<body>\n",
+        lang.tag(),
+        text("report.title", &[])
+    ));
+    out.push_str(&format!("<h1>{}</h1>\n", text("report.title", &[])));
+    out.push_str(&format!(
+        "<p>{}<br>\n{}</p>\n",
+        text("report.folder", &[("path", dir.display().to_string())]),
+        text("report.generated", &[("date", generated.to_string())])
+    ));
+
+    out.push_str("<table>\n");
+    for (heading, indices) in groups.iter(lang) {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&heading),
+            indices.len()
+        ));
+    }
+    out.push_str(&format!(
+        "<tr><th>{}</th><th>{}</th></tr>\n</table>\n",
+        text("report.total", &[]),
+        items.len()
+    ));
+
+    for (heading, indices) in groups.iter(lang) {
+        out.push_str(&format!(
+            "<h2>{} ({})</h2>\n",
+            escape_html(&heading),
+            indices.len()
+        ));
+        for &index in indices {
+            let item = &items[index];
+            let class = if item.problem.is_some() {
+                " class=\"missing\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "<section{}>\n<h3>{}</h3>\n",
+                class,
+                escape_html(&title(item, lang))
+            ));
+            if let Some(problem) = &item.problem {
+                out.push_str(&format!(
+                    "<p class=\"problem\">{}</p>\n",
+                    escape_html(problem)
+                ));
+            }
+            out.push_str("<dl>\n");
+            for (label, value) in fields(item, lang) {
+                out.push_str(&format!(
+                    "<dt>{}</dt><dd>{}</dd>\n",
+                    escape_html(&label),
+                    escape_html(&value)
+                ));
+            }
+            out.push_str("</dl>\n");
+            if !item.excerpt.is_empty() {
+                out.push_str(&format!(
+                    "<blockquote>{}</blockquote>\n",
+                    escape_html(&item.excerpt)
+                ));
+            }
+            out.push_str("</section>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out.into_bytes()
+}
+
+fn render_pdf(
+    dir: &Path,
+    generated: &str,
+    items: &[Item],
+    groups: &Groups,
+    lang: Lang,
+) -> std::io::Result<Vec<u8>> {
+    let text = |code: &str, args: &[(&str, String)]| i18n::text(lang, code, args);
+    let mut pdf = PdfWriter::new(Vec::new())?;
+    pdf.text(Style::Title, &text("report.title", &[]))?;
+    pdf.space(6.0);
+    pdf.text(
+        Style::Small,
+        &text("report.folder", &[("path", dir.display().to_string())]),
+    )?;
+    pdf.text(
+        Style::Small,
+        &text("report.generated", &[("date", generated.to_string())]),
+    )?;
+    pdf.space(10.0);
+    for (heading, indices) in groups.iter(lang) {
+        pdf.text(Style::Body, &format!("{}: {}", heading, indices.len()))?;
+    }
+    pdf.text(
+        Style::Subheading,
+        &format!("{}: {}", text("report.total", &[]), items.len()),
+    )?;
+
+    for (heading, indices) in groups.iter(lang) {
+        pdf.space(16.0);
+        pdf.text(Style::Heading, &format!("{} ({})", heading, indices.len()))?;
+        for &index in indices {
+            let item = &items[index];
+            pdf.space(8.0);
+            pdf.text(Style::Subheading, &title(item, lang))?;
+            if let Some(problem) = &item.problem {
+                pdf.text(Style::Subheading, problem)?;
+            }
+            for (label, value) in fields(item, lang) {
+                pdf.text(Style::Small, &format!("{}: {}", label, value))?;
+            }
+            if !item.excerpt.is_empty() {
+                pdf.space(2.0);
+                pdf.text(Style::Body, &item.excerpt)?;
+            }
+        }
+    }
+    let mut data = pdf.finish()?;
+    data.flush()?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParseOptions};
+
+    #[test]
+    fn report_groups_by_tag_and_marks_missing_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let dir = dir.canonicalize().unwrap();
+        let notes = Notes::in_memory();
+        for (name, subject, tags) in [
+            (
+                "a.eml",
+                "Contract <draft>",
+                &["Privileged", "responsive"][..],
+            ),
+            ("b.eml", "Lunch", &[][..]),
+            ("gone.eml", "Deleted later", &["privileged"][..]),
+        ] {
+            let path = dir.join(name);
+            let eml = format!(
+                "From: Ann <ann@example.com>\r\nSubject: {}\r\n\r\nPlease review the {} attached.",
+                subject,
+                "contract ".repeat(60)
+            );
+            std::fs::write(&path, eml).unwrap();
+            let key = notes::content_hash(&path).unwrap();
+            notes.record_path(&key, &path);
+            for tag in tags {
+                notes.tag_many(std::slice::from_ref(&key), tag).unwrap();
+            }
+            notes.set(&key, &format!("note on {}", name)).unwrap();
+        }
+        std::fs::remove_file(dir.join("gone.eml")).unwrap();
+
+        let load = |path: &Path| parser::parse_file(path, &ParseOptions::default());
+        let target = dir.join("report.html");
+        let report = export_review_report(
+            &dir,
+            &target,
+            ReportFormat::Html,
+            &DateFormatter::default(),
+            &notes,
+            load,
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(report.messages, 3);
+        assert_eq!(report.missing, 1);
+
+        let html = std::fs::read_to_string(&target).unwrap();
+        assert!(html.contains("<h2>Privileged (2)</h2>"));
+        assert!(html.contains("<h2>responsive (1)</h2>"));
+        assert!(html.contains("<h2>Untagged (1)</h2>"));
+        assert!(html.contains("Contract &lt;draft&gt;"));
+        assert!(html.contains("note on gone.eml"));
+        assert!(html.contains("<p class=\"problem\">The file no longer exists</p>"));
+        assert!(html.contains("contract contract…</blockquote>"));
+
+        let target = dir.join("report.pdf");
+        export_review_report(
+            &dir,
+            &target,
+            ReportFormat::Pdf,
+            &DateFormatter {
+                lang: Lang::De,
+                ..DateFormatter::default()
+            },
+            &notes,
+            load,
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert!(std::fs::read(&target).unwrap().starts_with(b"%PDF-"));
+    }
+}
@@ -0,0 +1,382 @@
+//! How long the files the app writes besides settings and notes are kept: extracted temp
+//! files, cached view states, the session file and logs. Cleanup runs at startup, at exit
+//! and on request (`run_cleanup_now`); paranoid mode keeps caches off the disk entirely.
+
+use crate::settings::SettingsStore;
+use crate::{audit, drafts, integrity, logging, notes, session, tempdir, view_state, workspaces};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// When files of one kind are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    /// When the app exits
+    OnExit,
+    /// This many days after they were last written or used
+    Days(u32),
+    Forever,
+}
+
+/// Retention of each kind of file the app writes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Extracted attachments and pasted messages in the temp directory; those of earlier
+    /// runs that didn't exit cleanly go at the next startup for `OnExit`
+    pub temp: Retention,
+    /// View states (scroll positions and the like) of opened files
+    pub caches: Retention,
+    /// The log file and crash reports; `Days` cuts the older lines from the log
+    pub logs: Retention,
+    /// Forwards being put together and never written, by when they were last changed
+    pub drafts: Retention,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            temp: Retention::OnExit,
+            caches: Retention::Days(30),
+            logs: Retention::Days(7),
+            drafts: Retention::Days(14),
+        }
+    }
+}
+
+/// What a cleanup deleted
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    pub deleted: Vec<DeletedFile>,
+    /// View states dropped from the cache
+    pub view_states: usize,
+    /// Unfinished drafts deleted
+    pub drafts: usize,
+    pub bytes_reclaimed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedFile {
+    pub path: String,
+    pub bytes: u64,
+    /// Only old lines were cut from the file
+    pub trimmed: bool,
+}
+
+impl CleanupReport {
+    fn push(&mut self, path: &Path, bytes: u64, trimmed: bool) {
+        self.bytes_reclaimed += bytes;
+        self.deleted.push(DeletedFile {
+            path: path.display().to_string(),
+            bytes,
+            trimmed,
+        });
+    }
+}
+
+/// Where the app keeps things, as listed by `get_storage_paths`
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageLocation {
+    /// `settings`, `notes`, `workspaces`, `integrity_baselines`, `drafts`, `view_states`,
+    /// `session`, `logs`, `audit_log` or `temp`
+    pub name: &'static str,
+    pub path: Option<String>,
+    /// Written to disk at the moment; paranoid mode keeps view states, the session and
+    /// the log in memory. Extracted files need the disk to be opened by other apps.
+    pub on_disk: bool,
+    /// For `temp`: which location is in use, and why the ones before it were skipped
+    pub usage: Option<tempdir::Usage>,
+}
+
+/// Delete what the retention policy says is due; `at_exit` adds what goes on exit
+pub fn run(app: &AppHandle, at_exit: bool) -> CleanupReport {
+    let policy = app.state::<SettingsStore>().get().retention;
+    let mut report = CleanupReport::default();
+    clean_temp(
+        &tempdir::session_dir_path(),
+        &tempdir::leftover_session_dirs(),
+        policy.temp,
+        at_exit,
+        &mut report,
+    );
+    // Where this run wrote before falling back to another location
+    for dir in tempdir::abandoned_session_dirs() {
+        clean_temp(&dir, &[], policy.temp, at_exit, &mut report);
+    }
+    clean_caches(app, policy.caches, at_exit, &mut report);
+    clean_logs(policy.logs, at_exit, &mut report);
+    clean_drafts(app, policy.drafts, at_exit, &mut report);
+    // After an exit cleanup of the log, this line would start it over
+    if !at_exit && (!report.deleted.is_empty() || report.view_states > 0 || report.drafts > 0) {
+        log_line!(
+            "Cleanup deleted {} files, {} view states and {} drafts, {} bytes",
+            report.deleted.len(),
+            report.view_states,
+            report.drafts,
+            report.bytes_reclaimed
+        );
+    }
+    report
+}
+
+/// Keep view states, the session and the log in memory only (turning paranoid mode on,
+/// which deletes their files), or write them to disk again
+pub fn apply_paranoid_mode(app: &AppHandle, paranoid: bool) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let view_states = app.state::<view_state::ViewStates>();
+    let path = view_states.file_path().map(Path::to_path_buf);
+    match (view_states.set_on_disk(!paranoid), path) {
+        (Ok(Some(bytes)), Some(path)) => report.push(&path, bytes, false),
+        (Err(e), _) => log_line!("Failed to move view states: {}", e),
+        _ => {}
+    }
+    logging::set_file_enabled(!paranoid);
+    if paranoid {
+        for (path, bytes) in logging::delete_files() {
+            report.push(&path, bytes, false);
+        }
+        if let Some(path) = session::session_path(app) {
+            remove_file(&path, &mut report);
+        }
+    }
+    report
+}
+
+pub fn storage_locations(app: &AppHandle) -> Vec<StorageLocation> {
+    let paranoid = app.state::<SettingsStore>().get().paranoid_mode;
+    let location = |name, path: Option<&Path>, on_disk: bool| StorageLocation {
+        name,
+        path: path.map(|path| path.display().to_string()),
+        on_disk: on_disk && path.is_some(),
+        usage: None,
+    };
+    let view_states = app.state::<view_state::ViewStates>();
+    let view_state_path = view_states.file_path();
+    vec![
+        location("settings", app.state::<SettingsStore>().path(), true),
+        location("notes", app.state::<notes::Notes>().file_path(), true),
+        location(
+            "workspaces",
+            app.state::<workspaces::Workspaces>().file_path(),
+            true,
+        ),
+        location(
+            "integrity_baselines",
+            app.state::<integrity::Baselines>().file_path(),
+            true,
+        ),
+        location("drafts", app.state::<drafts::Drafts>().file_path(), true),
+        location("view_states", view_state_path, view_state_path.is_some()),
+        location("session", session::session_path(app).as_deref(), !paranoid),
+        location(
+            "logs",
+            logging::log_file_path().as_deref(),
+            logging::file_enabled(),
+        ),
+        location(
+            "audit_log",
+            app.state::<audit::AuditLog>().dir(),
+            app.state::<SettingsStore>().get().audit_log,
+        ),
+        StorageLocation {
+            usage: Some(tempdir::usage()),
+            ..location("temp", Some(&tempdir::session_dir_path()), true)
+        },
+    ]
+}
+
+fn clean_temp(
+    current: &Path,
+    leftovers: &[PathBuf],
+    retention: Retention,
+    at_exit: bool,
+    report: &mut CleanupReport,
+) {
+    match retention {
+        Retention::Forever => {}
+        Retention::OnExit => {
+            for dir in leftovers {
+                remove_dir(dir, report);
+            }
+            if at_exit {
+                remove_dir(current, report);
+            }
+        }
+        Retention::Days(days) => {
+            let cutoff = SystemTime::now() - DAY * days;
+            remove_files_before(current, cutoff, report);
+            for dir in leftovers {
+                remove_files_before(dir, cutoff, report);
+                // Gone once empty
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+    }
+}
+
+fn clean_caches(app: &AppHandle, retention: Retention, at_exit: bool, report: &mut CleanupReport) {
+    let view_states = app.state::<view_state::ViewStates>();
+    let removed = match retention {
+        Retention::Forever => Ok(0),
+        Retention::OnExit if at_exit => view_states.clear(),
+        Retention::OnExit => Ok(0),
+        Retention::Days(days) => {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days.into());
+            view_states.remove_unused_since(cutoff.timestamp())
+        }
+    };
+    match removed {
+        Ok(removed) => report.view_states += removed,
+        Err(e) => log_line!("Failed to clean up view states: {}", e),
+    }
+}
+
+fn clean_drafts(app: &AppHandle, retention: Retention, at_exit: bool, report: &mut CleanupReport) {
+    let drafts = app.state::<drafts::Drafts>();
+    let removed = match retention {
+        Retention::Forever => Ok(0),
+        Retention::OnExit if at_exit => drafts.remove_updated_before(i64::MAX),
+        Retention::OnExit => Ok(0),
+        Retention::Days(days) => {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days.into());
+            drafts.remove_updated_before(cutoff.timestamp())
+        }
+    };
+    match removed {
+        Ok(removed) => report.drafts += removed,
+        Err(e) => log_line!("Failed to clean up drafts: {}", e),
+    }
+}
+
+fn clean_logs(retention: Retention, at_exit: bool, report: &mut CleanupReport) {
+    match retention {
+        Retention::Forever => {}
+        Retention::OnExit => {
+            if at_exit {
+                for (path, bytes) in logging::delete_files() {
+                    report.push(&path, bytes, false);
+                }
+            }
+        }
+        Retention::Days(days) => {
+            let cutoff = chrono::Local::now() - chrono::Duration::days(days.into());
+            match logging::trim_before(cutoff.naive_local()) {
+                Ok(0) => {}
+                Ok(bytes) => {
+                    if let Some(path) = logging::log_file_path() {
+                        report.push(&path, bytes, true);
+                    }
+                }
+                Err(e) => log_line!("Failed to clean up the log: {}", e),
+            }
+            if let Some(crash) = logging::crash_report_path() {
+                if modified_before(&crash, SystemTime::now() - DAY * days) {
+                    remove_file(&crash, report);
+                }
+            }
+        }
+    }
+}
+
+fn modified_before(path: &Path, cutoff: SystemTime) -> bool {
+    std::fs::symlink_metadata(path)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| modified < cutoff)
+}
+
+fn remove_file(path: &Path, report: &mut CleanupReport) {
+    let bytes = std::fs::symlink_metadata(path)
+        .map(|meta| meta.len())
+        .unwrap_or(0);
+    if std::fs::remove_file(path).is_ok() {
+        report.push(path, bytes, false);
+    }
+}
+
+/// Files in `dir` and below last modified before `cutoff`
+fn remove_files_before(dir: &Path, cutoff: SystemTime, report: &mut CleanupReport) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            remove_files_before(&path, cutoff, report);
+            let _ = std::fs::remove_dir(&path);
+        } else if modified_before(&path, cutoff) {
+            remove_file(&path, report);
+        }
+    }
+}
+
+fn remove_dir(dir: &Path, report: &mut CleanupReport) {
+    let bytes = dir_size(dir);
+    if std::fs::remove_dir_all(dir).is_ok() {
+        report.push(dir, bytes, false);
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let meta = std::fs::symlink_metadata(entry.path()).ok()?;
+            Some(match meta.is_dir() {
+                true => dir_size(&entry.path()),
+                false => meta.len(),
+            })
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_files_go_by_age_or_at_exit() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let current = root.join("current");
+        let leftover = root.join("leftover");
+        for dir in [&current, &leftover] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let old = SystemTime::now() - DAY * 3;
+        let write = |path: PathBuf, modified: Option<SystemTime>| {
+            std::fs::write(&path, b"12345").unwrap();
+            if let Some(modified) = modified {
+                let file = std::fs::File::options().write(true).open(&path).unwrap();
+                file.set_modified(modified).unwrap();
+            }
+            path
+        };
+        let fresh = write(current.join("fresh.pdf"), None);
+        let stale = write(current.join("stale.pdf"), Some(old));
+        write(leftover.join("stale.txt"), Some(old));
+
+        let mut report = CleanupReport::default();
+        let leftovers = [leftover.clone()];
+        clean_temp(&current, &leftovers, Retention::Days(2), false, &mut report);
+        assert!(fresh.exists() && !stale.exists() && !leftover.exists());
+        assert_eq!(report.deleted.len(), 2);
+        assert_eq!(report.bytes_reclaimed, 10);
+
+        // On exit only at exit
+        let mut report = CleanupReport::default();
+        clean_temp(&current, &[], Retention::OnExit, false, &mut report);
+        assert!(fresh.exists());
+        clean_temp(&current, &[], Retention::OnExit, true, &mut report);
+        assert!(!current.exists());
+        assert_eq!(report.bytes_reclaimed, 5);
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes written (and re-read) per step; progress is reported after each one
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A file written and verified on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedFile {
+    pub path: String,
+    pub size: usize,
+    /// SHA-256 of the written content (hex), for users to record
+    pub sha256: String,
+}
+
+/// Write `data` to `target` in chunks, fsync it and read it back to compare its SHA-256
+/// with `expected_sha256`, so a flaky (network) drive can't corrupt a save unnoticed.
+///
+/// With `atomic`, the data goes to `<target>.partial` first and is only renamed to `target`
+/// once verified. `on_chunk(written, total)` runs after every chunk;
+/// an error from it (cancellation) stops the save and removes what was written.
+pub fn write_verified(
+    target: &Path,
+    data: &[u8],
+    expected_sha256: &str,
+    atomic: bool,
+    mut on_chunk: impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<SavedFile, String> {
+    let output = if atomic {
+        partial_path(target)
+    } else {
+        target.to_path_buf()
+    };
+    let result = write_and_verify(&output, data, expected_sha256, &mut on_chunk).and_then(|()| {
+        if atomic {
+            replace(&output, target)?;
+        }
+        Ok(())
+    });
+    if let Err(error) = result {
+        // Never leave a truncated or corrupt file behind
+        let _ = std::fs::remove_file(&output);
+        return Err(error);
+    }
+
+    Ok(SavedFile {
+        path: target.to_string_lossy().to_string(),
+        size: data.len(),
+        sha256: expected_sha256.to_string(),
+    })
+}
+
+fn write_and_verify(
+    path: &Path,
+    data: &[u8],
+    expected_sha256: &str,
+    on_chunk: &mut impl FnMut(usize, usize) -> Result<(), String>,
+) -> Result<(), String> {
+    let write_error = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut file = std::fs::File::create(path).map_err(write_error)?;
+    let mut written = 0;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        file.write_all(chunk).map_err(write_error)?;
+        written += chunk.len();
+        on_chunk(written, data.len())?;
+    }
+    file.sync_all().map_err(write_error)?;
+    drop(file);
+
+    let actual =
+        file_sha256(path).map_err(|e| format!("Failed to verify {}: {}", path.display(), e))?;
+    if !actual.eq_ignore_ascii_case(expected_sha256) {
+        return Err(format!(
+            "Verification of {} failed: content on disk differs from the attachment",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// SHA-256 (hex) of a file's content, read in chunks
+pub(crate) fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// `<target>.partial`: next to the target, so the final rename stays on one file system
+pub fn partial_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    target.with_file_name(name)
+}
+
+/// An export being written to `<target>.partial`. `commit` syncs it and renames it over
+/// the target; dropped without that (an error, a cancelled job, a panic), it's removed, so
+/// the target is either what it was before or the complete new file.
+pub struct AtomicOutput {
+    target: PathBuf,
+    partial: PathBuf,
+    file: Option<File>,
+}
+
+impl AtomicOutput {
+    pub fn create(target: &Path) -> Result<Self, String> {
+        let partial = partial_path(target);
+        let file = File::create(&partial)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        Ok(Self {
+            target: target.to_path_buf(),
+            partial,
+            file: Some(file),
+        })
+    }
+
+    pub fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("output already committed")
+    }
+
+    pub fn commit(mut self) -> Result<(), String> {
+        let file = self.file.take().expect("output already committed");
+        file.sync_all()
+            .map_err(|e| format!("Failed to write {}: {}", self.target.display(), e))?;
+        drop(file);
+        replace(&self.partial, &self.target)
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        // Also after a failed commit: the partial file is never left behind
+        self.file = None;
+        if self.partial.exists() {
+            if let Err(e) = std::fs::remove_file(&self.partial) {
+                log_line!("Failed to remove {}: {}", self.partial.display(), e);
+            }
+        }
+    }
+}
+
+/// Write an export to `target` all or nothing: `write` fills the partial file of an
+/// [`AtomicOutput`], which replaces the target only when `write` succeeds
+pub fn atomic_output<T>(
+    target: &Path,
+    write: impl FnOnce(&mut File) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut output = AtomicOutput::create(target)?;
+    let value = write(output.file())?;
+    output.commit()?;
+    Ok(value)
+}
+
+/// [`atomic_output`] of data already in memory
+pub fn write_atomic(target: &Path, data: &[u8]) -> Result<(), String> {
+    atomic_output(target, |file| {
+        file.write_all(data)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+    })
+}
+
+/// Rename `partial` over `target`. On Windows `std::fs::rename` replaces an existing
+/// target (`MoveFileExW` with `MOVEFILE_REPLACE_EXISTING`) but fails while another process
+/// has it open without delete sharing, as virus scanners and indexers briefly do with
+/// files just written, so a denied rename is retried a few times there.
+fn replace(partial: &Path, target: &Path) -> Result<(), String> {
+    let attempts = if cfg!(windows) { 5 } else { 1 };
+    let mut attempt = 1;
+    loop {
+        match std::fs::rename(partial, target) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < attempts && e.kind() == std::io::ErrorKind::PermissionDenied => {
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            Err(e) => return Err(format!("Failed to write {}: {}", target.display(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sha256_hex;
+
+    #[test]
+    fn verified_atomic_save() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let target = dir.join("report.pdf");
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let hash = sha256_hex(&data);
+
+        let mut progress = Vec::new();
+        let saved = write_verified(&target, &data, &hash, true, |written, total| {
+            progress.push((written, total));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(saved.sha256, hash);
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert_eq!(progress.last(), Some(&(data.len(), data.len())));
+        assert_eq!(progress.len(), 3);
+
+        // A mismatching hash fails and leaves no file with the final name
+        std::fs::remove_file(&target).unwrap();
+        let wrong = sha256_hex(b"something else");
+        assert!(write_verified(&target, &data, &wrong, false, |_, _| Ok(())).is_err());
+        assert!(!target.exists());
+
+        // Cancelling an atomic save removes the temporary file
+        let cancelled = write_verified(&target, &data, &hash, true, |_, _| {
+            Err(crate::jobs::CANCELLED.to_string())
+        });
+        assert_eq!(cancelled.unwrap_err(), crate::jobs::CANCELLED);
+        let leftovers = std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn atomic_output_replaces_the_target_only_when_complete() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let target = dir.join("export.txt");
+        std::fs::write(&target, b"previous export").unwrap();
+
+        let cancelled = atomic_output(&target, |file| {
+            file.write_all(b"half of the new").unwrap();
+            Err::<(), _>(crate::jobs::CANCELLED.to_string())
+        });
+        assert_eq!(cancelled.unwrap_err(), crate::jobs::CANCELLED);
+        assert_eq!(std::fs::read(&target).unwrap(), b"previous export");
+        assert!(!partial_path(&target).exists());
+
+        let panicked = std::panic::catch_unwind(|| {
+            atomic_output(&target, |file| -> Result<(), String> {
+                file.write_all(b"half").unwrap();
+                panic!("export crashed");
+            })
+        });
+        assert!(panicked.is_err());
+        assert!(!partial_path(&target).exists());
+
+        write_atomic(&target, b"new export").unwrap();
+        let content = std::fs::read(&target).unwrap();
+        let leftovers = std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(content, b"new export");
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,261 @@
+//! Health check for support requests (`run_self_test`): exercises the critical paths with
+//! built-in data and reports each as a line of copyable text. Diagnostics bundles include
+//! the checks that need no network.
+
+use crate::parser::{self, MessageFormat, ParseOptions};
+use crate::settings::SettingsStore;
+use crate::{launcher, quarantine, remote, tempdir};
+use serde::Serialize;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+const MINIMAL_MSG: &[u8] = include_bytes!("../tests/fixtures/self-test/minimal.msg");
+const MINIMAL_EML: &[u8] = include_bytes!("../tests/fixtures/self-test/minimal.eml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found, or why it failed
+    pub details: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<Check>,
+    /// No check failed (skipped ones don't count)
+    pub passed: bool,
+    /// The report as plain text, for pasting into a support request
+    pub text: String,
+}
+
+/// Run every check; the updater endpoint is only contacted unless `offline` or in
+/// quarantine mode
+pub async fn run(app: &AppHandle, offline: bool) -> SelfTestReport {
+    let mut checks = local_checks(app);
+    if quarantine::active(app) {
+        checks.push(skipped("updater endpoint", "Skipped (quarantine mode)"));
+        return report(app, checks);
+    }
+    checks.push(match (offline, updater_endpoint(app)) {
+        (true, _) => skipped("updater endpoint", "Skipped (offline)"),
+        (false, None) => skipped("updater endpoint", "No updater endpoint configured"),
+        (false, Some(url)) => {
+            let started = Instant::now();
+            let result =
+                remote::check_reachable(&url)
+                    .await
+                    .and_then(|status| match status < 500 {
+                        true => Ok(format!("{} answered with HTTP {}", url, status)),
+                        false => Err(format!("{} answered with HTTP {}", url, status)),
+                    });
+            finish("updater endpoint", started, result)
+        }
+    });
+    report(app, checks)
+}
+
+/// The checks that need no network, as included in diagnostics bundles
+pub fn run_offline(app: &AppHandle) -> SelfTestReport {
+    let mut checks = local_checks(app);
+    checks.push(skipped(
+        "updater endpoint",
+        "Skipped (not run for diagnostics bundles)",
+    ));
+    report(app, checks)
+}
+
+fn local_checks(app: &AppHandle) -> Vec<Check> {
+    vec![
+        check("parse MSG", check_msg),
+        check("parse EML", check_eml),
+        check("temp directory", check_temp_dir),
+        check("settings store", || check_settings(app)),
+        check("open handler for .txt", check_open_handler),
+    ]
+}
+
+fn report(app: &AppHandle, checks: Vec<Check>) -> SelfTestReport {
+    let passed = checks
+        .iter()
+        .all(|check| check.status != CheckStatus::Failed);
+    let info = app.package_info();
+    let mut text = format!(
+        "msgReader {} self-test ({} {}): {}\n",
+        info.version,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        match passed {
+            true => "all checks passed",
+            false => "some checks FAILED",
+        }
+    );
+    for check in &checks {
+        let status = match check.status {
+            CheckStatus::Passed => "PASS",
+            CheckStatus::Failed => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        };
+        text.push_str(&format!(
+            "[{}] {} ({} ms): {}\n",
+            status, check.name, check.duration_ms, check.details
+        ));
+    }
+    SelfTestReport {
+        checks,
+        passed,
+        text,
+    }
+}
+
+fn check(name: &'static str, run: impl FnOnce() -> Result<String, String>) -> Check {
+    let started = Instant::now();
+    // A panicking check fails alone instead of taking the report with it
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(run))
+        .unwrap_or_else(|_| Err("The check panicked".to_string()));
+    finish(name, started, result)
+}
+
+fn finish(name: &'static str, started: Instant, result: Result<String, String>) -> Check {
+    let (status, details) = match result {
+        Ok(details) => (CheckStatus::Passed, details),
+        Err(details) => (CheckStatus::Failed, details),
+    };
+    Check {
+        name,
+        status,
+        details,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+fn skipped(name: &'static str, details: &str) -> Check {
+    Check {
+        name,
+        status: CheckStatus::Skipped,
+        details: details.to_string(),
+        duration_ms: 0,
+    }
+}
+
+fn check_msg() -> Result<String, String> {
+    let message = parser::parse_bytes(MessageFormat::Msg, MINIMAL_MSG, &ParseOptions::default())?;
+    expect(
+        message.subject.as_deref(),
+        Some("Quarterly figures"),
+        "subject",
+    )?;
+    expect(
+        message.attachments.first().map(|a| a.file_name.as_str()),
+        Some("report-0.pdf"),
+        "attachment",
+    )?;
+    if message.body_html.is_none() {
+        return Err("The HTML body is missing".to_string());
+    }
+    Ok(format!(
+        "Subject, HTML body and {} attachment read",
+        message.attachments.len()
+    ))
+}
+
+fn check_eml() -> Result<String, String> {
+    let message = parser::parse_bytes(MessageFormat::Eml, MINIMAL_EML, &ParseOptions::default())?;
+    expect(message.subject.as_deref(), Some("Self-test"), "subject")?;
+    expect(
+        message.body_text.as_deref().map(str::trim_end),
+        Some("Grüße from the self-test."),
+        "body",
+    )?;
+    let attachment = message
+        .attachments
+        .first()
+        .ok_or_else(|| "The attachment is missing".to_string())?;
+    if attachment.data != b"ok" {
+        return Err("The attachment decoded wrong".to_string());
+    }
+    Ok("Subject, quoted-printable body and base64 attachment read".to_string())
+}
+
+fn expect(found: Option<&str>, expected: Option<&str>, what: &str) -> Result<(), String> {
+    match found == expected {
+        true => Ok(()),
+        false => Err(format!(
+            "Unexpected {}: {:?} instead of {:?}",
+            what, found, expected
+        )),
+    }
+}
+
+fn check_temp_dir() -> Result<String, String> {
+    let data = format!("self-test {}", chrono::Local::now().to_rfc3339());
+    let path = tempdir::write_file("self-test.txt", data.as_bytes(), false)?;
+    let read =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+    let removed = std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to delete {}: {}", path.display(), e));
+    if read? != data.as_bytes() {
+        return Err(format!("{} read back different content", path.display()));
+    }
+    removed?;
+    Ok(format!(
+        "Wrote, read and deleted a file in {}",
+        tempdir::session_dir_path().display()
+    ))
+}
+
+/// Saves the settings unchanged, which is what every settings change does
+fn check_settings(app: &AppHandle) -> Result<String, String> {
+    let store = app.state::<SettingsStore>();
+    let Some(path) = store.path().map(|path| path.to_path_buf()) else {
+        return Err("No config directory; settings are kept in memory only".to_string());
+    };
+    store.update(|_| {})?;
+    Ok(format!("Saved {}", path.display()))
+}
+
+/// Looks the handler up without launching it
+fn check_open_handler() -> Result<String, String> {
+    let applications = launcher::applications_for("txt");
+    match applications.is_empty() {
+        true => Err("No application is registered for .txt files".to_string()),
+        false => Ok(applications
+            .iter()
+            .map(|application| application.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")),
+    }
+}
+
+fn updater_endpoint(app: &AppHandle) -> Option<String> {
+    app.config()
+        .plugins
+        .0
+        .get("updater")?
+        .get("endpoints")?
+        .as_array()?
+        .first()?
+        .as_str()
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_fixtures_parse() {
+        check_msg().unwrap();
+        check_eml().unwrap();
+        check_temp_dir().unwrap();
+    }
+}
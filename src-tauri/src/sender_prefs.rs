@@ -0,0 +1,217 @@
+use crate::parser::{charset, DisplayPreferences, Message};
+use crate::sender_rules;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How messages from a sender are shown; unset fields fall back to the domain's
+/// preference, then to the global settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SenderPreference {
+    pub load_remote_images: Option<bool>,
+    pub default_collapse_quotes: Option<bool>,
+    /// Charset label to decode bodies with, for senders that declare the wrong one
+    pub preferred_encoding_override: Option<String>,
+}
+
+impl SenderPreference {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A stored preference, as listed for the settings screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SenderPreferenceEntry {
+    /// `anna@example.com`, or `*@example.com` for a whole domain
+    pub sender: String,
+    pub preference: SenderPreference,
+}
+
+/// Preferences by sender pattern (see `sender_rules::normalize_pattern`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SenderPreferences(BTreeMap<String, SenderPreference>);
+
+impl SenderPreferences {
+    /// Store the preference for an address or domain (`example.com`, `@example.com` and
+    /// `*@example.com` all mean the domain), replacing an earlier one; a preference with
+    /// nothing set removes it. Returns the normalized pattern.
+    pub fn set(
+        &mut self,
+        sender: &str,
+        mut preference: SenderPreference,
+    ) -> Result<String, String> {
+        let pattern = sender_rules::normalize_sender(sender)?;
+        preference.preferred_encoding_override = preference
+            .preferred_encoding_override
+            .map(|label| label.trim().to_ascii_lowercase())
+            .filter(|label| !label.is_empty());
+        if let Some(label) = &preference.preferred_encoding_override {
+            if !charset::is_supported(label) {
+                return Err(format!("Unsupported charset: {}", label));
+            }
+        }
+        match preference.is_empty() {
+            true => self.0.remove(&pattern),
+            false => self.0.insert(pattern.clone(), preference),
+        };
+        Ok(pattern)
+    }
+
+    /// Drop the preference for an address or domain; false if there was none
+    pub fn remove(&mut self, sender: &str) -> Result<bool, String> {
+        Ok(self
+            .0
+            .remove(&sender_rules::normalize_sender(sender)?)
+            .is_some())
+    }
+
+    pub fn list(&self) -> Vec<SenderPreferenceEntry> {
+        self.0
+            .iter()
+            .map(|(sender, preference)| SenderPreferenceEntry {
+                sender: sender.clone(),
+                preference: preference.clone(),
+            })
+            .collect()
+    }
+
+    /// Display preferences for `message`, field by field: the sender's exact address, then
+    /// its domain, then `defaults`. Remote images are only loaded on a sender's preference
+    /// when authentication doesn't contradict the `From` domain, so a spoofed sender can't
+    /// borrow a trusted newsletter's setting to track the reader.
+    pub fn resolve(&self, message: &Message, defaults: DisplayPreferences) -> DisplayPreferences {
+        let Some((address, verified)) = sender_rules::sender_address(message) else {
+            return defaults;
+        };
+        let domain = address
+            .rsplit_once('@')
+            .map(|(_, domain)| format!("*@{}", domain))
+            .unwrap_or_default();
+        let matches: Vec<(&String, &SenderPreference)> = [&address, &domain]
+            .into_iter()
+            .filter_map(|pattern| self.0.get_key_value(pattern))
+            .collect();
+        let pick = |field: fn(&SenderPreference) -> Option<bool>| {
+            matches
+                .iter()
+                .find_map(|(pattern, preference)| Some((*pattern, field(preference)?)))
+        };
+
+        let mut resolved = defaults;
+        let mut applied = Vec::new();
+        if let Some((pattern, load)) = pick(|p| p.load_remote_images).filter(|_| verified) {
+            resolved.load_remote_images = load;
+            applied.push(pattern);
+        }
+        if let Some((pattern, collapse)) = pick(|p| p.default_collapse_quotes) {
+            resolved.collapse_quotes = collapse;
+            applied.push(pattern);
+        }
+        if let Some((pattern, label)) = matches.iter().find_map(|(pattern, preference)| {
+            Some((*pattern, preference.preferred_encoding_override.clone()?))
+        }) {
+            resolved.encoding_override = Some(label);
+            applied.push(pattern);
+        }
+        // The most specific pattern that applied
+        if let Some(pattern) = [&address, &domain]
+            .into_iter()
+            .find(|pattern| applied.contains(pattern))
+        {
+            resolved.matched = Some(pattern.clone());
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Address, MessageFormat};
+
+    fn message(email: &str, authenticated: Option<&str>) -> Message {
+        let mut message = Message::new(MessageFormat::Eml);
+        message.from = Some(Address::new(None, Some(email.to_string())));
+        if let Some(domain) = authenticated {
+            message.headers.push((
+                "Authentication-Results".to_string(),
+                format!("mx.example.net; dmarc=pass header.from={}", domain),
+            ));
+        }
+        message
+    }
+
+    fn defaults() -> DisplayPreferences {
+        DisplayPreferences {
+            load_remote_images: false,
+            collapse_quotes: true,
+            ..DisplayPreferences::default()
+        }
+    }
+
+    #[test]
+    fn address_beats_domain_beats_global_settings() {
+        let mut prefs = SenderPreferences::default();
+        let domain = SenderPreference {
+            load_remote_images: Some(true),
+            default_collapse_quotes: Some(false),
+            ..SenderPreference::default()
+        };
+        assert_eq!(prefs.set("News.Example", domain).unwrap(), "*@news.example");
+        let address = SenderPreference {
+            load_remote_images: Some(false),
+            preferred_encoding_override: Some(" Windows-1252 ".to_string()),
+            ..SenderPreference::default()
+        };
+        prefs.set("Digest@news.example", address).unwrap();
+
+        // The address wins where it has a value, the domain fills the rest
+        let digest = prefs.resolve(&message("digest@news.example", None), defaults());
+        assert!(!digest.load_remote_images);
+        assert!(!digest.collapse_quotes);
+        assert_eq!(digest.encoding_override.as_deref(), Some("windows-1252"));
+        assert_eq!(digest.matched.as_deref(), Some("digest@news.example"));
+
+        let weekly = prefs.resolve(&message("weekly@news.example", None), defaults());
+        assert!(weekly.load_remote_images);
+        assert!(!weekly.collapse_quotes);
+        assert_eq!(weekly.encoding_override, None);
+        assert_eq!(weekly.matched.as_deref(), Some("*@news.example"));
+
+        assert_eq!(
+            prefs.resolve(&message("anna@example.com", None), defaults()),
+            defaults()
+        );
+
+        // A spoofed sender gets the collapsing, not the remote images
+        let spoofed = prefs.resolve(
+            &message("weekly@news.example", Some("attacker.example")),
+            defaults(),
+        );
+        assert!(!spoofed.load_remote_images);
+        assert!(!spoofed.collapse_quotes);
+        assert!(
+            prefs
+                .resolve(
+                    &message("weekly@news.example", Some("news.example")),
+                    defaults()
+                )
+                .load_remote_images
+        );
+
+        assert!(prefs
+            .set(
+                "anna@example.com",
+                SenderPreference {
+                    preferred_encoding_override: Some("klingon".to_string()),
+                    ..SenderPreference::default()
+                }
+            )
+            .is_err());
+        assert!(prefs.remove("*@news.example").unwrap());
+        assert!(!prefs.remove("news.example").unwrap());
+        assert_eq!(prefs.list().len(), 1);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::parser::{idn, Message, SenderTrust};
+use serde::{Deserialize, Serialize};
+
+/// Longest rule accepted, in characters
+const MAX_PATTERN_CHARS: usize = 320;
+
+/// Senders the user trusts or blocks: exact addresses (`anna@example.com`) and whole
+/// domains (`*@example.com`), stored lowercase with punycode domains
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SenderRules {
+    pub trusted: Vec<String>,
+    pub blocked: Vec<String>,
+}
+
+impl SenderRules {
+    /// Add a trusted pattern, dropping it from the blocked ones; false if it was trusted
+    /// already
+    pub fn trust(&mut self, pattern: &str) -> Result<bool, String> {
+        let pattern = normalize_pattern(pattern)?;
+        self.blocked.retain(|p| *p != pattern);
+        Ok(insert(&mut self.trusted, pattern))
+    }
+
+    /// Add a blocked pattern, dropping it from the trusted ones; false if it was blocked
+    /// already
+    pub fn block(&mut self, pattern: &str) -> Result<bool, String> {
+        let pattern = normalize_pattern(pattern)?;
+        self.trusted.retain(|p| *p != pattern);
+        Ok(insert(&mut self.blocked, pattern))
+    }
+
+    /// Add the rules of a shared file; invalid patterns fail the whole import
+    pub fn merge(&mut self, other: &SenderRules) -> Result<(), String> {
+        for pattern in &other.trusted {
+            self.trust(pattern)?;
+        }
+        for pattern in &other.blocked {
+            self.block(pattern)?;
+        }
+        Ok(())
+    }
+
+    /// How far the sender of `message` is known. A blocked address or domain wins. A
+    /// trusted one only counts when the domain that passed authentication (see
+    /// `authenticated_domain`) is the sender's as well, so a spoofed `From` stays unknown;
+    /// messages without Authentication-Results go by `From` alone.
+    pub fn evaluate(&self, message: &Message) -> SenderTrust {
+        let Some(email) = message.from.as_ref().and_then(|from| from.email.as_deref()) else {
+            return SenderTrust::Unknown;
+        };
+        let Some((sender, sender_domain)) = split_address(email) else {
+            return SenderTrust::Unknown;
+        };
+        let authenticated = message
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Authentication-Results"))
+            .map(|(_, value)| authenticated_domain(value));
+
+        let blocked = self.blocked.iter().any(|pattern| {
+            matches_address(pattern, &sender)
+                || authenticated
+                    .as_ref()
+                    .and_then(Option::as_deref)
+                    .is_some_and(|domain| {
+                        is_wildcard(pattern) && pattern_domain(pattern) == Some(domain)
+                    })
+        });
+        if blocked {
+            return SenderTrust::Blocked;
+        }
+        let trusted = self
+            .trusted
+            .iter()
+            .any(|pattern| matches_address(pattern, &sender));
+        let verified = match &authenticated {
+            None => true,
+            Some(domain) => domain.as_deref() == Some(sender_domain.as_str()),
+        };
+        match trusted && verified {
+            true => SenderTrust::Trusted,
+            false => SenderTrust::Unknown,
+        }
+    }
+}
+
+fn insert(patterns: &mut Vec<String>, pattern: String) -> bool {
+    if patterns.contains(&pattern) {
+        return false;
+    }
+    patterns.push(pattern);
+    true
+}
+
+/// The sender of `message` (lowercase, punycode domain) and whether authentication backs
+/// it: true when the domain that passed authentication is the sender's, or the message has
+/// no Authentication-Results to go by
+pub(crate) fn sender_address(message: &Message) -> Option<(String, bool)> {
+    let email = message.from.as_ref()?.email.as_deref()?;
+    let (sender, sender_domain) = split_address(email)?;
+    let verified = message
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Authentication-Results"))
+        .is_none_or(|(_, value)| authenticated_domain(value).as_deref() == Some(&sender_domain));
+    Some((sender, verified))
+}
+
+/// An address or a domain (`example.com`, `@example.com` or `*@example.com`) as a pattern
+pub(crate) fn normalize_sender(sender: &str) -> Result<String, String> {
+    let sender = sender.trim();
+    let pattern = match sender.split_once('@') {
+        None => format!("*@{}", sender),
+        Some(("", domain)) => format!("*@{}", domain),
+        Some(_) => sender.to_string(),
+    };
+    normalize_pattern(&pattern)
+}
+
+/// `user@host` or `*@host`, lowercase with the host in punycode
+pub(crate) fn normalize_pattern(pattern: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid sender pattern: {:?}", pattern);
+    let trimmed = pattern.trim();
+    if trimmed.chars().count() > MAX_PATTERN_CHARS
+        || trimmed.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(invalid());
+    }
+    let (local, _) = trimmed.rsplit_once('@').ok_or_else(invalid)?;
+    if local.is_empty() || (local.contains('*') && local != "*") {
+        return Err(invalid());
+    }
+    let (address, domain) = split_address(trimmed).ok_or_else(invalid)?;
+    if !domain
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    {
+        return Err(invalid());
+    }
+    Ok(address)
+}
+
+/// An address as `(whole address, domain)`, lowercase with the domain in punycode
+fn split_address(email: &str) -> Option<(String, String)> {
+    let (local, host) = email.trim().rsplit_once('@')?;
+    let host = idn::host_info(host)?.ascii_host.to_lowercase();
+    Some((format!("{}@{}", local.to_lowercase(), host), host))
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.starts_with("*@")
+}
+
+fn pattern_domain(pattern: &str) -> Option<&str> {
+    pattern.rsplit_once('@').map(|(_, domain)| domain)
+}
+
+/// Whether a normalized pattern covers a normalized address
+pub(crate) fn matches_address(pattern: &str, address: &str) -> bool {
+    match is_wildcard(pattern) {
+        true => pattern_domain(pattern) == address.rsplit_once('@').map(|(_, domain)| domain),
+        false => pattern == address,
+    }
+}
+
+/// The domain an `Authentication-Results` header (RFC 8601) vouches for: the `From`
+/// domain of a passed DMARC check, else the signing domain of a passed DKIM signature,
+/// else the envelope sender's domain of a passed SPF check; `None` when nothing passed
+pub fn authenticated_domain(value: &str) -> Option<String> {
+    let value = strip_comments(value);
+    let passed: Vec<(String, Vec<(String, String)>)> = value
+        .split(';')
+        // The first item names the server that checked
+        .skip(1)
+        .filter_map(|result| {
+            let mut tokens = result.split_whitespace();
+            let (method, outcome) = tokens.next()?.split_once('=')?;
+            let properties: Vec<(String, String)> = tokens
+                .filter_map(|token| token.split_once('='))
+                .map(|(name, value)| {
+                    (
+                        name.to_ascii_lowercase(),
+                        value.trim_matches('"').to_string(),
+                    )
+                })
+                .collect();
+            outcome
+                .eq_ignore_ascii_case("pass")
+                .then(|| (method.to_ascii_lowercase(), properties))
+        })
+        .collect();
+    let property = |method: &str, names: &[&str]| {
+        passed
+            .iter()
+            .filter(|(m, _)| m == method)
+            .flat_map(|(_, properties)| properties.iter())
+            .find(|(name, _)| names.contains(&name.as_str()))
+            .map(|(_, value)| value.rsplit('@').next().unwrap_or(value).to_string())
+    };
+    property("dmarc", &["header.from"])
+        .or_else(|| property("dkim", &["header.d", "header.i"]))
+        .or_else(|| property("spf", &["smtp.mailfrom", "smtp.helo"]))
+        .and_then(|domain| idn::host_info(&domain))
+        .map(|host| host.ascii_host.to_lowercase())
+}
+
+/// A header value without its (possibly nested) comments
+fn strip_comments(value: &str) -> String {
+    let mut depth = 0usize;
+    let mut stripped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Address, MessageFormat};
+
+    fn message(from: &str, auth: Option<&str>) -> Message {
+        let mut message = Message::new(MessageFormat::Eml);
+        message.from = Some(Address::new(None, Some(from.to_string())));
+        if let Some(auth) = auth {
+            message.headers = vec![("Authentication-Results".to_string(), auth.to_string())];
+        }
+        message
+    }
+
+    #[test]
+    fn rules_check_from_against_the_authenticated_domain() {
+        let mut rules = SenderRules::default();
+        assert!(rules.trust(" *@Example.com ").unwrap());
+        assert!(!rules.trust("*@example.com").unwrap());
+        rules.trust("boss@partner.org").unwrap();
+        rules.block("*@spam.test").unwrap();
+        assert!(rules.trust("*@*.com").is_err());
+        assert!(rules.block("no-at-sign").is_err());
+        assert_eq!(rules.trusted, ["*@example.com", "boss@partner.org"]);
+
+        assert_eq!(
+            rules.evaluate(&message("Anna@example.com", None)),
+            SenderTrust::Trusted
+        );
+        assert_eq!(
+            rules.evaluate(&message("other@partner.org", None)),
+            SenderTrust::Unknown
+        );
+        let passed = "mx.example.net; spf=pass smtp.mailfrom=bounce@example.com; \
+                      dkim=pass (good signature) header.d=example.com; dmarc=pass header.from=example.com";
+        assert_eq!(
+            rules.evaluate(&message("anna@example.com", Some(passed))),
+            SenderTrust::Trusted
+        );
+        // The display From says example.com, but only spam.test passed
+        let spoofed = "mx.example.net; dmarc=fail header.from=example.com; spf=pass smtp.mailfrom=x@spam.test";
+        assert_eq!(authenticated_domain(spoofed).as_deref(), Some("spam.test"));
+        assert_eq!(
+            rules.evaluate(&message("anna@example.com", Some(spoofed))),
+            SenderTrust::Blocked
+        );
+        assert_eq!(
+            rules.evaluate(&message("anna@example.com", Some("mx.example.net; none"))),
+            SenderTrust::Unknown
+        );
+
+        // Moving a pattern between lists
+        rules.block("boss@partner.org").unwrap();
+        assert_eq!(rules.trusted, ["*@example.com"]);
+        assert_eq!(
+            rules.evaluate(&message("boss@partner.org", None)),
+            SenderTrust::Blocked
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::documents::OpenDocuments;
+use crate::persist;
+use crate::settings::SettingsStore;
+use crate::window_placement;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Changes are written after this much quiet time
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Working set persisted between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Open files in the order they were opened
+    pub files: Vec<PathBuf>,
+    pub active: Option<PathBuf>,
+    pub windows: Vec<WindowGeometry>,
+}
+
+/// Outer position and inner size in physical pixels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub label: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    /// Scale factor of the monitor the window was on, so the size can be converted for
+    /// another one; sessions written before it was recorded are taken as 100%
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Payload of the `session-restore-partial` event
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialRestore {
+    pub missing: Vec<String>,
+}
+
+/// Session state: the session loaded at startup (until consumed) and the debounce counter
+#[derive(Default)]
+pub struct SessionState {
+    previous: Mutex<Option<Session>>,
+    generation: AtomicU64,
+}
+
+/// Files of the previous session to reopen, and the ones that are gone
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Restore {
+    /// Existing files, the active one last
+    pub files: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+}
+
+impl SessionState {
+    /// Keep the session loaded at startup until the frontend asks for it
+    pub(crate) fn hold(&self, session: Session) {
+        *self.previous.lock().unwrap() = Some(session);
+    }
+
+    /// The held session's files, once; nothing is restored when `superseded`, but the
+    /// session is consumed all the same
+    pub fn take(&self, superseded: bool) -> Restore {
+        match self.previous.lock().unwrap().take() {
+            Some(session) if !superseded => restore(session),
+            _ => Restore::default(),
+        }
+    }
+}
+
+/// Read a saved session; a missing or corrupt file is no session
+fn load(path: &Path) -> Option<Session> {
+    persist::read_json(path)
+}
+
+/// Split the files of `session` into existing and missing ones, with the active file
+/// last: the frontend selects the last file it opens
+fn restore(session: Session) -> Restore {
+    let (mut files, missing): (Vec<PathBuf>, Vec<PathBuf>) =
+        session.files.into_iter().partition(|path| path.is_file());
+    if let Some(active) = &session.active {
+        if let Some(pos) = files.iter().position(|path| path == active) {
+            let active = files.remove(pos);
+            files.push(active);
+        }
+    }
+    Restore { files, missing }
+}
+
+/// Where the session is saved (nothing is saved in paranoid mode)
+pub fn session_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("session.json"))
+}
+
+/// Load the previous session and restore window geometry; called from `setup`
+pub fn init(app: &AppHandle) {
+    let session = match app.state::<SettingsStore>().get().restore_session {
+        true => session_path(app).and_then(|path| load(&path)),
+        false => None,
+    };
+    let Some(session) = session else {
+        // On first run the configured size may not fit small screens
+        if let Some(main) = app.get_webview_window("main") {
+            window_placement::fit(&main);
+        }
+        return;
+    };
+
+    for geometry in &session.windows {
+        if let Some(window) = app.get_webview_window(&geometry.label) {
+            // Monitors may have been rearranged, disconnected or rescaled since
+            let saved = window_placement::Rect {
+                x: geometry.x,
+                y: geometry.y,
+                width: geometry.width,
+                height: geometry.height,
+            };
+            let monitors = window_placement::monitors(&window);
+            window_placement::apply(
+                &window,
+                window_placement::restore(saved, geometry.scale, &monitors),
+            );
+            if geometry.maximized {
+                let _ = window.maximize();
+            }
+        }
+    }
+
+    app.state::<SessionState>().hold(session);
+}
+
+/// Tell the frontend which files of the restored session are gone
+pub fn report_missing(app: &AppHandle, missing: &[PathBuf]) {
+    if missing.is_empty() {
+        return;
+    }
+    let payload = PartialRestore {
+        missing: missing
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect(),
+    };
+    if let Err(e) = app.emit("session-restore-partial", &payload) {
+        log_line!("Failed to emit session-restore-partial event: {}", e);
+    }
+}
+
+/// Capture the current working set from the document registry and the windows
+fn capture(app: &AppHandle) -> Session {
+    let (files, active) = {
+        let documents = app.state::<OpenDocuments>();
+        let registry = documents.0.lock().unwrap();
+        let files = registry.list().iter().map(|doc| doc.path.clone()).collect();
+        (files, registry.active().map(|doc| doc.path.clone()))
+    };
+
+    let windows = app
+        .webview_windows()
+        .into_iter()
+        .filter_map(|(label, window)| {
+            let position = window.outer_position().ok()?;
+            let size = window.inner_size().ok()?;
+            Some(WindowGeometry {
+                label,
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+                maximized: window.is_maximized().unwrap_or(false),
+                scale: window.scale_factor().unwrap_or(1.0),
+            })
+        })
+        .collect();
+
+    Session {
+        files,
+        active,
+        windows,
+    }
+}
+
+/// Write the session immediately (used on exit)
+pub fn save_now(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    if !settings.restore_session || settings.paranoid_mode {
+        return;
+    }
+    // Don't overwrite the previous session before the frontend has picked it up
+    if app
+        .state::<SessionState>()
+        .previous
+        .lock()
+        .unwrap()
+        .is_some()
+    {
+        return;
+    }
+    let Some(path) = session_path(app) else {
+        return;
+    };
+    if let Err(e) = persist::write_json(&path, &capture(app)) {
+        log_line!("Failed to save session: {}", e);
+    }
+}
+
+/// Save after changes settle, so bursts of opens or window moves cause one write
+pub fn schedule_save(app: &AppHandle) {
+    let generation = app
+        .state::<SessionState>()
+        .generation
+        .fetch_add(1, Ordering::SeqCst)
+        + 1;
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(SAVE_DEBOUNCE);
+        if app
+            .state::<SessionState>()
+            .generation
+            .load(Ordering::SeqCst)
+            == generation
+        {
+            save_now(&app);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_left_out_and_the_active_one_comes_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"").unwrap();
+            path
+        };
+        let (a, b, c) = (file("a.msg"), file("b.eml"), file("c.msg"));
+        let gone = dir.path().join("gone.msg");
+        let state = SessionState::default();
+        state.hold(Session {
+            files: vec![a.clone(), gone.clone(), b.clone(), c.clone()],
+            active: Some(b.clone()),
+            windows: Vec::new(),
+        });
+
+        assert_eq!(
+            state.take(false),
+            Restore {
+                files: vec![a, c, b],
+                missing: vec![gone],
+            }
+        );
+        // Restored once only
+        assert_eq!(state.take(false), Restore::default());
+    }
+
+    #[test]
+    fn files_opened_at_launch_supersede_the_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.msg");
+        std::fs::write(&path, b"").unwrap();
+        let state = SessionState::default();
+        state.hold(Session {
+            files: vec![path],
+            ..Session::default()
+        });
+
+        assert_eq!(state.take(true), Restore::default());
+        // A later window without launch files doesn't bring it back
+        assert_eq!(state.take(false), Restore::default());
+    }
+
+    #[test]
+    fn corrupt_or_missing_session_files_start_afresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        assert!(load(&path).is_none());
+        std::fs::write(&path, b"{\"files\": [\"a.msg\"").unwrap();
+        assert!(load(&path).is_none());
+        std::fs::write(&path, b"{\"files\": 3}").unwrap();
+        assert!(load(&path).is_none());
+
+        // Fields added since the file was written take their defaults
+        std::fs::write(
+            &path,
+            br#"{"files": ["a.msg"], "windows": [{"label": "main", "x": 1, "y": 2,
+                "width": 800, "height": 600, "maximized": false}]}"#,
+        )
+        .unwrap();
+        let session = load(&path).unwrap();
+        assert_eq!(session.files, [PathBuf::from("a.msg")]);
+        assert_eq!(session.active, None);
+        assert_eq!(session.windows[0].scale, 1.0);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::auto_extract::AutoExtractRule;
+use crate::formatting::{DateFormat, TimeZoneChoice};
+use crate::i18n::Lang;
+use crate::parser::spoofing::SenderChecks;
+use crate::persist;
+use crate::retention::RetentionPolicy;
+use crate::sender_prefs::SenderPreferences;
+use crate::sender_rules::SenderRules;
+use crate::theme::Theme;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+/// Layout version of the settings file; every change that old files can't be read into
+/// with defaults alone adds a step to [`MIGRATIONS`]
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Steps upgrading a settings file, the first from version 0 (files from before versions
+/// were recorded) to 1, each one version further
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    // Version 1 only starts recording the version
+    |_| {},
+];
+
+/// User settings persisted as JSON in the app config directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Layout version of the file the settings were read from, see [`SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// Explicit theme; `None` follows the operating system setting
+    pub theme_override: Option<Theme>,
+    /// Show OS notifications for messages arriving in watched folders
+    pub notifications_enabled: bool,
+    /// Daily window during which notifications are suppressed
+    pub quiet_hours: Option<QuietHours>,
+    /// Reopen the files that were open at last exit
+    pub restore_session: bool,
+    /// Redact file paths and email addresses from diagnostics bundles
+    pub privacy_mode: bool,
+    /// Show the message inside an Exchange journal report instead of the report itself
+    pub unwrap_journal_reports: bool,
+    /// Decode message bodies strictly with their declared charset, without detection
+    pub trust_declared_charset: bool,
+    /// Repair bodies that were double-encoded (UTF-8 misread as Windows-1252 and similar)
+    pub repair_mojibake: bool,
+    /// URL fragments marking tracking images, on top of the built-in list
+    pub tracker_patterns: Vec<String>,
+    /// Folders the user allowed the app to access, in a native dialog
+    pub approved_dirs: Vec<String>,
+    /// More files than this opened at once are offered as a bulk open
+    pub bulk_open_threshold: usize,
+    /// Application id (see `list_applications_for`) to open files with, by lowercase extension;
+    /// extensions without an entry use the system default
+    pub open_with: BTreeMap<String, String>,
+    /// Load large inline images of opened files through the `attach://` protocol instead of
+    /// embedding them as data URIs
+    pub inline_images_via_protocol: bool,
+    /// Parsing a file may allocate at most this many times its size in total
+    pub parse_allocation_factor: u64,
+    /// Parsing a file is stopped after this many seconds
+    pub parse_time_budget_secs: u64,
+    /// Language of errors, warnings and generated exports (see `set_locale`)
+    pub locale: Lang,
+    /// Show the original destination of links wrapped by SafeLinks or URLDefense when
+    /// hovering them (as their `title`)
+    pub show_unwrapped_link_titles: bool,
+    /// Display color of each message tag (CSS color), by lowercase tag name
+    pub tag_colors: BTreeMap<String, String>,
+    /// How exports and reports write dates
+    pub date_format: DateFormat,
+    /// Time zone of the dates in exports and reports
+    pub date_time_zone: TimeZoneChoice,
+    /// Allow and block lists behind the sender trust badge
+    pub sender_rules: SenderRules,
+    /// Sender spoofing heuristics behind the warning banner
+    pub sender_checks: SenderChecks,
+    /// When temp files, caches and logs are deleted
+    pub retention: RetentionPolicy,
+    /// Keep view states, the session and the log in memory only, and delete their files
+    pub paranoid_mode: bool,
+    /// Record attachments saved, exported or opened in other applications (see
+    /// `get_audit_log`)
+    pub audit_log: bool,
+    /// Fetch remote images of opened messages and inline them (trackers stay blocked)
+    pub load_remote_images: bool,
+    /// Start with quoted text collapsed
+    pub collapse_quotes: bool,
+    /// Display preferences by sender address or domain, over the two above (see
+    /// `set_sender_preference`)
+    pub sender_preferences: SenderPreferences,
+    /// Rules saving attachments into folders when a message is opened; none by default
+    pub auto_extract_rules: Vec<AutoExtractRule>,
+    /// Gated command groups switched on or off, by group id (see `permissions`); only set
+    /// through `set_command_group`
+    pub command_groups: BTreeMap<String, bool>,
+    /// Directory for extracted files when neither the system temp directory nor the app's
+    /// cache directory can be written; only set through `choose_temp_dir`
+    pub temp_dir: Option<String>,
+    /// Attachments up to this many bytes are embedded in HTML archives (see
+    /// `export_html_archive`); larger ones are only listed
+    pub html_archive_embed_limit: usize,
+    /// Regenerable data held for open documents (extracted attachments, sources, archive
+    /// contents) beyond this many bytes is evicted, least recently used first (see
+    /// `get_memory_report`)
+    pub memory_threshold: usize,
+    /// Nothing leaves the app: no saving attachments, opening them elsewhere or remote
+    /// content (see `quarantine`); only set through `set_quarantine_mode`
+    pub quarantine_mode: bool,
+    /// The user's own address, as the attendee answering meeting requests (see
+    /// `generate_calendar_response`)
+    pub calendar_address: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            theme_override: None,
+            notifications_enabled: true,
+            quiet_hours: None,
+            restore_session: true,
+            privacy_mode: false,
+            unwrap_journal_reports: true,
+            trust_declared_charset: false,
+            repair_mojibake: true,
+            tracker_patterns: Vec::new(),
+            approved_dirs: Vec::new(),
+            bulk_open_threshold: 20,
+            open_with: BTreeMap::new(),
+            inline_images_via_protocol: true,
+            parse_allocation_factor: 16,
+            parse_time_budget_secs: 30,
+            locale: Lang::En,
+            show_unwrapped_link_titles: false,
+            tag_colors: BTreeMap::new(),
+            date_format: DateFormat::default(),
+            date_time_zone: TimeZoneChoice::default(),
+            sender_rules: SenderRules::default(),
+            sender_checks: SenderChecks::default(),
+            retention: RetentionPolicy::default(),
+            paranoid_mode: false,
+            audit_log: false,
+            load_remote_images: false,
+            collapse_quotes: true,
+            sender_preferences: SenderPreferences::default(),
+            auto_extract_rules: Vec::new(),
+            command_groups: BTreeMap::new(),
+            temp_dir: None,
+            html_archive_embed_limit: 10 * 1024 * 1024,
+            memory_threshold: 512 * 1024 * 1024,
+            quarantine_mode: false,
+            calendar_address: None,
+        }
+    }
+}
+
+/// Local-time window in "HH:MM" format; `start` after `end` wraps past midnight
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+/// Settings the webview can't change; they are only set from native dialogs. Exports
+/// leave them out, as they grant access on one machine.
+const PROTECTED_SETTINGS: &[&str] = &[
+    "schema_version",
+    "approved_dirs",
+    "command_groups",
+    "temp_dir",
+    "quarantine_mode",
+];
+
+/// Payload of the `settings-reset` event: the settings file couldn't be read and defaults
+/// are used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingsReset {
+    pub reason: String,
+    /// Where the unreadable file was moved
+    pub backup: Option<String>,
+}
+
+/// What `import_settings` did with each key of the file
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub settings: Settings,
+    pub applied: Vec<String>,
+    pub ignored: Vec<IgnoredSetting>,
+    /// Schema version of the imported file, when older than the current one
+    pub migrated_from: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IgnoredSetting {
+    pub key: String,
+    /// `unknown`, `protected` or `invalid: <error>`
+    pub reason: String,
+}
+
+/// Managed settings state, backed by a file once the config directory is known
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Mutex<Settings>,
+    /// Set when loading fell back to defaults, until announced
+    reset: Mutex<Option<SettingsReset>>,
+}
+
+impl SettingsStore {
+    /// Load settings from `path`, upgrading files of older schema versions (the original is
+    /// kept as `settings.v<N>.json`). A file that can't be read is moved aside as
+    /// `settings.corrupt.json` and defaults are used, see [`SettingsStore::take_reset`].
+    pub fn load(path: PathBuf) -> Self {
+        let (settings, reset) = match read_versioned(&path) {
+            Ok(settings) => (settings.unwrap_or_default(), None),
+            Err(reason) => {
+                log_line!("Settings reset to defaults: {}", reason);
+                let backup = path.with_extension("corrupt.json");
+                let backup = match std::fs::rename(&path, &backup) {
+                    Ok(()) => Some(backup.to_string_lossy().to_string()),
+                    Err(e) => {
+                        log_line!("Failed to move {} aside: {}", path.display(), e);
+                        None
+                    }
+                };
+                (Settings::default(), Some(SettingsReset { reason, backup }))
+            }
+        };
+        Self {
+            path: Some(path),
+            settings: Mutex::new(settings),
+            reset: Mutex::new(reset),
+        }
+    }
+
+    /// Settings that only live in memory (used when no config directory is available)
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            settings: Mutex::new(Settings::default()),
+            reset: Mutex::new(None),
+        }
+    }
+
+    /// Why loading fell back to defaults, once
+    pub fn take_reset(&self) -> Option<SettingsReset> {
+        self.reset.lock().unwrap().take()
+    }
+
+    /// The file settings are stored in
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Snapshot of the current settings
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Apply a modification and persist the result
+    pub fn update<F: FnOnce(&mut Settings)>(&self, f: F) -> Result<Settings, String> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut updated = settings.clone();
+        f(&mut updated);
+
+        if let Some(path) = &self.path {
+            persist::write_json(path, &updated)?;
+        }
+
+        *settings = updated.clone();
+        Ok(updated)
+    }
+
+    /// Merge a partial JSON object into the settings (unknown and protected keys are ignored)
+    pub fn merge(&self, patch: serde_json::Value) -> Result<Settings, String> {
+        let serde_json::Value::Object(patch) = patch else {
+            return Err("Settings patch must be a JSON object".to_string());
+        };
+
+        let mut current = serde_json::to_value(self.get())
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        if let serde_json::Value::Object(map) = &mut current {
+            for (key, value) in patch {
+                if PROTECTED_SETTINGS.contains(&key.as_str()) {
+                    continue;
+                }
+                map.insert(key, value);
+            }
+        }
+
+        let merged: Settings =
+            serde_json::from_value(current).map_err(|e| format!("Invalid settings: {}", e))?;
+        self.update(|settings| *settings = merged)
+    }
+
+    /// The settings as an export file for another machine, without the protected ones
+    pub fn export(&self) -> Result<Value, String> {
+        let mut value = serde_json::to_value(self.get())
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        if let Value::Object(map) = &mut value {
+            map.retain(|key, _| {
+                key == "schema_version" || !PROTECTED_SETTINGS.contains(&key.as_str())
+            });
+        }
+        Ok(value)
+    }
+
+    /// Take the settings of an export file, upgraded to the current schema: over the
+    /// current ones with `merge`, else over the defaults. Keys that are unknown, protected
+    /// or have invalid values are left out and reported; protected settings keep their
+    /// current values either way.
+    pub fn import(&self, file: Value, merge: bool) -> Result<ImportReport, String> {
+        let Value::Object(mut file) = file else {
+            return Err("Settings file must be a JSON object".to_string());
+        };
+        let version = migrate(&mut file)?;
+        let current = self.get();
+        let base = match merge {
+            true => current.clone(),
+            false => Settings::default(),
+        };
+        let Value::Object(mut candidate) = serde_json::to_value(&base)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?
+        else {
+            return Err("Settings must serialize to a JSON object".to_string());
+        };
+
+        let mut applied = Vec::new();
+        let mut ignored = Vec::new();
+        for (key, value) in file {
+            if key == "schema_version" {
+                continue;
+            }
+            let reason = if !candidate.contains_key(&key) {
+                Some("unknown".to_string())
+            } else if PROTECTED_SETTINGS.contains(&key.as_str()) {
+                Some("protected".to_string())
+            } else {
+                let mut trial = candidate.clone();
+                trial.insert(key.clone(), value.clone());
+                match serde_json::from_value::<Settings>(Value::Object(trial)) {
+                    Ok(_) => {
+                        candidate.insert(key.clone(), value);
+                        None
+                    }
+                    Err(e) => Some(format!("invalid: {}", e)),
+                }
+            };
+            match reason {
+                Some(reason) => ignored.push(IgnoredSetting { key, reason }),
+                None => applied.push(key),
+            }
+        }
+
+        let mut imported: Settings = serde_json::from_value(Value::Object(candidate))
+            .map_err(|e| format!("Invalid settings: {}", e))?;
+        imported.schema_version = SCHEMA_VERSION;
+        imported.approved_dirs = current.approved_dirs;
+        imported.command_groups = current.command_groups;
+        imported.temp_dir = current.temp_dir;
+        imported.quarantine_mode = current.quarantine_mode;
+        let settings = self.update(|settings| *settings = imported)?;
+        Ok(ImportReport {
+            settings,
+            applied,
+            ignored,
+            migrated_from: (version < SCHEMA_VERSION).then_some(version),
+        })
+    }
+}
+
+/// Tell the webview with a `settings-reset` event that the settings file couldn't be read,
+/// once it has loaded
+pub fn announce_reset<R: Runtime>(app: &AppHandle<R>) {
+    let Some(reset) = app.state::<SettingsStore>().take_reset() else {
+        return;
+    };
+    if let Err(e) = app.emit("settings-reset", reset) {
+        log_line!("Failed to emit settings-reset: {}", e);
+    }
+}
+
+/// Upgrade a settings object to [`SCHEMA_VERSION`] and return the version it had
+fn migrate(map: &mut Map<String, Value>) -> Result<u32, String> {
+    let version = match map.get("schema_version") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("Invalid schema_version: {}", value))?,
+    };
+    // Files of newer versions are read as far as this one understands them
+    for step in MIGRATIONS.iter().skip(version as usize) {
+        step(map);
+    }
+    map.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    Ok(version)
+}
+
+/// Read the settings file at `path`, upgrading and rewriting it when it has an older schema
+/// version; `None` if there is no file
+fn read_versioned(path: &Path) -> Result<Option<Settings>, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let Value::Object(mut map) =
+        serde_json::from_slice(&bytes).map_err(|e| format!("Invalid JSON: {}", e))?
+    else {
+        return Err("Settings file is not a JSON object".to_string());
+    };
+    let version = migrate(&mut map)?;
+    let settings: Settings = serde_json::from_value(Value::Object(map))
+        .map_err(|e| format!("Invalid settings: {}", e))?;
+    if version < SCHEMA_VERSION {
+        // Without a backup the old file stays as it is; the upgrade runs again next time
+        let backup = path.with_extension(format!("v{}.json", version));
+        let upgraded = std::fs::write(&backup, &bytes)
+            .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))
+            .and_then(|()| persist::write_json(path, &settings));
+        match upgraded {
+            Ok(()) => log_line!(
+                "Upgraded settings from schema version {} to {}",
+                version,
+                SCHEMA_VERSION
+            ),
+            Err(e) => log_line!("Settings upgraded in memory only: {}", e),
+        }
+    }
+    Ok(Some(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn old_files_are_upgraded_and_unreadable_ones_reset() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("settings.json");
+
+        // Files from before versions were recorded are upgraded, the original kept alongside
+        let old = br#"{"notifications_enabled": false, "approved_dirs": ["/mail"]}"#;
+        std::fs::write(&path, old).unwrap();
+        let store = SettingsStore::load(path.clone());
+        assert!(!store.get().notifications_enabled);
+        assert_eq!(store.take_reset(), None);
+        assert_eq!(std::fs::read(dir.join("settings.v0.json")).unwrap(), old);
+        let saved: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], SCHEMA_VERSION);
+
+        let exported = store.export().unwrap();
+        assert_eq!(exported["schema_version"], SCHEMA_VERSION);
+        assert!(exported.get("approved_dirs").is_none());
+
+        let file = json!({
+            "schema_version": 0,
+            "bulk_open_threshold": 5,
+            "collapse_quotes": "yes",
+            "approved_dirs": ["/"],
+            "no_such_setting": true,
+        });
+        let report = store.import(file, true).unwrap();
+        assert_eq!(report.applied, ["bulk_open_threshold"]);
+        let ignored: Vec<(&str, &str)> = report
+            .ignored
+            .iter()
+            .map(|ignored| (ignored.key.as_str(), &ignored.reason[..7]))
+            .collect();
+        assert_eq!(
+            ignored,
+            [
+                ("approved_dirs", "protect"),
+                ("collapse_quotes", "invalid"),
+                ("no_such_setting", "unknown"),
+            ]
+        );
+        assert_eq!(report.migrated_from, Some(0));
+        assert_eq!(report.settings.bulk_open_threshold, 5);
+        assert_eq!(report.settings.approved_dirs, ["/mail"]);
+        assert!(!report.settings.notifications_enabled);
+        // Without merging, what the file leaves out goes back to its default
+        let report = store
+            .import(json!({"bulk_open_threshold": 7}), false)
+            .unwrap();
+        assert!(report.settings.notifications_enabled);
+        assert_eq!(report.settings.approved_dirs, ["/mail"]);
+
+        // Unreadable files give defaults and are moved aside
+        std::fs::write(&path, b"{\"notifications_enabled\": fal").unwrap();
+        let store = SettingsStore::load(path.clone());
+        let reset = store.take_reset().unwrap();
+        assert!(reset.reason.starts_with("Invalid JSON"), "{}", reset.reason);
+        let backup = dir.join("settings.corrupt.json");
+        assert_eq!(reset.backup, Some(backup.to_string_lossy().to_string()));
+        assert!(backup.exists() && !path.exists());
+        assert!(store.get().notifications_enabled);
+        assert_eq!(store.take_reset(), None);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::export;
+use crate::parser::{headers, mime, Message, MessageFormat};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+/// Largest window returned at once
+pub const MAX_WINDOW: usize = 1024 * 1024;
+
+/// Search results beyond this many are dropped
+const MAX_MATCHES: usize = 10_000;
+
+/// Total size of the sources kept for windowing and search; the oldest are dropped first
+const CACHE_BUDGET: usize = 128 * 1024 * 1024;
+
+/// Multipart nesting deeper than this is copied without eliding
+const MAX_DEPTH: usize = 32;
+
+/// How the source is presented
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceOptions {
+    /// Keep base64 bodies of binary parts instead of a placeholder
+    pub raw: bool,
+    /// Turn CRLF line endings into LF
+    pub normalize_line_endings: bool,
+}
+
+/// A piece of the source; offsets are bytes in the source as presented
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceWindow {
+    pub offset: usize,
+    /// Bytes covered by `text`; the next window starts at `offset + length`
+    pub length: usize,
+    pub total: usize,
+    /// Assembled from the parsed message (.msg files), not the bytes of the file
+    pub synthesized: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceMatches {
+    /// Byte offsets of the matches, in order
+    pub offsets: Vec<usize>,
+    /// More matches were found than returned
+    pub truncated: bool,
+}
+
+/// A source as presented
+pub struct Source {
+    data: Vec<u8>,
+    synthesized: bool,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    options: SourceOptions,
+    source: Arc<Source>,
+    used: Instant,
+}
+
+/// Sources of recently viewed files, so windows and searches don't rebuild them
+#[derive(Default)]
+pub struct SourceCache(Mutex<VecDeque<CacheEntry>>);
+
+impl SourceCache {
+    /// The source of the file at `path` (already checked against the access policy); `load`
+    /// parses it when it is a .msg file
+    pub fn get(
+        &self,
+        path: &Path,
+        options: SourceOptions,
+        load: impl FnOnce() -> Result<Message, String>,
+    ) -> Result<Arc<Source>, String> {
+        let modified = std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if let Some(entry) = self.0.lock().unwrap().iter_mut().find(|entry| {
+            entry.path == path && entry.modified == modified && entry.options == options
+        }) {
+            entry.used = Instant::now();
+            return Ok(entry.source.clone());
+        }
+
+        let source = Arc::new(build(path, options, load)?);
+        let mut cache = self.0.lock().unwrap();
+        cache.push_back(CacheEntry {
+            path: path.to_path_buf(),
+            modified,
+            options,
+            source: source.clone(),
+            used: Instant::now(),
+        });
+        while cache.len() > 1
+            && cache
+                .iter()
+                .map(|entry| entry.source.data.len())
+                .sum::<usize>()
+                > CACHE_BUDGET
+        {
+            cache.pop_front();
+        }
+        Ok(source)
+    }
+
+    pub fn invalidate(&self, path: &Path) {
+        self.0.lock().unwrap().retain(|entry| entry.path != path);
+    }
+
+    /// Bytes held and when they were last used, by file path (see `memory`)
+    pub fn held(&self) -> Vec<(PathBuf, usize, Instant)> {
+        let cache = self.0.lock().unwrap();
+        crate::memory::by_path(
+            cache
+                .iter()
+                .map(|entry| (entry.path.as_path(), entry.source.data.len(), entry.used)),
+        )
+    }
+}
+
+impl Source {
+    /// Up to `length` bytes from `offset`; a window is never cut inside a UTF-8 sequence
+    pub fn window(&self, offset: usize, length: usize) -> SourceWindow {
+        let data = &self.data;
+        let is_continuation = |i: usize| data.get(i).is_some_and(|b| b & 0xC0 == 0x80);
+        let mut start = offset.min(data.len());
+        while start < data.len() && is_continuation(start) {
+            start += 1;
+        }
+        let mut end = start.saturating_add(length.min(MAX_WINDOW)).min(data.len());
+        while end > start && end < data.len() && is_continuation(end) {
+            end -= 1;
+        }
+        // Always cover at least one whole character
+        if end == start && length > 0 {
+            end = (start + 1).min(data.len());
+            while end < data.len() && is_continuation(end) {
+                end += 1;
+            }
+        }
+        SourceWindow {
+            offset: start,
+            length: end - start,
+            total: data.len(),
+            synthesized: self.synthesized,
+            text: String::from_utf8_lossy(&data[start..end]).to_string(),
+        }
+    }
+
+    /// Offsets of `query`, ignoring ASCII case unless `match_case`
+    pub fn search(&self, query: &str, match_case: bool) -> SourceMatches {
+        let query = query.as_bytes();
+        let mut offsets = Vec::new();
+        if query.is_empty() {
+            return SourceMatches {
+                offsets,
+                truncated: false,
+            };
+        }
+        let mut i = 0;
+        while i + query.len() <= self.data.len() {
+            let candidate = &self.data[i..i + query.len()];
+            let found = if match_case {
+                candidate == query
+            } else {
+                candidate.eq_ignore_ascii_case(query)
+            };
+            if !found {
+                i += 1;
+                continue;
+            }
+            if offsets.len() == MAX_MATCHES {
+                return SourceMatches {
+                    offsets,
+                    truncated: true,
+                };
+            }
+            offsets.push(i);
+            i += query.len();
+        }
+        SourceMatches {
+            offsets,
+            truncated: false,
+        }
+    }
+}
+
+fn build(
+    path: &Path,
+    options: SourceOptions,
+    load: impl FnOnce() -> Result<Message, String>,
+) -> Result<Source, String> {
+    let (data, synthesized) = match MessageFormat::from_path(path) {
+        Some(MessageFormat::Eml | MessageFormat::Emlx) => {
+            let data = std::fs::read(path)
+                .map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+            if options.raw {
+                (data, false)
+            } else {
+                let mut out = Vec::with_capacity(data.len().min(1024 * 1024));
+                elide_entity(&data, &mut out, 0);
+                (out, false)
+            }
+        }
+        Some(MessageFormat::Msg) => (synthesize(&load()?).into_bytes(), true),
+        None => return Err(format!("Unsupported file type: {}", path.display())),
+    };
+    let data = if options.normalize_line_endings {
+        normalize(&data)
+    } else {
+        data
+    };
+    Ok(Source { data, synthesized })
+}
+
+/// Copy a MIME entity, with the base64 bodies of non-text parts replaced by a placeholder
+fn elide_entity(data: &[u8], out: &mut Vec<u8>, depth: usize) {
+    let (block, body) = headers::split_header_block(data);
+    out.extend_from_slice(&data[..data.len() - body.len()]);
+    let fields = headers::parse(block);
+    let content_type = mime::parse_parameters(fields.get("Content-Type").unwrap_or("text/plain"));
+    let encoding = fields
+        .get("Content-Transfer-Encoding")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    let boundary = content_type.param("boundary").filter(|b| !b.is_empty());
+    match boundary {
+        Some(boundary) if content_type.is_multipart() && depth < MAX_DEPTH => {
+            elide_multipart(body, boundary, out, depth)
+        }
+        _ if encoding == "base64"
+            && !content_type.mime.starts_with("text/")
+            && !content_type.mime.starts_with("message/")
+            && !body.trim_ascii().is_empty() =>
+        {
+            out.extend_from_slice(
+                format!("[... {} bytes of base64 ...]", body.trim_ascii_end().len()).as_bytes(),
+            );
+            let ending = body.len() - body.trim_ascii_end().len();
+            if ending > 0 {
+                out.extend_from_slice(if body.windows(2).any(|w| w == b"\r\n") {
+                    b"\r\n"
+                } else {
+                    b"\n"
+                });
+            }
+        }
+        _ => out.extend_from_slice(body),
+    }
+}
+
+/// Copy a multipart body, eliding within each part; preamble, delimiters and epilogue are
+/// kept as they are
+fn elide_multipart(body: &[u8], boundary: &str, out: &mut Vec<u8>, depth: usize) {
+    let delimiter = format!("--{}", boundary);
+    // Start of each delimiter line, with the end of the line
+    let mut delimiters = Vec::new();
+    let mut line_start = 0;
+    while line_start < body.len() {
+        let line_end = body[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(body.len(), |newline| line_start + newline + 1);
+        if body[line_start..line_end].starts_with(delimiter.as_bytes()) {
+            delimiters.push((line_start, line_end));
+        }
+        line_start = line_end;
+    }
+
+    let mut copied = 0;
+    for (index, &(start, end)) in delimiters.iter().enumerate() {
+        if index > 0 {
+            elide_entity(&body[copied..start], out, depth + 1);
+        } else {
+            out.extend_from_slice(&body[..start]);
+        }
+        out.extend_from_slice(&body[start..end]);
+        copied = end;
+        let closing = body[start + delimiter.len()..end].starts_with(b"--");
+        if closing {
+            break;
+        }
+    }
+    out.extend_from_slice(&body[copied..]);
+}
+
+/// Pseudo-source of a .msg file: its transport headers (or the main fields when it has
+/// none), then the decoded bodies and the attachment list, under a header saying so
+fn synthesize(message: &Message) -> String {
+    let mut out = String::from(
+        "X-MsgReader-Source: synthesized from an Outlook .msg file; \
+         this is not the original message source\r\n",
+    );
+    let mut field = |name: &str, value: &str| {
+        if !value.is_empty() {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    };
+    if message.headers.is_empty() {
+        field(
+            "From",
+            &message
+                .from
+                .as_ref()
+                .map(export::format_address)
+                .unwrap_or_default(),
+        );
+        field("To", &export::format_addresses(&message.to));
+        field("Cc", &export::format_addresses(&message.cc));
+        field(
+            "Date",
+            &message
+                .date
+                .as_deref()
+                .map(crate::formatting::rfc2822)
+                .unwrap_or_default(),
+        );
+        field("Subject", message.subject.as_deref().unwrap_or_default());
+        field(
+            "Message-ID",
+            message.message_id.as_deref().unwrap_or_default(),
+        );
+    } else {
+        for (name, value) in &message.headers {
+            field(name, value);
+        }
+    }
+
+    for (label, body) in [
+        ("text body", &message.body_text),
+        ("HTML body", &message.body_html),
+    ] {
+        if let Some(body) = body {
+            out.push_str(&format!("\r\n[{}]\r\n", label));
+            out.push_str(body);
+            if !body.ends_with('\n') {
+                out.push_str("\r\n");
+            }
+        }
+    }
+    for attachment in &message.attachments {
+        out.push_str(&format!(
+            "\r\n[attachment: {} ({}, {} bytes)]\r\n",
+            attachment.file_name,
+            attachment
+                .declared_mime
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+            attachment.size
+        ));
+    }
+    out
+}
+
+fn normalize(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            i += 1;
+            continue;
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EML: &str = "From: ann@example.com\r\n\
+        Content-Type: multipart/mixed; boundary=\"b1\"\r\n\r\n\
+        preamble\r\n\
+        --b1\r\nContent-Type: text/plain\r\nContent-Transfer-Encoding: base64\r\n\r\nSGVsbG8=\r\n\
+        --b1\r\nContent-Type: application/pdf\r\nContent-Transfer-Encoding: base64\r\n\r\n\
+        JVBERi0xLjQK\r\nJVBERi0xLjQK\r\n\
+        --b1--\r\nepilogue\r\n";
+
+    fn source(data: &str, normalize_line_endings: bool) -> Source {
+        let mut out = Vec::new();
+        elide_entity(data.as_bytes(), &mut out, 0);
+        let data = if normalize_line_endings {
+            normalize(&out)
+        } else {
+            out
+        };
+        Source {
+            data,
+            synthesized: false,
+        }
+    }
+
+    #[test]
+    fn binary_parts_are_elided() {
+        let elided = source(EML, false);
+        let text = String::from_utf8(elided.data.clone()).unwrap();
+        assert_eq!(
+            text,
+            EML.replace(
+                "JVBERi0xLjQK\r\nJVBERi0xLjQK\r\n",
+                "[... 26 bytes of base64 ...]\r\n"
+            )
+        );
+        // Text parts stay, even when base64
+        assert!(text.contains("SGVsbG8="));
+
+        let normalized = source(EML, true);
+        assert!(!normalized.data.contains(&b'\r'));
+        assert!(String::from_utf8_lossy(&normalized.data).contains("--b1--\nepilogue\n"));
+    }
+
+    #[test]
+    fn windows_and_search_cover_the_source() {
+        let source = Source {
+            data: "Grüße aus Köln, grüße".as_bytes().to_vec(),
+            synthesized: false,
+        };
+        // Byte 3 is inside "ü": the window starts after it, and covers "ß" whole
+        let window = source.window(3, 1);
+        assert_eq!((window.offset, window.length), (4, 2));
+        assert_eq!(window.text, "ß");
+        let window = source.window(4, 3);
+        assert_eq!((window.offset, window.length), (4, 3));
+        assert_eq!(window.text, "ße");
+        let window = source.window(0, 3);
+        assert_eq!(window.text, "Gr");
+        let all = source.window(0, usize::MAX);
+        assert_eq!(all.length, all.total);
+
+        let matches = source.search("GRÜ", false);
+        // ASCII case is ignored, the rest must match exactly
+        assert_eq!(matches.offsets, Vec::<usize>::new());
+        assert_eq!(source.search("gr", false).offsets, vec![0, 19]);
+        assert_eq!(source.search("gr", true).offsets, vec![19]);
+    }
+}
@@ -0,0 +1,158 @@
+//! State managed by the app: the files waiting for a webview, and the stores loaded at
+//! startup. Feature modules own their state types; this is where they are registered.
+
+use crate::settings::SettingsStore;
+use crate::{
+    access, archive, attachments, audit, avatars, clipboard, documents, drafts, file_open,
+    folder_index, freshness, i18n, integrity, jobs, media, notes, notifications, open_queue,
+    prefetch, preview_view, previews, quarantine, remote, session, source, view_state, volumes,
+    watcher, workspaces,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{App, Builder, Manager, Runtime};
+
+/// Store pending file paths for when app is launched via file association
+#[derive(Default)]
+pub struct PendingFiles(Mutex<Vec<PathBuf>>);
+
+impl PendingFiles {
+    pub fn push(&self, path: PathBuf) {
+        self.0.lock().unwrap().push(path);
+    }
+
+    /// The pending files, leaving none behind
+    pub fn take(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Files of windows opened with `open_in_new_window`, until their webview asks for them
+#[derive(Default)]
+pub struct WindowFiles(Mutex<HashMap<String, Vec<PathBuf>>>);
+
+impl WindowFiles {
+    pub fn assign(&self, label: &str, files: Vec<PathBuf>) {
+        self.0.lock().unwrap().insert(label.to_string(), files);
+    }
+
+    /// The files of window `label`, once
+    pub fn take(&self, label: &str) -> Option<Vec<PathBuf>> {
+        self.0.lock().unwrap().remove(label)
+    }
+}
+
+/// Register the state that needs nothing from the app to be created
+pub fn manage<R: Runtime>(builder: Builder<R>) -> Builder<R> {
+    builder
+        .manage(PendingFiles::default())
+        .manage(WindowFiles::default())
+        .manage(watcher::FolderWatchers::default())
+        .manage(folder_index::FolderIndexes::default())
+        .manage(notifications::NotificationState::default())
+        .manage(documents::OpenDocuments::default())
+        .manage(session::SessionState::default())
+        .manage(remote::KnownTrackers::default())
+        .manage(jobs::JobRegistry::default())
+        .manage(access::AccessPolicy::default())
+        .manage(open_queue::OpenQueue::default())
+        .manage(file_open::Forwards::default())
+        .manage(media::AttachmentCache::default())
+        .manage(clipboard::ClipboardFiles::default())
+        .manage(source::SourceCache::default())
+        .manage(attachments::DigestCache::default())
+        .manage(archive::Archives::default())
+        .manage(previews::PreviewCache::default())
+        .manage(prefetch::Prefetcher::default())
+        .manage(avatars::AvatarCache::default())
+        .manage(freshness::Stamps::default())
+        .manage(quarantine::Quarantine::default())
+        .manage(preview_view::PreviewViews::default())
+        .manage(volumes::VolumeWatch::default())
+}
+
+/// Load the settings from the config directory and apply their locale
+pub fn load_settings<R: Runtime>(app: &App<R>) {
+    let settings = match app.path().app_config_dir() {
+        Ok(dir) => SettingsStore::load(dir.join("settings.json")),
+        Err(e) => {
+            log_line!(
+                "Failed to resolve config directory, settings won't persist: {}",
+                e
+            );
+            SettingsStore::in_memory()
+        }
+    };
+    i18n::set_current(settings.get().locale);
+    app.manage(settings);
+}
+
+/// Load the stores kept in the data directory, in memory only when there is none
+pub fn load_data<R: Runtime>(app: &App<R>) {
+    let (view_states, notes, workspaces, audit_log, baselines, drafts) =
+        match app.path().app_data_dir() {
+            Ok(dir) => (
+                view_state::ViewStates::load(dir.join("view-state.json")),
+                notes::Notes::load(dir.join("notes.json")),
+                workspaces::Workspaces::load(dir.join("workspaces.json")),
+                audit::AuditLog::new(dir.join("audit")),
+                integrity::Baselines::load(dir.join("integrity.json")),
+                drafts::Drafts::load(dir.join("drafts.json")),
+            ),
+            Err(_) => (
+                view_state::ViewStates::in_memory(),
+                notes::Notes::in_memory(),
+                workspaces::Workspaces::in_memory(),
+                audit::AuditLog::in_memory(),
+                integrity::Baselines::in_memory(),
+                drafts::Drafts::in_memory(),
+            ),
+        };
+    app.manage(view_states);
+    app.manage(notes);
+    app.manage(workspaces);
+    app.manage(audit_log);
+    app.manage(baselines);
+    app.manage(drafts);
+}
+
+/// The state behind the file-opening commands, without a running app, and a folder of
+/// message files for them to open
+#[cfg(test)]
+pub struct Harness {
+    pub pending: PendingFiles,
+    pub windows: WindowFiles,
+    pub policy: access::AccessPolicy,
+    pub dir: PathBuf,
+    /// Removes `dir` when the harness is dropped, also when the test panics
+    _temp: tempfile::TempDir,
+}
+
+#[cfg(test)]
+impl Harness {
+    /// `files` are created empty in a fresh folder of its own, named after `name`
+    pub fn new(name: &str, files: &[&str]) -> Self {
+        let temp = tempfile::Builder::new()
+            .prefix(&format!("msgreader-{}-", name))
+            .tempdir()
+            .unwrap();
+        let dir = temp.path().to_path_buf();
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        Self {
+            pending: PendingFiles::default(),
+            windows: WindowFiles::default(),
+            policy: access::AccessPolicy::default(),
+            dir,
+            _temp: temp,
+        }
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+}
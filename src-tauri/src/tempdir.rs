@@ -0,0 +1,538 @@
+use crate::filenames::{sanitize_filename, TargetOs};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Session directories are named this and the process id
+const SESSION_DIR_PREFIX: &str = "msgreader-";
+
+/// Mark-of-the-Web for files from the internet zone, so SmartScreen checks them on launch
+#[cfg(windows)]
+const ZONE_IDENTIFIER: &str = "[ZoneTransfer]\r\nZoneId=3\r\n";
+
+/// Prefix of the error for a file that doesn't fit: `DiskFull: <needed> <available>`, in bytes
+pub const DISK_FULL: &str = "DiskFull";
+
+/// Prefix of the error returned when no temp location can be written; the rest says why
+/// each was skipped
+pub const TEMP_UNAVAILABLE: &str = "TempUnavailable";
+
+/// Space kept free on top of a file's size, for the OS and the app opening it
+const SPACE_MARGIN: u64 = 16 * 1024 * 1024;
+
+/// Skipped locations kept for `get_storage_paths`
+const MAX_SKIPPED: usize = 10;
+
+/// Where a temp location comes from, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// The user's temp directory
+    System,
+    /// The app's cache directory
+    AppCache,
+    /// A directory chosen by the user (the `temp_dir` setting)
+    Settings,
+}
+
+/// A location that was passed over, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct Skipped {
+    pub source: Source,
+    pub path: String,
+    pub reason: String,
+}
+
+/// The temp location in use, as shown by `get_storage_paths`
+#[derive(Debug, Clone, Serialize)]
+pub struct Usage {
+    pub source: Source,
+    /// The locations tried before it, latest attempt first
+    pub skipped: Vec<Skipped>,
+}
+
+struct Locations {
+    /// Base directories, in order of preference
+    candidates: Vec<(Source, PathBuf)>,
+    /// Index of the one in use
+    current: usize,
+    skipped: Vec<Skipped>,
+    /// Session directories of this run in locations given up on, still holding files
+    abandoned: Vec<PathBuf>,
+}
+
+static LOCATIONS: Mutex<Option<Locations>> = Mutex::new(None);
+
+fn with_locations<T>(f: impl FnOnce(&mut Locations) -> T) -> T {
+    let mut locations = LOCATIONS.lock().unwrap();
+    f(locations.get_or_insert_with(|| Locations::new(vec![(Source::System, base_dir())])))
+}
+
+/// Set the fallbacks behind the system temp directory (called at startup) and pick the
+/// first location that can be written
+pub fn configure(app_cache: Option<PathBuf>, user_dir: Option<PathBuf>) {
+    with_locations(|locations| {
+        locations.candidates.truncate(1);
+        locations
+            .candidates
+            .extend(app_cache.map(|dir| (Source::AppCache, dir)));
+        locations
+            .candidates
+            .extend(user_dir.map(|dir| (Source::Settings, dir)));
+        locations.current = 0;
+        locations.skipped.clear();
+    });
+    match session_dir() {
+        Ok(dir) => {
+            if !usage().skipped.is_empty() {
+                log_line!("Using fallback temp directory {}", dir.display());
+            }
+        }
+        Err(e) => log_line!("{}", e),
+    }
+}
+
+/// Make a directory chosen by the user the last fallback (see the `temp_dir` setting); once
+/// the others have failed, it is used from the next write on
+pub fn set_user_dir(dir: PathBuf) {
+    with_locations(|locations| {
+        locations
+            .candidates
+            .retain(|(source, _)| *source != Source::Settings);
+        locations.candidates.push((Source::Settings, dir));
+        locations.current = locations.current.min(locations.candidates.len() - 1);
+    });
+}
+
+/// Which location is in use and which were skipped
+pub fn usage() -> Usage {
+    with_locations(|locations| Usage {
+        source: locations.candidates[locations.current].0,
+        skipped: locations.skipped.iter().rev().cloned().collect(),
+    })
+}
+
+/// Per-run directory for everything the app writes to temp (extracted files, bundles).
+/// On Unix it is only accessible by the user (0700); on Windows it lives in the user's local
+/// temp folder, whose inherited ACLs already keep other users out.
+///
+/// The location is checked for writing first; when it can't be, the next fallback is used
+/// for the rest of the run. Fails with `TempUnavailable` when none can be.
+pub fn session_dir() -> Result<PathBuf, String> {
+    reserve(0)
+}
+
+/// Like `session_dir`, for writing `needed` bytes. A location without the space is passed
+/// over for this file; when none has it, fails with `DiskFull`.
+fn reserve(needed: u64) -> Result<PathBuf, String> {
+    with_locations(|locations| locations.reserve(needed))
+}
+
+impl Locations {
+    fn new(candidates: Vec<(Source, PathBuf)>) -> Self {
+        Self {
+            candidates,
+            current: 0,
+            skipped: Vec::new(),
+            abandoned: Vec::new(),
+        }
+    }
+
+    /// Use another location from now on; files already written stay where they are
+    fn switch_to(&mut self, index: usize) {
+        if index == self.current {
+            return;
+        }
+        let previous = session_dir_in(&self.candidates[self.current].1);
+        if previous.is_dir() && !self.abandoned.contains(&previous) {
+            self.abandoned.push(previous);
+        }
+        self.current = index;
+    }
+
+    fn reserve(&mut self, needed: u64) -> Result<PathBuf, String> {
+        let mut most_available = None;
+        let mut reasons = Vec::new();
+        for index in self.current..self.candidates.len() {
+            let (source, base) = self.candidates[index].clone();
+            let dir = session_dir_in(&base);
+            let reason = match probe(&dir) {
+                Err(e) => format!("not writable: {}", e),
+                Ok(()) => match available_space(&dir) {
+                    Some(available) if available < needed.saturating_add(SPACE_MARGIN) => {
+                        most_available = most_available.max(Some(available));
+                        format!("{} bytes needed, {} available", needed, available)
+                    }
+                    _ => {
+                        self.switch_to(index);
+                        return Ok(dir);
+                    }
+                },
+            };
+            log_line!("Temp directory {} skipped: {}", dir.display(), reason);
+            // Unwritable locations are given up on for the rest of the run, full ones
+            // only for this file
+            if most_available.is_none() && index + 1 < self.candidates.len() {
+                self.switch_to(index + 1);
+            }
+            reasons.push(format!("{} ({})", dir.display(), reason));
+            self.skipped.push(Skipped {
+                source,
+                path: dir.display().to_string(),
+                reason,
+            });
+            let excess = self.skipped.len().saturating_sub(MAX_SKIPPED);
+            self.skipped.drain(..excess);
+        }
+        match most_available {
+            Some(available) => Err(disk_full(needed, available)),
+            None => Err(format!("{}: {}", TEMP_UNAVAILABLE, reasons.join("; "))),
+        }
+    }
+}
+
+fn disk_full(needed: u64, available: u64) -> String {
+    format!("{}: {} {}", DISK_FULL, needed, available)
+}
+
+/// Create the session directory (and its base) and write, then delete a file in it
+fn probe(dir: &Path) -> std::io::Result<()> {
+    if let Some(base) = dir.parent() {
+        std::fs::create_dir_all(base)?;
+    }
+    create_private_dir(dir)?;
+    let path = dir.join(".write-probe");
+    std::fs::write(&path, b"")?;
+    std::fs::remove_file(&path)
+}
+
+fn session_dir_in(base: &Path) -> PathBuf {
+    base.join(format!("{}{}", SESSION_DIR_PREFIX, std::process::id()))
+}
+
+/// Where this run's session directory is (it is created on first use)
+pub fn session_dir_path() -> PathBuf {
+    with_locations(|locations| session_dir_in(&locations.candidates[locations.current].1))
+}
+
+/// This run's session directories in the locations it fell back from
+pub fn abandoned_session_dirs() -> Vec<PathBuf> {
+    with_locations(|locations| locations.abandoned.clone())
+}
+
+/// Session directories of earlier runs, left behind when they crashed or were killed (only
+/// one instance runs at a time)
+pub fn leftover_session_dirs() -> Vec<PathBuf> {
+    let own = format!("{}{}", SESSION_DIR_PREFIX, std::process::id());
+    let bases: Vec<PathBuf> = with_locations(|locations| {
+        locations
+            .candidates
+            .iter()
+            .map(|(_, base)| base.clone())
+            .collect()
+    });
+    bases
+        .iter()
+        .filter_map(|base| std::fs::read_dir(base).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .filter(|name| *name != own)
+                .and_then(|name| name.strip_prefix(SESSION_DIR_PREFIX))
+                .is_some_and(|pid| !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()))
+        })
+        // Not links planted under a predictable name
+        .filter(|path| std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()))
+        .collect()
+}
+
+/// Write an extracted file into the session directory, readable only by the user; `file_name`
+/// is sanitized (a file of the same name is replaced).
+/// Executable attachments never get an execute bit and are marked as downloaded, so the
+/// OS warns before running them.
+/// Fails with `DiskFull` when no temp location has room for it.
+pub fn write_file(file_name: &str, data: &[u8], executable: bool) -> Result<PathBuf, String> {
+    // Only the last component: names from messages must not point elsewhere
+    let name = Path::new(file_name)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let dir = reserve(data.len() as u64)?;
+    let path = dir.join(sanitize_filename(&name, TargetOs::CURRENT));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&path)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    if let Err(e) = file.write_all(data) {
+        drop(file);
+        let _ = std::fs::remove_file(&path);
+        if e.kind() == std::io::ErrorKind::StorageFull {
+            let available = available_space(&dir).unwrap_or(0);
+            return Err(disk_full(data.len() as u64, available));
+        }
+        return Err(format!("Failed to write temp file: {}", e));
+    }
+    #[cfg(unix)]
+    {
+        // An earlier file of the same name may have been created with other permissions
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict temp file: {}", e))?;
+    }
+
+    if executable {
+        mark_downloaded(&path);
+    }
+    Ok(path)
+}
+
+/// `write_file`, asking the user for another directory when no temp location can be
+/// written (the choice is kept in settings)
+pub fn write_file_or_ask(
+    app: &tauri::AppHandle,
+    file_name: &str,
+    data: &[u8],
+    executable: bool,
+) -> Result<PathBuf, String> {
+    match write_file(file_name, data, executable) {
+        Err(e) if e.starts_with(TEMP_UNAVAILABLE) => match choose_user_dir(app)? {
+            Some(_) => write_file(file_name, data, executable),
+            None => Err(e),
+        },
+        result => result,
+    }
+}
+
+/// Let the user pick the directory for temp files in a native dialog and use it; `None`
+/// if cancelled
+pub fn choose_user_dir(app: &tauri::AppHandle) -> Result<Option<PathBuf>, String> {
+    use crate::settings::SettingsStore;
+    use tauri::Manager;
+    use tauri_plugin_dialog::DialogExt;
+
+    let Some(dir) = app
+        .dialog()
+        .file()
+        .set_title(crate::i18n::t("temp.choose_title", &[]))
+        .blocking_pick_folder()
+    else {
+        return Ok(None);
+    };
+    let dir = dir
+        .into_path()
+        .map_err(|e| format!("Invalid directory: {}", e))?;
+    let setting = dir.to_string_lossy().to_string();
+    app.state::<SettingsStore>()
+        .update(|settings| settings.temp_dir = Some(setting))?;
+    set_user_dir(dir.clone());
+    Ok(Some(dir))
+}
+
+/// User-private temp location
+fn base_dir() -> PathBuf {
+    let temp = std::env::temp_dir();
+    #[cfg(windows)]
+    if let Some(local) = std::env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+        // A system-wide TEMP (C:\Windows\Temp, a shared drive) is readable by others
+        if !temp.starts_with(&local) {
+            return local.join("Temp");
+        }
+    }
+    temp
+}
+
+/// Bytes available to the user on the volume of `dir`; `None` if unknown
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is NUL-terminated and stats is written by statvfs before it is read
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs succeeded
+    let stats = unsafe { stats.assume_init() };
+    // The field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+#[cfg(windows)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_to_caller: *mut u64,
+            total: *mut u64,
+            total_free: *mut u64,
+        ) -> i32;
+    }
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let mut available = 0u64;
+    // SAFETY: wide is NUL-terminated; the other outputs may be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    // An existing directory must be ours and not a link planted under a predictable name
+    let meta = std::fs::symlink_metadata(dir)?;
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    if !meta.is_dir() || meta.uid() != uid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "exists and isn't a directory owned by the current user",
+        ));
+    }
+    if meta.mode() & 0o777 != 0o700 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
+
+/// Apply the OS marker for files from the internet: the `Zone.Identifier` stream on
+/// Windows, the quarantine attribute on macOS. Failing to set it isn't fatal.
+fn mark_downloaded(path: &Path) {
+    #[cfg(windows)]
+    {
+        let mut stream = path.as_os_str().to_os_string();
+        stream.push(":Zone.Identifier");
+        if let Err(e) = std::fs::write(&stream, ZONE_IDENTIFIER) {
+            log_line!("Failed to mark {} as downloaded: {}", path.display(), e);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let value = format!("0081;{:x};msgReader;", chrono::Utc::now().timestamp());
+        let status = std::process::Command::new("xattr")
+            .args(["-w", "com.apple.quarantine", &value])
+            .arg(path)
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            log_line!("Failed to mark {} as downloaded", path.display());
+        }
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn files_are_private_and_never_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let path = write_file("../setup.sh", b"#!/bin/sh\necho hi\n", true).unwrap();
+        assert_eq!(path.parent().unwrap(), session_dir().unwrap());
+        assert_eq!(path.file_name().unwrap(), "setup.sh");
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&session_dir().unwrap()), 0o700);
+
+        // Rewriting a file that was made executable clears the bits again
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        write_file("setup.sh", b"#!/bin/sh\n", true).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unwritable_locations_fall_back_and_full_ones_report_the_space() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        // A file where a directory should be can't hold the session directory
+        let blocked = root.join("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        let cache = root.join("cache");
+        let mut locations = Locations::new(vec![
+            (Source::System, blocked.clone()),
+            (Source::AppCache, cache.clone()),
+        ]);
+
+        assert_eq!(locations.reserve(0).unwrap(), session_dir_in(&cache));
+        assert_eq!(locations.current, 1);
+        assert_eq!(locations.skipped.len(), 1);
+        assert_eq!(locations.skipped[0].source, Source::System);
+        assert!(locations.skipped[0].reason.starts_with("not writable"));
+        // The unwritable one isn't tried again
+        locations.reserve(1).unwrap();
+        assert_eq!(locations.skipped.len(), 1);
+
+        #[cfg(unix)]
+        {
+            let error = locations.reserve(u64::MAX / 2).unwrap_err();
+            let available = available_space(&cache).unwrap();
+            assert_eq!(error, disk_full(u64::MAX / 2, available));
+            // Other files still go there
+            assert_eq!(locations.reserve(1).unwrap(), session_dir_in(&cache));
+        }
+
+        let mut nowhere = Locations::new(vec![(Source::System, blocked)]);
+        assert!(nowhere
+            .reserve(0)
+            .unwrap_err()
+            .starts_with(TEMP_UNAVAILABLE));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn executables_get_the_mark_of_the_web() {
+        let path = write_file("invoice.exe", b"MZ\x90\x00", true).unwrap();
+        let mut stream = path.as_os_str().to_os_string();
+        stream.push(":Zone.Identifier");
+        let zone = std::fs::read_to_string(&stream).unwrap();
+        assert!(zone.contains("ZoneId=3"));
+
+        let plain = write_file("notes.txt", b"hello", false).unwrap();
+        let mut stream = plain.as_os_str().to_os_string();
+        stream.push(":Zone.Identifier");
+        assert!(std::fs::metadata(&stream).is_err());
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(plain).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use tauri::webview::Color;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Theme resolved from the OS or the user's explicit override
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    Light,
+    Dark,
+    HighContrast,
+}
+
+impl Theme {
+    /// Window background painted before the webview has rendered anything
+    pub fn background_color(self) -> Color {
+        match self {
+            // Matches --background-color in styles.css
+            Theme::Light => Color(248, 250, 252, 255),
+            Theme::Dark => Color(15, 23, 42, 255),
+            Theme::HighContrast => Color(0, 0, 0, 255),
+        }
+    }
+}
+
+/// Payload of the `theme-changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ThemeInfo {
+    pub theme: Theme,
+    /// True when the theme comes from the OS rather than a settings override
+    pub follows_system: bool,
+}
+
+/// Resolve the effective theme, honoring the settings override
+pub fn current(app: &AppHandle) -> ThemeInfo {
+    let theme_override = app.state::<SettingsStore>().get().theme_override;
+    resolve(theme_override, || detect_system_theme(app))
+}
+
+/// The settings override if there is one, the OS theme (asked only then) otherwise
+fn resolve(theme_override: Option<Theme>, system: impl FnOnce() -> Theme) -> ThemeInfo {
+    match theme_override {
+        Some(theme) => ThemeInfo {
+            theme,
+            follows_system: false,
+        },
+        None => ThemeInfo {
+            theme: system(),
+            follows_system: true,
+        },
+    }
+}
+
+/// Ask the OS for its current theme
+fn detect_system_theme(app: &AppHandle) -> Theme {
+    // The window theme API covers Windows, macOS and the GTK setting on Linux
+    let window_theme = app
+        .get_webview_window("main")
+        .and_then(|window| window.theme().ok());
+    system_theme(platform_high_contrast(), window_theme, platform_theme)
+}
+
+/// High contrast wins over light and dark; the platform's own setting is read when the
+/// window doesn't know its theme
+fn system_theme(
+    high_contrast: bool,
+    window_theme: Option<tauri::Theme>,
+    platform: impl FnOnce() -> Option<Theme>,
+) -> Theme {
+    if high_contrast {
+        return Theme::HighContrast;
+    }
+    match window_theme {
+        Some(tauri::Theme::Dark) => Theme::Dark,
+        Some(_) => Theme::Light,
+        None => platform().unwrap_or(Theme::Light),
+    }
+}
+
+/// Paint the window background and notify the frontend about the effective theme
+pub fn apply(app: &AppHandle) {
+    let info = current(app);
+
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window.set_background_color(Some(info.theme.background_color())) {
+            log_line!("Failed to set window background: {}", e);
+        }
+    }
+
+    if let Err(e) = app.emit("theme-changed", &info) {
+        log_line!("Failed to emit theme-changed event: {}", e);
+    }
+}
+
+/// Handle an OS theme flip; ignored while a settings override is active
+pub fn on_system_theme_changed(app: &AppHandle) {
+    if app.state::<SettingsStore>().get().theme_override.is_none() {
+        apply(app);
+    }
+}
+
+/// Run a settings query tool and return its trimmed stdout
+fn query(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Read a REG_DWORD value via reg.exe (e.g. "Flags    REG_SZ    126")
+#[cfg(target_os = "windows")]
+fn read_registry_number(key: &str, value: &str) -> Option<u32> {
+    let output = query("reg", &["query", key, "/v", value])?;
+    let raw = output.split_whitespace().last()?;
+    match raw.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => raw.parse().ok(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn platform_high_contrast() -> bool {
+    const HCF_HIGHCONTRASTON: u32 = 0x1;
+    read_registry_number(r"HKCU\Control Panel\Accessibility\HighContrast", "Flags")
+        .is_some_and(|flags| flags & HCF_HIGHCONTRASTON != 0)
+}
+
+#[cfg(target_os = "windows")]
+fn platform_theme() -> Option<Theme> {
+    let light = read_registry_number(
+        r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+        "AppsUseLightTheme",
+    )?;
+    Some(if light == 0 {
+        Theme::Dark
+    } else {
+        Theme::Light
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn platform_high_contrast() -> bool {
+    query(
+        "defaults",
+        &["read", "com.apple.universalaccess", "increaseContrast"],
+    )
+    .is_some_and(|value| value == "1")
+}
+
+#[cfg(target_os = "macos")]
+fn platform_theme() -> Option<Theme> {
+    // The key is absent entirely in light mode, so a failed read means light
+    match query("defaults", &["read", "-g", "AppleInterfaceStyle"]) {
+        Some(style) if style.eq_ignore_ascii_case("dark") => Some(Theme::Dark),
+        _ => Some(Theme::Light),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn gtk_theme_name() -> Option<String> {
+    std::env::var("GTK_THEME")
+        .ok()
+        .or_else(|| {
+            query(
+                "gsettings",
+                &["get", "org.gnome.desktop.interface", "gtk-theme"],
+            )
+        })
+        .map(|name| name.trim_matches('\'').to_lowercase())
+}
+
+#[cfg(target_os = "linux")]
+fn platform_high_contrast() -> bool {
+    gtk_theme_name().is_some_and(|name| name.contains("highcontrast"))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_theme() -> Option<Theme> {
+    if let Some(scheme) = query(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "color-scheme"],
+    ) {
+        if scheme.contains("prefer-dark") {
+            return Some(Theme::Dark);
+        }
+    }
+    let name = gtk_theme_name()?;
+    Some(if name.contains("dark") {
+        Theme::Dark
+    } else {
+        Theme::Light
+    })
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn platform_high_contrast() -> bool {
+    false
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn platform_theme() -> Option<Theme> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_settings_override_wins_over_the_system_theme() {
+        let info = resolve(Some(Theme::Light), || panic!("the OS isn't asked"));
+        assert_eq!((info.theme, info.follows_system), (Theme::Light, false));
+        let info = resolve(Some(Theme::HighContrast), || Theme::Dark);
+        assert_eq!(
+            (info.theme, info.follows_system),
+            (Theme::HighContrast, false)
+        );
+        let info = resolve(None, || Theme::Dark);
+        assert_eq!((info.theme, info.follows_system), (Theme::Dark, true));
+
+        let theme: Option<Theme> = serde_json::from_str("\"high-contrast\"").unwrap();
+        assert_eq!(theme, Some(Theme::HighContrast));
+    }
+
+    #[test]
+    fn system_high_contrast_wins_over_light_and_dark() {
+        let unused = || -> Option<Theme> { panic!("the platform isn't asked") };
+        assert_eq!(
+            system_theme(true, Some(tauri::Theme::Light), unused),
+            Theme::HighContrast
+        );
+        assert_eq!(system_theme(true, None, unused), Theme::HighContrast);
+        assert_eq!(
+            system_theme(false, Some(tauri::Theme::Dark), unused),
+            Theme::Dark
+        );
+        assert_eq!(
+            system_theme(false, Some(tauri::Theme::Light), unused),
+            Theme::Light
+        );
+        assert_eq!(system_theme(false, None, || Some(Theme::Dark)), Theme::Dark);
+        assert_eq!(system_theme(false, None, || None), Theme::Light);
+    }
+}
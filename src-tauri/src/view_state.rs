@@ -0,0 +1,290 @@
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Oldest entries are evicted beyond this many files
+const MAX_ENTRIES: usize = 5000;
+
+/// Opaque per-file UI state (scroll position, collapsed quotes, ...) keyed by content hash,
+/// along with the charset chosen for the file's bodies with `reparse_with_encoding`
+pub struct ViewStates {
+    path: Option<PathBuf>,
+    /// Off in paranoid mode: states are kept in memory only
+    on_disk: AtomicBool,
+    inner: Mutex<ViewStateFile>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ViewStateFile {
+    /// Monotonic counter used as the LRU clock
+    clock: u64,
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// JSON document owned by the frontend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    /// Charset label the file's bodies are decoded with whatever they declare
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    last_used: u64,
+    /// Unix time of the last use, for the retention policy
+    #[serde(default)]
+    used_at: i64,
+}
+
+impl ViewStates {
+    /// Load stored states, silently dropping entries that aren't valid JSON
+    pub fn load(path: PathBuf) -> Self {
+        let mut file: ViewStateFile = persist::read_json(&path).unwrap_or_default();
+        for entry in file.entries.values_mut() {
+            entry.state = entry.state.take().filter(|state| is_json(state));
+        }
+        file.entries.retain(|_, entry| !entry.is_empty());
+        // Entries from before use times were recorded count as used now
+        let now = now();
+        for entry in file.entries.values_mut().filter(|entry| entry.used_at == 0) {
+            entry.used_at = now;
+        }
+        Self {
+            path: Some(path),
+            on_disk: AtomicBool::new(true),
+            inner: Mutex::new(file),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            on_disk: AtomicBool::new(false),
+            inner: Mutex::new(ViewStateFile::default()),
+        }
+    }
+
+    /// The file states are stored in while they are kept on disk
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path
+            .as_deref()
+            .filter(|_| self.on_disk.load(Ordering::Relaxed))
+    }
+
+    /// Keep states on disk or in memory only; the file is deleted when going to memory and
+    /// written anew when coming back. Returns the size of a deleted file.
+    pub fn set_on_disk(&self, on_disk: bool) -> Result<Option<u64>, String> {
+        let file = self.inner.lock().unwrap();
+        if self.on_disk.swap(on_disk, Ordering::Relaxed) == on_disk {
+            return Ok(None);
+        }
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        if on_disk {
+            return persist::write_json(path, &*file).map(|()| None);
+        }
+        let size = std::fs::metadata(path).map(|m| m.len()).ok();
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(size),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to delete {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Drop the states not used since `cutoff` (Unix time); the number dropped
+    pub fn remove_unused_since(&self, cutoff: i64) -> Result<usize, String> {
+        let mut file = self.inner.lock().unwrap();
+        let before = file.entries.len();
+        file.entries.retain(|_, entry| entry.used_at >= cutoff);
+        let removed = before - file.entries.len();
+        if removed > 0 {
+            self.persist(&file)?;
+        }
+        Ok(removed)
+    }
+
+    /// Store the state for a file, evicting the least recently used entries when full
+    pub fn save(&self, key: &str, state: String) -> Result<(), String> {
+        validate_key(key)?;
+        if !is_json(&state) {
+            return Err("View state must be valid JSON".to_string());
+        }
+
+        self.update(key, |entry| entry.state = Some(state))
+    }
+
+    /// Stored state for a file; counts as a use for LRU purposes
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.touch(key)?.state
+    }
+
+    /// Remember (or with `None` forget) the charset a file is decoded with
+    pub fn set_encoding(&self, key: &str, encoding: Option<String>) -> Result<(), String> {
+        validate_key(key)?;
+        self.update(key, |entry| entry.encoding = encoding)
+    }
+
+    /// The charset remembered for a file; counts as a use like `get`
+    pub fn encoding(&self, key: &str) -> Option<String> {
+        self.touch(key)?.encoding
+    }
+
+    /// Whether a charset is remembered for any file, sparing the content hash otherwise
+    pub fn has_encodings(&self) -> bool {
+        let file = self.inner.lock().unwrap();
+        file.entries.values().any(|entry| entry.encoding.is_some())
+    }
+
+    /// Change the entry of a file, dropping it once empty and evicting the least recently
+    /// used entries when full
+    fn update(&self, key: &str, change: impl FnOnce(&mut Entry)) -> Result<(), String> {
+        let mut file = self.inner.lock().unwrap();
+        file.clock += 1;
+        let last_used = file.clock;
+        let entry = file.entries.entry(key.to_string()).or_insert(Entry {
+            state: None,
+            encoding: None,
+            last_used,
+            used_at: 0,
+        });
+        change(entry);
+        entry.last_used = last_used;
+        entry.used_at = now();
+        if entry.is_empty() {
+            file.entries.remove(key);
+        }
+
+        if file.entries.len() > MAX_ENTRIES {
+            let mut by_age: Vec<(u64, String)> = file
+                .entries
+                .iter()
+                .map(|(key, entry)| (entry.last_used, key.clone()))
+                .collect();
+            by_age.sort();
+            let excess = file.entries.len() - MAX_ENTRIES;
+            for (_, key) in by_age.into_iter().take(excess) {
+                file.entries.remove(&key);
+            }
+        }
+
+        self.persist(&file)
+    }
+
+    /// The entry of a file, marked as used
+    fn touch(&self, key: &str) -> Option<Entry> {
+        let mut file = self.inner.lock().unwrap();
+        file.clock += 1;
+        let clock = file.clock;
+        let entry = file.entries.get_mut(key)?;
+        entry.last_used = clock;
+        entry.used_at = now();
+        Some(entry.clone())
+    }
+
+    /// Remove every stored state; the number removed
+    pub fn clear(&self) -> Result<usize, String> {
+        let mut file = self.inner.lock().unwrap();
+        let removed = file.entries.len();
+        file.entries.clear();
+        self.persist(&file)?;
+        Ok(removed)
+    }
+
+    fn persist(&self, file: &ViewStateFile) -> Result<(), String> {
+        match self.file_path() {
+            Some(path) => persist::write_json(path, file),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.state.is_none() && self.encoding.is_none()
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn is_json(text: &str) -> bool {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok()
+}
+
+/// Keys are content hashes (see `get_content_hash`)
+pub(crate) fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > 128 || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid content hash: {}", key));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_at_the_cap() {
+        let states = ViewStates::in_memory();
+        for i in 0..MAX_ENTRIES {
+            states.save(&format!("k{i}"), "{}".to_string()).unwrap();
+        }
+        // Reading an entry counts as a use, so the next oldest goes first
+        assert!(states.get("k0").is_some());
+        states.set_encoding("new", Some("koi8-r".into())).unwrap();
+        assert!(states.get("k0").is_some());
+        assert!(states.get("k1").is_none());
+        assert!(states.get("k2").is_some());
+        assert_eq!(states.encoding("new").as_deref(), Some("koi8-r"));
+        assert_eq!(states.inner.lock().unwrap().entries.len(), MAX_ENTRIES);
+
+        // Forgetting the charset of an entry with no state drops it
+        states.set_encoding("new", None).unwrap();
+        assert!(states.encoding("new").is_none());
+        assert_eq!(states.inner.lock().unwrap().entries.len(), MAX_ENTRIES - 1);
+    }
+
+    #[test]
+    fn corrupt_entries_are_dropped_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("view-state.json");
+        std::fs::write(
+            &path,
+            r#"{"clock": 7, "entries": {
+                "good": {"state": "{\"scroll\": 10}", "last_used": 3, "used_at": 1700000000},
+                "broken": {"state": "{\"scroll\": ", "last_used": 4},
+                "charset": {"state": "not json", "encoding": "windows-1252", "last_used": 5}
+            }}"#,
+        )
+        .unwrap();
+        let states = ViewStates::load(path.clone());
+        assert_eq!(states.get("good").as_deref(), Some("{\"scroll\": 10}"));
+        assert!(states.get("broken").is_none());
+        assert_eq!(states.get("charset"), None);
+        assert_eq!(states.encoding("charset").as_deref(), Some("windows-1252"));
+        assert_eq!(states.inner.lock().unwrap().entries.len(), 2);
+
+        // Entries without a use time count as used at load
+        let states = ViewStates::load(path.clone());
+        assert_eq!(states.remove_unused_since(now() - 60).unwrap(), 1);
+        assert!(states.get("good").is_none());
+        assert!(states.has_encodings());
+
+        // What is saved survives a reload; an unreadable file starts empty
+        states.save("good", "[1]".to_string()).unwrap();
+        assert_eq!(
+            ViewStates::load(path.clone()).get("good").as_deref(),
+            Some("[1]")
+        );
+        std::fs::write(&path, b"{\"entries\": ").unwrap();
+        assert!(!ViewStates::load(path).has_encodings());
+
+        assert!(states.save("a/b", "{}".to_string()).is_err());
+        assert!(states.save("good", "{".to_string()).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+//! Documents on volumes that go away: a USB stick pulled out, a VPN share dropping. Each
+//! open document remembers the volume it is on; when a read fails because that volume is
+//! gone (not because the file is), the error reads `VolumeUnavailable: <path>\n<volume>`
+//! instead of whatever the OS said, and the first such failure of a document emits a
+//! `document-unavailable` event so the frontend can grey it out rather than show the same
+//! error for every attachment and thumbnail. While any document is unavailable, its
+//! volume is polled; once it is back (or `retry_document` finds it back), the file's
+//! freshness is verified and `document-available` tells the frontend whether it has to
+//! reload.
+
+use crate::documents::{OpenDocument, OpenDocuments};
+use crate::{access, archive, freshness};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Prefix of the error for a file on a volume that can't be reached; followed by `: `,
+/// the path, a line break and the volume
+pub const VOLUME_UNAVAILABLE: &str = "VolumeUnavailable";
+
+/// How often the volumes of unavailable documents are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// OS errors of a device or share that is gone, rather than of a missing file
+#[cfg(windows)]
+const UNREACHABLE_ERRORS: &[i32] = &[
+    21,   // ERROR_NOT_READY
+    53,   // ERROR_BAD_NETPATH
+    55,   // ERROR_DEV_NOT_EXIST
+    59,   // ERROR_UNEXP_NET_ERR
+    64,   // ERROR_NETNAME_DELETED
+    67,   // ERROR_BAD_NET_NAME
+    121,  // ERROR_SEM_TIMEOUT
+    1112, // ERROR_NO_MEDIA_IN_DRIVE
+    1167, // ERROR_DEVICE_NOT_CONNECTED
+    1231, // ERROR_NETWORK_UNREACHABLE
+    1232, // ERROR_HOST_UNREACHABLE
+];
+#[cfg(target_os = "macos")]
+const UNREACHABLE_ERRORS: &[i32] = &[
+    5,  // EIO
+    6,  // ENXIO
+    19, // ENODEV
+    50, // ENETDOWN
+    51, // ENETUNREACH
+    57, // ENOTCONN
+    60, // ETIMEDOUT
+    64, // EHOSTDOWN
+    65, // EHOSTUNREACH
+    70, // ESTALE
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const UNREACHABLE_ERRORS: &[i32] = &[
+    5,   // EIO
+    6,   // ENXIO
+    19,  // ENODEV
+    100, // ENETDOWN
+    101, // ENETUNREACH
+    107, // ENOTCONN
+    110, // ETIMEDOUT
+    112, // EHOSTDOWN
+    113, // EHOSTUNREACH
+    116, // ESTALE
+    121, // EREMOTEIO
+    123, // ENOMEDIUM
+];
+
+/// The volume a file is on: its drive or UNC share on Windows, its mount point elsewhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    pub root: PathBuf,
+    /// Device the mount point had when recorded, to tell a mounted volume from the empty
+    /// directory it leaves behind
+    device: Option<u64>,
+}
+
+impl Volume {
+    #[cfg(windows)]
+    pub fn of(path: &Path) -> Option<Self> {
+        let std::path::Component::Prefix(prefix) = path.components().next()? else {
+            return None;
+        };
+        let mut root = PathBuf::from(prefix.as_os_str());
+        root.push(std::path::MAIN_SEPARATOR_STR);
+        Some(Self { root, device: None })
+    }
+
+    /// The highest ancestor on the same device as the nearest existing one
+    #[cfg(not(windows))]
+    pub fn of(path: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let mut ancestors = path
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty());
+        let (mut root, device) = ancestors.by_ref().find_map(|ancestor| {
+            std::fs::metadata(ancestor)
+                .ok()
+                .map(|meta| (ancestor, meta.dev()))
+        })?;
+        for parent in ancestors {
+            match std::fs::metadata(parent) {
+                Ok(meta) if meta.dev() == device => root = parent,
+                _ => break,
+            }
+        }
+        Some(Self {
+            root: root.to_path_buf(),
+            device: Some(device),
+        })
+    }
+
+    /// Whether the volume is mounted and answering. A volume mounted again may come back
+    /// as another device; a mount point on another device than its parent counts as well.
+    pub fn reachable(&self) -> bool {
+        let Ok(meta) = std::fs::metadata(&self.root) else {
+            return false;
+        };
+        let mounted = match self.device {
+            None => true,
+            Some(device) => device_of(&meta) == Some(device) || is_mount_point(&self.root),
+        };
+        mounted && std::fs::read_dir(&self.root).is_ok()
+    }
+}
+
+#[cfg(windows)]
+fn device_of(_meta: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(not(windows))]
+fn device_of(meta: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+fn is_mount_point(root: &Path) -> bool {
+    let Some(parent) = root.parent() else {
+        return true;
+    };
+    let device = |path: &Path| std::fs::metadata(path).ok().and_then(|m| device_of(&m));
+    device(root) != device(parent)
+}
+
+/// Whether `error` says the device or share is gone
+pub fn is_unreachable(error: &std::io::Error) -> bool {
+    error
+        .raw_os_error()
+        .is_some_and(|code| UNREACHABLE_ERRORS.contains(&code))
+}
+
+/// The `VolumeUnavailable` error for a failed access to `path`, when the failure is its
+/// volume being gone: an error only a missing device gives, or a missing file on a
+/// recorded volume that no longer answers
+pub fn classify(path: &Path, error: &std::io::Error, known: Option<&Volume>) -> Option<String> {
+    let gone = is_unreachable(error)
+        || (error.kind() == std::io::ErrorKind::NotFound
+            && known.is_some_and(|volume| !volume.reachable()));
+    if !gone {
+        return None;
+    }
+    let volume = known
+        .map(|volume| volume.root.clone())
+        .or_else(|| Volume::of(path).map(|volume| volume.root))
+        .unwrap_or_else(|| path.to_path_buf());
+    Some(unavailable_error(path, &volume))
+}
+
+fn unavailable_error(path: &Path, volume: &Path) -> String {
+    format!(
+        "{}: {}\n{}",
+        VOLUME_UNAVAILABLE,
+        path.display(),
+        volume.display()
+    )
+}
+
+/// The `VolumeUnavailable` error for `path` when it can't be reached, judged against the
+/// volume recorded for it when the document was opened; reported like [`report`]
+pub fn unavailable(app: &AppHandle, path: &Path) -> Option<String> {
+    let error = std::fs::metadata(path).err()?;
+    let known = app
+        .state::<OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .list()
+        .iter()
+        .find(|doc| doc.path == path)
+        .and_then(|doc| doc.volume.clone());
+    classify(path, &error, known.as_ref()).map(|error| report(app, error))
+}
+
+/// Payload of the `document-unavailable` event
+#[derive(Debug, Clone, Serialize)]
+struct DocumentUnavailable {
+    doc_id: String,
+    path: String,
+    volume: String,
+}
+
+/// Payload of the `document-available` event
+#[derive(Debug, Clone, Serialize)]
+struct DocumentAvailable {
+    doc_id: String,
+    path: String,
+    /// `stale` when the file changed (or went) while the volume was away
+    freshness: freshness::Freshness,
+}
+
+/// Pass `error` on; when it's a `VolumeUnavailable` one, the open documents at its path or
+/// on its volume are marked unavailable first, each emitting `document-unavailable` the
+/// first time, and the volume is polled until they're back
+pub fn report(app: &AppHandle, error: String) -> String {
+    let Some((path, volume)) = error
+        .strip_prefix(VOLUME_UNAVAILABLE)
+        .and_then(|rest| rest.strip_prefix(": "))
+        .and_then(|rest| rest.split_once('\n'))
+    else {
+        return error;
+    };
+    let marked = app
+        .state::<OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .mark_unavailable(Path::new(path), Path::new(volume));
+    if marked.is_empty() {
+        return error;
+    }
+    for doc in &marked {
+        log_line!(
+            "{} is unavailable: {} can't be reached",
+            doc.path.display(),
+            volume
+        );
+        let payload = DocumentUnavailable {
+            doc_id: doc.id.clone(),
+            path: doc.path.to_string_lossy().to_string(),
+            volume: volume.to_string(),
+        };
+        if let Err(e) = app.emit("document-unavailable", &payload) {
+            log_line!("Failed to emit document-unavailable: {}", e);
+        }
+    }
+    watch(app);
+    error
+}
+
+/// Whether the volume polling thread runs
+#[derive(Default)]
+pub struct VolumeWatch(AtomicBool);
+
+/// Poll the volumes of unavailable documents until none is left
+fn watch(app: &AppHandle) {
+    if app.state::<VolumeWatch>().0.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let waiting = unavailable_documents(&app);
+        if waiting.is_empty() {
+            let running = &app.state::<VolumeWatch>().0;
+            running.store(false, Ordering::SeqCst);
+            // A document reported in the meantime found the thread still running
+            if unavailable_documents(&app).is_empty() || running.swap(true, Ordering::SeqCst) {
+                break;
+            }
+            continue;
+        }
+        for doc in waiting {
+            let _ = recover(&app, &doc);
+        }
+    });
+}
+
+fn unavailable_documents(app: &AppHandle) -> Vec<OpenDocument> {
+    app.state::<OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .list()
+        .iter()
+        .filter(|doc| doc.unavailable)
+        .cloned()
+        .collect()
+}
+
+/// Check whether an open document can be read again; see `retry_document`
+pub fn retry(app: &AppHandle, doc_id: &str) -> Result<freshness::Freshness, String> {
+    let doc = app
+        .state::<OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .get(doc_id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown document: {}", doc_id))?;
+    recover(app, &doc)
+}
+
+/// Bring a document back once its volume answers: verify the file against what was
+/// parsed (dropping caches and the archive contents of a changed file), mark it available
+/// and emit `document-available`. Fails with `VolumeUnavailable` while it's still away.
+fn recover(app: &AppHandle, doc: &OpenDocument) -> Result<freshness::Freshness, String> {
+    // A file deleted while the volume was away is back too, as a stale one
+    let back = doc.volume.as_ref().is_none_or(Volume::reachable)
+        && std::fs::metadata(&doc.path)
+            .err()
+            .is_none_or(|e| !is_unreachable(&e));
+    if !back {
+        let volume = doc.volume.as_ref().map_or(&doc.path, |volume| &volume.root);
+        return Err(unavailable_error(&doc.path, volume));
+    }
+
+    let resolved = access::check(app, &doc.path.to_string_lossy())?;
+    let freshness = freshness::verify(app, &resolved);
+    if freshness != freshness::Freshness::Fresh {
+        app.state::<archive::Archives>().forget(&resolved);
+    }
+    let was_unavailable = app
+        .state::<OpenDocuments>()
+        .0
+        .lock()
+        .unwrap()
+        .mark_available(&doc.id);
+    if was_unavailable {
+        log_line!(
+            "{} is available again ({:?})",
+            doc.path.display(),
+            freshness
+        );
+        let payload = DocumentAvailable {
+            doc_id: doc.id.clone(),
+            path: doc.path.to_string_lossy().to_string(),
+            freshness,
+        };
+        if let Err(e) = app.emit("document-available", &payload) {
+            log_line!("Failed to emit document-available: {}", e);
+        }
+    }
+    Ok(freshness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::Registry;
+    use crate::i18n::Localized;
+
+    #[test]
+    fn lost_volumes_are_told_from_missing_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.eml");
+        std::fs::write(&path, b"Subject: x\r\n\r\n").unwrap();
+
+        let volume = Volume::of(&path).unwrap();
+        assert!(path.starts_with(&volume.root));
+        assert!(volume.reachable());
+        let gone = Volume {
+            root: dir.join("unplugged"),
+            device: volume.device,
+        };
+        assert!(!gone.reachable());
+
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(classify(&path, &missing, Some(&volume)), None);
+        assert_eq!(classify(&path, &missing, None), None);
+        let error = classify(&path, &missing, Some(&gone)).unwrap();
+        assert_eq!(
+            Localized::from(error),
+            Localized::new(
+                "error.volume_unavailable",
+                [
+                    ("path", path.display().to_string()),
+                    ("volume", gone.root.display().to_string()),
+                ]
+            )
+        );
+        let device = std::io::Error::from_raw_os_error(UNREACHABLE_ERRORS[0]);
+        assert!(classify(&path, &device, None).is_some());
+
+        let mut registry = Registry::default();
+        let doc = registry.open(path.clone(), "main");
+        let other = registry.open(dir.join("b.eml"), "main");
+        assert_eq!(doc.volume.as_ref(), Some(&volume));
+        let marked = registry.mark_unavailable(&path, &volume.root);
+        let ids: Vec<&str> = marked.iter().map(|doc| doc.id.as_str()).collect();
+        // Everything on the volume goes with it, once
+        assert_eq!(ids, [doc.id.as_str(), other.id.as_str()]);
+        assert!(registry.mark_unavailable(&path, &volume.root).is_empty());
+        assert!(registry.mark_available(&doc.id));
+        assert!(!registry.mark_available(&doc.id));
+        assert!(registry.get(&other.id).unwrap().unavailable);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::folder_index::FolderIndexes;
+use crate::freshness;
+use crate::notifications;
+use crate::parser::MessageFormat;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How often watched folders are rescanned
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watched folders and the stop flags of their polling threads
+#[derive(Default)]
+pub struct FolderWatchers(pub Mutex<HashMap<PathBuf, Arc<AtomicBool>>>);
+
+/// Payload of the `folder-changed` event
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct FolderChange {
+    pub dir: String,
+    pub created: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl FolderChange {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Size and modification time of each supported message file in a folder
+type Snapshot = HashMap<PathBuf, (u64, SystemTime)>;
+
+fn snapshot(dir: &Path) -> Snapshot {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Snapshot::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| MessageFormat::from_path(&entry.path()).is_some())
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((entry.path(), (meta.len(), modified)))
+        })
+        .collect()
+}
+
+/// Compare two snapshots of the same folder
+fn diff(dir: &Path, old: &Snapshot, new: &Snapshot) -> FolderChange {
+    let to_string = |p: &PathBuf| p.to_string_lossy().to_string();
+    let mut change = FolderChange {
+        dir: dir.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+
+    for (path, state) in new {
+        match old.get(path) {
+            None => change.created.push(to_string(path)),
+            Some(previous) if previous != state => change.modified.push(to_string(path)),
+            _ => {}
+        }
+    }
+    change.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .map(to_string)
+        .collect();
+
+    // Stable order so bursts are reported (and notified) predictably
+    change.created.sort();
+    change.modified.sort();
+    change.removed.sort();
+    change
+}
+
+/// Start polling a folder; watching an already watched folder is a no-op
+pub fn watch(app: &AppHandle, watchers: &FolderWatchers, dir: PathBuf) -> Result<(), String> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to watch folder {}: {}", dir.display(), e))?;
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {}", dir.display()));
+    }
+
+    let mut active = watchers.0.lock().unwrap();
+    if active.contains_key(&dir) {
+        return Ok(());
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    active.insert(dir.clone(), stop.clone());
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut previous = snapshot(&dir);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let current = snapshot(&dir);
+            let change = diff(&dir, &previous, &current);
+            previous = current;
+            if change.is_empty() {
+                continue;
+            }
+
+            // Listings fetch their next page from a new snapshot
+            app.state::<FolderIndexes>().invalidate(&dir);
+            for path in change.modified.iter().chain(&change.removed) {
+                freshness::invalidate(&app, Path::new(path));
+            }
+            if let Err(e) = app.emit("folder-changed", &change) {
+                log_line!("Failed to emit folder-changed event: {}", e);
+            }
+            notifications::on_files_created(&app, &change.created);
+        }
+    });
+
+    Ok(())
+}
+
+/// Stop polling a folder
+pub fn unwatch(watchers: &FolderWatchers, dir: &Path) {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    if let Some(stop) = watchers.0.lock().unwrap().remove(&dir) {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
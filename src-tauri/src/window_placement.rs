@@ -0,0 +1,260 @@
+//! Where windows go: new windows cascade from their parent, saved geometry is put back onto
+//! the monitors attached now. Positions and sizes are physical pixels throughout; sizes are
+//! converted between monitors by their scale factors.
+
+/// Offset of a cascaded window from its parent, in logical pixels
+const CASCADE_OFFSET: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Monitor {
+    /// The monitor without taskbars and docks
+    pub work_area: Rect,
+    pub scale: f64,
+}
+
+impl Rect {
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    fn center(&self) -> (i64, i64) {
+        (
+            self.x as i64 + self.width as i64 / 2,
+            self.y as i64 + self.height as i64 / 2,
+        )
+    }
+
+    fn contains(&self, (x, y): (i64, i64)) -> bool {
+        (self.x as i64..self.right()).contains(&x) && (self.y as i64..self.bottom()).contains(&y)
+    }
+
+    /// Squared distance from a point outside to the nearest edge; 0 inside
+    fn distance_squared(&self, (x, y): (i64, i64)) -> i64 {
+        let dx = (self.x as i64 - x).max(x - (self.right() - 1)).max(0);
+        let dy = (self.y as i64 - y).max(y - (self.bottom() - 1)).max(0);
+        dx * dx + dy * dy
+    }
+
+    fn scaled(self, factor: f64) -> Self {
+        Self {
+            width: (self.width as f64 * factor).round() as u32,
+            height: (self.height as f64 * factor).round() as u32,
+            ..self
+        }
+    }
+}
+
+/// The monitor under the center of `rect`, or the nearest one when that is off every
+/// monitor (it was on one that has been disconnected)
+pub fn monitor_for<'a>(rect: &Rect, monitors: &'a [Monitor]) -> Option<&'a Monitor> {
+    let center = rect.center();
+    monitors
+        .iter()
+        .find(|monitor| monitor.work_area.contains(center))
+        .or_else(|| {
+            monitors
+                .iter()
+                .min_by_key(|monitor| monitor.work_area.distance_squared(center))
+        })
+}
+
+/// `rect` moved into `area`, and shrunk where it is larger
+pub fn clamp(rect: Rect, area: &Rect) -> Rect {
+    let width = rect.width.min(area.width);
+    let height = rect.height.min(area.height);
+    let max_x = area.right() - width as i64;
+    let max_y = area.bottom() - height as i64;
+    Rect {
+        x: (rect.x as i64).clamp(area.x as i64, max_x) as i32,
+        y: (rect.y as i64).clamp(area.y as i64, max_y) as i32,
+        width,
+        height,
+    }
+}
+
+/// A new window of `size` (logical pixels) below and right of `parent`, on the parent's
+/// monitor; a window that wouldn't fit there starts over at the work area's top left
+pub fn cascade(parent: Rect, size: (f64, f64), monitors: &[Monitor]) -> Rect {
+    let Some(monitor) = monitor_for(&parent, monitors) else {
+        return Rect {
+            x: parent.x + CASCADE_OFFSET as i32,
+            y: parent.y + CASCADE_OFFSET as i32,
+            width: size.0.round() as u32,
+            height: size.1.round() as u32,
+        };
+    };
+    let area = monitor.work_area;
+    let offset = (CASCADE_OFFSET * monitor.scale).round() as i32;
+    let mut rect = Rect {
+        x: parent.x + offset,
+        y: parent.y + offset,
+        width: (size.0 * monitor.scale).round() as u32,
+        height: (size.1 * monitor.scale).round() as u32,
+    };
+    if rect.right() > area.right() || rect.bottom() > area.bottom() {
+        rect.x = area.x;
+        rect.y = area.y;
+    }
+    clamp(rect, &area)
+}
+
+/// Geometry saved at scale factor `saved_scale` put back on the current monitors: where
+/// it was when its monitor is still there, centered on the nearest one otherwise, and
+/// resized so it keeps its logical size on a monitor with another scale factor
+pub fn restore(saved: Rect, saved_scale: f64, monitors: &[Monitor]) -> Rect {
+    let Some(monitor) = monitor_for(&saved, monitors) else {
+        return saved;
+    };
+    let area = monitor.work_area;
+    let mut rect = saved.scaled(monitor.scale / saved_scale.max(0.1));
+    if !area.contains(saved.center()) {
+        rect.x = (area.x as i64 + (area.width as i64 - rect.width as i64) / 2) as i32;
+        rect.y = (area.y as i64 + (area.height as i64 - rect.height as i64) / 2) as i32;
+    }
+    clamp(rect, &area)
+}
+
+/// The monitors attached now
+pub fn monitors(window: &tauri::WebviewWindow) -> Vec<Monitor> {
+    window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|monitor| {
+            let area = monitor.work_area();
+            Monitor {
+                work_area: Rect {
+                    x: area.position.x,
+                    y: area.position.y,
+                    width: area.size.width,
+                    height: area.size.height,
+                },
+                scale: monitor.scale_factor(),
+            }
+        })
+        .collect()
+}
+
+/// Outer position and size of a window
+pub fn outer_rect(window: &tauri::WebviewWindow) -> Option<Rect> {
+    let position = window.outer_position().ok()?;
+    let size = window.outer_size().ok()?;
+    Some(Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+pub fn apply(window: &tauri::WebviewWindow, rect: Rect) {
+    let _ = window.set_size(tauri::PhysicalSize::new(rect.width, rect.height));
+    let _ = window.set_position(tauri::PhysicalPosition::new(rect.x, rect.y));
+}
+
+/// Keep a window that isn't restored from a session (the main window on first run)
+/// within the work area of its monitor
+pub fn fit(window: &tauri::WebviewWindow) {
+    let monitors = monitors(window);
+    if let Some(rect) = outer_rect(window) {
+        if let Some(monitor) = monitor_for(&rect, &monitors) {
+            let fitted = clamp(rect, &monitor.work_area);
+            if fitted != rect {
+                apply(window, fitted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A 4K monitor at 200% with a 40 px (logical) taskbar, and a 1080p one at 100%
+    /// placed at `offset` relative to it
+    fn layout(offset: (i32, i32)) -> Vec<Monitor> {
+        vec![
+            Monitor {
+                work_area: rect(0, 0, 3840, 2080),
+                scale: 2.0,
+            },
+            Monitor {
+                work_area: rect(offset.0, offset.1, 1920, 1040),
+                scale: 1.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn cascades_within_the_parents_monitor() {
+        let monitors = layout((3840, 0));
+        let placed = cascade(rect(100, 100, 2400, 1600), (1200.0, 800.0), &monitors);
+        assert_eq!(placed, rect(164, 164, 2400, 1600));
+
+        // Near the bottom right corner the cascade starts over
+        let placed = cascade(rect(1400, 400, 2400, 1600), (1200.0, 800.0), &monitors);
+        assert_eq!(placed, rect(0, 0, 2400, 1600));
+
+        // On the 1080p monitor, at its scale; too wide for it, the window is shrunk
+        let placed = cascade(rect(3900, 10, 1200, 800), (1000.0, 600.0), &monitors);
+        assert_eq!(placed, rect(3932, 42, 1000, 600));
+        let placed = cascade(rect(3900, 10, 1200, 800), (2000.0, 800.0), &monitors);
+        assert_eq!(placed, rect(3840, 0, 1920, 800));
+    }
+
+    #[test]
+    fn restores_onto_current_monitors() {
+        // Primary right: the secondary is at negative x and the window stays on it
+        let monitors = layout((-1920, 0));
+        let saved = rect(-1800, 100, 1200, 800);
+        assert_eq!(restore(saved, 1.0, &monitors), saved);
+
+        // Vertically stacked, secondary above
+        let monitors = layout((0, -1080));
+        let saved = rect(300, -1000, 1200, 800);
+        assert_eq!(restore(saved, 1.0, &monitors), saved);
+
+        // Saved at 200% on the 4K monitor, restored where the 1080p monitor is now
+        let monitors = layout((3840, 0));
+        assert_eq!(
+            restore(rect(4000, 100, 2400, 1600), 2.0, &monitors),
+            rect(4000, 100, 1200, 800)
+        );
+    }
+
+    #[test]
+    fn positions_on_disconnected_monitors_migrate() {
+        let monitors = layout((3840, 0));
+        let primary = &monitors[..1];
+        // Saved on the secondary, which is gone: centered on the primary at its scale
+        assert_eq!(
+            restore(rect(4000, 100, 1200, 800), 1.0, primary),
+            rect(720, 240, 2400, 1600)
+        );
+        assert_eq!(
+            monitor_for(&rect(-5000, 5000, 100, 100), &monitors),
+            Some(&monitors[0])
+        );
+    }
+}
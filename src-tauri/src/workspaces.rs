@@ -0,0 +1,205 @@
+use crate::folder_index::{ListingFilter, SortBy};
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Longest workspace name accepted, in characters
+const MAX_NAME_CHARS: usize = 100;
+
+/// The folders, listing settings and tags of one matter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceDefinition {
+    /// Folders to watch and index
+    pub folders: Vec<String>,
+    /// Order and filter the listings start with
+    pub sort_by: SortBy,
+    pub filter: ListingFilter,
+    /// Tag names with their colors (`None` for no color), written to `tag_colors` on open
+    pub tags: BTreeMap<String, Option<String>>,
+}
+
+/// A named definition, as listed and as exported to share
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub definition: WorkspaceDefinition,
+}
+
+/// A workspace as opened, with the folders that weren't found
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedWorkspace {
+    #[serde(flatten)]
+    pub workspace: Workspace,
+    pub missing_folders: Vec<String>,
+}
+
+/// Stored workspaces by name, persisted to a JSON file
+pub struct Workspaces {
+    path: Option<PathBuf>,
+    entries: Mutex<BTreeMap<String, WorkspaceDefinition>>,
+}
+
+impl Workspaces {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = persist::read_json(&path).unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Store `definition` as `name`, replacing a workspace of that name
+    pub fn save(&self, name: &str, definition: WorkspaceDefinition) -> Result<String, String> {
+        let name = normalize_name(name)?;
+        let mut entries = self.entries.lock().unwrap();
+        let previous = entries.insert(name.clone(), definition);
+        if let Err(e) = self.persist(&entries) {
+            match previous {
+                Some(previous) => entries.insert(name, previous),
+                None => entries.remove(&name),
+            };
+            return Err(e);
+        }
+        Ok(name)
+    }
+
+    /// Store an imported workspace under its name, or with a number appended when a
+    /// workspace of that name exists; returns the name used
+    pub fn import(&self, workspace: Workspace) -> Result<String, String> {
+        let name = normalize_name(&workspace.name)?;
+        let taken = |candidate: &str| self.entries.lock().unwrap().contains_key(candidate);
+        let name = (1..)
+            .map(|n| match n {
+                1 => name.clone(),
+                n => format!("{} ({})", name, n),
+            })
+            .find(|candidate| !taken(candidate))
+            .unwrap_or(name);
+        self.save(&name, workspace.definition)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Workspace> {
+        let name = name.trim();
+        self.entries
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|definition| Workspace {
+                name: name.to_string(),
+                definition: definition.clone(),
+            })
+    }
+
+    /// All workspaces by name
+    pub fn list(&self) -> Vec<Workspace> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, definition)| Workspace {
+                name: name.clone(),
+                definition: definition.clone(),
+            })
+            .collect()
+    }
+
+    /// Forget a workspace; false if there was none of that name
+    pub fn remove(&self, name: &str) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(removed) = entries.remove(name.trim()) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.persist(&entries) {
+            entries.insert(name.trim().to_string(), removed);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn persist(&self, entries: &BTreeMap<String, WorkspaceDefinition>) -> Result<(), String> {
+        match &self.path {
+            Some(path) => persist::write_json(path, entries),
+            None => Ok(()),
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_CHARS
+        || name.chars().any(char::is_control)
+    {
+        return Err(format!("Invalid workspace name: {:?}", name));
+    }
+    Ok(name.to_string())
+}
+
+/// Folders of `definition` that aren't folders on this machine (moved, renamed, or on a
+/// drive that isn't attached)
+pub fn missing_folders(definition: &WorkspaceDefinition) -> Vec<String> {
+    definition
+        .folders
+        .iter()
+        .filter(|folder| !Path::new(folder).is_dir())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspaces_persist_and_import_under_free_names() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("workspaces.json");
+        let existing = dir.join("matter");
+        std::fs::create_dir_all(&existing).unwrap();
+
+        let workspaces = Workspaces::load(path.clone());
+        let definition = WorkspaceDefinition {
+            folders: vec![
+                existing.display().to_string(),
+                dir.join("moved").display().to_string(),
+            ],
+            sort_by: SortBy::Sender,
+            tags: BTreeMap::from([("privileged".to_string(), Some("#c00".to_string()))]),
+            ..WorkspaceDefinition::default()
+        };
+        assert_eq!(
+            workspaces.save(" Smith v. Jones ", definition.clone()),
+            Ok("Smith v. Jones".to_string())
+        );
+        assert!(workspaces.save("  ", definition.clone()).is_err());
+        assert_eq!(
+            missing_folders(&definition),
+            [dir.join("moved").display().to_string()]
+        );
+
+        let reloaded = Workspaces::load(path);
+        let shared = reloaded.get("Smith v. Jones").unwrap();
+        assert_eq!(shared.definition, definition);
+        assert_eq!(
+            reloaded.import(shared.clone()),
+            Ok("Smith v. Jones (2)".to_string())
+        );
+        assert_eq!(reloaded.list().len(), 2);
+        assert_eq!(reloaded.remove("Smith v. Jones"), Ok(true));
+    }
+}
@@ -0,0 +1,153 @@
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const METHOD_DEFLATE: u16 = 8;
+/// General purpose flag bit 11: names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+const VERSION_NEEDED: u16 = 20;
+
+/// Minimal streaming zip writer (deflate, no zip64) for bundles and exports
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<CentralEntry>,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        let (dos_time, dos_date) = dos_timestamp(chrono::Local::now().naive_local());
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+            dos_time,
+            dos_date,
+        }
+    }
+
+    /// Compress and append one file
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        let too_large = || format!("Zip entry too large: {}", name);
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(data)
+            .map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        let compressed = encoder
+            .finish()
+            .map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        let compressed_size = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+        let crc = crc32fast::hash(data);
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION_NEEDED);
+        put_u16(&mut header, FLAG_UTF8);
+        put_u16(&mut header, METHOD_DEFLATE);
+        put_u16(&mut header, self.dos_time);
+        put_u16(&mut header, self.dos_date);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, compressed_size);
+        put_u32(&mut header, size);
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, 0);
+        header.extend_from_slice(name.as_bytes());
+
+        self.write(&header)?;
+        self.write(&compressed)?;
+        self.entries.push(CentralEntry {
+            name: name.to_string(),
+            crc,
+            compressed_size,
+            size,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer
+    pub fn finish(mut self) -> Result<W, String> {
+        let directory_offset = self.offset;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut directory, VERSION_NEEDED);
+            put_u16(&mut directory, VERSION_NEEDED);
+            put_u16(&mut directory, FLAG_UTF8);
+            put_u16(&mut directory, METHOD_DEFLATE);
+            put_u16(&mut directory, self.dos_time);
+            put_u16(&mut directory, self.dos_date);
+            put_u32(&mut directory, entry.crc);
+            put_u32(&mut directory, entry.compressed_size);
+            put_u32(&mut directory, entry.size);
+            put_u16(&mut directory, entry.name.len() as u16);
+            put_u16(&mut directory, 0); // extra field length
+            put_u16(&mut directory, 0); // comment length
+            put_u16(&mut directory, 0); // disk number
+            put_u16(&mut directory, 0); // internal attributes
+            put_u32(&mut directory, 0); // external attributes
+            put_u32(&mut directory, entry.offset);
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let count = self.entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        put_u32(&mut end, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, count);
+        put_u16(&mut end, count);
+        put_u32(&mut end, directory.len() as u32);
+        put_u32(&mut end, directory_offset as u32);
+        put_u16(&mut end, 0);
+
+        self.write(&directory)?;
+        self.write(&end)?;
+        self.out
+            .flush()
+            .map_err(|e| format!("Failed to write zip: {}", e))?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.out
+            .write_all(data)
+            .map_err(|e| format!("Failed to write zip: {}", e))?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// MS-DOS time and date fields (2-second resolution, years from 1980)
+fn dos_timestamp(now: chrono::NaiveDateTime) -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+    let year = now.year().clamp(1980, 2107) as u16;
+    let time =
+        ((now.hour() as u16) << 11) | ((now.minute() as u16) << 5) | (now.second() as u16 / 2);
+    let date = ((year - 1980) << 9) | ((now.month() as u16) << 5) | now.day() as u16;
+    (time, date)
+}
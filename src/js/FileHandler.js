@@ -1,5 +1,5 @@
 import { SUPPORTED_EMAIL_EXTENSIONS } from './constants.js';
-import { isTauri, readFileFromPath, getFileName } from './tauri-bridge.js';
+import { isTauri, readFileFromPath, getFileName, inlineImageUrlBuilder } from './tauri-bridge.js';
 
 /**
  * Handles file input via drag-and-drop and file input elements
@@ -245,12 +245,16 @@ class FileHandler {
                         // Read file from filesystem via Tauri
                         const fileBuffer = await readFileFromPath(filePath);
 
+                        // Large inline images are served by the backend instead of embedded
+                        const imageUrl = await inlineImageUrlBuilder(filePath).catch(() => null);
+                        const fileOptions = { ...parseOptions, imageUrl };
+
                         // Parse the email content
                         let msgInfo = null;
                         if (extension === 'msg' && this.extractMsg) {
-                            msgInfo = this.extractMsg(fileBuffer, parseOptions);
+                            msgInfo = this.extractMsg(fileBuffer, fileOptions);
                         } else if (extension === 'eml' && this.extractEml) {
-                            msgInfo = this.extractEml(fileBuffer, parseOptions);
+                            msgInfo = this.extractEml(fileBuffer, fileOptions);
                         }
 
                         if (!msgInfo) {
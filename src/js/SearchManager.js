@@ -35,7 +35,8 @@ export class SearchManager {
 
     /**
      * Checks if a message matches the current query
-     * All search terms must be found somewhere in the message
+     * All search terms must be found somewhere in the message; `lang:de` terms match
+     * the language of the body instead (`lang:de` also matches `de-AT`)
      * @param {Object} message - Message object to check
      * @returns {boolean} True if message matches query
      */
@@ -64,12 +65,27 @@ export class SearchManager {
 
         // All search terms must be found (AND logic)
         return this.searchTerms.every(term => {
+            if (term.startsWith('lang:')) {
+                return this.matchesLanguage(message, term.slice('lang:'.length));
+            }
             const normalizedTerm = this.normalizeText(term);
             return searchableText.includes(term) ||
                    normalizedText.includes(normalizedTerm);
         });
     }
 
+    /**
+     * Checks a message's body language against a `lang:` search term
+     * @param {Object} message - Message object to check
+     * @param {string} code - Language code from the term, lowercased
+     * @returns {boolean} True if the body is in that language
+     */
+    matchesLanguage(message, code) {
+        const wanted = code.split(/[-_]/)[0];
+        const language = message.language?.code?.toLowerCase();
+        return Boolean(wanted) && language === wanted;
+    }
+
     /**
      * Normalizes text by replacing separators with spaces
      * Allows "ada lovelace" to match "ada.lovelace", "ada-lovelace", "ada_lovelace"
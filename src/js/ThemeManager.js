@@ -126,6 +126,15 @@ export class ThemeManager {
         root.dataset.theme = effectiveTheme;
     }
 
+    /**
+     * Switches the high-contrast palette on or off, following the OS setting the backend
+     * reports (see getSystemTheme); it applies on top of the light or dark theme
+     * @param {boolean} enabled - Whether the OS asks for high contrast
+     */
+    setHighContrast(enabled) {
+        document.documentElement.classList.toggle('high-contrast', enabled);
+    }
+
     /**
      * Applies the email content theme
      * @param {string} theme - Email theme to apply
@@ -1,12 +1,15 @@
 /**
  * CID Reference Replacer Module
  * Handles replacement of Content-ID (cid:) references in HTML email content
- * with base64 encoded attachment data
+ * with base64 encoded attachment data, or URLs of the attach:// protocol for large images
  */
 
 import { escapeRegex, tryUrlDecoded, cleanContentId, isImageMimeType } from './helpers.js';
 import { PLACEHOLDER_IMAGE_SVG } from './constants.js';
 
+// Inline images up to this size stay data URIs even when a protocol URL is available
+export const INLINE_DATA_URI_LIMIT = 64 * 1024;
+
 /**
  * Builds an array of regex patterns to match CID references
  * @param {string} contentId - The Content-ID value
@@ -47,16 +50,33 @@ export function buildCidPatterns(contentId, fileName) {
     return patterns;
 }
 
+/**
+ * Picks the src for an inline image: a protocol URL for large images with a content ID,
+ * the base64 data URI otherwise
+ * @param {Object} attachment - The attachment object
+ * @param {function(string): string} [imageUrl] - Builds the URL for a content ID
+ * @returns {string} Image source
+ */
+export function inlineImageSource(attachment, imageUrl) {
+    const contentId = cleanContentId(attachment.pidContentId || attachment.contentId || '');
+    const size = attachment.contentLength || Math.floor(attachment.contentBase64.length * 0.75);
+    if (imageUrl && contentId && size > INLINE_DATA_URI_LIMIT) {
+        return imageUrl(contentId) || attachment.contentBase64;
+    }
+    return attachment.contentBase64;
+}
+
 /**
  * Replaces a single image CID reference with base64 data
  * @param {string} html - The HTML content
  * @param {Object} attachment - The attachment object
+ * @param {string} [source] - Image source to use instead of the base64 data
  * @returns {string} HTML with replaced CID references
  */
-export function replaceImageCid(html, attachment) {
+export function replaceImageCid(html, attachment, source = attachment.contentBase64) {
     const contentId = attachment.pidContentId || attachment.contentId || '';
     const fileName = attachment.fileName || '';
-    const base64String = attachment.contentBase64;
+    const base64String = source;
 
     const patterns = buildCidPatterns(contentId, fileName);
 
@@ -94,9 +114,12 @@ export function replaceHrefCid(html, attachment) {
  * Replaces all CID references in HTML with base64 attachment data
  * @param {string} html - The HTML content containing cid: references
  * @param {Object[]} attachments - Array of attachment objects with contentBase64, contentId, fileName, attachMimeTag
+ * @param {Object} [options] - Options object
+ * @param {function(string): string} [options.imageUrl] - Builds attach:// URLs for content IDs;
+ *     large inline images are then loaded on demand instead of embedded
  * @returns {string} HTML with all CID references replaced
  */
-export function replaceCidReferences(html, attachments) {
+export function replaceCidReferences(html, attachments, options = {}) {
     if (!html) {
         return '';
     }
@@ -110,7 +133,11 @@ export function replaceCidReferences(html, attachments) {
 
             if (isImageMimeType(attachment.attachMimeTag)) {
                 // Image attachments: replace src references
-                result = replaceImageCid(result, attachment);
+                result = replaceImageCid(
+                    result,
+                    attachment,
+                    inlineImageSource(attachment, options.imageUrl)
+                );
             } else if (attachment.contentId || attachment.pidContentId) {
                 // Non-image attachments: replace href references
                 result = replaceHrefCid(result, attachment);
@@ -4,7 +4,23 @@ import UIManager from './UIManager.js';
 import FileHandler from './FileHandler.js';
 import KeyboardManager from './KeyboardManager.js';
 import { extractMsg, extractEml } from './utils.js';
-import { isTauri, getPendingFiles, onFileOpen, onFileDrop, checkForUpdates } from './tauri-bridge.js';
+import {
+    isTauri,
+    getPendingFiles,
+    onFileOpen,
+    onFileDrop,
+    onBulkOpenRequest,
+    onForwardedOpenRequest,
+    onReadProgress,
+    getFileName,
+    confirmBulkOpen,
+    confirmForwardedFiles,
+    getCommandGroups,
+    getSystemTheme,
+    onThemeChanged,
+    setCommandGroup,
+    checkForUpdates
+} from './tauri-bridge.js';
 import { themeManager } from './ThemeManager.js';
 import {
     getInlineImageAttachmentVisibility,
@@ -186,6 +202,37 @@ class App {
  * Called after app initialization when running in Tauri
  */
 async function initTauriFileHandling() {
+    // Many files at once (multi-selection, big drops) are confirmed first; registered before
+    // fetching the pending files, which may arrive this way too
+    await onBulkOpenRequest(async (filePaths) => {
+        if (await confirmBulkOpen(filePaths.length)) {
+            await window.app.fileHandler.handleFilesFromPaths(filePaths);
+        }
+    });
+
+    // Files forwarded by another launch are only opened (and allowed) once confirmed
+    await onForwardedOpenRequest(async (filePaths) => {
+        const paths = await confirmForwardedFiles(filePaths);
+        if (paths && paths.length > 0) {
+            await window.app.fileHandler.handleFilesFromPaths(paths);
+        }
+    });
+
+    // Slow reads are announced once, so opening from a network share or a cloud placeholder
+    // doesn't look like nothing happens
+    const announcedReads = new Set();
+    await onReadProgress(({ path, read, total, hydrating }) => {
+        if (read >= total) {
+            announcedReads.delete(path);
+        } else if (!announcedReads.has(path)) {
+            announcedReads.add(path);
+            const name = getFileName(path);
+            window.app.uiManager.showInfo(
+                hydrating ? `Downloading ${name} from cloud storage…` : `Reading ${name}…`
+            );
+        }
+    });
+
     // Check for files passed on app startup (double-click to open)
     const pendingFiles = await getPendingFiles();
     if (pendingFiles.length > 0) {
@@ -258,6 +305,12 @@ function initTheme() {
                 }));
             } else if (type === 'pdf-attachments') {
                 setPdfAttachmentOpenMode(item.dataset.pdfOpenMode);
+            } else if (type === 'command-group') {
+                // Switching a group on asks in a native dialog; show what it ended up as
+                const enable = !item.classList.contains('active');
+                setCommandGroup(item.dataset.group, enable).then((enabled) => {
+                    item.classList.toggle('active', enabled === true);
+                });
             }
 
             updateThemeUI();
@@ -274,6 +327,22 @@ function initTheme() {
         updateThemeUI();
     });
 
+    // Sensitive command groups are off until switched on here, and the OS high-contrast
+    // setting comes from the backend (desktop app only)
+    if (isTauri()) {
+        const applyHighContrast = (info) => {
+            themeManager.setHighContrast(info?.theme === 'high-contrast');
+        };
+        getSystemTheme().then(applyHighContrast);
+        onThemeChanged(applyHighContrast);
+        document.getElementById('commandGroupsSection')?.classList.remove('hidden');
+        getCommandGroups().then((groups) => {
+            document.querySelectorAll('.theme-menu-item[data-type="command-group"]').forEach(item => {
+                item.classList.toggle('active', groups?.[item.dataset.group] === true);
+            });
+        });
+    }
+
     // Initial UI update
     updateThemeUI();
 }
@@ -6,7 +6,9 @@ import DOMPurify from 'dompurify';
 
 /**
  * Configuration for DOMPurify
- * Allows common email HTML elements while blocking dangerous ones
+ * Allows common email HTML elements while blocking dangerous ones.
+ * The accessible body view (src-tauri/src/parser/accessible.rs) mirrors these lists;
+ * its tests fail when they drift apart.
  */
 export const SANITIZE_CONFIG = {
     ALLOWED_TAGS: [
@@ -51,6 +53,19 @@ export const SANITIZE_CONFIG = {
     RETURN_DOM_FRAGMENT: false
 };
 
+// URLs of the attach:// protocol (http://attach.localhost on Windows)
+const ATTACH_URL = /^(?:attach:\/\/localhost\/|https?:\/\/attach\.localhost\/)/i;
+
+// Attachment URLs come from cid: rewriting and are only allowed as image sources
+DOMPurify.addHook('uponSanitizeAttribute', (node, data) => {
+    if (!ATTACH_URL.test((data.attrValue || '').trim())) return;
+    if (node.nodeName === 'IMG' && data.attrName === 'src') {
+        data.forceKeepAttr = true;
+    } else {
+        data.keepAttr = false;
+    }
+});
+
 /**
  * Removes problematic MS Office CSS properties that break layout in browsers
  * @param {string} html - HTML content with style attributes
@@ -75,12 +90,89 @@ function cleanMsoStyles(html) {
     });
 }
 
+// Images loaded from a web server (protocol-relative URLs included)
+const REMOTE_URL = /^\s*(?:https?:)?\/\//i;
+
+// url(...) in CSS pointing to a web server
+const REMOTE_CSS_URL = /url\(\s*(['"]?)\s*(?:https?:)?\/\/[^)]*?\1\s*\)/gi;
+
+/**
+ * Reads a pixel size from an attribute or style value (`600`, `600px`)
+ * @param {string|null} value - Declared size
+ * @returns {number|null} Size in pixels, or null for relative or missing sizes
+ */
+function pixelSize(value) {
+    const match = /^\s*(\d+(?:\.\d+)?)\s*(?:px)?\s*$/i.exec(value || '');
+    return match ? parseFloat(match[1]) : null;
+}
+
+/**
+ * Builds the placeholder for a blocked remote image: it keeps the declared size so the
+ * layout doesn't collapse, shows the alt text and keeps the URL for loading it later.
+ * Placeholders are spans of class `blocked-remote-image`, so they never look like an
+ * image that failed to load.
+ * @param {HTMLImageElement} img - Remote image
+ * @returns {HTMLSpanElement} Placeholder element
+ */
+function remoteImagePlaceholder(img) {
+    const placeholder = img.ownerDocument.createElement('span');
+    placeholder.className = 'blocked-remote-image';
+    placeholder.setAttribute('role', 'img');
+    placeholder.setAttribute('data-remote-src', img.getAttribute('src').trim());
+
+    const alt = (img.getAttribute('alt') || '').trim();
+    placeholder.setAttribute('aria-label', alt || 'Blocked remote image');
+    if (alt) placeholder.textContent = alt;
+    const title = img.getAttribute('title');
+    if (title) placeholder.setAttribute('title', title);
+
+    const width = pixelSize(img.style.width) ?? pixelSize(img.getAttribute('width'));
+    const height = pixelSize(img.style.height) ?? pixelSize(img.getAttribute('height'));
+    if (width !== null) placeholder.style.width = `${width}px`;
+    if (height !== null) placeholder.style.height = `${height}px`;
+    return placeholder;
+}
+
+/**
+ * Replaces remote images of sanitized HTML with placeholders and drops remote
+ * background images from styles; `srcset` and `background` attributes are already
+ * removed by the sanitizer (see SANITIZE_CONFIG)
+ * @param {string} html - Sanitized HTML
+ * @returns {string} HTML that loads nothing from the network
+ */
+export function blockRemoteImages(html) {
+    if (!html) return '';
+
+    const template = document.createElement('template');
+    template.innerHTML = html;
+    const root = template.content;
+
+    root.querySelectorAll('img[src]').forEach((img) => {
+        if (REMOTE_URL.test(img.getAttribute('src'))) {
+            img.replaceWith(remoteImagePlaceholder(img));
+        }
+    });
+    root.querySelectorAll('[style]').forEach((element) => {
+        const style = element.getAttribute('style');
+        const blocked = style.replace(REMOTE_CSS_URL, 'none');
+        if (blocked !== style) element.setAttribute('style', blocked);
+    });
+    root.querySelectorAll('style').forEach((styleTag) => {
+        styleTag.textContent = styleTag.textContent.replace(REMOTE_CSS_URL, 'none');
+    });
+
+    return template.innerHTML;
+}
+
 /**
  * Sanitizes HTML content to prevent XSS attacks
  * @param {string} html - The untrusted HTML content
+ * @param {Object} [options]
+ * @param {boolean} [options.blockRemoteImages=false] - Replace remote images with
+ *     placeholders (see blockRemoteImages)
  * @returns {string} Sanitized HTML safe for innerHTML
  */
-export function sanitizeHTML(html) {
+export function sanitizeHTML(html, { blockRemoteImages: blockRemote = false } = {}) {
     if (!html) return '';
 
     // Clean problematic MSO styles before sanitizing
@@ -96,7 +188,7 @@ export function sanitizeHTML(html) {
         return escapeHTML(html);
     }
 
-    return cleanHTML;
+    return blockRemote ? blockRemoteImages(cleanHTML) : cleanHTML;
 }
 
 /**
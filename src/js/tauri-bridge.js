@@ -37,47 +37,1420 @@ async function getTauriApis() {
 /**
  * Read a file from the filesystem using Tauri
  * @param {string} filePath - Absolute path to the file
+ * @param {Object} [options]
+ * @param {string} [options.jobId] - Job id under which the read can be cancelled with cancel_job
  * @returns {Promise<ArrayBuffer>} File contents as ArrayBuffer
  */
-export async function readFileFromPath(filePath) {
+export async function readFileFromPath(filePath, options = {}) {
     const apis = await getTauriApis();
     if (!apis) {
         throw new Error('Tauri API not available');
     }
 
     // Call Rust command to read file bytes
-    const bytes = await apis.invoke('read_file_as_bytes', { path: filePath });
+    const args = { path: filePath, jobId: options.jobId ?? null };
+    let bytes;
+    try {
+        bytes = await apis.invoke('read_file_as_bytes', args);
+    } catch (error) {
+        // Paths outside the backend's access policy need the user's approval first
+        if (error?.code !== 'error.permission_denied') {
+            throw error;
+        }
+        const approved = await apis.invoke('request_access', { path: filePath });
+        if (!approved) {
+            throw error;
+        }
+        bytes = await apis.invoke('read_file_as_bytes', args);
+    }
     // Convert array to ArrayBuffer
     return new Uint8Array(bytes).buffer;
 }
 
 /**
- * Get files that were passed to app on startup
- * @returns {Promise<string[]>} Array of file paths
+ * Listen for progress of slow file reads (network shares, cloud placeholder files)
+ * @param {function({path: string, read: number, total: number, hydrating: boolean}): void} callback
+ *     Called with the bytes read so far; hydrating is set while a placeholder is downloaded
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onReadProgress(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('read-progress', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Set the language of text generated by the backend: the `message` of errors and warnings
+ * (which arrive as `{ code, args, message }`), text exports, notifications and dialogs
+ * @param {string} lang - Locale tag such as 'de' or 'de-AT'; unsupported ones fall back to English
+ * @returns {Promise<string|null>} Language used ('en' or 'de'), null outside Tauri
+ */
+export async function setBackendLocale(lang) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('set_locale', { lang });
+}
+
+/**
+ * Switch a gated command group on or off. Commands of a group that is off fail with
+ * `error.command_group_disabled`; switching one on asks the user in a native dialog.
+ * @param {'system-integration'|'crypto'} group - Group id
+ * @param {boolean} enabled - Whether the group's commands may run
+ * @returns {Promise<boolean|null>} Whether the group is on now, null outside Tauri
+ */
+export async function setCommandGroup(group, enabled) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('set_command_group', { group, enabled });
+}
+
+/**
+ * Get the theme resolved by the backend: the OS setting (light, dark or high contrast) or
+ * the override from settings
+ * @returns {Promise<{theme: 'light'|'dark'|'high-contrast', follows_system: boolean}|null>}
+ *     The theme, null outside Tauri
+ */
+export async function getSystemTheme() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_system_theme');
+}
+
+/**
+ * Listen for theme changes, from the OS or the settings override
+ * @param {function({theme: string, follows_system: boolean}): void} callback - Called with
+ *     the new theme
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onThemeChanged(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('theme-changed', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Get which gated command groups are switched on; groups without an entry are off
+ * @returns {Promise<Object<string, boolean>|null>} On/off by group id, null outside Tauri
+ */
+export async function getCommandGroups() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    const settings = await apis.invoke('get_settings');
+    return settings.command_groups ?? {};
+}
+
+/**
+ * Get the security mode. In quarantine mode nothing leaves the app: saving attachments,
+ * opening them in other applications and remote content fail with
+ * `error.quarantine_blocked`.
+ * @returns {Promise<{quarantine: boolean, locked: boolean}|null>} `locked` when the
+ *     `--quarantine` command-line flag switched it on; null outside Tauri
+ */
+export async function getSecurityMode() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_security_mode');
+}
+
+/**
+ * Switch quarantine mode on or off; switching it off asks the user in a native dialog
+ * and fails while it is locked
+ * @param {boolean} enabled - Whether quarantine mode should be on
+ * @returns {Promise<{quarantine: boolean, locked: boolean}|null>} The resulting mode, null
+ *     outside Tauri
+ */
+export async function setQuarantineMode(enabled) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('set_quarantine_mode', { enabled });
+}
+
+/**
+ * Listen for quarantine mode being switched on or off
+ * @param {function({quarantine: boolean, locked: boolean}): void} callback - Called with
+ *     the new mode
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onSecurityModeChanged(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('security-mode-changed', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Write the settings to a JSON file for another machine; allowed folders, command groups,
+ * the temp directory and quarantine mode are left out
+ * @param {string} target - Where to write the file
+ * @returns {Promise<void|null>} Null outside Tauri
+ */
+export async function exportSettings(target) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_settings', { target });
+}
+
+/**
+ * Take the settings of a file written by exportSettings, upgraded to the current schema
+ * @param {string} source - The settings file
+ * @param {boolean} merge - Keep current settings the file leaves out (otherwise they go
+ *     back to their defaults)
+ * @returns {Promise<{settings: Object, applied: string[],
+ *     ignored: Array<{key: string, reason: string}>, migrated_from: number|null}|null>}
+ *     What was taken and which keys were ignored (unknown, protected or invalid); null
+ *     outside Tauri
+ */
+export async function importSettings(source, merge) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('import_settings', { source, merge });
+}
+
+/**
+ * Listen for the settings file having been unreadable at startup, so defaults are used
+ * @param {function({reason: string, backup: string|null}): void} callback - Called with
+ *     the reason and where the unreadable file was moved
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onSettingsReset(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('settings-reset', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Get the body of a message file as a semantic tree for screen readers: blocks of type
+ * heading, paragraph, list, table (cells with the header texts labelling them), quote
+ * (with its nesting level), preformatted and separator, holding inline text, link, image
+ * and line_break nodes. Only content the sanitizer keeps is included.
+ * @param {string} filePath - Absolute path to the message file
+ * @param {number[]} [partPath] - Attachment indices leading to an attached message
+ * @returns {Promise<Object[]|null>} Blocks of the body, null outside Tauri
+ */
+export async function getAccessibleBody(filePath, partPath = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_accessible_body', { path: filePath, partPath });
+}
+
+/**
+ * Report what in the HTML body of a message file may look different than in Outlook:
+ * conditional_blocks (MSO conditional comments with their condition, elements and text),
+ * vml_elements, web_fonts, external_stylesheets, wide_tables (fixed widths over the
+ * reading pane), and stripped_css, mso_css, stripped_elements and stripped_attributes as
+ * name/count pairs; may_differ_from_outlook sums it up
+ * @param {string} filePath - Absolute path to the message file
+ * @param {number[]} [partPath] - Attachment indices leading to an attached message
+ * @returns {Promise<Object|null>} The report, null outside Tauri
+ */
+export async function analyzeRendering(filePath, partPath = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('analyze_rendering', { path: filePath, partPath });
+}
+
+/**
+ * Get the part structure of a message for debugging: a tree of { id, content_type, params,
+ * transfer_encoding, disposition, file_name, size, content_id, role, children }, the id of
+ * the displayed part and the reasoning behind the body selection
+ * @param {string} filePath - Absolute path to the message file
+ * @returns {Promise<{root: Object, displayed: string|null, reasoning: string[]}|null>}
+ *     null outside Tauri
+ */
+export async function getMimeTree(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_mime_tree', { path: filePath });
+}
+
+/**
+ * Get where the bytes of a message file go, for a treemap: { total, headers, recipients,
+ * body_text, body_html, body_rtf, attachments: [{ index, file_name, stored, content,
+ * inflation, container_overhead }], properties, container, unaccounted, optimized_total }.
+ * Everything but total adds up to total; optimized_total is set for EML only
+ * @param {string} filePath - Absolute path to the message file
+ * @returns {Promise<Object|null>} null outside Tauri
+ */
+export async function getSizeBreakdown(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_size_breakdown', { path: filePath });
+}
+
+/**
+ * Get the spam and phishing verdicts mail gateways (SpamAssassin, Microsoft 365,
+ * Proofpoint, Mimecast, others as vendor "unknown") left in a message's headers
+ * @param {string} filePath - Absolute path to the message file
+ * @returns {Promise<Array<{vendor: string, header: string, verdict: string, score: number|null,
+ *     categories: string[], levels: Array<{name: string, value: number, meaning: Object}>,
+ *     raw: string}>|null>} null outside Tauri; verdict is clean, allowed, bulk, suspicious,
+ *     spam, phishing, malware or unknown
+ */
+export async function getGatewayVerdicts(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_gateway_verdicts', { path: filePath });
+}
+
+/**
+ * Get the avatar of a message's sender: an attached contact photo, a BIMI logo, or initials
+ * on a color that stays the same for the address
+ * @param {string} filePath - Absolute path to the message file
+ * @param {boolean} [allowRemote=false] - Whether a BIMI logo may be fetched from the web
+ * @returns {Promise<{kind: 'photo'|'bimi', data_url: string}|{kind: 'initials', initials: string, color: string}|null>}
+ *     null outside Tauri
+ */
+export async function getSenderAvatar(filePath, allowRemote = false) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_sender_avatar', { path: filePath, allowRemote });
+}
+
+/**
+ * Open the message files, pasted message source or HTML on the clipboard; the files arrive
+ * as `file-open` events like files opened through the OS
+ * @returns {Promise<string[]|null>} Opened paths, null outside Tauri
+ */
+export async function openFromClipboard() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('open_from_clipboard');
+}
+
+/**
+ * Get a window of the source of a message file; .msg files get a synthesized pseudo-source
+ * (`synthesized: true`). Base64 bodies of binary parts are elided unless `raw`.
+ * @param {string} filePath - Absolute path to the message file
+ * @param {number} offset - Byte offset of the window
+ * @param {number} length - Bytes wanted, at most 1 MiB
+ * @param {{raw?: boolean, normalizeLineEndings?: boolean}} [options]
+ * @returns {Promise<{offset: number, length: number, total: number, synthesized: boolean, text: string}|null>} Null outside Tauri
+ */
+export async function getMessageSource(filePath, offset, length, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_message_source', { path: filePath, offset, length, ...options });
+}
+
+/**
+ * Start a paged listing of the message files in a folder, for virtual scrolling
+ * @param {string} dir - Absolute path of the folder
+ * @param {number} limit - Entries of the first page, at most 1000
+ * @param {Object} [options]
+ * @param {'date_desc'|'date_asc'|'sender'|'subject'|'name'} [options.sortBy]
+ * @param {{sender?: string, delivered_to?: string, date_from?: string, date_to?: string,
+ *     has_attachments?: boolean, tag?: string, label?: string, language?: string,
+ *     errors_only?: boolean}} [options.filter] - `label` matches a Gmail label or keyword
+ *     exactly, in any case; `language` a body language code (`de`), as in `lang:de`
+ * @param {string} [options.jobId] - Job id under which indexing reports progress
+ * @returns {Promise<{total: number, offset: number, generation: number, entries: Object[]}|null>}
+ *     Null outside Tauri
+ */
+export async function listMessagesInDir(dir, limit, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('list_messages_in_dir', {
+        dir,
+        limit,
+        sortBy: options.sortBy ?? null,
+        filter: options.filter ?? null,
+        jobId: options.jobId ?? null,
+    });
+}
+
+/**
+ * Prepare a message file while the pointer rests on its row in the folder listing, so the
+ * tooltip and the click that may follow are quick. Waits while other parses run; cancel it
+ * with cancelJob once the pointer moves on (it then rejects with 'Cancelled').
+ * @param {string} filePath - Absolute path to the message file
+ * @param {'light'|'warm'} level - 'light' caches the listing metadata and snippet, 'warm'
+ *     also parses the whole message for opening it
+ * @param {string} [jobId] - Job id under which the prefetch can be cancelled
+ * @returns {Promise<void>}
+ */
+export async function prefetchMessage(filePath, level, jobId = null) {
+    const apis = await getTauriApis();
+    if (!apis) return;
+
+    await apis.invoke('prefetch_message', { path: filePath, level, jobId });
+}
+
+/**
+ * Check an archive folder against the hashes recorded by its first verification: files
+ * that changed, went missing, are new or no longer parse come back as `issues`. Runs as a
+ * job (see cancelJob and onJobProgress, phase 'verify').
+ * @param {string} dir - Absolute path of the folder
+ * @param {Object} [options]
+ * @param {boolean} [options.full] - Also hash files whose size and modification time are unchanged
+ * @param {boolean} [options.acceptChanges] - Take changed and missing files into the baseline
+ * @param {string} [options.reportPath] - Also write the report here, CSV for a .csv file, JSON otherwise
+ * @param {string} [options.jobId] - Id to cancel the run with
+ * @returns {Promise<{dir: string, verified_at: string, full: boolean, first_run: boolean,
+ *     counts: Object<string, number>, issues: Array<{kind: 'changed'|'missing'|'new'|'newly_unparseable',
+ *     file: string, expected_sha256: ?string, actual_sha256: ?string, error: ?string}>}|null>}
+ *     Null outside Tauri
+ */
+export async function verifyArchive(dir, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('verify_archive', {
+        dir,
+        options: {
+            full: options.full ?? false,
+            accept_changes: options.acceptChanges ?? false,
+        },
+        reportPath: options.reportPath ?? null,
+        jobId: options.jobId ?? null,
+    });
+}
+
+/**
+ * Rename a message file downloaded with a mangled name (`RE%3A%20Invoice.msg`,
+ * `message.msg.msg`) to its readable name; registered documents (whose `display_name`
+ * shows that name meanwhile) follow it, see onDocumentRenamed
+ * @param {string} path - Absolute path of the file
+ * @returns {Promise<{path: string, renamed: boolean, documents: string[]}|null>} The new
+ *     path and the ids of its open documents; null outside Tauri
+ */
+export async function normalizeFilename(path) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('normalize_filename', { path });
+}
+
+/**
+ * Listen for open documents whose file was renamed by normalizeFilename, to update tabs
+ * and recent files with the new path
+ * @param {function({doc_id: string, old_path: string, path: string}): void} callback
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onDocumentRenamed(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('document-renamed', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Export a folder listing as CSV (file, date, sender, subject, attachments, labels,
+ * flags, language, error), every entry matching the filter
+ * @param {string} dir - Absolute path of the folder
+ * @param {string} target - Where to write the .csv file
+ * @param {Object} [options] - `sortBy` and `filter` as for listMessagesInDir
+ * @returns {Promise<void|null>} Null outside Tauri
+ */
+export async function exportIndexCsv(dir, target, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_index_csv', {
+        dir,
+        target,
+        sortBy: options.sortBy ?? null,
+        filter: options.filter ?? null,
+    });
+}
+
+/**
+ * Get another page of a folder listing; a changed `generation` means the folder was
+ * indexed anew (after `folder-changed`) and pages fetched before are stale
+ * @param {string} dir - Absolute path of the folder
+ * @param {number} offset - Position of the first entry among the filtered ones
+ * @param {number} limit - Entries wanted, at most 1000
+ * @param {Object} [options] - `sortBy` and `filter` as for listMessagesInDir
+ * @returns {Promise<{total: number, offset: number, generation: number, entries: Object[]}|null>}
+ *     Null outside Tauri
+ */
+export async function getIndexPage(dir, offset, limit, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_index_page', {
+        dir,
+        offset,
+        limit,
+        sortBy: options.sortBy ?? null,
+        filter: options.filter ?? null,
+    });
+}
+
+/**
+ * Render the From/Sent/To/Cc/Subject/Attachments block of a message file, as exports write
+ * it, e.g. for the print view
+ * @param {string} filePath - Absolute path to the message file
+ * @param {'html'|'text'} format - `html` gives an escaped `table.header-block`
+ * @returns {Promise<string|null>} Null outside Tauri
+ */
+export async function renderHeaderBlock(filePath, format) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('render_header_block', { path: filePath, format });
+}
+
+/**
+ * Add a sender pattern to the allow or block list behind a message's `sender_trust`
+ * @param {'trusted'|'blocked'} list - A pattern in the other list moves over
+ * @param {string} pattern - An address (`anna@example.com`) or a domain (`*@example.com`)
+ * @returns {Promise<{trusted: string[], blocked: string[]}|null>} The lists, null outside Tauri
+ */
+export async function addSenderRule(list, pattern) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    const command = list === 'blocked' ? 'add_blocked_sender' : 'add_trusted_sender';
+    return await apis.invoke(command, { pattern });
+}
+
+/**
+ * Get the sender allow and block lists
+ * @returns {Promise<{trusted: string[], blocked: string[]}|null>} Null outside Tauri
+ */
+export async function listSenderRules() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('list_sender_rules');
+}
+
+/**
+ * Add the sender rules of a JSON file picked by the user, e.g. one shared by a team
+ * @returns {Promise<{trusted: string[], blocked: string[]}|null>} The lists, null if cancelled
+ */
+export async function importSenderRules() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('import_sender_rules');
+}
+
+/**
+ * Save the sender rules as a JSON file chosen by the user
+ * @returns {Promise<boolean>} False if cancelled or outside Tauri
+ */
+export async function exportSenderRules() {
+    const apis = await getTauriApis();
+    if (!apis) return false;
+
+    return await apis.invoke('export_sender_rules');
+}
+
+/**
+ * Set how messages from a sender are shown; fields left out fall back to the domain's
+ * preference, then to the global settings
+ * @param {string} sender - Address (anna@example.com) or domain (example.com, *@example.com)
+ * @param {{load_remote_images?: boolean, default_collapse_quotes?: boolean,
+ *     preferred_encoding_override?: string}} preference - Preference for the sender
+ * @returns {Promise<Array<{sender: string, preference: Object}>|null>} All stored
+ *     preferences, null outside Tauri
+ */
+export async function setSenderPreference(sender, preference) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('set_sender_preference', { sender, preference });
+}
+
+/**
+ * Get the stored sender preferences
+ * @returns {Promise<Array<{sender: string, preference: Object}>|null>} Null outside Tauri
+ */
+export async function listSenderPreferences() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('list_sender_preferences');
+}
+
+/**
+ * Remove the preference for a sender
+ * @param {string} sender - Address or domain, as passed to setSenderPreference
+ * @returns {Promise<Array<{sender: string, preference: Object}>|null>} The remaining
+ *     preferences, null outside Tauri
+ */
+export async function removeSenderPreference(sender) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('remove_sender_preference', { sender });
+}
+
+/**
+ * Save a workspace, replacing one of the same name
+ * @param {string} name - Workspace name
+ * @param {Object} definition - { folders, sort_by, filter, tags }, where tags maps tag
+ *     names to colors (or null)
+ * @returns {Promise<Object|null>} The saved { name, definition }, null outside Tauri
+ */
+export async function saveWorkspace(name, definition) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('save_workspace', { name, definition });
+}
+
+/**
+ * List the saved workspaces
+ * @returns {Promise<Object[]>} { name, definition } by name, empty outside Tauri
+ */
+export async function listWorkspaces() {
+    const apis = await getTauriApis();
+    if (!apis) return [];
+
+    return await apis.invoke('list_workspaces');
+}
+
+/**
+ * Delete a saved workspace
+ * @param {string} name - Workspace name
+ * @returns {Promise<boolean>} False if there was none or outside Tauri
+ */
+export async function deleteWorkspace(name) {
+    const apis = await getTauriApis();
+    if (!apis) return false;
+
+    return await apis.invoke('delete_workspace', { name });
+}
+
+/**
+ * Open a workspace: its folders are watched and indexed, its tag colors applied
+ * @param {string} name - Workspace name
+ * @returns {Promise<Object|null>} { name, definition, missing_folders }, null outside Tauri
+ */
+export async function openWorkspace(name) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('open_workspace', { name });
+}
+
+/**
+ * Start forwarding a message: the draft has a `Fwd:` subject, no recipients and every
+ * attachment included
+ * @param {string} filePath - Absolute path of the message file
+ * @returns {Promise<{id: string, source: string, to: string[], cc: string[], subject: string, note: string, attachments: Array<{index: number, file_name: string, size: number, inline: boolean, included: boolean}>, created_at: number, updated_at: number}|null>}
+ *     The draft, null outside Tauri
+ */
+export async function createDraftFrom(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('create_draft_from', { path: filePath });
+}
+
+/**
+ * Change a draft; fields left out stay as they are
+ * @param {string} id - Draft id
+ * @param {{to?: string[], cc?: string[], subject?: string, note?: string, attachments?: Object<number, boolean>}} patch
+ *     Recipients as `Name <address>` or the address; `attachments` includes or leaves
+ *     out attachments by index
+ * @returns {Promise<Object|null>} The updated draft, null outside Tauri
+ */
+export async function updateDraft(id, patch) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('update_draft', { id, patch });
+}
+
+/**
+ * List the drafts not written yet, including those left by an earlier run
+ * @returns {Promise<Object[]>} Drafts as from createDraftFrom, empty outside Tauri
+ */
+export async function listDrafts() {
+    const apis = await getTauriApis();
+    if (!apis) return [];
+
+    return await apis.invoke('list_drafts');
+}
+
+/**
+ * Throw a draft away
+ * @param {string} id - Draft id
+ * @returns {Promise<boolean>} False if there was none or outside Tauri
+ */
+export async function discardDraft(id) {
+    const apis = await getTauriApis();
+    if (!apis) return false;
+
+    return await apis.invoke('discard_draft', { id });
+}
+
+/**
+ * Write a draft as an .eml marked unsent; the draft is gone afterwards
+ * @param {string} id - Draft id
+ * @param {string|null} [target] - Where to save it; without one it is written to the temp
+ *     directory and opened in the mail client
+ * @returns {Promise<string|null>} Path of the .eml, null outside Tauri
+ */
+export async function finalizeDraft(id, target = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('finalize_draft', { id, target });
+}
+
+/**
+ * Answer the meeting request in a message as the calendar_address of the settings
+ * @param {string} filePath - Absolute path to the message file
+ * @param {'accept'|'decline'|'tentative'} response
+ * @param {string|null} [comment] - Sent to the organizer with the answer
+ * @param {string|null} [target] - Where to save the .ics; without one it is attached to an
+ *     unsent message to the organizer, opened in the mail client
+ * @param {string|null} [instance] - RECURRENCE-ID of the one occurrence answered; the
+ *     whole series when left out
+ * @returns {Promise<{ics: string, draft: string|null}|null>} Where the reply was written,
+ *     null outside Tauri
+ */
+export async function generateCalendarResponse(
+    filePath,
+    response,
+    comment = null,
+    target = null,
+    instance = null
+) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('generate_calendar_response', {
+        path: filePath,
+        response,
+        comment,
+        target,
+        instance,
+    });
+}
+
+/**
+ * Save a workspace as a JSON file chosen by the user, to share it
+ * @param {string} name - Workspace name
+ * @returns {Promise<boolean>} False if cancelled or outside Tauri
+ */
+export async function exportWorkspace(name) {
+    const apis = await getTauriApis();
+    if (!apis) return false;
+
+    return await apis.invoke('export_workspace', { name });
+}
+
+/**
+ * Add a workspace from a JSON file chosen by the user
+ * @returns {Promise<Object|null>} The imported { name, definition }, null if cancelled or
+ *     outside Tauri
+ */
+export async function importWorkspace() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('import_workspace');
+}
+
+/**
+ * Save the attachments matching a filter from message files and folders into one folder,
+ * with a manifest.csv mapping the written files back to their messages
+ * @param {string[]} paths - Message files, or folders whose message files are searched
+ * @param {Object} filter - Optional { name (glob), mime_type, min_size, max_size, sender,
+ *     date_from, date_to, tag, include_inline }
+ * @param {string} targetDir - Folder to write into
+ * @param {Object} [options] - { perMessageFolders, jobId } (progress as job-progress events)
+ * @returns {Promise<Object|null>} { matched, written, skipped, failed, manifest }, null
+ *     outside Tauri
+ */
+export async function exportAttachmentsMatching(paths, filter, targetDir, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_attachments_matching', {
+        paths,
+        filter,
+        target: targetDir,
+        perMessageFolders: options.perMessageFolders ?? false,
+        jobId: options.jobId ?? null,
+    });
+}
+
+/**
+ * Read the audit log of attachments saved, exported or opened externally (recorded while
+ * the audit_log setting is on)
+ * @param {Object} [filter] - Optional { action, message_hash, attachment_hash,
+ *     attachment_name, since, until, limit }
+ * @returns {Promise<Array<Object>|null>} Matching entries, oldest first; null outside Tauri
+ */
+export async function getAuditLog(filter = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_audit_log', { filter });
+}
+
+/**
+ * Write the whole audit log, rotated segments included, into one JSONL file
+ * @param {string} target - Path of the file to write
+ * @returns {Promise<number|null>} Number of entries written, null outside Tauri
+ */
+export async function exportAuditLog(target) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_audit_log', { target });
+}
+
+/**
+ * Delete the temp files, cached view states and log lines that the retention policy says
+ * are due now
+ * @returns {Promise<{deleted: Array<{path: string, bytes: number, trimmed: boolean}>, view_states: number, drafts: number, bytes_reclaimed: number}|null>}
+ *   What was deleted, null outside Tauri
+ */
+export async function runCleanupNow() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('run_cleanup_now');
+}
+
+/**
+ * List where the app keeps its files and which are written to disk at the moment, so
+ * paranoid mode can be verified. The `temp` entry has a `usage` saying which location is
+ * in use ('system', 'app_cache' or 'settings') and why the ones before it were skipped.
+ * @returns {Promise<Array<{name: string, path: string|null, on_disk: boolean, usage: ?{source: string, skipped: Array<{source: string, path: string, reason: string}>}}>>} Empty outside Tauri
+ */
+export async function getStoragePaths() {
+    const apis = await getTauriApis();
+    if (!apis) return [];
+
+    return await apis.invoke('get_storage_paths');
+}
+
+/**
+ * Write the sample message into the app data folder (recreated if it was deleted) and
+ * open it; it arrives like a file from the OS. For first runs without a file at hand.
+ * @returns {Promise<string|null>} Path of the sample; null outside Tauri
+ */
+export async function createSampleMessage() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('create_sample_message');
+}
+
+/**
+ * What this machine offers, so the onboarding only suggests what applies: file_associations
+ * has a flag for 'msg' and 'eml', xdg_open is null off Linux
+ * @returns {Promise<{default_mail_client: string|null, file_associations: Object<string, boolean>,
+ *     xdg_open: boolean|null, notifications_available: boolean,
+ *     notifications_enabled: boolean}|null>} null outside Tauri
+ */
+export async function getPlatformCapabilities() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_platform_capabilities');
+}
+
+/**
+ * Pick the folder for temporary files in a native dialog, for machines where neither the
+ * system temp folder nor the app's cache folder can be written
+ * @returns {Promise<boolean>} False if cancelled or outside Tauri
+ */
+export async function chooseTempDir() {
+    const apis = await getTauriApis();
+    if (!apis) return false;
+
+    return await apis.invoke('choose_temp_dir');
+}
+
+/**
+ * Find text in the source of a message file, as `getMessageSource` presents it
+ * @param {string} filePath - Absolute path to the message file
+ * @param {string} query - Text to find
+ * @param {{raw?: boolean, normalizeLineEndings?: boolean, matchCase?: boolean}} [options]
+ * @returns {Promise<{offsets: number[], truncated: boolean}|null>} Null outside Tauri
+ */
+export async function searchInSource(filePath, query, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('search_in_source', { path: filePath, query, ...options });
+}
+
+/**
+ * Find text in an open message: header fields, both bodies (collapsed quotes included),
+ * attachment names and the subjects of attached messages
+ * @param {string} docId - Id from register_document
+ * @param {string} query - Text, or a regular expression with `regex: true`
+ * @param {{case_sensitive?: boolean, whole_word?: boolean, regex?: boolean}} [options]
+ * @returns {Promise<{matches: {field: string, path: number[], attachment?: number,
+ *     start: number, end: number, snippet: string, snippet_start: number}[],
+ *     truncated: boolean}|null>} Matches in a stable order, ranges in string indices of
+ *     the field's text; null outside Tauri
+ */
+export async function findInMessage(docId, query, options = {}) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('find_in_message', { docId, query, options });
+}
+
+/**
+ * Get files that were passed to app on startup
+ * @returns {Promise<string[]>} Array of file paths
+ */
+export async function getPendingFiles() {
+    const apis = await getTauriApis();
+    if (!apis) return [];
+
+    return await apis.invoke('get_pending_files');
+}
+
+/**
+ * Listen for file open events from Tauri
+ * @param {function(string): void} callback - Called with file path
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onFileOpen(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('file-open', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Listen for encrypted zip archives that need a password before their message can be shown
+ * @param {function(Object): void} callback - Called with `{path, docId, entry, encryption, attemptsLeft}`
+ * @returns {Promise<function(): void>} Unlisten function
  */
-export async function getPendingFiles() {
+export async function onArchivePasswordNeeded(callback) {
     const apis = await getTauriApis();
-    if (!apis) return [];
+    if (!apis) return () => {};
 
-    return await apis.invoke('get_pending_files');
+    return await apis.listen('needs-archive-password', (event) => {
+        const request = event.payload;
+        if (request) {
+            callback({
+                path: request.path,
+                docId: request.doc_id,
+                entry: request.entry,
+                encryption: request.encryption,
+                attemptsLeft: request.attempts_left,
+            });
+        }
+    });
 }
 
 /**
- * Listen for file open events from Tauri
- * @param {function(string): void} callback - Called with file path
+ * Listen for files not opened because their content is open already under another path;
+ * the read of the new path rejects with code `error.already_open`
+ * @param {function({existingPath: string, newPath: string}): void} callback
  * @returns {Promise<function(): void>} Unlisten function
  */
-export async function onFileOpen(callback) {
+export async function onDuplicateOpen(callback) {
     const apis = await getTauriApis();
     if (!apis) return () => {};
 
-    return await apis.listen('file-open', (event) => {
+    return await apis.listen('duplicate-open', (event) => {
+        const payload = event.payload;
+        if (payload) {
+            callback({ existingPath: payload.existing_path, newPath: payload.new_path });
+        }
+    });
+}
+
+/**
+ * Check whether a file is unchanged since it was parsed, before trusting results cached
+ * for it; a stale file's cache entries are dropped and `cache-invalidated` is emitted
+ * @param {string} filePath - Path to the message file
+ * @returns {Promise<'fresh'|'stale'|'unknown'|null>} null outside Tauri
+ */
+export async function verifyFreshness(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('verify_freshness', { path: filePath });
+}
+
+/**
+ * Parse an open document again after its file was edited elsewhere; `document-reloaded`
+ * is emitted with the same result
+ * @param {string} docId - Id from register_document
+ * @returns {Promise<{doc_id: string, path: string, message: Object|null,
+ *     diff: {changed_fields: string[], added_attachments: Object[],
+ *     removed_attachments: Object[]}|null, error: Object|null}|null>} The new parse result
+ *     and what changed since the document was last shown (no diff if it wasn't), or the
+ *     error that keeps the old view; null outside Tauri
+ */
+export async function reloadDocument(docId) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('reload_document', { docId });
+}
+
+/**
+ * Check whether a document whose volume went away (onDocumentUnavailable) can be read
+ * again; fails with error code `error.volume_unavailable` while it can't
+ * @param {string} docId - Id from register_document
+ * @returns {Promise<'fresh'|'stale'|'unknown'|null>} Whether the file is unchanged (stale:
+ *     reload it with reloadDocument); null outside Tauri
+ */
+export async function retryDocument(docId) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('retry_document', { docId });
+}
+
+/**
+ * Listen for open documents whose USB stick or network share went away, once per
+ * document until it's back
+ * @param {function({doc_id: string, path: string, volume: string}): void} callback
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onDocumentUnavailable(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('document-unavailable', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Listen for unavailable documents whose volume is back
+ * @param {function({doc_id: string, path: string, freshness: string}): void} callback -
+ *     freshness is `stale` when the file changed meanwhile and should be reloaded
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onDocumentAvailable(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('document-available', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * What each open document keeps in memory: `parsed` is kept while it's open, the
+ * attachments, source and archive contents are released by unloadDocumentCaches or when
+ * `regenerable` goes over `threshold` (the memory_threshold setting)
+ * @returns {Promise<{documents: Array<{doc_id: string, path: string, parsed: number,
+ *     attachments: number, source: number, archive: number, total: number}>,
+ *     other: number, regenerable: number, total: number, threshold: number}|null>} Bytes;
+ *     null outside Tauri
+ */
+export async function getMemoryReport() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_memory_report');
+}
+
+/**
+ * Release what the caches hold for a document; it stays open, and attachments are
+ * extracted again when next requested
+ * @param {string} docId - Id from register_document
+ * @returns {Promise<number|null>} Bytes released; null outside Tauri
+ */
+export async function unloadDocumentCaches(docId) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('unload_document_caches', { docId });
+}
+
+/**
+ * Listen for the caches going over the memory threshold, after the least recently used
+ * documents were unloaded
+ * @param {function({before: number, after: number, threshold: number,
+ *     unloaded: string[]}): void} callback - Receives the bytes before and after, and the
+ *     ids of the unloaded documents
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onMemoryPressure(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('memory-pressure', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Show a document's body in a sandboxed child webview, positioned like an iframe over an
+ * element of the page; it replaces an earlier preview of the document
+ * @param {string} docId - Id from register_document
+ * @param {string} parentLabel - Label of the window to show it in
+ * @param {{x: number, y: number, width: number, height: number}} [bounds] - Where, in
+ *     logical pixels within the window; hidden until setPreviewBounds when left out
+ * @returns {Promise<string|null>} Label of the preview webview, null outside Tauri
+ */
+export async function createPreviewView(docId, parentLabel, bounds = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('create_preview_view', { docId, parentLabel, bounds });
+}
+
+/**
+ * Move and resize a document's preview webview and show it; call whenever the element
+ * standing in for it moves or resizes
+ * @param {string} docId - Id from register_document
+ * @param {{x: number, y: number, width: number, height: number}} bounds - Logical pixels
+ *     within the window
+ * @returns {Promise<void>}
+ */
+export async function setPreviewBounds(docId, bounds) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('set_preview_bounds', { docId, bounds });
+}
+
+/**
+ * Close a document's preview webview, if it has one
+ * @param {string} docId - Id from register_document
+ * @returns {Promise<void>}
+ */
+export async function destroyPreviewView(docId) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('destroy_preview_view', { docId });
+}
+
+/**
+ * Listen for what preview webviews of this window report: `content-size` when the laid
+ * out body changes size, `link` when a link in it is clicked (the preview never follows
+ * links itself)
+ * @param {function({doc_id: string, kind: 'content-size', width: number, height: number}
+ *     |{doc_id: string, kind: 'link', url: string}): void} callback - Called per report
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onPreviewMessage(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('preview-message', (event) => {
+        if (event.payload) {
+            callback(event.payload);
+        }
+    });
+}
+
+/**
+ * Decode a message's bodies again with a charset chosen by hand (View → Encoding)
+ * @param {string} filePath - Path to the .msg or .eml file
+ * @param {string|null} encoding - One of listSupportedEncodings, or null for the declared
+ *     or detected charset
+ * @param {boolean} [remember=false] - Keep the choice for this file (null forgets it), so
+ *     opening it again decodes it the same way
+ * @returns {Promise<{body_text: string|null, body_html: string|null,
+ *     body_encodings: Object[], body_repaired: boolean,
+ *     charset_override: string|null}|null>} The bodies; null outside Tauri
+ */
+export async function reparseWithEncoding(filePath, encoding, remember = false) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('reparse_with_encoding', { path: filePath, encoding, remember });
+}
+
+/**
+ * Get the charsets reparseWithEncoding accepts
+ * @returns {Promise<string[]|null>} Charset names, null outside Tauri
+ */
+export async function listSupportedEncodings() {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('list_supported_encodings');
+}
+
+/**
+ * Page through a recipient list parseMessage cut short (`recipients_truncated`; it
+ * returns the first 100 of each type, `recipient_counts` has the full lengths)
+ * @param {string} filePath - Path to the .msg or .eml file
+ * @param {'to'|'cc'|'bcc'} type - Recipient type
+ * @param {number} offset - Position of the first recipient to return
+ * @param {number} limit - Recipients to return, at most 1000
+ * @returns {Promise<{kind: string, total: number, offset: number,
+ *     recipients: Object[]}|null>} The page; null outside Tauri
+ */
+export async function getAllRecipients(filePath, type, offset, limit) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('get_all_recipients', { path: filePath, kind: type, offset, limit });
+}
+
+/**
+ * Listen for documents parsed again with reloadDocument, from any window
+ * @param {function(Object): void} callback - Called with the reload result
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onDocumentReloaded(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('document-reloaded', (event) => {
         if (event.payload) {
             callback(event.payload);
         }
     });
 }
 
+/**
+ * Run the backend health check for support requests
+ * @param {boolean} [offline=false] - Skip contacting the updater endpoint
+ * @returns {Promise<{checks: Array<{name: string, status: 'passed'|'failed'|'skipped',
+ *     details: string, duration_ms: number}>, passed: boolean, text: string}|null>}
+ *     The report, with `text` ready to copy; null outside Tauri
+ */
+export async function runSelfTest(offline = false) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('run_self_test', { offline });
+}
+
+/**
+ * Write a redacted copy of a message file for a bug report: text, addresses and
+ * attachment content replaced, structure kept
+ * @param {string} filePath - Path to the .msg or .eml file
+ * @param {string} target - Where to write the copy
+ * @returns {Promise<{reproduces: boolean, original: string[], redacted: string[]}|null>}
+ *     The warning (or `error:`) codes of both files; null outside Tauri
+ */
+export async function exportRedacted(filePath, target) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_redacted', { path: filePath, target });
+}
+
+/**
+ * Export a message file as one self-contained .html file that works offline: header
+ * block, sanitized body with inline images, plain-text alternative and the attachments
+ * up to the `html_archive_embed_limit` setting
+ * @param {string} filePath - Path to the .msg or .eml file
+ * @param {string} target - Where to write the .html file
+ * @returns {Promise<void|null>} Null outside Tauri
+ */
+export async function exportHtmlArchive(filePath, target) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_html_archive', { path: filePath, target });
+}
+
+/**
+ * Export every To, Cc and Bcc recipient of a message file as CSV (type,name,email),
+ * including the ones parseMessage leaves out of long lists
+ * @param {string} filePath - Path to the .msg or .eml file
+ * @param {string} target - Where to write the .csv file
+ * @returns {Promise<void|null>} Null outside Tauri
+ */
+export async function exportRecipientsCsv(filePath, target) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('export_recipients_csv', { path: filePath, target });
+}
+
+/**
+ * Listen for files whose cached results were dropped because they changed
+ * @param {function(string): void} callback - Called with the file path
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onCacheInvalidated(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('cache-invalidated', (event) => {
+        if (event.payload?.path) {
+            callback(event.payload.path);
+        }
+    });
+}
+
+/**
+ * Preview what auto-extract rules would save from a message, without writing anything
+ * @param {string} filePath - Path to the message file
+ * @param {Object} [rule] - A rule to try ({ name, sender, attachment_name, mime_type,
+ *     include_inline, target }); all enabled rules from settings when left out
+ * @returns {Promise<{extracted: Array<{rule: string, file_name: string, path: string,
+ *     renamed: boolean}>, failed: Array<{rule: string, error: string}>,
+ *     present: string[]}|null>} Planned files, null outside Tauri
+ */
+export async function previewAutoExtract(filePath, rule = null) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('preview_auto_extract', { path: filePath, rule });
+}
+
+/**
+ * Listen for attachments saved by auto-extract rules when a message was opened
+ * @param {function(Object): void} callback - Called with { message_path, extracted, failed }
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onAutoExtracted(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('auto-extracted', (event) => callback(event.payload));
+}
+
+/**
+ * Unlock an encrypted zip archive; rejects with code `error.wrong_archive_password` (retry)
+ * or `error.archive_attempts_exhausted`
+ * @param {string} docId - Document id of the archive
+ * @param {string} password - Password entered by the user
+ * @returns {Promise<Object|null>} The message in the archive, null outside Tauri
+ */
+export async function provideArchivePassword(docId, password) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('provide_archive_password', { docId, password });
+}
+
+/**
+ * Listen for bulk open requests: more files opened at once than the configured threshold
+ * @param {function(string[]): void} callback - Called with all file paths, in order
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onBulkOpenRequest(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('bulk-open-request', (event) => {
+        if (event.payload && event.payload.paths) {
+            callback(event.payload.paths);
+        }
+    });
+}
+
+/**
+ * Ask whether a bulk of files should really be opened
+ * @param {number} count - Number of files
+ * @returns {Promise<boolean>} True if the user wants to open them all
+ */
+export async function confirmBulkOpen(count) {
+    const { ask } = await import('@tauri-apps/plugin-dialog');
+    return await ask(`Open all ${count} files?`, {
+        title: 'Open files',
+        kind: 'info',
+        okLabel: 'Open all',
+        cancelLabel: 'Cancel',
+    });
+}
+
+/**
+ * Listen for files forwarded by another launch of the app, which wait for confirmation
+ * @param {function(string[]): void} callback - Called with the forwarded file paths
+ * @returns {Promise<function(): void>} Unlisten function
+ */
+export async function onForwardedOpenRequest(callback) {
+    const apis = await getTauriApis();
+    if (!apis) return () => {};
+
+    return await apis.listen('forwarded-open-request', (event) => {
+        if (event.payload && event.payload.paths) {
+            callback(event.payload.paths);
+        }
+    });
+}
+
+/**
+ * Ask whether files forwarded by another launch of the app should be opened, and pass the
+ * answer to the backend, which only then allows them
+ * @param {string[]} paths - Paths from the forwarded-open-request
+ * @returns {Promise<string[]|null>} Paths to open; empty when declined
+ */
+export async function confirmForwardedFiles(paths) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    const { ask } = await import('@tauri-apps/plugin-dialog');
+    const names = paths.map(getFileName).join('\n');
+    const accept = await ask(`Another launch of the app asks to open:\n${names}`, {
+        title: 'Open files',
+        kind: 'warning',
+        okLabel: 'Open',
+        cancelLabel: 'Cancel',
+    });
+    return await apis.invoke('confirm_forwarded_files', { paths, accept });
+}
+
+// Threshold above which the backend turns drops into bulk open requests
+let bulkOpenThreshold = null;
+
+/**
+ * Get the bulk open threshold from the backend settings (cached)
+ * @returns {Promise<number>} Maximum number of files opened directly
+ */
+async function getBulkOpenThreshold() {
+    if (bulkOpenThreshold === null) {
+        const apis = await getTauriApis();
+        const settings = apis ? await apis.invoke('get_settings') : {};
+        bulkOpenThreshold = settings.bulk_open_threshold ?? Infinity;
+    }
+    return bulkOpenThreshold;
+}
+
 /**
  * Extract filename from a file path
  * @param {string} filePath - Full file path
@@ -89,6 +1462,50 @@ export function getFileName(filePath) {
     return parts[parts.length - 1];
 }
 
+/**
+ * Build the URL of an attachment for <audio> and <video> elements, which stream it with
+ * range requests instead of loading it as one base64 blob (Tauri only)
+ * @param {string} docId - Id from register_document
+ * @param {number[]} indices - Indices of attached messages leading to it, then the attachment index
+ * @returns {Promise<string|null>} URL, or null outside of Tauri
+ */
+export async function attachmentStreamUrl(docId, indices) {
+    if (!isTauri()) return null;
+    const { convertFileSrc } = await import('@tauri-apps/api/core');
+    return convertFileSrc([docId, ...indices].join('/'), 'attach');
+}
+
+/**
+ * Open a message file in a new window, cascaded from the current one (Tauri only)
+ * @param {string} filePath - Absolute path to the message file
+ * @returns {Promise<string|null>} Label of the new window, null outside of Tauri
+ */
+export async function openInNewWindow(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    return await apis.invoke('open_in_new_window', { path: filePath });
+}
+
+/**
+ * Register an opened file and get a builder for attach:// URLs of its inline images, so
+ * large ones are loaded on demand instead of embedded as data URIs (Tauri only)
+ * @param {string} filePath - Path of the opened message file
+ * @returns {Promise<function(string): string|null>} Builds the URL for a content ID, or null
+ *     when disabled in settings or outside of Tauri
+ */
+export async function inlineImageUrlBuilder(filePath) {
+    const apis = await getTauriApis();
+    if (!apis) return null;
+
+    const settings = await apis.invoke('get_settings');
+    if (!settings.inline_images_via_protocol) return null;
+
+    const { convertFileSrc } = await import('@tauri-apps/api/core');
+    const document = await apis.invoke('register_document', { path: filePath });
+    return (contentId) => convertFileSrc(`${document.id}/cid/${contentId}`, 'attach');
+}
+
 /**
  * Open a file with the system's default application (Tauri only)
  * Saves the file to a temp location and opens it
@@ -236,6 +1653,7 @@ export async function onFileDrop(callbacks) {
 
     const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
     const webview = getCurrentWebviewWindow();
+    const threshold = await getBulkOpenThreshold();
 
     return await webview.onDragDropEvent((event) => {
         switch (event.payload.type) {
@@ -255,7 +1673,8 @@ export async function onFileDrop(callbacks) {
                     callbacks.onLeave(); // Hide overlay on drop
                 }
                 const paths = event.payload.paths;
-                if (paths && paths.length > 0 && callbacks.onDrop) {
+                // Bulk drops arrive as a bulk-open-request from the backend instead
+                if (paths && paths.length > 0 && paths.length <= threshold && callbacks.onDrop) {
                     callbacks.onDrop(paths);
                 }
                 break;
@@ -352,9 +352,10 @@ export class MessageContentRenderer {
     /**
      * Processes email content: converts plain text to HTML if needed and scopes styles
      * @param {Object} msgInfo - Message object
+     * @param {Object} [options] - Options for sanitizeHTML, such as `blockRemoteImages`
      * @returns {string} Processed and sanitized email content
      */
-    processEmailContent(msgInfo) {
+    processEmailContent(msgInfo, options = {}) {
         let emailContent = msgInfo.bodyContentHTML || msgInfo.bodyContent;
 
         // If no HTML, convert plain text to HTML with paragraphs and line breaks
@@ -371,7 +372,7 @@ export class MessageContentRenderer {
 
         // Scope styles and sanitize
         emailContent = this.scopeEmailStyles(emailContent);
-        return sanitizeHTML(emailContent);
+        return sanitizeHTML(emailContent, options);
     }
 
     /**
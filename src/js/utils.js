@@ -665,6 +665,7 @@ function buildExportMeta(rawHeaders, fallbackHeaders = {}) {
  * @param {ArrayBuffer} fileBuffer - The raw MSG file content
  * @param {Object} [options] - Options object
  * @param {boolean} [options.collectDebugData=false] - Whether to collect debug data
+ * @param {function(string): string} [options.imageUrl] - Builds URLs for large inline images
  * @returns {Object|null} Parsed email object with subject, sender, recipients, body, attachments
  */
 export function extractMsg(fileBuffer, options = {}) {
@@ -715,7 +716,9 @@ export function extractMsg(fileBuffer, options = {}) {
             debugData.htmlBeforeCid = emailBodyContentHTML;
         }
 
-        emailBodyContentHTML = replaceCidReferences(emailBodyContentHTML, msgInfo.attachments);
+        emailBodyContentHTML = replaceCidReferences(emailBodyContentHTML, msgInfo.attachments, {
+            imageUrl: options.imageUrl
+        });
 
         if (debugData) {
             debugData.htmlAfterCid = emailBodyContentHTML;
@@ -828,6 +831,7 @@ function handleSinglePartContent(
  * @param {ArrayBuffer} fileBuffer - The raw EML file content
  * @param {Object} [options] - Options object
  * @param {boolean} [options.collectDebugData=false] - Whether to collect debug data
+ * @param {function(string): string} [options.imageUrl] - Builds URLs for large inline images
  * @returns {Object} Parsed email object with subject, sender, recipients, body, attachments
  * @throws {Error} If the EML file cannot be parsed
  */
@@ -905,7 +909,9 @@ export function extractEml(fileBuffer, options = {}) {
                 if (debugData) {
                     debugData.htmlBeforeCid = results.bodyHTML;
                 }
-                results.bodyHTML = replaceCidReferences(results.bodyHTML, results.attachments);
+                results.bodyHTML = replaceCidReferences(results.bodyHTML, results.attachments, {
+                    imageUrl: options.imageUrl
+                });
                 if (debugData) {
                     debugData.htmlAfterCid = results.bodyHTML;
                 }
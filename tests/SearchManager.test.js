@@ -70,6 +70,16 @@ describe('SearchManager', () => {
             expect(results[0].subject).toBe('Meeting Tomorrow');
         });
 
+        test('filters messages by body language with lang:', () => {
+            mockMessages[0].language = { code: 'de', confidence: 0.9 };
+            mockMessages[1].language = { code: 'en', confidence: 0.8 };
+
+            expect(searchManager.search('lang:de')).toEqual([mockMessages[0]]);
+            expect(searchManager.search('LANG:de-AT project')).toEqual([mockMessages[0]]);
+            expect(searchManager.search('lang:de invoice')).toHaveLength(0);
+            expect(searchManager.search('lang:')).toHaveLength(0);
+        });
+
         test('filters messages by sender name', () => {
             const results = searchManager.search('John');
             expect(results).toHaveLength(1);
@@ -17,7 +17,7 @@ describe('ThemeManager', () => {
         window.matchMedia = jest.fn(() => mockMediaQuery);
 
         // Mock document.documentElement
-        document.documentElement.classList.remove('light', 'dark');
+        document.documentElement.classList.remove('light', 'dark', 'high-contrast');
         delete document.documentElement.dataset.theme;
         delete document.documentElement.dataset.emailTheme;
 
@@ -147,6 +147,23 @@ describe('ThemeManager', () => {
         });
     });
 
+    describe('setHighContrast', () => {
+        test('adds the high-contrast class on top of the theme', () => {
+            themeManager.applyTheme(THEMES.DARK);
+            themeManager.setHighContrast(true);
+            expect(document.documentElement.classList.contains('high-contrast')).toBe(true);
+            expect(document.documentElement.classList.contains('dark')).toBe(true);
+        });
+
+        test('removes it again and survives theme changes', () => {
+            themeManager.setHighContrast(true);
+            themeManager.applyTheme(THEMES.LIGHT);
+            expect(document.documentElement.classList.contains('high-contrast')).toBe(true);
+            themeManager.setHighContrast(false);
+            expect(document.documentElement.classList.contains('high-contrast')).toBe(false);
+        });
+    });
+
     describe('applyEmailTheme', () => {
         test('applies light email theme', () => {
             themeManager.applyEmailTheme(EMAIL_THEMES.LIGHT);
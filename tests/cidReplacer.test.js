@@ -1,7 +1,11 @@
 /**
  * Tests for cidReplacer.js
  */
-import { replaceCidReferences, buildCidPatterns } from '../src/js/cidReplacer.js';
+import {
+    replaceCidReferences,
+    buildCidPatterns,
+    INLINE_DATA_URI_LIMIT
+} from '../src/js/cidReplacer.js';
 import { PLACEHOLDER_IMAGE_SVG } from '../src/js/constants.js';
 
 describe('buildCidPatterns', () => {
@@ -145,3 +149,45 @@ describe('replaceCidReferences', () => {
         expect(result).toContain(PLACEHOLDER_IMAGE_SVG);
     });
 });
+
+describe('replaceCidReferences with protocol URLs', () => {
+    const imageUrl = (contentId) => `attach://localhost/doc-1%2Fcid%2F${encodeURIComponent(contentId)}`;
+    const photo = (n, size) => ({
+        attachMimeTag: 'image/jpeg',
+        contentId: `photo${n}@example.com`,
+        contentLength: size,
+        contentBase64: `data:image/jpeg;base64,${'A'.repeat(Math.ceil(size / 3) * 4)}`
+    });
+
+    test('large images use the protocol, small ones stay data URIs', () => {
+        const html = '<img src="cid:photo1@example.com"><img src="cid:photo2@example.com">';
+        const attachments = [photo(1, INLINE_DATA_URI_LIMIT + 1), photo(2, 100)];
+
+        const result = replaceCidReferences(html, attachments, { imageUrl });
+        expect(result).toContain('src="attach://localhost/doc-1%2Fcid%2Fphoto1%40example.com"');
+        expect(result).toContain('src="data:image/jpeg;base64,');
+    });
+
+    test('falls back to data URIs without a URL builder', () => {
+        const html = '<img src="cid:photo1@example.com">';
+        const result = replaceCidReferences(html, [photo(1, INLINE_DATA_URI_LIMIT + 1)]);
+        expect(result).toContain('src="data:image/jpeg;base64,');
+    });
+
+    test('ten large inline images are linked instead of embedded', () => {
+        const size = 2 * 1024 * 1024;
+        const attachments = Array.from({ length: 10 }, (_, i) => photo(i, size));
+        const html = attachments.map((a) => `<p><img src="cid:${a.contentId}"></p>`).join('');
+
+        const embedded = replaceCidReferences(html, attachments);
+        expect(embedded.match(/src="data:image\/jpeg;base64,/g)).toHaveLength(10);
+        expect(embedded).not.toContain('cid:');
+        // Roughly 4/3 of the image bytes
+        expect(embedded.length).toBeGreaterThan(10 * size);
+
+        const linked = replaceCidReferences(html, attachments, { imageUrl });
+        expect(linked).toBe(
+            attachments.map((a) => `<p><img src="${imageUrl(a.contentId)}"></p>`).join('')
+        );
+    });
+});
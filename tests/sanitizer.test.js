@@ -44,6 +44,16 @@ describe('sanitizeHTML', () => {
         expect(result).toContain('Click');
     });
 
+    test('allows attach:// URLs only as image sources', () => {
+        const input = '<img src="attach://localhost/doc-1%2Fcid%2Flogo"><a href="attach://localhost/doc-1%2F0">x</a>';
+        const result = sanitizeHTML(input);
+        expect(result).toContain('src="attach://localhost/doc-1%2Fcid%2Flogo"');
+        expect(result).not.toContain('href=');
+
+        const windows = sanitizeHTML('<a href="http://attach.localhost/doc-1%2F0">x</a>');
+        expect(windows).not.toContain('attach.localhost');
+    });
+
     test('removes onerror handlers', () => {
         const input = '<img src="x" onerror="alert(1)">';
         const result = sanitizeHTML(input);
@@ -116,6 +126,25 @@ describe('sanitizeHTML', () => {
         expect(result).toContain('<font');
         expect(result).toContain('color="red"');
     });
+
+    test('replaces remote images with sized placeholders when blocking', () => {
+        const input =
+            '<img src="https://cdn.example.com/hero.jpg" width="600" height="400" alt="Autumn sale">' +
+            '<img src="cid:logo" alt="Logo">' +
+            '<div style="background-image: url(\'https://cdn.example.com/bg.png\'); color: red">x</div>' +
+            '<img src="https://cdn.example.com/a.png" srcset="https://cdn.example.com/a@2x.png 2x">';
+        const result = sanitizeHTML(input, { blockRemoteImages: true });
+        expect(result).not.toContain('https://cdn.example.com/hero.jpg"');
+        expect(result).toContain('class="blocked-remote-image"');
+        expect(result).toContain('data-remote-src="https://cdn.example.com/hero.jpg"');
+        expect(result).toContain('width: 600px; height: 400px;');
+        expect(result).toContain('>Autumn sale</span>');
+        expect(result).toContain('src="cid:logo"');
+        expect(result).not.toContain('bg.png');
+        expect(result).not.toContain('srcset');
+        // Without blocking, remote images stay
+        expect(sanitizeHTML(input)).toContain('<img src="https://cdn.example.com/hero.jpg"');
+    });
 });
 
 describe('escapeHTML', () => {